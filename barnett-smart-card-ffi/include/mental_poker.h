@@ -0,0 +1,224 @@
+/*
+ * C bindings for the Barnett-Smart card protocol on the starknet curve.
+ *
+ * Conventions:
+ *
+ * - Every function returns MP_OK on success, and otherwise one of the MP_ERR_* codes. The message
+ *   of the last error on the calling thread is available from mp_last_error_message, and its
+ *   stable code from mp_last_error_code.
+ * - Byte inputs are a pointer and a length, borrowed for the duration of the call. A null pointer
+ *   stands for an empty input if its length is zero.
+ * - Values are passed in their canonical encoding, and a list is the canonical encoding of a
+ *   vector: its length as a little-endian uint64_t followed by its elements.
+ * - Variable-length outputs are written to an MpBuffer the caller passes by pointer. Its contents
+ *   are allocated by the library and owned by the caller, who releases them with mp_buffer_free.
+ *   Outputs are only written on success.
+ * - MpParameters and MpKeyPair are opaque handles, owned by the caller and released with their
+ *   *_free function. Functions only read from handles, so a handle can be shared between threads
+ *   as long as it is not freed while in use.
+ */
+
+#ifndef MENTAL_POKER_H
+#define MENTAL_POKER_H
+
+#include <stddef.h>
+#include <stdint.h>
+
+#ifdef __cplusplus
+extern "C" {
+#endif
+
+#define MP_OK 0
+/* A required pointer was null. */
+#define MP_ERR_NULL_POINTER 1
+/* An input could not be decoded, or decoded to an invalid value. */
+#define MP_ERR_INVALID_INPUT 2
+/* A proof or a check on public values failed. */
+#define MP_ERR_VERIFICATION_FAILED 3
+/* An internal error of the library or of its environment. */
+#define MP_ERR_PROTOCOL 4
+/* The library panicked. This is a bug. */
+#define MP_ERR_PANIC 5
+
+/* Stable codes of the errors of the protocol, as returned by mp_last_error_code. New codes are
+ * appended, and existing codes never change or get reused. */
+#define MP_CODE_PROOF_VERIFICATION_ERROR 1
+#define MP_CODE_IO_ERROR 2
+#define MP_CODE_SERIALIZATION_ERROR 3
+#define MP_CODE_HASH_TO_CURVE_ERROR 4
+#define MP_CODE_KEY_DERIVATION_ERROR 5
+#define MP_CODE_UNEXPECTED_PHASE 6
+#define MP_CODE_UNKNOWN_PLAYER 7
+#define MP_CODE_DUPLICATE_MESSAGE 8
+#define MP_CODE_NOT_ENOUGH_PLAYERS 9
+#define MP_CODE_INVALID_THRESHOLD 10
+#define MP_CODE_NOT_ENOUGH_SHARES 11
+#define MP_CODE_CORRUPTED_SHARE 12
+#define MP_CODE_DUPLICATE_SHARE 13
+#define MP_CODE_MIXED_SHARES 14
+#define MP_CODE_VALUE_OUT_OF_RANGE 15
+#define MP_CODE_COMMITMENT_LENGTH_ERROR 16
+/* 17 was MP_CODE_INVALID_SHAPE, now reported as MP_CODE_DIMENSION_MISMATCH */
+#define MP_CODE_ZERO_MASKING_FACTOR 18
+#define MP_CODE_ENCRYPTION_ERROR 19
+#define MP_CODE_DECRYPTION_ERROR 20
+/* 21 was MP_CODE_LENGTH_MISMATCH, now reported as MP_CODE_DIMENSION_MISMATCH */
+#define MP_CODE_DIMENSION_MISMATCH 22
+#define MP_CODE_INVALID_PERMUTATION 23
+#define MP_CODE_IDENTITY_PUBLIC_KEY 24
+#define MP_CODE_INVALID_POINT 25
+#define MP_CODE_NON_CANONICAL_ENCODING 26
+#define MP_CODE_ENCODING_LENGTH_ERROR 27
+#define MP_CODE_RANDOMNESS_FAILURE 28
+#define MP_CODE_DUPLICATE_PLAYER_KEY 29
+#define MP_CODE_REUSED_MASKING_FACTOR 30
+#define MP_CODE_CALLER_SUPPLIED_RANDOMNESS 31
+#define MP_CODE_INVALID_SIGNATURE 32
+#define MP_CODE_CERTIFICATE_EXPIRED 33
+#define MP_CODE_CERTIFICATE_SESSION_MISMATCH 34
+#define MP_CODE_UNVERIFIABLE_PARAMETERS 35
+#define MP_CODE_MESSAGE_TOO_LARGE 36
+#define MP_CODE_INVALID_SECRET_KEY 37
+#define MP_CODE_NOT_YOUR_TURN 38
+#define MP_CODE_UNEXPECTED_REVEAL 39
+#define MP_CODE_INVALID_PLAYER_COUNT 40
+/* 41 was MP_CODE_DECK_SIZE_MISMATCH, now reported as MP_CODE_DIMENSION_MISMATCH */
+#define MP_CODE_VERIFICATION_FAILED 42
+#define MP_CODE_INVALID_KEY_OWNERSHIP_PROOF 43
+#define MP_CODE_INVALID_REVEAL_TOKEN 44
+#define MP_CODE_INVALID_DEAL 45
+#define MP_CODE_BURNED_CARD 46
+#define MP_CODE_DISCARDED_CARD 47
+#define MP_CODE_CARD_NOT_IN_HAND 48
+#define MP_CODE_UNFOUNDED_BLAME 49
+#define MP_CODE_MISSING_REVEALS 50
+#define MP_CODE_UNEXPECTED_POSITION 51
+#define MP_CODE_INVALID_OPENING 52
+#define MP_CODE_POT_MISMATCH 53
+#define MP_CODE_AMOUNT_OVERFLOW 54
+#define MP_CODE_DUPLICATE_LABEL 55
+#define MP_CODE_INVALID_CARD_LABEL 56
+#define MP_CODE_UNKNOWN_CARD 57
+#define MP_CODE_BROKEN_CHAIN 58
+#define MP_CODE_UNSUPPORTED_SNAPSHOT_VERSION 59
+#define MP_CODE_INVALID_SNAPSHOT 60
+#define MP_CODE_ILLEGAL_TRANSITION 61
+#define MP_CODE_TRANSPORT_ERROR 62
+#define MP_CODE_CONFORMANCE_MISMATCH 63
+#define MP_CODE_SHUFFLE_VERIFICATION_FAILED 64
+
+/* Bytes allocated by the library and owned by the caller. */
+typedef struct MpBuffer {
+    uint8_t *data;
+    size_t len;
+} MpBuffer;
+
+typedef struct MpParameters MpParameters;
+typedef struct MpKeyPair MpKeyPair;
+
+/* Owned by the library, valid until the next failing call on the same thread. Do not free. */
+const char *mp_last_error_message(void);
+
+/* MP_CODE_* value of the last error on the calling thread, or 0 if there was none or it was not an
+ * error of the protocol, such as a null pointer or a panic. */
+uint32_t mp_last_error_code(void);
+
+/* Releases the contents of a buffer written by the library and resets it. Null and reset buffers
+ * are ignored. */
+void mp_buffer_free(MpBuffer *buffer);
+
+/* *out receives a handle owned by the caller, released with mp_parameters_free. */
+int32_t mp_parameters_generate(size_t m, size_t n, MpParameters **out);
+
+/* Decodes parameters and checks that nobody knows a trapdoor to their commitment key. *out
+ * receives a handle owned by the caller, released with mp_parameters_free. */
+int32_t mp_parameters_from_bytes(const uint8_t *bytes, size_t bytes_len, MpParameters **out);
+
+/* *out receives a buffer owned by the caller. */
+int32_t mp_parameters_to_bytes(const MpParameters *pp, MpBuffer *out);
+
+/* pp must not be used afterwards. Null is ignored. */
+void mp_parameters_free(MpParameters *pp);
+
+/* *out receives a handle owned by the caller, released with mp_key_pair_free. */
+int32_t mp_key_pair_generate(const MpParameters *pp, MpKeyPair **out);
+
+/* *out receives a buffer owned by the caller. */
+int32_t mp_key_pair_public_key(const MpKeyPair *key_pair, MpBuffer *out);
+
+/* Releases the key pair and its secret key. key_pair must not be used afterwards. Null is
+ * ignored. */
+void mp_key_pair_free(MpKeyPair *key_pair);
+
+/* The list of the 52 cards of the standard deck. *out receives a buffer owned by the caller. */
+int32_t mp_standard_deck(MpBuffer *out);
+
+/* The 32-byte digest of the list of public keys, in seat order. *out receives a buffer owned by
+ * the caller. */
+int32_t mp_key_list_digest(const uint8_t *keys, size_t keys_len, MpBuffer *out);
+
+/* *proof_out receives a buffer owned by the caller. */
+int32_t mp_prove_key_ownership(const MpParameters *pp, const uint8_t *context, size_t context_len,
+                               const MpKeyPair *key_pair, const uint8_t *player_info,
+                               size_t player_info_len, size_t player_index,
+                               const uint8_t *key_list_digest, size_t key_list_digest_len,
+                               MpBuffer *proof_out);
+
+int32_t mp_verify_key_ownership(const MpParameters *pp, const uint8_t *context, size_t context_len,
+                                const uint8_t *public_key, size_t public_key_len,
+                                const uint8_t *player_info, size_t player_info_len,
+                                size_t player_index, const uint8_t *key_list_digest,
+                                size_t key_list_digest_len, const uint8_t *proof,
+                                size_t proof_len);
+
+/* keys and proofs are lists, player_info is a list of byte strings. Every proof is checked. *out
+ * receives a buffer owned by the caller. */
+int32_t mp_aggregate_key(const MpParameters *pp, const uint8_t *context, size_t context_len,
+                         const uint8_t *keys, size_t keys_len, const uint8_t *proofs,
+                         size_t proofs_len, const uint8_t *player_info, size_t player_info_len,
+                         MpBuffer *out);
+
+/* The list of cards masked with a masking factor of one. *out receives a buffer owned by the
+ * caller. */
+int32_t mp_starting_deck(const MpParameters *pp, const uint8_t *shared_key, size_t shared_key_len,
+                         const uint8_t *cards, size_t cards_len, MpBuffer *out);
+
+/* *masked_out and *proof_out receive buffers owned by the caller. */
+int32_t mp_mask(const MpParameters *pp, const uint8_t *context, size_t context_len,
+                const uint8_t *shared_key, size_t shared_key_len, const uint8_t *card,
+                size_t card_len, MpBuffer *masked_out, MpBuffer *proof_out);
+
+/* *shuffled_out, the shuffled list, and *proof_out receive buffers owned by the caller. */
+int32_t mp_shuffle(const MpParameters *pp, const uint8_t *context, size_t context_len,
+                   const uint8_t *shared_key, size_t shared_key_len, const uint8_t *deck,
+                   size_t deck_len, MpBuffer *shuffled_out, MpBuffer *proof_out);
+
+int32_t mp_verify_shuffle(const MpParameters *pp, const uint8_t *context, size_t context_len,
+                          const uint8_t *shared_key, size_t shared_key_len, const uint8_t *deck,
+                          size_t deck_len, const uint8_t *shuffled, size_t shuffled_len,
+                          const uint8_t *proof, size_t proof_len);
+
+/* *token_out and *proof_out receive buffers owned by the caller. */
+int32_t mp_compute_reveal_token(const MpParameters *pp, const uint8_t *context,
+                                size_t context_len, const MpKeyPair *key_pair,
+                                const uint8_t *masked_card, size_t masked_card_len,
+                                MpBuffer *token_out, MpBuffer *proof_out);
+
+int32_t mp_verify_reveal_token(const MpParameters *pp, const uint8_t *context, size_t context_len,
+                               const uint8_t *public_key, size_t public_key_len,
+                               const uint8_t *token, size_t token_len,
+                               const uint8_t *masked_card, size_t masked_card_len,
+                               const uint8_t *proof, size_t proof_len);
+
+/* tokens is a list with the reveal token of every one of the num_players players followed by its
+ * proof and the player's public key. Every proof is checked. *card_out receives a buffer owned by
+ * the caller. */
+int32_t mp_unmask(const MpParameters *pp, const uint8_t *context, size_t context_len,
+                  size_t num_players, const uint8_t *tokens, size_t tokens_len,
+                  const uint8_t *masked_card, size_t masked_card_len, MpBuffer *card_out);
+
+#ifdef __cplusplus
+}
+#endif
+
+#endif /* MENTAL_POKER_H */
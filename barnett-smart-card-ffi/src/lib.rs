@@ -0,0 +1,872 @@
+//! C bindings for the Barnett-Smart card protocol on the starknet curve. The declarations are in
+//! `include/mental_poker.h`.
+//!
+//! Every function follows the same conventions:
+//!
+//! - It returns `MP_OK` on success, and otherwise one of the `MP_ERR_*` codes. The message of the
+//!   last error on the calling thread is available from [`mp_last_error_message`], and its stable
+//!   `ErrorCode` from [`mp_last_error_code`].
+//! - Byte inputs are a pointer and a length, borrowed for the duration of the call. A null pointer
+//!   stands for an empty input if its length is zero.
+//! - Values cross the boundary in their canonical encoding, and a list is the canonical encoding of
+//!   a `Vec`: its length as a little-endian `u64` followed by its elements. Values that come from
+//!   other players are decoded with the checks of the `validation` module.
+//! - Variable-length outputs are written to an [`MpBuffer`] the caller passes by pointer. Its
+//!   contents are allocated by the library and owned by the caller, who releases them with
+//!   [`mp_buffer_free`]. Outputs are only written on success.
+//! - Parameters and key pairs are opaque handles, owned by the caller and released with their
+//!   `*_free` function. Functions only read from handles, so a handle can be shared between
+//!   threads as long as it is not freed while in use. Secret keys never leave their key pair.
+//!
+//! Panics are caught at the boundary and reported as `MP_ERR_PANIC`.
+
+#![allow(clippy::too_many_arguments)]
+
+use barnett_smart_card_protocol::discrete_log_cards::dkg::KeyOwnershipProof;
+use barnett_smart_card_protocol::discrete_log_cards::encoding::encode_standard_deck;
+use barnett_smart_card_protocol::discrete_log_cards::session::{RevealProof, ShuffleProof};
+use barnett_smart_card_protocol::discrete_log_cards::validation::{
+    bounded_from_bytes, from_bytes, public_key_from_bytes, to_bytes, validate_point,
+    vec_from_bytes, DecodingLimits,
+};
+use barnett_smart_card_protocol::discrete_log_cards::{
+    self, Card, DLCards, MaskedCard, Parameters, PlayerSecretKey, PublicKey, RevealToken,
+};
+use barnett_smart_card_protocol::error::{
+    CardProtocolError, CryptoError, InputError, ShuffleVerificationFailure,
+};
+use barnett_smart_card_protocol::{BarnettSmartProtocol, KeyListDigest};
+
+use ark_serialize::{CanonicalDeserialize, SerializationError};
+use rand::thread_rng;
+use std::cell::{Cell, RefCell};
+use std::convert::TryFrom;
+use std::ffi::CString;
+use std::os::raw::c_char;
+use std::panic::{self, AssertUnwindSafe};
+use std::{ptr, slice};
+use thiserror::Error;
+
+type Curve = starknet_curve::Projective;
+type CardProtocol<'a> = DLCards<'a, Curve>;
+
+pub const MP_OK: i32 = 0;
+/// A required pointer was null.
+pub const MP_ERR_NULL_POINTER: i32 = 1;
+/// An input could not be decoded, or decoded to an invalid value.
+pub const MP_ERR_INVALID_INPUT: i32 = 2;
+/// A proof or a check on public values failed.
+pub const MP_ERR_VERIFICATION_FAILED: i32 = 3;
+/// An internal error of the library or of its environment.
+pub const MP_ERR_PROTOCOL: i32 = 4;
+/// The library panicked. This is a bug.
+pub const MP_ERR_PANIC: i32 = 5;
+
+thread_local! {
+    static LAST_ERROR: RefCell<CString> = RefCell::new(CString::default());
+    static LAST_ERROR_CODE: Cell<u32> = Cell::new(0);
+}
+
+#[derive(Debug, Error)]
+enum FfiError {
+    #[error("Null pointer")]
+    NullPointer,
+
+    #[error(transparent)]
+    Protocol(#[from] CardProtocolError),
+}
+
+impl From<SerializationError> for FfiError {
+    fn from(err: SerializationError) -> Self {
+        Self::Protocol(err.into())
+    }
+}
+
+impl From<CryptoError> for FfiError {
+    fn from(err: CryptoError) -> Self {
+        Self::Protocol(err.into())
+    }
+}
+
+impl From<ShuffleVerificationFailure> for FfiError {
+    fn from(err: ShuffleVerificationFailure) -> Self {
+        Self::Protocol(err.into())
+    }
+}
+
+impl FfiError {
+    fn code(&self) -> i32 {
+        match self {
+            Self::NullPointer => MP_ERR_NULL_POINTER,
+            Self::Protocol(error) => match error {
+                CardProtocolError::InvalidInput(_) => MP_ERR_INVALID_INPUT,
+                CardProtocolError::VerificationFailed(_) => MP_ERR_VERIFICATION_FAILED,
+                CardProtocolError::Internal(_) => MP_ERR_PROTOCOL,
+            },
+        }
+    }
+
+    /// The stable code of an error of the protocol, or 0.
+    fn error_code(&self) -> u32 {
+        match self {
+            Self::NullPointer => 0,
+            Self::Protocol(error) => error.code().value(),
+        }
+    }
+}
+
+/// Bytes allocated by the library and owned by the caller.
+#[repr(C)]
+pub struct MpBuffer {
+    pub data: *mut u8,
+    pub len: usize,
+}
+
+impl MpBuffer {
+    fn new(bytes: Vec<u8>) -> Self {
+        let bytes = bytes.into_boxed_slice();
+        let len = bytes.len();
+
+        Self {
+            data: Box::into_raw(bytes) as *mut u8,
+            len,
+        }
+    }
+}
+
+/// Parameters of a game.
+pub struct MpParameters(Parameters<Curve>);
+
+/// A player's key pair.
+pub struct MpKeyPair {
+    pk: PublicKey<Curve>,
+    sk: PlayerSecretKey<Curve>,
+}
+
+/// Message of the last error on the calling thread, or an empty string if there was none.
+///
+/// # Safety
+///
+/// The string is owned by the library and stays valid until the next failing call on the same
+/// thread. The caller must not free it.
+#[no_mangle]
+pub extern "C" fn mp_last_error_message() -> *const c_char {
+    LAST_ERROR.with(|error| error.borrow().as_ptr())
+}
+
+/// Stable `ErrorCode` of the last error on the calling thread, one of the `MP_CODE_*` values. It is
+/// 0 if there was no error, or if the last error was not an error of the protocol, such as a null
+/// pointer or a panic.
+#[no_mangle]
+pub extern "C" fn mp_last_error_code() -> u32 {
+    LAST_ERROR_CODE.with(Cell::get)
+}
+
+/// Release the contents of a buffer written by the library, and reset it.
+///
+/// # Safety
+///
+/// `buffer` is null or points to a buffer written by the library, or already reset. Releasing
+/// the same contents twice through copies of the buffer is undefined behaviour.
+#[no_mangle]
+pub unsafe extern "C" fn mp_buffer_free(buffer: *mut MpBuffer) {
+    if let Some(buffer) = buffer.as_mut() {
+        if !buffer.data.is_null() {
+            drop(Box::from_raw(ptr::slice_from_raw_parts_mut(
+                buffer.data,
+                buffer.len,
+            )));
+        }
+        buffer.data = ptr::null_mut();
+        buffer.len = 0;
+    }
+}
+
+/// Run the setup for a deck of `m * n` cards.
+///
+/// # Safety
+///
+/// `out` points to writable memory. On success it receives a handle owned by the caller, to be
+/// released with [`mp_parameters_free`].
+#[no_mangle]
+pub unsafe extern "C" fn mp_parameters_generate(
+    m: usize,
+    n: usize,
+    out: *mut *mut MpParameters,
+) -> i32 {
+    run(|| {
+        let out = output(out)?;
+        let pp = CardProtocol::setup(&mut thread_rng(), m, n)?;
+        *out = Box::into_raw(Box::new(MpParameters(pp)));
+
+        Ok(())
+    })
+}
+
+/// Decode parameters produced by somebody else, and check that nobody knows a trapdoor to their
+/// commitment key.
+///
+/// # Safety
+///
+/// `bytes` points to `bytes_len` readable bytes, borrowed for the call. `out` points to writable
+/// memory. On success it receives a handle owned by the caller, to be released with
+/// [`mp_parameters_free`].
+#[no_mangle]
+pub unsafe extern "C" fn mp_parameters_from_bytes(
+    bytes: *const u8,
+    bytes_len: usize,
+    out: *mut *mut MpParameters,
+) -> i32 {
+    run(|| {
+        let out = output(out)?;
+        let pp = Parameters::<Curve>::deserialize(input(bytes, bytes_len)?)?;
+        CardProtocol::verify_parameters(&pp)?;
+        *out = Box::into_raw(Box::new(MpParameters(pp)));
+
+        Ok(())
+    })
+}
+
+/// Encoding of the parameters, to send to the other players.
+///
+/// # Safety
+///
+/// `pp` is a live handle, borrowed for the call. On success `out` receives a buffer owned by the
+/// caller, to be released with [`mp_buffer_free`].
+#[no_mangle]
+pub unsafe extern "C" fn mp_parameters_to_bytes(
+    pp: *const MpParameters,
+    out: *mut MpBuffer,
+) -> i32 {
+    run(|| {
+        let out = output(out)?;
+        *out = MpBuffer::new(to_bytes(&handle(pp)?.0)?);
+
+        Ok(())
+    })
+}
+
+/// Release parameters. Null is ignored.
+///
+/// # Safety
+///
+/// `pp` is null or a handle from [`mp_parameters_generate`] or [`mp_parameters_from_bytes`] that
+/// was not released yet. It must not be used after the call.
+#[no_mangle]
+pub unsafe extern "C" fn mp_parameters_free(pp: *mut MpParameters) {
+    if !pp.is_null() {
+        drop(Box::from_raw(pp));
+    }
+}
+
+/// Generate a key pair.
+///
+/// # Safety
+///
+/// `pp` is a live handle, borrowed for the call. `out` points to writable memory. On success it
+/// receives a handle owned by the caller, to be released with [`mp_key_pair_free`].
+#[no_mangle]
+pub unsafe extern "C" fn mp_key_pair_generate(
+    pp: *const MpParameters,
+    out: *mut *mut MpKeyPair,
+) -> i32 {
+    run(|| {
+        let out = output(out)?;
+        let (pk, sk) = CardProtocol::player_keygen(&mut thread_rng(), &handle(pp)?.0)?;
+        *out = Box::into_raw(Box::new(MpKeyPair { pk, sk }));
+
+        Ok(())
+    })
+}
+
+/// Encoding of the public key of a key pair.
+///
+/// # Safety
+///
+/// `key_pair` is a live handle, borrowed for the call. On success `out` receives a buffer owned by
+/// the caller, to be released with [`mp_buffer_free`].
+#[no_mangle]
+pub unsafe extern "C" fn mp_key_pair_public_key(
+    key_pair: *const MpKeyPair,
+    out: *mut MpBuffer,
+) -> i32 {
+    run(|| {
+        let out = output(out)?;
+        *out = MpBuffer::new(to_bytes(&handle(key_pair)?.pk)?);
+
+        Ok(())
+    })
+}
+
+/// Release a key pair, and with it the secret key. Null is ignored.
+///
+/// # Safety
+///
+/// `key_pair` is null or a handle from [`mp_key_pair_generate`] that was not released yet. It must
+/// not be used after the call.
+#[no_mangle]
+pub unsafe extern "C" fn mp_key_pair_free(key_pair: *mut MpKeyPair) {
+    if !key_pair.is_null() {
+        drop(Box::from_raw(key_pair));
+    }
+}
+
+/// The list of the 52 cards of the standard deck, in the order of `standard_deck_labels`.
+///
+/// # Safety
+///
+/// On success `out` receives a buffer owned by the caller, to be released with
+/// [`mp_buffer_free`].
+#[no_mangle]
+pub unsafe extern "C" fn mp_standard_deck(out: *mut MpBuffer) -> i32 {
+    run(|| {
+        let out = output(out)?;
+        *out = MpBuffer::new(to_bytes(&encode_standard_deck::<Curve>(1)?)?);
+
+        Ok(())
+    })
+}
+
+/// Digest of the list of the players' public keys, in seat order. The digest is 32 bytes long.
+///
+/// # Safety
+///
+/// `keys` points to `keys_len` readable bytes, borrowed for the call. On success `out` receives a
+/// buffer owned by the caller, to be released with [`mp_buffer_free`].
+#[no_mangle]
+pub unsafe extern "C" fn mp_key_list_digest(
+    keys: *const u8,
+    keys_len: usize,
+    out: *mut MpBuffer,
+) -> i32 {
+    run(|| {
+        let out = output(out)?;
+        let keys = keys_from_bytes(input(keys, keys_len)?, &DecodingLimits::default())?;
+        *out = MpBuffer::new(CardProtocol::key_list_digest(&keys)?.to_vec());
+
+        Ok(())
+    })
+}
+
+/// Prove ownership of the key pair for the seat `player_index`.
+///
+/// # Safety
+///
+/// `pp` and `key_pair` are live handles, and the byte inputs point to as many readable bytes as
+/// their lengths, all borrowed for the call. On success `proof_out` receives a buffer owned by the
+/// caller, to be released with [`mp_buffer_free`].
+#[no_mangle]
+pub unsafe extern "C" fn mp_prove_key_ownership(
+    pp: *const MpParameters,
+    context: *const u8,
+    context_len: usize,
+    key_pair: *const MpKeyPair,
+    player_info: *const u8,
+    player_info_len: usize,
+    player_index: usize,
+    key_list_digest: *const u8,
+    key_list_digest_len: usize,
+    proof_out: *mut MpBuffer,
+) -> i32 {
+    run(|| {
+        let proof_out = output(proof_out)?;
+        let key_pair = handle(key_pair)?;
+        let proof = CardProtocol::prove_key_ownership(
+            &mut thread_rng(),
+            &handle(pp)?.0,
+            input(context, context_len)?,
+            &key_pair.pk,
+            &key_pair.sk,
+            &input(player_info, player_info_len)?.to_vec(),
+            player_index,
+            &digest_from_bytes(input(key_list_digest, key_list_digest_len)?)?,
+        )?;
+        *proof_out = MpBuffer::new(to_bytes(&proof)?);
+
+        Ok(())
+    })
+}
+
+/// Verify a proof of key ownership for the seat `player_index`.
+///
+/// # Safety
+///
+/// `pp` is a live handle, and the byte inputs point to as many readable bytes as their lengths,
+/// all borrowed for the call.
+#[no_mangle]
+pub unsafe extern "C" fn mp_verify_key_ownership(
+    pp: *const MpParameters,
+    context: *const u8,
+    context_len: usize,
+    public_key: *const u8,
+    public_key_len: usize,
+    player_info: *const u8,
+    player_info_len: usize,
+    player_index: usize,
+    key_list_digest: *const u8,
+    key_list_digest_len: usize,
+    proof: *const u8,
+    proof_len: usize,
+) -> i32 {
+    run(|| {
+        let pp = &handle(pp)?.0;
+        let limits = DecodingLimits::for_parameters(pp);
+        let pk = public_key_from_bytes::<PublicKey<Curve>>(input(public_key, public_key_len)?)?;
+        let proof =
+            bounded_from_bytes::<KeyOwnershipProof<Curve>>(input(proof, proof_len)?, &limits)?;
+
+        Ok(CardProtocol::verify_key_ownership(
+            pp,
+            input(context, context_len)?,
+            &pk,
+            &input(player_info, player_info_len)?.to_vec(),
+            player_index,
+            &digest_from_bytes(input(key_list_digest, key_list_digest_len)?)?,
+            &proof,
+        )?)
+    })
+}
+
+/// Aggregate key of the players, in seat order, after checking every proof of key ownership.
+/// `keys` and `proofs` are lists, and `player_info` is a list of byte strings.
+///
+/// # Safety
+///
+/// `pp` is a live handle, and the byte inputs point to as many readable bytes as their lengths,
+/// all borrowed for the call. On success `out` receives a buffer owned by the caller, to be
+/// released with [`mp_buffer_free`].
+#[no_mangle]
+pub unsafe extern "C" fn mp_aggregate_key(
+    pp: *const MpParameters,
+    context: *const u8,
+    context_len: usize,
+    keys: *const u8,
+    keys_len: usize,
+    proofs: *const u8,
+    proofs_len: usize,
+    player_info: *const u8,
+    player_info_len: usize,
+    out: *mut MpBuffer,
+) -> i32 {
+    run(|| {
+        let out = output(out)?;
+        let pp = &handle(pp)?.0;
+        let limits = DecodingLimits::for_parameters(pp);
+        let keys = keys_from_bytes(input(keys, keys_len)?, &limits)?;
+        let proofs = bounded_from_bytes::<Vec<KeyOwnershipProof<Curve>>>(
+            input(proofs, proofs_len)?,
+            &limits,
+        )?;
+        let player_info =
+            bounded_from_bytes::<Vec<Vec<u8>>>(input(player_info, player_info_len)?, &limits)?;
+        if proofs.len() != keys.len() || player_info.len() != keys.len() {
+            return Err(
+                CardProtocolError::InvalidInput(InputError::InvalidPlayerCount(keys.len())).into(),
+            );
+        }
+
+        let key_proof_info = keys
+            .into_iter()
+            .zip(proofs)
+            .zip(player_info)
+            .map(|((pk, proof), info)| (pk, proof, info))
+            .collect::<Vec<_>>();
+        let shared_key =
+            CardProtocol::compute_aggregate_key(pp, input(context, context_len)?, &key_proof_info)?;
+        *out = MpBuffer::new(to_bytes(&shared_key)?);
+
+        Ok(())
+    })
+}
+
+/// The list of `cards` masked under `shared_key` with a masking factor of one, which every player
+/// can recompute before the first shuffle.
+///
+/// # Safety
+///
+/// `pp` is a live handle, and the byte inputs point to as many readable bytes as their lengths,
+/// all borrowed for the call. On success `out` receives a buffer owned by the caller, to be
+/// released with [`mp_buffer_free`].
+#[no_mangle]
+pub unsafe extern "C" fn mp_starting_deck(
+    pp: *const MpParameters,
+    shared_key: *const u8,
+    shared_key_len: usize,
+    cards: *const u8,
+    cards_len: usize,
+    out: *mut MpBuffer,
+) -> i32 {
+    run(|| {
+        let out = output(out)?;
+        let pp = &handle(pp)?.0;
+        let limits = DecodingLimits::for_parameters(pp);
+        let shared_key =
+            public_key_from_bytes::<PublicKey<Curve>>(input(shared_key, shared_key_len)?)?;
+        let cards = vec_from_bytes::<Card<Curve>>(input(cards, cards_len)?, &limits)?;
+        let deck = discrete_log_cards::starting_deck(pp, &shared_key, &cards)?;
+        *out = MpBuffer::new(to_bytes(&deck)?);
+
+        Ok(())
+    })
+}
+
+/// Mask a card under `shared_key`.
+///
+/// # Safety
+///
+/// `pp` is a live handle, and the byte inputs point to as many readable bytes as their lengths,
+/// all borrowed for the call. On success `masked_out` and `proof_out` receive buffers owned by the
+/// caller, to be released with [`mp_buffer_free`].
+#[no_mangle]
+pub unsafe extern "C" fn mp_mask(
+    pp: *const MpParameters,
+    context: *const u8,
+    context_len: usize,
+    shared_key: *const u8,
+    shared_key_len: usize,
+    card: *const u8,
+    card_len: usize,
+    masked_out: *mut MpBuffer,
+    proof_out: *mut MpBuffer,
+) -> i32 {
+    run(|| {
+        let masked_out = output(masked_out)?;
+        let proof_out = output(proof_out)?;
+        let shared_key =
+            public_key_from_bytes::<PublicKey<Curve>>(input(shared_key, shared_key_len)?)?;
+        let card = from_bytes::<Card<Curve>>(input(card, card_len)?)?;
+        let (masked_card, proof) = CardProtocol::mask_with_rng(
+            &mut thread_rng(),
+            &handle(pp)?.0,
+            input(context, context_len)?,
+            &shared_key,
+            &card,
+        )?;
+        let (masked_card, proof) = (to_bytes(&masked_card)?, to_bytes(&proof)?);
+        *masked_out = MpBuffer::new(masked_card);
+        *proof_out = MpBuffer::new(proof);
+
+        Ok(())
+    })
+}
+
+/// Shuffle and remask the list `deck`, and prove it.
+///
+/// # Safety
+///
+/// `pp` is a live handle, and the byte inputs point to as many readable bytes as their lengths,
+/// all borrowed for the call. On success `shuffled_out`, which receives the shuffled list, and
+/// `proof_out` receive buffers owned by the caller, to be released with [`mp_buffer_free`].
+#[no_mangle]
+pub unsafe extern "C" fn mp_shuffle(
+    pp: *const MpParameters,
+    context: *const u8,
+    context_len: usize,
+    shared_key: *const u8,
+    shared_key_len: usize,
+    deck: *const u8,
+    deck_len: usize,
+    shuffled_out: *mut MpBuffer,
+    proof_out: *mut MpBuffer,
+) -> i32 {
+    run(|| {
+        let shuffled_out = output(shuffled_out)?;
+        let proof_out = output(proof_out)?;
+        let pp = &handle(pp)?.0;
+        let limits = DecodingLimits::for_parameters(pp);
+        let shared_key =
+            public_key_from_bytes::<PublicKey<Curve>>(input(shared_key, shared_key_len)?)?;
+        let deck = vec_from_bytes::<MaskedCard<Curve>>(input(deck, deck_len)?, &limits)?;
+        let (shuffled, proof) = CardProtocol::shuffle_and_remask_with_rng(
+            &mut thread_rng(),
+            pp,
+            input(context, context_len)?,
+            &shared_key,
+            &deck,
+        )?;
+        let (shuffled, proof) = (to_bytes(&shuffled)?, to_bytes(&proof)?);
+        *shuffled_out = MpBuffer::new(shuffled);
+        *proof_out = MpBuffer::new(proof);
+
+        Ok(())
+    })
+}
+
+/// Verify that `shuffled` is a shuffle and remasking of `deck`.
+///
+/// # Safety
+///
+/// `pp` is a live handle, and the byte inputs point to as many readable bytes as their lengths,
+/// all borrowed for the call.
+#[no_mangle]
+pub unsafe extern "C" fn mp_verify_shuffle(
+    pp: *const MpParameters,
+    context: *const u8,
+    context_len: usize,
+    shared_key: *const u8,
+    shared_key_len: usize,
+    deck: *const u8,
+    deck_len: usize,
+    shuffled: *const u8,
+    shuffled_len: usize,
+    proof: *const u8,
+    proof_len: usize,
+) -> i32 {
+    run(|| {
+        let pp = &handle(pp)?.0;
+        let limits = DecodingLimits::for_parameters(pp);
+        let shared_key =
+            public_key_from_bytes::<PublicKey<Curve>>(input(shared_key, shared_key_len)?)?;
+        let deck = vec_from_bytes::<MaskedCard<Curve>>(input(deck, deck_len)?, &limits)?;
+        let shuffled =
+            vec_from_bytes::<MaskedCard<Curve>>(input(shuffled, shuffled_len)?, &limits)?;
+        let proof = bounded_from_bytes::<ShuffleProof<Curve>>(input(proof, proof_len)?, &limits)?;
+
+        Ok(CardProtocol::verify_shuffle(
+            pp,
+            input(context, context_len)?,
+            &shared_key,
+            &deck,
+            &shuffled,
+            &proof,
+        )?)
+    })
+}
+
+/// Compute the reveal token of a key pair for a masked card, and prove it.
+///
+/// # Safety
+///
+/// `pp` and `key_pair` are live handles, and the byte inputs point to as many readable bytes as
+/// their lengths, all borrowed for the call. On success `token_out` and `proof_out` receive
+/// buffers owned by the caller, to be released with [`mp_buffer_free`].
+#[no_mangle]
+pub unsafe extern "C" fn mp_compute_reveal_token(
+    pp: *const MpParameters,
+    context: *const u8,
+    context_len: usize,
+    key_pair: *const MpKeyPair,
+    masked_card: *const u8,
+    masked_card_len: usize,
+    token_out: *mut MpBuffer,
+    proof_out: *mut MpBuffer,
+) -> i32 {
+    run(|| {
+        let token_out = output(token_out)?;
+        let proof_out = output(proof_out)?;
+        let key_pair = handle(key_pair)?;
+        let masked_card = from_bytes::<MaskedCard<Curve>>(input(masked_card, masked_card_len)?)?;
+        let (token, proof) = CardProtocol::compute_reveal_token(
+            &mut thread_rng(),
+            &handle(pp)?.0,
+            input(context, context_len)?,
+            &key_pair.sk,
+            &key_pair.pk,
+            &masked_card,
+        )?;
+        let (token, proof) = (to_bytes(&token)?, to_bytes(&proof)?);
+        *token_out = MpBuffer::new(token);
+        *proof_out = MpBuffer::new(proof);
+
+        Ok(())
+    })
+}
+
+/// Verify the reveal token of the owner of `public_key` for a masked card.
+///
+/// # Safety
+///
+/// `pp` is a live handle, and the byte inputs point to as many readable bytes as their lengths,
+/// all borrowed for the call.
+#[no_mangle]
+pub unsafe extern "C" fn mp_verify_reveal_token(
+    pp: *const MpParameters,
+    context: *const u8,
+    context_len: usize,
+    public_key: *const u8,
+    public_key_len: usize,
+    token: *const u8,
+    token_len: usize,
+    masked_card: *const u8,
+    masked_card_len: usize,
+    proof: *const u8,
+    proof_len: usize,
+) -> i32 {
+    run(|| {
+        let pp = &handle(pp)?.0;
+        let limits = DecodingLimits::for_parameters(pp);
+        let pk = public_key_from_bytes::<PublicKey<Curve>>(input(public_key, public_key_len)?)?;
+        let token = from_bytes::<RevealToken<Curve>>(input(token, token_len)?)?;
+        let masked_card = from_bytes::<MaskedCard<Curve>>(input(masked_card, masked_card_len)?)?;
+        let proof = bounded_from_bytes::<RevealProof<Curve>>(input(proof, proof_len)?, &limits)?;
+
+        Ok(CardProtocol::verify_reveal(
+            pp,
+            input(context, context_len)?,
+            &pk,
+            &token,
+            &masked_card,
+            &proof,
+        )?)
+    })
+}
+
+/// Open `masked_card` from `tokens`, a list with the reveal token of every one of the `num_players`
+/// players followed by its proof and the player's public key. Every proof is checked.
+///
+/// # Safety
+///
+/// `pp` is a live handle, and the byte inputs point to as many readable bytes as their lengths,
+/// all borrowed for the call. On success `card_out` receives a buffer owned by the caller, to be
+/// released with [`mp_buffer_free`].
+#[no_mangle]
+pub unsafe extern "C" fn mp_unmask(
+    pp: *const MpParameters,
+    context: *const u8,
+    context_len: usize,
+    num_players: usize,
+    tokens: *const u8,
+    tokens_len: usize,
+    masked_card: *const u8,
+    masked_card_len: usize,
+    card_out: *mut MpBuffer,
+) -> i32 {
+    run(|| {
+        let card_out = output(card_out)?;
+        let pp = &handle(pp)?.0;
+        let limits = DecodingLimits::for_parameters(pp);
+        let tokens = vec_from_bytes::<(RevealToken<Curve>, RevealProof<Curve>, PublicKey<Curve>)>(
+            input(tokens, tokens_len)?,
+            &limits,
+        )?;
+        let masked_card = from_bytes::<MaskedCard<Curve>>(input(masked_card, masked_card_len)?)?;
+        let card = CardProtocol::unmask(
+            pp,
+            input(context, context_len)?,
+            num_players,
+            &tokens,
+            &masked_card,
+        )?;
+        *card_out = MpBuffer::new(to_bytes(&card)?);
+
+        Ok(())
+    })
+}
+
+/// Run the body of an exported function, turning its errors and panics into a status code.
+fn run<F: FnOnce() -> Result<(), FfiError>>(body: F) -> i32 {
+    let (code, error_code, message) = match panic::catch_unwind(AssertUnwindSafe(body)) {
+        Ok(Ok(())) => return MP_OK,
+        Ok(Err(error)) => (error.code(), error.error_code(), error.to_string()),
+        Err(_) => (
+            MP_ERR_PANIC,
+            0,
+            String::from("Panic in the card protocol library"),
+        ),
+    };
+    let message = CString::new(message.replace('\0', "")).unwrap_or_default();
+    LAST_ERROR.with(|error| *error.borrow_mut() = message);
+    LAST_ERROR_CODE.with(|last| last.set(error_code));
+
+    code
+}
+
+unsafe fn input<'a>(data: *const u8, len: usize) -> Result<&'a [u8], FfiError> {
+    match (data.is_null(), len) {
+        (true, 0) => Ok(&[]),
+        (true, _) => Err(FfiError::NullPointer),
+        (false, _) => Ok(slice::from_raw_parts(data, len)),
+    }
+}
+
+unsafe fn handle<'a, T>(handle: *const T) -> Result<&'a T, FfiError> {
+    handle.as_ref().ok_or(FfiError::NullPointer)
+}
+
+unsafe fn output<'a, T>(out: *mut T) -> Result<&'a mut T, FfiError> {
+    out.as_mut().ok_or(FfiError::NullPointer)
+}
+
+fn keys_from_bytes(
+    bytes: &[u8],
+    limits: &DecodingLimits,
+) -> Result<Vec<PublicKey<Curve>>, CardProtocolError> {
+    let keys = bounded_from_bytes::<Vec<PublicKey<Curve>>>(bytes, limits)?;
+    keys.iter().try_for_each(validate_point)?;
+
+    Ok(keys)
+}
+
+fn digest_from_bytes(bytes: &[u8]) -> Result<KeyListDigest, CardProtocolError> {
+    KeyListDigest::try_from(bytes).map_err(|_| SerializationError::InvalidData.into())
+}
+
+#[cfg(test)]
+mod test {
+    use super::{
+        mp_buffer_free, mp_key_pair_free, mp_key_pair_generate, mp_key_pair_public_key,
+        mp_last_error_code, mp_last_error_message, mp_parameters_free, mp_parameters_from_bytes,
+        mp_parameters_generate, mp_parameters_to_bytes, MpBuffer, MP_ERR_INVALID_INPUT,
+        MP_ERR_NULL_POINTER, MP_OK,
+    };
+
+    use std::ffi::CStr;
+    use std::ptr;
+
+    fn last_error() -> String {
+        unsafe { CStr::from_ptr(mp_last_error_message()) }
+            .to_string_lossy()
+            .into_owned()
+    }
+
+    #[test]
+    fn test_handles_and_errors() {
+        let m = 4;
+        let n = 13;
+
+        unsafe {
+            let mut parameters = ptr::null_mut();
+            assert_eq!(MP_OK, mp_parameters_generate(m, n, &mut parameters));
+
+            let mut bytes = MpBuffer {
+                data: ptr::null_mut(),
+                len: 0,
+            };
+            assert_eq!(MP_OK, mp_parameters_to_bytes(parameters, &mut bytes));
+            let mut decoded = ptr::null_mut();
+            assert_eq!(
+                MP_OK,
+                mp_parameters_from_bytes(bytes.data, bytes.len, &mut decoded)
+            );
+
+            // Truncated parameters do not decode
+            assert_eq!(
+                MP_ERR_INVALID_INPUT,
+                mp_parameters_from_bytes(bytes.data, bytes.len - 1, &mut decoded)
+            );
+            assert!(!last_error().is_empty());
+            assert_ne!(0, mp_last_error_code());
+            mp_buffer_free(&mut bytes);
+            assert!(bytes.data.is_null());
+            mp_buffer_free(&mut bytes);
+
+            let mut key_pair = ptr::null_mut();
+            assert_eq!(MP_OK, mp_key_pair_generate(decoded, &mut key_pair));
+            assert_eq!(
+                MP_ERR_NULL_POINTER,
+                mp_key_pair_public_key(key_pair, ptr::null_mut())
+            );
+            assert_eq!("Null pointer", last_error());
+            assert_eq!(0, mp_last_error_code());
+            assert_eq!(
+                MP_ERR_NULL_POINTER,
+                mp_key_pair_generate(ptr::null(), &mut key_pair)
+            );
+
+            mp_key_pair_free(key_pair);
+            mp_parameters_free(decoded);
+            mp_parameters_free(parameters);
+            mp_parameters_free(ptr::null_mut());
+        }
+    }
+}
@@ -0,0 +1,35 @@
+//! Compiles `tests/round.c` against the shared library of this crate and runs it. The compiler is
+//! taken from `CC`, and defaults to `cc`.
+
+#![cfg(unix)]
+
+use std::env;
+use std::path::Path;
+use std::process::Command;
+
+#[test]
+fn test_c_round() {
+    let manifest_dir = Path::new(env!("CARGO_MANIFEST_DIR"));
+    // Cargo puts the shared library next to the test binaries
+    let deps = env::current_exe().unwrap().parent().unwrap().to_path_buf();
+    let program = deps.join("mental_poker_c_round");
+
+    let compiler = env::var("CC").unwrap_or_else(|_| String::from("cc"));
+    let status = Command::new(compiler)
+        .args(["-std=c99", "-Wall", "-Wextra", "-Werror"])
+        .arg("-I")
+        .arg(manifest_dir.join("include"))
+        .arg(manifest_dir.join("tests").join("round.c"))
+        .arg("-L")
+        .arg(&deps)
+        .arg(format!("-Wl,-rpath,{}", deps.display()))
+        .arg("-lbarnett_smart_card_ffi")
+        .arg("-o")
+        .arg(&program)
+        .status()
+        .expect("failed to run the C compiler");
+    assert!(status.success(), "round.c did not compile");
+
+    let status = Command::new(&program).status().unwrap();
+    assert!(status.success(), "round.c failed");
+}
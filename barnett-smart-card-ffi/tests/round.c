@@ -0,0 +1,213 @@
+/*
+ * A two-player round through the C bindings: setup from bytes, keys and their proofs, both
+ * players shuffling the deck, and the reveal of the top card. Exits with a non-zero status on the
+ * first unexpected result.
+ */
+
+#include "mental_poker.h"
+
+#include <stdio.h>
+#include <stdlib.h>
+#include <string.h>
+
+#define NUM_PLAYERS 2
+#define NUM_CARDS 52
+
+static const uint8_t SESSION_ID[] = "c round";
+#define CONTEXT SESSION_ID, sizeof(SESSION_ID) - 1
+
+#define CHECK(expected, call)                                                                     \
+    do {                                                                                          \
+        int32_t status = (call);                                                                  \
+        if (status != (expected)) {                                                               \
+            fprintf(stderr, "%s:%d: %s returned %d: %s\n", __FILE__, __LINE__, #call, status,     \
+                    mp_last_error_message());                                                     \
+            exit(1);                                                                              \
+        }                                                                                         \
+    } while (0)
+
+#define ASSERT(condition)                                                                         \
+    do {                                                                                          \
+        if (!(condition)) {                                                                       \
+            fprintf(stderr, "%s:%d: assertion failed: %s\n", __FILE__, __LINE__, #condition);     \
+            exit(1);                                                                              \
+        }                                                                                         \
+    } while (0)
+
+/* A growable byte string, owned by the program and released with free. */
+typedef struct {
+    uint8_t *data;
+    size_t len;
+} Bytes;
+
+static void append(Bytes *bytes, const uint8_t *data, size_t len) {
+    bytes->data = realloc(bytes->data, bytes->len + len);
+    ASSERT(bytes->data != NULL);
+    memcpy(bytes->data + bytes->len, data, len);
+    bytes->len += len;
+}
+
+static void append_u64(Bytes *bytes, uint64_t value) {
+    uint8_t encoded[8];
+    for (int i = 0; i < 8; i++) {
+        encoded[i] = (uint8_t)(value >> (8 * i));
+    }
+    append(bytes, encoded, sizeof(encoded));
+}
+
+/* Canonical encoding of a list of encoded elements. */
+static Bytes list(const MpBuffer *items, size_t count) {
+    Bytes bytes = {NULL, 0};
+    append_u64(&bytes, count);
+    for (size_t i = 0; i < count; i++) {
+        append(&bytes, items[i].data, items[i].len);
+    }
+    return bytes;
+}
+
+/* Element `index` of an encoded list whose elements all have the same size. */
+static const uint8_t *element(const MpBuffer *list, size_t count, size_t index, size_t *size) {
+    ASSERT(list->len >= 8 && (list->len - 8) % count == 0);
+    *size = (list->len - 8) / count;
+    return list->data + 8 + index * *size;
+}
+
+int main(void) {
+    const char *player_info[NUM_PLAYERS] = {"alice", "bob"};
+
+    /* The parameters travel from whoever generated them to every player */
+    MpParameters *generated = NULL;
+    MpParameters *pp = NULL;
+    MpBuffer encoded = {NULL, 0};
+    CHECK(MP_OK, mp_parameters_generate(4, 13, &generated));
+    CHECK(MP_OK, mp_parameters_to_bytes(generated, &encoded));
+    CHECK(MP_OK, mp_parameters_from_bytes(encoded.data, encoded.len, &pp));
+    CHECK(MP_ERR_INVALID_INPUT, mp_parameters_from_bytes(encoded.data, 3, &generated));
+    ASSERT(strlen(mp_last_error_message()) > 0);
+    mp_buffer_free(&encoded);
+    mp_parameters_free(generated);
+
+    MpKeyPair *players[NUM_PLAYERS];
+    MpBuffer keys[NUM_PLAYERS];
+    for (size_t i = 0; i < NUM_PLAYERS; i++) {
+        CHECK(MP_OK, mp_key_pair_generate(pp, &players[i]));
+        CHECK(MP_OK, mp_key_pair_public_key(players[i], &keys[i]));
+    }
+    Bytes key_list = list(keys, NUM_PLAYERS);
+    MpBuffer digest;
+    CHECK(MP_OK, mp_key_list_digest(key_list.data, key_list.len, &digest));
+    ASSERT(digest.len == 32);
+
+    MpBuffer proofs[NUM_PLAYERS];
+    Bytes info_list = {NULL, 0};
+    append_u64(&info_list, NUM_PLAYERS);
+    for (size_t i = 0; i < NUM_PLAYERS; i++) {
+        const uint8_t *info = (const uint8_t *)player_info[i];
+        size_t info_len = strlen(player_info[i]);
+        append_u64(&info_list, info_len);
+        append(&info_list, info, info_len);
+
+        CHECK(MP_OK, mp_prove_key_ownership(pp, CONTEXT, players[i], info, info_len, i,
+                                            digest.data, digest.len, &proofs[i]));
+        CHECK(MP_OK, mp_verify_key_ownership(pp, CONTEXT, keys[i].data, keys[i].len, info,
+                                             info_len, i, digest.data, digest.len,
+                                             proofs[i].data, proofs[i].len));
+    }
+    /* A proof does not hold for another seat */
+    CHECK(MP_ERR_VERIFICATION_FAILED,
+          mp_verify_key_ownership(pp, CONTEXT, keys[0].data, keys[0].len,
+                                  (const uint8_t *)player_info[0], strlen(player_info[0]), 1,
+                                  digest.data, digest.len, proofs[0].data, proofs[0].len));
+
+    Bytes proof_list = list(proofs, NUM_PLAYERS);
+    MpBuffer shared_key;
+    CHECK(MP_OK, mp_aggregate_key(pp, CONTEXT, key_list.data, key_list.len, proof_list.data,
+                                  proof_list.len, info_list.data, info_list.len, &shared_key));
+
+    /* Both players shuffle the deck in turn, and check each other's shuffle */
+    MpBuffer cards;
+    MpBuffer deck;
+    CHECK(MP_OK, mp_standard_deck(&cards));
+    CHECK(MP_OK, mp_starting_deck(pp, shared_key.data, shared_key.len, cards.data, cards.len,
+                                  &deck));
+    for (size_t i = 0; i < NUM_PLAYERS; i++) {
+        MpBuffer shuffled;
+        MpBuffer proof;
+        CHECK(MP_OK, mp_shuffle(pp, CONTEXT, shared_key.data, shared_key.len, deck.data,
+                                deck.len, &shuffled, &proof));
+        CHECK(MP_OK, mp_verify_shuffle(pp, CONTEXT, shared_key.data, shared_key.len, deck.data,
+                                       deck.len, shuffled.data, shuffled.len, proof.data,
+                                       proof.len));
+        CHECK(MP_ERR_VERIFICATION_FAILED,
+              mp_verify_shuffle(pp, CONTEXT, shared_key.data, shared_key.len, shuffled.data,
+                                shuffled.len, deck.data, deck.len, proof.data, proof.len));
+        mp_buffer_free(&deck);
+        mp_buffer_free(&proof);
+        deck = shuffled;
+    }
+
+    /* Both players reveal the top card */
+    size_t masked_len;
+    const uint8_t *top = element(&deck, NUM_CARDS, 0, &masked_len);
+    MpBuffer shares[NUM_PLAYERS];
+    for (size_t i = 0; i < NUM_PLAYERS; i++) {
+        MpBuffer token;
+        MpBuffer proof;
+        CHECK(MP_OK, mp_compute_reveal_token(pp, CONTEXT, players[i], top, masked_len, &token,
+                                             &proof));
+        CHECK(MP_OK, mp_verify_reveal_token(pp, CONTEXT, keys[i].data, keys[i].len, token.data,
+                                            token.len, top, masked_len, proof.data, proof.len));
+
+        Bytes share = {NULL, 0};
+        append(&share, token.data, token.len);
+        append(&share, proof.data, proof.len);
+        append(&share, keys[i].data, keys[i].len);
+        shares[i].data = share.data;
+        shares[i].len = share.len;
+        mp_buffer_free(&token);
+        mp_buffer_free(&proof);
+    }
+    Bytes token_list = list(shares, NUM_PLAYERS);
+    MpBuffer card;
+    CHECK(MP_OK, mp_unmask(pp, CONTEXT, NUM_PLAYERS, token_list.data, token_list.len, top,
+                           masked_len, &card));
+
+    int found = 0;
+    for (size_t i = 0; i < NUM_CARDS; i++) {
+        size_t card_len;
+        const uint8_t *open = element(&cards, NUM_CARDS, i, &card_len);
+        found |= card_len == card.len && memcmp(open, card.data, card_len) == 0;
+    }
+    ASSERT(found);
+
+    /* Tokens do not open any other card */
+    MpBuffer other;
+    const uint8_t *second = element(&deck, NUM_CARDS, 1, &masked_len);
+    CHECK(MP_ERR_VERIFICATION_FAILED,
+          mp_unmask(pp, CONTEXT, NUM_PLAYERS, token_list.data, token_list.len, second, masked_len,
+                    &other));
+    ASSERT(mp_last_error_code() == MP_CODE_INVALID_REVEAL_TOKEN);
+
+    /* Missing outputs are reported, not written through */
+    CHECK(MP_ERR_NULL_POINTER, mp_standard_deck(NULL));
+
+    for (size_t i = 0; i < NUM_PLAYERS; i++) {
+        free(shares[i].data);
+        mp_buffer_free(&proofs[i]);
+        mp_buffer_free(&keys[i]);
+        mp_key_pair_free(players[i]);
+    }
+    free(token_list.data);
+    free(proof_list.data);
+    free(info_list.data);
+    free(key_list.data);
+    mp_buffer_free(&card);
+    mp_buffer_free(&deck);
+    mp_buffer_free(&cards);
+    mp_buffer_free(&shared_key);
+    mp_buffer_free(&digest);
+    mp_parameters_free(pp);
+
+    printf("c round: ok\n");
+    return 0;
+}
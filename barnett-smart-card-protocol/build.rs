@@ -0,0 +1,9 @@
+fn main() {
+    println!("cargo:rerun-if-changed=build.rs");
+
+    // Generates the message types of the `proto` module. prost-build ships `protoc`, so none
+    // needs to be installed.
+    #[cfg(feature = "proto")]
+    prost_build::compile_protos(&["proto/cards.proto", "proto/messages.proto"], &["proto"])
+        .expect("failed to compile the protobuf definitions");
+}
@@ -0,0 +1,206 @@
+//! Writes reveal tokens and proofs over BN254 in the layout of `discrete_log_cards::evm`, for
+//! the test harness of the on-chain verifier. Every valid proof is followed by the same proof with
+//! its response incremented, which a verifier must reject. Fixtures are generated from a fixed
+//! seed, so running the example again reproduces the same file, and the `evm` tests check it.
+//!
+//! It also writes the calldata of reveal verifications in the layout of `export::evm`: every
+//! valid proof, then one entry for each way of breaking it, which the `export::evm` tests check.
+//!
+//! Run the example with `cargo run --example bn254_fixtures --features bn254`.
+
+use barnett_smart_card_protocol::discrete_log_cards;
+use barnett_smart_card_protocol::discrete_log_cards::evm::{
+    decode_scalar, encode_masked_card, encode_point, encode_public_key, encode_reveal,
+    encode_scalar, REVEAL_LENGTH,
+};
+use barnett_smart_card_protocol::export::evm::{
+    decode_reveal_verification, encode_reveal_verification, REVEAL_VERIFICATION_LENGTH,
+};
+use barnett_smart_card_protocol::BarnettSmartProtocol;
+
+use ark_bn254::Fr;
+use ark_ff::One;
+use ark_std::UniformRand;
+use rand::SeedableRng;
+use rand_chacha::ChaCha20Rng;
+use std::fmt::Write as _;
+
+// Choose elliptic curve setting
+type Curve = ark_bn254::G1Projective;
+
+// Instantiate concrete type for our card protocol
+type CardProtocol<'a> = discrete_log_cards::DLCards<'a, Curve>;
+type MaskedCard = discrete_log_cards::MaskedCard<Curve>;
+type Parameters = discrete_log_cards::Parameters<Curve>;
+
+const FIXTURE_PATH: &str = "tests/vectors/bn254_reveal_proofs.txt";
+const FIXTURE_SEED: [u8; 32] = *b"bn254 reveal proof fixtures v1..";
+const NUMBER_OF_FIXTURES: usize = 8;
+const VERIFICATION_PATH: &str = "tests/vectors/bn254_reveal_verification.txt";
+const VERIFICATION_SEED: [u8; 32] = *b"bn254 reveal verification v1....";
+
+fn to_hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+fn main() -> anyhow::Result<()> {
+    let rng = &mut ChaCha20Rng::from_seed(FIXTURE_SEED);
+
+    let parameters = CardProtocol::setup(rng, 4, 13)?;
+
+    let mut fixtures = String::new();
+    writeln!(
+        fixtures,
+        "# Reveal tokens and proofs over BN254 G1, in the layout of `discrete_log_cards::evm`."
+    )?;
+    writeln!(
+        fixtures,
+        "# Generated by `cargo run --example bn254_fixtures --features bn254`. The first line"
+    )?;
+    writeln!(
+        fixtures,
+        "# is the encryption generator of the parameters, which `setup` hashes to the curve; every"
+    )?;
+    writeln!(
+        fixtures,
+        "# other line is `label public_key masked_card_c1 reveal`, with"
+    )?;
+    writeln!(
+        fixtures,
+        "# the label `valid` or `invalid` and the values in hex."
+    )?;
+    writeln!(
+        fixtures,
+        "generator {}",
+        to_hex(&encode_point(&parameters.encryption_generator()))
+    )?;
+
+    for _ in 0..NUMBER_OF_FIXTURES {
+        let (pk, sk) = CardProtocol::player_keygen(rng, &parameters)?;
+        let masked = MaskedCard::rand(rng);
+        let (token, proof) =
+            CardProtocol::compute_reveal_token(rng, &parameters, b"", &sk, &pk, &masked)?;
+        CardProtocol::verify_reveal(&parameters, b"", &pk, &token, &masked, &proof)?;
+
+        let reveal = encode_reveal(&token, &proof)?;
+        let response = decode_scalar(&reveal[REVEAL_LENGTH - 32..])?;
+        let mut tampered = reveal.clone();
+        tampered[REVEAL_LENGTH - 32..].copy_from_slice(&encode_scalar(&(response + Fr::one())));
+
+        for (label, reveal) in [("valid", reveal), ("invalid", tampered)] {
+            writeln!(
+                fixtures,
+                "{} {} {} {}",
+                label,
+                to_hex(&encode_public_key(&pk)),
+                to_hex(&encode_masked_card(&masked)),
+                to_hex(&reveal)
+            )?;
+        }
+    }
+
+    std::fs::write(FIXTURE_PATH, fixtures)?;
+    println!(
+        "Wrote {} valid and {} invalid fixtures to {}",
+        NUMBER_OF_FIXTURES, NUMBER_OF_FIXTURES, FIXTURE_PATH
+    );
+
+    std::fs::write(VERIFICATION_PATH, reveal_verifications(&parameters)?)?;
+    println!(
+        "Wrote {} valid reveal verifications and their broken variants to {}",
+        NUMBER_OF_FIXTURES, VERIFICATION_PATH
+    );
+
+    Ok(())
+}
+
+/// Calldata of reveal verifications: each valid proof, followed by the proof with its response
+/// incremented and the same proof against another token, another public key and another masked
+/// card. Each entry is decoded and checked against `verify_reveal` before it is written.
+fn reveal_verifications(parameters: &Parameters) -> anyhow::Result<String> {
+    let rng = &mut ChaCha20Rng::from_seed(VERIFICATION_SEED);
+
+    let mut fixtures = String::new();
+    writeln!(
+        fixtures,
+        "# Reveal verification calldata over BN254 G1, in the layout of `export::evm`."
+    )?;
+    writeln!(
+        fixtures,
+        "# Generated by `cargo run --example bn254_fixtures --features bn254`. Every line is"
+    )?;
+    writeln!(
+        fixtures,
+        "# `name expected calldata`, with `expected` either `valid` or `invalid` and the calldata"
+    )?;
+    writeln!(
+        fixtures,
+        "# in hex. Proofs are bound to the empty context, and the encryption generator is the one"
+    )?;
+    writeln!(fixtures, "# of `setup` for a 4 x 13 deck.")?;
+
+    for i in 0..NUMBER_OF_FIXTURES {
+        let (pk, sk) = CardProtocol::player_keygen(rng, parameters)?;
+        let (other_pk, _) = CardProtocol::player_keygen(rng, parameters)?;
+        let masked = MaskedCard::rand(rng);
+        let other_masked = MaskedCard::rand(rng);
+        let (token, proof) =
+            CardProtocol::compute_reveal_token(rng, parameters, b"", &sk, &pk, &masked)?;
+        let (other_token, _) =
+            CardProtocol::compute_reveal_token(rng, parameters, b"", &sk, &pk, &other_masked)?;
+
+        let valid = encode_reveal_verification(&masked, &token, &pk, &proof)?;
+        let response = decode_scalar(&valid[REVEAL_VERIFICATION_LENGTH - 32..])?;
+        let mut tampered_response = valid.clone();
+        tampered_response[REVEAL_VERIFICATION_LENGTH - 32..]
+            .copy_from_slice(&encode_scalar(&(response + Fr::one())));
+
+        let cases = [
+            ("valid", true, valid),
+            ("tampered_response", false, tampered_response),
+            (
+                "wrong_token",
+                false,
+                encode_reveal_verification(&masked, &other_token, &pk, &proof)?,
+            ),
+            (
+                "wrong_public_key",
+                false,
+                encode_reveal_verification(&masked, &token, &other_pk, &proof)?,
+            ),
+            (
+                "wrong_masked_card",
+                false,
+                encode_reveal_verification(&other_masked, &token, &pk, &proof)?,
+            ),
+        ];
+        for (name, expected, calldata) in cases {
+            let decoded = decode_reveal_verification(&calldata)?;
+            let verified = CardProtocol::verify_reveal(
+                parameters,
+                b"",
+                &decoded.pk,
+                &decoded.token,
+                &decoded.masked_card,
+                &decoded.proof,
+            );
+            anyhow::ensure!(
+                verified.is_ok() == expected,
+                "case {}_{} does not verify as expected",
+                name,
+                i
+            );
+
+            writeln!(
+                fixtures,
+                "{}_{} {} {}",
+                name,
+                i,
+                if expected { "valid" } else { "invalid" },
+                to_hex(&calldata)
+            )?;
+        }
+    }
+
+    Ok(fixtures)
+}
@@ -0,0 +1,171 @@
+//! Writes reveal tokens and proofs over the Starknet curve in the felt layout of
+//! `export::cairo`, with valid and invalid cases, for the tests of the Cairo verifier. Fixtures are
+//! generated from a fixed seed, so running the example again reproduces the same file.
+//!
+//! Run the example with `cargo run --example cairo_fixtures`.
+
+use barnett_smart_card_protocol::discrete_log_cards;
+use barnett_smart_card_protocol::export::cairo::{
+    export_reveal, export_unmask, felt_to_hex, point_felts, transcript_seed_felts, Felt,
+};
+use barnett_smart_card_protocol::BarnettSmartProtocol;
+
+use ark_ff::One;
+use ark_std::UniformRand;
+use rand::SeedableRng;
+use rand_chacha::ChaCha20Rng;
+use std::fmt::Write as _;
+
+// Choose elliptic curve setting
+type Curve = starknet_curve::Projective;
+
+// Instantiate concrete type for our card protocol
+type CardProtocol<'a> = discrete_log_cards::DLCards<'a, Curve>;
+type MaskedCard = discrete_log_cards::MaskedCard<Curve>;
+
+const FIXTURE_PATH: &str = "tests/vectors/cairo_reveal_proofs.json";
+const FIXTURE_SEED: [u8; 32] = *b"cairo reveal proof fixtures v1..";
+const CONTEXT: &[u8] = b"cairo fixtures";
+const NUMBER_OF_PLAYERS: usize = 3;
+
+fn felt_list(felts: &[Felt]) -> String {
+    let felts = felts
+        .iter()
+        .map(|felt| format!("\"{}\"", felt_to_hex(felt)))
+        .collect::<Vec<_>>();
+
+    format!("[{}]", felts.join(", "))
+}
+
+fn case(name: &str, valid: bool, felts: &[Felt]) -> String {
+    format!(
+        "    {{\"name\": \"{}\", \"valid\": {}, \"felts\": {}}}",
+        name,
+        valid,
+        felt_list(felts)
+    )
+}
+
+fn main() -> anyhow::Result<()> {
+    let rng = &mut ChaCha20Rng::from_seed(FIXTURE_SEED);
+
+    let parameters = CardProtocol::setup(rng, 4, 13)?;
+    let keys = (0..NUMBER_OF_PLAYERS)
+        .map(|_| CardProtocol::player_keygen(rng, &parameters))
+        .collect::<Result<Vec<_>, _>>()?;
+    let masked = MaskedCard::rand(rng);
+    let other_masked = MaskedCard::rand(rng);
+
+    let decryption_key = keys
+        .iter()
+        .map(|(pk, sk)| {
+            let (token, proof) =
+                CardProtocol::compute_reveal_token(rng, &parameters, CONTEXT, sk, pk, &masked)?;
+            CardProtocol::verify_reveal(&parameters, CONTEXT, pk, &token, &masked, &proof)?;
+            Ok((token, proof, *pk))
+        })
+        .collect::<anyhow::Result<Vec<_>>>()?;
+    let (token, proof, pk) = decryption_key[0].clone();
+    let other_pk = &keys[1].0;
+
+    // Invalid reveals: each changes one input of the valid one
+    let (wrong_token, wrong_card_proof) = CardProtocol::compute_reveal_token(
+        rng,
+        &parameters,
+        CONTEXT,
+        &keys[0].1,
+        &pk,
+        &other_masked,
+    )?;
+    let (replayed_token, replayed_proof) = CardProtocol::compute_reveal_token(
+        rng,
+        &parameters,
+        b"another table",
+        &keys[0].1,
+        &pk,
+        &masked,
+    )?;
+    for (token, proof, pk) in [
+        (&wrong_token, &wrong_card_proof, &pk),
+        (&replayed_token, &replayed_proof, &pk),
+        (&token, &proof, other_pk),
+    ] {
+        assert!(
+            CardProtocol::verify_reveal(&parameters, CONTEXT, pk, token, &masked, proof).is_err()
+        );
+    }
+
+    let valid = export_reveal(&pk, &masked, &token, &proof)?;
+    let mut tampered_response = valid.clone();
+    tampered_response[12] += Felt::one();
+    let mut off_curve = valid.clone();
+    off_curve[7] += Felt::one();
+
+    let reveals = [
+        case("valid", true, &valid),
+        case(
+            "token_for_another_card",
+            false,
+            &export_reveal(&pk, &masked, &wrong_token, &wrong_card_proof)?,
+        ),
+        case(
+            "proof_from_another_context",
+            false,
+            &export_reveal(&pk, &masked, &replayed_token, &replayed_proof)?,
+        ),
+        case(
+            "public_key_of_another_player",
+            false,
+            &export_reveal(other_pk, &masked, &token, &proof)?,
+        ),
+        case("tampered_response", false, &tampered_response),
+        case("token_off_curve", false, &off_curve),
+    ];
+
+    // Invalid unmask: the second player's token is replaced by the first player's
+    let mut swapped = decryption_key.clone();
+    swapped[1].0 = token;
+    let unmasks = [
+        case(
+            "valid",
+            true,
+            &export_unmask(&parameters, CONTEXT, &decryption_key, &masked)?,
+        ),
+        case(
+            "swapped_token",
+            false,
+            &export_unmask(&parameters, CONTEXT, &swapped, &masked)?,
+        ),
+    ];
+
+    let mut fixtures = String::new();
+    writeln!(fixtures, "{{")?;
+    writeln!(
+        fixtures,
+        "  \"description\": \"Reveal proofs over the Starknet curve in the layout of \
+         export::cairo. Generated by `cargo run --example cairo_fixtures`.\","
+    )?;
+    writeln!(
+        fixtures,
+        "  \"generator\": {},",
+        felt_list(&point_felts(&parameters.encryption_generator()))
+    )?;
+    writeln!(
+        fixtures,
+        "  \"transcript_seed\": {},",
+        felt_list(&transcript_seed_felts(CONTEXT)?)
+    )?;
+    writeln!(fixtures, "  \"reveals\": [\n{}\n  ],", reveals.join(",\n"))?;
+    writeln!(fixtures, "  \"unmasks\": [\n{}\n  ]", unmasks.join(",\n"))?;
+    writeln!(fixtures, "}}")?;
+
+    std::fs::write(FIXTURE_PATH, fixtures)?;
+    println!(
+        "Wrote {} reveals and {} unmasks to {}",
+        reveals.len(),
+        unmasks.len(),
+        FIXTURE_PATH
+    );
+
+    Ok(())
+}
@@ -0,0 +1,21 @@
+//! Writes conformance vectors in the format of the `conformance` module, for other
+//! implementations of the protocol to check themselves against. Vectors are generated from a fixed
+//! seed, so running the example again reproduces the same file, which a test compares with what
+//! the crate generates. Only write the file again when the format changes on purpose, as
+//! described in the documentation of the `conformance` module.
+//!
+//! Run the example with `cargo run --example conformance_vectors --features conformance`.
+
+use barnett_smart_card_protocol::conformance::{check, known_answer_vectors};
+
+const VECTORS_PATH: &str = "tests/vectors/conformance.json";
+
+fn main() -> anyhow::Result<()> {
+    let vectors = known_answer_vectors()?;
+    check(&vectors)?;
+
+    std::fs::write(VECTORS_PATH, vectors.to_json()? + "\n")?;
+    println!("Wrote {} cases to {}", vectors.cases.len(), VECTORS_PATH);
+
+    Ok(())
+}
@@ -0,0 +1,58 @@
+//! Compares multi-scalar multiplication against a fixed set of bases with and without precomputed
+//! tables, for the sizes of typical decks.
+//!
+//! Run the example with `cargo run --example msm_precompute --release`.
+
+use ark_ec::ProjectiveCurve;
+use ark_ff::UniformRand;
+use barnett_smart_card_protocol::crypto_primitives::utils::msm::{msm, MsmPrecompute};
+use proof_essentials::utils::rand::sample_vector;
+use rand::thread_rng;
+use std::time::Instant;
+
+// Choose elliptic curve setting
+type Curve = starknet_curve::Projective;
+type Scalar = starknet_curve::Fr;
+
+const NUMBER_OF_EVALUATIONS: usize = 100;
+
+fn main() -> anyhow::Result<()> {
+    let mut rng = thread_rng();
+
+    for &size in &[13, 52, 300] {
+        let bases = (0..size)
+            .map(|_| Curve::rand(&mut rng).into_affine())
+            .collect::<Vec<_>>();
+        let scalars = (0..NUMBER_OF_EVALUATIONS)
+            .map(|_| sample_vector::<Scalar, _>(&mut rng, size))
+            .collect::<Vec<_>>();
+
+        println!("\n---------------------------------------------------");
+        println!(
+            "  {} evaluations over {} bases",
+            NUMBER_OF_EVALUATIONS, size
+        );
+
+        let start_time = Instant::now();
+        for s in &scalars {
+            msm::<Curve>(&bases, s)?;
+        }
+        println!("    Ad-hoc: {} seconds", start_time.elapsed().as_secs_f32());
+
+        let start_time = Instant::now();
+        let precompute = MsmPrecompute::<Curve>::new(&bases);
+        let precompute_duration = start_time.elapsed();
+
+        let start_time = Instant::now();
+        for s in &scalars {
+            precompute.evaluate(s)?;
+        }
+        println!(
+            "    Precomputed: {} seconds (+ {} seconds to build the tables)",
+            start_time.elapsed().as_secs_f32(),
+            precompute_duration.as_secs_f32()
+        );
+    }
+
+    Ok(())
+}
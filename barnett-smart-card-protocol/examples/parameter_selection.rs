@@ -3,10 +3,10 @@
 //! - the prover performs m*N exponentiations
 //! - the proof is approximately 6m*|G|+4n*|Z| where |G| is the size of a EC point and |Z| is the size of a scalar
 //! (note that this is because we are not using the FFT-like improvement suggested in the paper)
-//! 
+//!
 //! Analysis: increasing m will always increase the prover time. Assuming |G| ≈≈ 2*|Z|, proof size is approx 12m+4n and will
 //! be minimised when m ≈≈ n/3.
-//! 
+//!
 //! Run the example `cargo run --example parameter_selection --release` and notice how proof size hits a minimum at m=10, n=30
 
 use anyhow::anyhow;
@@ -18,7 +18,7 @@ use barnett_smart_card_protocol::{discrete_log_cards, BarnettSmartProtocol};
 use byte_unit::Byte;
 use proof_essentials::utils::permutation::Permutation;
 use proof_essentials::utils::rand::sample_vector;
-use rand::{thread_rng, Rng};
+use rand::{thread_rng, CryptoRng, Rng};
 use std::time::Instant;
 
 // Choose elliptic curve setting
@@ -29,6 +29,7 @@ type Scalar = ark_bls12_377::Fr;
 type CardProtocol<'a> = discrete_log_cards::DLCards<'a, Curve>;
 
 const NUMBER_OF_CARDS: usize = 300;
+const SESSION_ID: &[u8] = b"parameter selection";
 
 fn main() -> anyhow::Result<()> {
     let mut rng = thread_rng();
@@ -56,7 +57,7 @@ fn main() -> anyhow::Result<()> {
     Ok(())
 }
 
-fn benchmark_parameters<R: Rng>(
+fn benchmark_parameters<R: Rng + CryptoRng>(
     deck: &Vec<MaskedCard<Curve>>,
     m: usize,
     n: usize,
@@ -81,6 +82,7 @@ fn benchmark_parameters<R: Rng>(
     let (_shuffled_deck, proof) = CardProtocol::shuffle_and_remask(
         rng,
         &parameters,
+        SESSION_ID,
         &shared_key.into_affine(),
         deck,
         masking_factors,
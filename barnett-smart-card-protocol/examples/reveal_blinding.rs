@@ -0,0 +1,52 @@
+//! Measures the overhead of computing reveal tokens from a blinded secret key, as
+//! `compute_reveal_token` does with the `side-channel-hardening` feature.
+//!
+//! Run the example with `cargo run --example reveal_blinding --release`.
+
+use barnett_smart_card_protocol::discrete_log_cards;
+use barnett_smart_card_protocol::discrete_log_cards::reveal::{blinded_reveal_token, reveal_token};
+use barnett_smart_card_protocol::BarnettSmartProtocol;
+
+use proof_essentials::utils::rand::sample_vector;
+use rand::thread_rng;
+use std::time::Instant;
+
+// Choose elliptic curve setting
+type Curve = starknet_curve::Projective;
+
+// Instantiate concrete type for our card protocol
+type CardProtocol<'a> = discrete_log_cards::DLCards<'a, Curve>;
+type MaskedCard = discrete_log_cards::MaskedCard<Curve>;
+
+const NUMBER_OF_TOKENS: usize = 1000;
+
+fn main() -> anyhow::Result<()> {
+    let mut rng = thread_rng();
+
+    let parameters = CardProtocol::setup(&mut rng, 4, 13)?;
+    let (_, sk) = CardProtocol::player_keygen(&mut rng, &parameters)?;
+    let cards: Vec<MaskedCard> = sample_vector(&mut rng, NUMBER_OF_TOKENS);
+
+    println!("\n---------------------------------------------------");
+    println!("  {} reveal tokens", NUMBER_OF_TOKENS);
+
+    let start_time = Instant::now();
+    for card in &cards {
+        reveal_token(&sk, card);
+    }
+    let unblinded = start_time.elapsed().as_secs_f32();
+    println!("    Unblinded: {} seconds", unblinded);
+
+    let start_time = Instant::now();
+    for card in &cards {
+        blinded_reveal_token(&mut rng, &sk, card)?;
+    }
+    let blinded = start_time.elapsed().as_secs_f32();
+    println!(
+        "    Blinded: {} seconds ({:.2}x)",
+        blinded,
+        blinded / unblinded
+    );
+
+    Ok(())
+}
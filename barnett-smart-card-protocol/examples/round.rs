@@ -1,11 +1,17 @@
+use barnett_smart_card_protocol::crypto_primitives::utils::ct::ct_position;
+use barnett_smart_card_protocol::crypto_primitives::utils::rand::sample_nonzero_vector;
 use barnett_smart_card_protocol::discrete_log_cards;
-use barnett_smart_card_protocol::BarnettSmartProtocol;
+use barnett_smart_card_protocol::discrete_log_cards::encoding::STANDARD_DECK_SIZE;
+use barnett_smart_card_protocol::playing_card::ClassicPlayingCard;
+use barnett_smart_card_protocol::{BarnettSmartProtocol, KeyListDigest};
 
 use anyhow;
-use ark_ff::{to_bytes, UniformRand};
-use ark_std::{rand::Rng, One};
+use ark_ff::to_bytes;
+use ark_std::{
+    rand::{CryptoRng, Rng},
+    One,
+};
 use proof_essentials::utils::permutation::Permutation;
-use proof_essentials::utils::rand::sample_vector;
 use proof_essentials::zkp::proofs::{chaum_pedersen_dl_equality, schnorr_identification};
 use rand::thread_rng;
 use std::collections::HashMap;
@@ -30,6 +36,11 @@ type ProofKeyOwnership = schnorr_identification::proof::Proof<Curve>;
 type RemaskingProof = chaum_pedersen_dl_equality::proof::Proof<Curve>;
 type RevealProof = chaum_pedersen_dl_equality::proof::Proof<Curve>;
 
+/// Identifies this game, so that proofs from it cannot be replayed in any other
+const SESSION_ID: &[u8] = b"round example: game 1";
+/// Andrija, Kobi, Nico and Tom
+const NUM_OF_PLAYERS: usize = 4;
+
 #[derive(Error, Debug, PartialEq)]
 pub enum GameErrors {
     #[error("No such card in hand")]
@@ -39,118 +50,53 @@ pub enum GameErrors {
     InvalidCard,
 }
 
-#[derive(PartialEq, Clone, Copy, Eq)]
-pub enum Suite {
-    Club,
-    Diamond,
-    Heart,
-    Spade,
-}
-
-impl Suite {
-    const VALUES: [Self; 4] = [Self::Club, Self::Diamond, Self::Heart, Self::Spade];
-}
-
-#[derive(PartialEq, PartialOrd, Clone, Copy, Eq)]
-pub enum Value {
-    Two,
-    Three,
-    Four,
-    Five,
-    Six,
-    Seven,
-    Eight,
-    Nine,
-    Ten,
-    Jack,
-    Queen,
-    King,
-    Ace,
-}
-
-impl Value {
-    const VALUES: [Self; 13] = [
-        Self::Two,
-        Self::Three,
-        Self::Four,
-        Self::Five,
-        Self::Six,
-        Self::Seven,
-        Self::Eight,
-        Self::Nine,
-        Self::Ten,
-        Self::Jack,
-        Self::Queen,
-        Self::King,
-        Self::Ace,
-    ];
-}
-
-#[derive(PartialEq, Clone, Eq, Copy)]
-pub struct ClassicPlayingCard {
-    value: Value,
-    suite: Suite,
-}
-
-impl ClassicPlayingCard {
-    pub fn new(value: Value, suite: Suite) -> Self {
-        Self { value, suite }
-    }
-}
-
-impl std::fmt::Debug for ClassicPlayingCard {
-    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        let suite = match self.suite {
-            Suite::Club => "♣",
-            Suite::Diamond => "♦",
-            Suite::Heart => "♥",
-            Suite::Spade => "♠",
-        };
-
-        let val = match self.value {
-            Value::Two => "2",
-            Value::Three => "3",
-            Value::Four => "4",
-            Value::Five => "5",
-            Value::Six => "6",
-            Value::Seven => "7",
-            Value::Eight => "8",
-            Value::Nine => "9",
-            Value::Ten => "10",
-            Value::Jack => "J",
-            Value::Queen => "Q",
-            Value::King => "K",
-            Value::Ace => "A",
-        };
-
-        write!(f, "{}{}", val, suite)
-    }
-}
-
 #[derive(Clone)]
 struct Player {
     name: Vec<u8>,
     sk: SecretKey,
     pk: PublicKey,
-    proof_key: ProofKeyOwnership,
     cards: Vec<MaskedCard>,
     opened_cards: Vec<Option<ClassicPlayingCard>>,
 }
 
 impl Player {
-    pub fn new<R: Rng>(rng: &mut R, pp: &CardParameters, name: &Vec<u8>) -> anyhow::Result<Self> {
+    pub fn new<R: Rng + CryptoRng>(
+        rng: &mut R,
+        pp: &CardParameters,
+        name: &Vec<u8>,
+    ) -> anyhow::Result<Self> {
         let (pk, sk) = CardProtocol::player_keygen(rng, pp)?;
-        let proof_key = CardProtocol::prove_key_ownership(rng, pp, &pk, &sk, name)?;
         Ok(Self {
             name: name.clone(),
             sk,
             pk,
-            proof_key,
             cards: vec![],
             opened_cards: vec![],
         })
     }
 
+    /// Once every player's key is known, prove ownership of this player's key at position `index`.
+    pub fn prove_key_ownership<R: Rng + CryptoRng>(
+        &self,
+        rng: &mut R,
+        pp: &CardParameters,
+        index: usize,
+        key_list_digest: &KeyListDigest,
+    ) -> anyhow::Result<ProofKeyOwnership> {
+        let proof_key = CardProtocol::prove_key_ownership(
+            rng,
+            pp,
+            SESSION_ID,
+            &self.pk,
+            &self.sk,
+            &self.name,
+            index,
+            key_list_digest,
+        )?;
+
+        Ok(proof_key)
+    }
+
     pub fn receive_card(&mut self, card: MaskedCard) {
         self.cards.push(card);
         self.opened_cards.push(None);
@@ -172,22 +118,27 @@ impl Player {
         let own_reveal_token = self.compute_reveal_token(rng, parameters, card)?;
         reveal_tokens.push(own_reveal_token);
 
-        let unmasked_card = CardProtocol::unmask(&parameters, reveal_tokens, card)?;
-        let opened_card = card_mappings.get(&unmasked_card);
-        let opened_card = opened_card.ok_or(GameErrors::InvalidCard)?;
+        let unmasked_card =
+            CardProtocol::unmask(&parameters, SESSION_ID, NUM_OF_PLAYERS, reveal_tokens, card)?;
+
+        // Only this player learns the card, so look it up without a secret-dependent map access
+        let (encodings, classic_cards): (Vec<Card>, Vec<ClassicPlayingCard>) =
+            card_mappings.iter().unzip();
+        let j = ct_position(&unmasked_card, &encodings)?;
+        let j = j.ok_or(GameErrors::InvalidCard)?;
 
-        self.opened_cards[i] = Some(*opened_card);
+        self.opened_cards[i] = Some(classic_cards[j]);
         Ok(())
     }
 
-    pub fn compute_reveal_token<R: Rng>(
+    pub fn compute_reveal_token<R: Rng + CryptoRng>(
         &self,
         rng: &mut R,
         pp: &CardParameters,
         card: &MaskedCard,
     ) -> anyhow::Result<(RevealToken, RevealProof, PublicKey)> {
         let (reveal_token, reveal_proof) =
-            CardProtocol::compute_reveal_token(rng, &pp, &self.sk, &self.pk, card)?;
+            CardProtocol::compute_reveal_token(rng, &pp, SESSION_ID, &self.sk, &self.pk, card)?;
 
         Ok((reveal_token, reveal_proof, self.pk))
     }
@@ -200,39 +151,28 @@ pub fn open_card(
     card_mappings: &HashMap<Card, ClassicPlayingCard>,
     card: &MaskedCard,
 ) -> Result<ClassicPlayingCard, anyhow::Error> {
-    let unmasked_card = CardProtocol::unmask(&parameters, reveal_tokens, card)?;
+    let unmasked_card =
+        CardProtocol::unmask(&parameters, SESSION_ID, NUM_OF_PLAYERS, reveal_tokens, card)?;
     let opened_card = card_mappings.get(&unmasked_card);
     let opened_card = opened_card.ok_or(GameErrors::InvalidCard)?;
 
     Ok(*opened_card)
 }
 
-fn encode_cards<R: Rng>(rng: &mut R, num_of_cards: usize) -> HashMap<Card, ClassicPlayingCard> {
-    let mut map: HashMap<Card, ClassicPlayingCard> = HashMap::new();
-    let plaintexts = (0..num_of_cards)
-        .map(|_| Card::rand(rng))
-        .collect::<Vec<_>>();
-
-    let mut i = 0;
-    for value in Value::VALUES.iter().copied() {
-        for suite in Suite::VALUES.iter().copied() {
-            let current_card = ClassicPlayingCard::new(value, suite);
-            map.insert(plaintexts[i], current_card);
-            i += 1;
-        }
-    }
-
-    map
+fn encode_cards() -> anyhow::Result<HashMap<Card, ClassicPlayingCard>> {
+    (0..STANDARD_DECK_SIZE)
+        .filter_map(ClassicPlayingCard::from_standard_position)
+        .map(|card| Ok((card.to_card::<Curve>()?, card)))
+        .collect()
 }
 
 fn main() -> anyhow::Result<()> {
     let m = 2;
     let n = 26;
-    let num_of_cards = m * n;
     let rng = &mut thread_rng();
 
     let parameters = CardProtocol::setup(rng, m, n)?;
-    let card_mapping = encode_cards(rng, num_of_cards);
+    let card_mapping = encode_cards()?;
 
     let mut andrija = Player::new(rng, &parameters, &to_bytes![b"Andrija"].unwrap())?;
     let mut kobi = Player::new(rng, &parameters, &to_bytes![b"Kobi"].unwrap())?;
@@ -240,19 +180,37 @@ fn main() -> anyhow::Result<()> {
     let mut tom = Player::new(rng, &parameters, &to_bytes![b"Tom"].unwrap())?;
 
     let players = vec![andrija.clone(), kobi.clone(), nico.clone(), tom.clone()];
+    assert_eq!(players.len(), NUM_OF_PLAYERS);
+
+    // Keys are published first, then every player proves ownership of theirs for the final list
+    let keys = players.iter().map(|p| p.pk).collect::<Vec<_>>();
+    let key_list_digest = CardProtocol::key_list_digest(&keys)?;
 
     let key_proof_info = players
         .iter()
-        .map(|p| (p.pk, p.proof_key, p.name.clone()))
-        .collect::<Vec<_>>();
+        .enumerate()
+        .map(|(i, p)| {
+            let proof_key = p.prove_key_ownership(rng, &parameters, i, &key_list_digest)?;
+            Ok((p.pk, proof_key, p.name.clone()))
+        })
+        .collect::<anyhow::Result<Vec<_>>>()?;
 
     // Each player should run this computation. Alternatively, it can be ran by a smart contract
-    let joint_pk = CardProtocol::compute_aggregate_key(&parameters, &key_proof_info)?;
+    let joint_pk = CardProtocol::compute_aggregate_key(&parameters, SESSION_ID, &key_proof_info)?;
 
     // Each player should run this computation and verify that all players agree on the initial deck
     let deck_and_proofs: Vec<(MaskedCard, RemaskingProof)> = card_mapping
         .keys()
-        .map(|card| CardProtocol::mask(rng, &parameters, &joint_pk, &card, &Scalar::one()))
+        .map(|card| {
+            CardProtocol::mask(
+                rng,
+                &parameters,
+                SESSION_ID,
+                &joint_pk,
+                &card,
+                &Scalar::one(),
+            )
+        })
         .collect::<Result<Vec<_>, _>>()?;
 
     let deck = deck_and_proofs
@@ -263,11 +221,12 @@ fn main() -> anyhow::Result<()> {
     // SHUFFLE TIME --------------
     // 1.a Andrija shuffles first
     let permutation = Permutation::new(rng, m * n);
-    let masking_factors: Vec<Scalar> = sample_vector(rng, m * n);
+    let masking_factors: Vec<Scalar> = sample_nonzero_vector(rng, m * n)?;
 
     let (a_shuffled_deck, a_shuffle_proof) = CardProtocol::shuffle_and_remask(
         rng,
         &parameters,
+        SESSION_ID,
         &joint_pk,
         &deck,
         &masking_factors,
@@ -277,6 +236,7 @@ fn main() -> anyhow::Result<()> {
     // 1.b everyone checks!
     CardProtocol::verify_shuffle(
         &parameters,
+        SESSION_ID,
         &joint_pk,
         &deck,
         &a_shuffled_deck,
@@ -285,11 +245,12 @@ fn main() -> anyhow::Result<()> {
 
     //2.a Kobi shuffles second
     let permutation = Permutation::new(rng, m * n);
-    let masking_factors: Vec<Scalar> = sample_vector(rng, m * n);
+    let masking_factors: Vec<Scalar> = sample_nonzero_vector(rng, m * n)?;
 
     let (k_shuffled_deck, k_shuffle_proof) = CardProtocol::shuffle_and_remask(
         rng,
         &parameters,
+        SESSION_ID,
         &joint_pk,
         &a_shuffled_deck,
         &masking_factors,
@@ -299,6 +260,7 @@ fn main() -> anyhow::Result<()> {
     //2.b Everyone checks
     CardProtocol::verify_shuffle(
         &parameters,
+        SESSION_ID,
         &joint_pk,
         &a_shuffled_deck,
         &k_shuffled_deck,
@@ -307,11 +269,12 @@ fn main() -> anyhow::Result<()> {
 
     //3.a Nico shuffles third
     let permutation = Permutation::new(rng, m * n);
-    let masking_factors: Vec<Scalar> = sample_vector(rng, m * n);
+    let masking_factors: Vec<Scalar> = sample_nonzero_vector(rng, m * n)?;
 
     let (n_shuffled_deck, n_shuffle_proof) = CardProtocol::shuffle_and_remask(
         rng,
         &parameters,
+        SESSION_ID,
         &joint_pk,
         &k_shuffled_deck,
         &masking_factors,
@@ -321,6 +284,7 @@ fn main() -> anyhow::Result<()> {
     //3.b Everyone checks
     CardProtocol::verify_shuffle(
         &parameters,
+        SESSION_ID,
         &joint_pk,
         &k_shuffled_deck,
         &n_shuffled_deck,
@@ -329,11 +293,12 @@ fn main() -> anyhow::Result<()> {
 
     //4.a Tom shuffles last
     let permutation = Permutation::new(rng, m * n);
-    let masking_factors: Vec<Scalar> = sample_vector(rng, m * n);
+    let masking_factors: Vec<Scalar> = sample_nonzero_vector(rng, m * n)?;
 
     let (final_shuffled_deck, final_shuffle_proof) = CardProtocol::shuffle_and_remask(
         rng,
         &parameters,
+        SESSION_ID,
         &joint_pk,
         &n_shuffled_deck,
         &masking_factors,
@@ -343,6 +308,7 @@ fn main() -> anyhow::Result<()> {
     //4.b Everyone checks before accepting last deck for game
     CardProtocol::verify_shuffle(
         &parameters,
+        SESSION_ID,
         &joint_pk,
         &n_shuffled_deck,
         &final_shuffled_deck,
@@ -0,0 +1,40 @@
+//! Writes the messages of a complete game over the Starknet curve, serialized in order, for the
+//! replay tests of `discrete_log_cards::transcript`. The game is the one `seeded_game` plays from a
+//! fixed seed, so running the example again reproduces the same file.
+//!
+//! Run the example with `cargo run --example transcript_fixture --features test-helpers`.
+
+use barnett_smart_card_protocol::discrete_log_cards::session::GameMessage;
+use barnett_smart_card_protocol::discrete_log_cards::test_helpers::seeded_game;
+
+use ark_serialize::CanonicalSerialize;
+
+// Choose elliptic curve setting
+type Curve = starknet_curve::Projective;
+
+const FIXTURE_PATH: &str = "tests/vectors/starknet_transcript.bin";
+const FIXTURE_SEED: u64 = 7;
+const CONTEXT: &[u8] = b"transcript test";
+const NUMBER_OF_PLAYERS: usize = 3;
+
+fn main() -> anyhow::Result<()> {
+    let game = seeded_game::<Curve>(FIXTURE_SEED, NUMBER_OF_PLAYERS, CONTEXT)?;
+    let transcript = game
+        .messages
+        .into_iter()
+        .map(|(_, message)| message)
+        .collect::<Vec<GameMessage<Curve>>>();
+
+    let mut bytes = Vec::new();
+    transcript.serialize(&mut bytes)?;
+
+    std::fs::write(FIXTURE_PATH, &bytes)?;
+    println!(
+        "Wrote a transcript of {} messages ({} bytes) to {}",
+        transcript.len(),
+        bytes.len(),
+        FIXTURE_PATH
+    );
+
+    Ok(())
+}
@@ -0,0 +1,22 @@
+//! Decode a deck as it arrives from a peer, on its own and inside a shuffle message.
+
+#![no_main]
+
+use barnett_smart_card_protocol::discrete_log_cards::session::GameMessage;
+use barnett_smart_card_protocol::discrete_log_cards::validation::{
+    bounded_from_bytes, vec_from_bytes, DecodingLimits,
+};
+use barnett_smart_card_protocol::discrete_log_cards::MaskedCard;
+use libfuzzer_sys::fuzz_target;
+
+type Curve = starknet_curve::Projective;
+
+fuzz_target!(|data: &[u8]| {
+    let limits = DecodingLimits::default();
+    let _ = vec_from_bytes::<MaskedCard<Curve>>(data, &limits);
+
+    // The same bytes behind the tag of a shuffle message, so that the deck is read by `GameMessage`
+    let mut message = vec![2u8];
+    message.extend_from_slice(data);
+    let _ = bounded_from_bytes::<GameMessage<Curve>>(&message, &limits);
+});
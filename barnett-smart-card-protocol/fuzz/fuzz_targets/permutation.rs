@@ -0,0 +1,34 @@
+//! Build a permutation from an arbitrary mapping, as one decoded from a peer would be, and apply
+//! it once it has been checked. The first byte is the size of the deck and the others the mapping.
+
+#![no_main]
+
+use barnett_smart_card_protocol::crypto_primitives::matrix::{ApplyToMatrix, Matrix};
+use barnett_smart_card_protocol::crypto_primitives::permutation::check_permutation;
+use libfuzzer_sys::fuzz_target;
+use proof_essentials::utils::permutation::Permutation;
+
+fuzz_target!(|data: &[u8]| {
+    let (size, mapping) = match data.split_first() {
+        Some((&size, mapping)) => (size as usize, mapping),
+        None => return,
+    };
+    let mapping = mapping
+        .iter()
+        .map(|&position| position as usize)
+        .collect::<Vec<_>>();
+    let permutation = Permutation::from(&mapping);
+
+    let deck = Matrix::from_fn(1, size, |_, j| j);
+    let permuted = permutation.apply_to_matrix(&deck);
+    match check_permutation(&permutation, size) {
+        Ok(()) => {
+            let permuted = permuted.expect("a checked permutation applies");
+            let mut positions = permuted.rows().concat();
+            positions.sort_unstable();
+            assert!(positions.into_iter().eq(0..size));
+            assert_eq!(permutation.permute_array(&mapping).len(), size);
+        }
+        Err(_) => assert!(permuted.is_err()),
+    }
+});
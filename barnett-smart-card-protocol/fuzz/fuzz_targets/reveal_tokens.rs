@@ -0,0 +1,24 @@
+//! Decode a bundle of reveal tokens as it arrives from a peer, on its own and inside a message.
+
+#![no_main]
+
+use barnett_smart_card_protocol::discrete_log_cards::reveal::RevealProof;
+use barnett_smart_card_protocol::discrete_log_cards::session::GameMessage;
+use barnett_smart_card_protocol::discrete_log_cards::validation::{
+    bounded_from_bytes, vec_from_bytes, DecodingLimits,
+};
+use barnett_smart_card_protocol::discrete_log_cards::{PublicKey, RevealToken};
+use libfuzzer_sys::fuzz_target;
+
+type Curve = starknet_curve::Projective;
+
+fuzz_target!(|data: &[u8]| {
+    let limits = DecodingLimits::default();
+    let _ =
+        vec_from_bytes::<(RevealToken<Curve>, RevealProof<Curve>, PublicKey<Curve>)>(data, &limits);
+
+    // The same bytes behind the tag of a reveal message, as they are read by `GameMessage`
+    let mut message = vec![3u8];
+    message.extend_from_slice(data);
+    let _ = bounded_from_bytes::<GameMessage<Curve>>(&message, &limits);
+});
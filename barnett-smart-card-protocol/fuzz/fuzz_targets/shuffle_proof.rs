@@ -0,0 +1,74 @@
+//! Decode a shuffle proof as it arrives from a peer and verify it against a fixed shuffle, so that
+//! verification runs on every proof that decodes. The statement is derived from a fixed seed and
+//! built once, which keeps every run reproducible.
+
+#![no_main]
+
+use barnett_smart_card_protocol::discrete_log_cards::encoding::encode_standard_deck;
+use barnett_smart_card_protocol::discrete_log_cards::validation::{
+    bounded_from_bytes, DecodingLimits,
+};
+use barnett_smart_card_protocol::discrete_log_cards::{
+    starting_deck, DLCards, MaskedCard, Parameters, PublicKey,
+};
+use barnett_smart_card_protocol::BarnettSmartProtocol;
+use libfuzzer_sys::fuzz_target;
+use rand_chacha::rand_core::SeedableRng;
+use rand_chacha::ChaCha20Rng;
+use std::sync::OnceLock;
+
+type Curve = starknet_curve::Projective;
+type CardProtocol<'a> = DLCards<'a, Curve>;
+type ShuffleProof<'a> = <CardProtocol<'a> as BarnettSmartProtocol>::ZKProofShuffle;
+
+const CONTEXT: &[u8] = b"fuzz";
+
+struct Statement {
+    parameters: Parameters<Curve>,
+    shared_key: PublicKey<Curve>,
+    deck: Vec<MaskedCard<Curve>>,
+    shuffled_deck: Vec<MaskedCard<Curve>>,
+}
+
+fn statement() -> &'static Statement {
+    static STATEMENT: OnceLock<Statement> = OnceLock::new();
+    STATEMENT.get_or_init(|| {
+        let rng = &mut ChaCha20Rng::from_seed([0; 32]);
+        let parameters = CardProtocol::setup(rng, 2, 2).expect("setup");
+        // A table of one player, whose key is the shared key
+        let (shared_key, _) = CardProtocol::player_keygen(rng, &parameters).expect("keygen");
+        let cards = encode_standard_deck(1).expect("standard deck");
+        let deck = starting_deck(&parameters, &shared_key, &cards[..parameters.num_cards()])
+            .expect("starting deck");
+        let (shuffled_deck, _) = CardProtocol::shuffle_and_remask_with_rng(
+            rng,
+            &parameters,
+            CONTEXT,
+            &shared_key,
+            &deck,
+        )
+        .expect("shuffle");
+
+        Statement {
+            parameters,
+            shared_key,
+            deck,
+            shuffled_deck,
+        }
+    })
+}
+
+fuzz_target!(|data: &[u8]| {
+    let statement = statement();
+    let limits = DecodingLimits::for_parameters(&statement.parameters);
+    if let Ok(proof) = bounded_from_bytes::<ShuffleProof>(data, &limits) {
+        let _ = CardProtocol::verify_shuffle(
+            &statement.parameters,
+            CONTEXT,
+            &statement.shared_key,
+            &statement.deck,
+            &statement.shuffled_deck,
+            &proof,
+        );
+    }
+});
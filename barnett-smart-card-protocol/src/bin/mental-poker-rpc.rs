@@ -0,0 +1,23 @@
+//! JSON-RPC daemon acting for one player, see `barnett_smart_card_protocol::rpc` for its methods.
+//!
+//! Run it with `cargo run --features rpc --bin mental-poker-rpc -- [address]`. It listens on
+//! 127.0.0.1:3030 unless given another address.
+
+use barnett_smart_card_protocol::rpc;
+
+use std::net::SocketAddr;
+
+const DEFAULT_ADDRESS: &str = "127.0.0.1:3030";
+
+fn main() -> anyhow::Result<()> {
+    let address: SocketAddr = std::env::args()
+        .nth(1)
+        .unwrap_or_else(|| DEFAULT_ADDRESS.to_string())
+        .parse()?;
+
+    let server = rpc::start(&address)?;
+    println!("Listening on {}", server.address());
+    server.wait();
+
+    Ok(())
+}
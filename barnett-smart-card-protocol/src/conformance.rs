@@ -0,0 +1,593 @@
+//! Test vectors for checking that another implementation of the protocol interoperates with this
+//! one, built with the `conformance` feature.
+//!
+//! Vectors are JSON documents. Every value is the lowercase hex of its canonical encoding, the
+//! encoding this crate uses everywhere, and contexts and player information are hex of raw bytes.
+//! A document is
+//!
+//! ```json
+//! {
+//!   "version": 1,
+//!   "curve": "starknet",
+//!   "cases": [
+//!     {
+//!       "name": "three players, standard deck",
+//!       "context": "...",
+//!       "parameters": "...",
+//!       "parameters_digest": "...",
+//!       "players": [{"public_key": "...", "player_info": "...", "ownership_proof": "..."}],
+//!       "key_list_digest": "...",
+//!       "shared_key": "...",
+//!       "deck": ["..."],
+//!       "masked_deck": ["..."],
+//!       "shuffles": [{"player": 0, "output": ["..."], "proof": "..."}],
+//!       "reveals": [{"position": 0, "tokens": [{"player": 0, "token": "...", "proof": "..."}],
+//!                    "card": "..."}]
+//!     }
+//!   ]
+//! }
+//! ```
+//!
+//! where, in each case:
+//!
+//! - `parameters_digest` is the Blake2s digest of the encoding of `parameters`;
+//! - `players[i]` proves ownership of its key for seat `i`, against `key_list_digest`, and
+//!   `shared_key` is the aggregate of their keys;
+//! - `masked_deck` is `deck` masked under `shared_key` with a masking factor of one;
+//! - the players shuffle in the order of `shuffles`, starting from `masked_deck`;
+//! - each reveal opens the card at `position` in the output of the last shuffle, with a token from
+//!   every player, to `card`.
+//!
+//! [`generate`] produces a case from this crate, and [`check`] verifies every case of a document,
+//! whichever implementation produced it. A failure names the case and the field that does not
+//! check out.
+//!
+//! `tests/vectors/conformance.json` holds the [`known_answer_vectors`], which are generated from
+//! a fixed seed and written by the `conformance_vectors` example. A test generates them again
+//! and compares the bytes with the file, so that a change to the transcripts of the proofs or to
+//! an encoding cannot go unnoticed. Such a change breaks verifiers that are already deployed. If
+//! it is intended, bump [`VECTORS_VERSION`], write the vectors again with the example and commit
+//! the file with the change.
+
+use crate::discrete_log_cards::dkg::KeyOwnershipProof;
+use crate::discrete_log_cards::encoding::encode_standard_deck;
+use crate::discrete_log_cards::session::{RevealProof, ShuffleProof};
+use crate::discrete_log_cards::validation::{
+    bounded_from_bytes, from_bytes, public_key_from_bytes, to_bytes, DecodingLimits, Validate,
+};
+use crate::discrete_log_cards::{self, Card, DLCards, MaskedCard, Parameters, PublicKey};
+use crate::error::{CardProtocolError, InputError, InternalError, VerificationError};
+use crate::BarnettSmartProtocol;
+
+use ark_serialize::{CanonicalDeserialize, CanonicalSerialize};
+use blake2::{Blake2s, Digest};
+use rand::{CryptoRng, Rng, SeedableRng};
+use rand_chacha::ChaCha20Rng;
+use serde::{Deserialize, Serialize};
+
+type Curve = starknet_curve::Projective;
+type CardProtocol<'a> = DLCards<'a, Curve>;
+
+/// Version of the format described in the module documentation.
+pub const VECTORS_VERSION: u32 = 1;
+
+/// Curve the vectors of this crate are over.
+pub const VECTORS_CURVE: &str = "starknet";
+
+/// Seed of the [`known_answer_vectors`].
+pub const KNOWN_ANSWER_SEED: [u8; 32] = *b"conformance vectors v1..........";
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Vectors {
+    pub version: u32,
+    pub curve: String,
+    pub cases: Vec<Case>,
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Case {
+    pub name: String,
+    pub context: String,
+    pub parameters: String,
+    pub parameters_digest: String,
+    pub players: Vec<PlayerVector>,
+    pub key_list_digest: String,
+    pub shared_key: String,
+    pub deck: Vec<String>,
+    pub masked_deck: Vec<String>,
+    pub shuffles: Vec<ShuffleVector>,
+    pub reveals: Vec<RevealVector>,
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct PlayerVector {
+    pub public_key: String,
+    pub player_info: String,
+    pub ownership_proof: String,
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct ShuffleVector {
+    pub player: usize,
+    pub output: Vec<String>,
+    pub proof: String,
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct RevealVector {
+    pub position: usize,
+    pub tokens: Vec<TokenVector>,
+    pub card: String,
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct TokenVector {
+    pub player: usize,
+    pub token: String,
+    pub proof: String,
+}
+
+impl Vectors {
+    /// Vectors of this crate's curve, made of `cases`.
+    pub fn new(cases: Vec<Case>) -> Self {
+        Self {
+            version: VECTORS_VERSION,
+            curve: VECTORS_CURVE.to_string(),
+            cases,
+        }
+    }
+
+    pub fn from_json(json: &str) -> Result<Self, CardProtocolError> {
+        serde_json::from_str(json).map_err(|error| {
+            CardProtocolError::InvalidInput(InputError::SerializationError(error.to_string()))
+        })
+    }
+
+    pub fn to_json(&self) -> Result<String, CardProtocolError> {
+        serde_json::to_string_pretty(self).map_err(|error| {
+            CardProtocolError::Internal(InternalError::SerializationError(error.to_string()))
+        })
+    }
+}
+
+/// Play the case `name` over `pp`: `player_info.len()` players exchange keys, mask `cards`,
+/// shuffle once each in seat order and open the cards at `positions`.
+pub fn generate<R: Rng + CryptoRng>(
+    rng: &mut R,
+    name: &str,
+    pp: &Parameters<Curve>,
+    context: &[u8],
+    player_info: &[Vec<u8>],
+    cards: &[Card<Curve>],
+    positions: &[usize],
+) -> Result<Case, CardProtocolError> {
+    let keys = player_info
+        .iter()
+        .map(|_| CardProtocol::player_keygen(rng, pp))
+        .collect::<Result<Vec<_>, _>>()?;
+    let public_keys = keys.iter().map(|(pk, _)| *pk).collect::<Vec<_>>();
+    let key_list_digest = CardProtocol::key_list_digest(&public_keys)?;
+
+    let mut players = Vec::new();
+    let mut key_proof_info = Vec::new();
+    for (index, ((pk, sk), info)) in keys.iter().zip(player_info).enumerate() {
+        let proof = CardProtocol::prove_key_ownership(
+            rng,
+            pp,
+            context,
+            pk,
+            sk,
+            info,
+            index,
+            &key_list_digest,
+        )?;
+        players.push(PlayerVector {
+            public_key: encode(pk)?,
+            player_info: hex::encode(info),
+            ownership_proof: encode(&proof)?,
+        });
+        key_proof_info.push((*pk, proof, info.clone()));
+    }
+    let shared_key = CardProtocol::compute_aggregate_key(pp, context, &key_proof_info)?;
+
+    let masked_deck = discrete_log_cards::starting_deck(pp, &shared_key, cards)?;
+    let mut deck = masked_deck.clone();
+    let mut shuffles = Vec::new();
+    for player in 0..keys.len() {
+        let (output, proof) =
+            CardProtocol::shuffle_and_remask_with_rng(rng, pp, context, &shared_key, &deck)?;
+        shuffles.push(ShuffleVector {
+            player,
+            output: encode_all(&output)?,
+            proof: encode(&proof)?,
+        });
+        deck = output;
+    }
+
+    let mut reveals = Vec::new();
+    for &position in positions {
+        let masked_card =
+            deck.get(position)
+                .ok_or(CardProtocolError::InvalidInput(InputError::InvalidDeal(
+                    deck.len(),
+                )))?;
+
+        let mut tokens = Vec::new();
+        let mut decryption_key = Vec::new();
+        for (player, (pk, sk)) in keys.iter().enumerate() {
+            let (token, proof) =
+                CardProtocol::compute_reveal_token(rng, pp, context, sk, pk, masked_card)?;
+            tokens.push(TokenVector {
+                player,
+                token: encode(&token)?,
+                proof: encode(&proof)?,
+            });
+            decryption_key.push((token, proof, *pk));
+        }
+        let card = CardProtocol::unmask(pp, context, keys.len(), &decryption_key, masked_card)
+            .map_err(|error| error.at_card(position))?;
+
+        reveals.push(RevealVector {
+            position,
+            tokens,
+            card: encode(&card)?,
+        });
+    }
+
+    Ok(Case {
+        name: name.to_string(),
+        context: hex::encode(context),
+        parameters: encode(pp)?,
+        parameters_digest: parameters_digest(pp)?,
+        players,
+        key_list_digest: hex::encode(key_list_digest),
+        shared_key: encode(&shared_key)?,
+        deck: encode_all(cards)?,
+        masked_deck: encode_all(&masked_deck)?,
+        shuffles,
+        reveals,
+    })
+}
+
+/// The vectors under `tests/vectors/conformance.json`: two players over a small deck, and three
+/// over the standard deck, generated from [`KNOWN_ANSWER_SEED`]. The randomness of every key,
+/// proof and shuffle is drawn from that seed, so the same version of the crate always produces
+/// the same vectors, byte for byte. The draws depend on the `side-channel-hardening` feature, and
+/// the checked-in vectors are those of the default features.
+pub fn known_answer_vectors() -> Result<Vectors, CardProtocolError> {
+    let rng = &mut ChaCha20Rng::from_seed(KNOWN_ANSWER_SEED);
+    let deck = encode_standard_deck::<Curve>(1)?;
+
+    let small = CardProtocol::setup(rng, 2, 4)?;
+    let standard = CardProtocol::setup(rng, 4, 13)?;
+    let cases = vec![
+        generate(
+            rng,
+            "two players, eight cards",
+            &small,
+            b"conformance: two players",
+            &[b"alice".to_vec(), b"bob".to_vec()],
+            &deck[..8],
+            &[0, 7],
+        )?,
+        generate(
+            rng,
+            "three players, standard deck",
+            &standard,
+            b"conformance: three players",
+            &[b"alice".to_vec(), b"bob".to_vec(), b"carol".to_vec()],
+            &deck,
+            &[0, 1, 2, 3, 4, 5, 51],
+        )?,
+    ];
+
+    Ok(Vectors::new(cases))
+}
+
+/// Verify every case of `vectors`. The first failure is reported as a
+/// [`CardProtocolError::ConformanceMismatch`] naming the case and the field that does not check
+/// out.
+pub fn check(vectors: &Vectors) -> Result<(), CardProtocolError> {
+    if vectors.version != VECTORS_VERSION {
+        return Err(mismatch("", "version"));
+    }
+    if vectors.curve != VECTORS_CURVE {
+        return Err(mismatch("", "curve"));
+    }
+
+    vectors.cases.iter().try_for_each(check_case)
+}
+
+fn check_case(case: &Case) -> Result<(), CardProtocolError> {
+    let fail = |field: &str| mismatch(&case.name, field);
+    let context = hex::decode(&case.context).map_err(|_| fail("context"))?;
+
+    let pp = bounded_from_bytes::<Parameters<Curve>>(
+        &decode_hex(&case.parameters).map_err(|_| fail("parameters"))?,
+        &DecodingLimits::default(),
+    )
+    .map_err(|_| fail("parameters"))?;
+    CardProtocol::verify_parameters(&pp).map_err(|_| fail("parameters"))?;
+    if parameters_digest(&pp)? != case.parameters_digest {
+        return Err(fail("parameters_digest"));
+    }
+    let limits = DecodingLimits::for_parameters(&pp);
+
+    // Keys
+    let public_keys = case
+        .players
+        .iter()
+        .enumerate()
+        .map(|(index, player)| {
+            decode_public_key(&player.public_key)
+                .map_err(|_| fail(&format!("players[{}].public_key", index)))
+        })
+        .collect::<Result<Vec<_>, _>>()?;
+    let key_list_digest = CardProtocol::key_list_digest(&public_keys)?;
+    if hex::encode(key_list_digest) != case.key_list_digest {
+        return Err(fail("key_list_digest"));
+    }
+
+    let mut key_proof_info = Vec::new();
+    for (index, (player, pk)) in case.players.iter().zip(&public_keys).enumerate() {
+        let field = format!("players[{}].ownership_proof", index);
+        let info = hex::decode(&player.player_info)
+            .map_err(|_| fail(&format!("players[{}].player_info", index)))?;
+        let proof = decode_proof::<KeyOwnershipProof<Curve>>(&player.ownership_proof, &limits)
+            .map_err(|_| fail(&field))?;
+        CardProtocol::verify_key_ownership(
+            &pp,
+            &context,
+            pk,
+            &info,
+            index,
+            &key_list_digest,
+            &proof,
+        )
+        .map_err(|_| fail(&field))?;
+        key_proof_info.push((*pk, proof, info));
+    }
+    let shared_key = CardProtocol::compute_aggregate_key(&pp, &context, &key_proof_info)
+        .map_err(|_| fail("players"))?;
+    if encode(&shared_key)? != case.shared_key {
+        return Err(fail("shared_key"));
+    }
+
+    // Deck and shuffles
+    let cards = decode_all::<Card<Curve>>(&case.deck, &limits).map_err(|_| fail("deck"))?;
+    let masked_deck =
+        discrete_log_cards::starting_deck(&pp, &shared_key, &cards).map_err(|_| fail("deck"))?;
+    if encode_all(&masked_deck)? != case.masked_deck {
+        return Err(fail("masked_deck"));
+    }
+
+    let mut deck = masked_deck;
+    for (index, shuffle) in case.shuffles.iter().enumerate() {
+        let output = decode_all::<MaskedCard<Curve>>(&shuffle.output, &limits)
+            .map_err(|_| fail(&format!("shuffles[{}].output", index)))?;
+        let field = format!("shuffles[{}].proof", index);
+        let proof = decode_proof::<ShuffleProof<Curve>>(&shuffle.proof, &limits)
+            .map_err(|_| fail(&field))?;
+        CardProtocol::verify_shuffle(&pp, &context, &shared_key, &deck, &output, &proof)
+            .map_err(|_| fail(&field))?;
+        deck = output;
+    }
+
+    // Reveals
+    for (index, reveal) in case.reveals.iter().enumerate() {
+        let masked_card = deck
+            .get(reveal.position)
+            .ok_or_else(|| fail(&format!("reveals[{}].position", index)))?;
+
+        let mut decryption_key = Vec::new();
+        for (token_index, token) in reveal.tokens.iter().enumerate() {
+            let field = format!("reveals[{}].tokens[{}]", index, token_index);
+            let pk = public_keys.get(token.player).ok_or_else(|| fail(&field))?;
+            let revealed = decode(&token.token).map_err(|_| fail(&field))?;
+            let proof = decode_proof::<RevealProof<Curve>>(&token.proof, &limits)
+                .map_err(|_| fail(&field))?;
+            CardProtocol::verify_reveal(&pp, &context, pk, &revealed, masked_card, &proof)
+                .map_err(|_| fail(&field))?;
+            decryption_key.push((revealed, proof, *pk));
+        }
+
+        let card = CardProtocol::unmask(
+            &pp,
+            &context,
+            public_keys.len(),
+            &decryption_key,
+            masked_card,
+        )
+        .map_err(|_| fail(&format!("reveals[{}].tokens", index)))?;
+        if encode(&card)? != reveal.card {
+            return Err(fail(&format!("reveals[{}].card", index)));
+        }
+    }
+
+    Ok(())
+}
+
+/// Blake2s digest of the canonical encoding of `pp`, as hex.
+pub fn parameters_digest(pp: &Parameters<Curve>) -> Result<String, CardProtocolError> {
+    Ok(hex::encode(Blake2s::digest(&to_bytes(pp)?)))
+}
+
+fn mismatch(case: &str, field: &str) -> CardProtocolError {
+    CardProtocolError::VerificationFailed(VerificationError::ConformanceMismatch {
+        case: case.to_string(),
+        field: field.to_string(),
+    })
+}
+
+fn decode_hex(value: &str) -> Result<Vec<u8>, CardProtocolError> {
+    hex::decode(value).map_err(|error| {
+        CardProtocolError::InvalidInput(InputError::SerializationError(error.to_string()))
+    })
+}
+
+fn decode<T: CanonicalDeserialize + Validate>(value: &str) -> Result<T, CardProtocolError> {
+    from_bytes(&decode_hex(value)?)
+}
+
+fn decode_public_key(value: &str) -> Result<PublicKey<Curve>, CardProtocolError> {
+    public_key_from_bytes(&decode_hex(value)?)
+}
+
+fn decode_proof<T: CanonicalDeserialize>(
+    value: &str,
+    limits: &DecodingLimits,
+) -> Result<T, CardProtocolError> {
+    bounded_from_bytes(&decode_hex(value)?, limits)
+}
+
+fn decode_all<T: CanonicalDeserialize + Validate>(
+    values: &[String],
+    limits: &DecodingLimits,
+) -> Result<Vec<T>, CardProtocolError> {
+    if values.len() > limits.max_vector_length() {
+        return Err(InputError::MessageTooLarge(limits.max_vector_length()).into());
+    }
+
+    values.iter().map(|value| decode(value)).collect()
+}
+
+fn encode<T: CanonicalSerialize>(value: &T) -> Result<String, CardProtocolError> {
+    Ok(hex::encode(to_bytes(value)?))
+}
+
+fn encode_all<T: CanonicalSerialize>(values: &[T]) -> Result<Vec<String>, CardProtocolError> {
+    values.iter().map(encode).collect()
+}
+
+#[cfg(test)]
+mod test {
+    use super::{check, generate, known_answer_vectors, Vectors};
+    use crate::crypto_primitives::utils::rand::test_rng;
+    use crate::discrete_log_cards;
+    use crate::discrete_log_cards::encoding::encode_standard_deck;
+    use crate::error::{CardProtocolError, VerificationError};
+    use crate::BarnettSmartProtocol;
+
+    // Choose elliptic curve setting
+    type Curve = starknet_curve::Projective;
+
+    // Instantiate concrete type for our card protocol
+    type CardProtocol<'a> = discrete_log_cards::DLCards<'a, Curve>;
+
+    const SESSION_ID: &[u8] = b"conformance test";
+
+    const CONFORMANCE_VECTORS: &str = concat!(
+        env!("CARGO_MANIFEST_DIR"),
+        "/tests/vectors/conformance.json"
+    );
+
+    fn mismatch(case: &str, field: &str) -> Result<(), CardProtocolError> {
+        Err(CardProtocolError::VerificationFailed(
+            VerificationError::ConformanceMismatch {
+                case: case.to_string(),
+                field: field.to_string(),
+            },
+        ))
+    }
+
+    #[test]
+    fn test_generated_vectors() {
+        let rng = &mut test_rng();
+        let m = 2;
+        let n = 4;
+        let num_of_cards = m * n;
+
+        let parameters = CardProtocol::setup(rng, m, n).unwrap();
+        let info = vec![b"alice".to_vec(), b"bob".to_vec()];
+        let cards = encode_standard_deck(1).unwrap()[..num_of_cards].to_vec();
+        let case = generate(
+            rng,
+            "two players",
+            &parameters,
+            SESSION_ID,
+            &info,
+            &cards,
+            &[0, 5],
+        )
+        .unwrap();
+
+        // Vectors survive their JSON encoding
+        let vectors = Vectors::new(vec![case]);
+        let vectors = Vectors::from_json(&vectors.to_json().unwrap()).unwrap();
+        assert_eq!(Ok(()), check(&vectors));
+
+        // Changing any part of a case is caught, and named
+        let mut tampered = vectors.clone();
+        tampered.cases[0].parameters_digest = "00".repeat(32);
+        assert_eq!(
+            mismatch("two players", "parameters_digest"),
+            check(&tampered)
+        );
+
+        let mut tampered = vectors.clone();
+        tampered.cases[0].players.swap(0, 1);
+        assert_eq!(mismatch("two players", "key_list_digest"), check(&tampered));
+
+        let mut tampered = vectors.clone();
+        tampered.cases[0].deck.swap(0, 1);
+        assert_eq!(mismatch("two players", "masked_deck"), check(&tampered));
+
+        let mut tampered = vectors.clone();
+        tampered.cases[0].shuffles[1].output.swap(0, 1);
+        assert_eq!(
+            mismatch("two players", "shuffles[1].proof"),
+            check(&tampered)
+        );
+
+        let mut tampered = vectors.clone();
+        let token = tampered.cases[0].reveals[1].tokens[0].token.clone();
+        tampered.cases[0].reveals[0].tokens[0].token = token;
+        assert_eq!(
+            mismatch("two players", "reveals[0].tokens[0]"),
+            check(&tampered)
+        );
+
+        let mut tampered = vectors.clone();
+        tampered.cases[0].reveals[0].card = tampered.cases[0].reveals[1].card.clone();
+        assert_eq!(mismatch("two players", "reveals[0].card"), check(&tampered));
+
+        let mut tampered = vectors;
+        tampered.version += 1;
+        assert_eq!(mismatch("", "version"), check(&tampered));
+    }
+
+    #[test]
+    fn test_checked_in_vectors() {
+        // Written by `cargo run --example conformance_vectors --features conformance`
+        let json = std::fs::read_to_string(CONFORMANCE_VECTORS).unwrap();
+        let vectors = Vectors::from_json(&json).unwrap();
+
+        assert!(!vectors.cases.is_empty());
+        assert_eq!(Ok(()), check(&vectors));
+    }
+
+    #[cfg(feature = "side-channel-hardening")]
+    #[test]
+    fn test_known_answer_vectors() {
+        // A difference means that the bytes this crate produces changed, see the module
+        // documentation before writing the vectors again
+        let expected = std::fs::read_to_string(CONFORMANCE_VECTORS).unwrap();
+        let generated = known_answer_vectors().unwrap().to_json().unwrap() + "\n";
+
+        let mismatch = expected
+            .lines()
+            .zip(generated.lines())
+            .position(|(expected, generated)| expected != generated);
+        assert_eq!(
+            mismatch,
+            None,
+            "vectors differ from line {:?}",
+            mismatch.map(|i| i + 1)
+        );
+        assert_eq!(expected.lines().count(), generated.lines().count());
+        assert!(
+            expected == generated,
+            "vectors differ in their line endings"
+        );
+    }
+}
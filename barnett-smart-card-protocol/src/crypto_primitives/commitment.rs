@@ -0,0 +1,268 @@
+use crate::crypto_primitives::hash_to_curve::hash_to_curve;
+use crate::error::{CardProtocolError, InputError};
+
+use ark_ec::msm::VariableBaseMSM;
+use ark_ec::ProjectiveCurve;
+use ark_ff::{Field, PrimeField};
+use ark_std::fmt;
+use ark_std::vec::Vec;
+use proof_essentials::error::CryptoError;
+use proof_essentials::vector_commitment::pedersen::{self, PedersenCommitment};
+use proof_essentials::vector_commitment::HomomorphicCommitmentScheme;
+
+const PEDERSEN_GENERATOR_DOMAIN: &'static [u8] = b"Pedersen Generator";
+const PEDERSEN_BLINDING_DOMAIN: &'static [u8] = b"Pedersen Blinding Generator";
+
+/// A hiding and binding vector commitment scheme whose commitments can be combined homomorphically.
+/// `DLCards` is generic over this trait so that the commitment used by the shuffle argument can be
+/// swapped without touching the protocol.
+pub trait HomomorphicCommitment<Scalar: Field>: HomomorphicCommitmentScheme<Scalar> {
+    /// Commitment to the sum of the committed vectors, under the sum of the randomness.
+    fn add(a: &Self::Commitment, b: &Self::Commitment) -> Self::Commitment;
+
+    /// Commitment to the committed vector scaled by `x`, under the randomness scaled by `x`.
+    fn scalar_mul(a: &Self::Commitment, x: &Scalar) -> Self::Commitment;
+
+    /// Check that `(x, r)` opens `commitment`.
+    fn verify_opening(
+        commit_key: &Self::CommitKey,
+        commitment: &Self::Commitment,
+        x: &Vec<Scalar>,
+        r: Scalar,
+    ) -> Result<bool, CryptoError>;
+
+    /// Deterministically derive a commitment key for vectors of length `len` from a public label.
+    /// Unlike `setup`, nobody learns a trapdoor of the derived key, so it can be trusted without
+    /// trusting whoever produced it.
+    fn derive_commit_key(label: &[u8], len: usize) -> Result<Self::CommitKey, CardProtocolError>;
+}
+
+impl<C: ProjectiveCurve> HomomorphicCommitment<C::ScalarField> for PedersenCommitment<C> {
+    fn add(a: &Self::Commitment, b: &Self::Commitment) -> Self::Commitment {
+        *a + *b
+    }
+
+    fn scalar_mul(a: &Self::Commitment, x: &C::ScalarField) -> Self::Commitment {
+        *a * *x
+    }
+
+    fn verify_opening(
+        commit_key: &Self::CommitKey,
+        commitment: &Self::Commitment,
+        x: &Vec<C::ScalarField>,
+        r: C::ScalarField,
+    ) -> Result<bool, CryptoError> {
+        Ok(Self::commit(commit_key, x, r)? == *commitment)
+    }
+
+    /// Every generator is hashed to the curve independently, so no discrete log relation between
+    /// them is known to anyone.
+    fn derive_commit_key(label: &[u8], len: usize) -> Result<Self::CommitKey, CardProtocolError> {
+        let prefixed_label = [&(label.len() as u64).to_le_bytes()[..], label].concat();
+
+        let g = (0..len as u64)
+            .map(|i| {
+                let msg = [&prefixed_label[..], &i.to_le_bytes()[..]].concat();
+                hash_to_curve::<C>(PEDERSEN_GENERATOR_DOMAIN, &msg)
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+        let h = hash_to_curve::<C>(PEDERSEN_BLINDING_DOMAIN, &prefixed_label)?;
+
+        Ok(pedersen::CommitKey::new(g, h))
+    }
+}
+
+/// Incremental Pedersen commitment. Chunks of the committed vector are fed in order and folded into
+/// a running point, so only one chunk needs to be in memory at a time. The result is identical to
+/// committing to the concatenation of all chunks at once.
+pub struct Committer<'a, C: ProjectiveCurve> {
+    commit_key: &'a pedersen::CommitKey<C>,
+    position: usize,
+    acc: C,
+}
+
+impl<C: ProjectiveCurve> fmt::Debug for Committer<'_, C> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Committer")
+            .field("position", &self.position)
+            .finish_non_exhaustive()
+    }
+}
+
+impl<'a, C: ProjectiveCurve> Committer<'a, C> {
+    pub fn new(commit_key: &'a pedersen::CommitKey<C>) -> Self {
+        Self {
+            commit_key,
+            position: 0,
+            acc: C::zero(),
+        }
+    }
+
+    /// Number of elements committed so far.
+    pub fn len(&self) -> usize {
+        self.position
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.position == 0
+    }
+
+    pub fn feed(&mut self, chunk: &[C::ScalarField]) -> Result<(), CardProtocolError> {
+        let end = self.position + chunk.len();
+        if end > self.commit_key.g.len() {
+            return Err(InputError::CommitmentLengthError(self.commit_key.g.len(), end).into());
+        }
+
+        let scalars = chunk.iter().map(|x| x.into_repr()).collect::<Vec<_>>();
+        self.acc +=
+            VariableBaseMSM::multi_scalar_mul(&self.commit_key.g[self.position..end], &scalars);
+        self.position = end;
+
+        Ok(())
+    }
+
+    pub fn finalize(self, r: C::ScalarField) -> pedersen::Commitment<C> {
+        let blinding = self.commit_key.h.mul(r.into_repr());
+
+        pedersen::Commitment((self.acc + blinding).into_affine())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{Committer, HomomorphicCommitment};
+    use crate::crypto_primitives::utils::rand::test_rng;
+    use crate::error::{CardProtocolError, InputError};
+
+    use ark_ff::UniformRand;
+    use ark_serialize::CanonicalSerialize;
+    use proof_essentials::utils::rand::sample_vector;
+    use proof_essentials::vector_commitment::pedersen::PedersenCommitment;
+    use proof_essentials::vector_commitment::HomomorphicCommitmentScheme;
+
+    // Choose elliptic curve setting
+    type Curve = starknet_curve::Projective;
+    type Scalar = starknet_curve::Fr;
+
+    type Comm = PedersenCommitment<Curve>;
+
+    const STARKNET_COMMIT_KEY: &str = include_str!("../../tests/vectors/starknet_commit_key.txt");
+
+    fn to_hex<T: CanonicalSerialize>(point: &T) -> String {
+        let mut bytes = Vec::new();
+        point.serialize(&mut bytes).unwrap();
+        bytes.iter().map(|b| format!("{:02x}", b)).collect()
+    }
+
+    #[test]
+    fn test_pedersen_homomorphism() {
+        let rng = &mut test_rng();
+        let len = 13;
+
+        let commit_key = Comm::setup(rng, len);
+
+        let x: Vec<Scalar> = sample_vector(rng, len);
+        let y: Vec<Scalar> = sample_vector(rng, len);
+        let r = Scalar::rand(rng);
+        let s = Scalar::rand(rng);
+        let k = Scalar::rand(rng);
+
+        let c_x = Comm::commit(&commit_key, &x, r).unwrap();
+        let c_y = Comm::commit(&commit_key, &y, s).unwrap();
+
+        let sum = x.iter().zip(y.iter()).map(|(a, b)| *a + b).collect();
+        assert_eq!(
+            Ok(true),
+            Comm::verify_opening(&commit_key, &Comm::add(&c_x, &c_y), &sum, r + s)
+        );
+
+        let scaled = x.iter().map(|a| *a * k).collect();
+        assert_eq!(
+            Ok(true),
+            Comm::verify_opening(&commit_key, &Comm::scalar_mul(&c_x, &k), &scaled, r * k)
+        );
+
+        assert_eq!(Ok(false), Comm::verify_opening(&commit_key, &c_x, &y, r));
+    }
+
+    #[test]
+    fn test_streaming_commitment() {
+        let rng = &mut test_rng();
+        let len = 13;
+
+        let commit_key = Comm::setup(rng, len);
+
+        let x: Vec<Scalar> = sample_vector(rng, len);
+        let r = Scalar::rand(rng);
+        let expected = Comm::commit(&commit_key, &x, r).unwrap();
+
+        let chunkings: Vec<Vec<usize>> = vec![
+            vec![13],
+            vec![1; 13],
+            vec![5, 8],
+            vec![0, 6, 0, 7],
+            vec![12, 1],
+        ];
+
+        for chunk_sizes in chunkings {
+            let mut committer = Committer::new(&commit_key);
+            let mut start = 0;
+            for size in chunk_sizes.iter() {
+                committer.feed(&x[start..start + size]).unwrap();
+                start += size;
+            }
+
+            assert_eq!(committer.len(), len);
+            assert_eq!(
+                committer.finalize(r),
+                expected,
+                "chunking {:?} changed the commitment",
+                chunk_sizes
+            );
+        }
+
+        // A prefix commits like the one-shot commitment to the prefix
+        let mut committer = Committer::new(&commit_key);
+        committer.feed(&x[..4]).unwrap();
+        assert_eq!(
+            committer.finalize(r),
+            Comm::commit(&commit_key, &x[..4].to_vec(), r).unwrap()
+        );
+
+        let mut committer = Committer::new(&commit_key);
+        committer.feed(&x).unwrap();
+        assert_eq!(
+            committer.feed(&x[..1]),
+            Err(CardProtocolError::InvalidInput(
+                InputError::CommitmentLengthError(13, 14)
+            ))
+        );
+    }
+
+    #[test]
+    fn test_derived_commit_key_is_stable() {
+        // Label used by `DLCards::setup` for m = 4 and n = 13
+        let label = [
+            &b"Shuffle Commit Key"[..],
+            &4u64.to_le_bytes(),
+            &13u64.to_le_bytes(),
+        ]
+        .concat();
+
+        let commit_key = Comm::derive_commit_key(&label, 13).unwrap();
+        let pinned = STARKNET_COMMIT_KEY
+            .lines()
+            .filter(|line| !line.starts_with('#') && !line.is_empty())
+            .map(|line| line.split_once(' ').unwrap().1)
+            .collect::<Vec<_>>();
+
+        assert_eq!(pinned.len(), 14);
+        for (i, generator) in commit_key.g.iter().enumerate() {
+            assert_eq!(to_hex(generator), pinned[i], "generator g{} changed", i);
+        }
+        assert_eq!(to_hex(&commit_key.h), pinned[13], "generator h changed");
+
+        let other_key = Comm::derive_commit_key(b"other label", 13).unwrap();
+        assert_ne!(to_hex(&other_key.h), pinned[13]);
+    }
+}
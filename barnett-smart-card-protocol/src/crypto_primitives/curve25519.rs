@@ -0,0 +1,303 @@
+//! The twisted Edwards form of curve25519, `-x^2 + y^2 = 1 + d x^2 y^2` over the field of order
+//! `2^255 - 19`, on which `crypto_primitives::ristretto255` is built.
+//!
+//! The protocol runs on ristretto255 rather than on this group, which has order `8 * l`. Points of
+//! this group still decode only inside the subgroup of order `l`, and `validation::validate_point`
+//! checks `l * P == 0` explicitly for any curve with a cofactor, so a point with a torsion
+//! component is rejected wherever it is received.
+
+use ark_ec::models::twisted_edwards_extended::{GroupAffine, GroupProjective};
+use ark_ec::models::{ModelParameters, MontgomeryModelParameters, TEModelParameters};
+use ark_ff::biginteger::BigInteger256 as BigInteger;
+use ark_ff::field_new;
+use ark_ff::fields::{FftParameters, Fp256, Fp256Parameters, FpParameters};
+
+pub type Fq = Fp256<FqParameters>;
+pub type Fr = Fp256<FrParameters>;
+
+pub type Affine = GroupAffine<Parameters>;
+pub type Projective = GroupProjective<Parameters>;
+
+#[derive(Debug)]
+pub struct FqParameters;
+
+impl Fp256Parameters for FqParameters {}
+
+impl FftParameters for FqParameters {
+    type BigInt = BigInteger;
+
+    const TWO_ADICITY: u32 = 2;
+
+    const TWO_ADIC_ROOT_OF_UNITY: BigInteger = BigInteger([
+        0x3b5807d4fe2bdb04,
+        0x03f590fdb51be9ed,
+        0x6d6e16bf336202d1,
+        0x75776b0bd6c71ba8,
+    ]);
+}
+
+impl FpParameters for FqParameters {
+    const MODULUS: BigInteger = BigInteger([
+        0xffffffffffffffed,
+        0xffffffffffffffff,
+        0xffffffffffffffff,
+        0x7fffffffffffffff,
+    ]);
+
+    const MODULUS_BITS: u32 = 255;
+
+    const CAPACITY: u32 = Self::MODULUS_BITS - 1;
+
+    const REPR_SHAVE_BITS: u32 = 1;
+
+    const R: BigInteger = BigInteger([0x26, 0x0, 0x0, 0x0]);
+
+    const R2: BigInteger = BigInteger([0x5a4, 0x0, 0x0, 0x0]);
+
+    const INV: u64 = 0x86bca1af286bca1b;
+
+    /// 2, in Montgomery form
+    const GENERATOR: BigInteger = BigInteger([0x4c, 0x0, 0x0, 0x0]);
+
+    const MODULUS_MINUS_ONE_DIV_TWO: BigInteger = BigInteger([
+        0xfffffffffffffff6,
+        0xffffffffffffffff,
+        0xffffffffffffffff,
+        0x3fffffffffffffff,
+    ]);
+
+    const T: BigInteger = BigInteger([
+        0xfffffffffffffffb,
+        0xffffffffffffffff,
+        0xffffffffffffffff,
+        0x1fffffffffffffff,
+    ]);
+
+    const T_MINUS_ONE_DIV_TWO: BigInteger = BigInteger([
+        0xfffffffffffffffd,
+        0xffffffffffffffff,
+        0xffffffffffffffff,
+        0x0fffffffffffffff,
+    ]);
+}
+
+#[derive(Debug)]
+pub struct FrParameters;
+
+impl Fp256Parameters for FrParameters {}
+
+impl FftParameters for FrParameters {
+    type BigInt = BigInteger;
+
+    const TWO_ADICITY: u32 = 2;
+
+    const TWO_ADIC_ROOT_OF_UNITY: BigInteger = BigInteger([
+        0x7c790e32b42f0e7d,
+        0x4c8ce706a7ae2cc8,
+        0xd73823cc921779ad,
+        0x05599959893f562a,
+    ]);
+}
+
+impl FpParameters for FrParameters {
+    const MODULUS: BigInteger = BigInteger([
+        0x5812631a5cf5d3ed,
+        0x14def9dea2f79cd6,
+        0x0000000000000000,
+        0x1000000000000000,
+    ]);
+
+    const MODULUS_BITS: u32 = 253;
+
+    const CAPACITY: u32 = Self::MODULUS_BITS - 1;
+
+    const REPR_SHAVE_BITS: u32 = 3;
+
+    const R: BigInteger = BigInteger([
+        0xd6ec31748d98951d,
+        0xc6ef5bf4737dcf70,
+        0xfffffffffffffffe,
+        0x0fffffffffffffff,
+    ]);
+
+    const R2: BigInteger = BigInteger([
+        0xa40611e3449c0f01,
+        0xd00e1ba768859347,
+        0xceec73d217f5be65,
+        0x0399411b7c309a3d,
+    ]);
+
+    const INV: u64 = 0xd2b51da312547e1b;
+
+    /// 2, in Montgomery form
+    const GENERATOR: BigInteger = BigInteger([
+        0x55c5ffcebe3b564d,
+        0x78ffbe0a4404020b,
+        0xfffffffffffffffd,
+        0x0fffffffffffffff,
+    ]);
+
+    const MODULUS_MINUS_ONE_DIV_TWO: BigInteger = BigInteger([
+        0x2c09318d2e7ae9f6,
+        0x0a6f7cef517bce6b,
+        0x0000000000000000,
+        0x0800000000000000,
+    ]);
+
+    const T: BigInteger = BigInteger([
+        0x960498c6973d74fb,
+        0x0537be77a8bde735,
+        0x0000000000000000,
+        0x0400000000000000,
+    ]);
+
+    const T_MINUS_ONE_DIV_TWO: BigInteger = BigInteger([
+        0xcb024c634b9eba7d,
+        0x029bdf3bd45ef39a,
+        0x0000000000000000,
+        0x0200000000000000,
+    ]);
+}
+
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct Parameters;
+
+impl ModelParameters for Parameters {
+    type BaseField = Fq;
+    type ScalarField = Fr;
+}
+
+impl TEModelParameters for Parameters {
+    /// -1
+    const COEFF_A: Fq = field_new!(Fq, "-1");
+
+    /// -121665 / 121666
+    const COEFF_D: Fq = field_new!(
+        Fq,
+        "37095705934669439343138083508754565189542113879843219016388785533085940283555"
+    );
+
+    const COFACTOR: &'static [u64] = &[8];
+
+    /// 8^{-1} mod l
+    const COFACTOR_INV: Fr = field_new!(
+        Fr,
+        "2713877091499598330239944961141122840321418634767465352250731601857045344121"
+    );
+
+    const AFFINE_GENERATOR_COEFFS: (Self::BaseField, Self::BaseField) = (GENERATOR_X, GENERATOR_Y);
+
+    type MontgomeryModelParameters = Parameters;
+
+    #[inline(always)]
+    fn mul_by_a(elem: &Self::BaseField) -> Self::BaseField {
+        -*elem
+    }
+}
+
+impl MontgomeryModelParameters for Parameters {
+    /// 486662
+    const COEFF_A: Fq = field_new!(Fq, "486662");
+
+    /// -486664, which makes the Montgomery curve birationally equivalent to the Edwards curve above
+    const COEFF_B: Fq = field_new!(
+        Fq,
+        "57896044618658097711785492504343953926634992332820282019728792003956564333285"
+    );
+
+    type TEModelParameters = Parameters;
+}
+
+/// x-coordinate of the standard base point
+pub const GENERATOR_X: Fq = field_new!(
+    Fq,
+    "15112221349535400772501151409588531511454012693041857206046113283949847762202"
+);
+
+/// y-coordinate of the standard base point, 4/5
+pub const GENERATOR_Y: Fq = field_new!(
+    Fq,
+    "46316835694926478169428394003475163141307993866256225615783033603165251855960"
+);
+
+#[cfg(test)]
+mod test {
+    use super::{Affine, Fq, Fr};
+    use crate::crypto_primitives::hash_to_curve::hash_to_curve;
+    use crate::crypto_primitives::utils::rand::test_rng;
+    use crate::discrete_log_cards::validation::{public_key_from_bytes, validate_point};
+    use crate::error::{CardProtocolError, InputError};
+
+    use ark_ec::{AffineCurve, ProjectiveCurve};
+    use ark_ff::{Field, One, UniformRand, Zero};
+    use ark_serialize::CanonicalSerialize;
+
+    // Choose elliptic curve setting
+    type Curve = super::Projective;
+
+    #[test]
+    fn test_fields() {
+        let rng = &mut test_rng();
+
+        for _ in 0..100 {
+            let a = Fq::rand(rng);
+            let b = Fq::rand(rng);
+            assert_eq!((a + b) * (a - b), a.square() - b.square());
+            if let Some(inverse) = a.inverse() {
+                assert_eq!(a * inverse, Fq::one());
+            }
+
+            let x = Fr::rand(rng);
+            if let Some(inverse) = x.inverse() {
+                assert_eq!(x * inverse, Fr::one());
+            }
+        }
+
+        // p = 2^255 - 19
+        assert_eq!(Fq::from(2u64).pow([255]), Fq::from(19u64));
+    }
+
+    #[test]
+    fn test_generator() {
+        let g = Affine::prime_subgroup_generator();
+        assert!(g.is_on_curve());
+        assert!(g.is_in_correct_subgroup_assuming_on_curve());
+        assert_eq!(g.y, Fq::from(4u64) / Fq::from(5u64));
+        assert_eq!(g.serialized_size(), 32);
+    }
+
+    #[test]
+    fn test_torsion_points_are_rejected() {
+        let rng = &mut test_rng();
+
+        // (0, -1) is on the curve and has order 2
+        let torsion = Affine::new(Fq::zero(), -Fq::one());
+        assert!(torsion.is_on_curve());
+        assert!(torsion.into_projective().double().is_zero());
+
+        let point = Affine::prime_subgroup_generator()
+            .mul(Fr::rand(rng))
+            .into_affine();
+        assert_eq!(Ok(()), validate_point(&point));
+        assert_eq!(
+            validate_point(&torsion),
+            Err(CardProtocolError::InvalidInput(InputError::InvalidPoint))
+        );
+        assert_eq!(
+            validate_point(&(point + torsion)),
+            Err(CardProtocolError::InvalidInput(InputError::InvalidPoint))
+        );
+
+        // Decoding a key from another player rejects it as well
+        let mut bytes = Vec::new();
+        (point + torsion).serialize(&mut bytes).unwrap();
+        assert_eq!(
+            public_key_from_bytes::<Affine>(&bytes),
+            Err(CardProtocolError::InvalidInput(InputError::InvalidPoint))
+        );
+
+        // Hashed points have their torsion component cleared
+        let hashed = hash_to_curve::<Curve>(b"domain", b"message").unwrap();
+        assert_eq!(Ok(()), validate_point(&hashed));
+    }
+}
@@ -0,0 +1,233 @@
+//! Encryption of short messages, such as reveal tokens, to a single player's public key.
+//!
+//! Every envelope uses a fresh ephemeral key. The Diffie-Hellman point shared with the recipient is
+//! expanded with HKDF into a ChaCha20-Poly1305 key and nonce, so callers never handle nonces and
+//! a nonce is never reused under the same key. The ephemeral key is authenticated as associated
+//! data, so modifying any part of the envelope makes decryption fail.
+
+use crate::crypto_primitives::utils::format::Hex;
+use crate::crypto_primitives::utils::rand::sample_nonzero;
+use crate::crypto_primitives::utils::secret::SecretScalar;
+use crate::error::{CardProtocolError, InputError, InternalError, VerificationError};
+
+use ark_ec::{AffineCurve, ProjectiveCurve};
+use ark_ff::{PrimeField, Zero};
+use ark_serialize::{CanonicalDeserialize, CanonicalSerialize};
+use ark_std::fmt;
+use ark_std::rand::{CryptoRng, Rng};
+use blake2::Blake2s;
+use chacha20poly1305::aead::{Aead, NewAead, Payload};
+use chacha20poly1305::{ChaCha20Poly1305, Key, Nonce};
+use hkdf::Hkdf;
+use zeroize::Zeroize;
+
+const ECIES_DOMAIN: &'static [u8] = b"ECIES ChaCha20-Poly1305";
+
+const KEY_LENGTH: usize = 32;
+const NONCE_LENGTH: usize = 12;
+
+#[derive(Clone, PartialEq, Eq, CanonicalSerialize, CanonicalDeserialize)]
+pub struct Envelope<C: ProjectiveCurve> {
+    pub ephemeral_key: C::Affine,
+    pub ciphertext: Vec<u8>,
+}
+
+impl<C: ProjectiveCurve> fmt::Debug for Envelope<C> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Envelope")
+            .field("ephemeral_key", &Hex(&self.ephemeral_key))
+            .field(
+                "ciphertext",
+                &format_args!("{} bytes", self.ciphertext.len()),
+            )
+            .finish()
+    }
+}
+
+/// Encrypt `plaintext` to the owner of `pk = generator^sk`.
+pub fn encrypt_to<C: ProjectiveCurve, R: Rng + CryptoRng>(
+    rng: &mut R,
+    generator: &C::Affine,
+    pk: &C::Affine,
+    plaintext: &[u8],
+) -> Result<Envelope<C>, CardProtocolError> {
+    if pk.is_zero() {
+        return Err(InputError::KeyDerivationError.into());
+    }
+
+    let ephemeral_sk = SecretScalar::<C::ScalarField>::new(sample_nonzero(rng)?);
+    let ephemeral_key = generator.mul(ephemeral_sk.into_repr()).into_affine();
+    let shared_point = pk.mul(ephemeral_sk.into_repr()).into_affine();
+
+    let (cipher, nonce, aad) = derive_cipher::<C>(&shared_point, &ephemeral_key)?;
+    let ciphertext = cipher
+        .encrypt(
+            &nonce,
+            Payload {
+                msg: plaintext,
+                aad: &aad,
+            },
+        )
+        .map_err(|_| CardProtocolError::Internal(InternalError::EncryptionError))?;
+
+    Ok(Envelope {
+        ephemeral_key,
+        ciphertext,
+    })
+}
+
+/// Open an envelope addressed to the public key corresponding to `sk`.
+pub fn decrypt<C: ProjectiveCurve>(
+    sk: &C::ScalarField,
+    envelope: &Envelope<C>,
+) -> Result<Vec<u8>, CardProtocolError> {
+    let shared_point = envelope.ephemeral_key.mul(sk.into_repr()).into_affine();
+    if shared_point.is_zero() {
+        return Err(VerificationError::DecryptionError.into());
+    }
+
+    let (cipher, nonce, aad) = derive_cipher::<C>(&shared_point, &envelope.ephemeral_key)?;
+    cipher
+        .decrypt(
+            &nonce,
+            Payload {
+                msg: &envelope.ciphertext,
+                aad: &aad,
+            },
+        )
+        .map_err(|_| CardProtocolError::VerificationFailed(VerificationError::DecryptionError))
+}
+
+/// Encrypt a serializable value, e.g. a reveal token, to the owner of `pk`.
+pub fn encrypt_element_to<C: ProjectiveCurve, T: CanonicalSerialize, R: Rng + CryptoRng>(
+    rng: &mut R,
+    generator: &C::Affine,
+    pk: &C::Affine,
+    element: &T,
+) -> Result<Envelope<C>, CardProtocolError> {
+    let mut bytes = Vec::new();
+    element.serialize(&mut bytes)?;
+
+    encrypt_to(rng, generator, pk, &bytes)
+}
+
+/// Open an envelope produced by [`encrypt_element_to`].
+pub fn decrypt_element<C: ProjectiveCurve, T: CanonicalDeserialize>(
+    sk: &C::ScalarField,
+    envelope: &Envelope<C>,
+) -> Result<T, CardProtocolError> {
+    let bytes = decrypt(sk, envelope)?;
+
+    Ok(T::deserialize(&bytes[..])?)
+}
+
+fn derive_cipher<C: ProjectiveCurve>(
+    shared_point: &C::Affine,
+    ephemeral_key: &C::Affine,
+) -> Result<(ChaCha20Poly1305, Nonce, Vec<u8>), CardProtocolError> {
+    let mut shared_secret = Vec::new();
+    shared_point.serialize(&mut shared_secret)?;
+
+    let mut aad = Vec::new();
+    ephemeral_key.serialize(&mut aad)?;
+
+    let info = [ECIES_DOMAIN, &aad].concat();
+    let mut okm = [0u8; KEY_LENGTH + NONCE_LENGTH];
+    let expanded = Hkdf::<Blake2s>::new(None, &shared_secret).expand(&info, &mut okm);
+    shared_secret.zeroize();
+    expanded.map_err(|_| CardProtocolError::InvalidInput(InputError::KeyDerivationError))?;
+
+    let cipher = ChaCha20Poly1305::new(Key::from_slice(&okm[..KEY_LENGTH]));
+    let nonce = *Nonce::from_slice(&okm[KEY_LENGTH..]);
+    okm.zeroize();
+
+    Ok((cipher, nonce, aad))
+}
+
+#[cfg(test)]
+mod test {
+    use super::{decrypt, decrypt_element, encrypt_element_to, encrypt_to};
+    use crate::crypto_primitives::utils::rand::test_rng;
+    use crate::discrete_log_cards;
+    use crate::error::{CardProtocolError, VerificationError};
+
+    use ark_ff::UniformRand;
+    use proof_essentials::homomorphic_encryption::{
+        el_gamal::ElGamal, HomomorphicEncryptionScheme,
+    };
+
+    // Choose elliptic curve setting
+    type Curve = starknet_curve::Projective;
+
+    type RevealToken = discrete_log_cards::RevealToken<Curve>;
+
+    #[test]
+    fn test_encrypt_and_decrypt() {
+        let rng = &mut test_rng();
+
+        let parameters = ElGamal::<Curve>::setup(rng).unwrap();
+        let generator = parameters.generator;
+        let (pk, sk) = ElGamal::<Curve>::keygen(&parameters, rng).unwrap();
+        let (_, other_sk) = ElGamal::<Curve>::keygen(&parameters, rng).unwrap();
+
+        let envelope = encrypt_to::<Curve, _>(rng, &generator, &pk, b"hello").unwrap();
+        assert_eq!(Ok(b"hello".to_vec()), decrypt(&sk, &envelope));
+
+        // Encrypting twice gives unrelated envelopes
+        let other_envelope = encrypt_to::<Curve, _>(rng, &generator, &pk, b"hello").unwrap();
+        assert_ne!(envelope, other_envelope);
+
+        assert_eq!(
+            decrypt(&other_sk, &envelope),
+            Err(CardProtocolError::VerificationFailed(
+                VerificationError::DecryptionError
+            ))
+        );
+
+        let token = RevealToken::rand(rng);
+        let envelope = encrypt_element_to::<Curve, _, _>(rng, &generator, &pk, &token).unwrap();
+        assert_eq!(Ok(token), decrypt_element(&sk, &envelope));
+    }
+
+    #[test]
+    fn test_tampered_envelope_is_rejected() {
+        let rng = &mut test_rng();
+
+        let parameters = ElGamal::<Curve>::setup(rng).unwrap();
+        let generator = parameters.generator;
+        let (pk, sk) = ElGamal::<Curve>::keygen(&parameters, rng).unwrap();
+
+        let envelope = encrypt_to::<Curve, _>(rng, &generator, &pk, b"reveal token").unwrap();
+
+        for i in 0..envelope.ciphertext.len() {
+            let mut tampered = envelope.clone();
+            tampered.ciphertext[i] ^= 1;
+            assert_eq!(
+                decrypt(&sk, &tampered),
+                Err(CardProtocolError::VerificationFailed(
+                    VerificationError::DecryptionError
+                ))
+            );
+        }
+
+        let mut truncated = envelope.clone();
+        truncated.ciphertext.pop();
+        assert_eq!(
+            decrypt(&sk, &truncated),
+            Err(CardProtocolError::VerificationFailed(
+                VerificationError::DecryptionError
+            ))
+        );
+
+        let mut tampered = envelope.clone();
+        tampered.ephemeral_key = encrypt_to::<Curve, _>(rng, &generator, &pk, b"")
+            .unwrap()
+            .ephemeral_key;
+        assert_eq!(
+            decrypt(&sk, &tampered),
+            Err(CardProtocolError::VerificationFailed(
+                VerificationError::DecryptionError
+            ))
+        );
+    }
+}
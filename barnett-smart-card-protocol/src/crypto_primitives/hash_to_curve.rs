@@ -0,0 +1,93 @@
+use crate::error::{CardProtocolError, InternalError};
+
+use ark_ec::{AffineCurve, ProjectiveCurve};
+use ark_serialize::CanonicalSerialize;
+use ark_std::vec::Vec;
+use ark_std::Zero;
+use blake2::{Blake2s, Digest};
+
+/// Number of counters tried before giving up. Each attempt succeeds with probability close to 1/2,
+/// so running out of attempts points at a misconfigured curve rather than bad luck.
+const MAX_ATTEMPTS: u32 = 256;
+
+/// Hash a message to a point of the prime order subgroup using try-and-increment.
+///
+/// For every counter value, the domain separator, the message and the counter are expanded with
+/// Blake2s to as many bytes as a compressed point takes. These bytes are read as an x-coordinate
+/// together with the sign flags, and the first candidate that lies on the curve is multiplied by
+/// the cofactor. All variable-length inputs are length-prefixed so that distinct
+/// `(domain, msg)` pairs never hash the same bytes.
+pub fn hash_to_curve<C: ProjectiveCurve>(
+    domain: &[u8],
+    msg: &[u8],
+) -> Result<C::Affine, CardProtocolError> {
+    let num_bytes = C::Affine::zero().serialized_size();
+
+    for counter in 0..MAX_ATTEMPTS {
+        let bytes = expand(domain, msg, counter, num_bytes);
+        if let Some(point) = C::Affine::from_random_bytes(&bytes) {
+            let point = point.mul_by_cofactor();
+            if !point.is_zero() {
+                return Ok(point);
+            }
+        }
+    }
+
+    Err(CardProtocolError::Internal(InternalError::HashToCurveError))
+}
+
+/// Expand `(domain, msg, counter)` into `num_bytes` pseudorandom bytes with Blake2s.
+pub(crate) fn expand(domain: &[u8], msg: &[u8], counter: u32, num_bytes: usize) -> Vec<u8> {
+    let mut output = Vec::with_capacity(num_bytes);
+    let mut block: u8 = 0;
+
+    while output.len() < num_bytes {
+        let mut hasher = Blake2s::new();
+        hasher.update(&(domain.len() as u64).to_le_bytes());
+        hasher.update(domain);
+        hasher.update(&(msg.len() as u64).to_le_bytes());
+        hasher.update(msg);
+        hasher.update(&counter.to_le_bytes());
+        hasher.update(&[block]);
+        output.extend_from_slice(&hasher.finalize());
+        block += 1;
+    }
+
+    output.truncate(num_bytes);
+    output
+}
+
+#[cfg(test)]
+mod test {
+    use super::hash_to_curve;
+
+    // Choose elliptic curve setting
+    type Curve = starknet_curve::Projective;
+
+    #[test]
+    fn test_hash_to_curve() {
+        let point = hash_to_curve::<Curve>(b"domain", b"message").unwrap();
+
+        assert!(point.is_on_curve());
+        assert!(point.is_in_correct_subgroup_assuming_on_curve());
+        assert_eq!(
+            point,
+            hash_to_curve::<Curve>(b"domain", b"message").unwrap()
+        );
+
+        assert_ne!(
+            point,
+            hash_to_curve::<Curve>(b"other domain", b"message").unwrap()
+        );
+        assert_ne!(
+            point,
+            hash_to_curve::<Curve>(b"domain", b"other message").unwrap()
+        );
+
+        // Length prefixes keep the boundary between domain and message unambiguous
+        assert_ne!(
+            hash_to_curve::<Curve>(b"ab", b"c").unwrap(),
+            hash_to_curve::<Curve>(b"a", b"bc").unwrap()
+        );
+    }
+}
@@ -0,0 +1,30 @@
+use crate::crypto_primitives::hash_to_curve::expand;
+
+use ark_ff::PrimeField;
+
+/// Hash a message to a field element.
+///
+/// The message is expanded to 128 bits more than the size of the modulus before being reduced,
+/// which makes the bias of the reduction negligible.
+pub fn hash_to_field<F: PrimeField>(domain: &[u8], msg: &[u8]) -> F {
+    let num_bytes = (F::size_in_bits() + 7) / 8 + 16;
+    let bytes = expand(domain, msg, 0, num_bytes);
+
+    F::from_le_bytes_mod_order(&bytes)
+}
+
+#[cfg(test)]
+mod test {
+    use super::hash_to_field;
+
+    type Scalar = starknet_curve::Fr;
+
+    #[test]
+    fn test_hash_to_field() {
+        let x: Scalar = hash_to_field(b"domain", b"message");
+
+        assert_eq!(x, hash_to_field(b"domain", b"message"));
+        assert_ne!(x, hash_to_field(b"other domain", b"message"));
+        assert_ne!(x, hash_to_field(b"domain", b"other message"));
+    }
+}
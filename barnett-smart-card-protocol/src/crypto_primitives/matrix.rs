@@ -0,0 +1,212 @@
+//! Matrix view of a deck.
+//!
+//! The shuffle argument arranges a deck of `m * n` cards as a matrix with `m` rows of `n` columns.
+//! Every conversion in this module is row-major: the element at row `i` and column `j` is element
+//! `i * n + j` of the flat vector, so `flatten(reshape(x, m, n)) == x`.
+
+use crate::crypto_primitives::permutation::check_permutation;
+use crate::error::{Bound, CardProtocolError, Dimension, InputError};
+
+use ark_std::vec;
+use ark_std::vec::Vec;
+use proof_essentials::utils::permutation::Permutation;
+
+/// An `m x n` matrix stored as a vector of rows.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Matrix<T> {
+    rows: Vec<Vec<T>>,
+    num_columns: usize,
+}
+
+impl<T> Matrix<T> {
+    /// Build an `m x n` matrix whose element at row `i` and column `j` is `f(i, j)`. Elements are
+    /// produced in row-major order.
+    pub fn from_fn<F: FnMut(usize, usize) -> T>(m: usize, n: usize, mut f: F) -> Self {
+        let rows = (0..m).map(|i| (0..n).map(|j| f(i, j)).collect()).collect();
+
+        Self {
+            rows,
+            num_columns: n,
+        }
+    }
+
+    pub fn num_rows(&self) -> usize {
+        self.rows.len()
+    }
+
+    pub fn num_columns(&self) -> usize {
+        self.num_columns
+    }
+
+    pub fn rows(&self) -> &[Vec<T>] {
+        &self.rows
+    }
+
+    pub fn get(&self, row: usize, column: usize) -> Option<&T> {
+        self.rows.get(row).and_then(|r| r.get(column))
+    }
+}
+
+impl<T: Clone> Matrix<T> {
+    /// Elements of column `j`, from the first row to the last.
+    pub fn column(&self, j: usize) -> Option<Vec<T>> {
+        if j >= self.num_columns {
+            return None;
+        }
+
+        Some(self.rows.iter().map(|row| row[j].clone()).collect())
+    }
+
+    /// The `n x m` matrix whose rows are the columns of this one.
+    pub fn transpose(&self) -> Self {
+        Self::from_fn(self.num_columns, self.num_rows(), |i, j| {
+            self.rows[j][i].clone()
+        })
+    }
+}
+
+/// Arrange `elements` into `m` rows of `n` columns, in row-major order.
+pub fn reshape<T: Clone>(
+    elements: &[T],
+    m: usize,
+    n: usize,
+) -> Result<Matrix<T>, CardProtocolError> {
+    if m.checked_mul(n) != Some(elements.len()) {
+        return Err(InputError::DimensionMismatch {
+            dimension: Dimension::MatrixElements,
+            expected: Bound::Exactly(m.saturating_mul(n)),
+            got: elements.len(),
+        }
+        .into());
+    }
+
+    let rows = if n == 0 {
+        vec![Vec::new(); m]
+    } else {
+        elements.chunks(n).map(|row| row.to_vec()).collect()
+    };
+
+    Ok(Matrix {
+        rows,
+        num_columns: n,
+    })
+}
+
+/// Concatenate the rows of `matrix`, inverting [`reshape`].
+pub fn flatten<T: Clone>(matrix: &Matrix<T>) -> Vec<T> {
+    matrix.rows.concat()
+}
+
+pub trait ApplyToMatrix {
+    /// Permute the elements of `matrix` as if it were the flat vector given by [`flatten`], and
+    /// return the result with the same dimensions.
+    fn apply_to_matrix<T: Clone>(&self, matrix: &Matrix<T>)
+        -> Result<Matrix<T>, CardProtocolError>;
+}
+
+impl ApplyToMatrix for Permutation {
+    fn apply_to_matrix<T: Clone>(
+        &self,
+        matrix: &Matrix<T>,
+    ) -> Result<Matrix<T>, CardProtocolError> {
+        let elements = flatten(matrix);
+        check_permutation(self, elements.len())?;
+
+        let permuted = self.permute_array(&elements);
+        reshape(&permuted, matrix.num_rows(), matrix.num_columns())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{flatten, reshape, ApplyToMatrix, Matrix};
+    use crate::crypto_primitives::utils::rand::test_rng;
+    use crate::error::{Bound, CardProtocolError, Dimension, InputError};
+
+    use proof_essentials::utils::permutation::Permutation;
+    use proptest::prelude::*;
+    use rand::Rng;
+
+    #[test]
+    fn test_reshape_is_row_major() {
+        let deck = (0..52).collect::<Vec<usize>>();
+        let matrix = reshape(&deck, 4, 13).unwrap();
+
+        assert_eq!(matrix.num_rows(), 4);
+        assert_eq!(matrix.num_columns(), 13);
+        assert_eq!(matrix.get(0, 12), Some(&12));
+        assert_eq!(matrix.get(1, 0), Some(&13));
+        assert_eq!(matrix.get(3, 12), Some(&51));
+        assert_eq!(matrix.get(4, 0), None);
+
+        assert_eq!(
+            reshape(&deck, 4, 12),
+            Err(CardProtocolError::InvalidInput(
+                InputError::DimensionMismatch {
+                    dimension: Dimension::MatrixElements,
+                    expected: Bound::Exactly(48),
+                    got: 52
+                }
+            ))
+        );
+    }
+
+    /// Dimensions of up to 10 x 10 and the elements of a matrix of that shape, in row-major order.
+    fn shape_and_elements() -> impl Strategy<Value = (usize, usize, Vec<u64>)> {
+        (0usize..10, 0usize..10)
+            .prop_flat_map(|(m, n)| (Just(m), Just(n), prop::collection::vec(any::<u64>(), m * n)))
+    }
+
+    proptest! {
+        #[test]
+        fn test_transpose_and_columns((m, n, elements) in shape_and_elements()) {
+            let matrix = reshape(&elements, m, n).unwrap();
+            let transposed = matrix.transpose();
+
+            prop_assert_eq!(transposed.num_rows(), n);
+            prop_assert_eq!(transposed.num_columns(), m);
+            prop_assert_eq!(&transposed.transpose(), &matrix);
+
+            for j in 0..n {
+                prop_assert_eq!(matrix.column(j).as_ref(), transposed.rows().get(j));
+            }
+            prop_assert_eq!(matrix.column(n), None);
+        }
+
+        #[test]
+        fn test_flatten_inverts_reshape((m, n, elements) in shape_and_elements()) {
+            prop_assert_eq!(flatten(&reshape(&elements, m, n).unwrap()), elements);
+        }
+
+        #[test]
+        fn test_from_fn_is_row_major(m in 0usize..10, n in 0usize..10) {
+            let matrix = Matrix::from_fn(m, n, |i, j| (i, j));
+
+            prop_assert_eq!(matrix.num_rows(), m);
+            prop_assert_eq!(matrix.num_columns(), n);
+            let expected = (0..m)
+                .flat_map(|i| (0..n).map(move |j| (i, j)))
+                .collect::<Vec<_>>();
+            prop_assert_eq!(flatten(&matrix), expected);
+        }
+    }
+
+    #[test]
+    fn test_matrix_application_agrees_with_flat_application() {
+        let rng = &mut test_rng();
+
+        for _ in 0..100 {
+            let m = rng.gen_range(1..10);
+            let n = rng.gen_range(1..10);
+            let elements = (0..m * n).map(|_| rng.gen::<u64>()).collect::<Vec<_>>();
+            let permutation = Permutation::new(rng, m * n);
+
+            let matrix = reshape(&elements, m, n).unwrap();
+            let permuted = permutation.apply_to_matrix(&matrix).unwrap();
+
+            assert_eq!(permuted.num_rows(), m);
+            assert_eq!(permuted.num_columns(), n);
+            assert_eq!(flatten(&permuted), permutation.permute_array(&elements));
+        }
+    }
+}
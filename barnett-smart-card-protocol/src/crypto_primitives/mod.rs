@@ -0,0 +1,16 @@
+//! Building blocks used by the card protocol that are not provided by `proof_essentials`.
+
+pub mod commitment;
+#[cfg(feature = "curve25519")]
+pub mod curve25519;
+#[cfg(feature = "std")]
+pub mod ecies;
+pub mod hash_to_curve;
+pub mod hash_to_field;
+pub mod matrix;
+pub mod permutation;
+#[cfg(feature = "curve25519")]
+pub mod ristretto255;
+pub mod signature;
+pub mod utils;
+pub mod vrf;
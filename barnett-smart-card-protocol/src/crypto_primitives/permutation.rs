@@ -0,0 +1,352 @@
+//! Permutations derived deterministically from a public seed.
+//!
+//! In a provably fair game the seed is the combination of entropy contributed by every player, so
+//! nobody controls the resulting order and any verifier given the seed recomputes it exactly.
+
+use crate::crypto_primitives::utils::rand::MAX_SAMPLING_ATTEMPTS;
+use crate::error::{check_dimension, CardProtocolError, Dimension, InputError, InternalError};
+
+use ark_std::rand::{CryptoRng, Rng, RngCore, SeedableRng};
+use ark_std::vec;
+use ark_std::vec::Vec;
+use blake2::{Blake2s, Digest};
+use proof_essentials::utils::permutation::Permutation;
+use rand_chacha::ChaCha20Rng;
+
+const PERMUTATION_SEED: &'static [u8] = b"Seeded Permutation";
+const JOINT_SEED: &'static [u8] = b"Joint Permutation Seed";
+
+pub trait FromSeed {
+    fn from_seed(seed: [u8; 32], size: usize) -> Self;
+}
+
+impl FromSeed for Permutation {
+    /// Fisher-Yates shuffle driven by a ChaCha20 stream keyed with a domain-separated hash of the
+    /// seed. Indices are drawn by rejection sampling rather than through `rand`'s distributions, so
+    /// the output only depends on the ChaCha20 keystream and stays stable across `rand` versions.
+    fn from_seed(seed: [u8; 32], size: usize) -> Self {
+        let mut hasher = Blake2s::new();
+        hasher.update(PERMUTATION_SEED);
+        hasher.update(&seed);
+
+        let mut key = [0u8; 32];
+        key.copy_from_slice(&hasher.finalize());
+        let mut rng = ChaCha20Rng::from_seed(key);
+
+        let mut mapping: Vec<usize> = (0..size).collect();
+        for i in (1..size).rev() {
+            let j = uniform_below(&mut rng, (i + 1) as u64) as usize;
+            mapping.swap(i, j);
+        }
+
+        Permutation::from(&mapping)
+    }
+}
+
+/// Sample a uniformly random permutation of `size` positions, e.g. for a shuffle. Unlike
+/// `Permutation::new`, fails rather than looping forever or returning a predictable permutation
+/// if the generator is broken: a working generator produces a zero word with negligible
+/// probability, so zero words are rejected and too many of them fail the sampling.
+pub fn sample_permutation<R: Rng + CryptoRng>(
+    rng: &mut R,
+    size: usize,
+) -> Result<Permutation, CardProtocolError> {
+    let mut mapping: Vec<usize> = (0..size).collect();
+    for i in (1..size).rev() {
+        let j = try_uniform_below(rng, (i + 1) as u64)? as usize;
+        mapping.swap(i, j);
+    }
+
+    Ok(Permutation::from(&mapping))
+}
+
+/// Check that `permutation` rearranges exactly `size` positions, each of them once. Applying a
+/// permutation that does not would panic, or silently drop and duplicate elements.
+pub fn check_permutation(permutation: &Permutation, size: usize) -> Result<(), CardProtocolError> {
+    check_dimension(Dimension::Permutation, size, permutation.mapping.len())?;
+
+    let mut used = vec![false; size];
+    for &position in &permutation.mapping {
+        match used.get_mut(position) {
+            Some(used) if !*used => *used = true,
+            _ => return Err(InputError::InvalidPermutation.into()),
+        }
+    }
+
+    Ok(())
+}
+
+/// Combine the contributions revealed by all players, in seat order, into a permutation seed.
+/// The result is uniform as long as at least one contribution is, and changes if any contribution
+/// changes or the order of the players does.
+pub fn combine_contributions(contributions: &[[u8; 32]]) -> [u8; 32] {
+    let mut hasher = Blake2s::new();
+    hasher.update(JOINT_SEED);
+    hasher.update(&(contributions.len() as u64).to_le_bytes());
+    for contribution in contributions {
+        hasher.update(contribution);
+    }
+
+    let mut seed = [0u8; 32];
+    seed.copy_from_slice(&hasher.finalize());
+    seed
+}
+
+/// Uniform integer in `[0, bound)`, rejecting draws from the incomplete top bucket.
+fn uniform_below<R: RngCore>(rng: &mut R, bound: u64) -> u64 {
+    // 2^64 mod bound: the number of values that would bias the reduction
+    let excess = (u64::MAX % bound + 1) % bound;
+    let limit = 0u64.wrapping_sub(excess);
+
+    loop {
+        let x = rng.next_u64();
+        if excess == 0 || x < limit {
+            return x % bound;
+        }
+    }
+}
+
+/// Uniform integer in `[0, bound)` from non-zero words of `rng`, rejecting zero words and draws
+/// from the incomplete top bucket of the remaining `2^64 - 1` values.
+fn try_uniform_below<R: RngCore>(rng: &mut R, bound: u64) -> Result<u64, CardProtocolError> {
+    // (2^64 - 1) mod bound: the number of values that would bias the reduction
+    let excess = u64::MAX % bound;
+    let limit = u64::MAX - excess;
+
+    for _ in 0..MAX_SAMPLING_ATTEMPTS {
+        let mut bytes = [0u8; 8];
+        rng.try_fill_bytes(&mut bytes)
+            .map_err(|_| CardProtocolError::Internal(InternalError::RandomnessFailure))?;
+
+        let x = u64::from_le_bytes(bytes);
+        if x != 0 && x - 1 < limit {
+            return Ok((x - 1) % bound);
+        }
+    }
+
+    Err(CardProtocolError::Internal(
+        InternalError::RandomnessFailure,
+    ))
+}
+
+#[cfg(test)]
+mod test {
+    use super::{check_permutation, combine_contributions, sample_permutation, FromSeed};
+    use crate::crypto_primitives::utils::rand::{test_rng, ExhaustedRng};
+    use crate::crypto_primitives::utils::statistics::assert_uniform;
+    use crate::error::{Bound, CardProtocolError, Dimension, InputError, InternalError};
+
+    use proof_essentials::utils::permutation::Permutation;
+    use std::collections::HashMap;
+
+    #[test]
+    fn test_from_seed_is_deterministic() {
+        let seed = combine_contributions(&[[1u8; 32], [2u8; 32], [3u8; 32]]);
+        let deck = (0..52).collect::<Vec<usize>>();
+
+        let shuffled = Permutation::from_seed(seed, 52).permute_array(&deck);
+        assert_eq!(
+            shuffled,
+            Permutation::from_seed(seed, 52).permute_array(&deck)
+        );
+
+        let mut sorted = shuffled.clone();
+        sorted.sort();
+        assert_eq!(sorted, deck);
+
+        // Changing or reordering a single contribution changes the permutation
+        let other_seed = combine_contributions(&[[2u8; 32], [1u8; 32], [3u8; 32]]);
+        assert_ne!(seed, other_seed);
+        assert_ne!(
+            shuffled,
+            Permutation::from_seed(other_seed, 52).permute_array(&deck)
+        );
+    }
+
+    #[test]
+    fn test_from_seed_is_uniform() {
+        // Chi-square test over the 24 orderings of 4 elements
+        let size = 4;
+        let num_of_orderings = 24;
+        let samples_per_ordering = 500;
+        let num_of_samples = num_of_orderings * samples_per_ordering;
+
+        let deck = (0..size).collect::<Vec<usize>>();
+        let mut counts: HashMap<Vec<usize>, usize> = HashMap::new();
+
+        for i in 0..num_of_samples as u64 {
+            let mut seed = [0u8; 32];
+            seed[..8].copy_from_slice(&i.to_le_bytes());
+            let ordering = Permutation::from_seed(seed, size).permute_array(&deck);
+            *counts.entry(ordering).or_insert(0) += 1;
+        }
+
+        assert_eq!(counts.len(), num_of_orderings);
+
+        let expected = samples_per_ordering as f64;
+        let chi_square: f64 = counts
+            .values()
+            .map(|&count| (count as f64 - expected).powi(2) / expected)
+            .sum();
+
+        // Critical value for 23 degrees of freedom at p = 0.001
+        assert!(chi_square < 49.73, "chi-square statistic {}", chi_square);
+    }
+
+    /// Rank of `ordering` among the orderings of its elements, in lexicographic order.
+    fn lexicographic_rank(ordering: &[usize]) -> usize {
+        ordering.iter().enumerate().fold(0, |rank, (i, &value)| {
+            let smaller_after = ordering[i + 1..]
+                .iter()
+                .filter(|&&later| later < value)
+                .count();
+            rank * (ordering.len() - i) + smaller_after
+        })
+    }
+
+    /// Check that every ordering of `size` elements comes out of `sample` equally often. Catches
+    /// the classic off-by-one Fisher-Yates, which never leaves an element in place.
+    fn assert_uniform_orderings(
+        mut sample: impl FnMut() -> Permutation,
+        size: usize,
+        samples_per_ordering: usize,
+        name: &str,
+    ) {
+        let num_of_orderings = (1..=size).product::<usize>();
+        let deck = (0..size).collect::<Vec<usize>>();
+
+        let mut counts = vec![0; num_of_orderings];
+        for _ in 0..num_of_orderings * samples_per_ordering {
+            counts[lexicographic_rank(&sample().permute_array(&deck))] += 1;
+        }
+
+        assert_uniform(
+            &counts,
+            num_of_orderings - 1,
+            &format!("orderings of {} elements from {}", size, name),
+        );
+    }
+
+    /// Check that every element of `size` lands in every position equally often, for sizes with
+    /// too many orderings to count them all.
+    fn assert_uniform_positions(
+        mut sample: impl FnMut() -> Permutation,
+        size: usize,
+        samples_per_cell: usize,
+        name: &str,
+    ) {
+        let deck = (0..size).collect::<Vec<usize>>();
+
+        let mut counts = vec![0; size * size];
+        for _ in 0..size * samples_per_cell {
+            let ordering = sample().permute_array(&deck);
+            for (position, element) in ordering.into_iter().enumerate() {
+                counts[element * size + position] += 1;
+            }
+        }
+
+        assert_uniform(
+            &counts,
+            (size - 1) * (size - 1),
+            &format!("positions of {} elements from {}", size, name),
+        );
+    }
+
+    #[test]
+    fn test_lexicographic_rank() {
+        assert_eq!(lexicographic_rank(&[0, 1, 2]), 0);
+        assert_eq!(lexicographic_rank(&[0, 2, 1]), 1);
+        assert_eq!(lexicographic_rank(&[1, 0, 2]), 2);
+        assert_eq!(lexicographic_rank(&[2, 1, 0]), 5);
+    }
+
+    /// `sample_permutation` draws the permutation of every shuffle.
+    #[test]
+    #[ignore]
+    fn test_random_permutations_are_uniform() {
+        let rng = &mut test_rng();
+
+        for size in [2, 3, 4, 5] {
+            assert_uniform_orderings(
+                || sample_permutation(rng, size).unwrap(),
+                size,
+                1000,
+                "sample_permutation",
+            );
+        }
+        for size in [8, 13, 52] {
+            assert_uniform_positions(
+                || sample_permutation(rng, size).unwrap(),
+                size,
+                200,
+                "sample_permutation",
+            );
+        }
+    }
+
+    #[test]
+    fn test_sample_permutation() {
+        let rng = &mut test_rng();
+
+        let permutation = sample_permutation(rng, 52).unwrap();
+        assert_eq!(Ok(()), check_permutation(&permutation, 52));
+        assert_eq!(
+            Ok(()),
+            check_permutation(&sample_permutation(rng, 0).unwrap(), 0)
+        );
+
+        // A generator stuck at zero would otherwise always produce the same permutation
+        assert_eq!(
+            sample_permutation(&mut ExhaustedRng, 52).err(),
+            Some(CardProtocolError::Internal(
+                InternalError::RandomnessFailure
+            ))
+        );
+    }
+
+    #[test]
+    #[ignore]
+    fn test_seeded_permutations_are_uniform() {
+        let mut counter = 0u64;
+        let mut sample = |size| {
+            counter += 1;
+            let mut seed = [0u8; 32];
+            seed[..8].copy_from_slice(&counter.to_le_bytes());
+            Permutation::from_seed(seed, size)
+        };
+
+        for size in [2, 3, 4, 5] {
+            assert_uniform_orderings(|| sample(size), size, 1000, "Permutation::from_seed");
+        }
+        for size in [8, 13, 52] {
+            assert_uniform_positions(|| sample(size), size, 200, "Permutation::from_seed");
+        }
+    }
+
+    #[test]
+    fn test_check_permutation() {
+        let permutation = Permutation::from(&vec![2, 0, 1]);
+        assert_eq!(Ok(()), check_permutation(&permutation, 3));
+
+        assert_eq!(
+            check_permutation(&permutation, 4),
+            Err(CardProtocolError::InvalidInput(
+                InputError::DimensionMismatch {
+                    dimension: Dimension::Permutation,
+                    expected: Bound::Exactly(4),
+                    got: 3
+                }
+            ))
+        );
+
+        // Positions out of range, or used twice, would index past the deck or duplicate cards
+        for mapping in [vec![0, 1, 3], vec![0, 1, 1]] {
+            assert_eq!(
+                check_permutation(&Permutation::from(&mapping), 3),
+                Err(CardProtocolError::InvalidInput(
+                    InputError::InvalidPermutation
+                ))
+            );
+        }
+    }
+}
@@ -0,0 +1,692 @@
+//! ristretto255 (RFC 9496), the prime-order group built on the Edwards form of curve25519 in
+//! `crypto_primitives::curve25519`, for clients that already ship Ristretto code.
+//!
+//! An element of the group is a class of points of the even subgroup of the Edwards curve, modulo
+//! its 4-torsion, and is represented here by any point of its class. [`Affine`] and [`Projective`]
+//! wrap the Edwards types, compute on the representative and implement arkworks' curve traits, so
+//! `proof_essentials` runs on the group unchanged:
+//! - equality and hashing are those of the group: two representatives that differ by a 4-torsion
+//!   point are equal and hash alike.
+//! - points are encoded in the 32 bytes of RFC 9496. Decoding rejects non-canonical and negative
+//!   field elements and encodings of no point, so the encoding of an element is unique and every
+//!   decoded value is an element of the group.
+//! - the group has prime order `l`, so its cofactor is 1 and the subgroup checks of `validation`
+//!   have nothing left to check.
+//! - `from_random_bytes` decodes its input as an encoding, so `hash_to_curve` finds an element,
+//!   uniformly distributed over the group, within a few counters.
+//!
+//! Encoding and decoding branch on the point. The protocol only encodes the values it sends to
+//! other players.
+
+use crate::crypto_primitives::curve25519::{self, Fq, Fr};
+
+use ark_ec::models::TEModelParameters;
+use ark_ec::{AffineCurve, ProjectiveCurve};
+use ark_ff::biginteger::BigInteger256;
+use ark_ff::{
+    field_new, BigInteger, Field, FromBytes, One, PrimeField, ToBytes, UniformRand, Zero,
+};
+use ark_serialize::{CanonicalDeserialize, CanonicalSerialize, Read, SerializationError, Write};
+use ark_std::hash::{Hash, Hasher};
+use ark_std::io::{ErrorKind, Result as IoResult};
+use ark_std::ops::{Add, AddAssign, MulAssign, Neg, Sub, SubAssign};
+use ark_std::rand::distributions::{Distribution, Standard};
+use ark_std::rand::Rng;
+use ark_std::vec::Vec;
+use ark_std::{fmt, iter};
+use zeroize::Zeroize;
+
+type EdwardsAffine = curve25519::Affine;
+type EdwardsProjective = curve25519::Projective;
+
+/// Length in bytes of the encoding of an element.
+pub const ENCODED_LENGTH: usize = 32;
+
+/// A square root of -1
+const SQRT_M1: Fq = field_new!(
+    Fq,
+    "19681161376707505956807079304988542015446066515923890162744021073123829784752"
+);
+
+/// 1 / sqrt(a - d), for a = -1
+const INVSQRT_A_MINUS_D: Fq = field_new!(
+    Fq,
+    "54469307008909316920995813868745141605393597292927456921205312896311721017578"
+);
+
+/// (p - 5) / 8, the exponent of the square root of a ratio
+const P_MINUS_5_DIV_8: [u64; 4] = [
+    0xfffffffffffffffd,
+    0xffffffffffffffff,
+    0xffffffffffffffff,
+    0x0fffffffffffffff,
+];
+
+/// An element of ristretto255 in affine coordinates.
+#[derive(Clone, Copy, Default)]
+pub struct Affine(EdwardsAffine);
+
+/// An element of ristretto255 in extended coordinates.
+#[derive(Clone, Copy, Default)]
+pub struct Projective(EdwardsProjective);
+
+/// Whether the canonical representative of `x` is odd, which RFC 9496 calls negative.
+fn is_negative(x: &Fq) -> bool {
+    x.into_repr().is_odd()
+}
+
+fn abs(x: Fq) -> Fq {
+    if is_negative(&x) {
+        -x
+    } else {
+        x
+    }
+}
+
+/// `SQRT_RATIO_M1` of RFC 9496: whether `u / v` is a square, and the non-negative square root of
+/// `u / v` if it is, or of `SQRT_M1 * u / v` if it is not.
+fn sqrt_ratio_m1(u: Fq, v: Fq) -> (bool, Fq) {
+    let v3 = v.square() * v;
+    let v7 = v3.square() * v;
+    let mut r = (u * v3) * (u * v7).pow(P_MINUS_5_DIV_8);
+    let check = v * r.square();
+
+    let correct_sign = check == u;
+    let flipped_sign = check == -u;
+    let flipped_sign_i = check == -u * SQRT_M1;
+    if flipped_sign || flipped_sign_i {
+        r *= SQRT_M1;
+    }
+
+    (correct_sign || flipped_sign, abs(r))
+}
+
+/// Encode the class of the Edwards point `(x0 : y0 : z0 : t0)` in extended coordinates.
+fn encode(x0: Fq, y0: Fq, z0: Fq, t0: Fq) -> [u8; ENCODED_LENGTH] {
+    let u1 = (z0 + y0) * (z0 - y0);
+    let u2 = x0 * y0;
+    let (_, invsqrt) = sqrt_ratio_m1(Fq::one(), u1 * u2.square());
+    let den1 = invsqrt * u1;
+    let den2 = invsqrt * u2;
+    let z_inv = den1 * den2 * t0;
+
+    let (x, mut y, den_inv) = if is_negative(&(t0 * z_inv)) {
+        (y0 * SQRT_M1, x0 * SQRT_M1, den1 * INVSQRT_A_MINUS_D)
+    } else {
+        (x0, y0, den2)
+    };
+    if is_negative(&(x * z_inv)) {
+        y = -y;
+    }
+    let s = abs(den_inv * (z0 - y));
+
+    let mut bytes = [0u8; ENCODED_LENGTH];
+    bytes.copy_from_slice(&s.into_repr().to_bytes_le());
+    bytes
+}
+
+/// Decode an encoding into a representative of its class, or `None` if it is not the canonical
+/// encoding of an element.
+fn decode(bytes: &[u8; ENCODED_LENGTH]) -> Option<EdwardsAffine> {
+    let mut limbs = [0u64; 4];
+    for (limb, chunk) in limbs.iter_mut().zip(bytes.chunks_exact(8)) {
+        let mut word = [0u8; 8];
+        word.copy_from_slice(chunk);
+        *limb = u64::from_le_bytes(word);
+    }
+
+    let s = Fq::from_repr(BigInteger256(limbs))?;
+    if is_negative(&s) {
+        return None;
+    }
+
+    let ss = s.square();
+    let u1 = Fq::one() - ss;
+    let u2 = Fq::one() + ss;
+    let u2_sqr = u2.square();
+    let v = -(curve25519::Parameters::COEFF_D * u1.square()) - u2_sqr;
+    let (was_square, invsqrt) = sqrt_ratio_m1(Fq::one(), v * u2_sqr);
+    let den_x = invsqrt * u2;
+    let den_y = invsqrt * den_x * v;
+
+    let x = abs((s + s) * den_x);
+    let y = u1 * den_y;
+    if !was_square || is_negative(&(x * y)) || y.is_zero() {
+        return None;
+    }
+
+    Some(EdwardsAffine::new(x, y))
+}
+
+impl Affine {
+    /// The RFC 9496 encoding of the element.
+    pub fn encode(&self) -> [u8; ENCODED_LENGTH] {
+        encode(self.0.x, self.0.y, Fq::one(), self.0.x * self.0.y)
+    }
+
+    /// Decode an RFC 9496 encoding, or `None` if it is not the canonical encoding of an element.
+    pub fn decode(bytes: &[u8; ENCODED_LENGTH]) -> Option<Self> {
+        decode(bytes).map(Self)
+    }
+}
+
+impl Projective {
+    /// The RFC 9496 encoding of the element.
+    pub fn encode(&self) -> [u8; ENCODED_LENGTH] {
+        encode(self.0.x, self.0.y, self.0.z, self.0.t)
+    }
+}
+
+/// Representatives are equal in the group when `x1 y2 == y1 x2` or `y1 y2 == x1 x2`, which holds
+/// in projective coordinates as well since both sides scale alike.
+fn equal_classes(x1: Fq, y1: Fq, x2: Fq, y2: Fq) -> bool {
+    x1 * y2 == y1 * x2 || y1 * y2 == x1 * x2
+}
+
+impl PartialEq for Affine {
+    fn eq(&self, other: &Self) -> bool {
+        equal_classes(self.0.x, self.0.y, other.0.x, other.0.y)
+    }
+}
+
+impl Eq for Affine {}
+
+impl PartialEq for Projective {
+    fn eq(&self, other: &Self) -> bool {
+        equal_classes(self.0.x, self.0.y, other.0.x, other.0.y)
+    }
+}
+
+impl Eq for Projective {}
+
+impl Hash for Affine {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.encode().hash(state)
+    }
+}
+
+impl Hash for Projective {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.encode().hash(state)
+    }
+}
+
+fn write_hex(f: &mut fmt::Formatter<'_>, bytes: &[u8]) -> fmt::Result {
+    bytes.iter().try_for_each(|byte| write!(f, "{:02x}", byte))
+}
+
+impl fmt::Display for Affine {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write_hex(f, &self.encode())
+    }
+}
+
+impl fmt::Debug for Affine {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("Affine(")?;
+        write_hex(f, &self.encode())?;
+        f.write_str(")")
+    }
+}
+
+impl fmt::Display for Projective {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write_hex(f, &self.encode())
+    }
+}
+
+impl fmt::Debug for Projective {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("Projective(")?;
+        write_hex(f, &self.encode())?;
+        f.write_str(")")
+    }
+}
+
+impl Zeroize for Affine {
+    fn zeroize(&mut self) {
+        self.0.zeroize();
+    }
+}
+
+impl Zeroize for Projective {
+    fn zeroize(&mut self) {
+        self.0.zeroize();
+    }
+}
+
+/// The identity is the class of the 4-torsion, whose points have `x == 0` or `y == 0`.
+impl Zero for Affine {
+    fn zero() -> Self {
+        Self(EdwardsAffine::zero())
+    }
+
+    fn is_zero(&self) -> bool {
+        self.0.x.is_zero() || self.0.y.is_zero()
+    }
+}
+
+impl Zero for Projective {
+    fn zero() -> Self {
+        Self(EdwardsProjective::zero())
+    }
+
+    fn is_zero(&self) -> bool {
+        self.0.x.is_zero() || self.0.y.is_zero()
+    }
+}
+
+impl Neg for Affine {
+    type Output = Self;
+
+    fn neg(self) -> Self {
+        Self(-self.0)
+    }
+}
+
+impl Neg for Projective {
+    type Output = Self;
+
+    fn neg(self) -> Self {
+        Self(-self.0)
+    }
+}
+
+impl Add<Self> for Affine {
+    type Output = Self;
+
+    fn add(self, other: Self) -> Self {
+        Self(self.0 + other.0)
+    }
+}
+
+impl<'a> AddAssign<&'a Self> for Affine {
+    fn add_assign(&mut self, other: &'a Self) {
+        self.0 = self.0 + other.0;
+    }
+}
+
+impl Add<Self> for Projective {
+    type Output = Self;
+
+    fn add(self, other: Self) -> Self {
+        Self(self.0 + other.0)
+    }
+}
+
+impl<'a> Add<&'a Self> for Projective {
+    type Output = Self;
+
+    fn add(self, other: &'a Self) -> Self {
+        Self(self.0 + other.0)
+    }
+}
+
+impl AddAssign<Self> for Projective {
+    fn add_assign(&mut self, other: Self) {
+        self.0 += other.0;
+    }
+}
+
+impl<'a> AddAssign<&'a Self> for Projective {
+    fn add_assign(&mut self, other: &'a Self) {
+        self.0 += other.0;
+    }
+}
+
+impl Sub<Self> for Projective {
+    type Output = Self;
+
+    fn sub(self, other: Self) -> Self {
+        Self(self.0 - other.0)
+    }
+}
+
+impl<'a> Sub<&'a Self> for Projective {
+    type Output = Self;
+
+    fn sub(self, other: &'a Self) -> Self {
+        Self(self.0 - other.0)
+    }
+}
+
+impl SubAssign<Self> for Projective {
+    fn sub_assign(&mut self, other: Self) {
+        self.0 -= other.0;
+    }
+}
+
+impl<'a> SubAssign<&'a Self> for Projective {
+    fn sub_assign(&mut self, other: &'a Self) {
+        self.0 -= other.0;
+    }
+}
+
+impl MulAssign<Fr> for Projective {
+    fn mul_assign(&mut self, other: Fr) {
+        self.0 *= other;
+    }
+}
+
+impl iter::Sum<Self> for Affine {
+    fn sum<I: Iterator<Item = Self>>(iter: I) -> Self {
+        iter.fold(Self::zero(), Add::add)
+    }
+}
+
+impl<'a> iter::Sum<&'a Self> for Affine {
+    fn sum<I: Iterator<Item = &'a Self>>(iter: I) -> Self {
+        iter.fold(Self::zero(), |acc, point| acc + *point)
+    }
+}
+
+impl iter::Sum<Self> for Projective {
+    fn sum<I: Iterator<Item = Self>>(iter: I) -> Self {
+        iter.fold(Self::zero(), Add::add)
+    }
+}
+
+impl<'a> iter::Sum<&'a Self> for Projective {
+    fn sum<I: Iterator<Item = &'a Self>>(iter: I) -> Self {
+        iter.fold(Self::zero(), |acc, point| acc + point)
+    }
+}
+
+impl From<Projective> for Affine {
+    fn from(point: Projective) -> Self {
+        Self(point.0.into_affine())
+    }
+}
+
+impl From<Affine> for Projective {
+    fn from(point: Affine) -> Self {
+        Self(point.0.into_projective())
+    }
+}
+
+/// A uniform element, as a uniform multiple of the generator.
+impl Distribution<Projective> for Standard {
+    fn sample<R: Rng + ?Sized>(&self, rng: &mut R) -> Projective {
+        Projective::prime_subgroup_generator().mul(Fr::rand(rng).into_repr())
+    }
+}
+
+impl Distribution<Affine> for Standard {
+    fn sample<R: Rng + ?Sized>(&self, rng: &mut R) -> Affine {
+        Projective::rand(rng).into_affine()
+    }
+}
+
+impl CanonicalSerialize for Affine {
+    fn serialize<W: Write>(&self, mut writer: W) -> Result<(), SerializationError> {
+        Ok(writer.write_all(&self.encode())?)
+    }
+
+    fn serialized_size(&self) -> usize {
+        ENCODED_LENGTH
+    }
+}
+
+impl CanonicalDeserialize for Affine {
+    fn deserialize<R: Read>(mut reader: R) -> Result<Self, SerializationError> {
+        let mut bytes = [0u8; ENCODED_LENGTH];
+        reader.read_exact(&mut bytes)?;
+
+        Self::decode(&bytes).ok_or(SerializationError::InvalidData)
+    }
+}
+
+impl CanonicalSerialize for Projective {
+    fn serialize<W: Write>(&self, mut writer: W) -> Result<(), SerializationError> {
+        Ok(writer.write_all(&self.encode())?)
+    }
+
+    fn serialized_size(&self) -> usize {
+        ENCODED_LENGTH
+    }
+}
+
+impl CanonicalDeserialize for Projective {
+    fn deserialize<R: Read>(reader: R) -> Result<Self, SerializationError> {
+        Affine::deserialize(reader).map(Self::from)
+    }
+}
+
+impl ToBytes for Affine {
+    fn write<W: Write>(&self, mut writer: W) -> IoResult<()> {
+        writer.write_all(&self.encode())
+    }
+}
+
+impl FromBytes for Affine {
+    fn read<R: Read>(mut reader: R) -> IoResult<Self> {
+        let mut bytes = [0u8; ENCODED_LENGTH];
+        reader.read_exact(&mut bytes)?;
+
+        Self::decode(&bytes).ok_or_else(|| ErrorKind::InvalidData.into())
+    }
+}
+
+impl ToBytes for Projective {
+    fn write<W: Write>(&self, mut writer: W) -> IoResult<()> {
+        writer.write_all(&self.encode())
+    }
+}
+
+impl FromBytes for Projective {
+    fn read<R: Read>(reader: R) -> IoResult<Self> {
+        Affine::read(reader).map(Self::from)
+    }
+}
+
+impl AffineCurve for Affine {
+    const COFACTOR: &'static [u64] = &[1];
+    type ScalarField = Fr;
+    type BaseField = Fq;
+    type Projective = Projective;
+
+    fn prime_subgroup_generator() -> Self {
+        Self(EdwardsAffine::prime_subgroup_generator())
+    }
+
+    /// Reads the first 32 bytes as an encoding, with the top bit and the sign bit cleared, so that
+    /// about half of all inputs decode.
+    fn from_random_bytes(bytes: &[u8]) -> Option<Self> {
+        let mut encoding = [0u8; ENCODED_LENGTH];
+        encoding.copy_from_slice(bytes.get(..ENCODED_LENGTH)?);
+        encoding[0] &= 0xfe;
+        encoding[ENCODED_LENGTH - 1] &= 0x7f;
+
+        Self::decode(&encoding)
+    }
+
+    fn mul<S: Into<<Fr as PrimeField>::BigInt>>(&self, other: S) -> Projective {
+        Projective(AffineCurve::mul(&self.0, other))
+    }
+
+    fn mul_by_cofactor_to_projective(&self) -> Projective {
+        Projective::from(*self)
+    }
+
+    fn mul_by_cofactor_inv(&self) -> Self {
+        *self
+    }
+}
+
+impl ProjectiveCurve for Projective {
+    const COFACTOR: &'static [u64] = &[1];
+    type ScalarField = Fr;
+    type BaseField = Fq;
+    type Affine = Affine;
+
+    fn prime_subgroup_generator() -> Self {
+        Self(EdwardsProjective::prime_subgroup_generator())
+    }
+
+    fn batch_normalization(v: &mut [Self]) {
+        let mut points = v.iter().map(|point| point.0).collect::<Vec<_>>();
+        EdwardsProjective::batch_normalization(&mut points);
+        for (point, normalized) in v.iter_mut().zip(points) {
+            point.0 = normalized;
+        }
+    }
+
+    fn is_normalized(&self) -> bool {
+        self.0.is_normalized()
+    }
+
+    fn double_in_place(&mut self) -> &mut Self {
+        self.0.double_in_place();
+        self
+    }
+
+    fn add_assign_mixed(&mut self, other: &Affine) {
+        self.0.add_assign_mixed(&other.0);
+    }
+
+    fn mul<S: AsRef<[u64]>>(self, other: S) -> Self {
+        Self(ProjectiveCurve::mul(self.0, other))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{Affine, Projective, ENCODED_LENGTH, SQRT_M1};
+    use crate::crypto_primitives::curve25519::{self, Fq, Fr};
+    use crate::crypto_primitives::hash_to_curve::hash_to_curve;
+    use crate::crypto_primitives::utils::rand::test_rng;
+    use crate::discrete_log_cards::validation::{public_key_from_bytes, validate_point};
+
+    use ark_ec::{AffineCurve, ProjectiveCurve};
+    use ark_ff::{FpParameters, One, PrimeField, UniformRand, Zero};
+    use ark_serialize::{CanonicalDeserialize, CanonicalSerialize};
+    use std::collections::hash_map::DefaultHasher;
+    use std::hash::{Hash, Hasher};
+
+    fn from_hex(hex: &str) -> [u8; ENCODED_LENGTH] {
+        let mut bytes = [0u8; ENCODED_LENGTH];
+        for (i, byte) in bytes.iter_mut().enumerate() {
+            *byte = u8::from_str_radix(&hex[2 * i..2 * i + 2], 16).unwrap();
+        }
+        bytes
+    }
+
+    fn hash_of<T: Hash>(value: &T) -> u64 {
+        let mut hasher = DefaultHasher::new();
+        value.hash(&mut hasher);
+        hasher.finish()
+    }
+
+    #[test]
+    fn test_multiples_of_the_generator() {
+        // The encodings of 0, B, ..., 5B from RFC 9496, appendix A.1
+        let encodings = [
+            "0000000000000000000000000000000000000000000000000000000000000000",
+            "e2f2ae0a6abc4e71a884a961c500515f58e30b6aa582dd8db6a65945e08d2d76",
+            "6a493210f7499cd17fecb510ae0cea23a110e8d5b901f8acadd3095c73a3b919",
+            "94741f5d5d52755ece4f23f044ee27d5d1ea1e2bd196b462166b16152a9d0259",
+            "da80862773358b466ffadfe0b3293ab3d9fd53c5ea6c955358f568322daf6a57",
+            "e882b131016b52c1d3337080187cf768423efccbb517bb495ab812c4160ff44e",
+        ];
+
+        let mut point = Projective::zero();
+        for encoding in encodings.iter() {
+            let bytes = from_hex(encoding);
+            assert_eq!(point.encode(), bytes);
+            assert_eq!(point.into_affine().encode(), bytes);
+            assert_eq!(Affine::decode(&bytes), Some(point.into_affine()));
+
+            point += Projective::prime_subgroup_generator();
+        }
+    }
+
+    #[test]
+    fn test_invalid_encodings_are_rejected() {
+        let encodings = [
+            // Not reduced modulo p
+            "00ffffffffffffffffffffffffffffffffffffffffffffffffffffffffffffff",
+            "ffffffffffffffffffffffffffffffffffffffffffffffffffffffffffffff7f",
+            "edffffffffffffffffffffffffffffffffffffffffffffffffffffffffffff7f",
+            // Negative field elements
+            "0100000000000000000000000000000000000000000000000000000000000000",
+            "01ffffffffffffffffffffffffffffffffffffffffffffffffffffffffffff7f",
+            // Not the encoding of any element
+            "0200000000000000000000000000000000000000000000000000000000000000",
+        ];
+
+        for encoding in encodings.iter() {
+            let bytes = from_hex(encoding);
+            assert_eq!(Affine::decode(&bytes), None, "{}", encoding);
+            assert!(Affine::deserialize(&bytes[..]).is_err(), "{}", encoding);
+        }
+    }
+
+    #[test]
+    fn test_torsion_is_quotiented_out() {
+        let rng = &mut test_rng();
+        let point = Projective::rand(rng).into_affine();
+
+        // Adding a 4-torsion point to the representative gives the same element
+        for torsion in [
+            curve25519::Affine::new(Fq::zero(), -Fq::one()),
+            curve25519::Affine::new(SQRT_M1, Fq::zero()),
+            curve25519::Affine::new(-SQRT_M1, Fq::zero()),
+        ] {
+            let other = Affine(point.0 + torsion);
+            assert_ne!(other.0, point.0);
+            assert_eq!(other, point);
+            assert_eq!(other.encode(), point.encode());
+            assert_eq!(hash_of(&other), hash_of(&point));
+            assert!(Affine(torsion).is_zero());
+        }
+    }
+
+    #[test]
+    fn test_group_laws() {
+        let rng = &mut test_rng();
+
+        for _ in 0..20 {
+            let a = Projective::rand(rng);
+            let b = Projective::rand(rng);
+            let x = Fr::rand(rng);
+
+            assert_eq!(a + b, b + a);
+            assert_eq!(a - a, Projective::zero());
+            assert_eq!(
+                (a + b).mul(x.into_repr()),
+                a.mul(x.into_repr()) + b.mul(x.into_repr())
+            );
+            assert_eq!(a.mul(Fr::from(2u64).into_repr()), a.double());
+            assert_eq!(Projective::from(a.into_affine()), a);
+
+            let mut bytes = Vec::new();
+            a.serialize(&mut bytes).unwrap();
+            assert_eq!(bytes.len(), ENCODED_LENGTH);
+            assert_eq!(Projective::deserialize(&bytes[..]).unwrap(), a);
+        }
+
+        // The generator has order l
+        let g = Affine::prime_subgroup_generator();
+        assert!(!g.is_zero());
+        assert!(g.mul(<Fr as PrimeField>::Params::MODULUS).is_zero());
+    }
+
+    #[test]
+    fn test_points_from_other_players() {
+        let rng = &mut test_rng();
+        let point = Projective::rand(rng).into_affine();
+        assert_eq!(Ok(()), validate_point(&point));
+
+        let mut bytes = Vec::new();
+        point.serialize(&mut bytes).unwrap();
+        assert_eq!(Ok(point), public_key_from_bytes::<Affine>(&bytes));
+
+        // Hashed points are elements of the group like any other
+        let hashed = hash_to_curve::<Projective>(b"domain", b"message").unwrap();
+        assert_eq!(Ok(()), validate_point(&hashed));
+        assert_ne!(
+            hashed,
+            hash_to_curve::<Projective>(b"domain", b"other message").unwrap()
+        );
+    }
+}
@@ -0,0 +1,191 @@
+//! Schnorr signatures over the protocol curve.
+//!
+//! A signature on `msg` is a pair `(R, s)` with `R = g^k` and `s = k + e * sk`, where the challenge
+//! `e` hashes the generator, `R`, the public key and the message. The nonce `k` is derived from the
+//! secret key and the message, so signing is deterministic and needs no randomness.
+
+use crate::crypto_primitives::hash_to_field::hash_to_field;
+use crate::crypto_primitives::utils::format::Hex;
+use crate::crypto_primitives::utils::rand::sample_nonzero;
+use crate::crypto_primitives::utils::secret::wipe;
+use crate::error::{CardProtocolError, VerificationError};
+
+use ark_ec::{AffineCurve, ProjectiveCurve};
+use ark_serialize::{CanonicalDeserialize, CanonicalSerialize};
+use ark_std::fmt;
+use ark_std::rand::{CryptoRng, Rng};
+use ark_std::vec::Vec;
+use zeroize::{Zeroize, ZeroizeOnDrop};
+
+const SIGNATURE_NONCE_DOMAIN: &'static [u8] = b"Schnorr Signature Nonce";
+const SIGNATURE_CHALLENGE_DOMAIN: &'static [u8] = b"Schnorr Signature Challenge";
+
+/// Signing key, wiped when dropped.
+pub struct SigningKey<C: ProjectiveCurve> {
+    sk: C::ScalarField,
+    pk: VerifyingKey<C>,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, CanonicalSerialize, CanonicalDeserialize)]
+pub struct VerifyingKey<C: ProjectiveCurve>(pub C::Affine);
+
+#[derive(Clone, Copy, PartialEq, Eq, CanonicalSerialize, CanonicalDeserialize)]
+pub struct Signature<C: ProjectiveCurve> {
+    r: C::Affine,
+    s: C::ScalarField,
+}
+
+impl<C: ProjectiveCurve> SigningKey<C> {
+    pub fn new<R: Rng + CryptoRng>(rng: &mut R) -> Result<Self, CardProtocolError> {
+        Ok(Self::from_scalar(sample_nonzero(rng)?))
+    }
+
+    pub fn from_scalar(sk: C::ScalarField) -> Self {
+        let pk = VerifyingKey(C::Affine::prime_subgroup_generator().mul(sk).into_affine());
+
+        Self { sk, pk }
+    }
+
+    pub fn verifying_key(&self) -> VerifyingKey<C> {
+        self.pk
+    }
+
+    pub fn sign(&self, msg: &[u8]) -> Result<Signature<C>, CardProtocolError> {
+        let mut nonce_input = Vec::new();
+        self.sk.serialize(&mut nonce_input)?;
+        nonce_input.extend_from_slice(msg);
+        let mut k: C::ScalarField = hash_to_field(SIGNATURE_NONCE_DOMAIN, &nonce_input);
+        nonce_input.zeroize();
+
+        let r = C::Affine::prime_subgroup_generator().mul(k).into_affine();
+        let s = challenge::<C>(&r, &self.pk.0, msg).map(|e| k + e * self.sk);
+
+        // The nonce reveals the secret key together with the signature, so it must not outlive it
+        wipe(ark_std::slice::from_mut(&mut k));
+
+        Ok(Signature { r, s: s? })
+    }
+}
+
+impl<C: ProjectiveCurve> fmt::Debug for SigningKey<C> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("SigningKey")
+            .field("pk", &self.pk)
+            .finish_non_exhaustive()
+    }
+}
+
+impl<C: ProjectiveCurve> Zeroize for SigningKey<C> {
+    fn zeroize(&mut self) {
+        wipe(ark_std::slice::from_mut(&mut self.sk));
+    }
+}
+
+impl<C: ProjectiveCurve> Drop for SigningKey<C> {
+    fn drop(&mut self) {
+        self.zeroize();
+    }
+}
+
+impl<C: ProjectiveCurve> ZeroizeOnDrop for SigningKey<C> {}
+
+impl<C: ProjectiveCurve> VerifyingKey<C> {
+    /// Check that `signature` is a signature on `msg` under this key.
+    pub fn verify(&self, msg: &[u8], signature: &Signature<C>) -> Result<(), CardProtocolError> {
+        let e = challenge::<C>(&signature.r, &self.0, msg)?;
+
+        // g^s = R * pk^e
+        let expected_r = C::Affine::prime_subgroup_generator().mul(signature.s) - self.0.mul(e);
+        if expected_r.into_affine() != signature.r {
+            return Err(VerificationError::InvalidSignature.into());
+        }
+
+        Ok(())
+    }
+}
+
+impl<C: ProjectiveCurve> fmt::Debug for VerifyingKey<C> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "VerifyingKey({:?})", Hex(&self.0))
+    }
+}
+
+impl<C: ProjectiveCurve> fmt::Debug for Signature<C> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Signature({:?})", Hex(self))
+    }
+}
+
+fn challenge<C: ProjectiveCurve>(
+    r: &C::Affine,
+    pk: &C::Affine,
+    msg: &[u8],
+) -> Result<C::ScalarField, CardProtocolError> {
+    let mut bytes = Vec::new();
+    for point in [&C::Affine::prime_subgroup_generator(), r, pk] {
+        point.serialize(&mut bytes)?;
+    }
+    bytes.extend_from_slice(msg);
+
+    Ok(hash_to_field(SIGNATURE_CHALLENGE_DOMAIN, &bytes))
+}
+
+#[cfg(test)]
+mod test {
+    use super::SigningKey;
+    use crate::crypto_primitives::utils::format::Hex;
+    use crate::crypto_primitives::utils::rand::test_rng;
+    use crate::error::{CardProtocolError, VerificationError};
+
+    // Choose elliptic curve setting
+    type Curve = starknet_curve::Projective;
+
+    #[test]
+    fn test_signature() {
+        let rng = &mut test_rng();
+
+        let sk = SigningKey::<Curve>::new(rng).unwrap();
+        let pk = sk.verifying_key();
+
+        let signature = sk.sign(b"message").unwrap();
+        assert_eq!(Ok(()), pk.verify(b"message", &signature));
+
+        // Signing is deterministic
+        assert_eq!(signature, sk.sign(b"message").unwrap());
+
+        assert_eq!(
+            pk.verify(b"other message", &signature),
+            Err(CardProtocolError::VerificationFailed(
+                VerificationError::InvalidSignature
+            ))
+        );
+
+        let other_pk = SigningKey::<Curve>::new(rng).unwrap().verifying_key();
+        assert_eq!(
+            other_pk.verify(b"message", &signature),
+            Err(CardProtocolError::VerificationFailed(
+                VerificationError::InvalidSignature
+            ))
+        );
+    }
+
+    #[test]
+    fn test_debug() {
+        let rng = &mut test_rng();
+
+        let sk = SigningKey::<Curve>::new(rng).unwrap();
+        let pk = sk.verifying_key();
+        let signature = sk.sign(b"message").unwrap();
+
+        let formatted_pk = format!("VerifyingKey({:?})", Hex(&pk.0));
+        assert_eq!(formatted_pk, format!("{:?}", pk));
+        assert_eq!(
+            format!("SigningKey {{ pk: {}, .. }}", formatted_pk),
+            format!("{:?}", sk)
+        );
+        assert_eq!(
+            format!("Signature({:?})", Hex(&signature)),
+            format!("{:?}", signature)
+        );
+    }
+}
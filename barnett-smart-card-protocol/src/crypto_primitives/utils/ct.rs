@@ -0,0 +1,107 @@
+//! Constant-time comparisons.
+//!
+//! Derived `PartialEq` on field elements, points and byte arrays returns at the first difference,
+//! so comparing a secret-derived value leaks how much of it an attacker guessed right. Comparisons
+//! in which either side depends on a secret go through [`ct_eq`], [`ct_eq_bytes`] or
+//! [`ct_position`] instead. Values are compared through their canonical serialization, which
+//! processes every limb regardless of its value.
+//!
+//! The remaining comparisons in the library are variable time on purpose, because both sides are
+//! public:
+//! - proof and commitment verification (`verify_opening`, the VRF checks, `validate_point`),
+//!   which only involve broadcast values;
+//! - the commitment and identity checks of the key setup, on revealed public keys;
+//! - share indices and thresholds in key sharing, which are stored alongside the shares in the
+//!   clear;
+//! - the decoding of lifted values, whose result is the public outcome of an unmasking.
+//!
+//! The zero checks on freshly sampled or derived secrets (`sample_nonzero`, key derivation, the
+//! ECIES shared point) branch on a secret, but only on an event of negligible probability.
+
+use crate::error::CardProtocolError;
+
+use ark_serialize::CanonicalSerialize;
+use ark_std::vec::Vec;
+use subtle::{Choice, ConditionallySelectable, ConstantTimeEq};
+
+/// Compare two byte strings in time that depends only on their lengths.
+pub fn ct_eq_bytes(a: &[u8], b: &[u8]) -> bool {
+    a.ct_eq(b).into()
+}
+
+/// Compare two values in constant time through their canonical serialization.
+pub fn ct_eq<T: CanonicalSerialize>(a: &T, b: &T) -> Result<bool, CardProtocolError> {
+    Ok(ct_eq_bytes(&serialize(a)?, &serialize(b)?))
+}
+
+/// Index of the first element of `haystack` equal to `needle`. Every element is compared, so the
+/// running time reveals neither the index nor whether the needle was found. Use this rather than
+/// a map lookup to decode a privately opened card.
+pub fn ct_position<'a, T: CanonicalSerialize + 'a>(
+    needle: &T,
+    haystack: impl IntoIterator<Item = &'a T>,
+) -> Result<Option<usize>, CardProtocolError> {
+    let needle = serialize(needle)?;
+
+    let mut found = Choice::from(0);
+    let mut position = 0u64;
+    for (i, candidate) in haystack.into_iter().enumerate() {
+        let is_match = needle[..].ct_eq(&serialize(candidate)?[..]) & !found;
+        position.conditional_assign(&(i as u64), is_match);
+        found |= is_match;
+    }
+
+    Ok(bool::from(found).then(|| position as usize))
+}
+
+fn serialize<T: CanonicalSerialize>(value: &T) -> Result<Vec<u8>, CardProtocolError> {
+    let mut bytes = Vec::with_capacity(value.serialized_size());
+    value.serialize(&mut bytes)?;
+
+    Ok(bytes)
+}
+
+#[cfg(test)]
+mod test {
+    use super::{ct_eq, ct_eq_bytes, ct_position};
+    use crate::crypto_primitives::utils::rand::test_rng;
+
+    use ark_ff::UniformRand;
+    use proof_essentials::utils::rand::sample_vector;
+
+    type Scalar = starknet_curve::Fr;
+    type Point = starknet_curve::Affine;
+
+    #[test]
+    fn test_ct_eq() {
+        let rng = &mut test_rng();
+
+        let x = Scalar::rand(rng);
+        let y = Scalar::rand(rng);
+        assert_eq!(Ok(true), ct_eq(&x, &x.clone()));
+        assert_eq!(Ok(false), ct_eq(&x, &y));
+
+        let p = Point::rand(rng);
+        assert_eq!(Ok(true), ct_eq(&p, &p.clone()));
+        assert_eq!(Ok(false), ct_eq(&p, &Point::rand(rng)));
+
+        assert!(ct_eq_bytes(b"tag", b"tag"));
+        assert!(!ct_eq_bytes(b"tag", b"tab"));
+        assert!(!ct_eq_bytes(b"tag", b"tags"));
+    }
+
+    #[test]
+    fn test_ct_position() {
+        let rng = &mut test_rng();
+
+        let mut deck: Vec<Point> = sample_vector(rng, 52);
+        deck[40] = deck[17];
+
+        // The first match wins, as with `Iterator::position`
+        assert_eq!(Ok(Some(17)), ct_position(&deck[17], &deck));
+        assert_eq!(Ok(Some(0)), ct_position(&deck[0], &deck));
+        assert_eq!(Ok(Some(51)), ct_position(&deck[51], &deck));
+        assert_eq!(Ok(None), ct_position(&Point::rand(rng), &deck));
+        assert_eq!(Ok(None), ct_position(&deck[0], &[]));
+    }
+}
@@ -0,0 +1,289 @@
+//! Compact formatting of group elements and proofs. Their `Debug` output prints every projective
+//! coordinate in full, so the types of the crate print them instead as truncated hex of their
+//! compressed encoding: the first two bytes and the last one, as in `0x3fa1…c2`.
+//!
+//! Cards, masked cards, reveal tokens and proofs are types of `proof_essentials`, whose `Debug`
+//! this crate cannot replace. Wrap them in [`Compact`] to print them the same way:
+//!
+//! ```ignore
+//! println!("{:?}", Compact(&masked_card)); // MaskedCard(0x3fa1…c2, 0x90de…77)
+//! println!("{}", Compact(&card)); // Q♥, or the hex of a card outside the standard deck
+//! ```
+//!
+//! Secrets are never printed: no secret type implements [`CompactFormat`], and the secret types of
+//! the crate print as `REDACTED`.
+
+use crate::playing_card::ClassicPlayingCard;
+
+use ark_ec::ProjectiveCurve;
+use ark_serialize::CanonicalSerialize;
+use ark_std::fmt;
+use ark_std::vec::Vec;
+use proof_essentials::homomorphic_encryption::el_gamal::{self, ElGamal};
+use proof_essentials::vector_commitment::pedersen::PedersenCommitment;
+use proof_essentials::zkp::arguments::shuffle;
+use proof_essentials::zkp::proofs::{chaum_pedersen_dl_equality, schnorr_identification};
+
+/// Number of leading bytes of an encoding that [`Hex`] prints.
+const HEAD_BYTES: usize = 2;
+
+/// Number of trailing bytes of an encoding that [`Hex`] prints.
+const TAIL_BYTES: usize = 1;
+
+/// Truncated hex of the compressed encoding of a value, such as `0x3fa1…c2`. Encodings too short to
+/// truncate are printed whole. Only for public values: a secret would leak three of its bytes.
+pub(crate) struct Hex<'a, T>(pub(crate) &'a T);
+
+/// Truncated hex of raw bytes, such as a context or a digest, in the same form as [`Hex`].
+pub(crate) struct HexBytes<'a>(pub(crate) &'a [u8]);
+
+fn fmt_truncated(bytes: &[u8], f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    let write_bytes = |f: &mut fmt::Formatter<'_>, bytes: &[u8]| {
+        bytes.iter().try_for_each(|byte| write!(f, "{:02x}", byte))
+    };
+
+    f.write_str("0x")?;
+    if bytes.len() <= HEAD_BYTES + TAIL_BYTES {
+        return write_bytes(f, bytes);
+    }
+
+    write_bytes(f, &bytes[..HEAD_BYTES])?;
+    f.write_str("…")?;
+    write_bytes(f, &bytes[bytes.len() - TAIL_BYTES..])
+}
+
+impl<T: CanonicalSerialize> fmt::Debug for Hex<'_, T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut bytes = Vec::new();
+        self.0.serialize(&mut bytes).map_err(|_| fmt::Error)?;
+
+        fmt_truncated(&bytes, f)
+    }
+}
+
+impl<T: CanonicalSerialize> fmt::Display for Hex<'_, T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt::Debug::fmt(self, f)
+    }
+}
+
+impl fmt::Debug for HexBytes<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt_truncated(self.0, f)
+    }
+}
+
+/// Public values with a compact rendering, printed through [`Compact`].
+pub trait CompactFormat {
+    fn fmt_compact(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result;
+}
+
+/// Prints a value of another crate in its compact form. See the module documentation.
+pub struct Compact<'a, T: ?Sized>(pub &'a T);
+
+impl<T: CompactFormat + ?Sized> fmt::Debug for Compact<'_, T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.0.fmt_compact(f)
+    }
+}
+
+/// A card prints as the classic playing card it encodes, such as `Q♥`, and any other value,
+/// including a reveal token, as its hex. The lookup encodes the whole standard deck, so it is meant
+/// for logs and tests rather than for a hot path.
+impl<C: ProjectiveCurve> fmt::Display for Compact<'_, el_gamal::Plaintext<C>> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match ClassicPlayingCard::try_from_card(self.0) {
+            Ok(card) => fmt::Display::fmt(&card, f),
+            Err(_) => fmt::Debug::fmt(&Hex(self.0), f),
+        }
+    }
+}
+
+impl<T: CompactFormat> CompactFormat for [T] {
+    fn fmt_compact(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_list().entries(self.iter().map(Compact)).finish()
+    }
+}
+
+impl<T: CompactFormat> CompactFormat for Vec<T> {
+    fn fmt_compact(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.as_slice().fmt_compact(f)
+    }
+}
+
+impl<T: CompactFormat> CompactFormat for Option<T> {
+    fn fmt_compact(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Some(value) => f.debug_tuple("Some").field(&Compact(value)).finish(),
+            None => f.write_str("None"),
+        }
+    }
+}
+
+/// Reveal tokens with their proofs, each for the card at the given deck index, as in messages.
+pub(crate) struct IndexedTokens<'a, C: ProjectiveCurve>(
+    pub(crate)  &'a [(
+        usize,
+        el_gamal::Plaintext<C>,
+        chaum_pedersen_dl_equality::proof::Proof<C>,
+    )],
+);
+
+impl<C: ProjectiveCurve> fmt::Debug for IndexedTokens<'_, C> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_list()
+            .entries(
+                self.0
+                    .iter()
+                    .map(|(index, token, proof)| (index, Compact(token), Compact(proof))),
+            )
+            .finish()
+    }
+}
+
+/// Cards and reveal tokens
+impl<C: ProjectiveCurve> CompactFormat for el_gamal::Plaintext<C> {
+    fn fmt_compact(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt::Debug::fmt(&Hex(self), f)
+    }
+}
+
+/// Masked cards
+impl<C: ProjectiveCurve> CompactFormat for el_gamal::Ciphertext<C> {
+    fn fmt_compact(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "MaskedCard({:?}, {:?})", Hex(&self.0), Hex(&self.1))
+    }
+}
+
+/// Proofs print their type, the hex of their encoding and its length.
+fn fmt_proof<T: CanonicalSerialize>(
+    name: &str,
+    proof: &T,
+    f: &mut fmt::Formatter<'_>,
+) -> fmt::Result {
+    write!(
+        f,
+        "{}({:?}, {} bytes)",
+        name,
+        Hex(proof),
+        proof.serialized_size()
+    )
+}
+
+/// Key ownership proofs
+impl<C: ProjectiveCurve> CompactFormat for schnorr_identification::proof::Proof<C> {
+    fn fmt_compact(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt_proof("SchnorrProof", self, f)
+    }
+}
+
+/// Reveal proofs
+impl<C: ProjectiveCurve> CompactFormat for chaum_pedersen_dl_equality::proof::Proof<C> {
+    fn fmt_compact(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt_proof("ChaumPedersenProof", self, f)
+    }
+}
+
+/// Shuffle proofs
+impl<C: ProjectiveCurve> CompactFormat
+    for shuffle::proof::Proof<C::ScalarField, ElGamal<C>, PedersenCommitment<C>>
+{
+    fn fmt_compact(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt_proof("ShuffleProof", self, f)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{Compact, Hex, HexBytes};
+    use crate::crypto_primitives::utils::rand::test_rng;
+    use crate::discrete_log_cards::test_helpers::{prove_keys, reveal_tokens, seeded_table};
+    use crate::discrete_log_cards::{Card, MaskedCard};
+    use crate::playing_card::ClassicPlayingCard;
+
+    use ark_ec::ProjectiveCurve;
+    use ark_ff::Zero;
+    use ark_serialize::CanonicalSerialize;
+    use proof_essentials::homomorphic_encryption::el_gamal;
+
+    // Choose elliptic curve setting
+    type Curve = starknet_curve::Projective;
+    type Affine = <Curve as ProjectiveCurve>::Affine;
+
+    #[test]
+    fn test_hex() {
+        assert_eq!("0x8877…11", format!("{:?}", Hex(&0x1122334455667788u64)));
+        assert_eq!("0x3412", format!("{}", Hex(&0x1234u16)));
+        assert_eq!("0x6d65…65", format!("{:?}", HexBytes(b"message")));
+        assert_eq!("0x", format!("{:?}", HexBytes(b"")));
+
+        // The point at infinity encodes as zeros with the infinity flag in the last byte
+        let card: Card<Curve> = el_gamal::Plaintext(Affine::zero());
+        let masked_card: MaskedCard<Curve> = el_gamal::Ciphertext(Affine::zero(), Affine::zero());
+        assert_eq!("0x0000…40", format!("{:?}", Compact(&card)));
+        assert_eq!(
+            "MaskedCard(0x0000…40, 0x0000…40)",
+            format!("{:?}", Compact(&masked_card))
+        );
+        assert_eq!(
+            "[MaskedCard(0x0000…40, 0x0000…40)]",
+            format!("{:?}", Compact(&vec![masked_card]))
+        );
+        assert_eq!("Some(0x0000…40)", format!("{:?}", Compact(&Some(card))));
+
+        // Values outside the standard deck display as hex
+        assert_eq!("0x0000…40", format!("{}", Compact(&card)));
+    }
+
+    #[test]
+    fn test_card_display() {
+        let queen = "Q♥".parse::<ClassicPlayingCard>().unwrap();
+        assert_eq!(
+            "Q♥",
+            Compact(&queen.to_card::<Curve>().unwrap()).to_string()
+        );
+
+        let table = seeded_table::<Curve>(7, 2).unwrap();
+        for card in &table.cards {
+            let classic = ClassicPlayingCard::try_from_card(card).unwrap();
+            assert_eq!(classic.to_string(), Compact(card).to_string());
+        }
+    }
+
+    #[test]
+    fn test_proof_formatting() {
+        let rng = &mut test_rng();
+        let table = seeded_table::<Curve>(7, 2).unwrap();
+
+        let key_proof_info = prove_keys(rng, &table.parameters, b"", &table.players).unwrap();
+        let key_proof = &key_proof_info[0].1;
+        assert_eq!(
+            format!(
+                "SchnorrProof({:?}, {} bytes)",
+                Hex(key_proof),
+                key_proof.serialized_size()
+            ),
+            format!("{:?}", Compact(key_proof))
+        );
+
+        let decryption_key =
+            reveal_tokens(rng, &table.parameters, b"", &table.players, &table.deck[0]).unwrap();
+        let (token, proof, _) = &decryption_key[0];
+        assert_eq!(format!("{:?}", Hex(token)), format!("{:?}", Compact(token)));
+        assert_eq!(
+            format!(
+                "ChaumPedersenProof({:?}, {} bytes)",
+                Hex(proof),
+                proof.serialized_size()
+            ),
+            format!("{:?}", Compact(proof))
+        );
+
+        // Every element prints in the same width as `0x3fa1…c2`
+        let masked_card = format!("{:?}", Compact(&table.deck[0]));
+        assert_eq!(
+            "MaskedCard(0x3fa1…c2, 0x90de…77)".chars().count(),
+            masked_card.chars().count()
+        );
+    }
+}
@@ -0,0 +1,11 @@
+//! Small helpers shared by the protocol and by applications building on it.
+
+pub mod ct;
+pub mod format;
+pub mod msm;
+pub mod rand;
+pub mod secret;
+#[cfg(test)]
+pub(crate) mod statistics;
+#[cfg(all(feature = "std", any(test, feature = "test-utils")))]
+pub mod tamper;
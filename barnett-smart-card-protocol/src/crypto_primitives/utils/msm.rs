@@ -0,0 +1,197 @@
+//! Multi-scalar multiplication over the protocol curve.
+//!
+//! [`msm`] evaluates `sum_i scalars[i] * bases[i]` with Pippenger's algorithm and suits one-off
+//! computations. When the same bases are used many times, e.g. the cards of a known deck or the
+//! generators of a commitment key, [`MsmPrecompute`] stores multiples of every base so that each
+//! evaluation only needs additions.
+
+use crate::error::{check_dimension, CardProtocolError, Dimension};
+
+use ark_ec::msm::VariableBaseMSM;
+use ark_ec::{AffineCurve, ProjectiveCurve};
+use ark_ff::{BigInteger, PrimeField, Zero};
+use ark_std::fmt;
+use ark_std::vec::Vec;
+
+/// Compute `sum_i scalars[i] * bases[i]`.
+pub fn msm<C: ProjectiveCurve>(
+    bases: &[C::Affine],
+    scalars: &[C::ScalarField],
+) -> Result<C, CardProtocolError> {
+    check_dimension(Dimension::Scalars, bases.len(), scalars.len())?;
+
+    let scalars = scalars.iter().map(|s| s.into_repr()).collect::<Vec<_>>();
+
+    Ok(VariableBaseMSM::multi_scalar_mul(bases, &scalars))
+}
+
+/// Window size, in bits, of the tables built for `num_bases` bases. Every base gets
+/// `2^window * ceil(scalar_bits / window)` precomputed points: wider windows make evaluation
+/// cheaper, so they are used while the total table size stays modest.
+pub fn window_size(num_bases: usize) -> usize {
+    match num_bases {
+        0..=16 => 6,
+        17..=1024 => 5,
+        _ => 4,
+    }
+}
+
+/// Tables of multiples of a fixed list of bases.
+pub struct MsmPrecompute<C: ProjectiveCurve> {
+    window: usize,
+    num_windows: usize,
+    /// For base `i`, entry `j * 2^window + d` of `tables[i]` is `d * 2^(j * window) * bases[i]`
+    tables: Vec<Vec<C::Affine>>,
+}
+
+impl<C: ProjectiveCurve> fmt::Debug for MsmPrecompute<C> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("MsmPrecompute")
+            .field("bases", &self.tables.len())
+            .field("window", &self.window)
+            .field("num_windows", &self.num_windows)
+            .finish()
+    }
+}
+
+impl<C: ProjectiveCurve> MsmPrecompute<C> {
+    pub fn new(bases: &[C::Affine]) -> Self {
+        let window = window_size(bases.len());
+        let num_windows = (C::ScalarField::size_in_bits() + window - 1) / window;
+
+        let tables = bases
+            .iter()
+            .map(|base| {
+                let mut table = Vec::with_capacity(num_windows << window);
+                let mut window_base = base.into_projective();
+                for _ in 0..num_windows {
+                    let mut multiple = C::zero();
+                    for _ in 0..(1 << window) {
+                        table.push(multiple);
+                        multiple += &window_base;
+                    }
+                    // After the loop `multiple` is 2^window times the base of this window
+                    window_base = multiple;
+                }
+
+                C::batch_normalization_into_affine(&table)
+            })
+            .collect();
+
+        Self {
+            window,
+            num_windows,
+            tables,
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.tables.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.tables.is_empty()
+    }
+
+    /// Compute `sum_i scalars[i] * bases[i]` for the bases given to [`MsmPrecompute::new`].
+    pub fn evaluate(&self, scalars: &[C::ScalarField]) -> Result<C, CardProtocolError> {
+        check_dimension(Dimension::Scalars, self.tables.len(), scalars.len())?;
+
+        let mut result = C::zero();
+        for (table, scalar) in self.tables.iter().zip(scalars.iter()) {
+            let bits = scalar.into_repr().to_bits_le();
+
+            for j in 0..self.num_windows {
+                let digit = bits
+                    .iter()
+                    .skip(j * self.window)
+                    .take(self.window)
+                    .enumerate()
+                    .fold(0usize, |acc, (k, &bit)| acc | ((bit as usize) << k));
+
+                if digit != 0 {
+                    result.add_assign_mixed(&table[(j << self.window) + digit]);
+                }
+            }
+        }
+
+        Ok(result)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{msm, MsmPrecompute};
+    use crate::crypto_primitives::utils::rand::test_rng;
+    use crate::error::{Bound, CardProtocolError, Dimension, InputError};
+
+    use ark_ec::{AffineCurve, ProjectiveCurve};
+    use ark_ff::{PrimeField, UniformRand, Zero};
+    use proof_essentials::utils::rand::sample_vector;
+
+    // Choose elliptic curve setting
+    type Curve = starknet_curve::Projective;
+    type Affine = starknet_curve::Affine;
+    type Scalar = starknet_curve::Fr;
+
+    fn naive_msm(bases: &[Affine], scalars: &[Scalar]) -> Curve {
+        bases
+            .iter()
+            .zip(scalars.iter())
+            .fold(Curve::zero(), |acc, (base, scalar)| {
+                acc + base.mul(scalar.into_repr())
+            })
+    }
+
+    #[test]
+    fn test_msm_matches_naive_computation() {
+        let rng = &mut test_rng();
+
+        for &size in &[0, 1, 2, 17, 52] {
+            let bases: Vec<Affine> = (0..size).map(|_| Curve::rand(rng).into_affine()).collect();
+            let scalars: Vec<Scalar> = sample_vector(rng, size);
+
+            let expected = naive_msm(&bases, &scalars);
+            assert_eq!(Ok(expected), msm::<Curve>(&bases, &scalars));
+            assert_eq!(
+                Ok(expected),
+                MsmPrecompute::<Curve>::new(&bases).evaluate(&scalars)
+            );
+        }
+    }
+
+    #[test]
+    fn test_precompute_edge_scalars() {
+        let rng = &mut test_rng();
+
+        let bases: Vec<Affine> = (0..3).map(|_| Curve::rand(rng).into_affine()).collect();
+        let precompute = MsmPrecompute::<Curve>::new(&bases);
+
+        let scalars = vec![Scalar::zero(), -Scalar::from(1u64), Scalar::from(1u64)];
+        assert_eq!(
+            Ok(naive_msm(&bases, &scalars)),
+            precompute.evaluate(&scalars)
+        );
+
+        assert_eq!(
+            precompute.evaluate(&scalars[..2]),
+            Err(CardProtocolError::InvalidInput(
+                InputError::DimensionMismatch {
+                    dimension: Dimension::Scalars,
+                    expected: Bound::Exactly(3),
+                    got: 2
+                }
+            ))
+        );
+        assert_eq!(
+            msm::<Curve>(&bases, &scalars[..2]),
+            Err(CardProtocolError::InvalidInput(
+                InputError::DimensionMismatch {
+                    dimension: Dimension::Scalars,
+                    expected: Bound::Exactly(3),
+                    got: 2
+                }
+            ))
+        );
+    }
+}
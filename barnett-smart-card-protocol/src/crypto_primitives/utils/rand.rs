@@ -0,0 +1,616 @@
+use crate::crypto_primitives::matrix::Matrix;
+use crate::error::{CardProtocolError, InputError, InternalError};
+
+use ark_ff::{Field, ToBytes};
+use ark_serialize::CanonicalSerialize;
+use ark_std::collections::BTreeSet;
+use ark_std::rand::{CryptoRng, Rng, RngCore, SeedableRng};
+use ark_std::vec::Vec;
+use blake2::{Blake2s, Digest};
+use proof_essentials::error::CryptoError;
+use rand_chacha::ChaCha20Rng;
+use zeroize::Zeroize;
+
+const GUARD_SEED: &'static [u8] = b"Randomness Guard";
+const HEDGE_SEED: &'static [u8] = b"Hedged Proof Randomness";
+#[cfg(all(feature = "std", any(test, feature = "test-utils")))]
+const TEST_STREAM_SEED: &'static [u8] = b"Test Stream";
+
+/// Number of zero draws after which the generator is considered broken. A working generator
+/// produces even two zero field elements in a row with negligible probability.
+pub(crate) const MAX_SAMPLING_ATTEMPTS: usize = 64;
+
+/// Sample a uniformly random non-zero field element. Fails rather than looping forever if the
+/// generator keeps producing zero, as an exhausted or broken generator would.
+pub fn sample_nonzero<F: Field, R: Rng + CryptoRng>(rng: &mut R) -> Result<F, CardProtocolError> {
+    for _ in 0..MAX_SAMPLING_ATTEMPTS {
+        let x = F::rand(rng);
+        if !x.is_zero() {
+            return Ok(x);
+        }
+    }
+
+    Err(CardProtocolError::Internal(
+        InternalError::RandomnessFailure,
+    ))
+}
+
+/// Sample `length` uniformly random non-zero field elements, e.g. to use as masking factors.
+pub fn sample_nonzero_vector<F: Field, R: Rng + CryptoRng>(
+    rng: &mut R,
+    length: usize,
+) -> Result<Vec<F>, CardProtocolError> {
+    (0..length).map(|_| sample_nonzero(rng)).collect()
+}
+
+/// Records the masking factors supplied by the caller during a session and rejects zero factors
+/// and factors used before. Reusing a factor for two cards makes their ciphertexts linkable. Only
+/// digests of the factors are kept, so the guard does not extend their lifetime.
+///
+/// In strict mode every caller-supplied factor is rejected, so that only the `_with_rng` variants,
+/// which always sample their factors internally, can be used.
+#[derive(Clone, Debug, Default)]
+pub struct RandomnessGuard {
+    strict: bool,
+    used: BTreeSet<[u8; 32]>,
+}
+
+impl RandomnessGuard {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn strict() -> Self {
+        Self {
+            strict: true,
+            used: BTreeSet::new(),
+        }
+    }
+
+    /// Number of factors registered so far.
+    pub fn len(&self) -> usize {
+        self.used.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.used.is_empty()
+    }
+
+    /// Check a factor and record it as used.
+    pub fn register<F: Field>(&mut self, factor: &F) -> Result<(), CardProtocolError> {
+        self.register_all(ark_std::slice::from_ref(factor))
+    }
+
+    /// Check a batch of factors and record them as used. Either every factor is recorded or, if
+    /// any is rejected, none is.
+    pub fn register_all<F: Field>(&mut self, factors: &[F]) -> Result<(), CardProtocolError> {
+        if self.strict {
+            return Err(InputError::CallerSuppliedRandomness.into());
+        }
+
+        let mut digests = BTreeSet::new();
+        for factor in factors {
+            if factor.is_zero() {
+                return Err(InputError::ZeroMaskingFactor.into());
+            }
+
+            let digest = factor_digest(factor)?;
+            if self.used.contains(&digest) || !digests.insert(digest) {
+                return Err(InputError::ReusedMaskingFactor.into());
+            }
+        }
+
+        self.used.extend(digests);
+
+        Ok(())
+    }
+}
+
+fn factor_digest<F: Field>(factor: &F) -> Result<[u8; 32], CardProtocolError> {
+    let mut bytes = Vec::new();
+    factor.serialize(&mut bytes)?;
+
+    let mut hasher = Blake2s::new();
+    hasher.update(GUARD_SEED);
+    hasher.update(&bytes);
+
+    let mut digest = [0u8; 32];
+    digest.copy_from_slice(&hasher.finalize());
+
+    Ok(digest)
+}
+
+/// Generator for the nonces of a proof, keyed with fresh randomness from `rng` together with the
+/// `witness`, the transcript `seed` and the public `statement`. If `rng` is broken or exhausted
+/// the nonces still depend on the witness, so they neither reveal it nor repeat across
+/// statements; if `rng` works they stay fresh even when the same statement is proven twice.
+pub(crate) fn hedged_rng<R: Rng + CryptoRng, W: ToBytes, S: ToBytes>(
+    rng: &mut R,
+    witness: &[W],
+    seed: &[u8],
+    statement: &[S],
+) -> Result<ChaCha20Rng, CryptoError> {
+    let mut fresh = [0u8; 32];
+    rng.fill_bytes(&mut fresh);
+
+    let mut witness_bytes = Vec::new();
+    let written = witness
+        .iter()
+        .try_for_each(|element| element.write(&mut witness_bytes));
+
+    let mut hasher = Blake2s::new();
+    hasher.update(HEDGE_SEED);
+    hasher.update(&fresh);
+    hasher.update(&(witness_bytes.len() as u64).to_le_bytes());
+    hasher.update(&witness_bytes);
+    witness_bytes.zeroize();
+    written?;
+
+    let mut statement_bytes = Vec::new();
+    for element in statement {
+        element.write(&mut statement_bytes)?;
+    }
+    hasher.update(&(seed.len() as u64).to_le_bytes());
+    hasher.update(seed);
+    hasher.update(&statement_bytes);
+
+    let mut key = [0u8; 32];
+    key.copy_from_slice(&hasher.finalize());
+    let rng = ChaCha20Rng::from_seed(key);
+    key.zeroize();
+
+    Ok(rng)
+}
+
+/// Sample an `m x n` matrix of uniformly random field elements.
+pub fn sample_matrix<F: Field, R: Rng + CryptoRng>(rng: &mut R, m: usize, n: usize) -> Matrix<F> {
+    Matrix::from_fn(m, n, |_, _| F::rand(rng))
+}
+
+/// Declares any generator cryptographically secure, so that weak generators can drive the protocol
+/// in tests that need them. Deterministic tests do not: seeded generators such as `ChaCha20Rng`
+/// already implement `CryptoRng`. Never enable `test-utils` in production builds.
+#[cfg(feature = "test-utils")]
+#[derive(Debug)]
+pub struct InsecureTestRng<R: RngCore>(pub R);
+
+#[cfg(feature = "test-utils")]
+impl<R: RngCore> RngCore for InsecureTestRng<R> {
+    fn next_u32(&mut self) -> u32 {
+        self.0.next_u32()
+    }
+
+    fn next_u64(&mut self) -> u64 {
+        self.0.next_u64()
+    }
+
+    fn fill_bytes(&mut self, dest: &mut [u8]) {
+        self.0.fill_bytes(dest)
+    }
+
+    fn try_fill_bytes(&mut self, dest: &mut [u8]) -> Result<(), ark_std::rand::Error> {
+        self.0.try_fill_bytes(dest)
+    }
+}
+
+#[cfg(feature = "test-utils")]
+impl<R: RngCore> CryptoRng for InsecureTestRng<R> {}
+
+/// Environment variable that sets the seed of [`test_rng`]: a decimal `u64`, or `random` for a
+/// fresh seed drawn once per process.
+#[cfg(all(feature = "std", any(test, feature = "test-utils")))]
+pub const TEST_SEED_VARIABLE: &str = "MENTAL_POKER_TEST_SEED";
+
+/// Seed of [`test_rng`] when [`TEST_SEED_VARIABLE`] is not set.
+#[cfg(all(feature = "std", any(test, feature = "test-utils")))]
+pub const DEFAULT_TEST_SEED: u64 = 0x6d65_6e74_616c_706b;
+
+/// Generator for tests: ChaCha20 seeded from [`TEST_SEED_VARIABLE`], so that a failure can be
+/// replayed. If the thread panics while the generator is alive, the seed is printed with the
+/// command line that replays it.
+#[cfg(all(feature = "std", any(test, feature = "test-utils")))]
+pub struct TestRng {
+    seed: u64,
+    stream: u64,
+    inner: rand_chacha::ChaCha20Rng,
+}
+
+/// Prints the seed and stream that replay the generator, not its state.
+#[cfg(all(feature = "std", any(test, feature = "test-utils")))]
+impl ark_std::fmt::Debug for TestRng {
+    fn fmt(&self, f: &mut ark_std::fmt::Formatter<'_>) -> ark_std::fmt::Result {
+        f.debug_struct("TestRng")
+            .field("seed", &self.seed)
+            .field("stream", &self.stream)
+            .finish_non_exhaustive()
+    }
+}
+
+#[cfg(all(feature = "std", any(test, feature = "test-utils")))]
+impl TestRng {
+    /// Generator for `stream` of `seed`. Different streams of a seed are independent.
+    pub fn new(seed: u64, stream: u64) -> Self {
+        use ark_std::rand::SeedableRng;
+
+        let mut inner = rand_chacha::ChaCha20Rng::seed_from_u64(seed);
+        inner.set_stream(stream);
+
+        Self {
+            seed,
+            stream,
+            inner,
+        }
+    }
+
+    pub fn seed(&self) -> u64 {
+        self.seed
+    }
+
+    pub fn stream(&self) -> u64 {
+        self.stream
+    }
+}
+
+/// A [`TestRng`] for the seed of the process. The stream is derived from the name of the thread,
+/// which the test harness names after the test, and from the number of generators the thread
+/// created before. A test therefore draws the same values every time it runs with the same seed,
+/// whichever other tests run with it, and generators it creates one after the other do not repeat
+/// each other.
+#[cfg(all(feature = "std", any(test, feature = "test-utils")))]
+pub fn test_rng() -> TestRng {
+    use std::cell::Cell;
+    use std::sync::OnceLock;
+
+    static SEED: OnceLock<u64> = OnceLock::new();
+    std::thread_local! {
+        static NEXT_STREAM: Cell<u64> = Cell::new(0);
+    }
+
+    let seed = *SEED.get_or_init(|| match std::env::var(TEST_SEED_VARIABLE) {
+        Err(_) => DEFAULT_TEST_SEED,
+        Ok(value) if value == "random" => rand::random(),
+        Ok(value) => value.parse().unwrap_or_else(|_| {
+            panic!(
+                "{} must be a decimal u64 or `random`, not {:?}",
+                TEST_SEED_VARIABLE, value
+            )
+        }),
+    });
+    let count = NEXT_STREAM.with(|next| next.replace(next.get() + 1));
+
+    TestRng::new(seed, test_stream(std::thread::current().name(), count))
+}
+
+/// Stream of the `count`th generator created on the thread called `name`: the low 16 bits count
+/// the generators, the others come from a hash of the name.
+#[cfg(all(feature = "std", any(test, feature = "test-utils")))]
+fn test_stream(name: Option<&str>, count: u64) -> u64 {
+    let mut hasher = Blake2s::new();
+    hasher.update(TEST_STREAM_SEED);
+    hasher.update(name.unwrap_or_default().as_bytes());
+
+    let mut bytes = [0u8; 8];
+    bytes.copy_from_slice(&hasher.finalize()[..8]);
+
+    (u64::from_le_bytes(bytes) << 16) | (count & 0xffff)
+}
+
+#[cfg(all(feature = "std", any(test, feature = "test-utils")))]
+impl RngCore for TestRng {
+    fn next_u32(&mut self) -> u32 {
+        self.inner.next_u32()
+    }
+
+    fn next_u64(&mut self) -> u64 {
+        self.inner.next_u64()
+    }
+
+    fn fill_bytes(&mut self, dest: &mut [u8]) {
+        self.inner.fill_bytes(dest)
+    }
+
+    fn try_fill_bytes(&mut self, dest: &mut [u8]) -> Result<(), ark_std::rand::Error> {
+        self.inner.try_fill_bytes(dest)
+    }
+}
+
+#[cfg(all(feature = "std", any(test, feature = "test-utils")))]
+impl CryptoRng for TestRng {}
+
+#[cfg(all(feature = "std", any(test, feature = "test-utils")))]
+impl Drop for TestRng {
+    fn drop(&mut self) {
+        if std::thread::panicking() {
+            // The stream follows from the name of the test, so running it alone replays it
+            let thread = std::thread::current();
+            std::eprintln!(
+                "test randomness: seed {}, stream {}; replay with {}={} cargo test -- --exact {}",
+                self.seed,
+                self.stream,
+                TEST_SEED_VARIABLE,
+                self.seed,
+                thread.name().unwrap_or("<test name>")
+            );
+        }
+    }
+}
+
+/// A generator that has run dry and only produces zeros.
+#[cfg(test)]
+pub(crate) struct ExhaustedRng;
+
+#[cfg(test)]
+impl RngCore for ExhaustedRng {
+    fn next_u32(&mut self) -> u32 {
+        0
+    }
+
+    fn next_u64(&mut self) -> u64 {
+        0
+    }
+
+    fn fill_bytes(&mut self, dest: &mut [u8]) {
+        dest.iter_mut().for_each(|b| *b = 0);
+    }
+
+    fn try_fill_bytes(&mut self, dest: &mut [u8]) -> Result<(), ark_std::rand::Error> {
+        self.fill_bytes(dest);
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+impl CryptoRng for ExhaustedRng {}
+
+#[cfg(test)]
+mod test {
+    use super::{
+        hedged_rng, sample_matrix, sample_nonzero, sample_nonzero_vector, test_rng, ExhaustedRng,
+        RandomnessGuard, TestRng,
+    };
+    use crate::crypto_primitives::utils::statistics::assert_uniform;
+    use crate::error::{CardProtocolError, InputError, InternalError};
+
+    use ark_ff::{BigInteger, PrimeField, Zero};
+    use ark_serialize::CanonicalSerialize;
+    use ark_std::rand::{CryptoRng, Error, RngCore};
+    use proof_essentials::utils::rand::sample_vector;
+
+    type Scalar = starknet_curve::Fr;
+
+    /// Produces zeros for the first few draws, so that the first candidate scalar is zero.
+    struct ZeroThenRandom {
+        zeros_left: usize,
+        inner: TestRng,
+    }
+
+    impl RngCore for ZeroThenRandom {
+        fn next_u32(&mut self) -> u32 {
+            self.next_u64() as u32
+        }
+
+        fn next_u64(&mut self) -> u64 {
+            if self.zeros_left > 0 {
+                self.zeros_left -= 1;
+                0
+            } else {
+                self.inner.next_u64()
+            }
+        }
+
+        fn fill_bytes(&mut self, dest: &mut [u8]) {
+            if self.zeros_left > 0 {
+                self.zeros_left -= 1;
+                dest.iter_mut().for_each(|b| *b = 0);
+            } else {
+                self.inner.fill_bytes(dest)
+            }
+        }
+
+        fn try_fill_bytes(&mut self, dest: &mut [u8]) -> Result<(), Error> {
+            self.fill_bytes(dest);
+            Ok(())
+        }
+    }
+
+    impl CryptoRng for ZeroThenRandom {}
+
+    #[test]
+    fn test_sampled_scalars_are_nonzero() {
+        let rng = &mut ZeroThenRandom {
+            zeros_left: 8,
+            inner: test_rng(),
+        };
+
+        // The zero candidates are rejected rather than returned
+        let x: Scalar = sample_nonzero(rng).unwrap();
+        assert!(!x.is_zero());
+        assert_eq!(rng.zeros_left, 0);
+
+        let factors: Vec<Scalar> = sample_nonzero_vector(&mut test_rng(), 52).unwrap();
+        assert_eq!(factors.len(), 52);
+        assert!(factors.iter().all(|factor| !factor.is_zero()));
+    }
+
+    #[test]
+    fn test_broken_generator_is_reported() {
+        let rng = &mut ZeroThenRandom {
+            zeros_left: usize::MAX,
+            inner: test_rng(),
+        };
+
+        assert_eq!(
+            sample_nonzero::<Scalar, _>(rng),
+            Err(CardProtocolError::Internal(
+                InternalError::RandomnessFailure
+            ))
+        );
+        assert_eq!(
+            sample_nonzero_vector::<Scalar, _>(rng, 52),
+            Err(CardProtocolError::Internal(
+                InternalError::RandomnessFailure
+            ))
+        );
+    }
+
+    #[test]
+    fn test_test_rng_streams_follow_the_test_name() {
+        let draw = |name: &str| {
+            std::thread::Builder::new()
+                .name(String::from(name))
+                .spawn(|| {
+                    let (mut first, mut second) = (test_rng(), test_rng());
+                    (first.stream(), first.next_u64(), second.next_u64())
+                })
+                .unwrap()
+                .join()
+                .unwrap()
+        };
+
+        // A test replays on its own, whatever ran on other threads before it
+        let (stream, first, second) = draw("module::test_a");
+        assert_eq!(draw("module::test_a"), (stream, first, second));
+        assert_ne!(first, second);
+
+        assert_ne!(draw("module::test_b").0, stream);
+
+        // The seed and stream in the report are enough to rebuild the generator
+        assert_eq!(TestRng::new(test_rng().seed(), stream).next_u64(), first);
+    }
+
+    #[test]
+    fn test_hedged_rng_depends_on_witness() {
+        let draw = |rng: &mut ExhaustedRng, witness: u64, statement: u64| {
+            hedged_rng(rng, &[Scalar::from(witness)], b"seed", &[statement])
+                .unwrap()
+                .next_u64()
+        };
+
+        // With a broken generator the nonces still change with the witness and the statement
+        let exhausted = &mut ExhaustedRng;
+        let nonce = draw(exhausted, 1, 1);
+        assert_eq!(nonce, draw(exhausted, 1, 1));
+        assert_ne!(nonce, draw(exhausted, 2, 1));
+        assert_ne!(nonce, draw(exhausted, 1, 2));
+
+        // With a working generator they are fresh even for the same witness and statement
+        let rng = &mut test_rng();
+        let witness = [Scalar::from(1u64)];
+        let mut first = hedged_rng(rng, &witness, b"seed", &[1u64]).unwrap();
+        let mut second = hedged_rng(rng, &witness, b"seed", &[1u64]).unwrap();
+        assert_ne!(first.next_u64(), second.next_u64());
+    }
+
+    #[test]
+    fn test_randomness_guard_batches() {
+        let rng = &mut test_rng();
+
+        let mut guard = RandomnessGuard::new();
+        let mut factors: Vec<Scalar> = sample_nonzero_vector(rng, 52).unwrap();
+        factors[30] = factors[3];
+
+        // A batch that repeats a factor is rejected as a whole
+        assert_eq!(
+            guard.register_all(&factors),
+            Err(CardProtocolError::InvalidInput(
+                InputError::ReusedMaskingFactor
+            ))
+        );
+        assert!(guard.is_empty());
+
+        factors[30] = Scalar::zero();
+        assert_eq!(
+            guard.register_all(&factors),
+            Err(CardProtocolError::InvalidInput(
+                InputError::ZeroMaskingFactor
+            ))
+        );
+
+        factors[30] = sample_nonzero(rng).unwrap();
+        assert_eq!(Ok(()), guard.register_all(&factors));
+        assert_eq!(guard.len(), 52);
+        assert_eq!(
+            guard.register(&factors[51]),
+            Err(CardProtocolError::InvalidInput(
+                InputError::ReusedMaskingFactor
+            ))
+        );
+
+        assert_eq!(
+            RandomnessGuard::strict().register(&factors[0]),
+            Err(CardProtocolError::InvalidInput(
+                InputError::CallerSuppliedRandomness
+            ))
+        );
+    }
+
+    #[test]
+    fn test_sample_matrix_shape() {
+        let rng = &mut test_rng();
+
+        let matrix = sample_matrix::<Scalar, _>(rng, 4, 13);
+        assert_eq!(matrix.num_rows(), 4);
+        assert_eq!(matrix.num_columns(), 13);
+        assert!(matrix.rows().iter().all(|row| row.len() == 13));
+
+        let empty = sample_matrix::<Scalar, _>(rng, 0, 13);
+        assert_eq!(empty.num_rows(), 0);
+        assert_eq!(empty.num_columns(), 13);
+    }
+
+    /// Check the bits of uniformly sampled scalars. Reduction modulo the group order biases the
+    /// topmost bits, so only the bits below them are expected to be fair coins, and the bytes made
+    /// of them to be uniform.
+    fn assert_uniform_bits(scalars: &[Scalar], name: &str) {
+        let fair_bits = Scalar::size_in_bits() - 2;
+        let fair_bytes = fair_bits / 8;
+
+        let mut ones = vec![0usize; fair_bits];
+        let mut byte_counts = vec![0usize; 256];
+        for scalar in scalars {
+            let repr = scalar.into_repr();
+            for (bit, ones) in ones.iter_mut().enumerate() {
+                *ones += repr.get_bit(bit) as usize;
+            }
+
+            let mut bytes = Vec::new();
+            scalar.serialize(&mut bytes).unwrap();
+            for &byte in &bytes[..fair_bytes] {
+                byte_counts[byte as usize] += 1;
+            }
+        }
+
+        // Every bit is tested, so each runs at p = 0.0001 / 250: 5.1 standard deviations
+        let half = scalars.len() as f64 / 2.0;
+        let bound = 5.1 * (scalars.len() as f64 / 4.0).sqrt();
+        for (bit, &count) in ones.iter().enumerate() {
+            assert!(
+                (count as f64 - half).abs() < bound,
+                "{}: bit {} is set in {} of {} scalars",
+                name,
+                bit,
+                count,
+                scalars.len()
+            );
+        }
+
+        assert_uniform(
+            &byte_counts,
+            255,
+            &format!("bytes of scalars from {}", name),
+        );
+    }
+
+    #[test]
+    #[ignore]
+    fn test_sampled_scalar_bits_are_uniform() {
+        let rng = &mut test_rng();
+        let num_of_scalars = 100_000;
+
+        let scalars: Vec<Scalar> = sample_vector(rng, num_of_scalars);
+        assert_uniform_bits(&scalars, "sample_vector");
+
+        let factors: Vec<Scalar> = sample_nonzero_vector(rng, num_of_scalars).unwrap();
+        assert_uniform_bits(&factors, "sample_nonzero_vector");
+    }
+}
@@ -0,0 +1,171 @@
+//! Wiping secrets from memory.
+//!
+//! Field elements from arkworks do not implement `Zeroize`, and the protocol's secret key and
+//! masking factors are plain field elements. [`wipe`] overwrites them with volatile writes that
+//! the compiler cannot elide, and [`SecretScalar`] and [`SecretScalars`] own secrets and wipe them
+//! when dropped. Hold player secret keys and masking factors in them when they are not needed in
+//! the clear.
+//!
+//! The nonces of the signature and VRF provers are sampled here and wiped after use. The nonces of
+//! the key ownership, masking, reveal and shuffle proofs are sampled inside the `proof_essentials`
+//! provers from the generator they are given, and never reach this crate, so it cannot wipe them.
+
+use ark_ff::Field;
+use ark_std::fmt;
+use ark_std::ops::{Deref, DerefMut};
+use ark_std::sync::atomic::{compiler_fence, Ordering};
+use ark_std::vec::Vec;
+use zeroize::{Zeroize, ZeroizeOnDrop};
+
+/// Overwrite `elements` with zero.
+pub fn wipe<F: Field>(elements: &mut [F]) {
+    for element in elements.iter_mut() {
+        // SAFETY: `element` is a valid, aligned and exclusive reference
+        unsafe { ark_std::ptr::write_volatile(element, F::zero()) };
+    }
+    compiler_fence(Ordering::SeqCst);
+}
+
+/// A secret field element, such as a `PlayerSecretKey`, that is wiped when dropped.
+#[derive(Clone)]
+pub struct SecretScalar<F: Field>(F);
+
+impl<F: Field> SecretScalar<F> {
+    pub fn new(secret: F) -> Self {
+        Self(secret)
+    }
+}
+
+impl<F: Field> Deref for SecretScalar<F> {
+    type Target = F;
+
+    fn deref(&self) -> &F {
+        &self.0
+    }
+}
+
+impl<F: Field> fmt::Debug for SecretScalar<F> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("SecretScalar(REDACTED)")
+    }
+}
+
+impl<F: Field> Zeroize for SecretScalar<F> {
+    fn zeroize(&mut self) {
+        wipe(ark_std::slice::from_mut(&mut self.0));
+    }
+}
+
+impl<F: Field> Drop for SecretScalar<F> {
+    fn drop(&mut self) {
+        self.zeroize();
+    }
+}
+
+impl<F: Field> ZeroizeOnDrop for SecretScalar<F> {}
+
+/// Secret field elements, such as masking factors, that are wiped when dropped.
+#[derive(Clone)]
+pub struct SecretScalars<F: Field>(Vec<F>);
+
+impl<F: Field> SecretScalars<F> {
+    pub fn new(secrets: Vec<F>) -> Self {
+        Self(secrets)
+    }
+}
+
+impl<F: Field> Deref for SecretScalars<F> {
+    type Target = Vec<F>;
+
+    fn deref(&self) -> &Vec<F> {
+        &self.0
+    }
+}
+
+impl<F: Field> DerefMut for SecretScalars<F> {
+    fn deref_mut(&mut self) -> &mut Vec<F> {
+        &mut self.0
+    }
+}
+
+/// Only the number of elements is printed.
+impl<F: Field> fmt::Debug for SecretScalars<F> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "SecretScalars({} × REDACTED)", self.0.len())
+    }
+}
+
+impl<F: Field> Zeroize for SecretScalars<F> {
+    /// Wipe the elements and empty the vector.
+    fn zeroize(&mut self) {
+        wipe(&mut self.0);
+        self.0.clear();
+    }
+}
+
+impl<F: Field> Drop for SecretScalars<F> {
+    fn drop(&mut self) {
+        self.zeroize();
+    }
+}
+
+impl<F: Field> ZeroizeOnDrop for SecretScalars<F> {}
+
+#[cfg(test)]
+mod test {
+    use super::{wipe, SecretScalar, SecretScalars};
+    use crate::crypto_primitives::utils::rand::test_rng;
+
+    use ark_ff::{UniformRand, Zero};
+    use proof_essentials::utils::rand::sample_vector;
+    use zeroize::{Zeroize, ZeroizeOnDrop};
+
+    type Scalar = starknet_curve::Fr;
+
+    #[test]
+    fn test_wipe() {
+        let rng = &mut test_rng();
+
+        let mut secrets: Vec<Scalar> = sample_vector(rng, 52);
+        wipe(&mut secrets);
+        assert!(secrets.iter().all(|x| x.is_zero()));
+    }
+
+    #[test]
+    fn test_secret_scalar_is_wiped() {
+        let rng = &mut test_rng();
+
+        let secret = Scalar::rand(rng);
+        let mut guarded = SecretScalar::new(secret);
+        assert_eq!(*guarded, secret);
+
+        guarded.zeroize();
+        assert!(guarded.0.is_zero());
+
+        // Dropping the secret runs the same zeroize
+        fn wiped_on_drop<T: ZeroizeOnDrop>() {}
+        wiped_on_drop::<SecretScalar<Scalar>>();
+    }
+
+    #[test]
+    fn test_secret_scalars_are_wiped() {
+        let rng = &mut test_rng();
+
+        let mut secrets = SecretScalars::new(sample_vector::<Scalar, _>(rng, 52));
+        assert_eq!(secrets.len(), 52);
+
+        secrets.zeroize();
+        assert!(secrets.is_empty());
+    }
+
+    #[test]
+    fn test_secrets_are_redacted() {
+        let rng = &mut test_rng();
+
+        let secret = SecretScalar::new(Scalar::rand(rng));
+        assert_eq!("SecretScalar(REDACTED)", format!("{:?}", secret));
+
+        let secrets = SecretScalars::new(sample_vector::<Scalar, _>(rng, 3));
+        assert_eq!("SecretScalars(3 × REDACTED)", format!("{:?}", secrets));
+    }
+}
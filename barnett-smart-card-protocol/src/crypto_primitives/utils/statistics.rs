@@ -0,0 +1,69 @@
+//! Goodness-of-fit checks for the statistical tests of the samplers. The tests that use them draw
+//! hundreds of thousands of samples and are ignored by default: run them with
+//! `cargo test --release -- --ignored`.
+//!
+//! Thresholds are set at p = 0.0001, so a correct sampler fails one of these tests about once in
+//! ten thousand runs, and a failing seed can be replayed with `MENTAL_POKER_TEST_SEED`.
+
+/// Upper quantile of the standard normal distribution at p = 0.0001.
+pub const Z_CRITICAL: f64 = 3.719;
+
+/// Pearson's chi-square statistic of `counts` against the same `expected` count in every cell.
+pub fn chi_square(counts: &[usize], expected: f64) -> f64 {
+    counts
+        .iter()
+        .map(|&count| (count as f64 - expected).powi(2) / expected)
+        .sum()
+}
+
+/// Critical value of the chi-square distribution with `degrees_of_freedom` at p = 0.0001, from the
+/// Wilson-Hilferty approximation. It is a few percent above the exact value for a handful of
+/// degrees of freedom, and within a fraction of a percent from about twenty up.
+pub fn chi_square_critical_value(degrees_of_freedom: usize) -> f64 {
+    let k = degrees_of_freedom as f64;
+    let h = 2.0 / (9.0 * k);
+
+    k * (1.0 - h + Z_CRITICAL * h.sqrt()).powi(3)
+}
+
+/// Panic unless `counts` fit the same expected count in every cell, for a statistic with
+/// `degrees_of_freedom`: one less than the number of cells for a single distribution, or
+/// `(rows - 1) * (columns - 1)` for a table whose rows and columns each sum to a known total.
+pub fn assert_uniform(counts: &[usize], degrees_of_freedom: usize, context: &str) {
+    let total = counts.iter().sum::<usize>();
+    let expected = total as f64 / counts.len() as f64;
+    let statistic = chi_square(counts, expected);
+    let critical_value = chi_square_critical_value(degrees_of_freedom);
+
+    assert!(
+        statistic < critical_value,
+        "{}: chi-square statistic {} with {} degrees of freedom exceeds {}",
+        context,
+        statistic,
+        degrees_of_freedom,
+        critical_value
+    );
+}
+
+#[cfg(test)]
+mod test {
+    use super::{chi_square, chi_square_critical_value};
+
+    #[test]
+    fn test_critical_values() {
+        // Exact values at p = 0.0001
+        for (degrees_of_freedom, exact) in [(5, 25.745), (23, 57.075), (119, 185.086)] {
+            let approximation = chi_square_critical_value(degrees_of_freedom);
+            assert!(
+                (approximation - exact).abs() / exact < 0.03,
+                "{} degrees of freedom: {} instead of {}",
+                degrees_of_freedom,
+                approximation,
+                exact
+            );
+        }
+
+        assert_eq!(chi_square(&[10, 10, 10], 10.0), 0.0);
+        assert_eq!(chi_square(&[5, 15], 10.0), 5.0);
+    }
+}
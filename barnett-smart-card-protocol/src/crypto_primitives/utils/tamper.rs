@@ -0,0 +1,419 @@
+//! Systematic negative tests for proofs. [`ProofTamper`] corrupts a serialized proof one change at
+//! a time and checks that no corrupted proof still verifies, instead of relying on a few negative
+//! cases written by hand.
+//!
+//! Byte-level mutations flip single bits and truncate the proof. Element-level mutations act on
+//! the encodings of the field and group elements registered with [`ProofTamper::with_element`]:
+//! each one is replaced by a random element of the same type, and neighbouring elements of the
+//! same type are swapped. Elements are found by decoding at every offset, so a few matches are
+//! accidental; mutating those is still a corruption, only a less targeted one.
+//!
+//! By default a few mutations of each kind are sampled, which keeps the tests fast. Setting
+//! [`EXHAUSTIVE_TAMPER_VARIABLE`] runs every mutation instead.
+
+use crate::crypto_primitives::utils::format::HexBytes;
+
+use ark_ff::UniformRand;
+use ark_serialize::{CanonicalDeserialize, CanonicalSerialize, SerializationError};
+use ark_std::rand::{Rng, SeedableRng};
+use rand_chacha::ChaCha20Rng;
+use std::fmt;
+
+/// Environment variable that runs every mutation instead of a sample, when set to anything but
+/// an empty string or `0`.
+pub const EXHAUSTIVE_TAMPER_VARIABLE: &str = "MENTAL_POKER_TAMPER_EXHAUSTIVE";
+
+/// Mutations of each kind run in [`TamperMode::Sampled`].
+pub const SAMPLED_MUTATIONS_PER_KIND: usize = 8;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum TamperMode {
+    /// [`SAMPLED_MUTATIONS_PER_KIND`] random mutations of each kind
+    Sampled,
+    /// Every mutation of every kind
+    Exhaustive,
+}
+
+impl TamperMode {
+    /// The mode selected by [`EXHAUSTIVE_TAMPER_VARIABLE`].
+    pub fn from_env() -> Self {
+        match std::env::var(EXHAUSTIVE_TAMPER_VARIABLE) {
+            Ok(value) if !value.is_empty() && value != "0" => Self::Exhaustive,
+            _ => Self::Sampled,
+        }
+    }
+}
+
+/// A single change to the encoding of a proof.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Mutation {
+    /// Flip bit `bit` of the byte at `offset`
+    FlipBit { offset: usize, bit: u8 },
+    /// Keep only the first `length` bytes
+    Truncate { length: usize },
+    /// Replace the element of type `element` encoded at `offset` with a random one
+    ReplaceElement {
+        offset: usize,
+        length: usize,
+        element: &'static str,
+    },
+    /// Swap the element encoded at `offset` with the next one, of the same type
+    SwapElements { offset: usize, length: usize },
+}
+
+/// Samples a random element of a registered type and encodes it.
+type Sampler = fn(&mut ChaCha20Rng) -> Result<Vec<u8>, SerializationError>;
+
+fn sample<T: UniformRand + CanonicalSerialize>(
+    rng: &mut ChaCha20Rng,
+) -> Result<Vec<u8>, SerializationError> {
+    let mut bytes = Vec::new();
+    T::rand(rng).serialize(&mut bytes)?;
+
+    Ok(bytes)
+}
+
+/// Encoding of an element of a registered type found in the proof.
+#[derive(Clone, Copy)]
+struct Element {
+    offset: usize,
+    length: usize,
+    element: &'static str,
+}
+
+/// Mutates the canonical encoding of a proof. See the module documentation.
+pub struct ProofTamper {
+    proof: Vec<u8>,
+    samplers: Vec<(&'static str, Sampler)>,
+    elements: Vec<Element>,
+}
+
+impl fmt::Debug for ProofTamper {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("ProofTamper")
+            .field("proof", &HexBytes(&self.proof))
+            .field("length", &self.proof.len())
+            .field("elements", &self.elements.len())
+            .finish_non_exhaustive()
+    }
+}
+
+impl ProofTamper {
+    pub fn new<P: CanonicalSerialize>(proof: &P) -> Result<Self, SerializationError> {
+        let mut bytes = Vec::new();
+        proof.serialize(&mut bytes)?;
+
+        Ok(Self {
+            proof: bytes,
+            samplers: Vec::new(),
+            elements: Vec::new(),
+        })
+    }
+
+    /// Find the elements of type `T` in the proof, for element-level mutations. An element is
+    /// found at every offset where a value of type `T` decodes and encodes back to the same bytes.
+    pub fn with_element<T>(mut self) -> Self
+    where
+        T: UniformRand + CanonicalSerialize + CanonicalDeserialize,
+    {
+        let element = core::any::type_name::<T>();
+        self.samplers.push((element, sample::<T> as Sampler));
+
+        for offset in 0..self.proof.len() {
+            let mut reader = &self.proof[offset..];
+            let mut encoding = Vec::new();
+            let found = T::deserialize(&mut reader)
+                .and_then(|value| value.serialize(&mut encoding))
+                .is_ok();
+            if found && self.proof[offset..].starts_with(&encoding) {
+                self.elements.push(Element {
+                    offset,
+                    length: encoding.len(),
+                    element,
+                });
+            }
+        }
+
+        self
+    }
+
+    /// Canonical encoding of the untouched proof.
+    pub fn proof(&self) -> &[u8] {
+        &self.proof
+    }
+
+    /// Every mutation of every kind in `Exhaustive` mode, a sample of each kind otherwise.
+    pub fn mutations<R: Rng>(&self, rng: &mut R, mode: TamperMode) -> Vec<Mutation> {
+        let flips = (0..self.proof.len())
+            .flat_map(|offset| (0..8).map(move |bit| Mutation::FlipBit { offset, bit }))
+            .collect::<Vec<_>>();
+        let truncations = (0..self.proof.len())
+            .map(|length| Mutation::Truncate { length })
+            .collect::<Vec<_>>();
+        let replacements = self
+            .elements
+            .iter()
+            .map(|found| Mutation::ReplaceElement {
+                offset: found.offset,
+                length: found.length,
+                element: found.element,
+            })
+            .collect::<Vec<_>>();
+        let swaps = self
+            .elements
+            .iter()
+            .filter(|found| {
+                self.elements.iter().any(|next| {
+                    next.element == found.element && next.offset == found.offset + found.length
+                })
+            })
+            .map(|found| Mutation::SwapElements {
+                offset: found.offset,
+                length: found.length,
+            })
+            .collect::<Vec<_>>();
+
+        [flips, truncations, replacements, swaps]
+            .into_iter()
+            .flat_map(|kind| match mode {
+                TamperMode::Exhaustive => kind,
+                TamperMode::Sampled if kind.is_empty() => kind,
+                TamperMode::Sampled => (0..SAMPLED_MUTATIONS_PER_KIND)
+                    .map(|_| kind[rng.gen_range(0..kind.len())])
+                    .collect(),
+            })
+            .collect()
+    }
+
+    /// The encoding of the proof after `mutation`. Replacements draw the new element from `rng`.
+    pub fn apply<R: Rng>(
+        &self,
+        rng: &mut R,
+        mutation: &Mutation,
+    ) -> Result<Vec<u8>, SerializationError> {
+        let mut mutant = self.proof.clone();
+
+        match *mutation {
+            Mutation::FlipBit { offset, bit } => mutant[offset] ^= 1 << bit,
+            Mutation::Truncate { length } => mutant.truncate(length),
+            Mutation::ReplaceElement {
+                offset,
+                length,
+                element,
+            } => {
+                let sampler = self
+                    .samplers
+                    .iter()
+                    .find(|(name, _)| *name == element)
+                    .map(|(_, sampler)| sampler)
+                    .ok_or(SerializationError::InvalidData)?;
+                let replacement = sampler(&mut ChaCha20Rng::seed_from_u64(rng.gen()))?;
+                if replacement.len() != length {
+                    return Err(SerializationError::InvalidData);
+                }
+                mutant[offset..offset + length].copy_from_slice(&replacement);
+            }
+            Mutation::SwapElements { offset, length } => {
+                let (first, second) = mutant[offset..offset + 2 * length].split_at_mut(length);
+                first.swap_with_slice(second);
+            }
+        }
+
+        Ok(mutant)
+    }
+
+    /// The first mutation whose result still decodes to a proof accepted by `verify`, with that
+    /// result. Mutations that leave the encoding unchanged, such as swapping two equal elements,
+    /// are skipped.
+    pub fn first_accepted<P, R>(
+        &self,
+        rng: &mut R,
+        mode: TamperMode,
+        mut verify: impl FnMut(&P) -> bool,
+    ) -> Result<Option<(Mutation, Vec<u8>)>, SerializationError>
+    where
+        P: CanonicalDeserialize,
+        R: Rng,
+    {
+        for mutation in self.mutations(rng, mode) {
+            let mutant = self.apply(rng, &mutation)?;
+            if mutant == self.proof {
+                continue;
+            }
+
+            if let Ok(proof) = P::deserialize(&mutant[..]) {
+                if verify(&proof) {
+                    return Ok(Some((mutation, mutant)));
+                }
+            }
+        }
+
+        Ok(None)
+    }
+
+    /// Panic if any mutation of the mode selected by [`EXHAUSTIVE_TAMPER_VARIABLE`] still
+    /// verifies. A mutant that decodes back to the original proof points at a malleable encoding,
+    /// any other one at a soundness bug.
+    pub fn assert_rejected<P, R>(&self, rng: &mut R, verify: impl FnMut(&P) -> bool)
+    where
+        P: CanonicalSerialize + CanonicalDeserialize,
+        R: Rng,
+    {
+        let accepted = self
+            .first_accepted(rng, TamperMode::from_env(), verify)
+            .expect("mutations of a serialized proof can be applied");
+
+        if let Some((mutation, mutant)) = accepted {
+            let mut encoding = Vec::new();
+            let same_proof = P::deserialize(&mutant[..])
+                .and_then(|proof| proof.serialize(&mut encoding))
+                .is_ok()
+                && encoding == self.proof;
+
+            if same_proof {
+                panic!(
+                    "{:?} decodes to the original proof: the encoding is malleable",
+                    mutation
+                );
+            }
+            panic!("{:?} still verifies: soundness bug", mutation);
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{Mutation, ProofTamper, TamperMode};
+    use crate::crypto_primitives::utils::rand::test_rng;
+    use crate::discrete_log_cards;
+    use crate::BarnettSmartProtocol;
+
+    use ark_ff::UniformRand;
+    use proof_essentials::utils::rand::sample_vector;
+
+    // Choose elliptic curve setting
+    type Curve = starknet_curve::Projective;
+    type Scalar = starknet_curve::Fr;
+
+    // Instantiate concrete type for our card protocol
+    type CardProtocol<'a> = discrete_log_cards::DLCards<'a, Curve>;
+    type MaskedCard = discrete_log_cards::MaskedCard<Curve>;
+    type ZKProofKeyOwnership = <CardProtocol<'static> as BarnettSmartProtocol>::ZKProofKeyOwnership;
+    type ZKProofReveal = <CardProtocol<'static> as BarnettSmartProtocol>::ZKProofReveal;
+    type ZKProofShuffle = <CardProtocol<'static> as BarnettSmartProtocol>::ZKProofShuffle;
+
+    fn tamper<P: ark_serialize::CanonicalSerialize>(proof: &P) -> ProofTamper {
+        ProofTamper::new(proof)
+            .unwrap()
+            .with_element::<Curve>()
+            .with_element::<Scalar>()
+    }
+
+    #[test]
+    fn test_mutations_cover_every_kind() {
+        let rng = &mut test_rng();
+        let elements = sample_vector::<Scalar, _>(rng, 4);
+        let tamper = tamper(&elements);
+
+        let mutations = tamper.mutations(rng, TamperMode::Exhaustive);
+        let length = tamper.proof().len();
+        assert_eq!(
+            mutations
+                .iter()
+                .filter(|mutation| matches!(mutation, Mutation::FlipBit { .. }))
+                .count(),
+            8 * length
+        );
+        assert!(mutations.contains(&Mutation::Truncate { length: 0 }));
+        assert!(mutations.contains(&Mutation::SwapElements {
+            offset: 8,
+            length: 32
+        }));
+
+        let sampled = tamper.mutations(rng, TamperMode::Sampled);
+        assert_eq!(sampled.len(), 4 * super::SAMPLED_MUTATIONS_PER_KIND);
+        for mutation in &sampled {
+            let mutant = tamper.apply(rng, mutation).unwrap();
+            assert_ne!(mutant, tamper.proof());
+        }
+
+        // A verifier that ignores the proof accepts the first mutant
+        assert!(tamper
+            .first_accepted(rng, TamperMode::Sampled, |_: &Vec<Scalar>| true)
+            .unwrap()
+            .is_some());
+    }
+
+    #[test]
+    fn test_tampered_key_ownership_proofs_are_rejected() {
+        let rng = &mut test_rng();
+        let parameters = CardProtocol::setup(rng, 2, 2).unwrap();
+        let (pk, sk) = CardProtocol::player_keygen(rng, &parameters).unwrap();
+        let info = Scalar::rand(rng);
+        let key_list_digest = CardProtocol::key_list_digest(&[pk]).unwrap();
+
+        let proof = CardProtocol::prove_key_ownership(
+            rng,
+            &parameters,
+            b"",
+            &pk,
+            &sk,
+            &info,
+            0,
+            &key_list_digest,
+        )
+        .unwrap();
+
+        tamper(&proof).assert_rejected(rng, |proof: &ZKProofKeyOwnership| {
+            CardProtocol::verify_key_ownership(
+                &parameters,
+                b"",
+                &pk,
+                &info,
+                0,
+                &key_list_digest,
+                proof,
+            )
+            .is_ok()
+        });
+    }
+
+    #[test]
+    fn test_tampered_reveal_proofs_are_rejected() {
+        let rng = &mut test_rng();
+        let parameters = CardProtocol::setup(rng, 2, 2).unwrap();
+        let (pk, sk) = CardProtocol::player_keygen(rng, &parameters).unwrap();
+        let masked = MaskedCard::rand(rng);
+
+        let (token, proof) =
+            CardProtocol::compute_reveal_token(rng, &parameters, b"", &sk, &pk, &masked).unwrap();
+
+        tamper(&proof).assert_rejected(rng, |proof: &ZKProofReveal| {
+            CardProtocol::verify_reveal(&parameters, b"", &pk, &token, &masked, proof).is_ok()
+        });
+    }
+
+    #[test]
+    fn test_tampered_shuffle_proofs_are_rejected() {
+        let rng = &mut test_rng();
+        let parameters = CardProtocol::setup(rng, 2, 4).unwrap();
+        let (shared_key, _) = CardProtocol::player_keygen(rng, &parameters).unwrap();
+        let deck: Vec<MaskedCard> = sample_vector(rng, 8);
+
+        let (shuffled_deck, proof) =
+            CardProtocol::shuffle_and_remask_with_rng(rng, &parameters, b"", &shared_key, &deck)
+                .unwrap();
+
+        tamper(&proof).assert_rejected(rng, |proof: &ZKProofShuffle| {
+            CardProtocol::verify_shuffle(
+                &parameters,
+                b"",
+                &shared_key,
+                &deck,
+                &shuffled_deck,
+                proof,
+            )
+            .is_ok()
+        });
+    }
+}
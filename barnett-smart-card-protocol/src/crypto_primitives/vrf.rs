@@ -0,0 +1,270 @@
+//! ECVRF-style verifiable random function over the protocol curve.
+//!
+//! The output for an input `x` is derived from `Gamma = H(x)^sk`, where `H` hashes to the curve,
+//! and the proof is a Chaum-Pedersen proof that `Gamma` and the public key share the same discrete
+//! logarithm. The nonce is derived from the secret key and the input, so proving is deterministic.
+//! Players evaluating the VRF on a common input (e.g. the session id) obtain outputs that nobody
+//! could predict or bias, which [`select_dealer`] combines into a seat index.
+
+use crate::crypto_primitives::hash_to_curve::hash_to_curve;
+use crate::crypto_primitives::hash_to_field::hash_to_field;
+use crate::crypto_primitives::utils::format::{Hex, HexBytes};
+use crate::crypto_primitives::utils::rand::sample_nonzero;
+use crate::crypto_primitives::utils::secret::wipe;
+use crate::error::{CardProtocolError, InputError, VerificationError};
+
+use ark_ec::{AffineCurve, ProjectiveCurve};
+use ark_serialize::{CanonicalDeserialize, CanonicalSerialize};
+use ark_std::fmt;
+use ark_std::rand::{CryptoRng, Rng};
+use ark_std::string::String;
+use ark_std::vec::Vec;
+use blake2::{Blake2s, Digest};
+use proof_essentials::error::CryptoError;
+use zeroize::{Zeroize, ZeroizeOnDrop};
+
+const VRF_INPUT_DOMAIN: &'static [u8] = b"VRF Input";
+const VRF_NONCE_DOMAIN: &'static [u8] = b"VRF Nonce";
+const VRF_CHALLENGE_DOMAIN: &'static [u8] = b"VRF Challenge";
+const VRF_OUTPUT_DOMAIN: &'static [u8] = b"VRF Output";
+const DEALER_SELECTION_DOMAIN: &'static [u8] = b"Dealer Selection";
+
+/// VRF secret key, wiped when dropped.
+pub struct VrfSecretKey<C: ProjectiveCurve> {
+    sk: C::ScalarField,
+    pk: VrfPublicKey<C>,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, CanonicalSerialize, CanonicalDeserialize)]
+pub struct VrfPublicKey<C: ProjectiveCurve>(pub C::Affine);
+
+#[derive(Clone, Copy, PartialEq, Eq, Hash)]
+pub struct VrfOutput(pub [u8; 32]);
+
+#[derive(Clone, Copy, PartialEq, Eq, CanonicalSerialize, CanonicalDeserialize)]
+pub struct VrfProof<C: ProjectiveCurve> {
+    gamma: C::Affine,
+    c: C::ScalarField,
+    s: C::ScalarField,
+}
+
+impl<C: ProjectiveCurve> VrfSecretKey<C> {
+    pub fn new<R: Rng + CryptoRng>(rng: &mut R) -> Result<Self, CardProtocolError> {
+        Ok(Self::from_scalar(sample_nonzero(rng)?))
+    }
+
+    pub fn from_scalar(sk: C::ScalarField) -> Self {
+        let pk = VrfPublicKey(C::Affine::prime_subgroup_generator().mul(sk).into_affine());
+
+        Self { sk, pk }
+    }
+
+    pub fn public_key(&self) -> VrfPublicKey<C> {
+        self.pk
+    }
+
+    /// Evaluate the VRF on `input`.
+    pub fn prove(&self, input: &[u8]) -> Result<(VrfOutput, VrfProof<C>), CardProtocolError> {
+        let generator = C::Affine::prime_subgroup_generator();
+        let h = hash_to_curve::<C>(VRF_INPUT_DOMAIN, input)?;
+        let gamma = h.mul(self.sk).into_affine();
+
+        let mut nonce_input = Vec::new();
+        self.sk.serialize(&mut nonce_input)?;
+        nonce_input.extend_from_slice(input);
+        let mut k: C::ScalarField = hash_to_field(VRF_NONCE_DOMAIN, &nonce_input);
+        nonce_input.zeroize();
+
+        let u = generator.mul(k).into_affine();
+        let v = h.mul(k).into_affine();
+        let c = challenge::<C>(&h, &self.pk.0, &gamma, &u, &v);
+        let s = c.as_ref().ok().map(|c| k + *c * self.sk);
+
+        // The nonce reveals the secret key together with the proof, so it must not outlive it
+        wipe(ark_std::slice::from_mut(&mut k));
+        let (c, s) = (c?, s.unwrap_or_default());
+
+        Ok((output::<C>(&gamma)?, VrfProof { gamma, c, s }))
+    }
+}
+
+impl<C: ProjectiveCurve> fmt::Debug for VrfSecretKey<C> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("VrfSecretKey")
+            .field("pk", &self.pk)
+            .finish_non_exhaustive()
+    }
+}
+
+impl<C: ProjectiveCurve> Zeroize for VrfSecretKey<C> {
+    fn zeroize(&mut self) {
+        wipe(ark_std::slice::from_mut(&mut self.sk));
+    }
+}
+
+impl<C: ProjectiveCurve> Drop for VrfSecretKey<C> {
+    fn drop(&mut self) {
+        self.zeroize();
+    }
+}
+
+impl<C: ProjectiveCurve> ZeroizeOnDrop for VrfSecretKey<C> {}
+
+impl fmt::Debug for VrfOutput {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "VrfOutput({:?})", HexBytes(&self.0))
+    }
+}
+
+impl<C: ProjectiveCurve> fmt::Debug for VrfPublicKey<C> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "VrfPublicKey({:?})", Hex(&self.0))
+    }
+}
+
+impl<C: ProjectiveCurve> fmt::Debug for VrfProof<C> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "VrfProof({:?})", Hex(self))
+    }
+}
+
+impl<C: ProjectiveCurve> VrfPublicKey<C> {
+    /// Check that `output` is the VRF evaluation on `input` under this key.
+    pub fn verify(
+        &self,
+        input: &[u8],
+        output: &VrfOutput,
+        proof: &VrfProof<C>,
+    ) -> Result<(), CardProtocolError> {
+        let generator = C::Affine::prime_subgroup_generator();
+        let h = hash_to_curve::<C>(VRF_INPUT_DOMAIN, input)?;
+
+        // g^s = u * pk^c and h^s = v * gamma^c
+        let u = (generator.mul(proof.s) - self.0.mul(proof.c)).into_affine();
+        let v = (h.mul(proof.s) - proof.gamma.mul(proof.c)).into_affine();
+
+        if challenge::<C>(&h, &self.0, &proof.gamma, &u, &v)? != proof.c
+            || output::<C>(&proof.gamma)? != *output
+        {
+            return Err(VerificationError::ProofVerificationError(
+                CryptoError::ProofVerificationError(String::from("VRF")),
+            )
+            .into());
+        }
+
+        Ok(())
+    }
+}
+
+/// Combine the VRF outputs of all players, in seat order, into the index of the dealer.
+pub fn select_dealer(player_vrf_outputs: &[VrfOutput]) -> Result<usize, CardProtocolError> {
+    if player_vrf_outputs.is_empty() {
+        return Err(InputError::NotEnoughPlayers.into());
+    }
+
+    let mut hasher = Blake2s::new();
+    hasher.update(DEALER_SELECTION_DOMAIN);
+    for output in player_vrf_outputs {
+        hasher.update(&output.0);
+    }
+    let digest = hasher.finalize();
+
+    // Reducing 128 bits modulo the number of players has negligible bias
+    let mut bytes = [0u8; 16];
+    bytes.copy_from_slice(&digest[..16]);
+    let index = u128::from_le_bytes(bytes) % player_vrf_outputs.len() as u128;
+
+    Ok(index as usize)
+}
+
+fn challenge<C: ProjectiveCurve>(
+    h: &C::Affine,
+    pk: &C::Affine,
+    gamma: &C::Affine,
+    u: &C::Affine,
+    v: &C::Affine,
+) -> Result<C::ScalarField, CardProtocolError> {
+    let mut bytes = Vec::new();
+    C::Affine::prime_subgroup_generator().serialize(&mut bytes)?;
+    for point in [h, pk, gamma, u, v] {
+        point.serialize(&mut bytes)?;
+    }
+
+    Ok(hash_to_field(VRF_CHALLENGE_DOMAIN, &bytes))
+}
+
+fn output<C: ProjectiveCurve>(gamma: &C::Affine) -> Result<VrfOutput, CardProtocolError> {
+    let mut bytes = Vec::new();
+    gamma.serialize(&mut bytes)?;
+
+    let mut hasher = Blake2s::new();
+    hasher.update(VRF_OUTPUT_DOMAIN);
+    hasher.update(&bytes);
+
+    let mut output = [0u8; 32];
+    output.copy_from_slice(&hasher.finalize());
+
+    Ok(VrfOutput(output))
+}
+
+#[cfg(test)]
+mod test {
+    use super::{select_dealer, VrfOutput, VrfSecretKey};
+    use crate::crypto_primitives::utils::rand::test_rng;
+    use crate::error::{CardProtocolError, InputError, VerificationError};
+
+    use proof_essentials::error::CryptoError;
+
+    // Choose elliptic curve setting
+    type Curve = starknet_curve::Projective;
+
+    #[test]
+    fn test_vrf() {
+        let rng = &mut test_rng();
+
+        let sk = VrfSecretKey::<Curve>::new(rng).unwrap();
+        let pk = sk.public_key();
+
+        let (output, proof) = sk.prove(b"session id").unwrap();
+        assert_eq!(Ok(()), pk.verify(b"session id", &output, &proof));
+
+        // Evaluation is deterministic
+        assert_eq!(output, sk.prove(b"session id").unwrap().0);
+
+        let expected_error = Err(CardProtocolError::VerificationFailed(
+            VerificationError::ProofVerificationError(CryptoError::ProofVerificationError(
+                String::from("VRF"),
+            )),
+        ));
+
+        assert_eq!(pk.verify(b"other session", &output, &proof), expected_error);
+
+        let other_output = sk.prove(b"other session").unwrap().0;
+        assert_eq!(
+            pk.verify(b"session id", &other_output, &proof),
+            expected_error
+        );
+
+        let other_pk = VrfSecretKey::<Curve>::new(rng).unwrap().public_key();
+        assert_eq!(
+            other_pk.verify(b"session id", &output, &proof),
+            expected_error
+        );
+    }
+
+    #[test]
+    fn test_select_dealer() {
+        let outputs = (0..6u8).map(|i| VrfOutput([i; 32])).collect::<Vec<_>>();
+
+        let dealer = select_dealer(&outputs).unwrap();
+        assert!(dealer < outputs.len());
+        assert_eq!(Ok(dealer), select_dealer(&outputs));
+
+        assert_eq!(
+            select_dealer(&[]),
+            Err(CardProtocolError::InvalidInput(
+                InputError::NotEnoughPlayers
+            ))
+        );
+    }
+}
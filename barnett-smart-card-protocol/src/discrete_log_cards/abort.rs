@@ -0,0 +1,621 @@
+//! Reports of misbehavior that an arbiter can check without having taken part in the game.
+//!
+//! Every message a player broadcasts is signed with their identity key, together with the game's
+//! context, their seat and a slot that numbers their messages. A [`MisbehaviorReport`] carries the
+//! signed messages that show a player cheated, and [`MisbehaviorReport::verify`] checks it against
+//! the public record of the game alone, so the player who aborts the game does not have to be
+//! trusted and cannot frame an honest player.
+//!
+//! The exception is a timeout: no message proves that another one was never sent. A `Timeout`
+//! report only names the player and the last message they signed; the arbiter should give the
+//! accused the chance to produce a signed message for a later slot. A `TimeoutClaim` shows that
+//! the game waited for the accused, with a deadline counted in messages of the event log.
+
+use crate::crypto_primitives::signature::Signature;
+use crate::crypto_primitives::utils::format::{Compact, Hex, HexBytes};
+use crate::discrete_log_cards::identity::{IdentityKey, PublicIdentity};
+use crate::discrete_log_cards::session::{GameMessage, ShuffleProof};
+use crate::discrete_log_cards::{starting_deck, Card, DLCards, MaskedCard, Parameters, PublicKey};
+use crate::error::{CardProtocolError, InputError, VerificationError};
+use crate::BarnettSmartProtocol;
+
+use ark_ec::ProjectiveCurve;
+use ark_ff::{ToBytes, Zero};
+use ark_serialize::{CanonicalDeserialize, CanonicalSerialize, Read, SerializationError, Write};
+use ark_std::fmt;
+
+const SIGNED_MESSAGE_DOMAIN: &'static [u8] = b"Signed Game Message";
+
+/// A message signed by the player in seat `sender`. An honest player signs at most one message
+/// for each `slot`.
+#[derive(Clone, Debug, CanonicalSerialize, CanonicalDeserialize)]
+pub struct SignedMessage<C: ProjectiveCurve> {
+    pub sender: usize,
+    pub slot: u64,
+    pub message: GameMessage<C>,
+    pub signature: Signature<C>,
+}
+
+impl<C: ProjectiveCurve> SignedMessage<C> {
+    pub fn sign(
+        identity: &IdentityKey<C>,
+        context: &[u8],
+        sender: usize,
+        slot: u64,
+        message: GameMessage<C>,
+    ) -> Result<Self, CardProtocolError> {
+        let bytes = signed_bytes(context, sender, slot, &message)?;
+
+        Ok(Self {
+            sender,
+            slot,
+            message,
+            signature: identity.sign(&bytes)?,
+        })
+    }
+
+    /// Check the signature against the identity of the sender. `identities[i]` is the identity
+    /// of the player in seat `i`.
+    pub fn verify(
+        &self,
+        context: &[u8],
+        identities: &[PublicIdentity<C>],
+    ) -> Result<(), CardProtocolError> {
+        let identity = identities
+            .get(self.sender)
+            .ok_or(CardProtocolError::InvalidInput(InputError::UnknownPlayer(
+                self.sender,
+            )))?;
+
+        let bytes = signed_bytes(context, self.sender, self.slot, &self.message)?;
+        identity.verify(&bytes, &self.signature)
+    }
+}
+
+fn signed_bytes<C: ProjectiveCurve>(
+    context: &[u8],
+    sender: usize,
+    slot: u64,
+    message: &GameMessage<C>,
+) -> Result<Vec<u8>, CardProtocolError> {
+    let mut bytes = SIGNED_MESSAGE_DOMAIN.to_vec();
+    (context.len() as u64).serialize(&mut bytes)?;
+    bytes.extend_from_slice(context);
+    (sender as u64, slot).serialize(&mut bytes)?;
+    message.serialize(&mut bytes)?;
+
+    Ok(bytes)
+}
+
+/// Public record of a game: everything an arbiter needs to check a report besides the parameters.
+/// Every vector is indexed by seat, and seats shuffle in increasing order as in a `Session`.
+#[derive(Clone)]
+pub struct GameRecord<C: ProjectiveCurve, B> {
+    pub context: Vec<u8>,
+    pub identities: Vec<PublicIdentity<C>>,
+    pub keys: Vec<PublicKey<C>>,
+    pub player_info: Vec<B>,
+    /// Open cards the starting deck is built from
+    pub cards: Vec<Card<C>>,
+}
+
+impl<C: ProjectiveCurve, B: fmt::Debug> fmt::Debug for GameRecord<C, B> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("GameRecord")
+            .field("context", &HexBytes(&self.context))
+            .field("identities", &self.identities)
+            .field("keys", &self.keys.iter().map(Hex).collect::<Vec<_>>())
+            .field("player_info", &self.player_info)
+            .field("cards", &Compact(&self.cards))
+            .finish()
+    }
+}
+
+/// Evidence that a player cheated or stalled the game.
+#[derive(Clone, Debug)]
+pub enum MisbehaviorReport<C: ProjectiveCurve> {
+    /// A shuffle whose proof does not verify. `previous` is the shuffle of the seat before, whose
+    /// output the culprit had to shuffle, and `None` for the first shuffle, which starts from the
+    /// starting deck.
+    BadShuffle {
+        previous: Option<SignedMessage<C>>,
+        shuffle: SignedMessage<C>,
+    },
+    /// Reveal tokens with an invalid one for the card at `index` of the deck output by
+    /// `last_shuffle`, the shuffle of the last seat.
+    BadRevealToken {
+        last_shuffle: SignedMessage<C>,
+        tokens: SignedMessage<C>,
+        index: usize,
+    },
+    /// A proof of key ownership that does not verify for the key list of the game.
+    BadKeyProof { proof: SignedMessage<C> },
+    /// Two different messages signed by the same player for the same slot.
+    Equivocation {
+        msg_a: SignedMessage<C>,
+        msg_b: SignedMessage<C>,
+    },
+    /// No message after `last_seen` by `deadline`, in seconds since the Unix epoch.
+    Timeout {
+        deadline: u64,
+        last_seen: SignedMessage<C>,
+    },
+}
+
+impl<C: ProjectiveCurve> MisbehaviorReport<C> {
+    /// Check the report against the public record of the game. Returns the seat of the player at
+    /// fault, or `UnfoundedBlame` if the messages do not show any misbehavior.
+    pub fn verify<B: ToBytes>(
+        &self,
+        pp: &Parameters<C>,
+        record: &GameRecord<C, B>,
+    ) -> Result<usize, CardProtocolError> {
+        let context = &record.context[..];
+
+        match self {
+            MisbehaviorReport::BadShuffle { previous, shuffle } => {
+                shuffle.verify(context, &record.identities)?;
+                let (deck, proof) = shuffle_of(shuffle)?;
+
+                let aggregate_key = record
+                    .keys
+                    .iter()
+                    .fold(PublicKey::<C>::zero(), |acc, pk| acc + *pk);
+                let input = match previous {
+                    None if shuffle.sender == 0 => {
+                        starting_deck(pp, &aggregate_key, &record.cards)?
+                    }
+                    Some(previous) if previous.sender + 1 == shuffle.sender => {
+                        previous.verify(context, &record.identities)?;
+                        shuffle_of(previous)?.0.clone()
+                    }
+                    _ => return Err(VerificationError::UnfoundedBlame.into()),
+                };
+
+                if deck.len() != input.len() {
+                    return Ok(shuffle.sender);
+                }
+
+                match DLCards::<C>::verify_shuffle(pp, context, &aggregate_key, &input, deck, proof)
+                {
+                    Ok(()) => Err(CardProtocolError::VerificationFailed(
+                        VerificationError::UnfoundedBlame,
+                    )),
+                    Err(_) => Ok(shuffle.sender),
+                }
+            }
+            MisbehaviorReport::BadRevealToken {
+                last_shuffle,
+                tokens,
+                index,
+            } => {
+                last_shuffle.verify(context, &record.identities)?;
+                if last_shuffle.sender + 1 != record.identities.len() {
+                    return Err(VerificationError::UnfoundedBlame.into());
+                }
+                let (deck, _) = shuffle_of(last_shuffle)?;
+
+                tokens.verify(context, &record.identities)?;
+                let (token, proof) = match &tokens.message {
+                    GameMessage::RevealTokens(tokens) => tokens
+                        .iter()
+                        .find(|(token_index, _, _)| token_index == index)
+                        .map(|(_, token, proof)| (token, proof)),
+                    _ => None,
+                }
+                .ok_or(CardProtocolError::VerificationFailed(
+                    VerificationError::UnfoundedBlame,
+                ))?;
+                let masked_card = deck
+                    .get(*index)
+                    .ok_or(CardProtocolError::VerificationFailed(
+                        VerificationError::UnfoundedBlame,
+                    ))?;
+                let pk = record
+                    .keys
+                    .get(tokens.sender)
+                    .ok_or(CardProtocolError::InvalidInput(InputError::UnknownPlayer(
+                        tokens.sender,
+                    )))?;
+
+                match DLCards::<C>::verify_reveal(pp, context, pk, token, masked_card, proof) {
+                    Ok(()) => Err(CardProtocolError::VerificationFailed(
+                        VerificationError::UnfoundedBlame,
+                    )),
+                    Err(_) => Ok(tokens.sender),
+                }
+            }
+            MisbehaviorReport::BadKeyProof { proof } => {
+                proof.verify(context, &record.identities)?;
+                let key_proof = match &proof.message {
+                    GameMessage::KeyOwnership(key_proof) => key_proof,
+                    _ => return Err(VerificationError::UnfoundedBlame.into()),
+                };
+
+                let player = proof.sender;
+                let (pk, player_info) = record
+                    .keys
+                    .get(player)
+                    .zip(record.player_info.get(player))
+                    .ok_or(CardProtocolError::InvalidInput(InputError::UnknownPlayer(
+                        player,
+                    )))?;
+                let key_list_digest = DLCards::<C>::key_list_digest(&record.keys)?;
+
+                match DLCards::<C>::verify_key_ownership(
+                    pp,
+                    context,
+                    pk,
+                    player_info,
+                    player,
+                    &key_list_digest,
+                    key_proof,
+                ) {
+                    Ok(()) => Err(CardProtocolError::VerificationFailed(
+                        VerificationError::UnfoundedBlame,
+                    )),
+                    Err(_) => Ok(player),
+                }
+            }
+            MisbehaviorReport::Equivocation { msg_a, msg_b } => {
+                msg_a.verify(context, &record.identities)?;
+                msg_b.verify(context, &record.identities)?;
+
+                let (mut bytes_a, mut bytes_b) = (Vec::new(), Vec::new());
+                msg_a.message.serialize(&mut bytes_a)?;
+                msg_b.message.serialize(&mut bytes_b)?;
+
+                if msg_a.sender != msg_b.sender || msg_a.slot != msg_b.slot || bytes_a == bytes_b {
+                    return Err(VerificationError::UnfoundedBlame.into());
+                }
+
+                Ok(msg_a.sender)
+            }
+            MisbehaviorReport::Timeout { last_seen, .. } => {
+                last_seen.verify(context, &record.identities)?;
+
+                Ok(last_seen.sender)
+            }
+        }
+    }
+}
+
+fn shuffle_of<C: ProjectiveCurve>(
+    message: &SignedMessage<C>,
+) -> Result<(&Vec<MaskedCard<C>>, &ShuffleProof<C>), CardProtocolError> {
+    match &message.message {
+        GameMessage::Shuffle { deck, proof } => Ok((deck, proof)),
+        _ => Err(CardProtocolError::VerificationFailed(
+            VerificationError::UnfoundedBlame,
+        )),
+    }
+}
+
+impl<C: ProjectiveCurve> CanonicalSerialize for MisbehaviorReport<C> {
+    fn serialize<W: Write>(&self, mut writer: W) -> Result<(), SerializationError> {
+        match self {
+            MisbehaviorReport::BadShuffle { previous, shuffle } => {
+                0u8.serialize(&mut writer)?;
+                previous.serialize(&mut writer)?;
+                shuffle.serialize(&mut writer)
+            }
+            MisbehaviorReport::BadRevealToken {
+                last_shuffle,
+                tokens,
+                index,
+            } => {
+                1u8.serialize(&mut writer)?;
+                last_shuffle.serialize(&mut writer)?;
+                tokens.serialize(&mut writer)?;
+                index.serialize(&mut writer)
+            }
+            MisbehaviorReport::BadKeyProof { proof } => {
+                2u8.serialize(&mut writer)?;
+                proof.serialize(&mut writer)
+            }
+            MisbehaviorReport::Equivocation { msg_a, msg_b } => {
+                3u8.serialize(&mut writer)?;
+                msg_a.serialize(&mut writer)?;
+                msg_b.serialize(&mut writer)
+            }
+            MisbehaviorReport::Timeout {
+                deadline,
+                last_seen,
+            } => {
+                4u8.serialize(&mut writer)?;
+                deadline.serialize(&mut writer)?;
+                last_seen.serialize(&mut writer)
+            }
+        }
+    }
+
+    fn serialized_size(&self) -> usize {
+        1 + match self {
+            MisbehaviorReport::BadShuffle { previous, shuffle } => {
+                previous.serialized_size() + shuffle.serialized_size()
+            }
+            MisbehaviorReport::BadRevealToken {
+                last_shuffle,
+                tokens,
+                index,
+            } => {
+                last_shuffle.serialized_size() + tokens.serialized_size() + index.serialized_size()
+            }
+            MisbehaviorReport::BadKeyProof { proof } => proof.serialized_size(),
+            MisbehaviorReport::Equivocation { msg_a, msg_b } => {
+                msg_a.serialized_size() + msg_b.serialized_size()
+            }
+            MisbehaviorReport::Timeout {
+                deadline,
+                last_seen,
+            } => deadline.serialized_size() + last_seen.serialized_size(),
+        }
+    }
+}
+
+impl<C: ProjectiveCurve> CanonicalDeserialize for MisbehaviorReport<C> {
+    fn deserialize<R: Read>(mut reader: R) -> Result<Self, SerializationError> {
+        match u8::deserialize(&mut reader)? {
+            0 => Ok(MisbehaviorReport::BadShuffle {
+                previous: CanonicalDeserialize::deserialize(&mut reader)?,
+                shuffle: CanonicalDeserialize::deserialize(&mut reader)?,
+            }),
+            1 => Ok(MisbehaviorReport::BadRevealToken {
+                last_shuffle: CanonicalDeserialize::deserialize(&mut reader)?,
+                tokens: CanonicalDeserialize::deserialize(&mut reader)?,
+                index: CanonicalDeserialize::deserialize(&mut reader)?,
+            }),
+            2 => Ok(MisbehaviorReport::BadKeyProof {
+                proof: CanonicalDeserialize::deserialize(&mut reader)?,
+            }),
+            3 => Ok(MisbehaviorReport::Equivocation {
+                msg_a: CanonicalDeserialize::deserialize(&mut reader)?,
+                msg_b: CanonicalDeserialize::deserialize(&mut reader)?,
+            }),
+            4 => Ok(MisbehaviorReport::Timeout {
+                deadline: CanonicalDeserialize::deserialize(&mut reader)?,
+                last_seen: CanonicalDeserialize::deserialize(&mut reader)?,
+            }),
+            _ => Err(SerializationError::InvalidData),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{GameRecord, MisbehaviorReport, SignedMessage};
+    use crate::crypto_primitives::utils::format::Hex;
+    use crate::crypto_primitives::utils::rand::test_rng;
+    use crate::discrete_log_cards;
+    use crate::discrete_log_cards::encoding::encode_standard_deck;
+    use crate::discrete_log_cards::identity::IdentityKey;
+    use crate::discrete_log_cards::session::GameMessage;
+    use crate::discrete_log_cards::starting_deck;
+    use crate::discrete_log_cards::test_helpers::setup_players;
+    use crate::error::{CardProtocolError, VerificationError};
+    use crate::BarnettSmartProtocol;
+
+    use ark_ff::Zero;
+    use ark_serialize::{CanonicalDeserialize, CanonicalSerialize};
+    use ark_std::rand::{CryptoRng, Rng};
+
+    // Choose elliptic curve setting
+    type Curve = starknet_curve::Projective;
+
+    // Instantiate concrete type for our card protocol
+    type CardProtocol<'a> = discrete_log_cards::DLCards<'a, Curve>;
+    type CardParameters = discrete_log_cards::Parameters<Curve>;
+    type MaskedCard = discrete_log_cards::MaskedCard<Curve>;
+    type PublicKey = discrete_log_cards::PublicKey<Curve>;
+
+    const SESSION_ID: &[u8] = b"abort test";
+
+    fn setup<R: Rng + CryptoRng>(
+        rng: &mut R,
+        num_of_players: usize,
+    ) -> (
+        CardParameters,
+        Vec<IdentityKey<Curve>>,
+        GameRecord<Curve, u64>,
+    ) {
+        let m = 4;
+        let n = 13;
+
+        let parameters = CardProtocol::setup(rng, m, n).unwrap();
+        let (players, _) = setup_players(rng, &parameters, num_of_players).unwrap();
+        let identity_keys = (0..num_of_players)
+            .map(|_| IdentityKey::new(rng).unwrap())
+            .collect::<Vec<_>>();
+        let record = GameRecord {
+            context: SESSION_ID.to_vec(),
+            identities: identity_keys
+                .iter()
+                .map(|identity| identity.verifying_key())
+                .collect(),
+            keys: players.iter().map(|(pk, _, _)| *pk).collect(),
+            player_info: (0..num_of_players as u64).collect(),
+            cards: encode_standard_deck(1).unwrap(),
+        };
+
+        (parameters, identity_keys, record)
+    }
+
+    fn shuffle<R: Rng + CryptoRng>(
+        rng: &mut R,
+        parameters: &CardParameters,
+        record: &GameRecord<Curve, u64>,
+        deck: &Vec<MaskedCard>,
+    ) -> GameMessage<Curve> {
+        let aggregate_key = record
+            .keys
+            .iter()
+            .fold(PublicKey::zero(), |acc, pk| acc + *pk);
+        let (deck, proof) = CardProtocol::shuffle_and_remask_with_rng(
+            rng,
+            parameters,
+            SESSION_ID,
+            &aggregate_key,
+            deck,
+        )
+        .unwrap();
+
+        GameMessage::Shuffle { deck, proof }
+    }
+
+    #[test]
+    fn test_bad_shuffle_report() {
+        let rng = &mut test_rng();
+        let (parameters, identity_keys, record) = setup(rng, 3);
+
+        let aggregate_key = record
+            .keys
+            .iter()
+            .fold(PublicKey::zero(), |acc, pk| acc + *pk);
+        let start = starting_deck(&parameters, &aggregate_key, &record.cards).unwrap();
+
+        // Player 0 shuffles honestly
+        let first = shuffle(rng, &parameters, &record, &start);
+        let first = SignedMessage::sign(&identity_keys[0], SESSION_ID, 0, 1, first).unwrap();
+        let report = MisbehaviorReport::BadShuffle {
+            previous: None,
+            shuffle: first.clone(),
+        };
+        assert_eq!(
+            report.verify(&parameters, &record),
+            Err(CardProtocolError::VerificationFailed(
+                VerificationError::UnfoundedBlame
+            ))
+        );
+
+        // Player 1 duplicates a card after shuffling
+        let input = match &first.message {
+            GameMessage::Shuffle { deck, .. } => deck.clone(),
+            _ => unreachable!(),
+        };
+        let second = match shuffle(rng, &parameters, &record, &input) {
+            GameMessage::Shuffle { mut deck, proof } => {
+                deck[0] = deck[1];
+                GameMessage::Shuffle { deck, proof }
+            }
+            _ => unreachable!(),
+        };
+        let second = SignedMessage::sign(&identity_keys[1], SESSION_ID, 1, 1, second).unwrap();
+
+        // The report travels as bytes to the arbiter
+        let report = MisbehaviorReport::BadShuffle {
+            previous: Some(first.clone()),
+            shuffle: second.clone(),
+        };
+        let mut bytes = Vec::new();
+        report.serialize(&mut bytes).unwrap();
+        let received = MisbehaviorReport::<Curve>::deserialize(&bytes[..]).unwrap();
+        assert_eq!(received.verify(&parameters, &record), Ok(1));
+
+        // The shuffle must be judged against the output of the seat before
+        let report = MisbehaviorReport::BadShuffle {
+            previous: None,
+            shuffle: second.clone(),
+        };
+        assert_eq!(
+            report.verify(&parameters, &record),
+            Err(CardProtocolError::VerificationFailed(
+                VerificationError::UnfoundedBlame
+            ))
+        );
+
+        // Nor can the accuser put words in player 1's mouth
+        let mut forged = second;
+        forged.sender = 2;
+        let report = MisbehaviorReport::BadShuffle {
+            previous: Some(first),
+            shuffle: forged,
+        };
+        assert_eq!(
+            report.verify(&parameters, &record),
+            Err(CardProtocolError::VerificationFailed(
+                VerificationError::InvalidSignature
+            ))
+        );
+    }
+
+    #[test]
+    fn test_equivocation_report() {
+        let rng = &mut test_rng();
+        let (parameters, identity_keys, record) = setup(rng, 3);
+
+        // Player 2 announces two different keys for the same slot
+        let msg_a = GameMessage::PublicKey(record.keys[2]);
+        let msg_b = GameMessage::PublicKey(record.keys[0]);
+        let msg_a = SignedMessage::sign(&identity_keys[2], SESSION_ID, 2, 0, msg_a).unwrap();
+        let msg_b = SignedMessage::sign(&identity_keys[2], SESSION_ID, 2, 0, msg_b).unwrap();
+
+        let report = MisbehaviorReport::Equivocation {
+            msg_a: msg_a.clone(),
+            msg_b: msg_b.clone(),
+        };
+        assert_eq!(report.verify(&parameters, &record), Ok(2));
+        assert_eq!(
+            format!(
+                concat!(
+                    "Equivocation {{ msg_a: SignedMessage {{ sender: 2, slot: 0, ",
+                    "message: PublicKey({:?}), signature: {:?} }}, msg_b: {:?} }}"
+                ),
+                Hex(&record.keys[2]),
+                msg_a.signature,
+                msg_b
+            ),
+            format!("{:?}", report)
+        );
+
+        // The same message twice is not equivocation, nor are messages for different slots
+        let report = MisbehaviorReport::Equivocation {
+            msg_a: msg_a.clone(),
+            msg_b: msg_a.clone(),
+        };
+        assert_eq!(
+            report.verify(&parameters, &record),
+            Err(CardProtocolError::VerificationFailed(
+                VerificationError::UnfoundedBlame
+            ))
+        );
+
+        let later = SignedMessage::sign(
+            &identity_keys[2],
+            SESSION_ID,
+            2,
+            1,
+            GameMessage::PublicKey(record.keys[0]),
+        )
+        .unwrap();
+        let report = MisbehaviorReport::Equivocation {
+            msg_a,
+            msg_b: later,
+        };
+        assert_eq!(
+            report.verify(&parameters, &record),
+            Err(CardProtocolError::VerificationFailed(
+                VerificationError::UnfoundedBlame
+            ))
+        );
+
+        // Signatures are bound to the game
+        let other_game = SignedMessage::sign(
+            &identity_keys[2],
+            b"another game",
+            2,
+            0,
+            GameMessage::PublicKey(record.keys[1]),
+        )
+        .unwrap();
+        let report = MisbehaviorReport::Equivocation {
+            msg_a: msg_b,
+            msg_b: other_game,
+        };
+        assert_eq!(
+            report.verify(&parameters, &record),
+            Err(CardProtocolError::VerificationFailed(
+                VerificationError::InvalidSignature
+            ))
+        );
+    }
+}
@@ -0,0 +1,486 @@
+//! Players who cheat, to check that the honest players at the table catch them.
+//!
+//! An [`Adversary`] has a hook for every message a seat sends in a simulated game: its public key,
+//! its key ownership proof, its shuffle, its reveal tokens for the deal and for the showdown. Each
+//! hook receives the message an honest player would send and returns what the adversary sends
+//! instead, by default the honest message. [`assert_detected`] plays a game with one adversarial
+//! seat and checks that the watchdog of every other seat flags that seat with the report the
+//! adversary expects, and that the report convinces an arbiter.
+//!
+//! A new behavior is a type that overrides the hooks it needs. The behaviors of this module cover
+//! a tampered key proof, a stale shuffle proof, a shuffle without remasking, a shuffle that drops
+//! a card, decks equivocated to different players, and forged or misplaced reveal tokens.
+
+use crate::discrete_log_cards::abort::MisbehaviorReport;
+use crate::discrete_log_cards::encoding::DeckSpec;
+use crate::discrete_log_cards::session::GameMessage;
+use crate::discrete_log_cards::simulation::{
+    play, Hooks, Outgoing, Played, SeatView, SimulationRules, Step,
+};
+use crate::discrete_log_cards::DLCards;
+use crate::error::{CardProtocolError, InputError};
+use crate::BarnettSmartProtocol;
+
+use ark_ec::ProjectiveCurve;
+use ark_std::rand::{CryptoRng, Rng};
+use proof_essentials::utils::permutation::Permutation;
+
+/// Variants of [`MisbehaviorReport`], for adversaries to say which one they should be caught
+/// with.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ReportKind {
+    BadShuffle,
+    BadRevealToken,
+    BadKeyProof,
+    Equivocation,
+    Timeout,
+}
+
+impl ReportKind {
+    pub fn of<C: ProjectiveCurve>(report: &MisbehaviorReport<C>) -> Self {
+        match report {
+            MisbehaviorReport::BadShuffle { .. } => ReportKind::BadShuffle,
+            MisbehaviorReport::BadRevealToken { .. } => ReportKind::BadRevealToken,
+            MisbehaviorReport::BadKeyProof { .. } => ReportKind::BadKeyProof,
+            MisbehaviorReport::Equivocation { .. } => ReportKind::Equivocation,
+            MisbehaviorReport::Timeout { .. } => ReportKind::Timeout,
+        }
+    }
+}
+
+/// A cheating seat. Every hook receives the message an honest player would send at that step.
+pub trait Adversary<C: ProjectiveCurve> {
+    /// The report the honest players should raise against this adversary.
+    fn expected_report(&self) -> ReportKind;
+
+    fn public_key(
+        &mut self,
+        _view: &mut SeatView<'_, C>,
+        honest: GameMessage<C>,
+    ) -> Result<Outgoing<C>, CardProtocolError> {
+        Ok(Outgoing::Broadcast(honest))
+    }
+
+    fn key_ownership(
+        &mut self,
+        _view: &mut SeatView<'_, C>,
+        honest: GameMessage<C>,
+    ) -> Result<Outgoing<C>, CardProtocolError> {
+        Ok(Outgoing::Broadcast(honest))
+    }
+
+    fn shuffle(
+        &mut self,
+        _view: &mut SeatView<'_, C>,
+        honest: GameMessage<C>,
+    ) -> Result<Outgoing<C>, CardProtocolError> {
+        Ok(Outgoing::Broadcast(honest))
+    }
+
+    /// Tokens for the hole cards of the other players.
+    fn deal(
+        &mut self,
+        _view: &mut SeatView<'_, C>,
+        honest: GameMessage<C>,
+    ) -> Result<Outgoing<C>, CardProtocolError> {
+        Ok(Outgoing::Broadcast(honest))
+    }
+
+    /// Tokens for the own hole cards of the seat, which open them at showdown.
+    fn showdown(
+        &mut self,
+        _view: &mut SeatView<'_, C>,
+        honest: GameMessage<C>,
+    ) -> Result<Outgoing<C>, CardProtocolError> {
+        Ok(Outgoing::Broadcast(honest))
+    }
+}
+
+/// Routes the messages of `seat` through the adversary, and leaves the other seats honest.
+struct AdversarialSeat<'a, A> {
+    seat: usize,
+    adversary: &'a mut A,
+}
+
+impl<'a, C: ProjectiveCurve, A: Adversary<C>> Hooks<C> for AdversarialSeat<'a, A> {
+    fn send(
+        &mut self,
+        step: Step,
+        view: &mut SeatView<'_, C>,
+        honest: GameMessage<C>,
+    ) -> Result<Outgoing<C>, CardProtocolError> {
+        if view.seat != self.seat {
+            return Ok(Outgoing::Broadcast(honest));
+        }
+
+        match step {
+            Step::PublicKey => self.adversary.public_key(view, honest),
+            Step::KeyOwnership => self.adversary.key_ownership(view, honest),
+            Step::Shuffle => self.adversary.shuffle(view, honest),
+            Step::Deal => self.adversary.deal(view, honest),
+            Step::Showdown => self.adversary.showdown(view, honest),
+        }
+    }
+}
+
+/// Play a game of `num_of_players` with `adversary` in `seat`, and panic unless the watchdog of
+/// every other seat flags `seat` with the report the adversary expects, and the report, checked
+/// against the record of the game, blames `seat`.
+pub fn assert_detected<R, C, A>(
+    rng: &mut R,
+    num_of_players: usize,
+    deck_spec: &DeckSpec,
+    rules: &SimulationRules,
+    seat: usize,
+    mut adversary: A,
+) where
+    R: Rng + CryptoRng,
+    C: ProjectiveCurve,
+    A: Adversary<C>,
+{
+    let expected = adversary.expected_report();
+    let mut hooks = AdversarialSeat {
+        seat,
+        adversary: &mut adversary,
+    };
+    let aborted = match play::<_, C, _>(rng, num_of_players, deck_spec, rules, &mut hooks) {
+        Ok(Played::Aborted(aborted)) => aborted,
+        Ok(Played::Completed(_)) => panic!("the game completed without detecting seat {}", seat),
+        Err(error) => panic!("the game failed before detecting seat {}: {}", seat, error),
+    };
+    let record = aborted
+        .record
+        .expect("the adversary is caught after every key is published");
+
+    for observer in (0..num_of_players).filter(|&observer| observer != seat) {
+        let violation = aborted
+            .violations
+            .iter()
+            .find(|(flagged_by, _)| *flagged_by == observer)
+            .map(|(_, violation)| violation)
+            .unwrap_or_else(|| panic!("seat {} did not flag anyone", observer));
+        assert_eq!(
+            violation.player(),
+            seat,
+            "seat {} blamed the wrong seat",
+            observer
+        );
+
+        let report = violation
+            .report
+            .as_ref()
+            .unwrap_or_else(|| panic!("seat {} raised no report: {}", observer, violation.error));
+        assert_eq!(ReportKind::of(report), expected);
+        assert_eq!(report.verify(&aborted.parameters, &record), Ok(seat));
+    }
+}
+
+/// Proves ownership of its key for the seat after its own.
+#[derive(Debug)]
+pub struct KeyProofForAnotherSeat;
+
+impl<C: ProjectiveCurve> Adversary<C> for KeyProofForAnotherSeat {
+    fn expected_report(&self) -> ReportKind {
+        ReportKind::BadKeyProof
+    }
+
+    fn key_ownership(
+        &mut self,
+        view: &mut SeatView<'_, C>,
+        _honest: GameMessage<C>,
+    ) -> Result<Outgoing<C>, CardProtocolError> {
+        let other = (view.seat + 1) % view.session.num_of_players();
+        let key_list_digest = view
+            .session
+            .key_list_digest()?
+            .ok_or(CardProtocolError::InvalidInput(InputError::UnexpectedPhase))?;
+        let proof = DLCards::<C>::prove_key_ownership(
+            &mut view.rng,
+            view.parameters,
+            view.session.context(),
+            &view.public_key,
+            view.secret_key,
+            &(other as u64),
+            other,
+            &key_list_digest,
+        )?;
+
+        Ok(Outgoing::Broadcast(GameMessage::KeyOwnership(proof)))
+    }
+}
+
+/// Sends a fresh shuffle with the proof of the shuffle before it. Must not sit in the first seat,
+/// which has no shuffle to reuse.
+#[derive(Debug)]
+pub struct StaleShuffleProof;
+
+impl<C: ProjectiveCurve> Adversary<C> for StaleShuffleProof {
+    fn expected_report(&self) -> ReportKind {
+        ReportKind::BadShuffle
+    }
+
+    fn shuffle(
+        &mut self,
+        view: &mut SeatView<'_, C>,
+        honest: GameMessage<C>,
+    ) -> Result<Outgoing<C>, CardProtocolError> {
+        let stale = view
+            .log
+            .entries()
+            .iter()
+            .rev()
+            .find_map(|entry| match &entry.message {
+                GameMessage::Shuffle { proof, .. } => Some(proof.clone()),
+                _ => None,
+            })
+            .ok_or(CardProtocolError::InvalidInput(InputError::UnexpectedPhase))?;
+
+        match honest {
+            GameMessage::Shuffle { deck, .. } => Ok(Outgoing::Broadcast(GameMessage::Shuffle {
+                deck,
+                proof: stale,
+            })),
+            _ => Err(CardProtocolError::InvalidInput(InputError::UnexpectedPhase)),
+        }
+    }
+}
+
+/// Permutes the deck it received without remasking it, with the proof of an honest shuffle.
+#[derive(Debug)]
+pub struct PermuteWithoutRemasking;
+
+impl<C: ProjectiveCurve> Adversary<C> for PermuteWithoutRemasking {
+    fn expected_report(&self) -> ReportKind {
+        ReportKind::BadShuffle
+    }
+
+    fn shuffle(
+        &mut self,
+        view: &mut SeatView<'_, C>,
+        honest: GameMessage<C>,
+    ) -> Result<Outgoing<C>, CardProtocolError> {
+        let deck = view.session.deck().to_vec();
+        let permutation = Permutation::new(&mut view.rng, deck.len());
+
+        match honest {
+            GameMessage::Shuffle { proof, .. } => Ok(Outgoing::Broadcast(GameMessage::Shuffle {
+                deck: permutation.permute_array(&deck),
+                proof,
+            })),
+            _ => Err(CardProtocolError::InvalidInput(InputError::UnexpectedPhase)),
+        }
+    }
+}
+
+/// Shuffles honestly, then drops the last card of the deck.
+#[derive(Debug)]
+pub struct DroppedCard;
+
+impl<C: ProjectiveCurve> Adversary<C> for DroppedCard {
+    fn expected_report(&self) -> ReportKind {
+        ReportKind::BadShuffle
+    }
+
+    fn shuffle(
+        &mut self,
+        _view: &mut SeatView<'_, C>,
+        honest: GameMessage<C>,
+    ) -> Result<Outgoing<C>, CardProtocolError> {
+        match honest {
+            GameMessage::Shuffle { mut deck, proof } => {
+                deck.pop();
+                Ok(Outgoing::Broadcast(GameMessage::Shuffle { deck, proof }))
+            }
+            _ => Err(CardProtocolError::InvalidInput(InputError::UnexpectedPhase)),
+        }
+    }
+}
+
+/// Sends its honest shuffle to the even seats and another valid shuffle to the odd seats, so that
+/// no single shuffle is invalid but players no longer hold the same deck.
+#[derive(Debug)]
+pub struct EquivocatedDeck;
+
+impl<C: ProjectiveCurve> Adversary<C> for EquivocatedDeck {
+    fn expected_report(&self) -> ReportKind {
+        ReportKind::Equivocation
+    }
+
+    fn shuffle(
+        &mut self,
+        view: &mut SeatView<'_, C>,
+        honest: GameMessage<C>,
+    ) -> Result<Outgoing<C>, CardProtocolError> {
+        let shared_key = view
+            .session
+            .aggregate_key()
+            .ok_or(CardProtocolError::InvalidInput(InputError::UnexpectedPhase))?;
+        let (deck, proof) = DLCards::<C>::shuffle_and_remask_with_rng(
+            &mut view.rng,
+            view.parameters,
+            view.session.context(),
+            &shared_key,
+            &view.session.deck().to_vec(),
+        )?;
+        let other = GameMessage::Shuffle { deck, proof };
+
+        let messages = (0..view.session.num_of_players())
+            .map(|recipient| match recipient % 2 {
+                0 => honest.clone(),
+                _ => other.clone(),
+            })
+            .collect();
+
+        Ok(Outgoing::PerRecipient(messages))
+    }
+}
+
+/// Deals a token computed for another card of the deck in place of its first token, with the
+/// proof for that other card.
+#[derive(Debug)]
+pub struct TokenForWrongCard;
+
+impl<C: ProjectiveCurve> Adversary<C> for TokenForWrongCard {
+    fn expected_report(&self) -> ReportKind {
+        ReportKind::BadRevealToken
+    }
+
+    fn deal(
+        &mut self,
+        view: &mut SeatView<'_, C>,
+        honest: GameMessage<C>,
+    ) -> Result<Outgoing<C>, CardProtocolError> {
+        let mut tokens = match honest {
+            GameMessage::RevealTokens(tokens) => tokens,
+            _ => return Err(InputError::UnexpectedPhase.into()),
+        };
+        let deck = view.session.deck();
+        let first = tokens
+            .first_mut()
+            .ok_or(CardProtocolError::InvalidInput(InputError::UnexpectedPhase))?;
+        let other = (first.0 + 1) % deck.len();
+        let (token, proof) = DLCards::<C>::compute_reveal_token(
+            &mut view.rng,
+            view.parameters,
+            view.session.context(),
+            view.secret_key,
+            &view.public_key,
+            &deck[other],
+        )?;
+        first.1 = token;
+        first.2 = proof;
+
+        Ok(Outgoing::Broadcast(GameMessage::RevealTokens(tokens)))
+    }
+}
+
+/// Opens its hand at showdown with a token that is not the one its proof is for, to claim a card
+/// it was not dealt.
+#[derive(Debug)]
+pub struct ForgedShowdownToken;
+
+impl<C: ProjectiveCurve> Adversary<C> for ForgedShowdownToken {
+    fn expected_report(&self) -> ReportKind {
+        ReportKind::BadRevealToken
+    }
+
+    fn showdown(
+        &mut self,
+        _view: &mut SeatView<'_, C>,
+        honest: GameMessage<C>,
+    ) -> Result<Outgoing<C>, CardProtocolError> {
+        match honest {
+            GameMessage::RevealTokens(mut tokens) => {
+                let first = tokens
+                    .first_mut()
+                    .ok_or(CardProtocolError::InvalidInput(InputError::UnexpectedPhase))?;
+                first.1 = first.1 + first.1;
+                Ok(Outgoing::Broadcast(GameMessage::RevealTokens(tokens)))
+            }
+            _ => Err(CardProtocolError::InvalidInput(InputError::UnexpectedPhase)),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{
+        assert_detected, DroppedCard, EquivocatedDeck, ForgedShowdownToken, KeyProofForAnotherSeat,
+        PermuteWithoutRemasking, StaleShuffleProof, TokenForWrongCard,
+    };
+    use crate::crypto_primitives::utils::rand::test_rng;
+    use crate::discrete_log_cards::encoding::DeckSpec;
+    use crate::discrete_log_cards::simulation::SimulationRules;
+
+    // Choose elliptic curve setting
+    type Curve = starknet_curve::Projective;
+
+    // A small deck keeps every game short: one hole card each and a street of one card
+    fn table() -> (DeckSpec, SimulationRules) {
+        let deck = DeckSpec::custom((0..8).map(|card| format!("card{}", card)).collect());
+        let rules = SimulationRules {
+            context: b"adversary test".to_vec(),
+            hole_cards_per_player: 1,
+            community_cards: vec![1],
+            burns: 0,
+        };
+
+        (deck, rules)
+    }
+
+    #[test]
+    fn test_key_proof_for_another_seat_is_detected() {
+        let (deck, rules) = table();
+        assert_detected::<_, Curve, _>(
+            &mut test_rng(),
+            3,
+            &deck,
+            &rules,
+            1,
+            KeyProofForAnotherSeat,
+        );
+    }
+
+    #[test]
+    fn test_stale_shuffle_proof_is_detected() {
+        let (deck, rules) = table();
+        assert_detected::<_, Curve, _>(&mut test_rng(), 3, &deck, &rules, 1, StaleShuffleProof);
+    }
+
+    #[test]
+    fn test_permutation_without_remasking_is_detected() {
+        let (deck, rules) = table();
+        assert_detected::<_, Curve, _>(
+            &mut test_rng(),
+            3,
+            &deck,
+            &rules,
+            0,
+            PermuteWithoutRemasking,
+        );
+    }
+
+    #[test]
+    fn test_dropped_card_is_detected() {
+        let (deck, rules) = table();
+        assert_detected::<_, Curve, _>(&mut test_rng(), 3, &deck, &rules, 2, DroppedCard);
+    }
+
+    #[test]
+    fn test_equivocated_deck_is_detected() {
+        let (deck, rules) = table();
+        assert_detected::<_, Curve, _>(&mut test_rng(), 3, &deck, &rules, 1, EquivocatedDeck);
+    }
+
+    #[test]
+    fn test_token_for_wrong_card_is_detected() {
+        let (deck, rules) = table();
+        assert_detected::<_, Curve, _>(&mut test_rng(), 3, &deck, &rules, 2, TokenForWrongCard);
+    }
+
+    #[test]
+    fn test_forged_showdown_token_is_detected() {
+        let (deck, rules) = table();
+        assert_detected::<_, Curve, _>(&mut test_rng(), 3, &deck, &rules, 0, ForgedShowdownToken);
+    }
+}
@@ -0,0 +1,181 @@
+//! Evidence against a player who sent an invalid reveal token.
+//!
+//! `unmask` only reports that some proof failed. [`unmask_or_blame`] instead returns a
+//! [`BlameEvidence`] naming the offending token: the sender's public key, the masked card, the token
+//! and its proof, together with the context the proof had to be bound to. Anyone who holds the
+//! parameters of the game can check it with [`BlameEvidence::verify`], so a player can forward it to
+//! the others or to an arbiter instead of asking to be trusted.
+//!
+//! A valid proof does not verify under another context, so whoever checks the evidence must also
+//! check that its context is the identifier of the game and its key that of the accused player.
+
+use crate::crypto_primitives::utils::format::{Compact, Hex, HexBytes};
+use crate::discrete_log_cards::reveal::RevealProof;
+use crate::discrete_log_cards::{Card, DLCards, MaskedCard, Parameters, PublicKey, RevealToken};
+use crate::error::{CardProtocolError, VerificationError};
+use crate::{BarnettSmartProtocol, Reveal};
+
+use ark_ec::ProjectiveCurve;
+use ark_ff::Zero;
+use ark_serialize::{CanonicalDeserialize, CanonicalSerialize};
+use ark_std::fmt;
+
+/// A reveal token whose proof does not verify, and everything needed to check that it does not.
+#[derive(Clone, CanonicalSerialize, CanonicalDeserialize)]
+pub struct BlameEvidence<C: ProjectiveCurve> {
+    /// Context the proof had to be bound to, typically the session identifier
+    pub context: Vec<u8>,
+    /// Public key of the player who sent the token
+    pub pk: PublicKey<C>,
+    pub masked_card: MaskedCard<C>,
+    pub token: RevealToken<C>,
+    pub proof: RevealProof<C>,
+}
+
+impl<C: ProjectiveCurve> fmt::Debug for BlameEvidence<C> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("BlameEvidence")
+            .field("context", &HexBytes(&self.context))
+            .field("pk", &Hex(&self.pk))
+            .field("masked_card", &Compact(&self.masked_card))
+            .field("token", &Compact(&self.token))
+            .field("proof", &Compact(&self.proof))
+            .finish()
+    }
+}
+
+impl<C: ProjectiveCurve> BlameEvidence<C> {
+    /// Check that the evidence shows misbehavior, that is that the proof does not verify.
+    pub fn verify(&self, pp: &Parameters<C>) -> Result<(), CardProtocolError> {
+        match DLCards::<C>::verify_reveal(
+            pp,
+            &self.context,
+            &self.pk,
+            &self.token,
+            &self.masked_card,
+            &self.proof,
+        ) {
+            Ok(()) => Err(CardProtocolError::VerificationFailed(
+                VerificationError::UnfoundedBlame,
+            )),
+            Err(_) => Ok(()),
+        }
+    }
+}
+
+/// Failure to unmask a card.
+#[derive(Debug)]
+pub enum UnmaskError<C: ProjectiveCurve> {
+    /// A player sent an invalid reveal token
+    Blame(BlameEvidence<C>),
+    Protocol(CardProtocolError),
+}
+
+impl<C: ProjectiveCurve> From<CardProtocolError> for UnmaskError<C> {
+    fn from(err: CardProtocolError) -> Self {
+        Self::Protocol(err)
+    }
+}
+
+/// Like `unmask`, but returns evidence against the sender of the first token whose proof fails.
+pub fn unmask_or_blame<C: ProjectiveCurve>(
+    pp: &Parameters<C>,
+    context: &[u8],
+    decryption_key: &[(RevealToken<C>, RevealProof<C>, PublicKey<C>)],
+    masked_card: &MaskedCard<C>,
+) -> Result<Card<C>, UnmaskError<C>> {
+    let mut aggregate_token = RevealToken::<C>::zero();
+
+    for (token, proof, pk) in decryption_key {
+        if DLCards::<C>::verify_reveal(pp, context, pk, token, masked_card, proof).is_err() {
+            return Err(UnmaskError::Blame(BlameEvidence {
+                context: context.to_vec(),
+                pk: *pk,
+                masked_card: *masked_card,
+                token: *token,
+                proof: proof.clone(),
+            }));
+        }
+
+        aggregate_token = aggregate_token + *token;
+    }
+
+    Ok(aggregate_token.reveal(masked_card)?)
+}
+
+#[cfg(test)]
+mod test {
+    use super::{unmask_or_blame, BlameEvidence, UnmaskError};
+    use crate::crypto_primitives::utils::rand::test_rng;
+    use crate::discrete_log_cards;
+    use crate::discrete_log_cards::test_helpers::{reveal_tokens, setup_players};
+    use crate::error::{CardProtocolError, VerificationError};
+    use crate::BarnettSmartProtocol;
+
+    use ark_ff::UniformRand;
+    use ark_serialize::{CanonicalDeserialize, CanonicalSerialize};
+
+    // Choose elliptic curve setting
+    type Curve = starknet_curve::Projective;
+
+    // Instantiate concrete type for our card protocol
+    type CardProtocol<'a> = discrete_log_cards::DLCards<'a, Curve>;
+    type Card = discrete_log_cards::Card<Curve>;
+    type RevealToken = discrete_log_cards::RevealToken<Curve>;
+
+    const SESSION_ID: &[u8] = b"blame test";
+
+    #[test]
+    fn test_blame_evidence() {
+        let rng = &mut test_rng();
+        let m = 4;
+        let n = 13;
+        let num_of_players = 3;
+
+        let parameters = CardProtocol::setup(rng, m, n).unwrap();
+        let (players, aggregate_key) = setup_players(rng, &parameters, num_of_players).unwrap();
+
+        let card = Card::rand(rng);
+        let (masked, _) =
+            CardProtocol::mask_with_rng(rng, &parameters, SESSION_ID, &aggregate_key, &card)
+                .unwrap();
+
+        let mut decryption_key =
+            reveal_tokens(rng, &parameters, SESSION_ID, &players, &masked).unwrap();
+
+        match unmask_or_blame(&parameters, SESSION_ID, &decryption_key, &masked) {
+            Ok(unmasked) => assert_eq!(unmasked, card),
+            Err(_) => panic!("honest tokens were blamed"),
+        }
+
+        // Player 1 sends a wrong token with their valid proof
+        decryption_key[1].0 = RevealToken::rand(rng);
+        let evidence = match unmask_or_blame(&parameters, SESSION_ID, &decryption_key, &masked) {
+            Err(UnmaskError::Blame(evidence)) => evidence,
+            _ => panic!("the wrong token was not blamed"),
+        };
+        assert_eq!(evidence.pk, players[1].0);
+
+        // The evidence travels as bytes and verifies at another node
+        let mut bytes = Vec::new();
+        evidence.serialize(&mut bytes).unwrap();
+        let received = BlameEvidence::<Curve>::deserialize(&bytes[..]).unwrap();
+        assert_eq!(Ok(()), received.verify(&parameters));
+
+        // Blaming an honest player does not verify
+        let (token, proof, pk) = &decryption_key[0];
+        let unfounded = BlameEvidence {
+            context: SESSION_ID.to_vec(),
+            pk: *pk,
+            masked_card: masked,
+            token: *token,
+            proof: proof.clone(),
+        };
+        assert_eq!(
+            unfounded.verify(&parameters),
+            Err(CardProtocolError::VerificationFailed(
+                VerificationError::UnfoundedBlame
+            ))
+        );
+    }
+}
@@ -0,0 +1,630 @@
+//! The protocol test suite, written once over a generic curve and stamped out for every supported
+//! curve by `instantiate_protocol_tests!`. The starknet curve and BLS12-377 are always tested; the
+//! other curves have their own feature, which pulls in their arkworks implementation:
+//!
+//! - `bls12-381`: the G1 group of BLS12-381. Points take 48 bytes compressed against 32 on the
+//!   starknet curve while scalars take 32 bytes on both, so masked cards grow from 64 to 96 bytes
+//!   and proofs grow by half of their point component. Group operations work over a 381-bit base
+//!   field instead of a 252-bit one and are correspondingly slower.
+//! - `bn254`: the G1 group of BN254, for verification on the EVM. Points and scalars take 32 bytes
+//!   as on the starknet curve.
+//! - `curve25519`: ristretto255, the prime-order group of `crypto_primitives::ristretto255`,
+//!   built on curve25519. Points and scalars take 32 bytes as on the starknet curve, and points are
+//!   encoded as in RFC 9496, so they interoperate with other Ristretto implementations.
+//! - `jubjub`: the twisted Edwards curve defined over the scalar field of BLS12-381. Compressed
+//!   points hold `y` and the sign of `x` in 32 bytes, so masked cards take 64 bytes. The group has
+//!   cofactor 8: checked decoding rejects points outside the prime-order
+//!   subgroup and `hash_to_curve` clears the cofactor, and the protocol makes no other assumption
+//!   on the curve model.
+//! - `pasta`: Pallas and Vesta, whose scalar fields are each other's base fields, for recursive
+//!   verification. Their 255-bit fields leave one spare bit where compressed points need two flags,
+//!   so points take 33 bytes and masked cards 66. The Fiat-Shamir transcripts are seeded from
+//!   bytes and do not depend on the scalar field, so a transcript over the Pallas scalar field
+//!   only needs a hash instantiated for it.
+
+#[cfg(test)]
+mod test {
+    use crate::crypto_primitives::utils::rand::test_rng;
+    use crate::discrete_log_cards;
+    use crate::discrete_log_cards::encoding::encode_standard_deck;
+    use crate::discrete_log_cards::test_helpers::{prove_keys, reveal_tokens, setup_players};
+    use crate::discrete_log_cards::validation::{
+        from_bytes, public_key_from_bytes, to_bytes, Validate,
+    };
+    use crate::error::{
+        CardProtocolError, InputError, Operation, OperationError, ShuffleVerificationFailure,
+        VerificationError,
+    };
+    use crate::BarnettSmartProtocol;
+
+    use ark_ec::ProjectiveCurve;
+    use ark_ff::UniformRand;
+    use ark_serialize::{CanonicalDeserialize, CanonicalSerialize};
+    use ark_std::Zero;
+    use proof_essentials::error::CryptoError;
+    use proof_essentials::utils::permutation::Permutation;
+    use proof_essentials::utils::rand::sample_vector;
+
+    /// Stamp out the protocol tests for a curve, in a module named `$name`. `$masked_card_size` is
+    /// the size in bytes of a compressed masked card on that curve.
+    macro_rules! instantiate_protocol_tests {
+        ($name:ident, $curve:ty, $masked_card_size:expr) => {
+            mod $name {
+                #[test]
+                fn test_generate_and_verify_key() {
+                    super::generate_and_verify_key::<$curve>();
+                }
+
+                #[test]
+                fn test_aggregate_keys() {
+                    super::aggregate_keys::<$curve>(10);
+                }
+
+                #[test]
+                fn test_unmask() {
+                    super::unmask::<$curve>(10);
+                }
+
+                #[test]
+                fn test_shuffle() {
+                    super::shuffle::<$curve>(10);
+                }
+
+                #[test]
+                fn test_serialization_round_trip() {
+                    super::serialization_round_trip::<$curve>();
+                }
+
+                #[test]
+                fn test_full_round() {
+                    super::full_round::<$curve>(4);
+                }
+
+                #[test]
+                fn test_masked_card_size() {
+                    assert_eq!(super::masked_card_size::<$curve>(), $masked_card_size);
+                }
+            }
+        };
+    }
+
+    instantiate_protocol_tests!(starknet, starknet_curve::Projective, 64);
+    instantiate_protocol_tests!(bls12_377, ark_bls12_377::G1Projective, 96);
+
+    #[cfg(feature = "bls12-381")]
+    instantiate_protocol_tests!(bls12_381, ark_bls12_381::G1Projective, 96);
+
+    #[cfg(feature = "bn254")]
+    instantiate_protocol_tests!(bn254, ark_bn254::G1Projective, 64);
+
+    #[cfg(feature = "curve25519")]
+    instantiate_protocol_tests!(
+        curve25519,
+        crate::crypto_primitives::ristretto255::Projective,
+        64
+    );
+
+    #[cfg(feature = "jubjub")]
+    instantiate_protocol_tests!(jubjub, ark_ed_on_bls12_381::EdwardsProjective, 64);
+
+    #[cfg(feature = "pasta")]
+    instantiate_protocol_tests!(pallas, ark_pallas::Projective, 66);
+
+    #[cfg(feature = "pasta")]
+    instantiate_protocol_tests!(vesta, ark_vesta::Projective, 66);
+
+    fn generate_and_verify_key<C: ProjectiveCurve>() {
+        let rng = &mut test_rng();
+        let m = 4;
+        let n = 13;
+
+        let parameters = discrete_log_cards::DLCards::<C>::setup(rng, m, n).unwrap();
+
+        let (pk, sk) = discrete_log_cards::DLCards::<C>::player_keygen(rng, &parameters).unwrap();
+        let player_name = b"Alice";
+        let key_list_digest = discrete_log_cards::DLCards::<C>::key_list_digest(&[pk]).unwrap();
+
+        let p1_keyproof = discrete_log_cards::DLCards::<C>::prove_key_ownership(
+            rng,
+            &parameters,
+            b"",
+            &pk,
+            &sk,
+            &player_name,
+            0,
+            &key_list_digest,
+        )
+        .unwrap();
+
+        assert_eq!(
+            Ok(()),
+            discrete_log_cards::DLCards::<C>::verify_key_ownership(
+                &parameters,
+                b"",
+                &pk,
+                &player_name,
+                0,
+                &key_list_digest,
+                &p1_keyproof
+            )
+        );
+
+        let other_key = discrete_log_cards::PlayerSecretKey::<C>::from(C::ScalarField::rand(rng));
+        let wrong_proof = discrete_log_cards::DLCards::<C>::prove_key_ownership(
+            rng,
+            &parameters,
+            b"",
+            &pk,
+            &other_key,
+            &player_name,
+            0,
+            &key_list_digest,
+        )
+        .unwrap();
+
+        assert_eq!(
+            discrete_log_cards::DLCards::<C>::verify_key_ownership(
+                &parameters,
+                b"",
+                &pk,
+                &player_name,
+                0,
+                &key_list_digest,
+                &wrong_proof
+            ),
+            Err(CryptoError::ProofVerificationError(String::from(
+                "Schnorr Identification"
+            )))
+        )
+    }
+
+    fn aggregate_keys<C: ProjectiveCurve>(num_of_players: usize) {
+        let rng = &mut test_rng();
+        let m = 4;
+        let n = 13;
+
+        let parameters = discrete_log_cards::DLCards::<C>::setup(rng, m, n).unwrap();
+
+        let (players, expected_shared_key) =
+            setup_players(rng, &parameters, num_of_players).unwrap();
+        let key_proof_info = prove_keys(rng, &parameters, b"", &players).unwrap();
+
+        let test_aggregate = discrete_log_cards::DLCards::<C>::compute_aggregate_key(
+            &parameters,
+            b"",
+            &key_proof_info,
+        )
+        .unwrap();
+
+        assert_eq!(test_aggregate, expected_shared_key);
+
+        // The identity is rejected before any proof is checked
+        let mut bad_key_proof_pairs = key_proof_info.clone();
+        bad_key_proof_pairs[3].0 = discrete_log_cards::PublicKey::<C>::zero();
+
+        assert_eq!(
+            discrete_log_cards::DLCards::<C>::compute_aggregate_key(
+                &parameters,
+                b"",
+                &bad_key_proof_pairs
+            ),
+            Err(CardProtocolError::InvalidInput(
+                InputError::IdentityPublicKey(3)
+            ))
+        );
+
+        // Proofs are bound to the complete, ordered key list
+        let mut reordered = key_proof_info.clone();
+        reordered.swap(0, 1);
+
+        let error =
+            discrete_log_cards::DLCards::<C>::compute_aggregate_key(&parameters, b"", &reordered)
+                .unwrap_err();
+        assert!(matches!(
+            error,
+            CardProtocolError::VerificationFailed(VerificationError::InvalidKeyOwnershipProof {
+                index: 0,
+                ref public_key,
+                source: OperationError {
+                    operation: Operation::DuringKeyAggregation,
+                    ..
+                },
+            }) if *public_key == to_bytes(&reordered[0].0).unwrap()
+        ));
+
+        let keys = players.iter().map(|player| player.0).collect::<Vec<_>>();
+        let key_list_digest = discrete_log_cards::DLCards::<C>::key_list_digest(&keys).unwrap();
+        let other_digest = discrete_log_cards::DLCards::<C>::key_list_digest(&keys[1..]).unwrap();
+        let player = &players[1];
+        let proof_for_other_list = discrete_log_cards::DLCards::<C>::prove_key_ownership(
+            rng,
+            &parameters,
+            b"",
+            &player.0,
+            &player.1,
+            &player.2,
+            1,
+            &other_digest,
+        )
+        .unwrap();
+
+        assert_eq!(
+            discrete_log_cards::DLCards::<C>::verify_key_ownership(
+                &parameters,
+                b"",
+                &player.0,
+                &player.2,
+                1,
+                &key_list_digest,
+                &proof_for_other_list
+            ),
+            Err(CryptoError::ProofVerificationError(String::from(
+                "Schnorr Identification"
+            )))
+        );
+
+        let mut replayed = key_proof_info;
+        replayed[1].1 = proof_for_other_list;
+        assert!(discrete_log_cards::DLCards::<C>::compute_aggregate_key(
+            &parameters,
+            b"",
+            &replayed
+        )
+        .is_err());
+    }
+
+    fn unmask<C: ProjectiveCurve>(num_of_players: usize) {
+        let rng = &mut test_rng();
+        let m = 4;
+        let n = 13;
+
+        let parameters = discrete_log_cards::DLCards::<C>::setup(rng, m, n).unwrap();
+
+        let (players, expected_shared_key) =
+            setup_players(rng, &parameters, num_of_players).unwrap();
+
+        let card = discrete_log_cards::Card::<C>::rand(rng);
+        let alpha = C::ScalarField::rand(rng);
+        let (masked, _) = discrete_log_cards::DLCards::<C>::mask(
+            rng,
+            &parameters,
+            b"",
+            &expected_shared_key,
+            &card,
+            &alpha,
+        )
+        .unwrap();
+
+        let decryption_key = reveal_tokens(rng, &parameters, b"", &players, &masked).unwrap();
+
+        let unmasked = discrete_log_cards::DLCards::<C>::unmask(
+            &parameters,
+            b"",
+            num_of_players,
+            &decryption_key,
+            &masked,
+        )
+        .unwrap();
+
+        assert_eq!(card, unmasked);
+
+        let mut bad_decryption_key = decryption_key;
+        bad_decryption_key[0].0 = discrete_log_cards::RevealToken::<C>::rand(rng);
+
+        let failed_decryption = discrete_log_cards::DLCards::<C>::unmask(
+            &parameters,
+            b"",
+            num_of_players,
+            &bad_decryption_key,
+            &masked,
+        );
+
+        assert!(matches!(
+            failed_decryption,
+            Err(CardProtocolError::VerificationFailed(VerificationError::InvalidRevealToken {
+                index: 0,
+                ref public_key,
+                source: OperationError {
+                    operation: Operation::DuringUnmask { card_index: None },
+                    ..
+                },
+            })) if *public_key == to_bytes(&bad_decryption_key[0].2).unwrap()
+        ))
+    }
+
+    fn shuffle<C: ProjectiveCurve>(num_of_players: usize) {
+        let rng = &mut test_rng();
+        let m = 4;
+        let n = 13;
+
+        let parameters = discrete_log_cards::DLCards::<C>::setup(rng, m, n).unwrap();
+
+        let (_, aggregate_key) = setup_players(rng, &parameters, num_of_players).unwrap();
+
+        let deck: Vec<discrete_log_cards::MaskedCard<C>> = sample_vector(rng, m * n);
+
+        let permutation = Permutation::new(rng, m * n);
+        let masking_factors: Vec<C::ScalarField> = sample_vector(rng, m * n);
+
+        let (shuffled_deck, shuffle_proof) = discrete_log_cards::DLCards::<C>::shuffle_and_remask(
+            rng,
+            &parameters,
+            b"",
+            &aggregate_key,
+            &deck,
+            &masking_factors,
+            &permutation,
+        )
+        .unwrap();
+
+        assert_eq!(
+            Ok(()),
+            discrete_log_cards::DLCards::<C>::verify_shuffle(
+                &parameters,
+                b"",
+                &aggregate_key,
+                &deck,
+                &shuffled_deck,
+                &shuffle_proof
+            )
+        );
+
+        let wrong_output: Vec<discrete_log_cards::MaskedCard<C>> = sample_vector(rng, m * n);
+
+        assert_eq!(
+            discrete_log_cards::DLCards::<C>::verify_shuffle(
+                &parameters,
+                b"",
+                &aggregate_key,
+                &deck,
+                &wrong_output,
+                &shuffle_proof
+            ),
+            Err(ShuffleVerificationFailure::HadamardProduct)
+        )
+    }
+
+    /// Every message a player sends survives encoding and checked decoding, and decoded proofs
+    /// still verify.
+    fn serialization_round_trip<C: ProjectiveCurve>() {
+        let rng = &mut test_rng();
+        let m = 4;
+        let n = 13;
+
+        let parameters = discrete_log_cards::DLCards::<C>::setup(rng, m, n).unwrap();
+        let (players, aggregate_key) = setup_players(rng, &parameters, 2).unwrap();
+        let player = &players[0];
+
+        let key_proof_info = prove_keys(rng, &parameters, b"", &players).unwrap();
+        let keys = players.iter().map(|player| player.0).collect::<Vec<_>>();
+        let key_list_digest = discrete_log_cards::DLCards::<C>::key_list_digest(&keys).unwrap();
+        let key_proof = round_trip(&key_proof_info[0].1);
+        assert_eq!(
+            Ok(player.0),
+            public_key_from_bytes(&to_bytes(&player.0).unwrap())
+        );
+        assert_eq!(
+            Ok(()),
+            discrete_log_cards::DLCards::<C>::verify_key_ownership(
+                &parameters,
+                b"",
+                &player.0,
+                &player.2,
+                0,
+                &key_list_digest,
+                &key_proof
+            )
+        );
+
+        let card = discrete_log_cards::Card::<C>::rand(rng);
+        let alpha = C::ScalarField::rand(rng);
+        let (masked, mask_proof) = discrete_log_cards::DLCards::<C>::mask(
+            rng,
+            &parameters,
+            b"",
+            &aggregate_key,
+            &card,
+            &alpha,
+        )
+        .unwrap();
+        let decoded: discrete_log_cards::MaskedCard<C> =
+            from_bytes(&to_bytes(&masked).unwrap()).unwrap();
+        assert_eq!(decoded, masked);
+        assert_eq!(
+            Ok(()),
+            discrete_log_cards::DLCards::<C>::verify_mask(
+                &parameters,
+                b"",
+                &aggregate_key,
+                &card,
+                &decoded,
+                &round_trip(&mask_proof)
+            )
+        );
+
+        let (token, reveal_proof) = discrete_log_cards::DLCards::<C>::compute_reveal_token(
+            rng,
+            &parameters,
+            b"",
+            &player.1,
+            &player.0,
+            &masked,
+        )
+        .unwrap();
+        let decoded: discrete_log_cards::RevealToken<C> =
+            from_bytes(&to_bytes(&token).unwrap()).unwrap();
+        assert_eq!(decoded, token);
+        assert_eq!(
+            Ok(()),
+            discrete_log_cards::DLCards::<C>::verify_reveal(
+                &parameters,
+                b"",
+                &player.0,
+                &decoded,
+                &masked,
+                &round_trip(&reveal_proof)
+            )
+        );
+
+        let deck: Vec<discrete_log_cards::MaskedCard<C>> = sample_vector(rng, m * n);
+        let (shuffled_deck, shuffle_proof) =
+            discrete_log_cards::DLCards::<C>::shuffle_and_remask_with_rng(
+                rng,
+                &parameters,
+                b"",
+                &aggregate_key,
+                &deck,
+            )
+            .unwrap();
+        let decoded: Vec<discrete_log_cards::MaskedCard<C>> =
+            from_bytes(&to_bytes(&shuffled_deck).unwrap()).unwrap();
+        assert_eq!(decoded, shuffled_deck);
+        assert_eq!(
+            Ok(()),
+            discrete_log_cards::DLCards::<C>::verify_shuffle(
+                &parameters,
+                b"",
+                &aggregate_key,
+                &deck,
+                &decoded,
+                &round_trip(&shuffle_proof)
+            )
+        );
+    }
+
+    fn round_trip<T: CanonicalSerialize + CanonicalDeserialize>(value: &T) -> T {
+        T::deserialize(&to_bytes(value).unwrap()[..]).unwrap()
+    }
+
+    /// Key generation, aggregation, masking, shuffling and unmasking of a full deck of canonical
+    /// card encodings by `num_of_players` players.
+    fn full_round<C: ProjectiveCurve>(num_of_players: usize) {
+        let rng = &mut test_rng();
+        let m = 4;
+        let n = 13;
+
+        let parameters = discrete_log_cards::DLCards::<C>::setup(rng, m, n).unwrap();
+        assert_eq!(
+            Ok(()),
+            discrete_log_cards::DLCards::<C>::verify_parameters(&parameters)
+        );
+
+        let (players, expected_key) = setup_players(rng, &parameters, num_of_players).unwrap();
+        let key_proof_info = prove_keys(rng, &parameters, b"", &players).unwrap();
+
+        let aggregate_key = discrete_log_cards::DLCards::<C>::compute_aggregate_key(
+            &parameters,
+            b"",
+            &key_proof_info,
+        )
+        .unwrap();
+        assert_eq!(aggregate_key, expected_key);
+
+        // Canonical encodings are valid points of the prime-order subgroup
+        let deck = encode_standard_deck::<C>(1).unwrap();
+        assert_eq!(Ok(()), deck.validate());
+
+        let masked_deck = deck
+            .iter()
+            .map(|card| {
+                let alpha = C::ScalarField::rand(rng);
+                let (masked, proof) = discrete_log_cards::DLCards::<C>::mask(
+                    rng,
+                    &parameters,
+                    b"",
+                    &aggregate_key,
+                    card,
+                    &alpha,
+                )
+                .unwrap();
+                assert_eq!(
+                    Ok(()),
+                    discrete_log_cards::DLCards::<C>::verify_mask(
+                        &parameters,
+                        b"",
+                        &aggregate_key,
+                        card,
+                        &masked,
+                        &proof
+                    )
+                );
+                masked
+            })
+            .collect::<Vec<_>>();
+
+        let permutation = Permutation::new(rng, m * n);
+        let masking_factors: Vec<C::ScalarField> = sample_vector(rng, m * n);
+        let (shuffled_deck, shuffle_proof) = discrete_log_cards::DLCards::<C>::shuffle_and_remask(
+            rng,
+            &parameters,
+            b"",
+            &aggregate_key,
+            &masked_deck,
+            &masking_factors,
+            &permutation,
+        )
+        .unwrap();
+        assert_eq!(
+            Ok(()),
+            discrete_log_cards::DLCards::<C>::verify_shuffle(
+                &parameters,
+                b"",
+                &aggregate_key,
+                &masked_deck,
+                &shuffled_deck,
+                &shuffle_proof
+            )
+        );
+
+        let wrong_output: Vec<discrete_log_cards::MaskedCard<C>> = sample_vector(rng, m * n);
+        assert_eq!(
+            discrete_log_cards::DLCards::<C>::verify_shuffle(
+                &parameters,
+                b"",
+                &aggregate_key,
+                &masked_deck,
+                &wrong_output,
+                &shuffle_proof
+            ),
+            Err(ShuffleVerificationFailure::HadamardProduct)
+        );
+
+        // Every player contributes a reveal token to each card, which opens to the permuted deck
+        let permuted_deck = permutation.permute_array(&deck);
+        for (masked, expected) in shuffled_deck.iter().zip(permuted_deck.iter()) {
+            let decryption_key = reveal_tokens(rng, &parameters, b"", &players, masked).unwrap();
+
+            let card = discrete_log_cards::DLCards::<C>::unmask(
+                &parameters,
+                b"",
+                num_of_players,
+                &decryption_key,
+                masked,
+            )
+            .unwrap();
+            assert_eq!(card, *expected);
+
+            let mut bad_tokens = decryption_key;
+            bad_tokens[1].0 = discrete_log_cards::RevealToken::<C>::rand(rng);
+            assert!(matches!(
+                discrete_log_cards::DLCards::<C>::unmask(
+                    &parameters,
+                    b"",
+                    num_of_players,
+                    &bad_tokens,
+                    masked
+                ),
+                Err(CardProtocolError::VerificationFailed(VerificationError::InvalidRevealToken {
+                    index: 1,
+                    ref public_key,
+                    ..
+                })) if *public_key == to_bytes(&bad_tokens[1].2).unwrap()
+            ));
+        }
+    }
+
+    /// Size in bytes of a masked card, as sent to other players.
+    fn masked_card_size<C: ProjectiveCurve>() -> usize {
+        discrete_log_cards::MaskedCard::<C>::rand(&mut test_rng()).serialized_size()
+    }
+}
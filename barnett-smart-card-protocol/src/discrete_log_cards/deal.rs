@@ -0,0 +1,203 @@
+//! Assignment of the positions of the shuffled deck to their destinations.
+//!
+//! Once the last shuffle is verified every player holds the same deck, and a [`DealPlan`] lets them
+//! agree on what each position is for without exchanging further messages. The plan deals like a
+//! live dealer: hole cards one at a time around the table, so card `k` of seat `p` is at position
+//! `k * num_players + p`, then for every street the burned cards followed by the community cards
+//! of that street.
+
+use crate::error::{CardProtocolError, InputError};
+
+use ark_serialize::{CanonicalDeserialize, CanonicalSerialize};
+use ark_std::vec::Vec;
+
+#[derive(Clone, Debug, PartialEq, Eq, CanonicalSerialize, CanonicalDeserialize)]
+pub struct DealPlan {
+    num_players: usize,
+    /// Positions of the hole cards, by seat
+    hole_cards: Vec<Vec<usize>>,
+    /// Positions of the community cards, by street
+    community: Vec<Vec<usize>>,
+    /// Positions of the cards burned before each street
+    burned: Vec<Vec<usize>>,
+}
+
+impl DealPlan {
+    /// Plan a deal of `hole_cards_per_player` cards to each of `num_players` seats, followed by
+    /// streets of `community_cards[i]` cards, each preceded by `burns` burned cards.
+    pub fn new(
+        num_players: usize,
+        hole_cards_per_player: usize,
+        community_cards: &[usize],
+        burns: usize,
+    ) -> Result<Self, CardProtocolError> {
+        if num_players == 0 {
+            return Err(InputError::InvalidPlayerCount(num_players).into());
+        }
+
+        let hole_cards = (0..num_players)
+            .map(|seat| {
+                (0..hole_cards_per_player)
+                    .map(|k| k * num_players + seat)
+                    .collect()
+            })
+            .collect();
+
+        let mut next = hole_cards_per_player * num_players;
+        let mut burned = Vec::with_capacity(community_cards.len());
+        let mut community = Vec::with_capacity(community_cards.len());
+        for &street_size in community_cards {
+            burned.push((next..next + burns).collect());
+            next += burns;
+            community.push((next..next + street_size).collect());
+            next += street_size;
+        }
+
+        Ok(Self {
+            num_players,
+            hole_cards,
+            community,
+            burned,
+        })
+    }
+
+    /// The Texas Hold'em deal: two hole cards per seat, then flop, turn and river, each preceded by
+    /// a burned card.
+    pub fn texas_holdem(num_players: usize) -> Result<Self, CardProtocolError> {
+        Self::new(num_players, 2, &[3, 1, 1], 1)
+    }
+
+    pub fn num_players(&self) -> usize {
+        self.num_players
+    }
+
+    /// Number of positions the plan uses, from the top of the deck.
+    pub fn num_dealt(&self) -> usize {
+        self.hole_cards.iter().map(Vec::len).sum::<usize>()
+            + self.community.iter().map(Vec::len).sum::<usize>()
+            + self.burned.iter().map(Vec::len).sum::<usize>()
+    }
+
+    /// Check that the plan fits a deck of `num_cards` cards.
+    pub fn validate(&self, num_cards: usize) -> Result<(), CardProtocolError> {
+        if self.num_dealt() > num_cards {
+            return Err(InputError::InvalidDeal(num_cards).into());
+        }
+
+        Ok(())
+    }
+
+    /// Positions of the hole cards of `seat`, empty if there is no such seat.
+    pub fn hole_cards(&self, seat: usize) -> &[usize] {
+        self.hole_cards.get(seat).map_or(&[], Vec::as_slice)
+    }
+
+    /// Positions of the community cards of `street`, counted from zero for the first street dealt
+    /// after the hole cards. Empty if there is no such street.
+    pub fn community(&self, street: usize) -> &[usize] {
+        self.community.get(street).map_or(&[], Vec::as_slice)
+    }
+
+    pub fn num_streets(&self) -> usize {
+        self.community.len()
+    }
+
+    /// Positions of the cards burned before `street`. Empty if there is no such street.
+    pub fn burns(&self, street: usize) -> &[usize] {
+        self.burned.get(street).map_or(&[], Vec::as_slice)
+    }
+
+    /// Positions of all burned cards, in dealing order.
+    pub fn burned(&self) -> Vec<usize> {
+        self.burned.iter().flatten().copied().collect()
+    }
+
+    /// Whether the card at `position` is burned. Burned cards are never revealed during the game;
+    /// they may only be opened for audit once it is over.
+    pub fn is_burned(&self, position: usize) -> bool {
+        self.burned
+            .iter()
+            .flatten()
+            .any(|&burned| burned == position)
+    }
+
+    /// Positions of the hole cards of every seat, in the form `session::Session::new` takes.
+    pub fn hands(&self) -> Vec<Vec<usize>> {
+        self.hole_cards.clone()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::DealPlan;
+    use crate::error::{CardProtocolError, InputError};
+
+    use std::collections::HashSet;
+
+    #[test]
+    fn test_texas_holdem_plan() {
+        let plan = DealPlan::texas_holdem(4).unwrap();
+
+        assert_eq!(plan.hole_cards(0), &[0, 4]);
+        assert_eq!(plan.hole_cards(3), &[3, 7]);
+        assert_eq!(plan.burned(), vec![8, 12, 14]);
+        assert_eq!(plan.burns(1), &[12]);
+        assert!(plan.is_burned(14));
+        assert!(!plan.is_burned(15));
+        assert_eq!(plan.community(0), &[9, 10, 11]);
+        assert_eq!(plan.community(1), &[13]);
+        assert_eq!(plan.community(2), &[15]);
+        assert_eq!(plan.num_dealt(), 16);
+
+        assert!(plan.hole_cards(4).is_empty());
+        assert!(plan.community(3).is_empty());
+    }
+
+    #[test]
+    fn test_positions_are_distinct() {
+        let plan = DealPlan::new(5, 3, &[2, 2, 1], 2).unwrap();
+
+        let mut positions = (0..plan.num_players())
+            .flat_map(|seat| plan.hole_cards(seat).to_vec())
+            .collect::<Vec<_>>();
+        for street in 0..plan.num_streets() {
+            positions.extend_from_slice(plan.community(street));
+        }
+        positions.extend(plan.burned());
+
+        assert_eq!(positions.len(), plan.num_dealt());
+        assert_eq!(
+            positions.iter().collect::<HashSet<_>>().len(),
+            positions.len()
+        );
+        assert!(positions
+            .iter()
+            .all(|&position| position < plan.num_dealt()));
+    }
+
+    #[test]
+    fn test_independent_plans_agree() {
+        for num_players in 2..=10 {
+            let plan = DealPlan::texas_holdem(num_players).unwrap();
+            let other = DealPlan::new(num_players, 2, &[3, 1, 1], 1).unwrap();
+            assert_eq!(plan, other);
+        }
+    }
+
+    #[test]
+    fn test_validation() {
+        // Hold'em seats at most 22 players with burned cards: 2 * 22 + 3 + 5 = 52
+        assert_eq!(Ok(()), DealPlan::texas_holdem(22).unwrap().validate(52));
+        assert_eq!(
+            DealPlan::texas_holdem(23).unwrap().validate(52),
+            Err(CardProtocolError::InvalidInput(InputError::InvalidDeal(52)))
+        );
+
+        assert_eq!(
+            DealPlan::new(0, 2, &[3, 1, 1], 1),
+            Err(CardProtocolError::InvalidInput(
+                InputError::InvalidPlayerCount(0)
+            ))
+        );
+    }
+}
@@ -0,0 +1,568 @@
+//! Commit-then-reveal setup of the aggregate key.
+//!
+//! Players first publish a hash commitment to their public key. Once the commitment phase is
+//! closed, keys are revealed and checked against the commitments, so nobody can choose their key
+//! after seeing the others'. When the reveal phase is closed the list of revealed keys is fixed and
+//! every player proves ownership of their key, bound to their position in that list and to its
+//! digest. Every missing or invalid message yields a [`Complaint`] naming the culprit, and the
+//! aggregate key is computed over the qualified players only. The result is a `PublicKey` like the
+//! one produced by `compute_aggregate_key`, so the rest of the protocol is unchanged.
+
+use crate::crypto_primitives::utils::format::{Compact, Hex, HexBytes};
+use crate::discrete_log_cards::{DLCards, Parameters, PublicKey};
+use crate::error::{CardProtocolError, InputError};
+use crate::{BarnettSmartProtocol, KeyListDigest};
+
+use ark_ec::ProjectiveCurve;
+use ark_ff::{ToBytes, Zero};
+use ark_serialize::CanonicalSerialize;
+use ark_std::fmt;
+use blake2::{Blake2s, Digest};
+use proof_essentials::zkp::proofs::schnorr_identification;
+
+const KEY_COMMITMENT_SEED: &'static [u8] = b"Key Commitment";
+
+pub type KeyOwnershipProof<C> = schnorr_identification::proof::Proof<C>;
+
+/// Hash commitment to a public key.
+pub type KeyCommitment = [u8; 32];
+
+/// Commit to the key that `player` will reveal.
+pub fn commit_to_key<C: ProjectiveCurve>(
+    player: usize,
+    pk: &PublicKey<C>,
+) -> Result<KeyCommitment, CardProtocolError> {
+    let mut bytes = Vec::new();
+    (player as u64).serialize(&mut bytes)?;
+    pk.serialize(&mut bytes)?;
+
+    let mut hasher = Blake2s::new();
+    hasher.update(KEY_COMMITMENT_SEED);
+    hasher.update(&bytes);
+
+    let mut commitment = [0u8; 32];
+    commitment.copy_from_slice(&hasher.finalize());
+
+    Ok(commitment)
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum KeySetupPhase {
+    Commit,
+    Reveal,
+    Prove,
+    Done,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ComplaintReason {
+    /// The player did not commit to a key before the commitment phase was closed
+    MissingCommitment,
+    /// The player committed but did not reveal before the reveal phase was closed
+    MissingReveal,
+    /// The revealed key does not open the player's commitment
+    CommitmentMismatch,
+    /// The revealed key is the identity
+    IdentityKey,
+    /// The revealed key was already revealed by another player
+    DuplicateKey,
+    /// The player revealed a key but did not prove ownership before finalization
+    MissingProof,
+    /// The proof of ownership of the revealed key is invalid
+    InvalidKeyProof,
+}
+
+/// A public accusation that excludes `player` from the aggregate key.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Complaint {
+    pub player: usize,
+    pub reason: ComplaintReason,
+}
+
+/// Result of a completed key setup.
+#[derive(Clone, PartialEq)]
+pub struct KeySetupOutcome<C: ProjectiveCurve> {
+    pub aggregate_key: PublicKey<C>,
+    /// Indices of the players whose keys make up the aggregate key, in increasing order
+    pub qualified: Vec<usize>,
+    pub complaints: Vec<Complaint>,
+}
+
+/// Local view of the key setup. Every player feeds it the same broadcast messages and therefore
+/// reaches the same outcome.
+pub struct KeySetup<C: ProjectiveCurve, B> {
+    phase: KeySetupPhase,
+    context: Vec<u8>,
+    player_info: Vec<B>,
+    commitments: Vec<Option<KeyCommitment>>,
+    reveals: Vec<Option<PublicKey<C>>>,
+    proofs: Vec<Option<KeyOwnershipProof<C>>>,
+    key_list_digest: Option<KeyListDigest>,
+    complaints: Vec<Complaint>,
+}
+
+impl<C: ProjectiveCurve> fmt::Debug for KeySetupOutcome<C> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("KeySetupOutcome")
+            .field("aggregate_key", &Hex(&self.aggregate_key))
+            .field("qualified", &self.qualified)
+            .field("complaints", &self.complaints)
+            .finish()
+    }
+}
+
+impl<C: ProjectiveCurve, B> fmt::Debug for KeySetup<C, B> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let commitments = self
+            .commitments
+            .iter()
+            .map(|c| c.as_ref().map(|c| HexBytes(c)));
+        let reveals = self.reveals.iter().map(|key| key.as_ref().map(Hex));
+
+        f.debug_struct("KeySetup")
+            .field("phase", &self.phase)
+            .field("context", &HexBytes(&self.context))
+            .field("commitments", &commitments.collect::<Vec<_>>())
+            .field("reveals", &reveals.collect::<Vec<_>>())
+            .field("proofs", &Compact(&self.proofs))
+            .field("complaints", &self.complaints)
+            .finish_non_exhaustive()
+    }
+}
+
+impl<C: ProjectiveCurve, B: ToBytes + Clone> KeySetup<C, B> {
+    /// Start a key setup among the players described by `player_info`. A player's index in this
+    /// vector is the index used in every subsequent message. Key ownership proofs are checked
+    /// against the session `context` of the game the keys are set up for.
+    pub fn new(context: &[u8], player_info: Vec<B>) -> Self {
+        let num_of_players = player_info.len();
+
+        Self {
+            phase: KeySetupPhase::Commit,
+            context: context.to_vec(),
+            player_info,
+            commitments: vec![None; num_of_players],
+            reveals: vec![None; num_of_players],
+            proofs: vec![None; num_of_players],
+            key_list_digest: None,
+            complaints: Vec::new(),
+        }
+    }
+
+    pub fn phase(&self) -> KeySetupPhase {
+        self.phase
+    }
+
+    pub fn complaints(&self) -> &[Complaint] {
+        &self.complaints
+    }
+
+    pub fn receive_commitment(
+        &mut self,
+        player: usize,
+        commitment: KeyCommitment,
+    ) -> Result<(), CardProtocolError> {
+        if self.phase != KeySetupPhase::Commit {
+            return Err(InputError::UnexpectedPhase.into());
+        }
+
+        let slot = self
+            .commitments
+            .get_mut(player)
+            .ok_or(CardProtocolError::InvalidInput(InputError::UnknownPlayer(
+                player,
+            )))?;
+
+        if slot.is_some() {
+            return Err(InputError::DuplicateMessage(player).into());
+        }
+
+        *slot = Some(commitment);
+
+        Ok(())
+    }
+
+    /// Close the commitment phase. Players who did not commit are excluded from the setup.
+    pub fn close_commitments(&mut self) -> Result<(), CardProtocolError> {
+        if self.phase != KeySetupPhase::Commit {
+            return Err(InputError::UnexpectedPhase.into());
+        }
+
+        for (player, commitment) in self.commitments.iter().enumerate() {
+            if commitment.is_none() {
+                self.complaints.push(Complaint {
+                    player,
+                    reason: ComplaintReason::MissingCommitment,
+                });
+            }
+        }
+
+        self.phase = KeySetupPhase::Reveal;
+
+        Ok(())
+    }
+
+    /// Check a revealed key against the player's commitment. Returns the complaint to broadcast if
+    /// the reveal is rejected.
+    pub fn receive_reveal(
+        &mut self,
+        player: usize,
+        pk: PublicKey<C>,
+    ) -> Result<Option<Complaint>, CardProtocolError> {
+        if self.phase != KeySetupPhase::Reveal {
+            return Err(InputError::UnexpectedPhase.into());
+        }
+
+        // Players who did not commit are no longer part of the setup
+        let commitment = match self.commitments.get(player) {
+            Some(Some(commitment)) => *commitment,
+            _ => return Err(InputError::UnknownPlayer(player).into()),
+        };
+
+        if self.reveals[player].is_some() || self.has_complaint(player) {
+            return Err(InputError::DuplicateMessage(player).into());
+        }
+
+        let reason = if commit_to_key(player, &pk)? != commitment {
+            Some(ComplaintReason::CommitmentMismatch)
+        } else if pk.is_zero() {
+            Some(ComplaintReason::IdentityKey)
+        } else if self.reveals.iter().flatten().any(|other| *other == pk) {
+            Some(ComplaintReason::DuplicateKey)
+        } else {
+            None
+        };
+
+        match reason {
+            Some(reason) => Ok(Some(self.complain(player, reason))),
+            None => {
+                self.reveals[player] = Some(pk);
+                Ok(None)
+            }
+        }
+    }
+
+    /// Close the reveal phase, complaining about every committed player who has not revealed. The
+    /// revealed keys, in increasing player order, form the key list that ownership proofs are bound
+    /// to.
+    pub fn close_reveals(&mut self) -> Result<(), CardProtocolError> {
+        if self.phase != KeySetupPhase::Reveal {
+            return Err(InputError::UnexpectedPhase.into());
+        }
+
+        for player in 0..self.player_info.len() {
+            if self.reveals[player].is_none() && !self.has_complaint(player) {
+                self.complain(player, ComplaintReason::MissingReveal);
+            }
+        }
+
+        let keys = self.reveals.iter().flatten().copied().collect::<Vec<_>>();
+        if keys.is_empty() {
+            return Err(InputError::NotEnoughPlayers.into());
+        }
+
+        self.key_list_digest = Some(DLCards::<C>::key_list_digest(&keys)?);
+        self.phase = KeySetupPhase::Prove;
+
+        Ok(())
+    }
+
+    /// Index and key list digest for which `player` must prove ownership of their key.
+    pub fn proof_context(
+        &self,
+        player: usize,
+    ) -> Result<(usize, KeyListDigest), CardProtocolError> {
+        let key_list_digest = self
+            .key_list_digest
+            .ok_or(CardProtocolError::InvalidInput(InputError::UnexpectedPhase))?;
+
+        match self.reveals.get(player) {
+            Some(Some(_)) => Ok((self.key_index(player), key_list_digest)),
+            _ => Err(CardProtocolError::InvalidInput(InputError::UnknownPlayer(
+                player,
+            ))),
+        }
+    }
+
+    /// Verify a player's proof of ownership of their revealed key. Returns the complaint to
+    /// broadcast if the proof is rejected.
+    pub fn receive_proof(
+        &mut self,
+        pp: &Parameters<C>,
+        player: usize,
+        proof: KeyOwnershipProof<C>,
+    ) -> Result<Option<Complaint>, CardProtocolError> {
+        if self.phase != KeySetupPhase::Prove {
+            return Err(InputError::UnexpectedPhase.into());
+        }
+
+        let (key_index, key_list_digest) = self.proof_context(player)?;
+        let pk = self.reveals[player].ok_or(CardProtocolError::InvalidInput(
+            InputError::UnknownPlayer(player),
+        ))?;
+
+        if self.proofs[player].is_some() || self.has_complaint(player) {
+            return Err(InputError::DuplicateMessage(player).into());
+        }
+
+        if DLCards::<C>::verify_key_ownership(
+            pp,
+            &self.context,
+            &pk,
+            &self.player_info[player],
+            key_index,
+            &key_list_digest,
+            &proof,
+        )
+        .is_err()
+        {
+            return Ok(Some(
+                self.complain(player, ComplaintReason::InvalidKeyProof),
+            ));
+        }
+
+        self.proofs[player] = Some(proof);
+
+        Ok(None)
+    }
+
+    /// Complain about every player who revealed but has not proven ownership, and aggregate the
+    /// keys of the qualified players. Excluding a player at this point does not invalidate the
+    /// others' proofs: each remaining key still comes with a proof of knowledge of its secret.
+    pub fn finalize(&mut self) -> Result<KeySetupOutcome<C>, CardProtocolError> {
+        if self.phase != KeySetupPhase::Prove {
+            return Err(InputError::UnexpectedPhase.into());
+        }
+
+        for player in 0..self.player_info.len() {
+            if self.reveals[player].is_some()
+                && self.proofs[player].is_none()
+                && !self.has_complaint(player)
+            {
+                self.complain(player, ComplaintReason::MissingProof);
+            }
+        }
+
+        let qualified = (0..self.player_info.len())
+            .filter(|&player| self.proofs[player].is_some())
+            .collect::<Vec<_>>();
+
+        if qualified.is_empty() {
+            return Err(InputError::NotEnoughPlayers.into());
+        }
+
+        let aggregate_key = qualified
+            .iter()
+            .filter_map(|&player| self.reveals[player])
+            .fold(PublicKey::<C>::zero(), |acc, pk| acc + pk);
+
+        self.phase = KeySetupPhase::Done;
+
+        Ok(KeySetupOutcome {
+            aggregate_key,
+            qualified,
+            complaints: self.complaints.clone(),
+        })
+    }
+
+    fn complain(&mut self, player: usize, reason: ComplaintReason) -> Complaint {
+        let complaint = Complaint { player, reason };
+        self.complaints.push(complaint);
+        complaint
+    }
+
+    fn has_complaint(&self, player: usize) -> bool {
+        self.complaints.iter().any(|c| c.player == player)
+    }
+
+    /// Position of `player`'s key in the list of revealed keys.
+    fn key_index(&self, player: usize) -> usize {
+        self.reveals[..player].iter().flatten().count()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::crypto_primitives::utils::rand::test_rng;
+    use crate::discrete_log_cards;
+    use crate::discrete_log_cards::dkg::{
+        commit_to_key, Complaint, ComplaintReason, KeySetup, KeySetupPhase,
+    };
+    use crate::discrete_log_cards::test_helpers::setup_players;
+    use crate::error::{CardProtocolError, InputError};
+    use crate::BarnettSmartProtocol;
+
+    use ark_ff::UniformRand;
+    use ark_std::Zero;
+
+    // Choose elliptic curve setting
+    type Curve = starknet_curve::Projective;
+    type Scalar = starknet_curve::Fr;
+
+    // Instantiate concrete type for our card protocol
+    type CardProtocol<'a> = discrete_log_cards::DLCards<'a, Curve>;
+    type PublicKey = discrete_log_cards::PublicKey<Curve>;
+
+    #[test]
+    fn test_silent_player_is_excluded() {
+        let rng = &mut test_rng();
+        let m = 4;
+        let n = 13;
+
+        let num_of_players = 4;
+        let silent_player = 2;
+        let unproven_player = 3;
+
+        let parameters = CardProtocol::setup(rng, m, n).unwrap();
+
+        let (players, _) = setup_players(rng, &parameters, num_of_players).unwrap();
+
+        let mut setup = KeySetup::new(b"", players.iter().map(|p| p.2).collect());
+
+        for (i, (pk, _, _)) in players.iter().enumerate() {
+            let commitment = commit_to_key(i, pk).unwrap();
+            setup.receive_commitment(i, commitment).unwrap();
+        }
+
+        setup.close_commitments().unwrap();
+        assert_eq!(setup.phase(), KeySetupPhase::Reveal);
+
+        for (i, (pk, _, _)) in players.iter().enumerate() {
+            if i != silent_player {
+                assert_eq!(Ok(None), setup.receive_reveal(i, *pk));
+            }
+        }
+
+        setup.close_reveals().unwrap();
+        assert_eq!(setup.phase(), KeySetupPhase::Prove);
+
+        // The silent player is not part of the key list, so the last player has index 2
+        assert_eq!(setup.proof_context(3).unwrap().0, 2);
+        assert_eq!(
+            setup.proof_context(silent_player),
+            Err(CardProtocolError::InvalidInput(InputError::UnknownPlayer(
+                silent_player
+            )))
+        );
+
+        for (i, (pk, sk, info)) in players.iter().enumerate() {
+            if i != silent_player && i != unproven_player {
+                let (key_index, key_list_digest) = setup.proof_context(i).unwrap();
+                let proof = CardProtocol::prove_key_ownership(
+                    rng,
+                    &parameters,
+                    b"",
+                    pk,
+                    sk,
+                    info,
+                    key_index,
+                    &key_list_digest,
+                )
+                .unwrap();
+                assert_eq!(Ok(None), setup.receive_proof(&parameters, i, proof));
+            }
+        }
+
+        let outcome = setup.finalize().unwrap();
+
+        assert_eq!(outcome.aggregate_key, players[0].0 + players[1].0);
+        assert_eq!(outcome.qualified, vec![0, 1]);
+        assert_eq!(
+            outcome.complaints,
+            vec![
+                Complaint {
+                    player: silent_player,
+                    reason: ComplaintReason::MissingReveal
+                },
+                Complaint {
+                    player: unproven_player,
+                    reason: ComplaintReason::MissingProof
+                }
+            ]
+        );
+    }
+
+    #[test]
+    fn test_reveal_must_open_commitment() {
+        let rng = &mut test_rng();
+        let m = 4;
+        let n = 13;
+
+        let parameters = CardProtocol::setup(rng, m, n).unwrap();
+
+        let info = Scalar::rand(rng);
+        let (pk, _) = CardProtocol::player_keygen(rng, &parameters).unwrap();
+        let (other_pk, _) = CardProtocol::player_keygen(rng, &parameters).unwrap();
+
+        let mut setup = KeySetup::new(b"", vec![info, info, info]);
+        setup
+            .receive_commitment(0, commit_to_key(0, &pk).unwrap())
+            .unwrap();
+        setup
+            .receive_commitment(2, commit_to_key(2, &PublicKey::zero()).unwrap())
+            .unwrap();
+        setup.close_commitments().unwrap();
+
+        // Player 0 changes their key after the commitment phase
+        assert_eq!(
+            Ok(Some(Complaint {
+                player: 0,
+                reason: ComplaintReason::CommitmentMismatch
+            })),
+            setup.receive_reveal(0, other_pk)
+        );
+
+        // Player 2 committed to the identity
+        assert_eq!(
+            Ok(Some(Complaint {
+                player: 2,
+                reason: ComplaintReason::IdentityKey
+            })),
+            setup.receive_reveal(2, PublicKey::zero())
+        );
+
+        assert_eq!(
+            setup.complaints()[0],
+            Complaint {
+                player: 1,
+                reason: ComplaintReason::MissingCommitment
+            }
+        );
+        assert_eq!(
+            setup.close_reveals(),
+            Err(CardProtocolError::InvalidInput(
+                InputError::NotEnoughPlayers
+            ))
+        );
+    }
+
+    #[test]
+    fn test_duplicate_key_is_rejected() {
+        let rng = &mut test_rng();
+        let m = 4;
+        let n = 13;
+
+        let parameters = CardProtocol::setup(rng, m, n).unwrap();
+
+        let info = Scalar::rand(rng);
+        let (pk, _) = CardProtocol::player_keygen(rng, &parameters).unwrap();
+
+        // Both players commit to, and reveal, the same key
+        let mut setup = KeySetup::new(b"", vec![info, info]);
+        for player in 0..2 {
+            setup
+                .receive_commitment(player, commit_to_key(player, &pk).unwrap())
+                .unwrap();
+        }
+        setup.close_commitments().unwrap();
+
+        assert_eq!(Ok(None), setup.receive_reveal(0, pk));
+        assert_eq!(
+            Ok(Some(Complaint {
+                player: 1,
+                reason: ComplaintReason::DuplicateKey
+            })),
+            setup.receive_reveal(1, pk)
+        );
+    }
+}
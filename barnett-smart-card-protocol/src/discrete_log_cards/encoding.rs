@@ -0,0 +1,421 @@
+use crate::crypto_primitives::hash_to_curve::hash_to_curve;
+use crate::crypto_primitives::utils::ct::ct_position;
+use crate::crypto_primitives::utils::format::Compact;
+use crate::discrete_log_cards::{deck_shape, Card};
+use crate::error::{CardProtocolError, InputError};
+use crate::playing_card::ClassicPlayingCard;
+use crate::FromLabel;
+
+use ark_ec::ProjectiveCurve;
+use ark_serialize::CanonicalSerialize;
+use ark_std::collections::{BTreeMap, BTreeSet};
+use ark_std::string::{String, ToString};
+use ark_std::vec::Vec;
+use ark_std::{fmt, format};
+use proof_essentials::homomorphic_encryption::el_gamal;
+
+/// Domain separator for card encodings. Every encoding depends on it, so it carries a version.
+const CARD_ENCODING_DOMAIN: &'static [u8] = b"Barnett-Smart Card Encoding v1";
+
+/// Number of cards in a standard deck.
+pub const STANDARD_DECK_SIZE: usize = 52;
+
+/// Ranks of the standard deck from lowest to highest, in the notation used for card labels.
+pub const STANDARD_RANKS: [&'static str; 13] = [
+    "2", "3", "4", "5", "6", "7", "8", "9", "T", "J", "Q", "K", "A",
+];
+
+/// Suits of the standard deck (clubs, diamonds, hearts, spades), in the notation used for card labels.
+pub const STANDARD_SUITS: [&'static str; 4] = ["c", "d", "h", "s"];
+
+impl<C: ProjectiveCurve> FromLabel for Card<C> {
+    /// Hash the label to the curve. Encodings do not depend on the protocol parameters, so the same
+    /// label maps to the same card at every table using the same curve.
+    fn from_label(label: &[u8]) -> Result<Self, CardProtocolError> {
+        let point = hash_to_curve::<C>(CARD_ENCODING_DOMAIN, label)?;
+        Ok(el_gamal::Plaintext(point))
+    }
+}
+
+/// Labels of the 52 standard cards, ordered by rank and then by suit: `2c, 2d, 2h, 2s, 3c, ..., As`.
+pub fn standard_deck_labels() -> Vec<String> {
+    STANDARD_RANKS
+        .iter()
+        .flat_map(|rank| {
+            STANDARD_SUITS
+                .iter()
+                .map(move |suit| format!("{}{}", rank, suit))
+        })
+        .collect()
+}
+
+/// Canonical encodings of a shoe of `copies` standard decks, one deck after the other, each in the
+/// order given by [`standard_deck_labels`]. Every copy of a card has its own encoding, so the cards
+/// of a shoe are distinct plaintexts like those of a single deck: the first copy is encoded from
+/// the label alone, which keeps single-deck encodings unchanged, and copy `k > 0` from the label
+/// followed by `#k`. [`logical_card`] maps a position in the shoe back to the card it stands for.
+pub fn encode_standard_deck<C: ProjectiveCurve>(
+    copies: usize,
+) -> Result<Vec<Card<C>>, CardProtocolError> {
+    let labels = standard_deck_labels();
+
+    (0..copies)
+        .flat_map(|copy| labels.iter().map(move |label| copy_label(label, copy)))
+        .map(|label| Card::<C>::from_label(label.as_bytes()))
+        .collect()
+}
+
+/// The card, as a position in [`standard_deck_labels`], that the card at `position` of a shoe
+/// encoded by [`encode_standard_deck`] stands for. All copies of a card map to the same position.
+pub fn logical_card(position: usize) -> usize {
+    position % STANDARD_DECK_SIZE
+}
+
+/// Look up an opened card among the encodings of a shoe, in constant time, and return the card it
+/// stands for as a position in [`standard_deck_labels`].
+pub fn decode_card<C: ProjectiveCurve>(
+    card: &Card<C>,
+    shoe: &[Card<C>],
+) -> Result<Option<usize>, CardProtocolError> {
+    Ok(ct_position(card, shoe)?.map(logical_card))
+}
+
+/// Composition of a deck: the label of every card, in deck order. Cards are encoded from their
+/// labels, so a label shared with the standard deck yields the same encoding as in the standard
+/// deck.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct DeckSpec {
+    labels: Vec<String>,
+}
+
+impl DeckSpec {
+    /// The 52 cards of [`standard_deck_labels`].
+    pub fn standard52() -> Self {
+        Self::custom(standard_deck_labels())
+    }
+
+    /// The 36 cards from six to ace used by short-deck hold'em, in the order of the standard deck.
+    pub fn short_deck() -> Self {
+        Self::custom(standard_deck_labels().split_off(4 * STANDARD_SUITS.len()))
+    }
+
+    /// A deck of arbitrary cards, such as a tarot deck. Labels must be distinct.
+    pub fn custom(labels: Vec<String>) -> Self {
+        Self { labels }
+    }
+
+    /// Add `count` jokers, labelled `Joker1`, `Joker2` and so on, at the end of the deck.
+    pub fn with_jokers(mut self, count: usize) -> Self {
+        self.labels
+            .extend((1..=count).map(|joker| format!("Joker{}", joker)));
+        self
+    }
+
+    pub fn labels(&self) -> &[String] {
+        &self.labels
+    }
+
+    pub fn len(&self) -> usize {
+        self.labels.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.labels.is_empty()
+    }
+
+    /// Encode every card, and arrange the deck in the shape given by `deck_shape`.
+    pub fn encode<C: ProjectiveCurve>(&self) -> Result<EncodedDeck<C>, CardProtocolError> {
+        let mut seen = BTreeSet::new();
+        if let Some(label) = self.labels.iter().find(|label| !seen.insert(*label)) {
+            return Err(InputError::DuplicateLabel(label.clone()).into());
+        }
+
+        let cards = self
+            .labels
+            .iter()
+            .map(|label| Card::<C>::from_label(label.as_bytes()))
+            .collect::<Result<Vec<_>, _>>()?;
+
+        Ok(EncodedDeck {
+            labels: self.labels.clone(),
+            cards,
+            shape: deck_shape(self.labels.len())?,
+        })
+    }
+}
+
+/// The cards of a [`DeckSpec`], with the dimensions of the deck and the means to look up opened
+/// cards.
+#[derive(Clone, PartialEq, Eq)]
+pub struct EncodedDeck<C: ProjectiveCurve> {
+    labels: Vec<String>,
+    cards: Vec<Card<C>>,
+    shape: (usize, usize),
+}
+
+/// The encodings follow from the labels, so only the labels and the shape are printed.
+impl<C: ProjectiveCurve> fmt::Debug for EncodedDeck<C> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("EncodedDeck")
+            .field("labels", &self.labels)
+            .field("shape", &self.shape)
+            .finish_non_exhaustive()
+    }
+}
+
+impl<C: ProjectiveCurve> EncodedDeck<C> {
+    /// Encodings of the cards, in deck order.
+    pub fn cards(&self) -> &[Card<C>] {
+        &self.cards
+    }
+
+    pub fn labels(&self) -> &[String] {
+        &self.labels
+    }
+
+    /// Recommended `(m, n)` for `setup`.
+    pub fn shape(&self) -> (usize, usize) {
+        self.shape
+    }
+
+    /// Position of an opened card in the deck, looked up in constant time.
+    pub fn position(&self, card: &Card<C>) -> Result<Option<usize>, CardProtocolError> {
+        ct_position(card, &self.cards)
+    }
+
+    /// Label of an opened card, looked up in constant time.
+    pub fn label(&self, card: &Card<C>) -> Result<Option<&str>, CardProtocolError> {
+        Ok(self
+            .position(card)?
+            .map(|position| self.labels[position].as_str()))
+    }
+}
+
+/// Map from the encodings of a deck to their positions, keyed by the canonical serialization of
+/// the affine point. A lookup takes time that depends on the card, so only use it for cards every
+/// player sees, such as community cards and hands shown at showdown. Cards opened privately are
+/// looked up with `ct_position` over [`CardLookup::cards`].
+#[derive(Clone)]
+pub struct CardLookup<C: ProjectiveCurve> {
+    cards: Vec<Card<C>>,
+    positions: BTreeMap<Vec<u8>, usize>,
+}
+
+impl<C: ProjectiveCurve> fmt::Debug for CardLookup<C> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("CardLookup")
+            .field("cards", &Compact(&self.cards))
+            .finish_non_exhaustive()
+    }
+}
+
+impl<C: ProjectiveCurve> CardLookup<C> {
+    /// Lookup of the given encodings. If an encoding appears more than once, its first position is
+    /// returned.
+    pub fn new(cards: Vec<Card<C>>) -> Result<Self, CardProtocolError> {
+        let mut positions = BTreeMap::new();
+        for (position, card) in cards.iter().enumerate() {
+            positions.entry(key(card)?).or_insert(position);
+        }
+
+        Ok(Self { cards, positions })
+    }
+
+    /// Lookup of the standard deck, as encoded by [`encode_standard_deck`].
+    pub fn standard() -> Result<Self, CardProtocolError> {
+        Self::new(encode_standard_deck(1)?)
+    }
+
+    pub fn cards(&self) -> &[Card<C>] {
+        &self.cards
+    }
+
+    /// Position of an opened card, or `None` if it is not one of the encodings.
+    pub fn index_of(&self, card: &Card<C>) -> Option<usize> {
+        self.positions.get(&key(card).ok()?).copied()
+    }
+
+    /// The card an opened card stands for, for a lookup of a shoe encoded by
+    /// [`encode_standard_deck`]. `None` means the card is not in the shoe, which only happens if
+    /// a player broke the protocol.
+    pub fn classic(&self, card: &Card<C>) -> Option<ClassicPlayingCard> {
+        self.index_of(card)
+            .map(logical_card)
+            .and_then(ClassicPlayingCard::from_standard_position)
+    }
+}
+
+fn key<C: ProjectiveCurve>(card: &Card<C>) -> Result<Vec<u8>, CardProtocolError> {
+    let mut bytes = Vec::new();
+    card.0.serialize(&mut bytes)?;
+
+    Ok(bytes)
+}
+
+fn copy_label(label: &str, copy: usize) -> String {
+    match copy {
+        0 => label.to_string(),
+        _ => format!("{}#{}", label, copy),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::discrete_log_cards;
+    use crate::discrete_log_cards::encoding::{
+        decode_card, encode_standard_deck, logical_card, standard_deck_labels, CardLookup, DeckSpec,
+    };
+    use crate::error::{Bound, CardProtocolError, Dimension, InputError};
+    use crate::playing_card::ClassicPlayingCard;
+    use crate::FromLabel;
+
+    use ark_ec::{AffineCurve, ProjectiveCurve};
+    use ark_serialize::CanonicalSerialize;
+    use proof_essentials::homomorphic_encryption::el_gamal;
+    use std::collections::HashSet;
+
+    // Choose elliptic curve setting
+    type Curve = starknet_curve::Projective;
+
+    type Card = discrete_log_cards::Card<Curve>;
+
+    const STARKNET_STANDARD_DECK: &str =
+        include_str!("../../tests/vectors/starknet_standard_deck.txt");
+
+    fn to_hex(bytes: &[u8]) -> String {
+        bytes.iter().map(|b| format!("{:02x}", b)).collect()
+    }
+
+    #[test]
+    fn test_standard_deck_encoding() {
+        let deck = encode_standard_deck::<Curve>(1).unwrap();
+        let labels = standard_deck_labels();
+
+        assert_eq!(deck.len(), 52);
+        assert_eq!(deck.iter().collect::<HashSet<_>>().len(), 52);
+        assert_eq!(deck[51], Card::from_label(b"As").unwrap());
+
+        let pinned = STARKNET_STANDARD_DECK
+            .lines()
+            .filter(|line| !line.starts_with('#') && !line.is_empty())
+            .map(|line| line.split_once(' ').unwrap())
+            .collect::<Vec<_>>();
+
+        assert_eq!(pinned.len(), 52);
+
+        for ((card, label), (pinned_label, pinned_hex)) in
+            deck.iter().zip(labels.iter()).zip(pinned.iter())
+        {
+            let mut bytes = Vec::new();
+            card.0.serialize(&mut bytes).unwrap();
+
+            assert_eq!(label, pinned_label);
+            assert_eq!(&to_hex(&bytes), pinned_hex, "encoding of {} changed", label);
+        }
+    }
+
+    #[test]
+    fn test_shoe_encoding() {
+        let deck = encode_standard_deck::<Curve>(1).unwrap();
+        let shoe = encode_standard_deck::<Curve>(6).unwrap();
+
+        // Every card of the shoe is distinct, and the first deck is the standard one
+        assert_eq!(shoe.len(), 312);
+        assert_eq!(shoe.iter().collect::<HashSet<_>>().len(), 312);
+        assert_eq!(&shoe[..52], &deck[..]);
+        assert_eq!(shoe[52 + 51], Card::from_label(b"As#1").unwrap());
+
+        // All copies of a card decode to the same card
+        for (position, card) in shoe.iter().enumerate() {
+            assert_eq!(Ok(Some(logical_card(position))), decode_card(card, &shoe));
+        }
+        assert_eq!(logical_card(5 * 52 + 51), 51);
+        assert_eq!(
+            Ok(None),
+            decode_card(&Card::from_label(b"Joker").unwrap(), &shoe)
+        );
+    }
+
+    #[test]
+    fn test_deck_spec() {
+        let standard = DeckSpec::standard52().encode::<Curve>().unwrap();
+        assert_eq!(
+            standard.cards(),
+            &encode_standard_deck::<Curve>(1).unwrap()[..]
+        );
+        assert_eq!(standard.shape(), (4, 13));
+
+        // The short deck keeps the encodings of the standard cards from six to ace
+        let short = DeckSpec::short_deck().encode::<Curve>().unwrap();
+        assert_eq!(short.cards().len(), 36);
+        assert_eq!(short.shape(), (6, 6));
+        assert_eq!(short.labels()[0], "6c");
+        assert_eq!(short.cards(), &standard.cards()[16..]);
+        assert_eq!(Ok(None), short.position(&standard.cards()[0]));
+
+        let jokers = DeckSpec::standard52().with_jokers(2);
+        assert_eq!(jokers.len(), 54);
+        assert_eq!(jokers.labels()[53], "Joker2");
+        let jokers = jokers.encode::<Curve>().unwrap();
+        assert_eq!(jokers.shape(), (6, 9));
+        assert_eq!(jokers.cards().iter().collect::<HashSet<_>>().len(), 54);
+        assert_eq!(
+            Ok(Some("Joker1")),
+            jokers.label(&Card::from_label(b"Joker1").unwrap())
+        );
+
+        let tarot = (0..22)
+            .map(|arcanum| format!("Major {}", arcanum))
+            .collect::<Vec<_>>();
+        let tarot = DeckSpec::custom(tarot).encode::<Curve>().unwrap();
+        assert_eq!(tarot.shape(), (2, 11));
+        assert_eq!(Ok(Some(21)), tarot.position(&tarot.cards()[21]));
+
+        assert_eq!(
+            DeckSpec::custom(vec!["Fool".to_string(), "Fool".to_string()]).encode::<Curve>(),
+            Err(CardProtocolError::InvalidInput(InputError::DuplicateLabel(
+                "Fool".to_string()
+            )))
+        );
+        assert_eq!(
+            DeckSpec::custom(Vec::new()).encode::<Curve>(),
+            Err(CardProtocolError::InvalidInput(
+                InputError::DimensionMismatch {
+                    dimension: Dimension::Deck,
+                    expected: Bound::AtLeast(1),
+                    got: 0
+                }
+            ))
+        );
+    }
+
+    #[test]
+    fn test_card_lookup() {
+        let lookup = CardLookup::<Curve>::standard().unwrap();
+
+        for (position, card) in lookup.cards().iter().enumerate() {
+            assert_eq!(lookup.index_of(card), Some(position));
+            assert_eq!(
+                lookup.classic(card),
+                ClassicPlayingCard::from_standard_position(position)
+            );
+
+            // A card that went through projective arithmetic is found all the same
+            let recomputed = el_gamal::Plaintext(
+                (card.0.into_projective().double() - card.0.into_projective()).into_affine(),
+            );
+            assert_eq!(lookup.index_of(&recomputed), Some(position));
+        }
+
+        let joker = Card::from_label(b"Joker").unwrap();
+        assert_eq!(lookup.index_of(&joker), None);
+        assert_eq!(lookup.classic(&joker), None);
+
+        // Every copy of a card in a shoe stands for the same card
+        let shoe = CardLookup::<Curve>::new(encode_standard_deck(2).unwrap()).unwrap();
+        let ace_of_spades = "As".parse::<ClassicPlayingCard>().unwrap();
+        assert_eq!(shoe.index_of(&shoe.cards()[52 + 51]), Some(52 + 51));
+        assert_eq!(shoe.classic(&shoe.cards()[51]), Some(ace_of_spades));
+        assert_eq!(shoe.classic(&shoe.cards()[52 + 51]), Some(ace_of_spades));
+    }
+}
@@ -0,0 +1,438 @@
+//! Append-only log of the messages of a game.
+//!
+//! Every entry of an [`EventLog`] is hashed together with the digest of the entry before it, so
+//! the digest of the last entry, the head of the log, commits to every message and to their order.
+//! Players who periodically compare heads agree on the whole history up to that point, and an
+//! entry modified, reordered or dropped afterwards breaks the chain at that position, which
+//! [`EventLog::verify_chain`] reports.
+//!
+//! The chain alone only shows that the log is consistent: whoever holds it can rebuild a chain
+//! over different messages. Entries may therefore carry the signature of their author, made with
+//! their identity key as for a `SignedMessage` whose slot is the position of the entry in the log.
+//! Signatures are part of the digest, and [`EventLog::verify_signatures`] checks them against the
+//! identities of the players. Dropping the last entries is only detected by comparing heads.
+
+use crate::crypto_primitives::signature::Signature;
+use crate::crypto_primitives::utils::format::HexBytes;
+use crate::discrete_log_cards::abort::SignedMessage;
+use crate::discrete_log_cards::identity::PublicIdentity;
+use crate::discrete_log_cards::session::GameMessage;
+use crate::error::{CardProtocolError, InputError, VerificationError};
+
+use ark_ec::ProjectiveCurve;
+use ark_serialize::{CanonicalDeserialize, CanonicalSerialize, Read, SerializationError, Write};
+use ark_std::fmt;
+use blake2::{Blake2s, Digest};
+
+const EVENT_LOG_GENESIS_DOMAIN: &'static [u8] = b"Event Log Genesis";
+const EVENT_LOG_ENTRY_DOMAIN: &'static [u8] = b"Event Log Entry";
+
+pub type EntryDigest = [u8; 32];
+
+/// A message of the player in seat `sender`, with the digest of the log up to and including it.
+#[derive(Clone)]
+pub struct LogEntry<C: ProjectiveCurve> {
+    pub sender: usize,
+    pub message: GameMessage<C>,
+    pub signature: Option<Signature<C>>,
+    pub digest: EntryDigest,
+}
+
+impl<C: ProjectiveCurve> fmt::Debug for LogEntry<C> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("LogEntry")
+            .field("sender", &self.sender)
+            .field("message", &self.message)
+            .field("signature", &self.signature)
+            .field("digest", &HexBytes(&self.digest))
+            .finish()
+    }
+}
+
+impl<C: ProjectiveCurve> CanonicalSerialize for LogEntry<C> {
+    fn serialize<W: Write>(&self, mut writer: W) -> Result<(), SerializationError> {
+        (self.sender as u64).serialize(&mut writer)?;
+        self.message.serialize(&mut writer)?;
+        self.signature.serialize(&mut writer)?;
+        writer.write_all(&self.digest)?;
+
+        Ok(())
+    }
+
+    fn serialized_size(&self) -> usize {
+        0u64.serialized_size()
+            + self.message.serialized_size()
+            + self.signature.serialized_size()
+            + self.digest.len()
+    }
+}
+
+impl<C: ProjectiveCurve> CanonicalDeserialize for LogEntry<C> {
+    fn deserialize<R: Read>(mut reader: R) -> Result<Self, SerializationError> {
+        let sender = u64::deserialize(&mut reader)? as usize;
+        let message = GameMessage::deserialize(&mut reader)?;
+        let signature = Option::<Signature<C>>::deserialize(&mut reader)?;
+        let mut digest = EntryDigest::default();
+        reader.read_exact(&mut digest)?;
+
+        Ok(Self {
+            sender,
+            message,
+            signature,
+            digest,
+        })
+    }
+}
+
+#[derive(Clone, CanonicalSerialize, CanonicalDeserialize)]
+pub struct EventLog<C: ProjectiveCurve> {
+    context: Vec<u8>,
+    entries: Vec<LogEntry<C>>,
+}
+
+impl<C: ProjectiveCurve> fmt::Debug for EventLog<C> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("EventLog")
+            .field("context", &HexBytes(&self.context))
+            .field("entries", &self.entries)
+            .finish()
+    }
+}
+
+impl<C: ProjectiveCurve> EventLog<C> {
+    /// Empty log of the game with session context `context`.
+    pub fn new(context: &[u8]) -> Self {
+        Self {
+            context: context.to_vec(),
+            entries: Vec::new(),
+        }
+    }
+
+    pub fn context(&self) -> &[u8] {
+        &self.context
+    }
+
+    pub fn entries(&self) -> &[LogEntry<C>] {
+        &self.entries
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    /// Digest of the last entry, or of the context alone for an empty log.
+    pub fn head(&self) -> Result<EntryDigest, CardProtocolError> {
+        match self.entries.last() {
+            Some(entry) => Ok(entry.digest),
+            None => genesis_digest(&self.context),
+        }
+    }
+
+    /// The messages in the order they were logged, with the seat of their sender.
+    pub fn messages(&self) -> Vec<(usize, GameMessage<C>)> {
+        self.entries
+            .iter()
+            .map(|entry| (entry.sender, entry.message.clone()))
+            .collect()
+    }
+
+    /// Append an unsigned message of `sender` and return the new head.
+    pub fn append(
+        &mut self,
+        sender: usize,
+        message: GameMessage<C>,
+    ) -> Result<EntryDigest, CardProtocolError> {
+        self.push(sender, message, None)
+    }
+
+    /// Append a signed message, whose slot must be the position it takes in the log, and return
+    /// the new head.
+    pub fn append_signed(
+        &mut self,
+        signed: SignedMessage<C>,
+    ) -> Result<EntryDigest, CardProtocolError> {
+        let expected = self.entries.len();
+        if signed.slot != expected as u64 {
+            return Err(InputError::UnexpectedPosition {
+                expected,
+                position: signed.slot as usize,
+            }
+            .into());
+        }
+
+        self.push(signed.sender, signed.message, Some(signed.signature))
+    }
+
+    /// The entry at `index` as the message its author signed, or `None` if it is unsigned.
+    pub fn signed_message(&self, index: usize) -> Option<SignedMessage<C>> {
+        let entry = self.entries.get(index)?;
+
+        entry.signature.map(|signature| SignedMessage {
+            sender: entry.sender,
+            slot: index as u64,
+            message: entry.message.clone(),
+            signature,
+        })
+    }
+
+    /// Recompute the digest of every entry from the one before it. Fails with `BrokenChain` at the
+    /// first entry whose digest does not match.
+    pub fn verify_chain(&self) -> Result<(), CardProtocolError> {
+        let mut previous = genesis_digest(&self.context)?;
+
+        for (index, entry) in self.entries.iter().enumerate() {
+            let digest = entry_digest(
+                &previous,
+                index,
+                entry.sender,
+                &entry.message,
+                &entry.signature,
+            )?;
+            if digest != entry.digest {
+                return Err(VerificationError::BrokenChain(index).into());
+            }
+
+            previous = digest;
+        }
+
+        Ok(())
+    }
+
+    /// Check the signature of every signed entry. `identities[i]` is the identity of the player in
+    /// seat `i`. Unsigned entries are accepted.
+    pub fn verify_signatures(
+        &self,
+        identities: &[PublicIdentity<C>],
+    ) -> Result<(), CardProtocolError> {
+        for index in 0..self.entries.len() {
+            if let Some(signed) = self.signed_message(index) {
+                signed.verify(&self.context, identities).map_err(|source| {
+                    CardProtocolError::verification_failed(signed.sender, source)
+                })?;
+            }
+        }
+
+        Ok(())
+    }
+
+    fn push(
+        &mut self,
+        sender: usize,
+        message: GameMessage<C>,
+        signature: Option<Signature<C>>,
+    ) -> Result<EntryDigest, CardProtocolError> {
+        let digest = entry_digest(
+            &self.head()?,
+            self.entries.len(),
+            sender,
+            &message,
+            &signature,
+        )?;
+
+        self.entries.push(LogEntry {
+            sender,
+            message,
+            signature,
+            digest,
+        });
+
+        Ok(digest)
+    }
+}
+
+fn genesis_digest(context: &[u8]) -> Result<EntryDigest, CardProtocolError> {
+    let mut bytes = Vec::new();
+    (context.len() as u64).serialize(&mut bytes)?;
+    bytes.extend_from_slice(context);
+
+    let mut hasher = Blake2s::new();
+    hasher.update(EVENT_LOG_GENESIS_DOMAIN);
+    hasher.update(&bytes);
+
+    let mut digest = EntryDigest::default();
+    digest.copy_from_slice(&hasher.finalize());
+
+    Ok(digest)
+}
+
+fn entry_digest<C: ProjectiveCurve>(
+    previous: &EntryDigest,
+    index: usize,
+    sender: usize,
+    message: &GameMessage<C>,
+    signature: &Option<Signature<C>>,
+) -> Result<EntryDigest, CardProtocolError> {
+    let mut bytes = Vec::new();
+    (index as u64, sender as u64).serialize(&mut bytes)?;
+    message.serialize(&mut bytes)?;
+    signature.serialize(&mut bytes)?;
+
+    let mut hasher = Blake2s::new();
+    hasher.update(EVENT_LOG_ENTRY_DOMAIN);
+    hasher.update(previous);
+    hasher.update(&bytes);
+
+    let mut digest = EntryDigest::default();
+    digest.copy_from_slice(&hasher.finalize());
+
+    Ok(digest)
+}
+
+#[cfg(test)]
+mod test {
+    use super::EventLog;
+    use crate::crypto_primitives::utils::rand::test_rng;
+    use crate::discrete_log_cards;
+    use crate::discrete_log_cards::abort::SignedMessage;
+    use crate::discrete_log_cards::identity::IdentityKey;
+    use crate::discrete_log_cards::session::GameMessage;
+    use crate::discrete_log_cards::test_helpers::setup_players;
+    use crate::error::{CardProtocolError, InputError, VerificationError};
+    use crate::BarnettSmartProtocol;
+
+    use ark_serialize::{CanonicalDeserialize, CanonicalSerialize};
+
+    // Choose elliptic curve setting
+    type Curve = starknet_curve::Projective;
+
+    // Instantiate concrete type for our card protocol
+    type CardProtocol<'a> = discrete_log_cards::DLCards<'a, Curve>;
+
+    const SESSION_ID: &[u8] = b"event log test";
+
+    /// A log of the public keys of four players, every one of them signed by its sender.
+    fn key_exchange_log() -> (EventLog<Curve>, Vec<IdentityKey<Curve>>) {
+        let rng = &mut test_rng();
+        let m = 4;
+        let n = 13;
+        let num_of_players = 4;
+
+        let parameters = CardProtocol::setup(rng, m, n).unwrap();
+        let (players, _) = setup_players(rng, &parameters, num_of_players).unwrap();
+        let identities = (0..num_of_players)
+            .map(|_| IdentityKey::new(rng).unwrap())
+            .collect::<Vec<_>>();
+
+        let mut log = EventLog::new(SESSION_ID);
+        for (player, (identity, (pk, _, _))) in identities.iter().zip(&players).enumerate() {
+            let signed = SignedMessage::sign(
+                identity,
+                SESSION_ID,
+                player,
+                log.len() as u64,
+                GameMessage::PublicKey(*pk),
+            )
+            .unwrap();
+            let head = log.append_signed(signed).unwrap();
+            assert_eq!(log.head(), Ok(head));
+        }
+
+        (log, identities)
+    }
+
+    #[test]
+    fn test_event_log() {
+        let (log, identities) = key_exchange_log();
+        let public_identities = identities
+            .iter()
+            .map(|identity| identity.verifying_key())
+            .collect::<Vec<_>>();
+
+        assert_eq!(Ok(()), log.verify_chain());
+        assert_eq!(Ok(()), log.verify_signatures(&public_identities));
+
+        let mut bytes = Vec::new();
+        log.serialize(&mut bytes).unwrap();
+        let restored = EventLog::<Curve>::deserialize(&bytes[..]).unwrap();
+        assert_eq!(Ok(()), restored.verify_chain());
+        assert_eq!(restored.head(), log.head());
+        assert_eq!(restored.len(), 4);
+
+        // The same messages in a log for another game have a different head
+        let mut other = EventLog::new(b"another game");
+        for (sender, message) in log.messages() {
+            other.append(sender, message).unwrap();
+        }
+        assert_eq!(Ok(()), other.verify_chain());
+        assert_ne!(other.head(), log.head());
+        assert_ne!(
+            EventLog::<Curve>::new(b"another game").head(),
+            EventLog::<Curve>::new(SESSION_ID).head()
+        );
+
+        // Signed messages must take the slot of their position in the log
+        let signed = log.signed_message(3).unwrap();
+        assert_eq!(
+            other.append_signed(signed).err(),
+            Some(CardProtocolError::InvalidInput(
+                InputError::UnexpectedPosition {
+                    expected: 4,
+                    position: 3
+                }
+            ))
+        );
+    }
+
+    #[test]
+    fn test_tampered_event_log() {
+        let (log, identities) = key_exchange_log();
+
+        // An entry modified in the middle of the chain
+        let mut modified = log.clone();
+        modified.entries[1].message = log.entries[2].message.clone();
+        assert_eq!(
+            modified.verify_chain(),
+            Err(CardProtocolError::VerificationFailed(
+                VerificationError::BrokenChain(1)
+            ))
+        );
+
+        // Two entries swapped, together with their digests
+        let mut reordered = log.clone();
+        reordered.entries.swap(1, 2);
+        assert_eq!(
+            reordered.verify_chain(),
+            Err(CardProtocolError::VerificationFailed(
+                VerificationError::BrokenChain(1)
+            ))
+        );
+
+        // An entry dropped
+        let mut dropped = log.clone();
+        dropped.entries.remove(1);
+        assert_eq!(
+            dropped.verify_chain(),
+            Err(CardProtocolError::VerificationFailed(
+                VerificationError::BrokenChain(1)
+            ))
+        );
+
+        // A chain rebuilt over a modified message is consistent, but its head differs and the
+        // signature of the modified entry no longer verifies
+        let mut rebuilt = EventLog::new(SESSION_ID);
+        for (index, entry) in modified.entries.iter().enumerate() {
+            let mut signed = log.signed_message(index).unwrap();
+            signed.message = entry.message.clone();
+            rebuilt.append_signed(signed).unwrap();
+        }
+        assert_eq!(Ok(()), rebuilt.verify_chain());
+        assert_ne!(rebuilt.head(), log.head());
+        let public_identities = identities
+            .iter()
+            .map(|identity| identity.verifying_key())
+            .collect::<Vec<_>>();
+        assert!(matches!(
+            rebuilt.verify_signatures(&public_identities),
+            Err(CardProtocolError::VerificationFailed(
+                VerificationError::PlayerMessage {
+                    player: 1,
+                    source: Some(_)
+                }
+            ))
+        ));
+    }
+}
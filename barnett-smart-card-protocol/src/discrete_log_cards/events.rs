@@ -0,0 +1,492 @@
+//! A stream of what happened at a table, as far as it has been verified.
+//!
+//! [`VerifiedEvents`] reads the signed messages of a table from a [`SignedTransport`] and hands
+//! each to a [`Watchdog`], which checks its signature and its proofs against a session of its own.
+//! Every message the watchdog accepts becomes one or more [`VerifiedEvent`]s, so that a user
+//! interface can react to the game instead of polling its state. Copies of accepted messages and
+//! messages with an invalid signature produce no event.
+//!
+//! The stream is lazy: it reads and verifies the next message only when polled with no event left
+//! from the previous one, so a slow consumer holds back verification rather than a queue of
+//! verified events growing without bound. It ends once every card is open, after the first
+//! [`VerifiedEvent::MisbehaviorDetected`] or after the transport fails.
+
+use crate::discrete_log_cards::abort::{MisbehaviorReport, SignedMessage};
+use crate::discrete_log_cards::runner::PlayerId;
+use crate::discrete_log_cards::session::{GameMessage, SessionPhase};
+use crate::discrete_log_cards::watchdog::Watchdog;
+use crate::discrete_log_cards::Parameters;
+use crate::error::{CardProtocolError, InternalError};
+
+use ark_ec::ProjectiveCurve;
+use ark_ff::ToBytes;
+use ark_serialize::{CanonicalDeserialize, CanonicalSerialize};
+use async_trait::async_trait;
+use futures_util::stream::{self, Stream};
+use std::collections::VecDeque;
+use std::fmt;
+use std::pin::Pin;
+use std::sync::Arc;
+use std::task::{Context, Poll};
+use tokio::sync::mpsc::UnboundedReceiver;
+
+/// Source of the signed messages broadcast at a table, in the order they were broadcast.
+#[async_trait]
+pub trait SignedTransport<C: ProjectiveCurve>: Send {
+    /// Next message broadcast by any player.
+    async fn recv_signed(&mut self) -> Result<SignedMessage<C>, CardProtocolError>;
+}
+
+#[async_trait]
+impl<C: ProjectiveCurve> SignedTransport<C> for UnboundedReceiver<SignedMessage<C>> {
+    async fn recv_signed(&mut self) -> Result<SignedMessage<C>, CardProtocolError> {
+        self.recv().await.ok_or_else(|| {
+            CardProtocolError::Internal(InternalError::TransportError("channel closed".to_string()))
+        })
+    }
+}
+
+/// Something that happened at the table, reported once the watchdog has verified it.
+#[derive(Debug)]
+pub enum VerifiedEvent<C: ProjectiveCurve> {
+    /// The player in `seat` published their key
+    PlayerJoined { seat: PlayerId },
+    /// The proof that the player in `seat` knows the secret key of their key verified
+    KeyOwnershipVerified { seat: PlayerId },
+    /// The shuffle of the player in `seat` verified, and its output is the deck now
+    ShuffleVerified { seat: PlayerId },
+    /// The reveal tokens of the player in `seat` for the cards at these deck indices verified
+    RevealTokensVerified { seat: PlayerId, indices: Vec<usize> },
+    /// The card at deck index `index` opened to `card`, a position in the open cards of the
+    /// session. Every dealt card opens once the last of their owners has sent their tokens.
+    CardRevealed { index: usize, card: usize },
+    /// The player in `seat` sent an invalid message, with the evidence if the protocol has a
+    /// report for that kind of misbehavior. Nothing follows it.
+    MisbehaviorDetected {
+        seat: PlayerId,
+        report: Option<MisbehaviorReport<C>>,
+    },
+    /// The transport failed. Nothing follows it.
+    Failed(CardProtocolError),
+}
+
+/// Verified events of the table watched by a [`Watchdog`], see the module documentation.
+pub struct VerifiedEvents<C: ProjectiveCurve> {
+    inner: Pin<Box<dyn Stream<Item = VerifiedEvent<C>> + Send>>,
+}
+
+impl<C: ProjectiveCurve> fmt::Debug for VerifiedEvents<C> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("VerifiedEvents").finish_non_exhaustive()
+    }
+}
+
+impl<C: ProjectiveCurve> VerifiedEvents<C> {
+    /// Watch the messages delivered by `transport` with `watchdog`, which must not have observed
+    /// any message yet.
+    pub fn new<B, T>(parameters: Arc<Parameters<C>>, watchdog: Watchdog<C, B>, transport: T) -> Self
+    where
+        B: ToBytes + Clone + CanonicalSerialize + CanonicalDeserialize + Send + 'static,
+        T: SignedTransport<C> + 'static,
+    {
+        let state = EventSource {
+            parameters,
+            watchdog,
+            transport,
+            queue: VecDeque::new(),
+            finished: false,
+        };
+        let inner = stream::unfold(state, |mut state| async move {
+            let event = state.next_event().await?;
+            Some((event, state))
+        });
+
+        Self {
+            inner: Box::pin(inner),
+        }
+    }
+}
+
+impl<C: ProjectiveCurve> Stream for VerifiedEvents<C> {
+    type Item = VerifiedEvent<C>;
+
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        self.inner.as_mut().poll_next(cx)
+    }
+}
+
+struct EventSource<C: ProjectiveCurve, B: CanonicalSerialize + CanonicalDeserialize, T> {
+    parameters: Arc<Parameters<C>>,
+    watchdog: Watchdog<C, B>,
+    transport: T,
+    /// Events of the last accepted message not yet taken
+    queue: VecDeque<VerifiedEvent<C>>,
+    finished: bool,
+}
+
+impl<C, B, T> EventSource<C, B, T>
+where
+    C: ProjectiveCurve,
+    B: ToBytes + Clone + CanonicalSerialize + CanonicalDeserialize,
+    T: SignedTransport<C>,
+{
+    async fn next_event(&mut self) -> Option<VerifiedEvent<C>> {
+        loop {
+            if let Some(event) = self.queue.pop_front() {
+                return Some(event);
+            }
+
+            if self.finished || self.watchdog.session().phase() == SessionPhase::Done {
+                return None;
+            }
+
+            match self.transport.recv_signed().await {
+                Ok(message) => self.check(message),
+                Err(error) => {
+                    self.finished = true;
+                    return Some(VerifiedEvent::Failed(error));
+                }
+            }
+        }
+    }
+
+    /// Have the watchdog check `message` and queue the events it gives rise to.
+    fn check(&mut self, message: SignedMessage<C>) {
+        let session = self.watchdog.session();
+        let (sequence, phase) = (session.sequence(), session.phase());
+        let seat = message.sender;
+        let event = match &message.message {
+            GameMessage::PublicKey(_) => VerifiedEvent::PlayerJoined { seat },
+            GameMessage::KeyOwnership(_) => VerifiedEvent::KeyOwnershipVerified { seat },
+            GameMessage::Shuffle { .. } => VerifiedEvent::ShuffleVerified { seat },
+            GameMessage::RevealTokens(tokens) => VerifiedEvent::RevealTokensVerified {
+                seat,
+                indices: tokens.iter().map(|(index, _, _)| *index).collect(),
+            },
+        };
+
+        match self.watchdog.observe(&self.parameters, message) {
+            Ok(Some(violation)) => {
+                let event = VerifiedEvent::MisbehaviorDetected {
+                    seat: violation.player(),
+                    report: violation.report.clone(),
+                };
+                self.queue.push_back(event);
+                self.finished = true;
+                return;
+            }
+            // A message with an invalid signature cannot be held against anyone
+            Err(_) => return,
+            Ok(None) => {}
+        }
+
+        let session = self.watchdog.session();
+        if session.sequence() == sequence {
+            // A copy of a message accepted before
+            return;
+        }
+        self.queue.push_back(event);
+
+        if phase == SessionPhase::Reveal && session.phase() == SessionPhase::Done {
+            for player in 0..session.num_of_players() {
+                let cards = session.opened_hand(player).unwrap_or_default();
+                for (&index, card) in session.hand(player).unwrap_or_default().iter().zip(cards) {
+                    self.queue
+                        .push_back(VerifiedEvent::CardRevealed { index, card });
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{SignedTransport, VerifiedEvent, VerifiedEvents};
+    use crate::crypto_primitives::utils::rand::test_rng;
+    use crate::discrete_log_cards;
+    use crate::discrete_log_cards::abort::{MisbehaviorReport, SignedMessage};
+    use crate::discrete_log_cards::deal::DealPlan;
+    use crate::discrete_log_cards::encoding::encode_standard_deck;
+    use crate::discrete_log_cards::identity::IdentityKey;
+    use crate::discrete_log_cards::session::{GameMessage, Session, SessionPhase};
+    use crate::discrete_log_cards::test_helpers::setup_players;
+    use crate::discrete_log_cards::watchdog::Watchdog;
+    use crate::error::{CardProtocolError, InternalError};
+    use crate::BarnettSmartProtocol;
+
+    use async_trait::async_trait;
+    use futures_util::StreamExt;
+    use std::collections::VecDeque;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::Arc;
+
+    // Choose elliptic curve setting
+    type Curve = starknet_curve::Projective;
+
+    // Instantiate concrete type for our card protocol
+    type CardProtocol<'a> = discrete_log_cards::DLCards<'a, Curve>;
+
+    const SESSION_ID: &[u8] = b"events test";
+
+    /// Delivers a scripted game and counts the messages taken from it.
+    struct ScriptedTransport {
+        messages: VecDeque<SignedMessage<Curve>>,
+        delivered: Arc<AtomicUsize>,
+    }
+
+    #[async_trait]
+    impl SignedTransport<Curve> for ScriptedTransport {
+        async fn recv_signed(&mut self) -> Result<SignedMessage<Curve>, CardProtocolError> {
+            self.delivered.fetch_add(1, Ordering::SeqCst);
+            self.messages.pop_front().ok_or_else(|| {
+                CardProtocolError::Internal(InternalError::TransportError(
+                    "end of script".to_string(),
+                ))
+            })
+        }
+    }
+
+    /// Events without their reports, which cannot be compared.
+    fn summary(event: &VerifiedEvent<Curve>) -> (&'static str, Vec<usize>) {
+        match event {
+            VerifiedEvent::PlayerJoined { seat } => ("joined", vec![*seat]),
+            VerifiedEvent::KeyOwnershipVerified { seat } => ("key ownership", vec![*seat]),
+            VerifiedEvent::ShuffleVerified { seat } => ("shuffle", vec![*seat]),
+            VerifiedEvent::RevealTokensVerified { seat, indices } => {
+                ("tokens", [vec![*seat], indices.clone()].concat())
+            }
+            VerifiedEvent::CardRevealed { index, card } => ("card", vec![*index, *card]),
+            VerifiedEvent::MisbehaviorDetected { seat, .. } => ("misbehavior", vec![*seat]),
+            VerifiedEvent::Failed(_) => ("failed", vec![]),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_events_of_scripted_game() {
+        let rng = &mut test_rng();
+        let m = 4;
+        let n = 13;
+        let num_of_players = 3;
+
+        let parameters = Arc::new(CardProtocol::setup(rng, m, n).unwrap());
+        let (keys, _) = setup_players(rng, &parameters, num_of_players).unwrap();
+        let identity_keys = (0..num_of_players)
+            .map(|_| IdentityKey::new(rng).unwrap())
+            .collect::<Vec<_>>();
+        let identities = identity_keys
+            .iter()
+            .map(|identity| identity.verifying_key())
+            .collect::<Vec<_>>();
+        let plan = DealPlan::texas_holdem(num_of_players).unwrap();
+        let events = |messages: &[SignedMessage<Curve>], delivered: &Arc<AtomicUsize>| {
+            let watchdog = Watchdog::new(
+                &parameters,
+                SESSION_ID,
+                identities.clone(),
+                (0..num_of_players as u64).collect(),
+                encode_standard_deck(1).unwrap(),
+                &plan,
+            )
+            .unwrap();
+            let transport = ScriptedTransport {
+                messages: messages.iter().cloned().collect(),
+                delivered: delivered.clone(),
+            };
+
+            VerifiedEvents::new(parameters.clone(), watchdog, transport)
+        };
+
+        // The players run the game, and the events they expect are noted along the way
+        let mut session = Session::new(
+            &parameters,
+            SESSION_ID,
+            (0..num_of_players as u64).collect(),
+            encode_standard_deck(1).unwrap(),
+            plan.hands(),
+        )
+        .unwrap();
+        let mut script = Vec::new();
+        let mut expected = Vec::new();
+        let mut send = |session: &mut Session<Curve, u64>,
+                        player: usize,
+                        message: GameMessage<Curve>,
+                        event: (&'static str, Vec<usize>)| {
+            session
+                .receive(&parameters, player, message.clone())
+                .unwrap();
+            let slot = script.len() as u64;
+            let signed =
+                SignedMessage::sign(&identity_keys[player], SESSION_ID, player, slot, message)
+                    .unwrap();
+            script.push(signed);
+            expected.push(event);
+        };
+
+        for (player, (pk, _, _)) in keys.iter().enumerate() {
+            send(
+                &mut session,
+                player,
+                GameMessage::PublicKey(*pk),
+                ("joined", vec![player]),
+            );
+        }
+        let digest = session.key_list_digest().unwrap().unwrap();
+        for (player, (pk, sk, _)) in keys.iter().enumerate() {
+            let proof = CardProtocol::prove_key_ownership(
+                rng,
+                &parameters,
+                SESSION_ID,
+                pk,
+                sk,
+                &(player as u64),
+                player,
+                &digest,
+            )
+            .unwrap();
+            send(
+                &mut session,
+                player,
+                GameMessage::KeyOwnership(proof),
+                ("key ownership", vec![player]),
+            );
+        }
+        for player in 0..num_of_players {
+            let (deck, proof) = CardProtocol::shuffle_and_remask_with_rng(
+                rng,
+                &parameters,
+                SESSION_ID,
+                &session.aggregate_key().unwrap(),
+                &session.deck().to_vec(),
+            )
+            .unwrap();
+            send(
+                &mut session,
+                player,
+                GameMessage::Shuffle { deck, proof },
+                ("shuffle", vec![player]),
+            );
+        }
+        while session.phase() != SessionPhase::Done {
+            for player in 0..num_of_players {
+                let (pk, sk, _) = &keys[player];
+                let indices = session.due_reveals(player).unwrap();
+                if indices.is_empty() {
+                    continue;
+                }
+
+                let tokens = indices
+                    .iter()
+                    .map(|&index| {
+                        let (token, proof) = CardProtocol::compute_reveal_token(
+                            rng,
+                            &parameters,
+                            SESSION_ID,
+                            sk,
+                            pk,
+                            &session.deck()[index],
+                        )
+                        .unwrap();
+                        (index, token, proof)
+                    })
+                    .collect::<Vec<_>>();
+                send(
+                    &mut session,
+                    player,
+                    GameMessage::RevealTokens(tokens),
+                    ("tokens", [vec![player], indices].concat()),
+                );
+            }
+        }
+        for player in 0..num_of_players {
+            let cards = session.opened_hand(player).unwrap();
+            for (&index, card) in session.hand(player).unwrap().iter().zip(cards) {
+                expected.push(("card", vec![index, card]));
+            }
+        }
+
+        // Copies and forgeries in the stream produce no event
+        let mut delivered_script = Vec::new();
+        for message in &script {
+            delivered_script.push(message.clone());
+            delivered_script.push(message.clone());
+        }
+        let forged = SignedMessage::sign(
+            &identity_keys[1],
+            SESSION_ID,
+            2,
+            script.len() as u64,
+            GameMessage::PublicKey(keys[0].0),
+        )
+        .unwrap();
+        delivered_script.insert(1, forged);
+
+        let delivered = Arc::new(AtomicUsize::new(0));
+        let collected = events(&delivered_script, &delivered)
+            .map(|event| summary(&event))
+            .collect::<Vec<_>>()
+            .await;
+        assert_eq!(expected, collected);
+
+        // Nothing is verified ahead of the consumer: the first event takes one message, and the
+        // second a forgery and the first player's key again
+        let delivered = Arc::new(AtomicUsize::new(0));
+        let mut stream = events(&delivered_script, &delivered);
+        assert_eq!(expected[0], summary(&stream.next().await.unwrap()));
+        assert_eq!(1, delivered.load(Ordering::SeqCst));
+        assert_eq!(expected[1], summary(&stream.next().await.unwrap()));
+        assert_eq!(4, delivered.load(Ordering::SeqCst));
+
+        // The first player to deal swaps two of their reveal tokens
+        let position = script
+            .iter()
+            .position(|message| matches!(message.message, GameMessage::RevealTokens(_)))
+            .unwrap();
+        let original = &script[position];
+        let mut tokens = match &original.message {
+            GameMessage::RevealTokens(tokens) => tokens.clone(),
+            _ => unreachable!(),
+        };
+        let (first, second) = (tokens[0].clone(), tokens[1].clone());
+        tokens[0] = (first.0, second.1, second.2);
+        tokens[1] = (second.0, first.1, first.2);
+        let tampered = SignedMessage::sign(
+            &identity_keys[original.sender],
+            SESSION_ID,
+            original.sender,
+            original.slot,
+            GameMessage::RevealTokens(tokens),
+        )
+        .unwrap();
+
+        let mut tampered_script = script[..position].to_vec();
+        tampered_script.push(tampered);
+        tampered_script.extend_from_slice(&script[position + 1..]);
+
+        let delivered = Arc::new(AtomicUsize::new(0));
+        let collected = events(&tampered_script, &delivered)
+            .collect::<Vec<_>>()
+            .await;
+        assert_eq!(position + 1, collected.len());
+        assert_eq!(position + 1, delivered.load(Ordering::SeqCst));
+        match collected.last().unwrap() {
+            VerifiedEvent::MisbehaviorDetected {
+                seat,
+                report: Some(MisbehaviorReport::BadRevealToken { index, .. }),
+            } => {
+                assert_eq!(original.sender, *seat);
+                assert!(expected[position].1[1..3].contains(index));
+            }
+            event => panic!("unexpected event {:?}", summary(event)),
+        }
+
+        // A transport that runs dry before the end of the game ends the stream with a failure
+        let delivered = Arc::new(AtomicUsize::new(0));
+        let collected = events(&script[..4], &delivered)
+            .map(|event| summary(&event))
+            .collect::<Vec<_>>()
+            .await;
+        assert_eq!(&expected[..4], &collected[..4]);
+        assert_eq!(("failed", vec![]), collected[4]);
+        assert_eq!(5, collected.len());
+    }
+}
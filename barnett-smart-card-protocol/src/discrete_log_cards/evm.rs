@@ -0,0 +1,291 @@
+//! Encoding of reveal tokens and their proofs for verifiers built on the `alt_bn128` precompiles
+//! of the EVM (`ecAdd` at 0x06, `ecMul` at 0x07), over the G1 group of BN254.
+//!
+//! Every value is a 32-byte big-endian word, the format the precompiles take:
+//! - a point is two words, `x` then `y`, with the identity encoded as `(0, 0)`;
+//! - a scalar is one word, reduced modulo the group order.
+//!
+//! A reveal token with its proof is the 224-byte string
+//!
+//! | offset | content                                   |
+//! |--------|-------------------------------------------|
+//! | 0      | reveal token `t = c1^sk`                  |
+//! | 64     | commitment `a = g^w` of the proof         |
+//! | 128    | commitment `b = c1^w` of the proof        |
+//! | 192    | response `r` of the proof                 |
+//!
+//! where `c1` is the first component of the masked card and `g` the generator of the encryption
+//! parameters. The Fiat-Shamir challenge is not part of the encoding: the verifier recomputes it from
+//! the statement as `proof_essentials` does.
+
+use crate::discrete_log_cards::{MaskedCard, PublicKey, RevealToken};
+use crate::error::{CardProtocolError, InputError};
+
+use ark_bn254::{Fq, Fr, G1Affine, G1Projective};
+use ark_ec::AffineCurve;
+use ark_ff::{BigInteger, FromBytes, PrimeField, Zero};
+use ark_serialize::{CanonicalDeserialize, CanonicalSerialize};
+use proof_essentials::homomorphic_encryption::el_gamal;
+use proof_essentials::zkp::proofs::chaum_pedersen_dl_equality;
+
+/// Length in bytes of an encoded point.
+pub const POINT_LENGTH: usize = 64;
+
+/// Length in bytes of an encoded scalar.
+pub const SCALAR_LENGTH: usize = 32;
+
+/// Length in bytes of an encoded reveal token and its proof.
+pub const REVEAL_LENGTH: usize = 3 * POINT_LENGTH + SCALAR_LENGTH;
+
+pub type RevealProof = chaum_pedersen_dl_equality::proof::Proof<G1Projective>;
+
+/// Encode a point as `x || y`, with the identity as 64 zero bytes.
+pub fn encode_point(point: &G1Affine) -> [u8; POINT_LENGTH] {
+    let mut bytes = [0u8; POINT_LENGTH];
+    if !point.is_zero() {
+        bytes[..32].copy_from_slice(&point.x.into_repr().to_bytes_be());
+        bytes[32..].copy_from_slice(&point.y.into_repr().to_bytes_be());
+    }
+
+    bytes
+}
+
+pub fn encode_scalar(scalar: &Fr) -> [u8; SCALAR_LENGTH] {
+    let mut bytes = [0u8; SCALAR_LENGTH];
+    bytes.copy_from_slice(&scalar.into_repr().to_bytes_be());
+
+    bytes
+}
+
+/// Decode a point encoded by [`encode_point`], checking that it is on the curve.
+pub fn decode_point(bytes: &[u8]) -> Result<G1Affine, CardProtocolError> {
+    check_length(bytes, POINT_LENGTH)?;
+    let x = decode_word::<Fq>(&bytes[..32])?;
+    let y = decode_word::<Fq>(&bytes[32..])?;
+    if x.is_zero() && y.is_zero() {
+        return Ok(G1Affine::zero());
+    }
+
+    // The group of BN254 has prime order, so every point on the curve is in it
+    let point = G1Affine::new(x, y, false);
+    if !point.is_on_curve() || !point.is_in_correct_subgroup_assuming_on_curve() {
+        return Err(InputError::InvalidPoint.into());
+    }
+
+    Ok(point)
+}
+
+/// Decode a scalar encoded by [`encode_scalar`], which must be smaller than the group order.
+pub fn decode_scalar(bytes: &[u8]) -> Result<Fr, CardProtocolError> {
+    check_length(bytes, SCALAR_LENGTH)?;
+    decode_word(bytes)
+}
+
+fn check_length(bytes: &[u8], expected: usize) -> Result<(), CardProtocolError> {
+    if bytes.len() != expected {
+        return Err(InputError::EncodingLengthError {
+            expected,
+            length: bytes.len(),
+        }
+        .into());
+    }
+
+    Ok(())
+}
+
+/// A big-endian word as an element of `F`, rejecting values that are not reduced.
+fn decode_word<F: PrimeField>(word: &[u8]) -> Result<F, CardProtocolError> {
+    let mut little_endian = word.to_vec();
+    little_endian.reverse();
+
+    F::from_repr(F::BigInt::read(&little_endian[..])?).ok_or(CardProtocolError::InvalidInput(
+        InputError::NonCanonicalEncoding,
+    ))
+}
+
+/// Encode a public key for the verifier.
+pub fn encode_public_key(pk: &PublicKey<G1Projective>) -> [u8; POINT_LENGTH] {
+    encode_point(pk)
+}
+
+/// Encode the part of a masked card the reveal statement is about, its first component.
+pub fn encode_masked_card(masked_card: &MaskedCard<G1Projective>) -> [u8; POINT_LENGTH] {
+    encode_point(&masked_card.0)
+}
+
+/// Encode a reveal token and its proof in the layout described in the module documentation.
+pub fn encode_reveal(
+    token: &RevealToken<G1Projective>,
+    proof: &RevealProof,
+) -> Result<Vec<u8>, CardProtocolError> {
+    let (a, b, r) = proof_components(proof)?;
+
+    let mut bytes = Vec::with_capacity(REVEAL_LENGTH);
+    bytes.extend_from_slice(&encode_point(&token.0));
+    bytes.extend_from_slice(&encode_point(&a));
+    bytes.extend_from_slice(&encode_point(&b));
+    bytes.extend_from_slice(&encode_scalar(&r));
+
+    Ok(bytes)
+}
+
+/// Decode a reveal token and its proof encoded by [`encode_reveal`].
+pub fn decode_reveal(
+    bytes: &[u8],
+) -> Result<(RevealToken<G1Projective>, RevealProof), CardProtocolError> {
+    check_length(bytes, REVEAL_LENGTH)?;
+    let token = decode_point(&bytes[..64])?;
+    let a = decode_point(&bytes[64..128])?;
+    let b = decode_point(&bytes[128..192])?;
+    let r = decode_scalar(&bytes[192..])?;
+    let proof = proof_from_components(&a, &b, &r)?;
+
+    Ok((el_gamal::Plaintext(token), proof))
+}
+
+/// The proof keeps its components private; its canonical encoding is the commitments `a` and `b`
+/// followed by the response `r`.
+pub(crate) fn proof_components(
+    proof: &RevealProof,
+) -> Result<(G1Affine, G1Affine, Fr), CardProtocolError> {
+    let mut bytes = Vec::new();
+    proof.serialize(&mut bytes)?;
+
+    Ok(CanonicalDeserialize::deserialize(&bytes[..])?)
+}
+
+/// The proof with commitments `a` and `b` and response `r`, the inverse of `proof_components`.
+pub(crate) fn proof_from_components(
+    a: &G1Affine,
+    b: &G1Affine,
+    r: &Fr,
+) -> Result<RevealProof, CardProtocolError> {
+    let mut bytes = Vec::new();
+    a.serialize(&mut bytes)?;
+    b.serialize(&mut bytes)?;
+    r.serialize(&mut bytes)?;
+
+    Ok(CanonicalDeserialize::deserialize(&bytes[..])?)
+}
+
+#[cfg(test)]
+mod test {
+    use super::{
+        decode_point, decode_reveal, encode_point, encode_reveal, proof_components, REVEAL_LENGTH,
+    };
+    use crate::crypto_primitives::utils::rand::test_rng;
+    use crate::discrete_log_cards;
+    use crate::BarnettSmartProtocol;
+
+    use ark_bn254::{Fq, G1Affine};
+    use ark_ec::AffineCurve;
+    use ark_ff::{One, Zero};
+    use ark_std::rand::SeedableRng;
+    use ark_std::UniformRand;
+    use proof_essentials::homomorphic_encryption::el_gamal;
+    use rand_chacha::ChaCha20Rng;
+
+    // Choose elliptic curve setting
+    type Curve = ark_bn254::G1Projective;
+
+    // Instantiate concrete type for our card protocol
+    type CardProtocol<'a> = discrete_log_cards::DLCards<'a, Curve>;
+    type MaskedCard = discrete_log_cards::MaskedCard<Curve>;
+
+    const FIXTURES: &str = concat!(
+        env!("CARGO_MANIFEST_DIR"),
+        "/tests/vectors/bn254_reveal_proofs.txt"
+    );
+
+    /// Seed of `examples/bn254_fixtures.rs`, which draws the parameters first
+    const FIXTURE_SEED: [u8; 32] = *b"bn254 reveal proof fixtures v1..";
+
+    fn from_hex(hex: &str) -> Vec<u8> {
+        (0..hex.len())
+            .step_by(2)
+            .map(|i| u8::from_str_radix(&hex[i..i + 2], 16).unwrap())
+            .collect()
+    }
+
+    #[test]
+    fn test_point_encoding() {
+        // The generator of BN254 is (1, 2)
+        let mut expected = [0u8; 64];
+        expected[31] = 1;
+        expected[63] = 2;
+        assert_eq!(
+            encode_point(&G1Affine::prime_subgroup_generator()),
+            expected
+        );
+        assert_eq!(G1Affine::prime_subgroup_generator().x, Fq::one());
+
+        assert_eq!(encode_point(&G1Affine::zero()), [0u8; 64]);
+    }
+
+    #[test]
+    fn test_reveal_encoding() {
+        let rng = &mut test_rng();
+        let m = 4;
+        let n = 13;
+
+        let parameters = CardProtocol::setup(rng, m, n).unwrap();
+        let (pk, sk) = CardProtocol::player_keygen(rng, &parameters).unwrap();
+        let masked = MaskedCard::rand(rng);
+
+        let (token, proof) =
+            CardProtocol::compute_reveal_token(rng, &parameters, b"", &sk, &pk, &masked).unwrap();
+        assert_eq!(
+            Ok(()),
+            CardProtocol::verify_reveal(&parameters, b"", &pk, &token, &masked, &proof)
+        );
+
+        let bytes = encode_reveal(&token, &proof).unwrap();
+        assert_eq!(bytes.len(), REVEAL_LENGTH);
+        assert_eq!(&bytes[..64], &encode_point(&token.0)[..]);
+
+        let (a, b, _) = proof_components(&proof).unwrap();
+        assert_eq!(&bytes[64..128], &encode_point(&a)[..]);
+        assert_eq!(&bytes[128..192], &encode_point(&b)[..]);
+    }
+
+    #[test]
+    fn test_checked_in_fixtures() {
+        // Written by `cargo run --example bn254_fixtures --features bn254`
+        let fixtures = std::fs::read_to_string(FIXTURES).unwrap();
+        let rng = &mut ChaCha20Rng::from_seed(FIXTURE_SEED);
+        let parameters = CardProtocol::setup(rng, 4, 13).unwrap();
+
+        let mut lines = fixtures.lines().filter(|line| !line.starts_with('#'));
+        let generator = lines.next().unwrap().strip_prefix("generator ").unwrap();
+        assert_eq!(
+            from_hex(generator),
+            encode_point(&parameters.enc_parameters.generator)
+        );
+
+        let (mut valid, mut invalid) = (0, 0);
+        for line in lines {
+            let fields = line.split(' ').collect::<Vec<_>>();
+            let pk = decode_point(&from_hex(fields[1])).unwrap();
+            // The reveal statement is only about the first component of the masked card
+            let masked = el_gamal::Ciphertext(decode_point(&from_hex(fields[2])).unwrap(), pk);
+            let (token, proof) = decode_reveal(&from_hex(fields[3])).unwrap();
+            assert_eq!(encode_reveal(&token, &proof).unwrap(), from_hex(fields[3]));
+
+            let verified =
+                CardProtocol::verify_reveal(&parameters, b"", &pk, &token, &masked, &proof);
+            match fields[0] {
+                "valid" => {
+                    assert_eq!(Ok(()), verified);
+                    valid += 1;
+                }
+                "invalid" => {
+                    assert!(verified.is_err());
+                    invalid += 1;
+                }
+                label => panic!("unknown label {}", label),
+            }
+        }
+
+        assert!(valid > 0 && invalid > 0);
+    }
+}
@@ -0,0 +1,356 @@
+//! Everything a player needs to resume a game after a crash.
+//!
+//! A [`GameState`] holds the seat and public key of the player, the deal plan, and the public
+//! [`Session`] with the phase, the deck and the reveal tokens received so far. A snapshot encodes
+//! it canonically after a version header. The secret key is not part of it: keys derived from a
+//! `MasterKey` are derived again from the session context with [`GameState::secret_key`], which
+//! checks them against the public key in the snapshot.
+//!
+//! A snapshot may have been corrupted or tampered with on disk, so restoring it decodes every
+//! point with its checks and validates the invariants of the session and the plan. Shuffles and
+//! reveal tokens were verified before they were stored and are trusted.
+
+use crate::crypto_primitives::utils::format::Hex;
+use crate::discrete_log_cards::deal::DealPlan;
+use crate::discrete_log_cards::key_derivation::MasterKey;
+use crate::discrete_log_cards::session::{GameMessage, Session, Transition};
+use crate::discrete_log_cards::{Card, Parameters, PlayerSecretKey, PublicKey};
+use crate::error::{CardProtocolError, InputError};
+
+use ark_ec::ProjectiveCurve;
+use ark_ff::ToBytes;
+use ark_serialize::{CanonicalDeserialize, CanonicalSerialize};
+use ark_std::fmt;
+
+/// Version of the snapshot encoding, written before the state.
+pub const SNAPSHOT_VERSION: u32 = 1;
+
+pub struct GameState<C: ProjectiveCurve, B: CanonicalSerialize + CanonicalDeserialize> {
+    seat: usize,
+    public_key: PublicKey<C>,
+    plan: DealPlan,
+    session: Session<C, B>,
+}
+
+impl<C, B> fmt::Debug for GameState<C, B>
+where
+    C: ProjectiveCurve,
+    B: CanonicalSerialize + CanonicalDeserialize,
+{
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("GameState")
+            .field("seat", &self.seat)
+            .field("public_key", &Hex(&self.public_key))
+            .field("plan", &self.plan)
+            .field("session", &self.session)
+            .finish()
+    }
+}
+
+impl<C, B> GameState<C, B>
+where
+    C: ProjectiveCurve,
+    B: ToBytes + Clone + CanonicalSerialize + CanonicalDeserialize,
+{
+    /// State of the player in `seat`, with key `public_key`, at the start of a game dealt with
+    /// `plan`. The other arguments are those of `Session::new`.
+    pub fn new(
+        pp: &Parameters<C>,
+        context: &[u8],
+        seat: usize,
+        public_key: PublicKey<C>,
+        player_info: Vec<B>,
+        cards: Vec<Card<C>>,
+        plan: DealPlan,
+    ) -> Result<Self, CardProtocolError> {
+        if plan.num_players() != player_info.len() {
+            return Err(InputError::InvalidPlayerCount(player_info.len()).into());
+        }
+        plan.validate(cards.len())?;
+
+        let session = Session::new(pp, context, player_info, cards, plan.hands())?;
+        if seat >= session.num_of_players() {
+            return Err(InputError::UnknownPlayer(seat).into());
+        }
+
+        Ok(Self {
+            seat,
+            public_key,
+            plan,
+            session,
+        })
+    }
+
+    pub fn seat(&self) -> usize {
+        self.seat
+    }
+
+    pub fn public_key(&self) -> PublicKey<C> {
+        self.public_key
+    }
+
+    pub fn plan(&self) -> &DealPlan {
+        &self.plan
+    }
+
+    pub fn session(&self) -> &Session<C, B> {
+        &self.session
+    }
+
+    /// Verify and apply a message broadcast by `player`, as `Session::receive` does.
+    pub fn receive(
+        &mut self,
+        pp: &Parameters<C>,
+        player: usize,
+        message: GameMessage<C>,
+    ) -> Result<Transition, CardProtocolError> {
+        self.session.receive(pp, player, message)
+    }
+
+    /// Derive the secret key of the player again from their master key, and check it against
+    /// their public key.
+    pub fn secret_key(
+        &self,
+        pp: &Parameters<C>,
+        master_key: &MasterKey,
+    ) -> Result<PlayerSecretKey<C>, CardProtocolError> {
+        let (pk, sk) = master_key.derive_session_key(pp, self.session.context())?;
+        if pk != self.public_key {
+            return Err(InputError::InvalidSecretKey.into());
+        }
+
+        Ok(sk)
+    }
+
+    /// Encode the state to be stored, after the version header.
+    pub fn snapshot(&self) -> Result<Vec<u8>, CardProtocolError> {
+        let mut bytes = Vec::new();
+        SNAPSHOT_VERSION.serialize(&mut bytes)?;
+        (self.seat as u64).serialize(&mut bytes)?;
+        self.public_key.serialize(&mut bytes)?;
+        self.plan.serialize(&mut bytes)?;
+        self.session.serialize(&mut bytes)?;
+
+        Ok(bytes)
+    }
+
+    /// Decode a snapshot and check that it describes a consistent game played with `pp`.
+    pub fn restore(pp: &Parameters<C>, bytes: &[u8]) -> Result<Self, CardProtocolError> {
+        let mut reader = bytes;
+        let version = u32::deserialize(&mut reader)?;
+        if version != SNAPSHOT_VERSION {
+            return Err(InputError::UnsupportedSnapshotVersion(version).into());
+        }
+
+        let seat = u64::deserialize(&mut reader)? as usize;
+        let public_key = PublicKey::<C>::deserialize(&mut reader)?;
+        let plan = DealPlan::deserialize(&mut reader)?;
+        let session = Session::<C, B>::deserialize(&mut reader)?;
+        if !reader.is_empty() {
+            return Err(InputError::InvalidSnapshot.into());
+        }
+
+        session.validate(pp)?;
+        plan.validate(pp.num_cards())?;
+        if plan.num_players() != session.num_of_players() || seat >= session.num_of_players() {
+            return Err(InputError::InvalidSnapshot.into());
+        }
+        if (0..plan.num_players()).any(|player| session.hand(player) != Ok(plan.hole_cards(player)))
+        {
+            return Err(InputError::InvalidSnapshot.into());
+        }
+        if let Some(pk) = session.public_key(seat) {
+            if pk != public_key {
+                return Err(InputError::InvalidSnapshot.into());
+            }
+        }
+
+        Ok(Self {
+            seat,
+            public_key,
+            plan,
+            session,
+        })
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{GameState, SNAPSHOT_VERSION};
+    use crate::crypto_primitives::utils::rand::test_rng;
+    use crate::discrete_log_cards;
+    use crate::discrete_log_cards::deal::DealPlan;
+    use crate::discrete_log_cards::encoding::encode_standard_deck;
+    use crate::discrete_log_cards::key_derivation::MasterKey;
+    use crate::discrete_log_cards::session::{GameMessage, SessionPhase};
+    use crate::error::{Bound, CardProtocolError, Dimension, InputError};
+    use crate::BarnettSmartProtocol;
+
+    use ark_serialize::CanonicalSerialize;
+
+    // Choose elliptic curve setting
+    type Curve = starknet_curve::Projective;
+
+    // Instantiate concrete type for our card protocol
+    type CardProtocol<'a> = discrete_log_cards::DLCards<'a, Curve>;
+    type MaskedCard = discrete_log_cards::MaskedCard<Curve>;
+
+    const SESSION_ID: &[u8] = b"game state test";
+
+    #[test]
+    fn test_resume_after_crash() {
+        let rng = &mut test_rng();
+        let m = 4;
+        let n = 13;
+        let num_of_players = 3;
+
+        let parameters = CardProtocol::setup(rng, m, n).unwrap();
+        let master_keys = (0..num_of_players)
+            .map(|_| MasterKey::new(rng))
+            .collect::<Vec<_>>();
+        let keys = master_keys
+            .iter()
+            .map(|master_key| {
+                master_key
+                    .derive_session_key(&parameters, SESSION_ID)
+                    .unwrap()
+            })
+            .collect::<Vec<_>>();
+
+        let mut states = keys
+            .iter()
+            .enumerate()
+            .map(|(seat, (pk, _))| {
+                GameState::new(
+                    &parameters,
+                    SESSION_ID,
+                    seat,
+                    *pk,
+                    (0..num_of_players as u64).collect(),
+                    encode_standard_deck(1).unwrap(),
+                    DealPlan::texas_holdem(num_of_players).unwrap(),
+                )
+                .unwrap()
+            })
+            .collect::<Vec<_>>();
+        let broadcast =
+            |states: &mut [GameState<Curve, u64>], player: usize, message: GameMessage<Curve>| {
+                for state in states.iter_mut() {
+                    state.receive(&parameters, player, message.clone()).unwrap();
+                }
+            };
+
+        for (player, (pk, _)) in keys.iter().enumerate() {
+            broadcast(&mut states, player, GameMessage::PublicKey(*pk));
+        }
+        let digest = states[0].session().key_list_digest().unwrap().unwrap();
+        for (player, (pk, sk)) in keys.iter().enumerate() {
+            let proof = CardProtocol::prove_key_ownership(
+                rng,
+                &parameters,
+                SESSION_ID,
+                pk,
+                sk,
+                &(player as u64),
+                player,
+                &digest,
+            )
+            .unwrap();
+            broadcast(&mut states, player, GameMessage::KeyOwnership(proof));
+        }
+        for player in 0..num_of_players {
+            let session = states[player].session();
+            let (deck, proof) = CardProtocol::shuffle_and_remask_with_rng(
+                rng,
+                &parameters,
+                SESSION_ID,
+                &session.aggregate_key().unwrap(),
+                &session.deck().to_vec(),
+            )
+            .unwrap();
+            broadcast(&mut states, player, GameMessage::Shuffle { deck, proof });
+        }
+        assert_eq!(states[1].session().phase(), SessionPhase::Deal);
+
+        // Player 0 deals their tokens, then player 1 crashes and only its snapshot survives
+        let reveal_tokens = |player: usize, indices: Vec<usize>, deck: &[MaskedCard]| {
+            let (pk, sk) = &keys[player];
+            let tokens = indices
+                .into_iter()
+                .map(|index| {
+                    let (token, proof) = CardProtocol::compute_reveal_token(
+                        &mut test_rng(),
+                        &parameters,
+                        SESSION_ID,
+                        sk,
+                        pk,
+                        &deck[index],
+                    )
+                    .unwrap();
+                    (index, token, proof)
+                })
+                .collect();
+            GameMessage::RevealTokens(tokens)
+        };
+        let session = states[0].session();
+        let message = reveal_tokens(0, session.due_reveals(0).unwrap(), session.deck());
+        broadcast(&mut states, 0, message);
+
+        let snapshot = states[1].snapshot().unwrap();
+        drop(states.remove(1));
+        let restored = GameState::<Curve, u64>::restore(&parameters, &snapshot).unwrap();
+        assert_eq!(restored.seat(), 1);
+        assert_eq!(restored.snapshot().unwrap(), snapshot);
+        states.insert(1, restored);
+
+        // The restored player derives their key again and plays on to the end
+        let sk = states[1].secret_key(&parameters, &master_keys[1]).unwrap();
+        assert_eq!(
+            states[1].secret_key(&parameters, &master_keys[0]).err(),
+            Some(CardProtocolError::InvalidInput(
+                InputError::InvalidSecretKey
+            ))
+        );
+        for phase in [SessionPhase::Deal, SessionPhase::Reveal] {
+            for player in 0..num_of_players {
+                let session = states[player].session();
+                if session.phase() != phase || session.due_reveals(player).unwrap().is_empty() {
+                    continue;
+                }
+                let message =
+                    reveal_tokens(player, session.due_reveals(player).unwrap(), session.deck());
+                broadcast(&mut states, player, message);
+            }
+        }
+        assert_eq!(states[1].session().phase(), SessionPhase::Done);
+        assert_eq!(
+            states[1].session().open_own_hand(1, &sk).unwrap(),
+            states[0].session().opened_hand(1).unwrap()
+        );
+
+        // Snapshots of another version, or of a game with other parameters, are refused
+        let mut other_version = Vec::new();
+        (SNAPSHOT_VERSION + 1)
+            .serialize(&mut other_version)
+            .unwrap();
+        other_version.extend_from_slice(&snapshot[4..]);
+        assert_eq!(
+            GameState::<Curve, u64>::restore(&parameters, &other_version).err(),
+            Some(CardProtocolError::InvalidInput(
+                InputError::UnsupportedSnapshotVersion(SNAPSHOT_VERSION + 1)
+            ))
+        );
+
+        let other_parameters = CardProtocol::setup(rng, 2, 13).unwrap();
+        assert_eq!(
+            GameState::<Curve, u64>::restore(&other_parameters, &snapshot).err(),
+            Some(CardProtocolError::InvalidInput(
+                InputError::DimensionMismatch {
+                    dimension: Dimension::Deck,
+                    expected: Bound::Exactly(26),
+                    got: 52
+                }
+            ))
+        );
+    }
+}
@@ -0,0 +1,175 @@
+//! Long-term identities for players who use a fresh protocol key in every game.
+//!
+//! A player keeps one `IdentityKey` and generates a new protocol key pair per game. The identity
+//! key signs a certificate binding the game's public key to the session id and an expiry time, so
+//! tables authenticate players by identity while the protocol only ever sees ephemeral keys, and a
+//! compromised game key exposes no other game.
+
+use crate::crypto_primitives::signature::{Signature, SigningKey, VerifyingKey};
+use crate::crypto_primitives::utils::format::{Hex, HexBytes};
+use crate::discrete_log_cards::PublicKey;
+use crate::error::{CardProtocolError, VerificationError};
+
+use ark_ec::ProjectiveCurve;
+use ark_serialize::{CanonicalDeserialize, CanonicalSerialize};
+use ark_std::fmt;
+
+const SESSION_CERTIFICATE_DOMAIN: &'static [u8] = b"Session Key Certificate";
+
+/// Long-term signing key of a player.
+pub type IdentityKey<C> = SigningKey<C>;
+
+/// Public part of an `IdentityKey`, by which tables recognise a player.
+pub type PublicIdentity<C> = VerifyingKey<C>;
+
+/// Statement by the holder of an identity key that `session_key` is their key in the game
+/// `session_id`, valid until `expires_at` (in seconds since the Unix epoch).
+#[derive(Clone, PartialEq, Eq, CanonicalSerialize, CanonicalDeserialize)]
+pub struct SessionCertificate<C: ProjectiveCurve> {
+    pub session_key: PublicKey<C>,
+    pub session_id: Vec<u8>,
+    pub expires_at: u64,
+    pub signature: Signature<C>,
+}
+
+impl<C: ProjectiveCurve> fmt::Debug for SessionCertificate<C> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("SessionCertificate")
+            .field("session_key", &Hex(&self.session_key))
+            .field("session_id", &HexBytes(&self.session_id))
+            .field("expires_at", &self.expires_at)
+            .field("signature", &self.signature)
+            .finish()
+    }
+}
+
+/// Certify `session_key` as the key of the identity's owner in the game `session_id`.
+pub fn certify_session_key<C: ProjectiveCurve>(
+    identity: &IdentityKey<C>,
+    session_key: &PublicKey<C>,
+    session_id: &[u8],
+    expires_at: u64,
+) -> Result<SessionCertificate<C>, CardProtocolError> {
+    let msg = certificate_message::<C>(session_key, session_id, expires_at)?;
+
+    Ok(SessionCertificate {
+        session_key: *session_key,
+        session_id: session_id.to_vec(),
+        expires_at,
+        signature: identity.sign(&msg)?,
+    })
+}
+
+/// Check that `certificate` was issued by `identity` for the game `session_id` and has not expired
+/// at time `now`. Returns the certified session key.
+pub fn verify_session_certificate<C: ProjectiveCurve>(
+    identity: &PublicIdentity<C>,
+    session_id: &[u8],
+    now: u64,
+    certificate: &SessionCertificate<C>,
+) -> Result<PublicKey<C>, CardProtocolError> {
+    if certificate.session_id != session_id {
+        return Err(VerificationError::CertificateSessionMismatch.into());
+    }
+
+    if now >= certificate.expires_at {
+        return Err(VerificationError::CertificateExpired(certificate.expires_at).into());
+    }
+
+    let msg = certificate_message::<C>(
+        &certificate.session_key,
+        &certificate.session_id,
+        certificate.expires_at,
+    )?;
+    identity.verify(&msg, &certificate.signature)?;
+
+    Ok(certificate.session_key)
+}
+
+fn certificate_message<C: ProjectiveCurve>(
+    session_key: &PublicKey<C>,
+    session_id: &[u8],
+    expires_at: u64,
+) -> Result<Vec<u8>, CardProtocolError> {
+    let mut msg = SESSION_CERTIFICATE_DOMAIN.to_vec();
+    session_key.serialize(&mut msg)?;
+    msg.extend_from_slice(&(session_id.len() as u64).to_le_bytes());
+    msg.extend_from_slice(session_id);
+    msg.extend_from_slice(&expires_at.to_le_bytes());
+
+    Ok(msg)
+}
+
+#[cfg(test)]
+mod test {
+    use super::{certify_session_key, verify_session_certificate, IdentityKey};
+    use crate::crypto_primitives::utils::rand::test_rng;
+    use crate::discrete_log_cards;
+    use crate::error::{CardProtocolError, VerificationError};
+    use crate::BarnettSmartProtocol;
+
+    // Choose elliptic curve setting
+    type Curve = starknet_curve::Projective;
+
+    // Instantiate concrete type for our card protocol
+    type CardProtocol<'a> = discrete_log_cards::DLCards<'a, Curve>;
+
+    #[test]
+    fn test_session_certificate() {
+        let rng = &mut test_rng();
+        let m = 4;
+        let n = 13;
+
+        let parameters = CardProtocol::setup(rng, m, n).unwrap();
+
+        let identity = IdentityKey::<Curve>::new(rng).unwrap();
+        let public_identity = identity.verifying_key();
+        let (session_key, _) = CardProtocol::player_keygen(rng, &parameters).unwrap();
+
+        let certificate = certify_session_key(&identity, &session_key, b"game 1", 2000).unwrap();
+        assert_eq!(
+            Ok(session_key),
+            verify_session_certificate(&public_identity, b"game 1", 1000, &certificate)
+        );
+
+        assert_eq!(
+            verify_session_certificate(&public_identity, b"game 1", 2000, &certificate),
+            Err(CardProtocolError::VerificationFailed(
+                VerificationError::CertificateExpired(2000)
+            ))
+        );
+        assert_eq!(
+            verify_session_certificate(&public_identity, b"game 2", 1000, &certificate),
+            Err(CardProtocolError::VerificationFailed(
+                VerificationError::CertificateSessionMismatch
+            ))
+        );
+
+        // Moving the certificate to another session or extending it breaks the signature
+        let mut foreign = certificate.clone();
+        foreign.session_id = b"game 2".to_vec();
+        assert_eq!(
+            verify_session_certificate(&public_identity, b"game 2", 1000, &foreign),
+            Err(CardProtocolError::VerificationFailed(
+                VerificationError::InvalidSignature
+            ))
+        );
+
+        let mut extended = certificate.clone();
+        extended.expires_at = 3000;
+        assert_eq!(
+            verify_session_certificate(&public_identity, b"game 1", 2500, &extended),
+            Err(CardProtocolError::VerificationFailed(
+                VerificationError::InvalidSignature
+            ))
+        );
+
+        let other_identity = IdentityKey::<Curve>::new(rng).unwrap().verifying_key();
+        assert_eq!(
+            verify_session_certificate(&other_identity, b"game 1", 1000, &certificate),
+            Err(CardProtocolError::VerificationFailed(
+                VerificationError::InvalidSignature
+            ))
+        );
+    }
+}
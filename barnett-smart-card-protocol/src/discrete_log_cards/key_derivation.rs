@@ -0,0 +1,177 @@
+use crate::crypto_primitives::hash_to_field::hash_to_field;
+use crate::discrete_log_cards::{Parameters, PlayerSecretKey, PublicKey};
+use crate::error::{CardProtocolError, InputError};
+
+use ark_ec::{AffineCurve, ProjectiveCurve};
+use ark_ff::{PrimeField, Zero};
+use ark_std::fmt;
+use ark_std::rand::{CryptoRng, Rng};
+use zeroize::{Zeroize, ZeroizeOnDrop};
+
+const SESSION_KEY_DOMAIN: &'static [u8] = b"Session Key Derivation";
+
+/// Long-term secret from which a player derives a fresh key pair for every session. The master key
+/// itself is never used by the protocol: session keys are obtained by hashing it together with the
+/// session identifier, so leaking one session key reveals nothing about the master key or about the
+/// keys of other sessions. The seed is wiped when the master key is dropped.
+pub struct MasterKey {
+    seed: [u8; 32],
+}
+
+impl MasterKey {
+    pub fn new<R: Rng + CryptoRng>(rng: &mut R) -> Self {
+        let mut seed = [0u8; 32];
+        rng.fill_bytes(&mut seed);
+
+        Self { seed }
+    }
+
+    /// Restore a master key from its stored seed.
+    pub fn from_bytes(seed: [u8; 32]) -> Self {
+        Self { seed }
+    }
+
+    pub fn to_bytes(&self) -> [u8; 32] {
+        self.seed
+    }
+
+    /// Deterministically derive the key pair to use in the session identified by `session_id`.
+    pub fn derive_session_key<C: ProjectiveCurve>(
+        &self,
+        pp: &Parameters<C>,
+        session_id: &[u8],
+    ) -> Result<(PublicKey<C>, PlayerSecretKey<C>), CardProtocolError> {
+        // The seed has a fixed length, so the concatenation with the session id is unambiguous
+        let mut msg = [&self.seed[..], session_id].concat();
+        let sk = PlayerSecretKey::<C>::from(hash_to_field(SESSION_KEY_DOMAIN, &msg));
+        msg.zeroize();
+
+        if sk.expose_secret().is_zero() {
+            return Err(InputError::KeyDerivationError.into());
+        }
+
+        let pk = pp
+            .enc_parameters
+            .generator
+            .mul(sk.expose_secret().into_repr())
+            .into_affine();
+
+        Ok((pk, sk))
+    }
+}
+
+impl fmt::Debug for MasterKey {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("MasterKey(REDACTED)")
+    }
+}
+
+impl Zeroize for MasterKey {
+    fn zeroize(&mut self) {
+        self.seed.zeroize();
+    }
+}
+
+impl Drop for MasterKey {
+    fn drop(&mut self) {
+        self.zeroize();
+    }
+}
+
+impl ZeroizeOnDrop for MasterKey {}
+
+#[cfg(test)]
+mod test {
+    use crate::crypto_primitives::utils::rand::test_rng;
+    use crate::discrete_log_cards;
+    use crate::discrete_log_cards::key_derivation::MasterKey;
+    use crate::BarnettSmartProtocol;
+
+    use zeroize::{Zeroize, ZeroizeOnDrop};
+
+    // Choose elliptic curve setting
+    type Curve = starknet_curve::Projective;
+
+    // Instantiate concrete type for our card protocol
+    type CardProtocol<'a> = discrete_log_cards::DLCards<'a, Curve>;
+
+    #[test]
+    fn test_derive_session_key() {
+        let rng = &mut test_rng();
+        let m = 4;
+        let n = 13;
+
+        let parameters = CardProtocol::setup(rng, m, n).unwrap();
+
+        let master_key = MasterKey::new(rng);
+        assert_eq!("MasterKey(REDACTED)", format!("{:?}", master_key));
+
+        let (pk_a, sk_a) = master_key
+            .derive_session_key(&parameters, b"session A")
+            .unwrap();
+        let (pk_b, sk_b) = master_key
+            .derive_session_key(&parameters, b"session B")
+            .unwrap();
+
+        // Same session id gives the same keys, also after restoring the master key
+        let restored = MasterKey::from_bytes(master_key.to_bytes());
+        assert_eq!(
+            (pk_a, sk_a.clone()),
+            restored
+                .derive_session_key(&parameters, b"session A")
+                .unwrap()
+        );
+
+        // Different session ids give unrelated keys
+        assert_ne!(pk_a, pk_b);
+        assert_ne!(sk_a, sk_b);
+
+        // Different master keys give unrelated keys for the same session
+        let (pk_other, _) = MasterKey::new(rng)
+            .derive_session_key(&parameters, b"session A")
+            .unwrap();
+        assert_ne!(pk_a, pk_other);
+
+        // Derived keys are usable protocol keys
+        let player_info = b"Alice";
+        let key_list_digest = CardProtocol::key_list_digest(&[pk_b]).unwrap();
+        let proof = CardProtocol::prove_key_ownership(
+            rng,
+            &parameters,
+            b"",
+            &pk_b,
+            &sk_b,
+            &player_info,
+            0,
+            &key_list_digest,
+        )
+        .unwrap();
+        assert_eq!(
+            Ok(()),
+            CardProtocol::verify_key_ownership(
+                &parameters,
+                b"",
+                &pk_b,
+                &player_info,
+                0,
+                &key_list_digest,
+                &proof
+            )
+        );
+    }
+
+    #[test]
+    fn test_master_key_is_wiped() {
+        let rng = &mut test_rng();
+
+        let mut master_key = MasterKey::new(rng);
+        assert_ne!(master_key.to_bytes(), [0u8; 32]);
+
+        master_key.zeroize();
+        assert_eq!(master_key.seed, [0u8; 32]);
+
+        // Dropping the key runs the same zeroize
+        fn wiped_on_drop<T: ZeroizeOnDrop>() {}
+        wiped_on_drop::<MasterKey>();
+    }
+}
@@ -0,0 +1,303 @@
+//! Shamir sharing of player secret keys, for backup and escrow.
+//!
+//! Every share carries the threshold, the identifier of the key it belongs to and an integrity tag
+//! over its contents. The tag detects accidental corruption and mixing of shares from different
+//! keys; it is not a signature, so shares must still be stored and transported authentically.
+
+use crate::crypto_primitives::utils::ct::ct_eq_bytes;
+use crate::crypto_primitives::utils::format::HexBytes;
+use crate::crypto_primitives::utils::secret::{wipe, SecretScalars};
+use crate::error::{CardProtocolError, InputError, VerificationError};
+
+use ark_ff::{One, PrimeField, ToBytes, UniformRand, Zero};
+use ark_std::fmt;
+use ark_std::rand::{CryptoRng, Rng};
+use ark_std::vec::Vec;
+use blake2::{Blake2s, Digest};
+use zeroize::Zeroize;
+
+const KEY_ID_SEED: &'static [u8] = b"Key Share Id";
+const SHARE_TAG_SEED: &'static [u8] = b"Key Share Tag";
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub struct KeyShare<F: PrimeField> {
+    /// Evaluation point of the share, starting at 1
+    pub index: u64,
+    pub threshold: u64,
+    pub value: F,
+    pub key_id: [u8; 32],
+    pub tag: [u8; 32],
+}
+
+impl<F: PrimeField> KeyShare<F> {
+    fn compute_tag(
+        index: u64,
+        threshold: u64,
+        value: &F,
+        key_id: &[u8; 32],
+    ) -> Result<[u8; 32], CardProtocolError> {
+        let mut bytes = Vec::new();
+        value.write(&mut bytes)?;
+
+        let mut hasher = Blake2s::new();
+        hasher.update(SHARE_TAG_SEED);
+        hasher.update(key_id);
+        hasher.update(&index.to_le_bytes());
+        hasher.update(&threshold.to_le_bytes());
+        hasher.update(&bytes);
+
+        Ok(to_array(&hasher.finalize()))
+    }
+
+    /// Check the share's integrity tag.
+    pub fn is_intact(&self) -> Result<bool, CardProtocolError> {
+        let tag = Self::compute_tag(self.index, self.threshold, &self.value, &self.key_id)?;
+
+        Ok(ct_eq_bytes(&tag, &self.tag))
+    }
+}
+
+/// The value of the share is never formatted.
+impl<F: PrimeField> fmt::Debug for KeyShare<F> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("KeyShare")
+            .field("index", &self.index)
+            .field("threshold", &self.threshold)
+            .field("value", &format_args!("REDACTED"))
+            .field("key_id", &HexBytes(&self.key_id))
+            .field("tag", &HexBytes(&self.tag))
+            .finish()
+    }
+}
+
+impl<F: PrimeField> Zeroize for KeyShare<F> {
+    /// Shares are `Copy`, so they cannot wipe themselves on drop. Call this once a share is no
+    /// longer needed.
+    fn zeroize(&mut self) {
+        wipe(ark_std::slice::from_mut(&mut self.value));
+    }
+}
+
+/// Split a secret key into `n` shares such that any `t` of them recover it.
+pub trait KeySharing: Sized {
+    type Share;
+
+    fn split<R: Rng + CryptoRng>(
+        &self,
+        rng: &mut R,
+        t: usize,
+        n: usize,
+    ) -> Result<Vec<Self::Share>, CardProtocolError>;
+
+    fn reconstruct(shares: &[Self::Share]) -> Result<Self, CardProtocolError>;
+}
+
+impl<F: PrimeField> KeySharing for F {
+    type Share = KeyShare<F>;
+
+    fn split<R: Rng + CryptoRng>(
+        &self,
+        rng: &mut R,
+        t: usize,
+        n: usize,
+    ) -> Result<Vec<KeyShare<F>>, CardProtocolError> {
+        if t == 0 || t > n {
+            return Err(InputError::InvalidThreshold(t, n).into());
+        }
+
+        // Random polynomial of degree t - 1 whose constant term is the key
+        let mut coefficients = SecretScalars::new(Vec::with_capacity(t));
+        coefficients.push(*self);
+        for _ in 1..t {
+            coefficients.push(F::rand(rng));
+        }
+
+        let key_id = key_id(self)?;
+
+        (1..=n as u64)
+            .map(|index| {
+                let value = evaluate(&coefficients, F::from(index));
+                let tag = KeyShare::compute_tag(index, t as u64, &value, &key_id)?;
+
+                Ok(KeyShare {
+                    index,
+                    threshold: t as u64,
+                    value,
+                    key_id,
+                    tag,
+                })
+            })
+            .collect()
+    }
+
+    fn reconstruct(shares: &[KeyShare<F>]) -> Result<F, CardProtocolError> {
+        let first = shares.first().ok_or(CardProtocolError::InvalidInput(
+            InputError::NotEnoughShares(1, 0),
+        ))?;
+
+        for (i, share) in shares.iter().enumerate() {
+            if share.key_id != first.key_id || share.threshold != first.threshold {
+                return Err(InputError::MixedShares.into());
+            }
+            if !share.is_intact()? {
+                return Err(VerificationError::CorruptedShare(share.index).into());
+            }
+            if shares[..i].iter().any(|other| other.index == share.index) {
+                return Err(InputError::DuplicateShare(share.index).into());
+            }
+        }
+
+        let threshold = first.threshold as usize;
+        if shares.len() < threshold {
+            return Err(InputError::NotEnoughShares(threshold, shares.len()).into());
+        }
+
+        // Lagrange interpolation at zero over the first `threshold` shares
+        let shares = &shares[..threshold];
+        let mut secret = F::zero();
+        for share in shares {
+            let x_i = F::from(share.index);
+            let mut numerator = F::one();
+            let mut denominator = F::one();
+            for other in shares.iter().filter(|other| other.index != share.index) {
+                let x_j = F::from(other.index);
+                numerator *= x_j;
+                denominator *= x_j - x_i;
+            }
+            let coefficient = numerator
+                * denominator
+                    .inverse()
+                    .ok_or(CardProtocolError::VerificationFailed(
+                        VerificationError::CorruptedShare(share.index),
+                    ))?;
+
+            secret += coefficient * share.value;
+        }
+
+        // A share that is consistent with its tag but was crafted for another polynomial still
+        // leads to the wrong key, which the key identifier catches
+        if !ct_eq_bytes(&key_id(&secret)?, &first.key_id) {
+            return Err(InputError::MixedShares.into());
+        }
+
+        Ok(secret)
+    }
+}
+
+fn key_id<F: PrimeField>(key: &F) -> Result<[u8; 32], CardProtocolError> {
+    let mut bytes = Vec::new();
+    key.write(&mut bytes)?;
+
+    let mut hasher = Blake2s::new();
+    hasher.update(KEY_ID_SEED);
+    hasher.update(&bytes);
+
+    Ok(to_array(&hasher.finalize()))
+}
+
+fn evaluate<F: PrimeField>(coefficients: &[F], x: F) -> F {
+    coefficients
+        .iter()
+        .rev()
+        .fold(F::zero(), |acc, coefficient| acc * x + coefficient)
+}
+
+fn to_array(digest: &[u8]) -> [u8; 32] {
+    let mut array = [0u8; 32];
+    array.copy_from_slice(digest);
+    array
+}
+
+#[cfg(test)]
+mod test {
+    use crate::crypto_primitives::utils::rand::test_rng;
+    use crate::discrete_log_cards;
+    use crate::discrete_log_cards::key_sharing::KeySharing;
+    use crate::error::{CardProtocolError, InputError, VerificationError};
+    use crate::BarnettSmartProtocol;
+
+    use ark_ff::{UniformRand, Zero};
+    use zeroize::Zeroize;
+
+    // Choose elliptic curve setting
+    type Curve = starknet_curve::Projective;
+    type Scalar = starknet_curve::Fr;
+
+    // Instantiate concrete type for our card protocol
+    type CardProtocol<'a> = discrete_log_cards::DLCards<'a, Curve>;
+    type SecretKey = discrete_log_cards::PlayerSecretKey<Curve>;
+
+    #[test]
+    fn test_split_and_reconstruct() {
+        let rng = &mut test_rng();
+        let m = 4;
+        let n = 13;
+
+        let parameters = CardProtocol::setup(rng, m, n).unwrap();
+        let (_, sk) = CardProtocol::player_keygen(rng, &parameters).unwrap();
+
+        let shares = sk.split(rng, 3, 5).unwrap();
+        assert_eq!(shares.len(), 5);
+        assert!(format!("{:?}", shares[0]).contains("value: REDACTED"));
+
+        // Any t shares recover the key
+        assert_eq!(Ok(sk.clone()), SecretKey::reconstruct(&shares[..3]));
+        assert_eq!(Ok(sk.clone()), SecretKey::reconstruct(&shares[2..]));
+        assert_eq!(
+            Ok(sk),
+            SecretKey::reconstruct(&[shares[4], shares[0], shares[2]])
+        );
+
+        // t - 1 shares do not
+        assert_eq!(
+            SecretKey::reconstruct(&shares[..2]),
+            Err(CardProtocolError::InvalidInput(
+                InputError::NotEnoughShares(3, 2)
+            ))
+        );
+
+        // A repeated share does not count twice
+        assert_eq!(
+            SecretKey::reconstruct(&[shares[0], shares[1], shares[0]]),
+            Err(CardProtocolError::InvalidInput(InputError::DuplicateShare(
+                1
+            )))
+        );
+
+        let mut share = shares[0];
+        share.zeroize();
+        assert!(share.value.is_zero());
+    }
+
+    #[test]
+    fn test_bad_shares_are_detected() {
+        let rng = &mut test_rng();
+
+        let sk = Scalar::rand(rng);
+        let other_sk = Scalar::rand(rng);
+
+        let shares = sk.split(rng, 2, 3).unwrap();
+        let other_shares = other_sk.split(rng, 2, 3).unwrap();
+
+        let mut corrupted = shares[1];
+        corrupted.value = Scalar::rand(rng);
+        assert_eq!(
+            SecretKey::reconstruct(&[shares[0], corrupted]),
+            Err(CardProtocolError::VerificationFailed(
+                VerificationError::CorruptedShare(2)
+            ))
+        );
+
+        assert_eq!(
+            SecretKey::reconstruct(&[shares[0], other_shares[1]]),
+            Err(CardProtocolError::InvalidInput(InputError::MixedShares))
+        );
+
+        assert_eq!(
+            sk.split(rng, 4, 3),
+            Err(CardProtocolError::InvalidInput(
+                InputError::InvalidThreshold(4, 3)
+            ))
+        );
+    }
+}
@@ -0,0 +1,223 @@
+//! Lifted ("exponential") ElGamal mode for numeric card values.
+//!
+//! A value `v` is encoded as `g^v`, so multiplying masked cards adds the underlying values. The
+//! price is that unmasking yields `g^v` and the value has to be recovered by solving a small
+//! discrete logarithm, which [`ValueDecoder`] does with baby-step giant-step up to a fixed bound.
+
+use crate::discrete_log_cards::{Card, DLCards, MaskedCard, Parameters, PublicKey, RevealToken};
+use crate::error::{CardProtocolError, InputError};
+use crate::BarnettSmartProtocol;
+
+use ark_ec::{AffineCurve, ProjectiveCurve};
+use ark_std::rand::{CryptoRng, Rng};
+use ark_std::Zero;
+use proof_essentials::homomorphic_encryption::el_gamal;
+use proof_essentials::zkp::proofs::chaum_pedersen_dl_equality;
+use std::collections::HashMap;
+use std::fmt;
+
+/// Default upper bound on decodable values.
+pub const DEFAULT_MAX_VALUE: u64 = 1 << 20;
+
+/// Encode a value as `g^v`, where `g` is the generator of the encryption scheme.
+pub fn encode_value<C: ProjectiveCurve>(pp: &Parameters<C>, value: u64) -> Card<C> {
+    el_gamal::Plaintext(
+        pp.enc_parameters
+            .generator
+            .mul(C::ScalarField::from(value))
+            .into_affine(),
+    )
+}
+
+/// Mask the encoding of `value`. The proof is the usual proof of masking for the encoded card.
+pub fn mask_value<R: Rng + CryptoRng, C: ProjectiveCurve>(
+    rng: &mut R,
+    pp: &Parameters<C>,
+    context: &[u8],
+    shared_key: &PublicKey<C>,
+    value: u64,
+    r: &C::ScalarField,
+) -> Result<(MaskedCard<C>, chaum_pedersen_dl_equality::proof::Proof<C>), CardProtocolError> {
+    DLCards::<C>::mask(rng, pp, context, shared_key, &encode_value(pp, value), r)
+}
+
+/// Masked encoding of the sum of the values under `a` and `b`.
+pub fn add_masked<C: ProjectiveCurve>(a: &MaskedCard<C>, b: &MaskedCard<C>) -> MaskedCard<C> {
+    *a + *b
+}
+
+/// Unmask a masked value and decode it.
+pub fn unmask_value<C: ProjectiveCurve>(
+    pp: &Parameters<C>,
+    context: &[u8],
+    num_of_players: usize,
+    decryption_key: &Vec<(
+        RevealToken<C>,
+        chaum_pedersen_dl_equality::proof::Proof<C>,
+        PublicKey<C>,
+    )>,
+    masked: &MaskedCard<C>,
+    decoder: &ValueDecoder<C>,
+) -> Result<u64, CardProtocolError> {
+    let encoded = DLCards::<C>::unmask(pp, context, num_of_players, decryption_key, masked)?;
+
+    decoder.decode(&encoded)
+}
+
+/// Baby-step giant-step solver for discrete logarithms in `[0, max_value]`. Building the decoder
+/// costs about `sqrt(max_value)` group operations and entries, after which every decoding takes at
+/// most as many more.
+pub struct ValueDecoder<C: ProjectiveCurve> {
+    max_value: u64,
+    step: u64,
+    baby_steps: HashMap<C::Affine, u64>,
+    giant_step: C,
+}
+
+/// Only the range is printed, not the table of baby steps.
+impl<C: ProjectiveCurve> fmt::Debug for ValueDecoder<C> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("ValueDecoder")
+            .field("max_value", &self.max_value)
+            .field("step", &self.step)
+            .finish_non_exhaustive()
+    }
+}
+
+impl<C: ProjectiveCurve> ValueDecoder<C> {
+    pub fn new(pp: &Parameters<C>, max_value: u64) -> Self {
+        let step = ((max_value as f64).sqrt() as u64) + 1;
+        let generator = pp.enc_parameters.generator.into_projective();
+
+        let mut powers = Vec::with_capacity(step as usize);
+        let mut acc = C::zero();
+        for _ in 0..step {
+            powers.push(acc);
+            acc += generator;
+        }
+
+        let baby_steps = C::batch_normalization_into_affine(&powers)
+            .into_iter()
+            .zip(0..step)
+            .collect();
+
+        // `acc` is now g^step
+        Self {
+            max_value,
+            step,
+            baby_steps,
+            giant_step: -acc,
+        }
+    }
+
+    pub fn max_value(&self) -> u64 {
+        self.max_value
+    }
+
+    /// Recover `v` from `g^v`, failing if `v` exceeds the decoder's bound.
+    pub fn decode(&self, encoded: &Card<C>) -> Result<u64, CardProtocolError> {
+        let mut gamma = encoded.0.into_projective();
+
+        for i in 0..self.step {
+            if let Some(j) = self.baby_steps.get(&gamma.into_affine()) {
+                let value = i * self.step + j;
+                if value <= self.max_value {
+                    return Ok(value);
+                }
+                break;
+            }
+            gamma += self.giant_step;
+        }
+
+        Err(CardProtocolError::InvalidInput(
+            InputError::ValueOutOfRange(self.max_value),
+        ))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::crypto_primitives::utils::rand::test_rng;
+    use crate::discrete_log_cards;
+    use crate::discrete_log_cards::lifted::{
+        add_masked, encode_value, mask_value, unmask_value, ValueDecoder, DEFAULT_MAX_VALUE,
+    };
+    use crate::discrete_log_cards::test_helpers::{reveal_tokens, setup_players};
+    use crate::error::{CardProtocolError, InputError};
+    use crate::BarnettSmartProtocol;
+
+    use ark_ff::UniformRand;
+
+    // Choose elliptic curve setting
+    type Curve = starknet_curve::Projective;
+    type Scalar = starknet_curve::Fr;
+
+    // Instantiate concrete type for our card protocol
+    type CardProtocol<'a> = discrete_log_cards::DLCards<'a, Curve>;
+
+    #[test]
+    fn test_sum_of_masked_values() {
+        let rng = &mut test_rng();
+        let m = 4;
+        let n = 13;
+
+        let num_of_players = 3;
+
+        let parameters = CardProtocol::setup(rng, m, n).unwrap();
+
+        let (players, shared_key) = setup_players(rng, &parameters, num_of_players).unwrap();
+
+        let values = [3u64, 14, 25, 1000];
+        let mut sum = None;
+        for value in values.iter() {
+            let (masked, _) = mask_value(
+                rng,
+                &parameters,
+                b"",
+                &shared_key,
+                *value,
+                &Scalar::rand(rng),
+            )
+            .unwrap();
+            sum = Some(match sum {
+                None => masked,
+                Some(acc) => add_masked(&acc, &masked),
+            });
+        }
+        let sum = sum.unwrap();
+
+        let decryption_key = reveal_tokens(rng, &parameters, b"", &players, &sum).unwrap();
+
+        let decoder = ValueDecoder::new(&parameters, DEFAULT_MAX_VALUE);
+        assert_eq!(
+            Ok(1042),
+            unmask_value(
+                &parameters,
+                b"",
+                num_of_players,
+                &decryption_key,
+                &sum,
+                &decoder
+            )
+        );
+
+        // Values above the bound are a typed error rather than a wrong answer
+        let small_decoder = ValueDecoder::new(&parameters, 1000);
+        assert_eq!(
+            Ok(1000),
+            small_decoder.decode(&encode_value(&parameters, 1000))
+        );
+        assert_eq!(
+            small_decoder.decode(&encode_value(&parameters, 1001)),
+            Err(CardProtocolError::InvalidInput(
+                InputError::ValueOutOfRange(1000)
+            ))
+        );
+        assert_eq!(
+            small_decoder.decode(&encode_value(&parameters, 1 << 30)),
+            Err(CardProtocolError::InvalidInput(
+                InputError::ValueOutOfRange(1000)
+            ))
+        );
+    }
+}
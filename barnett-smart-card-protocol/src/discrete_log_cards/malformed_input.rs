@@ -0,0 +1,324 @@
+#[cfg(test)]
+mod test {
+    use crate::crypto_primitives::utils::rand::test_rng;
+    use crate::discrete_log_cards;
+    use crate::discrete_log_cards::deal::DealPlan;
+    use crate::discrete_log_cards::reveal::{reveal_cards, unmask_from_players, HoleCardCollector};
+    use crate::discrete_log_cards::test_helpers::{reveal_tokens, setup_players};
+    use crate::error::{
+        Bound, CardProtocolError, Dimension, InputError, ShuffleVerificationFailure,
+        VerificationError,
+    };
+    use crate::BarnettSmartProtocol;
+
+    use proof_essentials::utils::permutation::Permutation;
+    use proof_essentials::utils::rand::sample_vector;
+
+    // Choose elliptic curve setting
+    type Curve = starknet_curve::Projective;
+    type Scalar = starknet_curve::Fr;
+
+    // Instantiate concrete type for our card protocol
+    type CardProtocol<'a> = discrete_log_cards::DLCards<'a, Curve>;
+    type MaskedCard = discrete_log_cards::MaskedCard<Curve>;
+
+    #[test]
+    fn test_malformed_shuffle_inputs_are_rejected() {
+        let rng = &mut test_rng();
+        let m = 4;
+        let n = 13;
+
+        let parameters = CardProtocol::setup(rng, m, n).unwrap();
+        let (aggregate_key, _) = CardProtocol::player_keygen(rng, &parameters).unwrap();
+
+        // Every combination of a deck, masking factors and a permutation that are one too short,
+        // the right size, or one too long
+        for deck_size in [m * n - 1, m * n, m * n + 1] {
+            for num_of_factors in [deck_size - 1, deck_size, deck_size + 1] {
+                for permutation_size in [deck_size - 1, deck_size, deck_size + 1] {
+                    let deck: Vec<MaskedCard> = sample_vector(rng, deck_size);
+                    let masking_factors: Vec<Scalar> = sample_vector(rng, num_of_factors);
+                    let permutation = Permutation::new(rng, permutation_size);
+
+                    let expected = if deck_size != m * n {
+                        CardProtocolError::InvalidInput(InputError::DimensionMismatch {
+                            dimension: Dimension::Deck,
+                            expected: Bound::Exactly(m * n),
+                            got: deck_size,
+                        })
+                    } else if num_of_factors != deck_size {
+                        CardProtocolError::InvalidInput(InputError::DimensionMismatch {
+                            dimension: Dimension::MaskingFactors,
+                            expected: Bound::Exactly(deck_size),
+                            got: num_of_factors,
+                        })
+                    } else if permutation_size != deck_size {
+                        CardProtocolError::InvalidInput(InputError::DimensionMismatch {
+                            dimension: Dimension::Permutation,
+                            expected: Bound::Exactly(deck_size),
+                            got: permutation_size,
+                        })
+                    } else {
+                        continue;
+                    };
+
+                    assert_eq!(
+                        CardProtocol::shuffle_and_remask(
+                            rng,
+                            &parameters,
+                            b"",
+                            &aggregate_key,
+                            &deck,
+                            &masking_factors,
+                            &permutation,
+                        )
+                        .err(),
+                        Some(expected)
+                    );
+                }
+            }
+        }
+
+        // A permutation of the right size that sends two cards to the same position
+        let deck: Vec<MaskedCard> = sample_vector(rng, m * n);
+        let masking_factors: Vec<Scalar> = sample_vector(rng, m * n);
+        let mut mapping = (0..m * n).collect::<Vec<_>>();
+        mapping[1] = 0;
+        assert_eq!(
+            CardProtocol::shuffle_and_remask(
+                rng,
+                &parameters,
+                b"",
+                &aggregate_key,
+                &deck,
+                &masking_factors,
+                &Permutation::from(&mapping),
+            )
+            .err(),
+            Some(CardProtocolError::InvalidInput(
+                InputError::InvalidPermutation
+            ))
+        );
+    }
+
+    #[test]
+    fn test_malformed_shuffle_statements_are_rejected() {
+        let rng = &mut test_rng();
+        let m = 4;
+        let n = 13;
+
+        let parameters = CardProtocol::setup(rng, m, n).unwrap();
+        let (aggregate_key, _) = CardProtocol::player_keygen(rng, &parameters).unwrap();
+
+        let deck: Vec<MaskedCard> = sample_vector(rng, m * n);
+        let (shuffled_deck, proof) =
+            CardProtocol::shuffle_and_remask_with_rng(rng, &parameters, b"", &aggregate_key, &deck)
+                .unwrap();
+
+        let shorter = |deck: &Vec<MaskedCard>| deck[1..].to_vec();
+        let longer = |deck: &Vec<MaskedCard>| [&deck[..], &deck[..1]].concat();
+        let failure = |dimension, got| {
+            Err(ShuffleVerificationFailure::DeckSize {
+                dimension,
+                expected: m * n,
+                got,
+            })
+        };
+
+        for (original, shuffled, expected) in [
+            (
+                shorter(&deck),
+                shuffled_deck.clone(),
+                failure(Dimension::Deck, m * n - 1),
+            ),
+            (
+                longer(&deck),
+                shuffled_deck.clone(),
+                failure(Dimension::Deck, m * n + 1),
+            ),
+            (
+                deck.clone(),
+                shorter(&shuffled_deck),
+                failure(Dimension::ShuffledDeck, m * n - 1),
+            ),
+            (
+                deck.clone(),
+                longer(&shuffled_deck),
+                failure(Dimension::ShuffledDeck, m * n + 1),
+            ),
+            (
+                shorter(&deck),
+                shorter(&shuffled_deck),
+                failure(Dimension::Deck, m * n - 1),
+            ),
+        ] {
+            assert_eq!(
+                CardProtocol::verify_shuffle(
+                    &parameters,
+                    b"",
+                    &aggregate_key,
+                    &original,
+                    &shuffled,
+                    &proof
+                ),
+                expected
+            );
+        }
+
+        // Malformed decks are the fault of whoever sent them, not a proof that fails
+        assert_eq!(
+            CardProtocolError::from(failure(Dimension::Deck, 0).unwrap_err()),
+            CardProtocolError::InvalidInput(InputError::DimensionMismatch {
+                dimension: Dimension::Deck,
+                expected: Bound::Exactly(m * n),
+                got: 0
+            })
+        );
+    }
+
+    #[test]
+    fn test_malformed_reveal_inputs_are_rejected() {
+        let rng = &mut test_rng();
+        let m = 4;
+        let n = 13;
+        let num_of_players = 3;
+
+        let parameters = CardProtocol::setup(rng, m, n).unwrap();
+        let (players, _) = setup_players(rng, &parameters, num_of_players).unwrap();
+        let keys = players.iter().map(|(pk, _, _)| *pk).collect::<Vec<_>>();
+        let deck: Vec<MaskedCard> = sample_vector(rng, m * n);
+
+        let decryption_key = reveal_tokens(rng, &parameters, b"", &players, &deck[0]).unwrap();
+        assert_eq!(
+            unmask_from_players(&parameters, b"", &keys, &decryption_key, &deck[0]),
+            CardProtocol::unmask(&parameters, b"", num_of_players, &decryption_key, &deck[0])
+        );
+
+        // One token too few or too many for the table
+        let mismatch = |expected, got| {
+            Err(CardProtocolError::InvalidInput(
+                InputError::DimensionMismatch {
+                    dimension: Dimension::RevealTokens,
+                    expected: Bound::Exactly(expected),
+                    got,
+                },
+            ))
+        };
+        assert_eq!(
+            unmask_from_players(
+                &parameters,
+                b"",
+                &keys,
+                &decryption_key[1..].to_vec(),
+                &deck[0]
+            ),
+            mismatch(num_of_players, num_of_players - 1)
+        );
+        assert_eq!(
+            unmask_from_players(&parameters, b"", &keys[1..], &decryption_key, &deck[0]),
+            mismatch(num_of_players - 1, num_of_players)
+        );
+        for table_size in [num_of_players - 1, num_of_players + 1] {
+            assert_eq!(
+                CardProtocol::unmask(&parameters, b"", table_size, &decryption_key, &deck[0]),
+                mismatch(table_size, num_of_players)
+            );
+        }
+
+        // Tokens of the right players, in the wrong seats
+        let mut swapped = decryption_key.clone();
+        swapped.swap(0, 1);
+        assert_eq!(
+            unmask_from_players(&parameters, b"", &keys, &swapped, &deck[0]),
+            Err(CardProtocolError::VerificationFailed(
+                VerificationError::PlayerMessage {
+                    player: 0,
+                    source: None
+                }
+            ))
+        );
+
+        // No token at all would leave the card open to its masked value
+        assert_eq!(
+            CardProtocol::unmask(&parameters, b"", num_of_players, &Vec::new(), &deck[0]),
+            Err(CardProtocolError::InvalidInput(
+                InputError::NotEnoughPlayers
+            ))
+        );
+
+        // Decks and tables one card or one player away from the parameters and the plan
+        let plan = DealPlan::new(num_of_players, 2, &[], 0).unwrap();
+        let (pk, sk, _) = &players[0];
+        for size in [m * n - 1, m * n + 1] {
+            let deck: Vec<MaskedCard> = sample_vector(rng, size);
+            assert_eq!(
+                reveal_cards(rng, &parameters, b"", &plan, &[0], sk, pk, &deck).err(),
+                Some(CardProtocolError::InvalidInput(
+                    InputError::DimensionMismatch {
+                        dimension: Dimension::Deck,
+                        expected: Bound::Exactly(m * n),
+                        got: size
+                    }
+                ))
+            );
+        }
+        for num_of_keys in [num_of_players - 1, num_of_players + 1] {
+            let keys = (0..num_of_keys)
+                .map(|i| players[i % num_of_players].0)
+                .collect();
+            assert_eq!(
+                HoleCardCollector::new(b"", &plan, 0, keys, deck.clone()).err(),
+                Some(CardProtocolError::InvalidInput(
+                    InputError::DimensionMismatch {
+                        dimension: Dimension::PublicKeys,
+                        expected: Bound::Exactly(num_of_players),
+                        got: num_of_keys
+                    }
+                ))
+            );
+        }
+    }
+
+    #[test]
+    fn test_malformed_input_and_failed_verification_are_told_apart() {
+        let rng = &mut test_rng();
+        let m = 4;
+        let n = 13;
+
+        let parameters = CardProtocol::setup(rng, m, n).unwrap();
+        let (pk, sk) = CardProtocol::player_keygen(rng, &parameters).unwrap();
+        let deck: Vec<MaskedCard> = sample_vector(rng, m * n);
+
+        // Passing one masking factor too few is a mistake of the caller
+        let error = CardProtocol::shuffle_and_remask(
+            rng,
+            &parameters,
+            b"",
+            &pk,
+            &deck,
+            &sample_vector(rng, m * n - 1),
+            &Permutation::new(rng, m * n),
+        )
+        .unwrap_err();
+        assert!(matches!(error, CardProtocolError::InvalidInput(_)));
+
+        // A well-formed Chaum-Pedersen proof for another card does not verify
+        let (token, proof) =
+            CardProtocol::compute_reveal_token(rng, &parameters, b"", &sk, &pk, &deck[0]).unwrap();
+        let error = CardProtocolError::from(
+            CardProtocol::verify_reveal(&parameters, b"", &pk, &token, &deck[1], &proof)
+                .unwrap_err(),
+        );
+        assert!(matches!(error, CardProtocolError::VerificationFailed(_)));
+
+        let error = CardProtocol::unmask(&parameters, b"", 1, &vec![(token, proof, pk)], &deck[1])
+            .unwrap_err();
+        assert!(matches!(
+            error,
+            CardProtocolError::VerificationFailed(VerificationError::InvalidRevealToken {
+                index: 0,
+                ..
+            })
+        ));
+    }
+}
@@ -21,14 +21,19 @@ impl<C: ProjectiveCurve> Mask<C::ScalarField, ElGamal<C>> for Card<C> {
 
 #[cfg(test)]
 mod test {
+    use crate::crypto_primitives::utils::rand::test_rng;
     use crate::discrete_log_cards;
+    use crate::discrete_log_cards::strategies;
+    use crate::discrete_log_cards::test_helpers::setup_players;
     use crate::BarnettSmartProtocol;
 
     use ark_ff::UniformRand;
-    use ark_std::{rand::Rng, Zero};
+    use ark_std::Zero;
     use proof_essentials::error::CryptoError;
     use proof_essentials::zkp::proofs::chaum_pedersen_dl_equality;
-    use rand::thread_rng;
+    use proptest::prelude::*;
+    use proptest::test_runner::TestRunner;
+    use std::sync::Arc;
 
     // Choose elliptic curve setting
     type Curve = starknet_curve::Projective;
@@ -36,34 +41,16 @@ mod test {
 
     // Instantiate concrete type for our card protocol
     type CardProtocol<'a> = discrete_log_cards::DLCards<'a, Curve>;
-    type CardParameters = discrete_log_cards::Parameters<Curve>;
     type PublicKey = discrete_log_cards::PublicKey<Curve>;
-    type SecretKey = discrete_log_cards::PlayerSecretKey<Curve>;
 
     type Card = discrete_log_cards::Card<Curve>;
     type MaskedCard = discrete_log_cards::MaskedCard<Curve>;
 
     type MaskingProof = chaum_pedersen_dl_equality::proof::Proof<Curve>;
 
-    fn setup_players<R: Rng>(
-        rng: &mut R,
-        parameters: &CardParameters,
-        num_of_players: usize,
-    ) -> (Vec<(PublicKey, SecretKey)>, PublicKey) {
-        let mut players: Vec<(PublicKey, SecretKey)> = Vec::with_capacity(num_of_players);
-        let mut expected_shared_key = PublicKey::zero();
-
-        for i in 0..parameters.n {
-            players.push(CardProtocol::player_keygen(rng, &parameters).unwrap());
-            expected_shared_key = expected_shared_key + players[i].0
-        }
-
-        (players, expected_shared_key)
-    }
-
     #[test]
     fn test_verify_masking() {
-        let rng = &mut thread_rng();
+        let rng = &mut test_rng();
         let m = 4;
         let n = 13;
 
@@ -71,18 +58,26 @@ mod test {
 
         let parameters = CardProtocol::setup(rng, m, n).unwrap();
 
-        let (_, aggregate_key) = setup_players(rng, &parameters, num_of_players);
+        let (_, aggregate_key) = setup_players(rng, &parameters, num_of_players).unwrap();
 
         let some_card = Card::rand(rng);
         let some_random = Scalar::rand(rng);
 
-        let (masked, masking_proof): (MaskedCard, MaskingProof) =
-            CardProtocol::mask(rng, &parameters, &aggregate_key, &some_card, &some_random).unwrap();
+        let (masked, masking_proof): (MaskedCard, MaskingProof) = CardProtocol::mask(
+            rng,
+            &parameters,
+            b"",
+            &aggregate_key,
+            &some_card,
+            &some_random,
+        )
+        .unwrap();
 
         assert_eq!(
             Ok(()),
             CardProtocol::verify_mask(
                 &parameters,
+                b"",
                 &aggregate_key,
                 &some_card,
                 &masked,
@@ -95,6 +90,7 @@ mod test {
         assert_eq!(
             CardProtocol::verify_mask(
                 &parameters,
+                b"",
                 &aggregate_key,
                 &some_card,
                 &wrong_masked,
@@ -105,4 +101,59 @@ mod test {
             )))
         )
     }
+
+    #[test]
+    fn test_mask_unmask_round_trip() {
+        let rng = &mut test_rng();
+        let m = 2;
+        let n = 2;
+
+        let parameters = Arc::new(CardProtocol::setup(rng, m, n).unwrap());
+        let strategy = (
+            strategies::players(parameters.clone(), Vec::new(), 1..6),
+            strategies::card::<Curve>(),
+            strategies::scalar::<Scalar>(),
+        );
+
+        let mut runner = TestRunner::new(ProptestConfig::with_cases(16));
+        runner
+            .run(&strategy, |(players, card, alpha)| {
+                let aggregate_key = players
+                    .iter()
+                    .fold(PublicKey::zero(), |acc, player| acc + player.pk);
+                let (masked, _) = CardProtocol::mask(
+                    &mut test_rng(),
+                    &parameters,
+                    b"",
+                    &aggregate_key,
+                    &card,
+                    &alpha,
+                )
+                .unwrap();
+
+                let decryption_key = players
+                    .iter()
+                    .map(|player| {
+                        let (token, proof) = CardProtocol::compute_reveal_token(
+                            &mut test_rng(),
+                            &parameters,
+                            b"",
+                            &player.sk,
+                            &player.pk,
+                            &masked,
+                        )
+                        .unwrap();
+                        (token, proof, player.pk)
+                    })
+                    .collect::<Vec<_>>();
+
+                prop_assert_eq!(
+                    Ok(card),
+                    CardProtocol::unmask(&parameters, b"", players.len(), &decryption_key, &masked)
+                );
+
+                Ok(())
+            })
+            .unwrap();
+    }
 }
@@ -1,13 +1,29 @@
-use super::BarnettSmartProtocol;
+use super::{BarnettSmartProtocol, KeyListDigest};
 use super::{Mask, Remask, Reveal};
 
-use crate::error::CardProtocolError;
+use crate::crypto_primitives::commitment::HomomorphicCommitment;
+use crate::crypto_primitives::hash_to_curve::hash_to_curve;
+use crate::crypto_primitives::permutation::check_permutation;
+use crate::crypto_primitives::utils::format::Hex;
+use crate::crypto_primitives::utils::rand::hedged_rng;
+use crate::discrete_log_cards::encoding::{DeckSpec, EncodedDeck};
+use crate::discrete_log_cards::validation::MAX_VECTOR_LENGTH;
+use crate::error::{
+    check_dimension, Bound, CardProtocolError, Dimension, InputError, Operation, OperationError,
+    ShuffleVerificationFailure, VerificationError,
+};
+use crate::telemetry::{record, rejected, span};
 
-use anyhow::Result;
 use ark_ec::{AffineCurve, ProjectiveCurve};
-use ark_ff::{to_bytes, One, PrimeField, ToBytes};
+use ark_ff::{to_bytes, One, ToBytes};
 use ark_marlin::rng::FiatShamirRng;
-use ark_std::rand::Rng;
+use ark_serialize::{CanonicalDeserialize, CanonicalSerialize, Read, SerializationError, Write};
+use ark_std::collections::BTreeSet;
+use ark_std::fmt;
+use ark_std::marker::PhantomData;
+use ark_std::rand::{CryptoRng, Rng};
+use ark_std::slice;
+use ark_std::vec::Vec;
 use ark_std::Zero;
 use blake2::Blake2s;
 use proof_essentials::error::CryptoError;
@@ -16,38 +32,108 @@ use proof_essentials::homomorphic_encryption::{
 };
 use proof_essentials::utils::permutation::Permutation;
 use proof_essentials::vector_commitment::pedersen::PedersenCommitment;
-use proof_essentials::vector_commitment::{pedersen, HomomorphicCommitmentScheme};
+use proof_essentials::vector_commitment::HomomorphicCommitmentScheme;
 use proof_essentials::zkp::{
     arguments::shuffle,
     proofs::{chaum_pedersen_dl_equality, schnorr_identification},
     ArgumentOfKnowledge,
 };
-use std::marker::PhantomData;
 
 // mod key_ownership;
+#[cfg(feature = "std")]
+pub mod abort;
+#[cfg(all(feature = "std", any(test, feature = "test-utils")))]
+pub mod adversary;
+#[cfg(feature = "std")]
+pub mod blame;
+mod curves;
+pub mod deal;
+#[cfg(feature = "std")]
+pub mod dkg;
+pub mod encoding;
+#[cfg(feature = "std")]
+pub mod event_log;
+#[cfg(feature = "events")]
+pub mod events;
+#[cfg(feature = "bn254")]
+pub mod evm;
+#[cfg(feature = "std")]
+pub mod game_state;
+#[cfg(feature = "std")]
+pub mod identity;
+#[cfg(feature = "std")]
+pub mod key_derivation;
+pub mod key_sharing;
+#[cfg(feature = "std")]
+pub mod lifted;
+mod malformed_input;
 mod masking;
+#[cfg(feature = "p2p")]
+pub mod p2p;
+#[cfg(feature = "std")]
+pub mod recovery;
+#[cfg(feature = "relay-client")]
+pub mod relay;
 mod remasking;
-mod reveal;
+pub mod reveal;
+#[cfg(feature = "runner")]
+pub mod runner;
+mod secret_key;
+#[cfg(any(feature = "p2p", feature = "relay-client"))]
+mod sender_logs;
+#[cfg(feature = "std")]
+pub mod session;
+#[cfg(feature = "std")]
+pub mod session_id;
+#[cfg(feature = "std")]
+pub mod simulation;
+#[cfg(any(test, feature = "proptest"))]
+pub mod strategies;
+#[cfg(feature = "std")]
+pub mod table_session;
+#[cfg(all(feature = "std", any(test, feature = "test-helpers")))]
+pub mod test_helpers;
 mod tests;
-
-pub struct DLCards<'a, C: ProjectiveCurve> {
+#[cfg(feature = "std")]
+pub mod timeout;
+#[cfg(feature = "std")]
+pub mod transcript;
+pub mod validation;
+#[cfg(feature = "std")]
+pub mod watchdog;
+
+pub use secret_key::SecretKey;
+
+/// Discrete log instantiation of the protocol. The vector commitment used by the shuffle argument
+/// defaults to Pedersen but can be replaced by any `HomomorphicCommitment`.
+pub struct DLCards<'a, C: ProjectiveCurve, Comm = PedersenCommitment<C>> {
     _group: &'a PhantomData<C>,
+    _commitment: PhantomData<Comm>,
+}
+
+impl<C: ProjectiveCurve, Comm> fmt::Debug for DLCards<'_, C, Comm> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("DLCards")
+    }
 }
 
-pub struct Parameters<C: ProjectiveCurve> {
+pub struct Parameters<
+    C: ProjectiveCurve,
+    Comm: HomomorphicCommitmentScheme<C::ScalarField> = PedersenCommitment<C>,
+> {
     m: usize,
     n: usize,
     enc_parameters: el_gamal::Parameters<C>,
-    commit_parameters: pedersen::CommitKey<C>,
+    commit_parameters: Comm::CommitKey,
     generator: el_gamal::Generator<C>,
 }
 
-impl<C: ProjectiveCurve> Parameters<C> {
+impl<C: ProjectiveCurve, Comm: HomomorphicCommitmentScheme<C::ScalarField>> Parameters<C, Comm> {
     pub fn new(
         m: usize,
         n: usize,
         enc_parameters: el_gamal::Parameters<C>,
-        commit_parameters: pedersen::CommitKey<C>,
+        commit_parameters: Comm::CommitKey,
         generator: el_gamal::Generator<C>,
     ) -> Self {
         Self {
@@ -58,11 +144,273 @@ impl<C: ProjectiveCurve> Parameters<C> {
             generator,
         }
     }
+
+    /// Number of cards in the deck, `m * n`. Decoded parameters may claim dimensions whose
+    /// product does not fit, which saturates rather than overflows.
+    pub fn num_cards(&self) -> usize {
+        self.m.saturating_mul(self.n)
+    }
+
+    /// Generator of the encryption group, the base of public keys and of the first component of
+    /// masked cards.
+    pub fn encryption_generator(&self) -> C::Affine {
+        self.enc_parameters.generator
+    }
+}
+
+impl<C, Comm> Parameters<C, Comm>
+where
+    C: ProjectiveCurve,
+    Comm: HomomorphicCommitment<C::ScalarField>,
+    Comm::CommitKey: CanonicalSerialize,
+{
+    /// Derive the parameters for an `m x n` deck. The generator of the encryption scheme, the
+    /// generators of the commitment key and the generator of the shuffle argument are all hashed
+    /// to the curve from public labels, so nobody knows a discrete log relation between them.
+    pub fn derive(m: usize, n: usize) -> Result<Self, CardProtocolError> {
+        check_shape(m, n)?;
+
+        let enc_parameters = el_gamal::Parameters {
+            generator: hash_to_curve::<C>(PARAMETERS_DOMAIN, ENC_GENERATOR_LABEL)?,
+        };
+        let commit_parameters = Comm::derive_commit_key(&commit_key_label(m, n), n)?;
+        let generator = el_gamal::Generator(hash_to_curve::<C>(
+            PARAMETERS_DOMAIN,
+            SHUFFLE_GENERATOR_LABEL,
+        )?);
+
+        Ok(Self::new(
+            m,
+            n,
+            enc_parameters,
+            commit_parameters,
+            generator,
+        ))
+    }
+
+    /// Check that the parameters are the ones `derive` produces for these dimensions: the
+    /// encryption generator, the commitment key and the shuffle generator alike. If they are,
+    /// nobody knows a relation between the generators and the proofs are sound regardless of who
+    /// produced the parameters.
+    pub fn is_nothing_up_my_sleeve(&self) -> Result<bool, CardProtocolError> {
+        let derived = Self::derive(self.m, self.n)?;
+
+        let mut expected = Vec::new();
+        derived.serialize(&mut expected)?;
+        let mut actual = Vec::new();
+        self.serialize(&mut actual)?;
+
+        Ok(expected == actual)
+    }
+
+    /// Produce parameters the way earlier versions did, sampling every generator from `rng`.
+    /// Whoever runs this could know relations between the generators, so it is only kept for
+    /// compatibility with deployments that already rely on such parameters.
+    #[cfg(feature = "legacy-setup")]
+    pub fn legacy_setup<R: Rng + CryptoRng>(
+        rng: &mut R,
+        m: usize,
+        n: usize,
+    ) -> Result<Self, CardProtocolError> {
+        check_shape(m, n)?;
+
+        let enc_parameters = ElGamal::<C>::setup(rng)?;
+        let commit_parameters = Comm::setup(rng, n);
+        let generator = ElGamal::<C>::generator(rng)?;
+
+        Ok(Self::new(
+            m,
+            n,
+            enc_parameters,
+            commit_parameters,
+            generator,
+        ))
+    }
+}
+
+/// Parameters are encoded as their dimensions followed by the encryption parameters, the
+/// commitment key and the generator. A decoded commitment key may have been sampled by whoever
+/// sent it: check it with `verify_parameters` before relying on the parameters.
+impl<C, Comm> CanonicalSerialize for Parameters<C, Comm>
+where
+    C: ProjectiveCurve,
+    Comm: HomomorphicCommitmentScheme<C::ScalarField>,
+    Comm::CommitKey: CanonicalSerialize,
+{
+    fn serialize<W: Write>(&self, mut writer: W) -> Result<(), SerializationError> {
+        (self.m as u64, self.n as u64).serialize(&mut writer)?;
+        self.enc_parameters.serialize(&mut writer)?;
+        self.commit_parameters.serialize(&mut writer)?;
+        self.generator.serialize(&mut writer)
+    }
+
+    fn serialized_size(&self) -> usize {
+        (self.m as u64, self.n as u64).serialized_size()
+            + self.enc_parameters.serialized_size()
+            + self.commit_parameters.serialized_size()
+            + self.generator.serialized_size()
+    }
+}
+
+impl<C, Comm> CanonicalDeserialize for Parameters<C, Comm>
+where
+    C: ProjectiveCurve,
+    Comm: HomomorphicCommitmentScheme<C::ScalarField>,
+    Comm::CommitKey: CanonicalDeserialize,
+{
+    fn deserialize<R: Read>(mut reader: R) -> Result<Self, SerializationError> {
+        let (m, n) = <(u64, u64)>::deserialize(&mut reader)?;
+
+        Ok(Self::new(
+            m as usize,
+            n as usize,
+            el_gamal::Parameters::<C>::deserialize(&mut reader)?,
+            Comm::CommitKey::deserialize(&mut reader)?,
+            el_gamal::Generator::<C>::deserialize(&mut reader)?,
+        ))
+    }
+}
+
+/// Group elements are printed as truncated hex of their encoding, such as
+/// `Parameters { m: 4, n: 13, enc_parameters: 0x3fa1…c2, commit_parameters: 0x90de…77, .. }`.
+impl<C, Comm> fmt::Debug for Parameters<C, Comm>
+where
+    C: ProjectiveCurve,
+    Comm: HomomorphicCommitmentScheme<C::ScalarField>,
+    Comm::CommitKey: CanonicalSerialize,
+{
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Parameters")
+            .field("m", &self.m)
+            .field("n", &self.n)
+            .field("enc_parameters", &Hex(&self.enc_parameters))
+            .field("commit_parameters", &Hex(&self.commit_parameters))
+            .field("generator", &Hex(&self.generator))
+            .finish()
+    }
+}
+
+/// The deck every player can recompute on their own once the aggregate key is known: each card
+/// masked with randomness one. It hides nothing; the shuffles that follow are what mask it.
+pub fn starting_deck<C, Comm>(
+    pp: &Parameters<C, Comm>,
+    shared_key: &PublicKey<C>,
+    cards: &[Card<C>],
+) -> Result<Vec<MaskedCard<C>>, CardProtocolError>
+where
+    C: ProjectiveCurve,
+    Comm: HomomorphicCommitmentScheme<C::ScalarField>,
+{
+    cards
+        .iter()
+        .map(|card| card.mask(&pp.enc_parameters, shared_key, &C::ScalarField::one()))
+        .collect()
+}
+
+/// Dimensions `(m, n)` of the matrix a deck of `num_cards` cards is arranged in for the shuffle
+/// argument, with `m * n = num_cards` and `m <= n` as close to each other as the divisors of
+/// `num_cards` allow: `(4, 13)` for a single deck, `(13, 24)` for a six-deck shoe.
+/// A deck of a single card gets the shape `(1, 1)`, which [`check_shape`] rejects.
+pub fn deck_shape(num_cards: usize) -> Result<(usize, usize), CardProtocolError> {
+    let m = (1..=num_cards)
+        .take_while(|m| m * m <= num_cards)
+        .filter(|m| num_cards % m == 0)
+        .last()
+        .ok_or(CardProtocolError::InvalidInput(
+            InputError::DimensionMismatch {
+                dimension: Dimension::Deck,
+                expected: Bound::AtLeast(1),
+                got: num_cards,
+            },
+        ))?;
+
+    Ok((m, num_cards / m))
+}
+
+/// Largest deck `setup` accepts: peers refuse to decode longer decks.
+pub const MAX_DECK_SIZE: usize = MAX_VECTOR_LENGTH;
+
+/// Check that the shuffle argument supports a deck of `m` rows of `n` columns, so that `setup`
+/// and `verify_parameters` fail here rather than inside a proof. The deck needs at least one row
+/// and two columns, as the single value product argument over a row of one element does not
+/// hold, and at most [`MAX_DECK_SIZE`] cards. Beyond that any shape works, with `m` smaller,
+/// equal to or larger than `n`.
+pub fn check_shape(m: usize, n: usize) -> Result<(), CardProtocolError> {
+    let (dimension, expected, got) = if m < 1 {
+        (Dimension::Rows, Bound::AtLeast(1), m)
+    } else if n < 2 {
+        (Dimension::Columns, Bound::AtLeast(2), n)
+    } else if m > MAX_DECK_SIZE / n {
+        (Dimension::Rows, Bound::AtMost(MAX_DECK_SIZE / n), m)
+    } else {
+        return Ok(());
+    };
+
+    Err(CardProtocolError::InvalidInput(
+        InputError::DimensionMismatch {
+            dimension,
+            expected,
+            got,
+        },
+    ))
+}
+
+/// Run `setup` for a deck of `num_cards` cards, in the shape given by [`deck_shape`].
+pub fn setup_for_deck<R: Rng + CryptoRng, C: ProjectiveCurve>(
+    rng: &mut R,
+    num_cards: usize,
+) -> Result<Parameters<C>, CardProtocolError> {
+    let (m, n) = deck_shape(num_cards)?;
+
+    DLCards::<C>::setup(rng, m, n)
+}
+
+/// Encode the cards of `spec` and run `setup` for a deck of that shape.
+pub fn setup_for_spec<R: Rng + CryptoRng, C: ProjectiveCurve>(
+    rng: &mut R,
+    spec: &DeckSpec,
+) -> Result<(Parameters<C>, EncodedDeck<C>), CardProtocolError> {
+    let deck = spec.encode()?;
+    let (m, n) = deck.shape();
+
+    Ok((DLCards::<C>::setup(rng, m, n)?, deck))
+}
+
+/// Seed of the Fiat-Shamir transcript of a proof: its domain separator followed by the context the
+/// proof is bound to, typically a session identifier. A proof produced for one context does not
+/// verify under another, so it cannot be replayed in a different game.
+fn transcript_seed(domain: &[u8], context: &[u8]) -> Result<Vec<u8>, CryptoError> {
+    Ok(to_bytes![domain, context.len() as u64, context]?)
+}
+
+/// Seed of the Fiat-Shamir transcript of reveal proofs in `context`, for verifiers that derive
+/// the challenge themselves.
+pub(crate) fn reveal_transcript_seed(context: &[u8]) -> Result<Vec<u8>, CryptoError> {
+    transcript_seed(REVEAL_RNG_SEED, context)
+}
+
+/// Public label from which the commitment key for an `m x n` deck is derived. The generators of
+/// the encryption scheme and of the shuffle argument do not depend on the dimensions.
+fn commit_key_label(m: usize, n: usize) -> Vec<u8> {
+    [
+        COMMIT_KEY_SEED,
+        &(m as u64).to_le_bytes()[..],
+        &(n as u64).to_le_bytes()[..],
+    ]
+    .concat()
+}
+
+/// Canonical encoding of a public key, for the errors that name the key at fault.
+fn key_bytes<K: CanonicalSerialize>(pk: &K) -> Result<Vec<u8>, CardProtocolError> {
+    let mut bytes = Vec::new();
+    pk.serialize(&mut bytes)?;
+
+    Ok(bytes)
 }
 
 pub type PublicKey<C> = el_gamal::PublicKey<C>;
 
-pub type PlayerSecretKey<C> = el_gamal::SecretKey<C>;
+pub type PlayerSecretKey<C> = SecretKey<<C as ProjectiveCurve>::ScalarField>;
 
 /// An open playing card. In this Discrete Log-based implementation of the Barnett-Smart card protocol
 /// a card is an el-Gamal plaintext. We create a type alias to implement the `Mask` trait on it.
@@ -82,12 +430,21 @@ const MASKING_RNG_SEED: &'static [u8] = b"Masking Proof";
 const REMASKING_RNG_SEED: &'static [u8] = b"Remasking Proof";
 const REVEAL_RNG_SEED: &'static [u8] = b"Reveal Proof";
 const SHUFFLE_RNG_SEED: &'static [u8] = b"Shuffle Proof";
-
-impl<'a, C: ProjectiveCurve> BarnettSmartProtocol for DLCards<'a, C> {
+const COMMIT_KEY_SEED: &'static [u8] = b"Shuffle Commit Key";
+const PARAMETERS_DOMAIN: &'static [u8] = b"Card Protocol Parameters";
+const ENC_GENERATOR_LABEL: &'static [u8] = b"Encryption Generator";
+const SHUFFLE_GENERATOR_LABEL: &'static [u8] = b"Shuffle Generator";
+
+impl<'a, C, Comm> BarnettSmartProtocol for DLCards<'a, C, Comm>
+where
+    C: ProjectiveCurve,
+    Comm: HomomorphicCommitment<C::ScalarField>,
+    Comm::CommitKey: CanonicalSerialize,
+{
     type Scalar = C::ScalarField;
     type Enc = ElGamal<C>;
-    type Comm = PedersenCommitment<C>;
-    type Parameters = Parameters<C>;
+    type Comm = Comm;
+    type Parameters = Parameters<C, Comm>;
     type PlayerPublicKey = PublicKey<C>;
     type PlayerSecretKey = PlayerSecretKey<C>;
     type AggregatePublicKey = PublicKey<C>;
@@ -102,60 +459,78 @@ impl<'a, C: ProjectiveCurve> BarnettSmartProtocol for DLCards<'a, C> {
     type ZKProofReveal = chaum_pedersen_dl_equality::proof::Proof<C>;
     type ZKProofShuffle = shuffle::proof::Proof<Self::Scalar, Self::Enc, Self::Comm>;
 
-    fn setup<R: Rng>(
-        rng: &mut R,
+    /// The parameters are derived rather than sampled, see [`Parameters::derive`], so `rng` is
+    /// not used.
+    fn setup<R: Rng + CryptoRng>(
+        _rng: &mut R,
         m: usize,
         n: usize,
     ) -> Result<Self::Parameters, CardProtocolError> {
-        let enc_parameters = Self::Enc::setup(rng)?;
-        let commit_parameters = Self::Comm::setup(rng, n);
-        let generator = Self::Enc::generator(rng)?;
+        Self::Parameters::derive(m, n)
+    }
 
-        Ok(Self::Parameters::new(
-            m,
-            n,
-            enc_parameters,
-            commit_parameters,
-            generator,
-        ))
+    fn verify_parameters(pp: &Self::Parameters) -> Result<(), CardProtocolError> {
+        check_shape(pp.m, pp.n)?;
+
+        if !pp.is_nothing_up_my_sleeve()? {
+            return Err(VerificationError::UnverifiableParameters.into());
+        }
+
+        Ok(())
     }
 
-    fn player_keygen<R: Rng>(
+    fn player_keygen<R: Rng + CryptoRng>(
         rng: &mut R,
         pp: &Self::Parameters,
     ) -> Result<(Self::PlayerPublicKey, Self::PlayerSecretKey), CardProtocolError> {
         let (pk, sk) = Self::Enc::keygen(&pp.enc_parameters, rng)?;
 
-        Ok((pk, sk))
+        Ok((pk, sk.into()))
     }
 
-    fn prove_key_ownership<B: ToBytes, R: Rng>(
+    fn prove_key_ownership<B: ToBytes, R: Rng + CryptoRng>(
         rng: &mut R,
         pp: &Self::Parameters,
+        context: &[u8],
         pk: &Self::PlayerPublicKey,
         sk: &Self::PlayerSecretKey,
         player_public_info: &B,
+        player_index: usize,
+        key_list_digest: &KeyListDigest,
     ) -> Result<Self::ZKProofKeyOwnership, CryptoError> {
-        let mut fs_rng =
-            FiatShamirRng::<Blake2s>::from_seed(&to_bytes![KEY_OWN_RNG_SEED, player_public_info]?);
+        let seed = to_bytes![
+            &transcript_seed(KEY_OWN_RNG_SEED, context)?[..],
+            player_public_info,
+            player_index as u64,
+            &key_list_digest[..]
+        ]?;
+        let mut fs_rng = FiatShamirRng::<Blake2s>::from_seed(&seed);
+        let mut rng = hedged_rng(rng, slice::from_ref(sk.expose_secret()), &seed, &[*pk])?;
 
         schnorr_identification::SchnorrIdentification::prove(
-            rng,
+            &mut rng,
             &pp.enc_parameters.generator,
             pk,
-            sk,
+            sk.expose_secret(),
             &mut fs_rng,
         )
     }
 
     fn verify_key_ownership<B: ToBytes>(
         pp: &Self::Parameters,
+        context: &[u8],
         pk: &Self::PlayerPublicKey,
         player_public_info: &B,
+        player_index: usize,
+        key_list_digest: &KeyListDigest,
         proof: &Self::ZKProofKeyOwnership,
     ) -> Result<(), CryptoError> {
-        let mut fs_rng =
-            FiatShamirRng::<Blake2s>::from_seed(&to_bytes![KEY_OWN_RNG_SEED, player_public_info]?);
+        let mut fs_rng = FiatShamirRng::<Blake2s>::from_seed(&to_bytes![
+            &transcript_seed(KEY_OWN_RNG_SEED, context)?[..],
+            player_public_info,
+            player_index as u64,
+            &key_list_digest[..]
+        ]?);
         schnorr_identification::SchnorrIdentification::verify(
             &pp.enc_parameters.generator,
             pk,
@@ -166,26 +541,74 @@ impl<'a, C: ProjectiveCurve> BarnettSmartProtocol for DLCards<'a, C> {
 
     fn compute_aggregate_key<B: ToBytes>(
         pp: &Self::Parameters,
+        context: &[u8],
         player_keys_proof_info: &Vec<(Self::PlayerPublicKey, Self::ZKProofKeyOwnership, B)>,
     ) -> Result<Self::AggregatePublicKey, CardProtocolError> {
+        let _span = span!(
+            "compute_aggregate_key",
+            players = player_keys_proof_info.len()
+        );
+
+        let keys = player_keys_proof_info
+            .iter()
+            .map(|(pk, _, _)| *pk)
+            .collect::<Vec<_>>();
+        let key_list_digest = Self::key_list_digest(&keys)?;
+
+        let mut seen_keys = BTreeSet::new();
+        for (i, pk) in keys.iter().enumerate() {
+            if pk.is_zero() {
+                return Err(InputError::IdentityPublicKey(i).into());
+            }
+
+            // A repeated key would count twice towards the aggregate
+            let mut encoding = Vec::new();
+            pk.serialize(&mut encoding)?;
+            if !seen_keys.insert(encoding) {
+                return Err(InputError::DuplicatePlayerKey { index: i }.into());
+            }
+        }
+
         let zero = Self::PlayerPublicKey::zero();
 
         let mut acc = zero;
-        for (pk, proof, player_public_info) in player_keys_proof_info {
-            Self::verify_key_ownership(pp, pk, player_public_info, proof)?;
+        for (i, (pk, proof, player_public_info)) in player_keys_proof_info.iter().enumerate() {
+            let verified = Self::verify_key_ownership(
+                pp,
+                context,
+                pk,
+                player_public_info,
+                i,
+                &key_list_digest,
+                proof,
+            );
+            rejected!(verified, "key ownership proof rejected", player = i);
+            if let Err(error) = verified {
+                return Err(VerificationError::InvalidKeyOwnershipProof {
+                    index: i,
+                    public_key: key_bytes(pk)?,
+                    source: OperationError::new(Operation::DuringKeyAggregation, error),
+                }
+                .into());
+            }
             acc = acc + *pk;
         }
 
         Ok(acc)
     }
 
-    fn mask<R: Rng>(
+    fn mask<R: Rng + CryptoRng>(
         rng: &mut R,
         pp: &Self::Parameters,
+        context: &[u8],
         shared_key: &Self::AggregatePublicKey,
         original_card: &Self::Card,
         r: &Self::Scalar,
     ) -> Result<(Self::MaskedCard, Self::ZKProofMasking), CardProtocolError> {
+        if r.is_zero() {
+            return Err(InputError::ZeroMaskingFactor.into());
+        }
+
         let masked_card = original_card.mask(&pp.enc_parameters, shared_key, r)?;
         let gen = pp.enc_parameters.generator;
 
@@ -199,9 +622,16 @@ impl<'a, C: ProjectiveCurve> BarnettSmartProtocol for DLCards<'a, C> {
         let cp_statement =
             chaum_pedersen_dl_equality::Statement::new(&masked_card.0, &statement_cipher);
 
-        let mut fs_rng = FiatShamirRng::<Blake2s>::from_seed(&to_bytes![MASKING_RNG_SEED]?);
-        let proof = chaum_pedersen_dl_equality::DLEquality::prove(
+        let seed = transcript_seed(MASKING_RNG_SEED, context)?;
+        let mut fs_rng = FiatShamirRng::<Blake2s>::from_seed(&seed);
+        let mut rng = hedged_rng(
             rng,
+            slice::from_ref(r),
+            &seed,
+            &[*shared_key, masked_card.0, statement_cipher],
+        )?;
+        let proof = chaum_pedersen_dl_equality::DLEquality::prove(
+            &mut rng,
             &cp_parameters,
             &cp_statement,
             r,
@@ -213,6 +643,7 @@ impl<'a, C: ProjectiveCurve> BarnettSmartProtocol for DLCards<'a, C> {
 
     fn verify_mask(
         pp: &Self::Parameters,
+        context: &[u8],
         shared_key: &Self::AggregatePublicKey,
         card: &Self::Card,
         masked_card: &Self::MaskedCard,
@@ -229,7 +660,8 @@ impl<'a, C: ProjectiveCurve> BarnettSmartProtocol for DLCards<'a, C> {
         let cp_statement =
             chaum_pedersen_dl_equality::Statement::new(&masked_card.0, &statement_cipher);
 
-        let mut fs_rng = FiatShamirRng::<Blake2s>::from_seed(&to_bytes![MASKING_RNG_SEED]?);
+        let mut fs_rng =
+            FiatShamirRng::<Blake2s>::from_seed(&transcript_seed(MASKING_RNG_SEED, context)?);
         chaum_pedersen_dl_equality::DLEquality::verify(
             &cp_parameters,
             &cp_statement,
@@ -238,13 +670,18 @@ impl<'a, C: ProjectiveCurve> BarnettSmartProtocol for DLCards<'a, C> {
         )
     }
 
-    fn remask<R: Rng>(
+    fn remask<R: Rng + CryptoRng>(
         rng: &mut R,
         pp: &Self::Parameters,
+        context: &[u8],
         shared_key: &Self::AggregatePublicKey,
         original_card: &Self::MaskedCard,
         alpha: &Self::Scalar,
     ) -> Result<(Self::MaskedCard, Self::ZKProofRemasking), CardProtocolError> {
+        if alpha.is_zero() {
+            return Err(InputError::ZeroMaskingFactor.into());
+        }
+
         let remasked = original_card.remask(&pp.enc_parameters, shared_key, alpha)?;
 
         // Map to Chaum-Pedersen parameters
@@ -258,9 +695,16 @@ impl<'a, C: ProjectiveCurve> BarnettSmartProtocol for DLCards<'a, C> {
         let cp_statement =
             chaum_pedersen_dl_equality::Statement::new(&statement_cipher.0, &statement_cipher.1);
 
-        let mut fs_rng = FiatShamirRng::<Blake2s>::from_seed(&to_bytes![REMASKING_RNG_SEED]?);
-        let proof = chaum_pedersen_dl_equality::DLEquality::prove(
+        let seed = transcript_seed(REMASKING_RNG_SEED, context)?;
+        let mut fs_rng = FiatShamirRng::<Blake2s>::from_seed(&seed);
+        let mut rng = hedged_rng(
             rng,
+            slice::from_ref(alpha),
+            &seed,
+            &[*shared_key, statement_cipher.0, statement_cipher.1],
+        )?;
+        let proof = chaum_pedersen_dl_equality::DLEquality::prove(
+            &mut rng,
             &cp_parameters,
             &cp_statement,
             alpha,
@@ -272,6 +716,7 @@ impl<'a, C: ProjectiveCurve> BarnettSmartProtocol for DLCards<'a, C> {
 
     fn verify_remask(
         pp: &Self::Parameters,
+        context: &[u8],
         shared_key: &Self::AggregatePublicKey,
         original_masked: &Self::MaskedCard,
         remasked: &Self::MaskedCard,
@@ -288,7 +733,8 @@ impl<'a, C: ProjectiveCurve> BarnettSmartProtocol for DLCards<'a, C> {
         let cp_statement =
             chaum_pedersen_dl_equality::Statement::new(&statement_cipher.0, &statement_cipher.1);
 
-        let mut fs_rng = FiatShamirRng::<Blake2s>::from_seed(&to_bytes![REMASKING_RNG_SEED]?);
+        let mut fs_rng =
+            FiatShamirRng::<Blake2s>::from_seed(&transcript_seed(REMASKING_RNG_SEED, context)?);
         chaum_pedersen_dl_equality::DLEquality::verify(
             &cp_parameters,
             &cp_statement,
@@ -297,15 +743,26 @@ impl<'a, C: ProjectiveCurve> BarnettSmartProtocol for DLCards<'a, C> {
         )
     }
 
-    fn compute_reveal_token<R: Rng>(
+    fn compute_reveal_token<R: Rng + CryptoRng>(
         rng: &mut R,
         pp: &Self::Parameters,
+        context: &[u8],
         sk: &Self::PlayerSecretKey,
         pk: &Self::PlayerPublicKey,
         masked_card: &Self::MaskedCard,
     ) -> Result<(Self::RevealToken, Self::ZKProofReveal), CardProtocolError> {
-        let reveal_token: RevealToken<C> =
-            el_gamal::Plaintext(masked_card.0.into().mul(sk.into_repr()).into_affine());
+        let seed = transcript_seed(REVEAL_RNG_SEED, context)?;
+        let mut rng = hedged_rng(
+            rng,
+            slice::from_ref(sk.expose_secret()),
+            &seed,
+            &[*pk, masked_card.0, masked_card.1],
+        )?;
+
+        #[cfg(feature = "side-channel-hardening")]
+        let reveal_token = reveal::blinded_reveal_token(&mut rng, sk, masked_card)?;
+        #[cfg(not(feature = "side-channel-hardening"))]
+        let reveal_token = reveal::reveal_token(sk, masked_card);
 
         // Map to Chaum-Pedersen parameters
         let cp_parameters = chaum_pedersen_dl_equality::Parameters::new(
@@ -316,12 +773,12 @@ impl<'a, C: ProjectiveCurve> BarnettSmartProtocol for DLCards<'a, C> {
         // Map to Chaum-Pedersen parameters
         let cp_statement = chaum_pedersen_dl_equality::Statement::new(&reveal_token.0, pk);
 
-        let mut fs_rng = FiatShamirRng::<Blake2s>::from_seed(&to_bytes![REVEAL_RNG_SEED]?);
+        let mut fs_rng = FiatShamirRng::<Blake2s>::from_seed(&seed);
         let proof = chaum_pedersen_dl_equality::DLEquality::prove(
-            rng,
+            &mut rng,
             &cp_parameters,
             &cp_statement,
-            sk,
+            sk.expose_secret(),
             &mut fs_rng,
         )?;
 
@@ -330,6 +787,7 @@ impl<'a, C: ProjectiveCurve> BarnettSmartProtocol for DLCards<'a, C> {
 
     fn verify_reveal(
         pp: &Self::Parameters,
+        context: &[u8],
         pk: &Self::PlayerPublicKey,
         reveal_token: &Self::RevealToken,
         masked_card: &Self::MaskedCard,
@@ -344,7 +802,8 @@ impl<'a, C: ProjectiveCurve> BarnettSmartProtocol for DLCards<'a, C> {
         // Map to Chaum-Pedersen parameters
         let cp_statement = chaum_pedersen_dl_equality::Statement::new(&reveal_token.0, pk);
 
-        let mut fs_rng = FiatShamirRng::<Blake2s>::from_seed(&to_bytes![REVEAL_RNG_SEED]?);
+        let mut fs_rng =
+            FiatShamirRng::<Blake2s>::from_seed(&transcript_seed(REVEAL_RNG_SEED, context)?);
         chaum_pedersen_dl_equality::DLEquality::verify(
             &cp_parameters,
             &cp_statement,
@@ -355,6 +814,8 @@ impl<'a, C: ProjectiveCurve> BarnettSmartProtocol for DLCards<'a, C> {
 
     fn unmask(
         pp: &Self::Parameters,
+        context: &[u8],
+        num_of_players: usize,
         decryption_key: &Vec<(
             Self::RevealToken,
             Self::ZKProofReveal,
@@ -362,12 +823,38 @@ impl<'a, C: ProjectiveCurve> BarnettSmartProtocol for DLCards<'a, C> {
         )>,
         masked_card: &Self::MaskedCard,
     ) -> Result<Self::Card, CardProtocolError> {
+        let _span = span!("unmask", players = decryption_key.len());
+
+        // Without any token, the card would open to its masked value
+        if decryption_key.is_empty() {
+            return Err(InputError::NotEnoughPlayers.into());
+        }
+        // A missing token leaves the card masked under that player's key, and an extra one masks
+        // it again
+        check_dimension(
+            Dimension::RevealTokens,
+            num_of_players,
+            decryption_key.len(),
+        )?;
+
         let zero = Self::RevealToken::zero();
 
         let mut aggregate_token = zero;
 
-        for (token, proof, pk) in decryption_key {
-            Self::verify_reveal(pp, pk, token, masked_card, proof)?;
+        for (player, (token, proof, pk)) in decryption_key.iter().enumerate() {
+            let verified = Self::verify_reveal(pp, context, pk, token, masked_card, proof);
+            rejected!(verified, "reveal token rejected", player);
+            if let Err(error) = verified {
+                return Err(VerificationError::InvalidRevealToken {
+                    index: player,
+                    public_key: key_bytes(pk)?,
+                    source: OperationError::new(
+                        Operation::DuringUnmask { card_index: None },
+                        error,
+                    ),
+                }
+                .into());
+            }
 
             aggregate_token = aggregate_token + *token;
         }
@@ -377,15 +864,29 @@ impl<'a, C: ProjectiveCurve> BarnettSmartProtocol for DLCards<'a, C> {
         Ok(decrypted)
     }
 
-    fn shuffle_and_remask<R: Rng>(
+    fn shuffle_and_remask<R: Rng + CryptoRng>(
         rng: &mut R,
         pp: &Self::Parameters,
+        context: &[u8],
         shared_key: &Self::AggregatePublicKey,
         deck: &Vec<Self::MaskedCard>,
         masking_factors: &Vec<Self::Scalar>,
         permutation: &Permutation,
     ) -> Result<(Vec<Self::MaskedCard>, Self::ZKProofShuffle), CardProtocolError> {
-        let permuted_deck = permutation.permute_array(&deck);
+        let _span = span!(
+            "shuffle_and_remask",
+            deck_size = deck.len(),
+            proof_bytes = tracing::field::Empty
+        );
+
+        check_dimension(Dimension::Deck, pp.num_cards(), deck.len())?;
+        check_dimension(Dimension::MaskingFactors, deck.len(), masking_factors.len())?;
+        check_permutation(permutation, deck.len())?;
+        if masking_factors.iter().any(|factor| factor.is_zero()) {
+            return Err(InputError::ZeroMaskingFactor.into());
+        }
+
+        let permuted_deck = permutation.permute_array(deck);
         let masked_shuffled = permuted_deck
             .iter()
             .zip(masking_factors.iter())
@@ -405,25 +906,55 @@ impl<'a, C: ProjectiveCurve> BarnettSmartProtocol for DLCards<'a, C> {
 
         let witness = shuffle::Witness::new(permutation, masking_factors);
 
-        let mut fs_rng = FiatShamirRng::<Blake2s>::from_seed(&to_bytes![SHUFFLE_RNG_SEED]?);
+        // The shuffled deck stands for the permutation in the statement of the nonces
+        let seed = transcript_seed(SHUFFLE_RNG_SEED, context)?;
+        let mut fs_rng = FiatShamirRng::<Blake2s>::from_seed(&seed);
+        let shuffled_points = masked_shuffled
+            .iter()
+            .flat_map(|masked_card| [masked_card.0, masked_card.1])
+            .collect::<Vec<_>>();
+        let mut rng = hedged_rng(rng, masking_factors, &seed, &shuffled_points)?;
         let proof = shuffle::ShuffleArgument::prove(
-            rng,
+            &mut rng,
             &shuffle_parameters,
             &shuffle_statement,
             &witness,
             &mut fs_rng,
         )?;
+        record!(_span, "proof_bytes", proof.serialized_size());
 
         Ok((masked_shuffled, proof))
     }
 
     fn verify_shuffle(
         pp: &Self::Parameters,
+        context: &[u8],
         shared_key: &Self::AggregatePublicKey,
         original_deck: &Vec<Self::MaskedCard>,
         shuffled_deck: &Vec<Self::MaskedCard>,
         proof: &Self::ZKProofShuffle,
-    ) -> Result<(), CryptoError> {
+    ) -> Result<(), ShuffleVerificationFailure> {
+        let _span = span!(
+            "verify_shuffle",
+            deck_size = original_deck.len(),
+            proof_bytes = proof.serialized_size()
+        );
+
+        // No proof holds for decks that do not fill the `m x n` matrix of the argument, and the
+        // argument would index past them
+        for (dimension, deck) in [
+            (Dimension::Deck, original_deck),
+            (Dimension::ShuffledDeck, shuffled_deck),
+        ] {
+            if deck.len() != pp.num_cards() {
+                return Err(ShuffleVerificationFailure::DeckSize {
+                    dimension,
+                    expected: pp.num_cards(),
+                    got: deck.len(),
+                });
+            }
+        }
+
         let shuffle_parameters = shuffle::Parameters::new(
             &pp.enc_parameters,
             shared_key,
@@ -433,12 +964,17 @@ impl<'a, C: ProjectiveCurve> BarnettSmartProtocol for DLCards<'a, C> {
 
         let shuffle_statement = shuffle::Statement::new(original_deck, shuffled_deck, pp.m, pp.n);
 
-        let mut fs_rng = FiatShamirRng::<Blake2s>::from_seed(&to_bytes![SHUFFLE_RNG_SEED]?);
-        shuffle::ShuffleArgument::verify(
+        let mut fs_rng =
+            FiatShamirRng::<Blake2s>::from_seed(&transcript_seed(SHUFFLE_RNG_SEED, context)?);
+        let verified = shuffle::ShuffleArgument::verify(
             &shuffle_parameters,
             &shuffle_statement,
             proof,
             &mut fs_rng,
         )
+        .map_err(ShuffleVerificationFailure::from);
+        rejected!(verified, "shuffle proof rejected");
+
+        verified
     }
 }
@@ -0,0 +1,475 @@
+//! Broadcast of game messages over libp2p gossipsub, as a [`Transport`] for the runner.
+//!
+//! The players of a table subscribe to a topic derived from the session context. Every message is
+//! wrapped in a [`SignedMessage`] signed with the identity key of its sender, whose slot numbers
+//! the messages of that sender, and published as its canonical encoding.
+//!
+//! Each node keeps one [`EventLog`] per player, mirroring the messages that player signed in
+//! slot order. A message is delivered to the runner once it extends the log of its sender, so
+//! messages of a sender are delivered in the order they were signed whatever the order gossip
+//! brings them in. Duplicates and replays, whose slot is already in the log, are dropped, as are
+//! messages whose signature does not match the identity of their seat: gossip peers relay
+//! messages but cannot forge or reorder them. Since every player mirrors the same signed
+//! messages, the heads of the logs of a sender agree across the table, which
+//! [`GossipTransport::log_head`] exposes for comparison.
+//!
+//! Gossipsub only forwards a message to the peers subscribed when it is published, so
+//! [`GossipNode::connect`] returns once every other player has subscribed to the topic.
+
+use crate::discrete_log_cards::abort::SignedMessage;
+use crate::discrete_log_cards::event_log::{EntryDigest, EventLog};
+use crate::discrete_log_cards::identity::{IdentityKey, PublicIdentity};
+use crate::discrete_log_cards::runner::{PlayerId, Transport};
+use crate::discrete_log_cards::sender_logs::{lock, transport_error, SenderLogs};
+use crate::discrete_log_cards::session::GameMessage;
+use crate::discrete_log_cards::validation::{
+    bounded_from_bytes, to_bytes, DecodingLimits, MAX_VECTOR_LENGTH,
+};
+use crate::error::{CardProtocolError, InputError, InternalError};
+
+use ark_ec::ProjectiveCurve;
+use async_trait::async_trait;
+use blake2::{Blake2s, Digest};
+use libp2p::core::upgrade;
+use libp2p::futures::StreamExt;
+use libp2p::identity::Keypair;
+use libp2p::swarm::{SwarmBuilder, SwarmEvent};
+use libp2p::{gossipsub, noise, tcp, yamux, Multiaddr, PeerId, Swarm, Transport as _};
+use std::collections::{HashSet, VecDeque};
+use std::fmt;
+use std::sync::{Arc, Mutex, MutexGuard};
+use std::time::Duration;
+use tokio::sync::mpsc::{unbounded_channel, UnboundedReceiver, UnboundedSender};
+use tokio::time::{interval, timeout};
+
+pub use crate::discrete_log_cards::sender_logs::MAX_PENDING_MESSAGES;
+
+const GOSSIP_TOPIC_DOMAIN: &'static [u8] = b"Mental Poker Gossip Topic";
+
+/// Largest message published or accepted, which leaves room for the shuffle of a large deck.
+pub const MAX_GOSSIP_BYTES: usize = 1 << 20;
+
+/// Interval between attempts to publish messages that found no subscribed peer.
+const PUBLISH_RETRY: Duration = Duration::from_millis(100);
+
+/// Gossipsub topic of the game with session context `context`.
+pub fn session_topic(context: &[u8]) -> gossipsub::IdentTopic {
+    let mut hasher = Blake2s::new();
+    hasher.update(GOSSIP_TOPIC_DOMAIN);
+    hasher.update(&(context.len() as u64).to_le_bytes());
+    hasher.update(context);
+
+    let digest = hasher
+        .finalize()
+        .iter()
+        .map(|byte| format!("{:02x}", byte))
+        .collect::<String>();
+
+    gossipsub::IdentTopic::new(format!("mental-poker/{}", digest))
+}
+
+/// A player's libp2p node, listening but not yet connected to the table.
+pub struct GossipNode<C: ProjectiveCurve> {
+    swarm: Swarm<gossipsub::Behaviour>,
+    topic: gossipsub::IdentTopic,
+    address: Multiaddr,
+    identity: IdentityKey<C>,
+    logs: SenderLogs<C>,
+}
+
+impl<C: ProjectiveCurve> fmt::Debug for GossipNode<C> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("GossipNode")
+            .field("address", &self.address)
+            .field("identity", &self.identity)
+            .finish_non_exhaustive()
+    }
+}
+
+impl<C: ProjectiveCurve> GossipNode<C> {
+    /// Start the node of the player in seat `seat`, who signs with `identity`, and listen on
+    /// `listen`. `identities[i]` is the identity of the player in seat `i`.
+    pub async fn new(
+        seat: PlayerId,
+        identity: IdentityKey<C>,
+        identities: Vec<PublicIdentity<C>>,
+        context: &[u8],
+        listen: Multiaddr,
+    ) -> Result<Self, CardProtocolError> {
+        if seat >= identities.len() {
+            return Err(InputError::UnknownPlayer(seat).into());
+        }
+
+        let keypair = Keypair::generate_ed25519();
+        let peer_id = PeerId::from(keypair.public());
+
+        let transport = tcp::tokio::Transport::new(tcp::Config::default().nodelay(true))
+            .upgrade(upgrade::Version::V1)
+            .authenticate(noise::NoiseAuthenticated::xx(&keypair).map_err(transport_error)?)
+            .multiplex(yamux::YamuxConfig::default())
+            .boxed();
+
+        // Identical payloads get the same id, so gossipsub drops copies relayed by other peers
+        let config = gossipsub::ConfigBuilder::default()
+            .max_transmit_size(MAX_GOSSIP_BYTES)
+            .validation_mode(gossipsub::ValidationMode::Strict)
+            .message_id_fn(|message: &gossipsub::Message| {
+                gossipsub::MessageId::from(Blake2s::digest(&message.data).to_vec())
+            })
+            .build()
+            .map_err(transport_error)?;
+        let mut behaviour =
+            gossipsub::Behaviour::new(gossipsub::MessageAuthenticity::Signed(keypair), config)
+                .map_err(transport_error)?;
+
+        let topic = session_topic(context);
+        behaviour.subscribe(&topic).map_err(transport_error)?;
+
+        let mut swarm = SwarmBuilder::with_tokio_executor(transport, behaviour, peer_id).build();
+        swarm.listen_on(listen).map_err(transport_error)?;
+        let address = loop {
+            if let SwarmEvent::NewListenAddr { address, .. } = swarm.select_next_some().await {
+                break address;
+            }
+        };
+
+        Ok(Self {
+            swarm,
+            topic,
+            address,
+            identity,
+            logs: SenderLogs::new(seat, identities, context),
+        })
+    }
+
+    /// Address the node listens on, for the other players to dial.
+    pub fn address(&self) -> &Multiaddr {
+        &self.address
+    }
+
+    /// Dial `peers` and wait, for at most `wait`, until every other player has subscribed to the
+    /// topic of the game. The node then runs on its own task until the transport is dropped.
+    pub async fn connect(
+        mut self,
+        peers: &[Multiaddr],
+        wait: Duration,
+    ) -> Result<GossipTransport<C>, CardProtocolError> {
+        for peer in peers {
+            self.swarm.dial(peer.clone()).map_err(transport_error)?;
+        }
+
+        let (outbox, outgoing) = unbounded_channel();
+        let (delivered, inbox) = unbounded_channel();
+        let logs = Arc::new(Mutex::new(self.logs));
+        let mut driver = Driver {
+            swarm: self.swarm,
+            topic: self.topic,
+            logs: logs.clone(),
+            delivered,
+            outgoing,
+            queue: VecDeque::new(),
+            subscribed: HashSet::new(),
+        };
+
+        let others = driver.logs()?.identities.len() - 1;
+        timeout(wait, async {
+            while driver.subscribed.len() < others {
+                let event = driver.swarm.select_next_some().await;
+                driver.handle(event);
+            }
+        })
+        .await
+        .map_err(|_| {
+            CardProtocolError::Internal(InternalError::TransportError(
+                "the other players did not join in time".to_string(),
+            ))
+        })?;
+
+        tokio::spawn(driver.run());
+
+        Ok(GossipTransport {
+            identity: self.identity,
+            logs,
+            outbox,
+            inbox,
+        })
+    }
+}
+
+/// Transport of a connected [`GossipNode`].
+pub struct GossipTransport<C: ProjectiveCurve> {
+    identity: IdentityKey<C>,
+    logs: Arc<Mutex<SenderLogs<C>>>,
+    outbox: UnboundedSender<Vec<u8>>,
+    inbox: UnboundedReceiver<(PlayerId, GameMessage<C>)>,
+}
+
+impl<C: ProjectiveCurve> fmt::Debug for GossipTransport<C> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("GossipTransport")
+            .field("identity", &self.identity)
+            .finish_non_exhaustive()
+    }
+}
+
+impl<C: ProjectiveCurve> GossipTransport<C> {
+    /// Head of the log of the messages `player` signed, as far as this node has delivered them.
+    pub fn log_head(&self, player: PlayerId) -> Result<EntryDigest, CardProtocolError> {
+        lock(&self.logs)?
+            .logs
+            .get(player)
+            .ok_or(CardProtocolError::InvalidInput(InputError::UnknownPlayer(
+                player,
+            )))?
+            .head()
+    }
+
+    /// Copy of the log of the messages `player` signed, as far as this node has delivered them.
+    pub fn log(&self, player: PlayerId) -> Result<EventLog<C>, CardProtocolError> {
+        lock(&self.logs)?
+            .logs
+            .get(player)
+            .cloned()
+            .ok_or(CardProtocolError::InvalidInput(InputError::UnknownPlayer(
+                player,
+            )))
+    }
+}
+
+#[async_trait]
+impl<C: ProjectiveCurve> Transport<C> for GossipTransport<C> {
+    async fn broadcast(&self, message: GameMessage<C>) -> Result<(), CardProtocolError> {
+        let signed = lock(&self.logs)?.sign_own(&self.identity, message)?;
+
+        self.outbox.send(to_bytes(&signed)?).map_err(|_| {
+            CardProtocolError::Internal(InternalError::TransportError(
+                "the node stopped".to_string(),
+            ))
+        })
+    }
+
+    async fn recv(&mut self) -> Result<(PlayerId, GameMessage<C>), CardProtocolError> {
+        self.inbox.recv().await.ok_or_else(|| {
+            CardProtocolError::Internal(InternalError::TransportError(
+                "the node stopped".to_string(),
+            ))
+        })
+    }
+}
+
+/// Task polling the swarm: it publishes the messages of the transport and delivers the ones of
+/// the other players.
+struct Driver<C: ProjectiveCurve> {
+    swarm: Swarm<gossipsub::Behaviour>,
+    topic: gossipsub::IdentTopic,
+    logs: Arc<Mutex<SenderLogs<C>>>,
+    delivered: UnboundedSender<(PlayerId, GameMessage<C>)>,
+    outgoing: UnboundedReceiver<Vec<u8>>,
+    /// Encoded messages not published yet
+    queue: VecDeque<Vec<u8>>,
+    subscribed: HashSet<PeerId>,
+}
+
+impl<C: ProjectiveCurve> Driver<C> {
+    fn logs(&self) -> Result<MutexGuard<'_, SenderLogs<C>>, CardProtocolError> {
+        lock(&self.logs)
+    }
+
+    /// Run until the transport is dropped or a message cannot be published.
+    async fn run(mut self) {
+        let mut retry = interval(PUBLISH_RETRY);
+
+        loop {
+            tokio::select! {
+                outgoing = self.outgoing.recv() => match outgoing {
+                    Some(bytes) => self.queue.push_back(bytes),
+                    None => return,
+                },
+                event = self.swarm.select_next_some() => self.handle(event),
+                _ = retry.tick() => {}
+            }
+
+            if self.publish().is_err() {
+                return;
+            }
+        }
+    }
+
+    fn handle<E>(&mut self, event: SwarmEvent<gossipsub::Event, E>) {
+        match event {
+            SwarmEvent::Behaviour(gossipsub::Event::Message { message, .. }) => {
+                self.receive(&message.data)
+            }
+            SwarmEvent::Behaviour(gossipsub::Event::Subscribed { peer_id, topic })
+                if topic == self.topic.hash() =>
+            {
+                self.subscribed.insert(peer_id);
+            }
+            _ => {}
+        }
+    }
+
+    fn receive(&mut self, data: &[u8]) {
+        let limits = DecodingLimits::new(MAX_VECTOR_LENGTH, MAX_GOSSIP_BYTES);
+        let signed = match bounded_from_bytes::<SignedMessage<C>>(data, &limits) {
+            Ok(signed) => signed,
+            Err(_) => return,
+        };
+
+        let delivered = match self.logs() {
+            Ok(mut logs) => logs.accept(signed),
+            Err(_) => return,
+        };
+        for message in delivered {
+            // The transport may already be gone, in which case the driver stops on its next turn
+            let _ = self.delivered.send(message);
+        }
+    }
+
+    /// Publish the queued messages in order, keeping them queued while no peer is subscribed.
+    fn publish(&mut self) -> Result<(), CardProtocolError> {
+        while let Some(bytes) = self.queue.front() {
+            match self
+                .swarm
+                .behaviour_mut()
+                .publish(self.topic.clone(), bytes.clone())
+            {
+                Ok(_) | Err(gossipsub::PublishError::Duplicate) => {
+                    self.queue.pop_front();
+                }
+                Err(gossipsub::PublishError::InsufficientPeers) => return Ok(()),
+                Err(error) => return Err(transport_error(error)),
+            }
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::GossipNode;
+    use crate::crypto_primitives::utils::rand::test_rng;
+    use crate::discrete_log_cards;
+    use crate::discrete_log_cards::encoding::encode_standard_deck;
+    use crate::discrete_log_cards::identity::IdentityKey;
+    use crate::discrete_log_cards::runner::{run_player, GameOutcome, Role, RunnerRules};
+    use crate::discrete_log_cards::session::Session;
+    use crate::discrete_log_cards::test_helpers::setup_players;
+    use crate::error::CardProtocolError;
+    use crate::BarnettSmartProtocol;
+
+    use std::sync::Arc;
+    use std::time::Duration;
+
+    // Choose elliptic curve setting
+    type Curve = starknet_curve::Projective;
+
+    // Instantiate concrete type for our card protocol
+    type CardProtocol<'a> = discrete_log_cards::DLCards<'a, Curve>;
+
+    const SESSION_ID: &[u8] = b"gossip test";
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 4)]
+    async fn test_three_player_hand() {
+        let rng = &mut test_rng();
+        let m = 4;
+        let n = 13;
+        let num_of_players = 3;
+        let rules = RunnerRules {
+            message_timeout: Duration::from_secs(120),
+        };
+
+        let parameters = Arc::new(CardProtocol::setup(rng, m, n).unwrap());
+        let (keys, _) = setup_players(rng, &parameters, num_of_players).unwrap();
+        let hands = (0..num_of_players)
+            .map(|player| vec![player, num_of_players + player])
+            .collect();
+        let session = Session::new(
+            &parameters,
+            SESSION_ID,
+            (0..num_of_players as u64).collect(),
+            encode_standard_deck(1).unwrap(),
+            hands,
+        )
+        .unwrap();
+
+        let identities = (0..num_of_players)
+            .map(|_| IdentityKey::<Curve>::new(rng).unwrap())
+            .collect::<Vec<_>>();
+        let public = identities
+            .iter()
+            .map(|identity| identity.verifying_key())
+            .collect::<Vec<_>>();
+
+        // Every node listens on localhost and dials the others
+        let mut nodes = Vec::new();
+        for (seat, identity) in identities.into_iter().enumerate() {
+            let node = GossipNode::new(
+                seat,
+                identity,
+                public.clone(),
+                SESSION_ID,
+                "/ip4/127.0.0.1/tcp/0".parse().unwrap(),
+            )
+            .await
+            .unwrap();
+            nodes.push(node);
+        }
+        let addresses = nodes
+            .iter()
+            .map(|node| node.address().clone())
+            .collect::<Vec<_>>();
+
+        let handles = nodes
+            .into_iter()
+            .zip(keys)
+            .enumerate()
+            .map(|(seat, (node, (pk, sk, _)))| {
+                let peers = addresses[..seat].to_vec();
+                let role = Role {
+                    seat,
+                    parameters: parameters.clone(),
+                    session: session.clone(),
+                };
+                let mut rng = test_rng();
+                tokio::spawn(async move {
+                    let mut transport = node.connect(&peers, Duration::from_secs(30)).await?;
+                    let outcome =
+                        run_player(&mut rng, (pk, sk), role, &mut transport, &rules).await;
+                    Ok::<_, CardProtocolError>((outcome, transport))
+                })
+            })
+            .collect::<Vec<_>>();
+
+        let mut outcomes = Vec::new();
+        for handle in handles {
+            outcomes.push(handle.await.unwrap().unwrap());
+        }
+
+        let hands = match &outcomes[0].0 {
+            GameOutcome::Completed { hands } => hands.clone(),
+            outcome => panic!("unexpected outcome {:?}", outcome),
+        };
+        for (outcome, _) in &outcomes {
+            assert_eq!(
+                &GameOutcome::Completed {
+                    hands: hands.clone()
+                },
+                outcome
+            );
+        }
+
+        // Every node holds the same log of each player's messages
+        for player in 0..num_of_players {
+            let head = outcomes[player].1.log_head(player).unwrap();
+            for (_, transport) in &outcomes {
+                assert_eq!(head, transport.log_head(player).unwrap());
+                assert_eq!(
+                    Ok(()),
+                    transport.log(player).unwrap().verify_signatures(&public)
+                );
+            }
+        }
+    }
+}
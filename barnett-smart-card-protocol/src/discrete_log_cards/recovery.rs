@@ -0,0 +1,404 @@
+//! Recovery of the reveal tokens of a player who drops out.
+//!
+//! Without a player's secret key no card masked under the aggregate key can be opened, so a player
+//! who disconnects for good ends the game. In resilience mode every player, at the start of the
+//! game, splits their secret key among the other players with Feldman's verifiable secret sharing:
+//! a Shamir sharing published together with commitments `g^{a_j}` to the coefficients of the
+//! polynomial, against which every holder checks their share and the dealer's public key. If the
+//! dealer drops out, any `t` holders each compute a partial reveal token `c1^{s_i}` with a proof
+//! against the public key `g^{s_i}` of their share. The partial tokens combine, by Lagrange
+//! interpolation in the exponent, into the token the dealer would have produced. The key itself is
+//! never reconstructed.
+//!
+//! Any `t` holders can open every card masked under the aggregate key together with the other
+//! players, so the threshold should be at least a majority of the holders.
+
+use crate::crypto_primitives::utils::format::{Compact, Hex};
+use crate::crypto_primitives::utils::secret::{wipe, SecretScalars};
+use crate::discrete_log_cards::reveal::RevealProof;
+use crate::discrete_log_cards::{
+    DLCards, MaskedCard, Parameters, PlayerSecretKey, PublicKey, RevealToken,
+};
+use crate::error::{CardProtocolError, InputError, VerificationError};
+use crate::BarnettSmartProtocol;
+
+use ark_ec::{AffineCurve, ProjectiveCurve};
+use ark_ff::{Field, One, PrimeField, UniformRand, Zero};
+use ark_serialize::{CanonicalDeserialize, CanonicalSerialize};
+use ark_std::fmt;
+use ark_std::rand::{CryptoRng, Rng};
+use zeroize::Zeroize;
+
+/// Commitments to the coefficients of a sharing polynomial. The first one is the dealer's public
+/// key, and the number of coefficients is the threshold.
+#[derive(Clone, PartialEq, Eq, CanonicalSerialize, CanonicalDeserialize)]
+pub struct FeldmanCommitment<C: ProjectiveCurve> {
+    pub coefficients: Vec<C::Affine>,
+}
+
+/// The share of a secret key held by the player in seat `index - 1`. Its value is never formatted.
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub struct RecoveryShare<C: ProjectiveCurve> {
+    /// Evaluation point of the share, the holder's seat plus one
+    pub index: u64,
+    pub value: C::ScalarField,
+}
+
+impl<C: ProjectiveCurve> Zeroize for RecoveryShare<C> {
+    /// Shares are `Copy`, so they cannot wipe themselves on drop. Call this once a share is no
+    /// longer needed.
+    fn zeroize(&mut self) {
+        wipe(std::slice::from_mut(&mut self.value));
+    }
+}
+
+/// A holder's contribution to the reveal token of a player who dropped out.
+#[derive(Clone)]
+pub struct PartialReveal<C: ProjectiveCurve> {
+    /// Evaluation point of the share it was computed from
+    pub index: u64,
+    pub token: RevealToken<C>,
+    pub proof: RevealProof<C>,
+}
+
+impl<C: ProjectiveCurve> fmt::Debug for FeldmanCommitment<C> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_tuple("FeldmanCommitment")
+            .field(&self.coefficients.iter().map(Hex).collect::<Vec<_>>())
+            .finish()
+    }
+}
+
+impl<C: ProjectiveCurve> fmt::Debug for RecoveryShare<C> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("RecoveryShare")
+            .field("index", &self.index)
+            .field("value", &format_args!("REDACTED"))
+            .finish()
+    }
+}
+
+impl<C: ProjectiveCurve> fmt::Debug for PartialReveal<C> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("PartialReveal")
+            .field("index", &self.index)
+            .field("token", &Compact(&self.token))
+            .field("proof", &Compact(&self.proof))
+            .finish()
+    }
+}
+
+impl<C: ProjectiveCurve> FeldmanCommitment<C> {
+    pub fn threshold(&self) -> usize {
+        self.coefficients.len()
+    }
+
+    /// Check that the commitment shares the public key `pk` of the player in seat `dealer`.
+    pub fn verify_dealer(&self, dealer: usize, pk: &PublicKey<C>) -> Result<(), CardProtocolError> {
+        match self.coefficients.first() {
+            Some(first) if first == pk => Ok(()),
+            _ => Err(CardProtocolError::VerificationFailed(
+                VerificationError::PlayerMessage {
+                    player: dealer,
+                    source: None,
+                },
+            )),
+        }
+    }
+
+    /// Public key `g^{s_i}` of the share with evaluation point `index`.
+    pub fn share_public_key(&self, index: u64) -> PublicKey<C> {
+        let x = C::ScalarField::from(index);
+
+        let mut power = C::ScalarField::one();
+        let mut share_key = C::zero();
+        for coefficient in &self.coefficients {
+            share_key += coefficient.mul(power.into_repr());
+            power *= x;
+        }
+
+        share_key.into_affine()
+    }
+
+    /// Check a share received from the dealer against the commitment.
+    pub fn verify_share(
+        &self,
+        pp: &Parameters<C>,
+        share: &RecoveryShare<C>,
+    ) -> Result<(), CardProtocolError> {
+        let expected = pp.enc_parameters.generator.mul(share.value.into_repr());
+        if expected.into_affine() != self.share_public_key(share.index) {
+            return Err(VerificationError::CorruptedShare(share.index).into());
+        }
+
+        Ok(())
+    }
+}
+
+/// Split `sk` among the players in the seats `holders` such that any `t` of them can recover its
+/// reveal tokens. Publish the commitment and send each share, in the order of `holders`, to its
+/// holder only.
+pub fn share_key_for_recovery<R: Rng + CryptoRng, C: ProjectiveCurve>(
+    rng: &mut R,
+    pp: &Parameters<C>,
+    sk: &PlayerSecretKey<C>,
+    holders: &[usize],
+    t: usize,
+) -> Result<(FeldmanCommitment<C>, Vec<RecoveryShare<C>>), CardProtocolError> {
+    if t == 0 || t > holders.len() {
+        return Err(InputError::InvalidThreshold(t, holders.len()).into());
+    }
+
+    for (i, holder) in holders.iter().enumerate() {
+        if holders[..i].contains(holder) {
+            return Err(InputError::DuplicateShare(*holder as u64 + 1).into());
+        }
+    }
+
+    // Random polynomial of degree t - 1 whose constant term is the key
+    let mut coefficients = SecretScalars::new(Vec::with_capacity(t));
+    coefficients.push(*sk.expose_secret());
+    for _ in 1..t {
+        coefficients.push(C::ScalarField::rand(rng));
+    }
+
+    let commitment = FeldmanCommitment {
+        coefficients: coefficients
+            .iter()
+            .map(|a| pp.enc_parameters.generator.mul(a.into_repr()).into_affine())
+            .collect(),
+    };
+
+    let shares = holders
+        .iter()
+        .map(|&holder| {
+            let index = holder as u64 + 1;
+            let x = C::ScalarField::from(index);
+            let value = coefficients
+                .iter()
+                .rev()
+                .fold(C::ScalarField::zero(), |acc, a| acc * x + a);
+
+            RecoveryShare { index, value }
+        })
+        .collect();
+
+    Ok((commitment, shares))
+}
+
+/// Compute the holder's contribution to the reveal token of the dealer of `share` for
+/// `masked_card`, with a proof against the public key of the share.
+pub fn partial_reveal_token<R: Rng + CryptoRng, C: ProjectiveCurve>(
+    rng: &mut R,
+    pp: &Parameters<C>,
+    context: &[u8],
+    commitment: &FeldmanCommitment<C>,
+    share: &RecoveryShare<C>,
+    masked_card: &MaskedCard<C>,
+) -> Result<PartialReveal<C>, CardProtocolError> {
+    let sk = PlayerSecretKey::<C>::from(share.value);
+    let pk = commitment.share_public_key(share.index);
+
+    let (token, proof) =
+        DLCards::<C>::compute_reveal_token(rng, pp, context, &sk, &pk, masked_card)?;
+
+    Ok(PartialReveal {
+        index: share.index,
+        token,
+        proof,
+    })
+}
+
+/// Verify the contributions of the holders and combine the first `t` of them into the reveal token
+/// of the dealer of `commitment` for `masked_card`. A contribution with an invalid proof is
+/// attributed to the player in seat `index - 1`.
+pub fn recover_reveal_share<C: ProjectiveCurve>(
+    pp: &Parameters<C>,
+    context: &[u8],
+    commitment: &FeldmanCommitment<C>,
+    partials: &[PartialReveal<C>],
+    masked_card: &MaskedCard<C>,
+) -> Result<RevealToken<C>, CardProtocolError> {
+    for (i, partial) in partials.iter().enumerate() {
+        if partials[..i]
+            .iter()
+            .any(|other| other.index == partial.index)
+        {
+            return Err(InputError::DuplicateShare(partial.index).into());
+        }
+
+        let pk = commitment.share_public_key(partial.index);
+        DLCards::<C>::verify_reveal(
+            pp,
+            context,
+            &pk,
+            &partial.token,
+            masked_card,
+            &partial.proof,
+        )
+        .map_err(|source| {
+            CardProtocolError::verification_failed(partial.index as usize - 1, source)
+        })?;
+    }
+
+    let threshold = commitment.threshold();
+    if partials.len() < threshold {
+        return Err(InputError::NotEnoughShares(threshold, partials.len()).into());
+    }
+
+    // Lagrange interpolation at zero, in the exponent, over the first `threshold` contributions
+    let partials = &partials[..threshold];
+    let mut token = RevealToken::<C>::zero();
+    for partial in partials {
+        let x_i = C::ScalarField::from(partial.index);
+        let mut numerator = C::ScalarField::one();
+        let mut denominator = C::ScalarField::one();
+        for other in partials.iter().filter(|other| other.index != partial.index) {
+            let x_j = C::ScalarField::from(other.index);
+            numerator *= x_j;
+            denominator *= x_j - x_i;
+        }
+        let coefficient = numerator
+            * denominator
+                .inverse()
+                .ok_or(CardProtocolError::VerificationFailed(
+                    VerificationError::CorruptedShare(partial.index),
+                ))?;
+
+        token = token + partial.token * coefficient;
+    }
+
+    Ok(token)
+}
+
+#[cfg(test)]
+mod test {
+    use super::{partial_reveal_token, recover_reveal_share, share_key_for_recovery};
+    use crate::crypto_primitives::utils::rand::test_rng;
+    use crate::discrete_log_cards;
+    use crate::discrete_log_cards::deal::DealPlan;
+    use crate::discrete_log_cards::encoding::encode_standard_deck;
+    use crate::discrete_log_cards::reveal::reveal_token;
+    use crate::discrete_log_cards::starting_deck;
+    use crate::discrete_log_cards::test_helpers::setup_players;
+    use crate::error::{CardProtocolError, InputError, VerificationError};
+    use crate::{BarnettSmartProtocol, Reveal};
+
+    use ark_ff::{One, Zero};
+
+    // Choose elliptic curve setting
+    type Curve = starknet_curve::Projective;
+
+    // Instantiate concrete type for our card protocol
+    type CardProtocol<'a> = discrete_log_cards::DLCards<'a, Curve>;
+    type RevealToken = discrete_log_cards::RevealToken<Curve>;
+
+    const SESSION_ID: &[u8] = b"recovery test";
+
+    #[test]
+    fn test_dropout_recovery() {
+        let rng = &mut test_rng();
+        let m = 4;
+        let n = 13;
+        let num_of_players = 4;
+        let t = 2;
+
+        let parameters = CardProtocol::setup(rng, m, n).unwrap();
+        let (keys, aggregate_key) = setup_players(rng, &parameters, num_of_players).unwrap();
+
+        // At the start of the game, player 3 shares their key among the others
+        let dropped = 3;
+        let holders = [0, 1, 2];
+        let (commitment, shares) =
+            share_key_for_recovery(rng, &parameters, &keys[dropped].1, &holders, t).unwrap();
+        assert_eq!(Ok(()), commitment.verify_dealer(dropped, &keys[dropped].0));
+        for share in &shares {
+            assert_eq!(Ok(()), commitment.verify_share(&parameters, share));
+        }
+        assert_eq!(
+            format!(
+                "RecoveryShare {{ index: {}, value: REDACTED }}",
+                shares[0].index
+            ),
+            format!("{:?}", shares[0])
+        );
+
+        let mut tampered = shares[1];
+        tampered.value += <Curve as ark_ec::ProjectiveCurve>::ScalarField::one();
+        assert_eq!(
+            commitment.verify_share(&parameters, &tampered),
+            Err(CardProtocolError::VerificationFailed(
+                VerificationError::CorruptedShare(2)
+            ))
+        );
+
+        let cards = encode_standard_deck::<Curve>(1).unwrap();
+        let deck = starting_deck(&parameters, &aggregate_key, &cards).unwrap();
+        let plan = DealPlan::texas_holdem(num_of_players).unwrap();
+
+        // Player 3 drops out after the deal. Holders 0 and 2 stand in for them on the flop
+        for &index in plan.community(0) {
+            let masked = &deck[index];
+            let partials = [shares[0], shares[2]]
+                .iter()
+                .map(|share| {
+                    partial_reveal_token(rng, &parameters, SESSION_ID, &commitment, share, masked)
+                        .unwrap()
+                })
+                .collect::<Vec<_>>();
+
+            let recovered =
+                recover_reveal_share(&parameters, SESSION_ID, &commitment, &partials, masked)
+                    .unwrap();
+            assert_eq!(recovered, reveal_token(&keys[dropped].1, masked));
+
+            let aggregate_token = (0..num_of_players)
+                .filter(|&player| player != dropped)
+                .map(|player| reveal_token(&keys[player].1, masked))
+                .fold(recovered, |acc, token| acc + token);
+            assert_eq!(aggregate_token.reveal(masked).unwrap(), cards[index]);
+
+            // One contribution is not enough, and contributions must match their share
+            assert_eq!(
+                recover_reveal_share(&parameters, SESSION_ID, &commitment, &partials[..1], masked),
+                Err(CardProtocolError::InvalidInput(
+                    InputError::NotEnoughShares(2, 1)
+                ))
+            );
+            let mut forged = partials.clone();
+            forged[1].token = RevealToken::zero();
+            assert!(matches!(
+                recover_reveal_share(&parameters, SESSION_ID, &commitment, &forged, masked),
+                Err(CardProtocolError::VerificationFailed(
+                    VerificationError::PlayerMessage {
+                        player: 2,
+                        source: Some(_)
+                    }
+                ))
+            ));
+        }
+    }
+
+    #[test]
+    fn test_invalid_sharing_is_rejected() {
+        let rng = &mut test_rng();
+        let m = 4;
+        let n = 13;
+
+        let parameters = CardProtocol::setup(rng, m, n).unwrap();
+        let (_, sk) = CardProtocol::player_keygen(rng, &parameters).unwrap();
+
+        assert_eq!(
+            share_key_for_recovery(rng, &parameters, &sk, &[0, 1], 3).err(),
+            Some(CardProtocolError::InvalidInput(
+                InputError::InvalidThreshold(3, 2)
+            ))
+        );
+        assert_eq!(
+            share_key_for_recovery(rng, &parameters, &sk, &[0, 1, 1], 2).err(),
+            Some(CardProtocolError::InvalidInput(InputError::DuplicateShare(
+                2
+            )))
+        );
+    }
+}
@@ -0,0 +1,677 @@
+//! Broadcast of game messages through a WebSocket relay, as a [`Transport`] for the runner.
+//!
+//! Players that cannot reach each other directly, such as browsers behind NAT, all connect to a
+//! relay which forwards opaque binary frames. The relay is not trusted: as over gossip, every
+//! message is a [`SignedMessage`] numbered by its sender, mirrored into one [`EventLog`] per
+//! player and delivered once it extends the log of its sender. A relay that drops, duplicates,
+//! reorders or forges messages can stall the game, which the runner's timeout ends, but cannot
+//! make a player accept a message its sender did not sign for that slot.
+//!
+//! The relay protocol is made of binary frames on one WebSocket per table:
+//!
+//! - after connecting, the client sends the 8-byte big-endian sequence number of the first frame
+//!   it has not received yet, 0 on its first connection;
+//! - the client then sends the canonical encoding of its signed messages, one per frame;
+//! - the relay numbers every frame it receives in the order it receives them, stores it, and
+//!   forwards it to every connection, the sender's included, as the sequence number followed by
+//!   the frame. It first replays the stored frames from the requested sequence number.
+//!
+//! When the connection is lost, the client reconnects and resumes from the sequence number
+//! after the last frame it received. Since the relay echoes messages back to their sender, the
+//! client knows which of its own messages the relay stored, and sends the rest of its log again.
+//!
+//! On `wasm32` targets the client speaks the same protocol over the `WebSocket` API of the
+//! browser and runs on its event loop; elsewhere it uses tokio-tungstenite on the tokio runtime.
+
+use crate::discrete_log_cards::abort::SignedMessage;
+use crate::discrete_log_cards::event_log::{EntryDigest, EventLog};
+use crate::discrete_log_cards::identity::{IdentityKey, PublicIdentity};
+use crate::discrete_log_cards::runner::{PlayerId, Transport};
+use crate::discrete_log_cards::sender_logs::{lock, SenderLogs};
+use crate::discrete_log_cards::session::GameMessage;
+use crate::discrete_log_cards::validation::{
+    bounded_from_bytes, to_bytes, DecodingLimits, MAX_VECTOR_LENGTH,
+};
+use crate::error::{CardProtocolError, InputError, InternalError};
+
+use ark_ec::ProjectiveCurve;
+use async_trait::async_trait;
+use socket::{next_frame, open, send_frame, spawn, Socket};
+use std::fmt;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tokio::sync::mpsc::{unbounded_channel, UnboundedReceiver, UnboundedSender};
+
+#[cfg(target_arch = "wasm32")]
+use crate::discrete_log_cards::runner::sleep;
+#[cfg(not(target_arch = "wasm32"))]
+use tokio::time::sleep;
+
+/// Largest message sent or accepted, which leaves room for the shuffle of a large deck.
+pub const MAX_RELAY_BYTES: usize = 1 << 20;
+
+/// Length of the sequence numbers the relay prefixes frames with.
+pub const SEQUENCE_LENGTH: usize = 8;
+
+/// Where the relay of a table is and how to recover from a lost connection.
+#[derive(Clone, Debug)]
+pub struct RelayConfig {
+    /// WebSocket URL of the table on the relay, such as `ws://relay.example/tables/42`
+    pub url: String,
+    /// How long to wait before each attempt to reconnect
+    pub reconnect_delay: Duration,
+    /// Attempts to reconnect in a row before the transport gives up
+    pub max_reconnects: usize,
+}
+
+/// Transport of a player connected to a relay.
+pub struct RelayTransport<C: ProjectiveCurve> {
+    identity: IdentityKey<C>,
+    logs: Arc<Mutex<SenderLogs<C>>>,
+    /// Wakes the driver up when the player's log has new messages to send
+    outbox: UnboundedSender<()>,
+    inbox: UnboundedReceiver<(PlayerId, GameMessage<C>)>,
+}
+
+impl<C: ProjectiveCurve> fmt::Debug for RelayTransport<C> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("RelayTransport")
+            .field("identity", &self.identity)
+            .finish_non_exhaustive()
+    }
+}
+
+impl<C: ProjectiveCurve> RelayTransport<C> {
+    /// Connect the player in seat `seat`, who signs with `identity`, to the relay of the table.
+    /// `identities[i]` is the identity of the player in seat `i`. The connection then runs on its
+    /// own task, reconnecting as `config` allows, until the transport is dropped.
+    pub async fn connect(
+        seat: PlayerId,
+        identity: IdentityKey<C>,
+        identities: Vec<PublicIdentity<C>>,
+        context: &[u8],
+        config: RelayConfig,
+    ) -> Result<Self, CardProtocolError> {
+        if seat >= identities.len() {
+            return Err(InputError::UnknownPlayer(seat).into());
+        }
+
+        let socket = open(&config.url).await?;
+
+        let (outbox, outgoing) = unbounded_channel();
+        let (delivered, inbox) = unbounded_channel();
+        let logs = Arc::new(Mutex::new(SenderLogs::new(seat, identities, context)));
+        let driver = Driver {
+            config,
+            logs: logs.clone(),
+            delivered,
+            outgoing,
+            next_sequence: 0,
+            stored: 0,
+        };
+        spawn(driver.run(socket));
+
+        Ok(Self {
+            identity,
+            logs,
+            outbox,
+            inbox,
+        })
+    }
+
+    /// Head of the log of the messages `player` signed, as far as this player has received them.
+    pub fn log_head(&self, player: PlayerId) -> Result<EntryDigest, CardProtocolError> {
+        lock(&self.logs)?
+            .logs
+            .get(player)
+            .ok_or(CardProtocolError::InvalidInput(InputError::UnknownPlayer(
+                player,
+            )))?
+            .head()
+    }
+
+    /// Copy of the log of the messages `player` signed, as far as this player has received them.
+    pub fn log(&self, player: PlayerId) -> Result<EventLog<C>, CardProtocolError> {
+        lock(&self.logs)?
+            .logs
+            .get(player)
+            .cloned()
+            .ok_or(CardProtocolError::InvalidInput(InputError::UnknownPlayer(
+                player,
+            )))
+    }
+}
+
+#[async_trait]
+impl<C: ProjectiveCurve> Transport<C> for RelayTransport<C> {
+    async fn broadcast(&self, message: GameMessage<C>) -> Result<(), CardProtocolError> {
+        lock(&self.logs)?.sign_own(&self.identity, message)?;
+
+        self.outbox.send(()).map_err(|_| {
+            CardProtocolError::Internal(InternalError::TransportError(
+                "the relay connection was lost".to_string(),
+            ))
+        })
+    }
+
+    async fn recv(&mut self) -> Result<(PlayerId, GameMessage<C>), CardProtocolError> {
+        self.inbox.recv().await.ok_or_else(|| {
+            CardProtocolError::Internal(InternalError::TransportError(
+                "the relay connection was lost".to_string(),
+            ))
+        })
+    }
+}
+
+/// Task holding the connection to the relay: it sends the player's messages, delivers the ones
+/// of the other players, and reconnects when the connection is lost.
+struct Driver<C: ProjectiveCurve> {
+    config: RelayConfig,
+    logs: Arc<Mutex<SenderLogs<C>>>,
+    delivered: UnboundedSender<(PlayerId, GameMessage<C>)>,
+    outgoing: UnboundedReceiver<()>,
+    /// Sequence number of the first frame not received from the relay
+    next_sequence: u64,
+    /// Number of the player's own messages the relay echoed back, and so stored
+    stored: u64,
+}
+
+impl<C: ProjectiveCurve> Driver<C> {
+    /// Run until the transport is dropped or the relay cannot be reached any more.
+    async fn run(mut self, mut socket: Socket) {
+        loop {
+            if self.serve(socket).await.is_ok() {
+                return;
+            }
+
+            socket = match self.reconnect().await {
+                Some(socket) => socket,
+                None => return,
+            };
+        }
+    }
+
+    async fn reconnect(&self) -> Option<Socket> {
+        for _ in 0..self.config.max_reconnects {
+            sleep(self.config.reconnect_delay).await;
+            if let Ok(socket) = open(&self.config.url).await {
+                return Some(socket);
+            }
+        }
+
+        None
+    }
+
+    /// Serve one connection, returning once the transport is dropped or with an error once the
+    /// connection is lost.
+    async fn serve(&mut self, mut socket: Socket) -> Result<(), CardProtocolError> {
+        let resume = self.next_sequence.to_be_bytes().to_vec();
+        send_frame(&mut socket, resume).await?;
+
+        // Messages the relay did not echo before the connection was lost may not have reached it
+        let mut sent = self.send_own(&mut socket, self.stored).await?;
+
+        loop {
+            tokio::select! {
+                outgoing = self.outgoing.recv() => match outgoing {
+                    Some(()) => sent = self.send_own(&mut socket, sent).await?,
+                    None => return Ok(()),
+                },
+                frame = next_frame(&mut socket) => self.receive(&frame?),
+            }
+        }
+    }
+
+    /// Send the messages of the player's log from slot `from` on, and return the slot after the
+    /// last one sent.
+    async fn send_own(&self, socket: &mut Socket, from: u64) -> Result<u64, CardProtocolError> {
+        let own = lock(&self.logs)?.own_from(from);
+        let sent = from + own.len() as u64;
+
+        for signed in own {
+            send_frame(socket, to_bytes(&signed)?).await?;
+        }
+
+        Ok(sent)
+    }
+
+    fn receive(&mut self, frame: &[u8]) {
+        if frame.len() < SEQUENCE_LENGTH {
+            return;
+        }
+        let (sequence, payload) = frame.split_at(SEQUENCE_LENGTH);
+        let mut word = [0u8; SEQUENCE_LENGTH];
+        word.copy_from_slice(sequence);
+        let sequence = u64::from_be_bytes(word);
+
+        // Frames replayed after a reconnection may overlap the ones already received
+        if sequence < self.next_sequence {
+            return;
+        }
+        self.next_sequence = sequence + 1;
+
+        let limits = DecodingLimits::new(MAX_VECTOR_LENGTH, MAX_RELAY_BYTES);
+        let signed = match bounded_from_bytes::<SignedMessage<C>>(payload, &limits) {
+            Ok(signed) => signed,
+            Err(_) => return,
+        };
+
+        let delivered = match lock(&self.logs) {
+            Ok(mut logs) => {
+                let seat = logs.seat;
+                if signed.sender == seat {
+                    // Only the player can sign for their seat, so a valid echo is one of theirs
+                    if signed.slot == self.stored
+                        && (signed.slot as usize) < logs.logs[seat].len()
+                        && signed
+                            .verify(logs.logs[seat].context(), &logs.identities)
+                            .is_ok()
+                    {
+                        self.stored += 1;
+                    }
+                    return;
+                }
+                logs.accept(signed)
+            }
+            Err(_) => return,
+        };
+        for message in delivered {
+            // The transport may already be gone, in which case the driver stops on its next turn
+            let _ = self.delivered.send(message);
+        }
+    }
+}
+
+/// Connections through tokio-tungstenite.
+#[cfg(not(target_arch = "wasm32"))]
+mod socket {
+    use super::{MAX_RELAY_BYTES, SEQUENCE_LENGTH};
+    use crate::discrete_log_cards::sender_logs::transport_error;
+    use crate::error::{CardProtocolError, InternalError};
+
+    use futures_util::{SinkExt, StreamExt};
+    use std::future::Future;
+    use tokio::net::TcpStream;
+    use tokio_tungstenite::tungstenite::protocol::WebSocketConfig;
+    use tokio_tungstenite::tungstenite::Message;
+    use tokio_tungstenite::{connect_async_with_config, MaybeTlsStream, WebSocketStream};
+
+    pub(super) type Socket = WebSocketStream<MaybeTlsStream<TcpStream>>;
+
+    pub(super) async fn open(url: &str) -> Result<Socket, CardProtocolError> {
+        let mut config = WebSocketConfig::default();
+        config.max_message_size = Some(SEQUENCE_LENGTH + MAX_RELAY_BYTES);
+        config.max_frame_size = Some(SEQUENCE_LENGTH + MAX_RELAY_BYTES);
+
+        let (socket, _) = connect_async_with_config(url, Some(config), true)
+            .await
+            .map_err(transport_error)?;
+
+        Ok(socket)
+    }
+
+    pub(super) async fn send_frame(
+        socket: &mut Socket,
+        frame: Vec<u8>,
+    ) -> Result<(), CardProtocolError> {
+        socket
+            .send(Message::Binary(frame))
+            .await
+            .map_err(transport_error)
+    }
+
+    /// Next binary frame, or an error once the connection is lost.
+    pub(super) async fn next_frame(socket: &mut Socket) -> Result<Vec<u8>, CardProtocolError> {
+        loop {
+            match socket.next().await {
+                Some(Ok(Message::Binary(frame))) => return Ok(frame),
+                Some(Ok(Message::Close(_))) | None => {
+                    return Err(InternalError::TransportError(
+                        "the relay closed the connection".to_string(),
+                    )
+                    .into())
+                }
+                Some(Ok(_)) => {}
+                Some(Err(error)) => return Err(transport_error(error)),
+            }
+        }
+    }
+
+    pub(super) fn spawn<F: Future<Output = ()> + Send + 'static>(future: F) {
+        tokio::spawn(future);
+    }
+}
+
+/// Connections through the `WebSocket` of the browser. Its events are forwarded to a channel by
+/// callbacks that live as long as the connection.
+#[cfg(target_arch = "wasm32")]
+mod socket {
+    use crate::discrete_log_cards::sender_logs::transport_error;
+    use crate::error::{CardProtocolError, InternalError};
+
+    use std::future::Future;
+    use tokio::sync::mpsc::{unbounded_channel, UnboundedReceiver, UnboundedSender};
+    use wasm_bindgen::closure::Closure;
+    use wasm_bindgen::{JsCast, JsValue};
+    use web_sys::{BinaryType, MessageEvent, WebSocket};
+
+    enum Event {
+        Open,
+        Frame(Vec<u8>),
+        Closed,
+    }
+
+    pub(super) struct Socket {
+        socket: WebSocket,
+        events: UnboundedReceiver<Event>,
+        _callbacks: Vec<Closure<dyn FnMut(JsValue)>>,
+    }
+
+    impl Drop for Socket {
+        fn drop(&mut self) {
+            // The callbacks are dropped with the socket, so the browser must not call them again
+            self.socket.set_onopen(None);
+            self.socket.set_onmessage(None);
+            self.socket.set_onerror(None);
+            self.socket.set_onclose(None);
+            let _ = self.socket.close();
+        }
+    }
+
+    fn callback(
+        events: &UnboundedSender<Event>,
+        event: impl Fn(JsValue) -> Option<Event> + 'static,
+    ) -> Closure<dyn FnMut(JsValue)> {
+        let events = events.clone();
+
+        Closure::wrap(Box::new(move |value: JsValue| {
+            if let Some(event) = event(value) {
+                let _ = events.send(event);
+            }
+        }) as Box<dyn FnMut(JsValue)>)
+    }
+
+    fn closed() -> CardProtocolError {
+        CardProtocolError::Internal(InternalError::TransportError(
+            "the relay closed the connection".to_string(),
+        ))
+    }
+
+    pub(super) async fn open(url: &str) -> Result<Socket, CardProtocolError> {
+        let socket = WebSocket::new(url).map_err(transport_error)?;
+        socket.set_binary_type(BinaryType::Arraybuffer);
+
+        let (sender, events) = unbounded_channel();
+        let on_open = callback(&sender, |_| Some(Event::Open));
+        let on_message = callback(&sender, |value| {
+            let data = value.unchecked_into::<MessageEvent>().data();
+            let buffer = data.dyn_into::<js_sys::ArrayBuffer>().ok()?;
+
+            Some(Event::Frame(js_sys::Uint8Array::new(&buffer).to_vec()))
+        });
+        let on_closed = callback(&sender, |_| Some(Event::Closed));
+        socket.set_onopen(Some(on_open.as_ref().unchecked_ref()));
+        socket.set_onmessage(Some(on_message.as_ref().unchecked_ref()));
+        socket.set_onerror(Some(on_closed.as_ref().unchecked_ref()));
+        socket.set_onclose(Some(on_closed.as_ref().unchecked_ref()));
+
+        let mut socket = Socket {
+            socket,
+            events,
+            _callbacks: vec![on_open, on_message, on_closed],
+        };
+
+        match socket.events.recv().await {
+            Some(Event::Open) => Ok(socket),
+            _ => Err(closed()),
+        }
+    }
+
+    pub(super) async fn send_frame(
+        socket: &mut Socket,
+        frame: Vec<u8>,
+    ) -> Result<(), CardProtocolError> {
+        socket
+            .socket
+            .send_with_u8_array(&frame)
+            .map_err(transport_error)
+    }
+
+    /// Next binary frame, or an error once the connection is lost.
+    pub(super) async fn next_frame(socket: &mut Socket) -> Result<Vec<u8>, CardProtocolError> {
+        loop {
+            match socket.events.recv().await {
+                Some(Event::Frame(frame)) => return Ok(frame),
+                Some(Event::Open) => {}
+                Some(Event::Closed) | None => return Err(closed()),
+            }
+        }
+    }
+
+    pub(super) fn spawn<F: Future<Output = ()> + 'static>(future: F) {
+        wasm_bindgen_futures::spawn_local(future);
+    }
+}
+
+#[cfg(all(test, not(target_arch = "wasm32")))]
+mod test {
+    use super::{RelayConfig, RelayTransport, SEQUENCE_LENGTH};
+    use crate::crypto_primitives::utils::rand::test_rng;
+    use crate::discrete_log_cards;
+    use crate::discrete_log_cards::encoding::encode_standard_deck;
+    use crate::discrete_log_cards::identity::IdentityKey;
+    use crate::discrete_log_cards::runner::{run_player, GameOutcome, Role, RunnerRules};
+    use crate::discrete_log_cards::session::Session;
+    use crate::discrete_log_cards::test_helpers::setup_players;
+    use crate::error::CardProtocolError;
+    use crate::BarnettSmartProtocol;
+
+    use futures_util::{SinkExt, StreamExt};
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::{Arc, Mutex};
+    use std::time::Duration;
+    use tokio::net::{TcpListener, TcpStream};
+    use tokio::sync::broadcast;
+    use tokio_tungstenite::accept_async;
+    use tokio_tungstenite::tungstenite::Message;
+
+    // Choose elliptic curve setting
+    type Curve = starknet_curve::Projective;
+
+    // Instantiate concrete type for our card protocol
+    type CardProtocol<'a> = discrete_log_cards::DLCards<'a, Curve>;
+
+    const SESSION_ID: &[u8] = b"relay test";
+
+    /// Frames the relay stored, each prefixed with its sequence number
+    type History = Arc<Mutex<Vec<Vec<u8>>>>;
+
+    /// Start a relay for one table on localhost, which drops its first connection once it has
+    /// forwarded `drop_after` frames to it. Returns the URL of the table and the number of
+    /// connections accepted so far.
+    async fn start_relay(drop_after: usize) -> (String, Arc<AtomicUsize>) {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("ws://{}", listener.local_addr().unwrap());
+        let connections = Arc::new(AtomicUsize::new(0));
+        let history = History::default();
+        let (frames, _) = broadcast::channel(4096);
+
+        let accepted = connections.clone();
+        tokio::spawn(async move {
+            while let Ok((stream, _)) = listener.accept().await {
+                let limit = match accepted.fetch_add(1, Ordering::SeqCst) {
+                    0 => Some(drop_after),
+                    _ => None,
+                };
+                tokio::spawn(serve(stream, history.clone(), frames.clone(), limit));
+            }
+        });
+
+        (url, connections)
+    }
+
+    async fn serve(
+        stream: TcpStream,
+        history: History,
+        frames: broadcast::Sender<Vec<u8>>,
+        limit: Option<usize>,
+    ) {
+        let mut socket = match accept_async(stream).await {
+            Ok(socket) => socket,
+            Err(_) => return,
+        };
+        let resume = match socket.next().await {
+            Some(Ok(Message::Binary(bytes))) if bytes.len() == SEQUENCE_LENGTH => {
+                let mut word = [0u8; SEQUENCE_LENGTH];
+                word.copy_from_slice(&bytes);
+                u64::from_be_bytes(word) as usize
+            }
+            _ => return,
+        };
+
+        // Subscribe before reading the history, so that no frame falls in between
+        let mut live = frames.subscribe();
+        let replay = history
+            .lock()
+            .unwrap()
+            .get(resume..)
+            .map(<[_]>::to_vec)
+            .unwrap_or_default();
+
+        let mut forwarded = 0;
+        for frame in replay {
+            if socket.send(Message::Binary(frame)).await.is_err() {
+                return;
+            }
+            forwarded += 1;
+        }
+
+        // Dropping the socket cuts the connection without a closing handshake
+        while !limit.is_some_and(|limit| forwarded >= limit) {
+            tokio::select! {
+                frame = live.recv() => match frame {
+                    Ok(frame) => {
+                        if socket.send(Message::Binary(frame)).await.is_err() {
+                            return;
+                        }
+                        forwarded += 1;
+                    }
+                    Err(_) => return,
+                },
+                message = socket.next() => match message {
+                    Some(Ok(Message::Binary(payload))) => {
+                        let mut history = history.lock().unwrap();
+                        let mut frame = (history.len() as u64).to_be_bytes().to_vec();
+                        frame.extend_from_slice(&payload);
+                        history.push(frame.clone());
+                        let _ = frames.send(frame);
+                    }
+                    Some(Ok(_)) => {}
+                    _ => return,
+                },
+            }
+        }
+    }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 4)]
+    async fn test_three_player_hand_with_reconnect() {
+        let rng = &mut test_rng();
+        let m = 4;
+        let n = 13;
+        let num_of_players = 3;
+        let rules = RunnerRules {
+            message_timeout: Duration::from_secs(120),
+        };
+
+        let parameters = Arc::new(CardProtocol::setup(rng, m, n).unwrap());
+        let (keys, _) = setup_players(rng, &parameters, num_of_players).unwrap();
+        let hands = (0..num_of_players)
+            .map(|player| vec![player, num_of_players + player])
+            .collect();
+        let session = Session::new(
+            &parameters,
+            SESSION_ID,
+            (0..num_of_players as u64).collect(),
+            encode_standard_deck(1).unwrap(),
+            hands,
+        )
+        .unwrap();
+
+        let identities = (0..num_of_players)
+            .map(|_| IdentityKey::<Curve>::new(rng).unwrap())
+            .collect::<Vec<_>>();
+        let public = identities
+            .iter()
+            .map(|identity| identity.verifying_key())
+            .collect::<Vec<_>>();
+
+        // The relay cuts the first player off in the middle of the key exchange
+        let (url, connections) = start_relay(4).await;
+        let config = RelayConfig {
+            url,
+            reconnect_delay: Duration::from_millis(50),
+            max_reconnects: 10,
+        };
+
+        let mut transports = Vec::new();
+        for (seat, identity) in identities.into_iter().enumerate() {
+            let transport =
+                RelayTransport::connect(seat, identity, public.clone(), SESSION_ID, config.clone())
+                    .await
+                    .unwrap();
+            transports.push(transport);
+        }
+
+        let handles = transports
+            .into_iter()
+            .zip(keys)
+            .enumerate()
+            .map(|(seat, (mut transport, (pk, sk, _)))| {
+                let role = Role {
+                    seat,
+                    parameters: parameters.clone(),
+                    session: session.clone(),
+                };
+                let mut rng = test_rng();
+                tokio::spawn(async move {
+                    let outcome =
+                        run_player(&mut rng, (pk, sk), role, &mut transport, &rules).await;
+                    Ok::<_, CardProtocolError>((outcome, transport))
+                })
+            })
+            .collect::<Vec<_>>();
+
+        let mut outcomes = Vec::new();
+        for handle in handles {
+            outcomes.push(handle.await.unwrap().unwrap());
+        }
+
+        let hands = match &outcomes[0].0 {
+            GameOutcome::Completed { hands } => hands.clone(),
+            outcome => panic!("unexpected outcome {:?}", outcome),
+        };
+        for (outcome, _) in &outcomes {
+            assert_eq!(
+                &GameOutcome::Completed {
+                    hands: hands.clone()
+                },
+                outcome
+            );
+        }
+
+        // The first player reconnected once
+        assert_eq!(num_of_players + 1, connections.load(Ordering::SeqCst));
+
+        // Every player holds the same log of each player's messages
+        for player in 0..num_of_players {
+            let head = outcomes[player].1.log_head(player).unwrap();
+            for (_, transport) in &outcomes {
+                assert_eq!(head, transport.log_head(player).unwrap());
+                assert_eq!(
+                    Ok(()),
+                    transport.log(player).unwrap().verify_signatures(&public)
+                );
+            }
+        }
+    }
+}
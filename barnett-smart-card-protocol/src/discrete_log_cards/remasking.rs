@@ -23,14 +23,14 @@ impl<C: ProjectiveCurve> Remask<C::ScalarField, ElGamal<C>> for MaskedCard<C> {
 
 #[cfg(test)]
 mod test {
+    use crate::crypto_primitives::utils::rand::test_rng;
     use crate::discrete_log_cards;
+    use crate::discrete_log_cards::test_helpers::setup_players;
     use crate::BarnettSmartProtocol;
 
     use ark_ff::UniformRand;
-    use ark_std::{rand::Rng, Zero};
     use proof_essentials::error::CryptoError;
     use proof_essentials::zkp::proofs::chaum_pedersen_dl_equality;
-    use rand::thread_rng;
 
     // Choose elliptic curve setting
     type Curve = starknet_curve::Projective;
@@ -38,33 +38,14 @@ mod test {
 
     // Instantiate concrete type for our card protocol
     type CardProtocol<'a> = discrete_log_cards::DLCards<'a, Curve>;
-    type CardParameters = discrete_log_cards::Parameters<Curve>;
-    type PublicKey = discrete_log_cards::PublicKey<Curve>;
-    type SecretKey = discrete_log_cards::PlayerSecretKey<Curve>;
 
     type MaskedCard = discrete_log_cards::MaskedCard<Curve>;
 
     type RemaskingProof = chaum_pedersen_dl_equality::proof::Proof<Curve>;
 
-    fn setup_players<R: Rng>(
-        rng: &mut R,
-        parameters: &CardParameters,
-        num_of_players: usize,
-    ) -> (Vec<(PublicKey, SecretKey)>, PublicKey) {
-        let mut players: Vec<(PublicKey, SecretKey)> = Vec::with_capacity(num_of_players);
-        let mut expected_shared_key = PublicKey::zero();
-
-        for i in 0..parameters.n {
-            players.push(CardProtocol::player_keygen(rng, &parameters).unwrap());
-            expected_shared_key = expected_shared_key + players[i].0
-        }
-
-        (players, expected_shared_key)
-    }
-
     #[test]
     fn test_verify_remasking() {
-        let rng = &mut thread_rng();
+        let rng = &mut test_rng();
         let m = 4;
         let n = 13;
 
@@ -72,7 +53,7 @@ mod test {
 
         let parameters = CardProtocol::setup(rng, m, n).unwrap();
 
-        let (_, aggregate_key) = setup_players(rng, &parameters, num_of_players);
+        let (_, aggregate_key) = setup_players(rng, &parameters, num_of_players).unwrap();
 
         let some_masked_card = MaskedCard::rand(rng);
         let some_random = Scalar::rand(rng);
@@ -80,6 +61,7 @@ mod test {
         let (remasked, remasking_proof): (MaskedCard, RemaskingProof) = CardProtocol::remask(
             rng,
             &parameters,
+            b"",
             &aggregate_key,
             &some_masked_card,
             &some_random,
@@ -90,6 +72,7 @@ mod test {
             Ok(()),
             CardProtocol::verify_remask(
                 &parameters,
+                b"",
                 &aggregate_key,
                 &some_masked_card,
                 &remasked,
@@ -102,6 +85,7 @@ mod test {
         assert_eq!(
             CardProtocol::verify_remask(
                 &parameters,
+                b"",
                 &aggregate_key,
                 &some_masked_card,
                 &wrong_output,
@@ -1,10 +1,326 @@
-use crate::discrete_log_cards::RevealToken;
-use crate::error::CardProtocolError;
-use crate::Reveal;
+use crate::crypto_primitives::utils::format::{Compact, Hex, HexBytes, IndexedTokens};
+use crate::crypto_primitives::utils::rand::sample_nonzero;
+use crate::crypto_primitives::utils::secret::SecretScalar;
+use crate::discrete_log_cards::deal::DealPlan;
+use crate::discrete_log_cards::{
+    check_dimension, Card, DLCards, MaskedCard, Parameters, PlayerSecretKey, PublicKey, RevealToken,
+};
+use crate::error::{CardProtocolError, Dimension, InputError, VerificationError};
+use crate::{BarnettSmartProtocol, Reveal};
 
-use ark_ec::ProjectiveCurve;
-use ark_ff::One;
+use ark_ec::{AffineCurve, ProjectiveCurve};
+use ark_ff::{One, PrimeField};
+use ark_std::collections::BTreeMap;
+use ark_std::fmt;
+use ark_std::rand::{CryptoRng, Rng};
+use ark_std::vec;
+use ark_std::vec::Vec;
 use proof_essentials::homomorphic_encryption::{el_gamal, el_gamal::ElGamal};
+use proof_essentials::zkp::proofs::chaum_pedersen_dl_equality;
+
+pub type RevealProof<C> = chaum_pedersen_dl_equality::proof::Proof<C>;
+
+/// Reveal token for `masked_card`, computed with a single scalar multiplication by the secret key.
+pub fn reveal_token<C: ProjectiveCurve>(
+    sk: &PlayerSecretKey<C>,
+    masked_card: &MaskedCard<C>,
+) -> RevealToken<C> {
+    el_gamal::Plaintext(
+        masked_card
+            .0
+            .mul(sk.expose_secret().into_repr())
+            .into_affine(),
+    )
+}
+
+/// Reveal token for `masked_card`, computed from a fresh additive sharing `sk = r + (sk - r)` of
+/// the secret key. The masked card is chosen by other players, and no scalar multiplication of it
+/// uses the key itself, which makes timing and cache side channels on the key much harder to
+/// exploit. The result equals `reveal_token`; `compute_reveal_token` uses this variant when the
+/// `side-channel-hardening` feature is enabled, which it is by default.
+pub fn blinded_reveal_token<R: Rng + CryptoRng, C: ProjectiveCurve>(
+    rng: &mut R,
+    sk: &PlayerSecretKey<C>,
+    masked_card: &MaskedCard<C>,
+) -> Result<RevealToken<C>, CardProtocolError> {
+    let r = SecretScalar::new(sample_nonzero::<C::ScalarField, _>(rng)?);
+    let s = SecretScalar::new(*sk.expose_secret() - *r);
+
+    let base = masked_card.0;
+    Ok(el_gamal::Plaintext(
+        (base.mul(r.into_repr()) + base.mul(s.into_repr())).into_affine(),
+    ))
+}
+
+/// Reveal tokens, with their proofs, for the hole cards `plan` deals to `seat`, computed by the
+/// holder of `sk` for the final shuffled `deck`. Each token is paired with its deck position.
+pub fn reveal_hole_cards<R: Rng + CryptoRng, C: ProjectiveCurve>(
+    rng: &mut R,
+    pp: &Parameters<C>,
+    context: &[u8],
+    plan: &DealPlan,
+    seat: usize,
+    sk: &PlayerSecretKey<C>,
+    pk: &PublicKey<C>,
+    deck: &[MaskedCard<C>],
+) -> Result<Vec<(usize, RevealToken<C>, RevealProof<C>)>, CardProtocolError> {
+    if seat >= plan.num_players() {
+        return Err(InputError::UnknownPlayer(seat).into());
+    }
+
+    reveal_cards(rng, pp, context, plan, plan.hole_cards(seat), sk, pk, deck)
+}
+
+/// Reveal tokens, with their proofs, for the community cards `plan` deals on `street`.
+pub fn reveal_community_cards<R: Rng + CryptoRng, C: ProjectiveCurve>(
+    rng: &mut R,
+    pp: &Parameters<C>,
+    context: &[u8],
+    plan: &DealPlan,
+    street: usize,
+    sk: &PlayerSecretKey<C>,
+    pk: &PublicKey<C>,
+    deck: &[MaskedCard<C>],
+) -> Result<Vec<(usize, RevealToken<C>, RevealProof<C>)>, CardProtocolError> {
+    reveal_cards(rng, pp, context, plan, plan.community(street), sk, pk, deck)
+}
+
+/// Reveal tokens, with their proofs, for the cards at `positions` of the deck dealt by `plan`.
+/// Burned cards are never revealed during the game, so asking for one fails.
+pub fn reveal_cards<R: Rng + CryptoRng, C: ProjectiveCurve>(
+    rng: &mut R,
+    pp: &Parameters<C>,
+    context: &[u8],
+    plan: &DealPlan,
+    positions: &[usize],
+    sk: &PlayerSecretKey<C>,
+    pk: &PublicKey<C>,
+    deck: &[MaskedCard<C>],
+) -> Result<Vec<(usize, RevealToken<C>, RevealProof<C>)>, CardProtocolError> {
+    plan.validate(deck.len())?;
+    check_dimension(Dimension::Deck, pp.num_cards(), deck.len())?;
+
+    if let Some(&position) = positions.iter().find(|&&position| plan.is_burned(position)) {
+        return Err(InputError::BurnedCard(position).into());
+    }
+
+    positions
+        .iter()
+        .map(|&position| {
+            let (token, proof) =
+                DLCards::<C>::compute_reveal_token(rng, pp, context, sk, pk, &deck[position])?;
+            Ok((position, token, proof))
+        })
+        .collect()
+}
+
+/// Open `masked_card` with the token of every player at the table, where `keys[i]` is the public
+/// key of the player in seat `i` and `decryption_key[i]` must hold their token. Unlike
+/// [`BarnettSmartProtocol::unmask`], which opens the card with whatever tokens it is given, a
+/// missing, extra or misattributed token is an error instead of a wrong card.
+pub fn unmask_from_players<C: ProjectiveCurve>(
+    pp: &Parameters<C>,
+    context: &[u8],
+    keys: &[PublicKey<C>],
+    decryption_key: &Vec<(RevealToken<C>, RevealProof<C>, PublicKey<C>)>,
+    masked_card: &MaskedCard<C>,
+) -> Result<Card<C>, CardProtocolError> {
+    check_dimension(Dimension::RevealTokens, keys.len(), decryption_key.len())?;
+    if let Some(seat) = (0..keys.len()).find(|&seat| decryption_key[seat].2 != keys[seat]) {
+        return Err(VerificationError::PlayerMessage {
+            player: seat,
+            source: None,
+        }
+        .into());
+    }
+
+    DLCards::<C>::unmask(pp, context, keys.len(), decryption_key, masked_card)
+}
+
+/// Reveal tokens for the hole cards of `recipient`, to be sent to that player only.
+#[derive(Clone)]
+pub struct HoleCardTokens<C: ProjectiveCurve> {
+    pub recipient: usize,
+    pub tokens: Vec<(usize, RevealToken<C>, RevealProof<C>)>,
+}
+
+impl<C: ProjectiveCurve> fmt::Debug for HoleCardTokens<C> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("HoleCardTokens")
+            .field("recipient", &self.recipient)
+            .field("tokens", &IndexedTokens(&self.tokens))
+            .finish()
+    }
+}
+
+/// Everything the player in `seat` has to send to deal the hole cards of `plan`: one message per
+/// other seat, holding the tokens for that seat's hole cards. The player never reveals their own
+/// hole cards, which they open with their secret key once the other players' tokens are in.
+pub fn deal_hole_cards<R: Rng + CryptoRng, C: ProjectiveCurve>(
+    rng: &mut R,
+    pp: &Parameters<C>,
+    context: &[u8],
+    plan: &DealPlan,
+    seat: usize,
+    sk: &PlayerSecretKey<C>,
+    pk: &PublicKey<C>,
+    deck: &[MaskedCard<C>],
+) -> Result<Vec<HoleCardTokens<C>>, CardProtocolError> {
+    if seat >= plan.num_players() {
+        return Err(InputError::UnknownPlayer(seat).into());
+    }
+
+    (0..plan.num_players())
+        .filter(|&recipient| recipient != seat)
+        .map(|recipient| {
+            Ok(HoleCardTokens {
+                recipient,
+                tokens: reveal_hole_cards(rng, pp, context, plan, recipient, sk, pk, deck)?,
+            })
+        })
+        .collect()
+}
+
+/// Tokens received by the player in `seat` for their own hole cards. Every token is verified on
+/// receipt, and a hole card can be peeked at as soon as every other player has revealed it.
+pub struct HoleCardCollector<C: ProjectiveCurve> {
+    context: Vec<u8>,
+    seat: usize,
+    keys: Vec<PublicKey<C>>,
+    deck: Vec<MaskedCard<C>>,
+    /// Verified tokens for each hole card of `seat`, by deck index and then by player
+    tokens: BTreeMap<usize, Vec<Option<RevealToken<C>>>>,
+}
+
+/// The deck is left out, and the tokens are printed by deck index and then by player.
+impl<C: ProjectiveCurve> fmt::Debug for HoleCardCollector<C> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let tokens = self
+            .tokens
+            .iter()
+            .map(|(index, tokens)| (index, Compact(tokens)));
+
+        f.debug_struct("HoleCardCollector")
+            .field("context", &HexBytes(&self.context))
+            .field("seat", &self.seat)
+            .field("keys", &self.keys.iter().map(Hex).collect::<Vec<_>>())
+            .field("tokens", &tokens.collect::<BTreeMap<_, _>>())
+            .finish_non_exhaustive()
+    }
+}
+
+impl<C: ProjectiveCurve> HoleCardCollector<C> {
+    /// Collect tokens for the hole cards `plan` deals to `seat` from the final `deck`. `keys[i]`
+    /// is the public key of the player in seat `i`.
+    pub fn new(
+        context: &[u8],
+        plan: &DealPlan,
+        seat: usize,
+        keys: Vec<PublicKey<C>>,
+        deck: Vec<MaskedCard<C>>,
+    ) -> Result<Self, CardProtocolError> {
+        check_dimension(Dimension::PublicKeys, plan.num_players(), keys.len())?;
+        if seat >= keys.len() {
+            return Err(InputError::UnknownPlayer(seat).into());
+        }
+        plan.validate(deck.len())?;
+
+        let tokens = plan
+            .hole_cards(seat)
+            .iter()
+            .map(|&index| (index, vec![None; keys.len()]))
+            .collect();
+
+        Ok(Self {
+            context: context.to_vec(),
+            seat,
+            keys,
+            deck,
+            tokens,
+        })
+    }
+
+    /// Players whose token for the hole card at `index` is still missing.
+    pub fn missing(&self, index: usize) -> Result<Vec<usize>, CardProtocolError> {
+        let tokens = self
+            .tokens
+            .get(&index)
+            .ok_or(CardProtocolError::InvalidInput(InputError::CardNotInHand {
+                seat: self.seat,
+                index,
+            }))?;
+
+        Ok((0..self.keys.len())
+            .filter(|&player| player != self.seat && tokens[player].is_none())
+            .collect())
+    }
+
+    /// Whether every other player has revealed the hole card at `index`.
+    pub fn is_ready(&self, index: usize) -> bool {
+        matches!(self.missing(index).as_deref(), Ok([]))
+    }
+
+    /// Verify and record tokens sent by `player`. Tokens for cards that are not hole cards of this
+    /// seat, that `player` already sent, or that the owner sent themselves, are rejected, as is the
+    /// whole message if any proof fails.
+    pub fn receive(
+        &mut self,
+        pp: &Parameters<C>,
+        player: usize,
+        tokens: Vec<(usize, RevealToken<C>, RevealProof<C>)>,
+    ) -> Result<(), CardProtocolError> {
+        let pk = *self
+            .keys
+            .get(player)
+            .ok_or(CardProtocolError::InvalidInput(InputError::UnknownPlayer(
+                player,
+            )))?;
+
+        // Check every token before storing any, so that a rejected message changes nothing
+        let mut seen = Vec::with_capacity(tokens.len());
+        for (index, token, proof) in &tokens {
+            let due = player != self.seat
+                && !seen.contains(index)
+                && matches!(self.tokens.get(index), Some(slot) if slot[player].is_none());
+            if !due {
+                return Err(InputError::UnexpectedReveal(*index).into());
+            }
+            seen.push(*index);
+
+            DLCards::<C>::verify_reveal(pp, &self.context, &pk, token, &self.deck[*index], proof)
+                .map_err(|source| CardProtocolError::verification_failed(player, source))?;
+        }
+
+        for (index, token, _) in tokens {
+            if let Some(slot) = self.tokens.get_mut(&index) {
+                slot[player] = Some(token);
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Open the hole card at `index` with the secret key of its owner. Fails with the players who
+    /// have not revealed it yet.
+    pub fn peek(
+        &self,
+        index: usize,
+        sk: &PlayerSecretKey<C>,
+    ) -> Result<Card<C>, CardProtocolError> {
+        let missing = self.missing(index)?;
+        if !missing.is_empty() {
+            return Err(InputError::MissingReveals(missing).into());
+        }
+
+        let masked_card = &self.deck[index];
+        let aggregate_token = self.tokens[&index]
+            .iter()
+            .flatten()
+            .fold(reveal_token(sk, masked_card), |acc, token| acc + *token);
+
+        aggregate_token.reveal(masked_card)
+    }
+}
 
 impl<C: ProjectiveCurve> Reveal<C::ScalarField, ElGamal<C>> for RevealToken<C> {
     fn reveal(
@@ -21,13 +337,26 @@ impl<C: ProjectiveCurve> Reveal<C::ScalarField, ElGamal<C>> for RevealToken<C> {
 
 #[cfg(test)]
 mod test {
+    use super::{
+        blinded_reveal_token, deal_hole_cards, reveal_cards, reveal_community_cards,
+        reveal_hole_cards, reveal_token, HoleCardCollector,
+    };
+    use crate::crypto_primitives::utils::rand::test_rng;
     use crate::discrete_log_cards;
+    use crate::discrete_log_cards::deal::DealPlan;
+    use crate::discrete_log_cards::encoding::encode_standard_deck;
+    use crate::discrete_log_cards::starting_deck;
+    use crate::discrete_log_cards::test_helpers::setup_players;
+    use crate::error::{CardProtocolError, InputError};
     use crate::BarnettSmartProtocol;
 
     use ark_ff::UniformRand;
     use proof_essentials::error::CryptoError;
     use proof_essentials::zkp::proofs::chaum_pedersen_dl_equality;
-    use rand::thread_rng;
+    #[cfg(not(debug_assertions))]
+    use rand::Rng;
+    #[cfg(not(debug_assertions))]
+    use std::time::Instant;
 
     // Choose elliptic curve setting
     type Curve = starknet_curve::Projective;
@@ -42,7 +371,7 @@ mod test {
 
     #[test]
     fn test_verify_reveal() {
-        let rng = &mut thread_rng();
+        let rng = &mut test_rng();
         let m = 4;
         let n = 13;
 
@@ -53,13 +382,14 @@ mod test {
         let some_masked_card = MaskedCard::rand(rng);
 
         let (reveal_token, reveal_proof): (RevealToken, RevealProof) =
-            CardProtocol::compute_reveal_token(rng, &parameters, &sk, &pk, &some_masked_card)
+            CardProtocol::compute_reveal_token(rng, &parameters, b"", &sk, &pk, &some_masked_card)
                 .unwrap();
 
         assert_eq!(
             Ok(()),
             CardProtocol::verify_reveal(
                 &parameters,
+                b"",
                 &pk,
                 &reveal_token,
                 &some_masked_card,
@@ -72,6 +402,7 @@ mod test {
         assert_eq!(
             CardProtocol::verify_reveal(
                 &parameters,
+                b"",
                 &pk,
                 &wrong_reveal,
                 &some_masked_card,
@@ -82,4 +413,261 @@ mod test {
             )))
         )
     }
+
+    #[test]
+    fn test_blinded_reveal_token() {
+        let rng = &mut test_rng();
+        let m = 4;
+        let n = 13;
+
+        let parameters = CardProtocol::setup(rng, m, n).unwrap();
+        let (pk, sk) = CardProtocol::player_keygen(rng, &parameters).unwrap();
+
+        for _ in 0..10 {
+            let masked_card = MaskedCard::rand(rng);
+
+            let expected = reveal_token(&sk, &masked_card);
+            assert_eq!(Ok(expected), blinded_reveal_token(rng, &sk, &masked_card));
+
+            // The protocol computes the same token whichever variant it uses
+            let (token, proof) =
+                CardProtocol::compute_reveal_token(rng, &parameters, b"", &sk, &pk, &masked_card)
+                    .unwrap();
+            assert_eq!(expected, token);
+            assert_eq!(
+                Ok(()),
+                CardProtocol::verify_reveal(&parameters, b"", &pk, &token, &masked_card, &proof)
+            );
+        }
+    }
+
+    #[test]
+    fn test_reveal_from_plan() {
+        let rng = &mut test_rng();
+        let m = 4;
+        let n = 13;
+
+        let parameters = CardProtocol::setup(rng, m, n).unwrap();
+        let (pk, sk) = CardProtocol::player_keygen(rng, &parameters).unwrap();
+        let deck = (0..m * n)
+            .map(|_| MaskedCard::rand(rng))
+            .collect::<Vec<_>>();
+
+        // Two players building the plan on their own pick the same positions
+        let plan = DealPlan::texas_holdem(4).unwrap();
+        let other_plan = DealPlan::new(4, 2, &[3, 1, 1], 1).unwrap();
+
+        let hole_cards =
+            reveal_hole_cards(rng, &parameters, b"", &plan, 2, &sk, &pk, &deck).unwrap();
+        let positions = hole_cards.iter().map(|(i, _, _)| *i).collect::<Vec<_>>();
+        assert_eq!(positions, other_plan.hole_cards(2));
+
+        for (position, token, proof) in &hole_cards {
+            assert_eq!(*token, reveal_token(&sk, &deck[*position]));
+            assert_eq!(
+                Ok(()),
+                CardProtocol::verify_reveal(&parameters, b"", &pk, token, &deck[*position], proof)
+            );
+        }
+
+        let flop =
+            reveal_community_cards(rng, &parameters, b"", &plan, 0, &sk, &pk, &deck).unwrap();
+        let positions = flop.iter().map(|(i, _, _)| *i).collect::<Vec<_>>();
+        assert_eq!(positions, other_plan.community(0));
+
+        let burned = plan.burns(0);
+        assert_eq!(
+            reveal_cards(rng, &parameters, b"", &plan, burned, &sk, &pk, &deck).err(),
+            Some(CardProtocolError::InvalidInput(InputError::BurnedCard(
+                burned[0]
+            )))
+        );
+        assert_eq!(
+            reveal_hole_cards(rng, &parameters, b"", &plan, 4, &sk, &pk, &deck).err(),
+            Some(CardProtocolError::InvalidInput(InputError::UnknownPlayer(
+                4
+            )))
+        );
+        assert_eq!(
+            reveal_hole_cards(rng, &parameters, b"", &plan, 0, &sk, &pk, &deck[..10]).err(),
+            Some(CardProtocolError::InvalidInput(InputError::InvalidDeal(10)))
+        );
+    }
+
+    #[test]
+    fn test_deal_hole_cards() {
+        let rng = &mut test_rng();
+        let m = 4;
+        let n = 13;
+        let num_of_players = 6;
+
+        let parameters = CardProtocol::setup(rng, m, n).unwrap();
+        let (keys, aggregate_key) = setup_players(rng, &parameters, num_of_players).unwrap();
+        let public_keys = keys.iter().map(|(pk, _, _)| *pk).collect::<Vec<_>>();
+
+        // Cards in the clear, masked under the aggregate key
+        let cards = encode_standard_deck::<Curve>(1).unwrap();
+        let deck = starting_deck(&parameters, &aggregate_key, &cards).unwrap();
+        let plan = DealPlan::texas_holdem(num_of_players).unwrap();
+
+        let mut collectors = (0..num_of_players)
+            .map(|seat| {
+                HoleCardCollector::new(b"", &plan, seat, public_keys.clone(), deck.clone()).unwrap()
+            })
+            .collect::<Vec<_>>();
+
+        for (seat, (pk, sk, _)) in keys.iter().enumerate() {
+            let messages =
+                deal_hole_cards(rng, &parameters, b"", &plan, seat, sk, pk, &deck).unwrap();
+
+            // One message to every other seat, none of which reveals the sender's own cards
+            let recipients = messages.iter().map(|m| m.recipient).collect::<Vec<_>>();
+            let expected = (0..num_of_players)
+                .filter(|&p| p != seat)
+                .collect::<Vec<_>>();
+            assert_eq!(recipients, expected);
+            for message in &messages {
+                let indices = message
+                    .tokens
+                    .iter()
+                    .map(|(i, _, _)| *i)
+                    .collect::<Vec<_>>();
+                assert_eq!(indices, plan.hole_cards(message.recipient));
+                assert!(indices.iter().all(|i| !plan.hole_cards(seat).contains(i)));
+            }
+
+            // Tokens routed to the wrong seat are refused
+            let misrouted = messages[0].tokens.clone();
+            let wrong_seat = (messages[0].recipient + 1) % num_of_players;
+            assert_eq!(
+                collectors[wrong_seat].receive(&parameters, seat, misrouted.clone()),
+                Err(CardProtocolError::InvalidInput(
+                    InputError::UnexpectedReveal(misrouted[0].0)
+                ))
+            );
+
+            for message in messages {
+                collectors[message.recipient]
+                    .receive(&parameters, seat, message.tokens)
+                    .unwrap();
+            }
+
+            // Until the last player has sent their tokens, nobody can peek
+            if seat + 1 < num_of_players {
+                let index = plan.hole_cards(num_of_players - 1)[0];
+                let last = &collectors[num_of_players - 1];
+                assert!(!last.is_ready(index));
+                assert_eq!(
+                    last.peek(index, &keys[num_of_players - 1].1),
+                    Err(CardProtocolError::InvalidInput(InputError::MissingReveals(
+                        (seat + 1..num_of_players - 1).collect()
+                    )))
+                );
+            }
+        }
+
+        // The owner cannot contribute to their own cards, and nobody can send a token twice
+        let (pk, sk, _) = &keys[0];
+        let own = reveal_hole_cards(rng, &parameters, b"", &plan, 0, sk, pk, &deck).unwrap();
+        assert_eq!(
+            collectors[0].receive(&parameters, 0, own.clone()),
+            Err(CardProtocolError::InvalidInput(
+                InputError::UnexpectedReveal(own[0].0)
+            ))
+        );
+        let (pk, sk, _) = &keys[1];
+        let again = reveal_hole_cards(rng, &parameters, b"", &plan, 0, sk, pk, &deck).unwrap();
+        assert_eq!(
+            collectors[0].receive(&parameters, 1, again.clone()),
+            Err(CardProtocolError::InvalidInput(
+                InputError::UnexpectedReveal(again[0].0)
+            ))
+        );
+
+        // Every player opens exactly their own hole cards
+        for (seat, collector) in collectors.iter().enumerate() {
+            for &index in plan.hole_cards(seat) {
+                assert!(collector.is_ready(index));
+                assert_eq!(collector.peek(index, &keys[seat].1), Ok(cards[index]));
+            }
+
+            let other = plan.hole_cards((seat + 1) % num_of_players)[0];
+            assert!(!collector.is_ready(other));
+            assert_eq!(
+                collector.peek(other, &keys[seat].1),
+                Err(CardProtocolError::InvalidInput(InputError::CardNotInHand {
+                    seat,
+                    index: other
+                }))
+            );
+        }
+    }
+
+    /// Welch's t-statistic of two timing samples, as used by dudect.
+    #[cfg(not(debug_assertions))]
+    fn welch_t(a: &[f64], b: &[f64]) -> f64 {
+        let mean = |x: &[f64]| x.iter().sum::<f64>() / x.len() as f64;
+        let var = |x: &[f64], m: f64| {
+            x.iter().map(|v| (v - m) * (v - m)).sum::<f64>() / (x.len() - 1) as f64
+        };
+
+        let (mean_a, mean_b) = (mean(a), mean(b));
+        let (var_a, var_b) = (var(a, mean_a), var(b, mean_b));
+
+        (mean_a - mean_b) / (var_a / a.len() as f64 + var_b / b.len() as f64).sqrt()
+    }
+
+    /// Dudect-style leakage check: time the computation with a fixed secret key and with random
+    /// secret keys, interleaved at random, and test whether the two timing distributions differ.
+    /// Timing is only meaningful in release builds, so the test only exists there. CI runs it in
+    /// a step of its own, with nothing else running alongside.
+    #[test]
+    #[cfg(not(debug_assertions))]
+    fn test_reveal_token_timing_does_not_depend_on_input() {
+        let rng = &mut test_rng();
+        let m = 4;
+        let n = 13;
+        let samples_per_class = 2000;
+
+        let parameters = CardProtocol::setup(rng, m, n).unwrap();
+        let (fixed_pk, fixed_sk) = CardProtocol::player_keygen(rng, &parameters).unwrap();
+
+        let card = MaskedCard::rand(rng);
+
+        let mut fixed_timings = Vec::with_capacity(samples_per_class);
+        let mut random_timings = Vec::with_capacity(samples_per_class);
+        while fixed_timings.len() < samples_per_class || random_timings.len() < samples_per_class {
+            let use_fixed = rng.gen::<bool>();
+            let (pk, sk) = if use_fixed {
+                (fixed_pk, fixed_sk.clone())
+            } else {
+                CardProtocol::player_keygen(rng, &parameters).unwrap()
+            };
+
+            let start = Instant::now();
+            let result = CardProtocol::compute_reveal_token(rng, &parameters, b"", &sk, &pk, &card);
+            let elapsed = start.elapsed().as_nanos() as f64;
+            assert!(result.is_ok());
+
+            match use_fixed {
+                true if fixed_timings.len() < samples_per_class => fixed_timings.push(elapsed),
+                false if random_timings.len() < samples_per_class => random_timings.push(elapsed),
+                _ => {}
+            }
+        }
+
+        // Discard the slowest tenth of each class, which is dominated by scheduler noise
+        for timings in [&mut fixed_timings, &mut random_timings] {
+            timings.sort_by(|a, b| a.partial_cmp(b).unwrap());
+            timings.truncate(samples_per_class * 9 / 10);
+        }
+
+        // The threshold above which dudect reports a leak
+        let t = welch_t(&fixed_timings, &random_timings);
+        assert!(
+            t.abs() < 4.5,
+            "timing depends on the secret key (t = {})",
+            t
+        );
+    }
 }
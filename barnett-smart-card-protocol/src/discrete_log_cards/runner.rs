@@ -0,0 +1,591 @@
+//! Asynchronous driver for one player of a [`Session`].
+//!
+//! [`run_player`] plays a complete hand over a [`Transport`]: it publishes the player's key and
+//! its ownership proof, shuffles when its seat comes up, sends reveal tokens for the cards dealt
+//! to the other players and finally opens its own cards. Every message from the other players is
+//! applied to a local copy of the session, which verifies it, so each player reaches the outcome on
+//! its own. A rejected message ends the hand with [`GameOutcome::Misbehavior`], and a wait longer
+//! than the timeout of the [`RunnerRules`] with [`GameOutcome::TimedOut`].
+//!
+//! Transports deliver the messages of a sender in the order they were sent, but not necessarily
+//! in the order of the game across senders: the shuffle of the next seat may overtake the one it
+//! builds on. Messages for a later phase are therefore held back until the session reaches it.
+//!
+//! The protocol calls run on the task driving the player. A server running many tables should
+//! drive each player on a runtime that tolerates long polls, since a shuffle takes a while. In a
+//! browser, where tokio has no timer, the player runs on the event loop of the page, for instance
+//! with `wasm_bindgen_futures::spawn_local`, and waits with `setTimeout`.
+
+use crate::discrete_log_cards::session::{GameMessage, Session, SessionPhase};
+use crate::discrete_log_cards::{DLCards, Parameters, PlayerSecretKey, PublicKey};
+use crate::error::{CardProtocolError, InputError, InternalError};
+use crate::BarnettSmartProtocol;
+
+use ark_ec::ProjectiveCurve;
+use ark_ff::ToBytes;
+use ark_serialize::{CanonicalDeserialize, CanonicalSerialize};
+use async_trait::async_trait;
+use rand::{CryptoRng, Rng};
+use std::fmt;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::sync::mpsc::{unbounded_channel, UnboundedReceiver, UnboundedSender};
+#[cfg(not(target_arch = "wasm32"))]
+use tokio::time::timeout;
+#[cfg(target_arch = "wasm32")]
+use wasm_bindgen::{JsCast, JsValue};
+
+/// Seat of a player, which is their index in the session.
+pub type PlayerId = usize;
+
+/// Channel between a player and the rest of the table.
+#[async_trait]
+pub trait Transport<C: ProjectiveCurve>: Send + Sync {
+    /// Send `message` to every other player.
+    async fn broadcast(&self, message: GameMessage<C>) -> Result<(), CardProtocolError>;
+
+    /// Next message from another player, with their seat. Messages from one sender must arrive in
+    /// the order they were sent.
+    async fn recv(&mut self) -> Result<(PlayerId, GameMessage<C>), CardProtocolError>;
+}
+
+/// The seat a player takes at a table, with the public data of the game.
+pub struct Role<C: ProjectiveCurve, B: CanonicalSerialize + CanonicalDeserialize> {
+    pub seat: PlayerId,
+    pub parameters: Arc<Parameters<C>>,
+    /// The session every player starts from
+    pub session: Session<C, B>,
+}
+
+impl<C, B> fmt::Debug for Role<C, B>
+where
+    C: ProjectiveCurve,
+    B: CanonicalSerialize + CanonicalDeserialize,
+{
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Role")
+            .field("seat", &self.seat)
+            .field("parameters", &self.parameters)
+            .field("session", &self.session)
+            .finish()
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct RunnerRules {
+    /// How long to wait for the next message before giving up on the players the game waits for
+    pub message_timeout: Duration,
+}
+
+#[derive(Debug, PartialEq)]
+pub enum GameOutcome {
+    /// Every player opened their hand. `hands[i]` lists the cards of player `i` as positions in
+    /// the open cards of the session.
+    Completed { hands: Vec<Vec<usize>> },
+    /// No message arrived in time while the game waited for the players in `waiting_for`
+    TimedOut {
+        phase: SessionPhase,
+        waiting_for: Vec<PlayerId>,
+    },
+    /// `player` sent a message the session rejected
+    Misbehavior {
+        player: PlayerId,
+        error: CardProtocolError,
+    },
+    /// The transport failed, or the player could not produce their own message
+    Failed(CardProtocolError),
+}
+
+/// Play a hand in the seat of `role` with the key pair `keys`, until it completes or fails. The
+/// proofs and shuffles of the player draw their randomness from `rng`.
+pub async fn run_player<R, C, B, T>(
+    rng: &mut R,
+    keys: (PublicKey<C>, PlayerSecretKey<C>),
+    role: Role<C, B>,
+    transport: &mut T,
+    rules: &RunnerRules,
+) -> GameOutcome
+where
+    R: Rng + CryptoRng,
+    C: ProjectiveCurve,
+    B: ToBytes + Clone + CanonicalSerialize + CanonicalDeserialize + Send,
+    T: Transport<C>,
+{
+    let Role {
+        seat,
+        parameters,
+        mut session,
+    } = role;
+    let num_of_players = session.num_of_players();
+
+    // Messages received ahead of their phase, and the number of token messages from each player
+    let mut pending: Vec<(PlayerId, SessionPhase, GameMessage<C>)> = Vec::new();
+    let mut token_messages = vec![0; num_of_players];
+
+    loop {
+        if let Some(position) = pending
+            .iter()
+            .position(|(_, phase, _)| *phase == session.phase())
+        {
+            let (player, _, message) = pending.remove(position);
+            if let Err(error) = session.receive(&parameters, player, message) {
+                return GameOutcome::Misbehavior { player, error };
+            }
+            continue;
+        }
+
+        if session.phase() == SessionPhase::Done {
+            let hands = (0..num_of_players)
+                .map(|player| session.opened_hand(player).unwrap_or_default())
+                .collect();
+            return GameOutcome::Completed { hands };
+        }
+
+        if session.awaits(seat) {
+            let message = match own_message(rng, &parameters, &session, seat, &keys) {
+                Ok(message) => message,
+                Err(error) => return GameOutcome::Failed(error),
+            };
+            if let Err(error) = session.receive(&parameters, seat, message.clone()) {
+                return GameOutcome::Failed(error);
+            }
+            if let Err(error) = transport.broadcast(message).await {
+                return GameOutcome::Failed(error);
+            }
+            continue;
+        }
+
+        let (player, message) = match timeout(rules.message_timeout, transport.recv()).await {
+            Ok(Ok(received)) => received,
+            Ok(Err(error)) => return GameOutcome::Failed(error),
+            Err(_) => {
+                return GameOutcome::TimedOut {
+                    phase: session.phase(),
+                    waiting_for: (0..num_of_players)
+                        .filter(|&player| session.awaits(player))
+                        .collect(),
+                }
+            }
+        };
+
+        if player >= num_of_players {
+            return GameOutcome::Misbehavior {
+                player,
+                error: CardProtocolError::InvalidInput(InputError::UnknownPlayer(player)),
+            };
+        }
+
+        let phase = message_phase(&session, player, &message, &mut token_messages);
+        if phase_rank(phase) > phase_rank(session.phase()) {
+            pending.push((player, phase, message));
+        } else if let Err(error) = session.receive(&parameters, player, message) {
+            return GameOutcome::Misbehavior { player, error };
+        }
+    }
+}
+
+/// Wait for `duration` with the `setTimeout` of the global scope, which exists in pages and
+/// workers alike.
+#[cfg(target_arch = "wasm32")]
+pub(crate) async fn sleep(duration: Duration) {
+    let millis = duration.as_millis().min(i32::MAX as u128) as i32;
+    let promise = js_sys::Promise::new(&mut |resolve, reject| {
+        let scheduled = js_sys::Reflect::get(&js_sys::global(), &JsValue::from_str("setTimeout"))
+            .and_then(|set_timeout| set_timeout.dyn_into::<js_sys::Function>())
+            .and_then(|set_timeout| {
+                set_timeout.call2(&JsValue::UNDEFINED, &resolve, &JsValue::from(millis))
+            });
+        if let Err(error) = scheduled {
+            let _ = reject.call1(&JsValue::UNDEFINED, &error);
+        }
+    });
+
+    // Without a timer the wait cannot end, rather than end early
+    if wasm_bindgen_futures::JsFuture::from(promise).await.is_err() {
+        std::future::pending::<()>().await;
+    }
+}
+
+/// `tokio::time::timeout` on the timer of [`sleep`].
+#[cfg(target_arch = "wasm32")]
+async fn timeout<F: std::future::Future>(duration: Duration, future: F) -> Result<F::Output, ()> {
+    tokio::select! {
+        output = future => Ok(output),
+        _ = sleep(duration) => Err(()),
+    }
+}
+
+/// The message the session waits for from `seat`.
+fn own_message<R, C, B>(
+    rng: &mut R,
+    pp: &Parameters<C>,
+    session: &Session<C, B>,
+    seat: PlayerId,
+    (pk, sk): &(PublicKey<C>, PlayerSecretKey<C>),
+) -> Result<GameMessage<C>, CardProtocolError>
+where
+    R: Rng + CryptoRng,
+    C: ProjectiveCurve,
+    B: ToBytes + Clone + CanonicalSerialize + CanonicalDeserialize,
+{
+    let context = session.context();
+
+    match session.phase() {
+        SessionPhase::KeyExchange => Ok(GameMessage::PublicKey(*pk)),
+        SessionPhase::KeyOwnership => {
+            let key_list_digest = session
+                .key_list_digest()?
+                .ok_or(CardProtocolError::InvalidInput(InputError::UnexpectedPhase))?;
+            let proof = DLCards::<C>::prove_key_ownership(
+                rng,
+                pp,
+                context,
+                pk,
+                sk,
+                &session.player_info()[seat],
+                seat,
+                &key_list_digest,
+            )?;
+
+            Ok(GameMessage::KeyOwnership(proof))
+        }
+        SessionPhase::Shuffle(_) => {
+            let aggregate_key = session
+                .aggregate_key()
+                .ok_or(CardProtocolError::InvalidInput(InputError::UnexpectedPhase))?;
+            let (deck, proof) = DLCards::<C>::shuffle_and_remask_with_rng(
+                rng,
+                pp,
+                context,
+                &aggregate_key,
+                &session.deck().to_vec(),
+            )?;
+
+            Ok(GameMessage::Shuffle { deck, proof })
+        }
+        SessionPhase::Deal | SessionPhase::Reveal => {
+            let tokens = session
+                .due_reveals(seat)?
+                .into_iter()
+                .map(|index| {
+                    let (token, proof) = DLCards::<C>::compute_reveal_token(
+                        rng,
+                        pp,
+                        context,
+                        sk,
+                        pk,
+                        &session.deck()[index],
+                    )?;
+
+                    Ok::<_, CardProtocolError>((index, token, proof))
+                })
+                .collect::<Result<_, CardProtocolError>>()?;
+
+            Ok(GameMessage::RevealTokens(tokens))
+        }
+        SessionPhase::Done => Err(CardProtocolError::InvalidInput(InputError::UnexpectedPhase)),
+    }
+}
+
+/// Phase a message from `player` belongs to. Reveal tokens are sent twice, for the deal and then
+/// for the player's own cards, unless the other players were dealt nothing.
+fn message_phase<C, B>(
+    session: &Session<C, B>,
+    player: PlayerId,
+    message: &GameMessage<C>,
+    token_messages: &mut [usize],
+) -> SessionPhase
+where
+    C: ProjectiveCurve,
+    B: ToBytes + Clone + CanonicalSerialize + CanonicalDeserialize,
+{
+    match message {
+        GameMessage::PublicKey(_) => SessionPhase::KeyExchange,
+        GameMessage::KeyOwnership(_) => SessionPhase::KeyOwnership,
+        GameMessage::Shuffle { .. } => SessionPhase::Shuffle(player),
+        GameMessage::RevealTokens(_) => {
+            let deals = (0..session.num_of_players()).any(|owner| {
+                owner != player && !session.hand(owner).unwrap_or_default().is_empty()
+            });
+            token_messages[player] += 1;
+
+            if token_messages[player] == 1 && deals {
+                SessionPhase::Deal
+            } else {
+                SessionPhase::Reveal
+            }
+        }
+    }
+}
+
+/// Position of a phase in the course of the game.
+fn phase_rank(phase: SessionPhase) -> (usize, usize) {
+    match phase {
+        SessionPhase::KeyExchange => (0, 0),
+        SessionPhase::KeyOwnership => (1, 0),
+        SessionPhase::Shuffle(shuffler) => (2, shuffler),
+        SessionPhase::Deal => (3, 0),
+        SessionPhase::Reveal => (4, 0),
+        SessionPhase::Done => (5, 0),
+    }
+}
+
+/// Transport between players in the same process, over unbounded channels.
+pub struct MemoryTransport<C: ProjectiveCurve> {
+    seat: PlayerId,
+    peers: Vec<UnboundedSender<(PlayerId, GameMessage<C>)>>,
+    inbox: UnboundedReceiver<(PlayerId, GameMessage<C>)>,
+}
+
+impl<C: ProjectiveCurve> fmt::Debug for MemoryTransport<C> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("MemoryTransport")
+            .field("seat", &self.seat)
+            .finish_non_exhaustive()
+    }
+}
+
+impl<C: ProjectiveCurve> MemoryTransport<C> {
+    /// Connect `num_of_players` players to each other. The transport of seat `i` is at index `i`.
+    pub fn network(num_of_players: usize) -> Vec<Self> {
+        let (senders, inboxes): (Vec<_>, Vec<_>) =
+            (0..num_of_players).map(|_| unbounded_channel()).unzip();
+
+        inboxes
+            .into_iter()
+            .enumerate()
+            .map(|(seat, inbox)| Self {
+                seat,
+                peers: senders
+                    .iter()
+                    .enumerate()
+                    .filter(|(peer, _)| *peer != seat)
+                    .map(|(_, sender)| sender.clone())
+                    .collect(),
+                inbox,
+            })
+            .collect()
+    }
+}
+
+#[async_trait]
+impl<C: ProjectiveCurve> Transport<C> for MemoryTransport<C> {
+    async fn broadcast(&self, message: GameMessage<C>) -> Result<(), CardProtocolError> {
+        for peer in &self.peers {
+            peer.send((self.seat, message.clone())).map_err(|_| {
+                CardProtocolError::Internal(InternalError::TransportError(
+                    "a player left".to_string(),
+                ))
+            })?;
+        }
+
+        Ok(())
+    }
+
+    async fn recv(&mut self) -> Result<(PlayerId, GameMessage<C>), CardProtocolError> {
+        self.inbox.recv().await.ok_or_else(|| {
+            CardProtocolError::Internal(InternalError::TransportError(
+                "every player left".to_string(),
+            ))
+        })
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{run_player, GameOutcome, MemoryTransport, Role, RunnerRules, Transport};
+    use crate::crypto_primitives::utils::rand::test_rng;
+    use crate::discrete_log_cards;
+    use crate::discrete_log_cards::encoding::encode_standard_deck;
+    use crate::discrete_log_cards::session::{GameMessage, Session, SessionPhase};
+    use crate::discrete_log_cards::test_helpers::{setup_players, Player};
+    use crate::error::{CardProtocolError, VerificationError};
+    use crate::BarnettSmartProtocol;
+
+    use std::sync::Arc;
+    use std::time::Duration;
+
+    // Choose elliptic curve setting
+    type Curve = starknet_curve::Projective;
+
+    // Instantiate concrete type for our card protocol
+    type CardProtocol<'a> = discrete_log_cards::DLCards<'a, Curve>;
+    type CardParameters = discrete_log_cards::Parameters<Curve>;
+
+    const SESSION_ID: &[u8] = b"runner test";
+
+    fn setup(
+        num_of_players: usize,
+    ) -> (Arc<CardParameters>, Vec<Player<Curve>>, Session<Curve, u64>) {
+        let rng = &mut test_rng();
+        let m = 4;
+        let n = 13;
+
+        let parameters = CardProtocol::setup(rng, m, n).unwrap();
+        let (keys, _) = setup_players(rng, &parameters, num_of_players).unwrap();
+
+        // Two cards per player, dealt one at a time around the table
+        let hands = (0..num_of_players)
+            .map(|player| vec![player, num_of_players + player])
+            .collect();
+        let session = Session::new(
+            &parameters,
+            SESSION_ID,
+            (0..num_of_players as u64).collect(),
+            encode_standard_deck(1).unwrap(),
+            hands,
+        )
+        .unwrap();
+
+        (Arc::new(parameters), keys, session)
+    }
+
+    /// Run the honest players on their own tasks. The transports are returned with the outcomes,
+    /// so that no player leaves the table before every outcome is collected.
+    async fn play(
+        parameters: &Arc<CardParameters>,
+        session: &Session<Curve, u64>,
+        players: Vec<(usize, Player<Curve>, MemoryTransport<Curve>)>,
+        rules: RunnerRules,
+    ) -> Vec<(GameOutcome, MemoryTransport<Curve>)> {
+        let handles = players
+            .into_iter()
+            .map(|(seat, (pk, sk, _), mut transport)| {
+                let role = Role {
+                    seat,
+                    parameters: parameters.clone(),
+                    session: session.clone(),
+                };
+                // Drawn on the test thread, so that every player gets the same stream on every run
+                let mut rng = test_rng();
+                tokio::spawn(async move {
+                    let outcome =
+                        run_player(&mut rng, (pk, sk), role, &mut transport, &rules).await;
+                    (outcome, transport)
+                })
+            })
+            .collect::<Vec<_>>();
+
+        let mut outcomes = Vec::new();
+        for handle in handles {
+            outcomes.push(handle.await.unwrap());
+        }
+
+        outcomes
+    }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 4)]
+    async fn test_four_player_hand() {
+        let (parameters, keys, session) = setup(4);
+        let rules = RunnerRules {
+            message_timeout: Duration::from_secs(120),
+        };
+
+        let players = MemoryTransport::network(4)
+            .into_iter()
+            .zip(keys)
+            .enumerate()
+            .map(|(seat, (transport, keys))| (seat, keys, transport))
+            .collect();
+        let outcomes = play(&parameters, &session, players, rules).await;
+
+        let hands = match &outcomes[0].0 {
+            GameOutcome::Completed { hands } => hands.clone(),
+            outcome => panic!("unexpected outcome {:?}", outcome),
+        };
+        for (outcome, _) in &outcomes {
+            assert_eq!(
+                &GameOutcome::Completed {
+                    hands: hands.clone()
+                },
+                outcome
+            );
+        }
+
+        let mut dealt = hands.iter().flatten().copied().collect::<Vec<_>>();
+        dealt.sort_unstable();
+        dealt.dedup();
+        assert_eq!(8, dealt.len());
+        assert!(hands.iter().all(|hand| hand.len() == 2));
+    }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+    async fn test_silent_player_times_out() {
+        let (parameters, keys, session) = setup(3);
+        let rules = RunnerRules {
+            message_timeout: Duration::from_millis(500),
+        };
+
+        // The player in seat 2 stays connected but never speaks
+        let mut transports = MemoryTransport::network(3);
+        let _silent = transports.pop().unwrap();
+        let players = transports
+            .into_iter()
+            .zip(keys)
+            .enumerate()
+            .map(|(seat, (transport, keys))| (seat, keys, transport))
+            .collect();
+
+        for (outcome, _) in play(&parameters, &session, players, rules).await {
+            assert_eq!(
+                GameOutcome::TimedOut {
+                    phase: SessionPhase::KeyExchange,
+                    waiting_for: vec![2],
+                },
+                outcome
+            );
+        }
+    }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+    async fn test_misbehaving_player() {
+        let rng = &mut test_rng();
+        let (parameters, mut keys, session) = setup(3);
+        let rules = RunnerRules {
+            message_timeout: Duration::from_secs(60),
+        };
+
+        // The player in seat 2 proves ownership of their key for a key list of their own
+        let mut transports = MemoryTransport::network(3);
+        let cheater = transports.pop().unwrap();
+        let (pk, sk, _) = keys.pop().unwrap();
+        let proof = CardProtocol::prove_key_ownership(
+            rng,
+            &parameters,
+            SESSION_ID,
+            &pk,
+            &sk,
+            &2u64,
+            2,
+            &CardProtocol::key_list_digest(&[pk]).unwrap(),
+        )
+        .unwrap();
+        cheater.broadcast(GameMessage::PublicKey(pk)).await.unwrap();
+        cheater
+            .broadcast(GameMessage::KeyOwnership(proof))
+            .await
+            .unwrap();
+
+        let players = transports
+            .into_iter()
+            .zip(keys)
+            .enumerate()
+            .map(|(seat, (transport, keys))| (seat, keys, transport))
+            .collect();
+
+        for (outcome, _) in play(&parameters, &session, players, rules).await {
+            assert!(matches!(
+                outcome,
+                GameOutcome::Misbehavior {
+                    player: 2,
+                    error: CardProtocolError::VerificationFailed(
+                        VerificationError::PlayerMessage {
+                            player: 2,
+                            source: Some(_)
+                        }
+                    ),
+                }
+            ));
+        }
+    }
+}
@@ -0,0 +1,116 @@
+use crate::crypto_primitives::utils::ct::ct_eq;
+use crate::crypto_primitives::utils::secret::SecretScalar;
+use crate::discrete_log_cards::key_sharing::{KeyShare, KeySharing};
+use crate::error::CardProtocolError;
+
+use ark_ff::PrimeField;
+use ark_std::fmt;
+use ark_std::rand::{CryptoRng, Rng};
+use ark_std::vec::Vec;
+
+/// A player's secret key, generic over the scalar field; use it through the `PlayerSecretKey`
+/// alias. The scalar is wiped when the key is dropped and is never formatted: `Debug` and
+/// `Display` print `PlayerSecretKey(REDACTED)`. Call `expose_secret` where the scalar itself is
+/// needed.
+#[derive(Clone)]
+pub struct SecretKey<F: PrimeField>(SecretScalar<F>);
+
+impl<F: PrimeField> SecretKey<F> {
+    pub fn expose_secret(&self) -> &F {
+        &self.0
+    }
+}
+
+impl<F: PrimeField> From<F> for SecretKey<F> {
+    fn from(secret: F) -> Self {
+        Self(SecretScalar::new(secret))
+    }
+}
+
+impl<F: PrimeField> fmt::Debug for SecretKey<F> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("PlayerSecretKey(REDACTED)")
+    }
+}
+
+impl<F: PrimeField> fmt::Display for SecretKey<F> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt::Debug::fmt(self, f)
+    }
+}
+
+impl<F: PrimeField> PartialEq for SecretKey<F> {
+    /// Keys are compared in constant time.
+    fn eq(&self, other: &Self) -> bool {
+        ct_eq(self.expose_secret(), other.expose_secret()).unwrap_or(false)
+    }
+}
+
+impl<F: PrimeField> Eq for SecretKey<F> {}
+
+impl<F: PrimeField> KeySharing for SecretKey<F> {
+    type Share = KeyShare<F>;
+
+    fn split<R: Rng + CryptoRng>(
+        &self,
+        rng: &mut R,
+        t: usize,
+        n: usize,
+    ) -> Result<Vec<Self::Share>, CardProtocolError> {
+        self.expose_secret().split(rng, t, n)
+    }
+
+    fn reconstruct(shares: &[Self::Share]) -> Result<Self, CardProtocolError> {
+        F::reconstruct(shares).map(Self::from)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::crypto_primitives::utils::rand::test_rng;
+    use crate::discrete_log_cards;
+    use crate::BarnettSmartProtocol;
+
+    use ark_ff::UniformRand;
+
+    // Choose elliptic curve setting
+    type Curve = starknet_curve::Projective;
+    type Scalar = starknet_curve::Fr;
+
+    // Instantiate concrete type for our card protocol
+    type CardProtocol<'a> = discrete_log_cards::DLCards<'a, Curve>;
+    type SecretKey = discrete_log_cards::PlayerSecretKey<Curve>;
+
+    #[test]
+    fn test_secret_key_is_redacted() {
+        let rng = &mut test_rng();
+        let m = 4;
+        let n = 13;
+
+        let parameters = CardProtocol::setup(rng, m, n).unwrap();
+        let (_, sk) = CardProtocol::player_keygen(rng, &parameters).unwrap();
+
+        let scalar = sk.expose_secret();
+        for formatted in [
+            format!("{:?}", sk),
+            format!("{:#?}", sk),
+            format!("{}", sk),
+            format!("{:?}", (1u8, &sk)),
+        ] {
+            assert!(formatted.contains("PlayerSecretKey(REDACTED)"));
+            assert!(!formatted.contains(&scalar.to_string()));
+            assert!(!formatted.contains(&format!("{:?}", scalar)));
+        }
+    }
+
+    #[test]
+    fn test_secret_key_conversion() {
+        let rng = &mut test_rng();
+
+        let scalar = Scalar::rand(rng);
+        let sk = SecretKey::from(scalar);
+        assert_eq!(*sk.expose_secret(), scalar);
+        assert_eq!(sk, SecretKey::from(scalar));
+        assert_ne!(sk, SecretKey::from(Scalar::rand(rng)));
+    }
+}
@@ -0,0 +1,208 @@
+//! Per-sender logs of signed game messages, shared by the transports that broadcast through
+//! untrusted relays.
+//!
+//! Every message is a [`SignedMessage`] whose slot numbers the messages of its sender. A node
+//! keeps one [`EventLog`] per player and delivers a message once it extends the log of its
+//! sender, so duplicated, replayed or reordered messages are delivered once and in order, and
+//! messages whose signature does not match the identity of their seat are dropped.
+
+use crate::discrete_log_cards::abort::SignedMessage;
+use crate::discrete_log_cards::event_log::EventLog;
+use crate::discrete_log_cards::identity::{IdentityKey, PublicIdentity};
+use crate::discrete_log_cards::runner::PlayerId;
+use crate::discrete_log_cards::session::GameMessage;
+use crate::error::{CardProtocolError, InternalError};
+
+use ark_ec::ProjectiveCurve;
+use std::collections::BTreeMap;
+use std::sync::{Mutex, MutexGuard};
+
+/// Number of messages of one sender held while an earlier one is missing.
+pub const MAX_PENDING_MESSAGES: usize = 64;
+
+/// Copies of the logs of every player at the table, `logs[i]` holding the messages of seat `i`.
+pub(crate) struct SenderLogs<C: ProjectiveCurve> {
+    pub(crate) seat: PlayerId,
+    pub(crate) identities: Vec<PublicIdentity<C>>,
+    pub(crate) logs: Vec<EventLog<C>>,
+    /// Messages waiting for an earlier slot of their sender, by slot
+    pending: Vec<BTreeMap<u64, SignedMessage<C>>>,
+}
+
+impl<C: ProjectiveCurve> SenderLogs<C> {
+    pub(crate) fn new(seat: PlayerId, identities: Vec<PublicIdentity<C>>, context: &[u8]) -> Self {
+        let num_of_players = identities.len();
+
+        Self {
+            seat,
+            identities,
+            logs: vec![EventLog::new(context); num_of_players],
+            pending: vec![BTreeMap::new(); num_of_players],
+        }
+    }
+
+    /// Sign `message` for the next slot of the node's own log and append it.
+    pub(crate) fn sign_own(
+        &mut self,
+        identity: &IdentityKey<C>,
+        message: GameMessage<C>,
+    ) -> Result<SignedMessage<C>, CardProtocolError> {
+        let log = &self.logs[self.seat];
+        let signed = SignedMessage::sign(
+            identity,
+            log.context(),
+            self.seat,
+            log.len() as u64,
+            message,
+        )?;
+        self.logs[self.seat].append_signed(signed.clone())?;
+
+        Ok(signed)
+    }
+
+    /// Messages of the node's own log from slot `from` on, to send again after a connection was
+    /// lost.
+    pub(crate) fn own_from(&self, from: u64) -> Vec<SignedMessage<C>> {
+        let log = &self.logs[self.seat];
+
+        (from as usize..log.len())
+            .filter_map(|slot| log.signed_message(slot))
+            .collect()
+    }
+
+    /// Accept a received message and return the messages it makes deliverable, in order.
+    /// Messages that are already logged, too far ahead, or not signed by their sender are dropped.
+    pub(crate) fn accept(&mut self, signed: SignedMessage<C>) -> Vec<(PlayerId, GameMessage<C>)> {
+        let sender = signed.sender;
+        if sender == self.seat || sender >= self.logs.len() {
+            return Vec::new();
+        }
+
+        let next = self.logs[sender].len() as u64;
+        if signed.slot < next
+            || signed.slot >= next + MAX_PENDING_MESSAGES as u64
+            || self.pending[sender].contains_key(&signed.slot)
+            || signed
+                .verify(self.logs[sender].context(), &self.identities)
+                .is_err()
+        {
+            return Vec::new();
+        }
+        self.pending[sender].insert(signed.slot, signed);
+
+        let mut delivered = Vec::new();
+        while let Some(signed) = self.pending[sender].remove(&(self.logs[sender].len() as u64)) {
+            let message = signed.message.clone();
+            if self.logs[sender].append_signed(signed).is_err() {
+                break;
+            }
+            delivered.push((sender, message));
+        }
+
+        delivered
+    }
+}
+
+pub(crate) fn lock<T>(mutex: &Mutex<T>) -> Result<MutexGuard<'_, T>, CardProtocolError> {
+    mutex.lock().map_err(|_| {
+        CardProtocolError::Internal(InternalError::TransportError(
+            "the node panicked".to_string(),
+        ))
+    })
+}
+
+pub(crate) fn transport_error<E: std::fmt::Debug>(error: E) -> CardProtocolError {
+    CardProtocolError::Internal(InternalError::TransportError(format!("{:?}", error)))
+}
+
+#[cfg(test)]
+mod test {
+    use super::SenderLogs;
+    use crate::crypto_primitives::utils::rand::test_rng;
+    use crate::discrete_log_cards::abort::SignedMessage;
+    use crate::discrete_log_cards::identity::IdentityKey;
+    use crate::discrete_log_cards::session::GameMessage;
+    use crate::discrete_log_cards::validation::to_bytes;
+
+    use ark_ec::ProjectiveCurve;
+    use ark_std::UniformRand;
+
+    // Choose elliptic curve setting
+    type Curve = starknet_curve::Projective;
+
+    const SESSION_ID: &[u8] = b"sender logs test";
+
+    fn public_key_message() -> GameMessage<Curve> {
+        GameMessage::PublicKey(Curve::rand(&mut test_rng()).into_affine())
+    }
+
+    #[test]
+    fn test_duplicates_and_replays() {
+        let rng = &mut test_rng();
+
+        let identities = (0..2)
+            .map(|_| IdentityKey::<Curve>::new(rng).unwrap())
+            .collect::<Vec<_>>();
+        let public = identities
+            .iter()
+            .map(|identity| identity.verifying_key())
+            .collect::<Vec<_>>();
+        let mut logs = SenderLogs::new(0, public.clone(), SESSION_ID);
+
+        let messages = (0..3)
+            .map(|slot| {
+                SignedMessage::sign(&identities[1], SESSION_ID, 1, slot, public_key_message())
+                    .unwrap()
+            })
+            .collect::<Vec<_>>();
+        let delivered = |signed: &SignedMessage<Curve>| (1, to_bytes(&signed.message).unwrap());
+        let encoded = |messages: Vec<(usize, GameMessage<Curve>)>| {
+            messages
+                .into_iter()
+                .map(|(sender, message)| (sender, to_bytes(&message).unwrap()))
+                .collect::<Vec<_>>()
+        };
+
+        // A message ahead of its slot waits for the one before it
+        assert!(logs.accept(messages[1].clone()).is_empty());
+        assert_eq!(
+            encoded(logs.accept(messages[0].clone())),
+            vec![delivered(&messages[0]), delivered(&messages[1])]
+        );
+
+        // Duplicates and replays are ignored, whatever their content
+        assert!(logs.accept(messages[0].clone()).is_empty());
+        assert!(logs.accept(messages[1].clone()).is_empty());
+        let replayed =
+            SignedMessage::sign(&identities[1], SESSION_ID, 1, 0, public_key_message()).unwrap();
+        assert!(logs.accept(replayed).is_empty());
+
+        // A message signed by another identity, or for another game, is dropped
+        let forged =
+            SignedMessage::sign(&identities[0], SESSION_ID, 1, 2, public_key_message()).unwrap();
+        assert!(logs.accept(forged).is_empty());
+        let other_game =
+            SignedMessage::sign(&identities[1], b"another game", 1, 2, public_key_message())
+                .unwrap();
+        assert!(logs.accept(other_game).is_empty());
+
+        assert_eq!(
+            encoded(logs.accept(messages[2].clone())),
+            vec![delivered(&messages[2])]
+        );
+        assert!(logs.accept(messages[2].clone()).is_empty());
+        assert_eq!(3, logs.logs[1].len());
+        assert_eq!(Ok(()), logs.logs[1].verify_signatures(&public));
+
+        // The node's own messages can be sent again from any slot
+        let own = logs.sign_own(&identities[0], public_key_message()).unwrap();
+        logs.sign_own(&identities[0], public_key_message()).unwrap();
+        assert_eq!(2, logs.own_from(0).len());
+        assert_eq!(
+            to_bytes(&own).unwrap(),
+            to_bytes(&logs.own_from(0)[0]).unwrap()
+        );
+        assert_eq!(1, logs.own_from(1).len());
+        assert!(logs.own_from(2).is_empty());
+    }
+}
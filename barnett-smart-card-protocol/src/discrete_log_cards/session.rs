@@ -0,0 +1,1123 @@
+//! Round enforcement for a game run over `DLCards`.
+//!
+//! A [`Session`] knows which message the protocol expects next and from whom. Players publish
+//! their keys, prove ownership of them, and shuffle the deck in turn. The private cards are then
+//! dealt by revealing each of them to its owner, and finally opened by their owners. Every message
+//! is verified with the corresponding protocol call before it is applied. A message sent in the
+//! wrong phase, by the wrong player, or with an invalid proof is rejected with a typed error and
+//! leaves the session unchanged, so application code does not have to police the order of rounds.
+//!
+//! The session holds public data only and is serializable, so a server can persist it between
+//! messages.
+
+use crate::crypto_primitives::utils::ct::ct_position;
+use crate::crypto_primitives::utils::format::{Compact, Hex, HexBytes, IndexedTokens};
+use crate::discrete_log_cards::dkg::KeyOwnershipProof;
+use crate::discrete_log_cards::reveal::reveal_token;
+use crate::discrete_log_cards::timeout::DeadlineRules;
+use crate::discrete_log_cards::validation::{read_vec, MAX_VECTOR_LENGTH};
+use crate::discrete_log_cards::{
+    starting_deck, Card, DLCards, MaskedCard, Parameters, PlayerSecretKey, PublicKey, RevealToken,
+};
+use crate::error::{check_dimension, CardProtocolError, Dimension, InputError};
+use crate::{BarnettSmartProtocol, KeyListDigest, Reveal};
+
+use ark_ec::ProjectiveCurve;
+use ark_ff::{ToBytes, Zero};
+use ark_serialize::{CanonicalDeserialize, CanonicalSerialize, Read, SerializationError, Write};
+use ark_std::fmt;
+use proof_essentials::homomorphic_encryption::el_gamal::ElGamal;
+use proof_essentials::vector_commitment::pedersen::PedersenCommitment;
+use proof_essentials::zkp::{arguments::shuffle, proofs::chaum_pedersen_dl_equality};
+
+pub type ShuffleProof<C> =
+    shuffle::proof::Proof<<C as ProjectiveCurve>::ScalarField, ElGamal<C>, PedersenCommitment<C>>;
+
+pub type RevealProof<C> = chaum_pedersen_dl_equality::proof::Proof<C>;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum SessionPhase {
+    /// Players publish their public keys
+    KeyExchange,
+    /// Players prove ownership of their key for the complete key list
+    KeyOwnership,
+    /// The player with this index shuffles next
+    Shuffle(usize),
+    /// Players send reveal tokens for the cards dealt to the other players
+    Deal,
+    /// Players open their own cards
+    Reveal,
+    Done,
+}
+
+impl CanonicalSerialize for SessionPhase {
+    fn serialize<W: Write>(&self, writer: W) -> Result<(), SerializationError> {
+        let (tag, player) = match self {
+            SessionPhase::KeyExchange => (0u8, 0u64),
+            SessionPhase::KeyOwnership => (1, 0),
+            SessionPhase::Shuffle(player) => (2, *player as u64),
+            SessionPhase::Deal => (3, 0),
+            SessionPhase::Reveal => (4, 0),
+            SessionPhase::Done => (5, 0),
+        };
+
+        (tag, player).serialize(writer)
+    }
+
+    fn serialized_size(&self) -> usize {
+        (0u8, 0u64).serialized_size()
+    }
+}
+
+impl CanonicalDeserialize for SessionPhase {
+    fn deserialize<R: Read>(reader: R) -> Result<Self, SerializationError> {
+        let (tag, player) = <(u8, u64)>::deserialize(reader)?;
+
+        match (tag, player) {
+            (0, 0) => Ok(SessionPhase::KeyExchange),
+            (1, 0) => Ok(SessionPhase::KeyOwnership),
+            (2, player) => Ok(SessionPhase::Shuffle(player as usize)),
+            (3, 0) => Ok(SessionPhase::Deal),
+            (4, 0) => Ok(SessionPhase::Reveal),
+            (5, 0) => Ok(SessionPhase::Done),
+            _ => Err(SerializationError::InvalidData),
+        }
+    }
+}
+
+/// A message broadcast by a player.
+#[derive(Clone)]
+pub enum GameMessage<C: ProjectiveCurve> {
+    PublicKey(PublicKey<C>),
+    KeyOwnership(KeyOwnershipProof<C>),
+    Shuffle {
+        deck: Vec<MaskedCard<C>>,
+        proof: ShuffleProof<C>,
+    },
+    /// Reveal tokens, each for the card at the given deck index
+    RevealTokens(Vec<(usize, RevealToken<C>, RevealProof<C>)>),
+}
+
+impl<C: ProjectiveCurve> fmt::Debug for GameMessage<C> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::PublicKey(pk) => f.debug_tuple("PublicKey").field(&Hex(pk)).finish(),
+            Self::KeyOwnership(proof) => f
+                .debug_tuple("KeyOwnership")
+                .field(&Compact(proof))
+                .finish(),
+            Self::Shuffle { deck, proof } => f
+                .debug_struct("Shuffle")
+                .field("deck", &Compact(deck))
+                .field("proof", &Compact(proof))
+                .finish(),
+            Self::RevealTokens(tokens) => f
+                .debug_tuple("RevealTokens")
+                .field(&IndexedTokens(tokens))
+                .finish(),
+        }
+    }
+}
+
+impl<C: ProjectiveCurve> CanonicalSerialize for GameMessage<C> {
+    fn serialize<W: Write>(&self, mut writer: W) -> Result<(), SerializationError> {
+        match self {
+            GameMessage::PublicKey(pk) => {
+                0u8.serialize(&mut writer)?;
+                pk.serialize(&mut writer)
+            }
+            GameMessage::KeyOwnership(proof) => {
+                1u8.serialize(&mut writer)?;
+                proof.serialize(&mut writer)
+            }
+            GameMessage::Shuffle { deck, proof } => {
+                2u8.serialize(&mut writer)?;
+                deck.serialize(&mut writer)?;
+                proof.serialize(&mut writer)
+            }
+            GameMessage::RevealTokens(tokens) => {
+                3u8.serialize(&mut writer)?;
+                tokens.serialize(&mut writer)
+            }
+        }
+    }
+
+    fn serialized_size(&self) -> usize {
+        1 + match self {
+            GameMessage::PublicKey(pk) => pk.serialized_size(),
+            GameMessage::KeyOwnership(proof) => proof.serialized_size(),
+            GameMessage::Shuffle { deck, proof } => {
+                deck.serialized_size() + proof.serialized_size()
+            }
+            GameMessage::RevealTokens(tokens) => tokens.serialized_size(),
+        }
+    }
+}
+
+/// Decks and token bundles are read with [`read_vec`], so that a forged length prefix is refused
+/// before anything is allocated for it.
+impl<C: ProjectiveCurve> CanonicalDeserialize for GameMessage<C> {
+    fn deserialize<R: Read>(mut reader: R) -> Result<Self, SerializationError> {
+        match u8::deserialize(&mut reader)? {
+            0 => Ok(GameMessage::PublicKey(CanonicalDeserialize::deserialize(
+                &mut reader,
+            )?)),
+            1 => Ok(GameMessage::KeyOwnership(
+                CanonicalDeserialize::deserialize(&mut reader)?,
+            )),
+            2 => Ok(GameMessage::Shuffle {
+                deck: read_vec(&mut reader, MAX_VECTOR_LENGTH)?,
+                proof: CanonicalDeserialize::deserialize(&mut reader)?,
+            }),
+            3 => Ok(GameMessage::RevealTokens(read_vec(
+                &mut reader,
+                MAX_VECTOR_LENGTH,
+            )?)),
+            _ => Err(SerializationError::InvalidData),
+        }
+    }
+}
+
+/// Phase of the session before and after an accepted message.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Transition {
+    pub from: SessionPhase,
+    pub to: SessionPhase,
+}
+
+/// Public state of a game. Every player, or a server on their behalf, feeds it the same messages
+/// in the same order and therefore reaches the same state.
+#[derive(Clone, CanonicalSerialize, CanonicalDeserialize)]
+pub struct Session<C: ProjectiveCurve, B: CanonicalSerialize + CanonicalDeserialize> {
+    phase: SessionPhase,
+    context: Vec<u8>,
+    player_info: Vec<B>,
+    cards: Vec<Card<C>>,
+    /// Deck indices dealt to each player
+    hands: Vec<Vec<usize>>,
+    keys: Vec<Option<PublicKey<C>>>,
+    proven: Vec<bool>,
+    aggregate_key: Option<PublicKey<C>>,
+    deck: Vec<MaskedCard<C>>,
+    /// Verified reveal tokens, by deck index and then by player
+    tokens: Vec<Vec<Option<RevealToken<C>>>>,
+    /// Cards opened by their owner, as positions in `cards`
+    opened: Vec<Option<usize>>,
+    /// Number of messages accepted so far
+    sequence: u64,
+    /// Sequence number of the first message of the current phase
+    phase_start: u64,
+}
+
+/// Only the public keys and the progress of the game are printed, not the deck or the tokens.
+impl<C, B> fmt::Debug for Session<C, B>
+where
+    C: ProjectiveCurve,
+    B: CanonicalSerialize + CanonicalDeserialize,
+{
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let keys = self.keys.iter().map(|key| key.as_ref().map(Hex));
+
+        f.debug_struct("Session")
+            .field("phase", &self.phase)
+            .field("context", &HexBytes(&self.context))
+            .field("keys", &keys.collect::<Vec<_>>())
+            .field("aggregate_key", &self.aggregate_key.as_ref().map(Hex))
+            .field("opened", &self.opened)
+            .field("sequence", &self.sequence)
+            .finish_non_exhaustive()
+    }
+}
+
+impl<C, B> Session<C, B>
+where
+    C: ProjectiveCurve,
+    B: ToBytes + Clone + CanonicalSerialize + CanonicalDeserialize,
+{
+    /// Start a session among the players described by `player_info`, whose index in this vector
+    /// is the index used in every message. `cards` are the open cards the deck is built from, and
+    /// `hands[i]` lists the indices of the final deck that are dealt to player `i`. Proofs are
+    /// checked against the session `context`.
+    pub fn new(
+        pp: &Parameters<C>,
+        context: &[u8],
+        player_info: Vec<B>,
+        cards: Vec<Card<C>>,
+        hands: Vec<Vec<usize>>,
+    ) -> Result<Self, CardProtocolError> {
+        let num_of_players = player_info.len();
+        if num_of_players < 2 || hands.len() != num_of_players {
+            return Err(InputError::InvalidPlayerCount(num_of_players).into());
+        }
+
+        check_dimension(Dimension::Deck, pp.num_cards(), cards.len())?;
+
+        let mut dealt = vec![false; cards.len()];
+        for &index in hands.iter().flatten() {
+            match dealt.get_mut(index) {
+                Some(dealt) if !*dealt => *dealt = true,
+                _ => return Err(InputError::InvalidDeal(cards.len()).into()),
+            }
+        }
+
+        Ok(Self {
+            phase: SessionPhase::KeyExchange,
+            context: context.to_vec(),
+            player_info,
+            tokens: vec![vec![None; num_of_players]; cards.len()],
+            opened: vec![None; cards.len()],
+            cards,
+            hands,
+            keys: vec![None; num_of_players],
+            proven: vec![false; num_of_players],
+            aggregate_key: None,
+            deck: Vec::new(),
+            sequence: 0,
+            phase_start: 0,
+        })
+    }
+
+    pub fn phase(&self) -> SessionPhase {
+        self.phase
+    }
+
+    pub fn context(&self) -> &[u8] {
+        &self.context
+    }
+
+    pub fn num_of_players(&self) -> usize {
+        self.player_info.len()
+    }
+
+    pub fn player_info(&self) -> &[B] {
+        &self.player_info
+    }
+
+    /// The open cards the deck is built from.
+    pub fn cards(&self) -> &[Card<C>] {
+        &self.cards
+    }
+
+    pub fn aggregate_key(&self) -> Option<PublicKey<C>> {
+        self.aggregate_key
+    }
+
+    /// Key published by `player`, if any.
+    pub fn public_key(&self, player: usize) -> Option<PublicKey<C>> {
+        self.keys.get(player).copied().flatten()
+    }
+
+    /// Digest of the key list, once every player has published their key.
+    pub fn key_list_digest(&self) -> Result<Option<KeyListDigest>, CardProtocolError> {
+        if self.keys.iter().any(Option::is_none) {
+            return Ok(None);
+        }
+
+        let keys = self.keys.iter().flatten().copied().collect::<Vec<_>>();
+        Ok(Some(DLCards::<C>::key_list_digest(&keys)?))
+    }
+
+    /// The current deck: the starting deck before the first shuffle, the output of the last
+    /// shuffle afterwards.
+    pub fn deck(&self) -> &[MaskedCard<C>] {
+        &self.deck
+    }
+
+    /// Deck indices dealt to `player`, or `UnknownPlayer` for a seat past the table.
+    pub fn hand(&self, player: usize) -> Result<&[usize], CardProtocolError> {
+        self.hands
+            .get(player)
+            .map(Vec::as_slice)
+            .ok_or(CardProtocolError::InvalidInput(InputError::UnknownPlayer(
+                player,
+            )))
+    }
+
+    /// Cards `player` opened in the reveal phase, as positions in the open cards the session was
+    /// started with.
+    pub fn opened_hand(&self, player: usize) -> Option<Vec<usize>> {
+        self.hands
+            .get(player)?
+            .iter()
+            .map(|&index| self.opened[index])
+            .collect()
+    }
+
+    /// Deck indices `player` still has to send reveal tokens for in the current phase, or
+    /// `UnknownPlayer` for a seat past the table.
+    pub fn due_reveals(&self, player: usize) -> Result<Vec<usize>, CardProtocolError> {
+        if player >= self.num_of_players() {
+            return Err(InputError::UnknownPlayer(player).into());
+        }
+
+        let indices = match self.phase {
+            SessionPhase::Deal => (0..self.num_of_players())
+                .filter(|&owner| owner != player)
+                .flat_map(|owner| self.hands[owner].iter().copied())
+                .collect(),
+            SessionPhase::Reveal => self.hand(player)?.to_vec(),
+            _ => Vec::new(),
+        };
+
+        Ok(indices
+            .into_iter()
+            .filter(|&index| self.tokens[index][player].is_none())
+            .collect())
+    }
+
+    /// Number of messages accepted so far, which is the sequence number of the next one.
+    pub fn sequence(&self) -> u64 {
+        self.sequence
+    }
+
+    /// Whether the current phase waits for a message from `player`.
+    pub fn awaits(&self, player: usize) -> bool {
+        if player >= self.num_of_players() {
+            return false;
+        }
+
+        match self.phase {
+            SessionPhase::KeyExchange => self.keys[player].is_none(),
+            SessionPhase::KeyOwnership => !self.proven[player],
+            SessionPhase::Shuffle(shuffler) => shuffler == player,
+            SessionPhase::Deal | SessionPhase::Reveal => self
+                .due_reveals(player)
+                .map_or(false, |due| !due.is_empty()),
+            SessionPhase::Done => false,
+        }
+    }
+
+    /// Sequence number by which the message the current phase waits for from `player` is due
+    /// under `rules`, or `None` if the phase does not wait for them.
+    pub fn deadline(&self, player: usize, rules: &DeadlineRules) -> Option<u64> {
+        if !self.awaits(player) {
+            return None;
+        }
+
+        Some(self.phase_start + rules.grace)
+    }
+
+    /// Check the invariants of a session read back from storage: the shape of every field, the
+    /// phase against the keys and proofs received, and the aggregate key against the keys. Tokens
+    /// and shuffles were verified when they were received and are not checked again.
+    pub fn validate(&self, pp: &Parameters<C>) -> Result<(), CardProtocolError> {
+        let num_of_players = self.num_of_players();
+        let num_cards = self.cards.len();
+        check_dimension(Dimension::Deck, pp.num_cards(), num_cards)?;
+
+        let consistent = num_of_players >= 2
+            && self.hands.len() == num_of_players
+            && self.keys.len() == num_of_players
+            && self.proven.len() == num_of_players
+            && self.tokens.len() == num_cards
+            && self.tokens.iter().all(|row| row.len() == num_of_players)
+            && self.opened.len() == num_cards
+            && self.phase_start <= self.sequence;
+        if !consistent {
+            return Err(InputError::InvalidSnapshot.into());
+        }
+
+        let mut dealt = vec![false; num_cards];
+        for &index in self.hands.iter().flatten() {
+            match dealt.get_mut(index) {
+                Some(dealt) if !*dealt => *dealt = true,
+                _ => return Err(InputError::InvalidDeal(num_cards).into()),
+            }
+        }
+
+        let all_keys = self.keys.iter().all(Option::is_some);
+        let all_proven = self.proven.iter().all(|&proven| proven);
+        let no_tokens = self.tokens.iter().flatten().all(Option::is_none);
+        let consistent = match self.phase {
+            SessionPhase::KeyExchange => {
+                !all_keys && !self.proven.contains(&true) && self.deck.is_empty()
+            }
+            SessionPhase::KeyOwnership => all_keys && !all_proven && self.deck.is_empty(),
+            SessionPhase::Shuffle(shuffler) => {
+                shuffler < num_of_players && all_proven && no_tokens && self.deck.len() == num_cards
+            }
+            SessionPhase::Deal | SessionPhase::Reveal | SessionPhase::Done => {
+                all_proven && self.deck.len() == num_cards
+            }
+        };
+        if !consistent {
+            return Err(InputError::InvalidSnapshot.into());
+        }
+
+        let aggregate_key = if all_proven {
+            Some(
+                self.keys
+                    .iter()
+                    .flatten()
+                    .fold(PublicKey::<C>::zero(), |acc, pk| acc + *pk),
+            )
+        } else {
+            None
+        };
+        if aggregate_key != self.aggregate_key {
+            return Err(InputError::InvalidSnapshot.into());
+        }
+
+        Ok(())
+    }
+
+    /// Verify and apply a message broadcast by `player`.
+    pub fn receive(
+        &mut self,
+        pp: &Parameters<C>,
+        player: usize,
+        message: GameMessage<C>,
+    ) -> Result<Transition, CardProtocolError> {
+        if player >= self.num_of_players() {
+            return Err(InputError::UnknownPlayer(player).into());
+        }
+
+        let from = self.phase;
+        match (from, message) {
+            (SessionPhase::KeyExchange, GameMessage::PublicKey(pk)) => {
+                self.receive_key(player, pk)?
+            }
+            (SessionPhase::KeyOwnership, GameMessage::KeyOwnership(proof)) => {
+                self.receive_key_proof(pp, player, proof)?
+            }
+            (SessionPhase::Shuffle(shuffler), GameMessage::Shuffle { deck, proof }) => {
+                if player != shuffler {
+                    return Err(InputError::NotYourTurn(player).into());
+                }
+                self.receive_shuffle(pp, player, deck, proof)?
+            }
+            (SessionPhase::Deal, GameMessage::RevealTokens(tokens))
+            | (SessionPhase::Reveal, GameMessage::RevealTokens(tokens)) => {
+                self.receive_tokens(pp, player, tokens)?
+            }
+            _ => return Err(InputError::UnexpectedPhase.into()),
+        }
+
+        self.sequence += 1;
+        if self.phase != from {
+            self.phase_start = self.sequence;
+        }
+
+        Ok(Transition {
+            from,
+            to: self.phase,
+        })
+    }
+
+    /// Open the cards dealt to `player` with their own secret key, once the deal is complete. The
+    /// result lists positions in the open cards the session was started with, looked up in
+    /// constant time.
+    pub fn open_own_hand(
+        &self,
+        player: usize,
+        sk: &PlayerSecretKey<C>,
+    ) -> Result<Vec<usize>, CardProtocolError> {
+        if player >= self.num_of_players() {
+            return Err(InputError::UnknownPlayer(player).into());
+        }
+
+        if !matches!(self.phase, SessionPhase::Reveal | SessionPhase::Done) {
+            return Err(InputError::UnexpectedPhase.into());
+        }
+
+        self.hands[player]
+            .iter()
+            .map(|&index| {
+                let mut tokens = self.tokens[index].clone();
+                tokens[player] = Some(reveal_token(sk, &self.deck[index]));
+                let card = self.open_card(index, &tokens)?;
+
+                ct_position(&card, &self.cards)?
+                    .ok_or(CardProtocolError::InvalidInput(InputError::InvalidPoint))
+            })
+            .collect()
+    }
+
+    fn receive_key(&mut self, player: usize, pk: PublicKey<C>) -> Result<(), CardProtocolError> {
+        if self.keys[player].is_some() {
+            return Err(InputError::DuplicateMessage(player).into());
+        }
+
+        if pk.is_zero() {
+            return Err(InputError::IdentityPublicKey(player).into());
+        }
+
+        if self.keys.iter().flatten().any(|other| *other == pk) {
+            return Err(InputError::DuplicatePlayerKey { index: player }.into());
+        }
+
+        self.keys[player] = Some(pk);
+
+        if self.keys.iter().all(Option::is_some) {
+            self.phase = SessionPhase::KeyOwnership;
+        }
+
+        Ok(())
+    }
+
+    fn receive_key_proof(
+        &mut self,
+        pp: &Parameters<C>,
+        player: usize,
+        proof: KeyOwnershipProof<C>,
+    ) -> Result<(), CardProtocolError> {
+        if self.proven[player] {
+            return Err(InputError::DuplicateMessage(player).into());
+        }
+
+        let key_list_digest = self
+            .key_list_digest()?
+            .ok_or(CardProtocolError::InvalidInput(InputError::UnexpectedPhase))?;
+        let pk = self.keys[player].ok_or(CardProtocolError::InvalidInput(
+            InputError::UnknownPlayer(player),
+        ))?;
+
+        DLCards::<C>::verify_key_ownership(
+            pp,
+            &self.context,
+            &pk,
+            &self.player_info[player],
+            player,
+            &key_list_digest,
+            &proof,
+        )
+        .map_err(|source| CardProtocolError::verification_failed(player, source))?;
+
+        self.proven[player] = true;
+
+        if self.proven.iter().all(|&proven| proven) {
+            let aggregate_key = self
+                .keys
+                .iter()
+                .flatten()
+                .fold(PublicKey::<C>::zero(), |acc, pk| acc + *pk);
+
+            self.deck = starting_deck(pp, &aggregate_key, &self.cards)?;
+            self.aggregate_key = Some(aggregate_key);
+            self.phase = SessionPhase::Shuffle(0);
+        }
+
+        Ok(())
+    }
+
+    fn receive_shuffle(
+        &mut self,
+        pp: &Parameters<C>,
+        player: usize,
+        deck: Vec<MaskedCard<C>>,
+        proof: ShuffleProof<C>,
+    ) -> Result<(), CardProtocolError> {
+        let aggregate_key = self
+            .aggregate_key
+            .ok_or(CardProtocolError::InvalidInput(InputError::UnexpectedPhase))?;
+
+        check_dimension(Dimension::ShuffledDeck, self.deck.len(), deck.len())?;
+
+        DLCards::<C>::verify_shuffle(pp, &self.context, &aggregate_key, &self.deck, &deck, &proof)
+            .map_err(|source| CardProtocolError::verification_failed(player, source))?;
+
+        self.deck = deck;
+        self.phase = if player + 1 < self.num_of_players() {
+            SessionPhase::Shuffle(player + 1)
+        } else {
+            SessionPhase::Deal
+        };
+
+        Ok(())
+    }
+
+    fn receive_tokens(
+        &mut self,
+        pp: &Parameters<C>,
+        player: usize,
+        tokens: Vec<(usize, RevealToken<C>, RevealProof<C>)>,
+    ) -> Result<(), CardProtocolError> {
+        let pk = self
+            .public_key(player)
+            .ok_or(CardProtocolError::InvalidInput(InputError::UnknownPlayer(
+                player,
+            )))?;
+        let mut due = self.due_reveals(player)?;
+
+        // Check every token before storing any, so that a rejected message changes nothing
+        for (index, token, proof) in &tokens {
+            match due.iter().position(|due_index| due_index == index) {
+                Some(position) => due.swap_remove(position),
+                None => return Err(InputError::UnexpectedReveal(*index).into()),
+            };
+
+            DLCards::<C>::verify_reveal(pp, &self.context, &pk, token, &self.deck[*index], proof)
+                .map_err(|source| CardProtocolError::verification_failed(player, source))?;
+        }
+
+        for (index, token, _) in tokens {
+            self.tokens[index][player] = Some(token);
+        }
+
+        for player in 0..self.num_of_players() {
+            if !self.due_reveals(player)?.is_empty() {
+                return Ok(());
+            }
+        }
+
+        if self.phase == SessionPhase::Reveal {
+            for index in self.hands.iter().flatten().copied().collect::<Vec<_>>() {
+                let card = self.open_card(index, &self.tokens[index])?;
+                let position = self
+                    .cards
+                    .iter()
+                    .position(|open| *open == card)
+                    .ok_or(CardProtocolError::InvalidInput(InputError::InvalidPoint))?;
+                self.opened[index] = Some(position);
+            }
+        }
+
+        self.phase = match self.phase {
+            SessionPhase::Deal => SessionPhase::Reveal,
+            _ => SessionPhase::Done,
+        };
+
+        Ok(())
+    }
+
+    /// Unmask the card at `index` from one token per player. The tokens were verified when they
+    /// were received.
+    fn open_card(
+        &self,
+        index: usize,
+        tokens: &[Option<RevealToken<C>>],
+    ) -> Result<Card<C>, CardProtocolError> {
+        let aggregate_token = tokens
+            .iter()
+            .try_fold(RevealToken::<C>::zero(), |acc, token| {
+                token
+                    .map(|token| acc + token)
+                    .ok_or(CardProtocolError::InvalidInput(InputError::UnexpectedPhase))
+            })?;
+
+        aggregate_token.reveal(&self.deck[index])
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{GameMessage, Session, SessionPhase, Transition};
+    use crate::crypto_primitives::utils::format::{Compact, Hex};
+    use crate::crypto_primitives::utils::rand::test_rng;
+    use crate::discrete_log_cards;
+    use crate::discrete_log_cards::encoding::encode_standard_deck;
+    use crate::discrete_log_cards::test_helpers::{setup_players, Player};
+    use crate::discrete_log_cards::validation::MAX_VECTOR_LENGTH;
+    use crate::error::{CardProtocolError, InputError, VerificationError};
+    use crate::BarnettSmartProtocol;
+
+    use ark_serialize::{CanonicalDeserialize, CanonicalSerialize, SerializationError};
+    use ark_std::rand::{CryptoRng, Rng};
+
+    // Choose elliptic curve setting
+    type Curve = starknet_curve::Projective;
+
+    // Instantiate concrete type for our card protocol
+    type CardProtocol<'a> = discrete_log_cards::DLCards<'a, Curve>;
+    type CardParameters = discrete_log_cards::Parameters<Curve>;
+
+    const SESSION_ID: &[u8] = b"session test";
+
+    fn setup<R: Rng + CryptoRng>(
+        rng: &mut R,
+        num_of_players: usize,
+    ) -> (CardParameters, Vec<Player<Curve>>, Session<Curve, u64>) {
+        let m = 4;
+        let n = 13;
+
+        let parameters = CardProtocol::setup(rng, m, n).unwrap();
+        let (keys, _) = setup_players(rng, &parameters, num_of_players).unwrap();
+
+        // Two cards per player, dealt one at a time around the table
+        let hands = (0..num_of_players)
+            .map(|player| vec![player, num_of_players + player])
+            .collect();
+        let session = Session::new(
+            &parameters,
+            SESSION_ID,
+            (0..num_of_players as u64).collect(),
+            encode_standard_deck(1).unwrap(),
+            hands,
+        )
+        .unwrap();
+
+        (parameters, keys, session)
+    }
+
+    fn key_proof<R: Rng + CryptoRng>(
+        rng: &mut R,
+        parameters: &CardParameters,
+        session: &Session<Curve, u64>,
+        keys: &[Player<Curve>],
+        player: usize,
+    ) -> GameMessage<Curve> {
+        let (pk, sk, _) = &keys[player];
+        let proof = CardProtocol::prove_key_ownership(
+            rng,
+            parameters,
+            SESSION_ID,
+            pk,
+            sk,
+            &(player as u64),
+            player,
+            &session.key_list_digest().unwrap().unwrap(),
+        )
+        .unwrap();
+
+        GameMessage::KeyOwnership(proof)
+    }
+
+    fn shuffle<R: Rng + CryptoRng>(
+        rng: &mut R,
+        parameters: &CardParameters,
+        session: &Session<Curve, u64>,
+    ) -> GameMessage<Curve> {
+        let (deck, proof) = CardProtocol::shuffle_and_remask_with_rng(
+            rng,
+            parameters,
+            SESSION_ID,
+            &session.aggregate_key().unwrap(),
+            &session.deck().to_vec(),
+        )
+        .unwrap();
+
+        GameMessage::Shuffle { deck, proof }
+    }
+
+    fn reveal<R: Rng + CryptoRng>(
+        rng: &mut R,
+        parameters: &CardParameters,
+        session: &Session<Curve, u64>,
+        (pk, sk, _): &Player<Curve>,
+        indices: &[usize],
+    ) -> GameMessage<Curve> {
+        let tokens = indices
+            .iter()
+            .map(|&index| {
+                let (token, proof) = CardProtocol::compute_reveal_token(
+                    rng,
+                    parameters,
+                    SESSION_ID,
+                    sk,
+                    pk,
+                    &session.deck()[index],
+                )
+                .unwrap();
+                (index, token, proof)
+            })
+            .collect();
+
+        GameMessage::RevealTokens(tokens)
+    }
+
+    /// Run key exchange and key ownership proofs.
+    fn exchange_keys<R: Rng + CryptoRng>(
+        rng: &mut R,
+        parameters: &CardParameters,
+        session: &mut Session<Curve, u64>,
+        keys: &[Player<Curve>],
+    ) {
+        for (player, (pk, _, _)) in keys.iter().enumerate() {
+            session
+                .receive(parameters, player, GameMessage::PublicKey(*pk))
+                .unwrap();
+        }
+        for player in 0..keys.len() {
+            let message = key_proof(rng, parameters, session, keys, player);
+            session.receive(parameters, player, message).unwrap();
+        }
+    }
+
+    #[test]
+    fn test_forged_vector_lengths_are_refused() {
+        let rng = &mut test_rng();
+        let (parameters, keys, mut session) = setup(rng, 2);
+        exchange_keys(rng, &parameters, &mut session, &keys);
+
+        let message = shuffle(rng, &parameters, &session);
+        let mut bytes = Vec::new();
+        message.serialize(&mut bytes).unwrap();
+        match (
+            message,
+            GameMessage::<Curve>::deserialize(&bytes[..]).unwrap(),
+        ) {
+            (GameMessage::Shuffle { deck, .. }, GameMessage::Shuffle { deck: decoded, .. }) => {
+                assert_eq!(deck, decoded)
+            }
+            _ => panic!("decoded another message"),
+        }
+
+        // Lengths past the global maximum, followed by a few elements or by nothing at all
+        for tag in [2u8, 3] {
+            for claimed in [MAX_VECTOR_LENGTH as u64 + 1, u64::MAX] {
+                let mut forged = vec![tag];
+                forged.extend_from_slice(&claimed.to_le_bytes());
+                forged.extend_from_slice(&bytes[9..bytes.len().min(200)]);
+                assert!(matches!(
+                    GameMessage::<Curve>::deserialize(&forged[..]),
+                    Err(SerializationError::InvalidData)
+                ));
+            }
+        }
+    }
+
+    #[test]
+    fn test_full_game() {
+        let rng = &mut test_rng();
+        let num_of_players = 3;
+        let (parameters, keys, mut session) = setup(rng, num_of_players);
+
+        exchange_keys(rng, &parameters, &mut session, &keys);
+        assert_eq!(session.phase(), SessionPhase::Shuffle(0));
+
+        for player in 0..num_of_players {
+            let message = shuffle(rng, &parameters, &session);
+            let transition = session.receive(&parameters, player, message).unwrap();
+            assert_eq!(transition.from, SessionPhase::Shuffle(player));
+        }
+        assert_eq!(session.phase(), SessionPhase::Deal);
+
+        // A server persists the session between messages
+        let mut bytes = Vec::new();
+        session.serialize(&mut bytes).unwrap();
+        let mut session = Session::<Curve, u64>::deserialize(&bytes[..]).unwrap();
+        assert_eq!(session.phase(), SessionPhase::Deal);
+
+        for player in 0..num_of_players {
+            let indices = session.due_reveals(player).unwrap();
+            assert_eq!(indices.len(), 2 * (num_of_players - 1));
+            let message = reveal(rng, &parameters, &session, &keys[player], &indices);
+            session.receive(&parameters, player, message).unwrap();
+        }
+        assert_eq!(session.phase(), SessionPhase::Reveal);
+
+        let hands = (0..num_of_players)
+            .map(|player| session.open_own_hand(player, &keys[player].1).unwrap())
+            .collect::<Vec<_>>();
+
+        for player in 0..num_of_players {
+            let indices = session.due_reveals(player).unwrap();
+            assert_eq!(indices, session.hand(player).unwrap());
+            let message = reveal(rng, &parameters, &session, &keys[player], &indices);
+            let transition = session.receive(&parameters, player, message).unwrap();
+            assert_eq!(transition.from, SessionPhase::Reveal);
+        }
+        assert_eq!(session.phase(), SessionPhase::Done);
+
+        for player in 0..num_of_players {
+            assert_eq!(session.opened_hand(player), Some(hands[player].clone()));
+        }
+    }
+
+    #[test]
+    fn test_debug() {
+        let rng = &mut test_rng();
+        let (parameters, keys, mut session) = setup(rng, 2);
+
+        let pk = keys[0].0;
+        assert_eq!(
+            format!("PublicKey({:?})", Hex(&pk)),
+            format!("{:?}", GameMessage::<Curve>::PublicKey(pk))
+        );
+
+        exchange_keys(rng, &parameters, &mut session, &keys);
+        let formatted = format!("{:?}", session);
+        assert!(formatted.starts_with(&format!(
+            "Session {{ phase: Shuffle(0), context: 0x7365…74, keys: [Some({:?}), ",
+            Hex(&pk)
+        )));
+        assert!(formatted.ends_with(", sequence: 4, .. }"));
+
+        match key_proof(rng, &parameters, &session, &keys, 0) {
+            GameMessage::KeyOwnership(proof) => assert_eq!(
+                format!("KeyOwnership({:?})", Compact(&proof)),
+                format!("{:?}", GameMessage::KeyOwnership(proof))
+            ),
+            _ => panic!("expected a key ownership proof"),
+        }
+
+        let message = shuffle(rng, &parameters, &session);
+        match &message {
+            GameMessage::Shuffle { deck, proof } => {
+                let formatted_proof = format!("{:?}", Compact(proof));
+                assert!(formatted_proof.starts_with("ShuffleProof(0x"));
+                assert_eq!(
+                    format!(
+                        "Shuffle {{ deck: {:?}, proof: {} }}",
+                        Compact(deck),
+                        formatted_proof
+                    ),
+                    format!("{:?}", message)
+                );
+            }
+            _ => panic!("expected a shuffle"),
+        }
+
+        match reveal(rng, &parameters, &session, &keys[0], &[3]) {
+            GameMessage::RevealTokens(tokens) => {
+                let (_, token, proof) = &tokens[0];
+                assert_eq!(
+                    format!(
+                        "RevealTokens([(3, {:?}, {:?})])",
+                        Compact(token),
+                        Compact(proof)
+                    ),
+                    format!("{:?}", GameMessage::RevealTokens(tokens.clone()))
+                );
+            }
+            _ => panic!("expected reveal tokens"),
+        }
+    }
+
+    #[test]
+    fn test_out_of_order_messages_are_rejected() {
+        let rng = &mut test_rng();
+        let num_of_players = 3;
+        let (parameters, keys, mut session) = setup(rng, num_of_players);
+
+        // No key proofs before every key is known
+        session
+            .receive(&parameters, 0, GameMessage::PublicKey(keys[0].0))
+            .unwrap();
+        let (pk, sk, _) = &keys[0];
+        let proof = CardProtocol::prove_key_ownership(
+            rng,
+            &parameters,
+            SESSION_ID,
+            pk,
+            sk,
+            &0u64,
+            0,
+            &[0u8; 32],
+        )
+        .unwrap();
+        assert_eq!(
+            session.receive(&parameters, 0, GameMessage::KeyOwnership(proof)),
+            Err(CardProtocolError::InvalidInput(InputError::UnexpectedPhase))
+        );
+        assert_eq!(
+            session.receive(&parameters, 0, GameMessage::PublicKey(keys[0].0)),
+            Err(CardProtocolError::InvalidInput(
+                InputError::DuplicateMessage(0)
+            ))
+        );
+
+        for (player, (pk, _, _)) in keys.iter().enumerate().skip(1) {
+            let transition = session
+                .receive(&parameters, player, GameMessage::PublicKey(*pk))
+                .unwrap();
+            assert_eq!(transition.from, SessionPhase::KeyExchange);
+        }
+        assert_eq!(session.phase(), SessionPhase::KeyOwnership);
+
+        // A proof made by another player does not verify for this seat
+        let message = key_proof(rng, &parameters, &session, &keys, 1);
+        assert!(matches!(
+            session.receive(&parameters, 2, message),
+            Err(CardProtocolError::VerificationFailed(
+                VerificationError::PlayerMessage {
+                    player: 2,
+                    source: Some(_)
+                }
+            ))
+        ));
+        for player in 0..num_of_players {
+            let message = key_proof(rng, &parameters, &session, &keys, player);
+            session.receive(&parameters, player, message).unwrap();
+        }
+        assert_eq!(session.phase(), SessionPhase::Shuffle(0));
+
+        // Player 1 cannot shuffle before player 0, and nobody can reveal before the deal
+        let message = shuffle(rng, &parameters, &session);
+        assert_eq!(
+            session.receive(&parameters, 1, message.clone()),
+            Err(CardProtocolError::InvalidInput(InputError::NotYourTurn(1)))
+        );
+        let tokens = reveal(rng, &parameters, &session, &keys[1], &[0]);
+        assert_eq!(
+            session.receive(&parameters, 1, tokens),
+            Err(CardProtocolError::InvalidInput(InputError::UnexpectedPhase))
+        );
+        assert_eq!(
+            session.receive(&parameters, 0, message),
+            Ok(Transition {
+                from: SessionPhase::Shuffle(0),
+                to: SessionPhase::Shuffle(1)
+            })
+        );
+
+        // A shuffle of a stale deck does not verify
+        let stale = shuffle(rng, &parameters, &session);
+        let message = shuffle(rng, &parameters, &session);
+        session.receive(&parameters, 1, message).unwrap();
+        assert!(matches!(
+            session.receive(&parameters, 2, stale),
+            Err(CardProtocolError::VerificationFailed(
+                VerificationError::PlayerMessage {
+                    player: 2,
+                    source: Some(_)
+                }
+            ))
+        ));
+        let message = shuffle(rng, &parameters, &session);
+        session.receive(&parameters, 2, message).unwrap();
+        assert_eq!(session.phase(), SessionPhase::Deal);
+
+        // A seat past the table is reported rather than indexed
+        let unknown = Some(CardProtocolError::InvalidInput(InputError::UnknownPlayer(
+            num_of_players,
+        )));
+        assert_eq!(session.hand(num_of_players).err(), unknown);
+        assert_eq!(session.due_reveals(num_of_players).err(), unknown);
+
+        // Players do not reveal their own cards during the deal, and tokens must match the key
+        let own = session.hand(0).unwrap().to_vec();
+        let message = reveal(rng, &parameters, &session, &keys[0], &own);
+        assert_eq!(
+            session.receive(&parameters, 0, message),
+            Err(CardProtocolError::InvalidInput(
+                InputError::UnexpectedReveal(own[0])
+            ))
+        );
+        let indices = session.due_reveals(0).unwrap();
+        let message = reveal(rng, &parameters, &session, &keys[1], &indices);
+        assert!(matches!(
+            session.receive(&parameters, 0, message),
+            Err(CardProtocolError::VerificationFailed(
+                VerificationError::PlayerMessage {
+                    player: 0,
+                    source: Some(_)
+                }
+            ))
+        ));
+        assert_eq!(session.due_reveals(0), Ok(indices));
+
+        // Nobody can open a hand before the deal is complete
+        assert_eq!(
+            session.open_own_hand(0, &keys[0].1),
+            Err(CardProtocolError::InvalidInput(InputError::UnexpectedPhase))
+        );
+    }
+
+    #[test]
+    fn test_invalid_deal_is_rejected() {
+        let rng = &mut test_rng();
+        let m = 4;
+        let n = 13;
+
+        let parameters = CardProtocol::setup(rng, m, n).unwrap();
+        let cards = encode_standard_deck::<Curve>(1).unwrap();
+
+        for hands in [vec![vec![0, 1], vec![1, 2]], vec![vec![0], vec![52]]] {
+            assert_eq!(
+                Session::new(&parameters, SESSION_ID, vec![0u64, 1], cards.clone(), hands).err(),
+                Some(CardProtocolError::InvalidInput(InputError::InvalidDeal(52)))
+            );
+        }
+    }
+}
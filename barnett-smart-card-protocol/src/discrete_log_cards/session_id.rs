@@ -0,0 +1,167 @@
+//! Canonical identifier of a game.
+//!
+//! Every proof is bound to a context, and every player must pass the same one. A [`SessionId`]
+//! hashes the canonical encodings of the parameters, the players' keys in seat order and a digest
+//! of the rules the table plays by, so that independent implementations agree on it byte for byte.
+//! Pass it wherever the protocol takes a `context`: it derefs to the bytes of the identifier.
+
+use crate::crypto_primitives::utils::format::HexBytes;
+use crate::discrete_log_cards::{Parameters, PublicKey};
+use crate::error::CardProtocolError;
+
+use ark_ec::ProjectiveCurve;
+use ark_serialize::CanonicalSerialize;
+use blake2::{Blake2s, Digest};
+use std::fmt;
+use std::ops::Deref;
+
+const SESSION_ID_DOMAIN: &'static [u8] = b"Session Id";
+
+#[derive(Clone, Copy, PartialEq, Eq, Hash)]
+pub struct SessionId([u8; 32]);
+
+impl SessionId {
+    /// Identifier of the game played with `pp` by the players with keys `player_keys`, in seat
+    /// order, under the rules with digest `rules_digest`.
+    pub fn derive<C: ProjectiveCurve>(
+        pp: &Parameters<C>,
+        player_keys: &[PublicKey<C>],
+        rules_digest: &[u8],
+    ) -> Result<Self, CardProtocolError> {
+        let mut bytes = Vec::new();
+        (pp.m as u64, pp.n as u64).serialize(&mut bytes)?;
+        pp.enc_parameters.serialize(&mut bytes)?;
+        pp.commit_parameters.serialize(&mut bytes)?;
+        pp.generator.serialize(&mut bytes)?;
+
+        (player_keys.len() as u64).serialize(&mut bytes)?;
+        for key in player_keys {
+            key.serialize(&mut bytes)?;
+        }
+
+        (rules_digest.len() as u64).serialize(&mut bytes)?;
+        bytes.extend_from_slice(rules_digest);
+
+        let mut hasher = Blake2s::new();
+        hasher.update(SESSION_ID_DOMAIN);
+        hasher.update(&bytes);
+
+        let mut id = [0u8; 32];
+        id.copy_from_slice(&hasher.finalize());
+
+        Ok(Self(id))
+    }
+
+    pub fn as_bytes(&self) -> &[u8; 32] {
+        &self.0
+    }
+}
+
+impl Deref for SessionId {
+    type Target = [u8];
+
+    fn deref(&self) -> &[u8] {
+        &self.0
+    }
+}
+
+impl AsRef<[u8]> for SessionId {
+    fn as_ref(&self) -> &[u8] {
+        &self.0
+    }
+}
+
+impl fmt::Debug for SessionId {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "SessionId({:?})", HexBytes(&self.0))
+    }
+}
+
+/// Lowercase hexadecimal, as the identifier appears in logs and certificates.
+impl fmt::Display for SessionId {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.0.iter().try_for_each(|byte| write!(f, "{:02x}", byte))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::SessionId;
+    use crate::crypto_primitives::utils::rand::test_rng;
+    use crate::discrete_log_cards;
+    use crate::discrete_log_cards::test_helpers::setup_players;
+    use crate::BarnettSmartProtocol;
+
+    use ark_std::UniformRand;
+
+    // Choose elliptic curve setting
+    type Curve = starknet_curve::Projective;
+
+    // Instantiate concrete type for our card protocol
+    type CardProtocol<'a> = discrete_log_cards::DLCards<'a, Curve>;
+    type MaskedCard = discrete_log_cards::MaskedCard<Curve>;
+
+    const RULES: &[u8] = b"no-limit hold'em";
+
+    #[test]
+    fn test_session_id() {
+        let rng = &mut test_rng();
+        let m = 4;
+        let n = 13;
+        let num_of_players = 3;
+
+        let parameters = CardProtocol::setup(rng, m, n).unwrap();
+        let (keys, _) = setup_players(rng, &parameters, num_of_players).unwrap();
+        let public_keys = keys.iter().map(|(pk, _, _)| *pk).collect::<Vec<_>>();
+
+        let id = SessionId::derive(&parameters, &public_keys, RULES).unwrap();
+        assert_eq!(
+            id,
+            SessionId::derive(&parameters, &public_keys, RULES).unwrap()
+        );
+
+        let hex = id.to_string();
+        assert_eq!(hex.len(), 64);
+        assert!(hex
+            .chars()
+            .all(|c| c.is_ascii_hexdigit() && !c.is_ascii_uppercase()));
+
+        // Player order, each key and the rules are all bound
+        let mut reordered = public_keys.clone();
+        reordered.swap(0, 1);
+        assert_ne!(
+            id,
+            SessionId::derive(&parameters, &reordered, RULES).unwrap()
+        );
+
+        let mut swapped = public_keys.clone();
+        swapped[2] = CardProtocol::player_keygen(rng, &parameters).unwrap().0;
+        assert_ne!(id, SessionId::derive(&parameters, &swapped, RULES).unwrap());
+
+        assert_ne!(
+            id,
+            SessionId::derive(&parameters, &public_keys, b"pot-limit omaha").unwrap()
+        );
+
+        let other_parameters = CardProtocol::setup(rng, m, n).unwrap();
+        assert_ne!(
+            id,
+            SessionId::derive(&other_parameters, &public_keys, RULES).unwrap()
+        );
+
+        // Proofs bound to the identifier do not verify in another game
+        let other_id = SessionId::derive(&parameters, &reordered, RULES).unwrap();
+        let masked = MaskedCard::rand(rng);
+        let (pk, sk, _) = &keys[0];
+        let (token, proof) =
+            CardProtocol::compute_reveal_token(rng, &parameters, &id, sk, pk, &masked).unwrap();
+        assert_eq!(
+            Ok(()),
+            CardProtocol::verify_reveal(&parameters, &id, pk, &token, &masked, &proof)
+        );
+        assert!(
+            CardProtocol::verify_reveal(&parameters, &other_id, pk, &token, &masked, &proof)
+                .is_err()
+        );
+    }
+}
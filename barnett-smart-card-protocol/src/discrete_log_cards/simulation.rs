@@ -0,0 +1,656 @@
+//! A whole game played by simulated players in a single process.
+//!
+//! [`simulate_game`] plays every seat of a table the way a real table does: each player generates
+//! a key and proves they own it, the aggregate key masks the starting deck, every player shuffles
+//! in turn, the hole cards are dealt as a [`DealPlan`] says and peeked at by their owners, the
+//! community cards are revealed street by street, and the hands are shown. Every message is
+//! signed with the identity key of its sender and checked by a [`Watchdog`] for every seat before
+//! it is appended to an [`EventLog`].
+//!
+//! The game draws all its randomness from the generator it is given, so a seeded generator always
+//! produces the same log. This makes the simulation both an integration test of the protocol and
+//! a source of transcripts for fixtures.
+
+use crate::crypto_primitives::utils::format::Hex;
+use crate::discrete_log_cards::abort::{GameRecord, SignedMessage};
+use crate::discrete_log_cards::deal::DealPlan;
+use crate::discrete_log_cards::encoding::{DeckSpec, EncodedDeck};
+use crate::discrete_log_cards::event_log::EventLog;
+use crate::discrete_log_cards::identity::{IdentityKey, PublicIdentity};
+use crate::discrete_log_cards::reveal::{reveal_community_cards, unmask_from_players};
+use crate::discrete_log_cards::session::{GameMessage, Session};
+use crate::discrete_log_cards::watchdog::{Violation, Watchdog};
+use crate::discrete_log_cards::{setup_for_spec, DLCards, Parameters, PlayerSecretKey, PublicKey};
+use crate::error::{CardProtocolError, InputError, VerificationError};
+use crate::BarnettSmartProtocol;
+
+use ark_ec::ProjectiveCurve;
+use ark_std::fmt;
+use ark_std::rand::{CryptoRng, Rng, SeedableRng};
+use rand_chacha::ChaCha20Rng;
+
+/// How the simulated game is dealt, and the context its proofs are bound to.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct SimulationRules {
+    pub context: Vec<u8>,
+    pub hole_cards_per_player: usize,
+    /// Number of community cards of each street
+    pub community_cards: Vec<usize>,
+    /// Number of cards burned before each street
+    pub burns: usize,
+}
+
+impl SimulationRules {
+    /// The deal of `DealPlan::texas_holdem`.
+    pub fn texas_holdem(context: &[u8]) -> Self {
+        Self {
+            context: context.to_vec(),
+            hole_cards_per_player: 2,
+            community_cards: vec![3, 1, 1],
+            burns: 1,
+        }
+    }
+
+    /// The plan these rules deal for `num_of_players` players.
+    pub fn plan(&self, num_of_players: usize) -> Result<DealPlan, CardProtocolError> {
+        DealPlan::new(
+            num_of_players,
+            self.hole_cards_per_player,
+            &self.community_cards,
+            self.burns,
+        )
+    }
+}
+
+/// Result of a simulated game. Cards are given as positions in the [`DeckSpec`] the game was
+/// played with.
+pub struct GameOutcome<C: ProjectiveCurve> {
+    pub parameters: Parameters<C>,
+    pub deck: EncodedDeck<C>,
+    pub plan: DealPlan,
+    /// Identity keys the messages of the log are signed with, by seat
+    pub identities: Vec<PublicIdentity<C>>,
+    pub keys: Vec<PublicKey<C>>,
+    /// Hole cards each player saw when peeking at their hand, by seat
+    pub peeked: Vec<Vec<usize>>,
+    /// Community cards, by street
+    pub community: Vec<Vec<usize>>,
+    /// Hole cards shown at showdown, by seat
+    pub hands: Vec<Vec<usize>>,
+    /// Every message of the game, in the order it was verified and applied
+    pub log: EventLog<C>,
+}
+
+impl<C: ProjectiveCurve> fmt::Debug for GameOutcome<C> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("GameOutcome")
+            .field("parameters", &self.parameters)
+            .field("deck", &self.deck)
+            .field("plan", &self.plan)
+            .field("identities", &self.identities)
+            .field("keys", &self.keys.iter().map(Hex).collect::<Vec<_>>())
+            .field("peeked", &self.peeked)
+            .field("community", &self.community)
+            .field("hands", &self.hands)
+            .field("log", &self.log)
+            .finish()
+    }
+}
+
+impl<C: ProjectiveCurve> GameOutcome<C> {
+    /// Every card revealed during the game, hole cards first.
+    pub fn revealed_cards(&self) -> Vec<usize> {
+        self.hands
+            .iter()
+            .chain(&self.community)
+            .flatten()
+            .copied()
+            .collect()
+    }
+
+    /// Label of the card at `position` of the deck.
+    pub fn label(&self, position: usize) -> Option<&str> {
+        self.deck.labels().get(position).map(String::as_str)
+    }
+}
+
+/// Play a game of `num_of_players` players over the cards of `deck_spec`, dealt by `rules`. Fails
+/// with the first error any step returns, which for an honest simulation is a bug.
+pub fn simulate_game<R: Rng + CryptoRng, C: ProjectiveCurve>(
+    rng: &mut R,
+    num_of_players: usize,
+    deck_spec: &DeckSpec,
+    rules: &SimulationRules,
+) -> Result<GameOutcome<C>, CardProtocolError> {
+    match play(rng, num_of_players, deck_spec, rules, &mut Honest)? {
+        Played::Completed(outcome) => Ok(outcome),
+        Played::Aborted(aborted) => Err(aborted
+            .violations
+            .into_iter()
+            .next()
+            .map(|(_, violation)| violation.error)
+            .unwrap_or(CardProtocolError::InvalidInput(InputError::UnexpectedPhase))),
+    }
+}
+
+/// What a seat sends for a step of the game.
+#[derive(Debug)]
+pub enum Outgoing<C: ProjectiveCurve> {
+    /// The same message to every player
+    Broadcast(GameMessage<C>),
+    /// A message for every player, by seat, all signed for the same slot
+    PerRecipient(Vec<GameMessage<C>>),
+}
+
+/// Everything a seat knows when it sends a message.
+pub struct SeatView<'a, C: ProjectiveCurve> {
+    pub seat: usize,
+    pub parameters: &'a Parameters<C>,
+    /// Public state of the game before the message, as every player holds it
+    pub session: &'a Session<C, u64>,
+    /// Messages sent so far
+    pub log: &'a EventLog<C>,
+    pub public_key: PublicKey<C>,
+    pub secret_key: &'a PlayerSecretKey<C>,
+    /// Randomness of the seat for this message, drawn from the generator of the game
+    pub rng: ChaCha20Rng,
+}
+
+/// The log and the generator are left out.
+impl<C: ProjectiveCurve> fmt::Debug for SeatView<'_, C> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("SeatView")
+            .field("seat", &self.seat)
+            .field("parameters", self.parameters)
+            .field("session", self.session)
+            .field("public_key", &Hex(&self.public_key))
+            .field("secret_key", self.secret_key)
+            .finish_non_exhaustive()
+    }
+}
+
+/// Steps of the game at which a seat sends a message.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub(crate) enum Step {
+    PublicKey,
+    KeyOwnership,
+    Shuffle,
+    Deal,
+    Showdown,
+}
+
+/// Turns the message the protocol computes for a seat into what the seat actually sends.
+pub(crate) trait Hooks<C: ProjectiveCurve> {
+    fn send(
+        &mut self,
+        step: Step,
+        view: &mut SeatView<'_, C>,
+        honest: GameMessage<C>,
+    ) -> Result<Outgoing<C>, CardProtocolError>;
+}
+
+/// Every seat sends what the protocol computes.
+struct Honest;
+
+impl<C: ProjectiveCurve> Hooks<C> for Honest {
+    fn send(
+        &mut self,
+        _: Step,
+        _: &mut SeatView<'_, C>,
+        honest: GameMessage<C>,
+    ) -> Result<Outgoing<C>, CardProtocolError> {
+        Ok(Outgoing::Broadcast(honest))
+    }
+}
+
+pub(crate) enum Played<C: ProjectiveCurve> {
+    Completed(GameOutcome<C>),
+    Aborted(Aborted<C>),
+}
+
+/// A game stopped by the first message a watchdog rejected.
+pub(crate) struct Aborted<C: ProjectiveCurve> {
+    pub(crate) parameters: Parameters<C>,
+    /// Record of the game, once every key is published
+    pub(crate) record: Option<GameRecord<C, u64>>,
+    /// The violation raised by the watchdog of every seat that raised one, by seat
+    pub(crate) violations: Vec<(usize, Violation<C>)>,
+}
+
+/// Play the game with every message going through `hooks`. Every seat checks the messages with a
+/// watchdog of its own, and the game stops as soon as one of them raises a violation.
+pub(crate) fn play<R, C, H>(
+    rng: &mut R,
+    num_of_players: usize,
+    deck_spec: &DeckSpec,
+    rules: &SimulationRules,
+    hooks: &mut H,
+) -> Result<Played<C>, CardProtocolError>
+where
+    R: Rng + CryptoRng,
+    C: ProjectiveCurve,
+    H: Hooks<C>,
+{
+    let (parameters, deck) = setup_for_spec::<_, C>(rng, deck_spec)?;
+    let plan = rules.plan(num_of_players)?;
+    plan.validate(deck.cards().len())?;
+
+    let context = rules.context.as_slice();
+    let identity_keys = (0..num_of_players)
+        .map(|_| IdentityKey::<C>::new(rng))
+        .collect::<Result<Vec<_>, _>>()?;
+    let identities = identity_keys
+        .iter()
+        .map(IdentityKey::verifying_key)
+        .collect::<Vec<_>>();
+    let watchdogs = (0..num_of_players)
+        .map(|_| {
+            Watchdog::new(
+                &parameters,
+                context,
+                identities.clone(),
+                (0..num_of_players as u64).collect(),
+                deck.cards().to_vec(),
+                &plan,
+            )
+        })
+        .collect::<Result<Vec<_>, _>>()?;
+    let mut table = Table {
+        parameters: &parameters,
+        identity_keys,
+        watchdogs,
+        log: EventLog::new(context),
+    };
+
+    let players = (0..num_of_players)
+        .map(|_| DLCards::<C>::player_keygen(rng, &parameters))
+        .collect::<Result<Vec<_>, _>>()?;
+    let dealt = match deal(rng, &mut table, &players, &deck, &plan, hooks) {
+        Ok(dealt) => dealt,
+        Err(_) if table.is_flagged() => {
+            let record = table.watchdogs.iter().find_map(Watchdog::record);
+            let violations = table
+                .watchdogs
+                .into_iter()
+                .enumerate()
+                .filter_map(|(seat, watchdog)| Some((seat, watchdog.into_violation()?)))
+                .collect();
+
+            return Ok(Played::Aborted(Aborted {
+                parameters,
+                record,
+                violations,
+            }));
+        }
+        Err(error) => return Err(error),
+    };
+
+    Ok(Played::Completed(GameOutcome {
+        log: table.log,
+        parameters,
+        deck,
+        plan,
+        identities,
+        keys: players.iter().map(|(pk, _)| *pk).collect(),
+        peeked: dealt.peeked,
+        community: dealt.community,
+        hands: dealt.hands,
+    }))
+}
+
+/// Cards revealed in a game, as in [`GameOutcome`].
+struct Dealt {
+    peeked: Vec<Vec<usize>>,
+    community: Vec<Vec<usize>>,
+    hands: Vec<Vec<usize>>,
+}
+
+fn deal<R, C, H>(
+    rng: &mut R,
+    table: &mut Table<'_, C>,
+    players: &[(PublicKey<C>, PlayerSecretKey<C>)],
+    deck: &EncodedDeck<C>,
+    plan: &DealPlan,
+    hooks: &mut H,
+) -> Result<Dealt, CardProtocolError>
+where
+    R: Rng + CryptoRng,
+    C: ProjectiveCurve,
+    H: Hooks<C>,
+{
+    let parameters = table.parameters;
+    let context = table.session().context().to_vec();
+    for (seat, player) in players.iter().enumerate() {
+        let message = GameMessage::PublicKey(player.0);
+        table.send(rng, hooks, Step::PublicKey, seat, player, message)?;
+    }
+
+    // The session aggregates the keys and masks the starting deck once the last proof is in
+    let key_list_digest = table
+        .session()
+        .key_list_digest()?
+        .ok_or(CardProtocolError::InvalidInput(InputError::UnexpectedPhase))?;
+    for (seat, (pk, sk)) in players.iter().enumerate() {
+        let proof = DLCards::<C>::prove_key_ownership(
+            rng,
+            parameters,
+            &context,
+            pk,
+            sk,
+            &(seat as u64),
+            seat,
+            &key_list_digest,
+        )?;
+        let message = GameMessage::KeyOwnership(proof);
+        table.send(
+            rng,
+            hooks,
+            Step::KeyOwnership,
+            seat,
+            &players[seat],
+            message,
+        )?;
+    }
+    let shared_key = table
+        .session()
+        .aggregate_key()
+        .ok_or(CardProtocolError::InvalidInput(InputError::UnexpectedPhase))?;
+
+    for (seat, player) in players.iter().enumerate() {
+        let (shuffled, proof) = DLCards::<C>::shuffle_and_remask_with_rng(
+            rng,
+            parameters,
+            &context,
+            &shared_key,
+            &table.session().deck().to_vec(),
+        )?;
+        let message = GameMessage::Shuffle {
+            deck: shuffled,
+            proof,
+        };
+        table.send(rng, hooks, Step::Shuffle, seat, player, message)?;
+    }
+
+    // Deal: every player reveals the hole cards of the others, then each peeks at their own
+    for (seat, player) in players.iter().enumerate() {
+        let message = table.reveal_due(rng, seat, player)?;
+        table.send(rng, hooks, Step::Deal, seat, player, message)?;
+    }
+    let peeked = players
+        .iter()
+        .enumerate()
+        .map(|(seat, (_, sk))| table.session().open_own_hand(seat, sk))
+        .collect::<Result<Vec<_>, _>>()?;
+
+    let keys = players.iter().map(|(pk, _)| *pk).collect::<Vec<_>>();
+    let final_deck = table.session().deck().to_vec();
+    let mut community = Vec::with_capacity(plan.num_streets());
+    for street in 0..plan.num_streets() {
+        let tokens = players
+            .iter()
+            .map(|(pk, sk)| {
+                reveal_community_cards(rng, parameters, &context, plan, street, sk, pk, &final_deck)
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+
+        let cards = plan
+            .community(street)
+            .iter()
+            .enumerate()
+            .map(|(k, &position)| {
+                let decryption_key = tokens
+                    .iter()
+                    .zip(&keys)
+                    .map(|(tokens, pk)| (tokens[k].1, tokens[k].2.clone(), *pk))
+                    .collect();
+                let card = unmask_from_players(
+                    parameters,
+                    &context,
+                    &keys,
+                    &decryption_key,
+                    &final_deck[position],
+                )?;
+
+                deck.position(&card)?
+                    .ok_or(CardProtocolError::InvalidInput(InputError::InvalidPoint))
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+
+        // The session does not follow the streets, so their tokens are logged once they opened
+        // the cards, without going through the watchdogs
+        for (seat, tokens) in tokens.into_iter().enumerate() {
+            table.log_signed(seat, GameMessage::RevealTokens(tokens))?;
+        }
+        community.push(cards);
+    }
+
+    // Showdown: every player opens their own hand
+    for (seat, player) in players.iter().enumerate() {
+        let message = table.reveal_due(rng, seat, player)?;
+        table.send(rng, hooks, Step::Showdown, seat, player, message)?;
+    }
+    let hands = (0..players.len())
+        .map(|seat| table.session().opened_hand(seat))
+        .collect::<Option<Vec<_>>>()
+        .ok_or(CardProtocolError::InvalidInput(InputError::UnexpectedPhase))?;
+
+    Ok(Dealt {
+        peeked,
+        community,
+        hands,
+    })
+}
+
+/// The simulated table: the identity key of every seat, the watchdog every seat checks the
+/// messages with, and the log of the messages sent.
+struct Table<'a, C: ProjectiveCurve> {
+    parameters: &'a Parameters<C>,
+    identity_keys: Vec<IdentityKey<C>>,
+    watchdogs: Vec<Watchdog<C, u64>>,
+    log: EventLog<C>,
+}
+
+impl<'a, C: ProjectiveCurve> Table<'a, C> {
+    /// Public state of the game. Every watchdog holds the same as long as none is flagged.
+    fn session(&self) -> &Session<C, u64> {
+        self.watchdogs[0].session()
+    }
+
+    fn is_flagged(&self) -> bool {
+        self.watchdogs
+            .iter()
+            .any(|watchdog| watchdog.violation().is_some())
+    }
+
+    /// Send a message of `seat` for `step` through `hooks`, sign what comes out for the next slot
+    /// and deliver it. Players then compare what they received, as the relay forwards every
+    /// message to everyone, so every watchdog sees every version. Fails once a watchdog raises a
+    /// violation.
+    fn send<R: Rng + CryptoRng, H: Hooks<C>>(
+        &mut self,
+        rng: &mut R,
+        hooks: &mut H,
+        step: Step,
+        seat: usize,
+        (pk, sk): &(PublicKey<C>, PlayerSecretKey<C>),
+        honest: GameMessage<C>,
+    ) -> Result<(), CardProtocolError> {
+        let mut seed = [0u8; 32];
+        rng.fill_bytes(&mut seed);
+        let mut view = SeatView {
+            seat,
+            parameters: self.parameters,
+            session: self.session(),
+            log: &self.log,
+            public_key: *pk,
+            secret_key: sk,
+            rng: ChaCha20Rng::from_seed(seed),
+        };
+        let messages = match hooks.send(step, &mut view, honest)? {
+            Outgoing::Broadcast(message) => vec![message; self.watchdogs.len()],
+            Outgoing::PerRecipient(messages) => messages,
+        };
+        if messages.len() != self.watchdogs.len() {
+            return Err(InputError::InvalidPlayerCount(messages.len()).into());
+        }
+
+        let context = self.log.context().to_vec();
+        let slot = self.log.len() as u64;
+        let signed = messages
+            .into_iter()
+            .map(|message| {
+                SignedMessage::sign(&self.identity_keys[seat], &context, seat, slot, message)
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+
+        for (recipient, watchdog) in self.watchdogs.iter_mut().enumerate() {
+            let delivered = signed[recipient].clone();
+            let others = signed.iter().cloned();
+            for message in ark_std::iter::once(delivered).chain(others) {
+                watchdog.observe(self.parameters, message)?;
+            }
+        }
+        if self.is_flagged() {
+            return Err(VerificationError::PlayerMessage {
+                player: seat,
+                source: None,
+            }
+            .into());
+        }
+
+        self.log.append_signed(signed[0].clone())?;
+
+        Ok(())
+    }
+
+    /// Sign a message of `seat` for the next slot and log it.
+    fn log_signed(
+        &mut self,
+        seat: usize,
+        message: GameMessage<C>,
+    ) -> Result<(), CardProtocolError> {
+        let slot = self.log.len() as u64;
+        let signed = SignedMessage::sign(
+            &self.identity_keys[seat],
+            self.log.context(),
+            seat,
+            slot,
+            message,
+        )?;
+        self.log.append_signed(signed)?;
+
+        Ok(())
+    }
+
+    /// Reveal tokens for every card the session expects from `seat` in the current phase.
+    fn reveal_due<R: Rng + CryptoRng>(
+        &self,
+        rng: &mut R,
+        seat: usize,
+        (pk, sk): &(PublicKey<C>, PlayerSecretKey<C>),
+    ) -> Result<GameMessage<C>, CardProtocolError> {
+        let session = self.session();
+        let tokens = session
+            .due_reveals(seat)?
+            .into_iter()
+            .map(|index| {
+                let (token, proof) = DLCards::<C>::compute_reveal_token(
+                    rng,
+                    self.parameters,
+                    session.context(),
+                    sk,
+                    pk,
+                    &session.deck()[index],
+                )?;
+                Ok((index, token, proof))
+            })
+            .collect::<Result<Vec<_>, CardProtocolError>>()?;
+
+        Ok(GameMessage::RevealTokens(tokens))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{simulate_game, SimulationRules};
+    use crate::crypto_primitives::utils::rand::test_rng;
+    use crate::discrete_log_cards::encoding::DeckSpec;
+    use crate::error::{CardProtocolError, InputError};
+
+    use ark_std::rand::SeedableRng;
+    use rand_chacha::ChaCha20Rng;
+    use std::collections::HashSet;
+
+    // Choose elliptic curve setting
+    type Curve = starknet_curve::Projective;
+
+    #[test]
+    fn test_simulated_game() {
+        let rng = &mut test_rng();
+        let num_of_players = 3;
+
+        let rules = SimulationRules::texas_holdem(b"simulation");
+        let outcome =
+            simulate_game::<_, Curve>(rng, num_of_players, &DeckSpec::standard52(), &rules)
+                .unwrap();
+
+        // Every dealt card is unique and from the deck, and burned cards stay masked
+        let revealed = outcome.revealed_cards();
+        assert_eq!(revealed.len(), num_of_players * 2 + 5);
+        assert_eq!(
+            revealed.iter().collect::<HashSet<_>>().len(),
+            revealed.len()
+        );
+        assert!(revealed
+            .iter()
+            .all(|&position| outcome.label(position).is_some()));
+
+        assert_eq!(outcome.peeked, outcome.hands);
+        assert_eq!(
+            outcome.community.iter().map(Vec::len).collect::<Vec<_>>(),
+            vec![3, 1, 1]
+        );
+
+        // Keys, key proofs, shuffles, deal, streets and showdown, one message per player each
+        assert_eq!(outcome.log.len(), num_of_players * (5 + 3));
+        assert_eq!(Ok(()), outcome.log.verify_chain());
+        assert_eq!(Ok(()), outcome.log.verify_signatures(&outcome.identities));
+        assert_eq!(outcome.log.context(), b"simulation");
+    }
+
+    #[test]
+    fn test_seeded_simulations_are_reproducible() {
+        let rules = SimulationRules {
+            context: b"fixture".to_vec(),
+            hole_cards_per_player: 1,
+            community_cards: vec![2],
+            burns: 0,
+        };
+        let simulate = || {
+            let rng = &mut ChaCha20Rng::from_seed([7; 32]);
+            simulate_game::<_, Curve>(rng, 2, &DeckSpec::short_deck(), &rules).unwrap()
+        };
+
+        let (first, second) = (simulate(), simulate());
+        assert_eq!(first.log.head(), second.log.head());
+        assert_eq!(first.revealed_cards(), second.revealed_cards());
+        assert_eq!(first.revealed_cards().len(), 4);
+    }
+
+    #[test]
+    fn test_simulation_rejects_a_plan_larger_than_the_deck() {
+        let rng = &mut test_rng();
+        let rules = SimulationRules {
+            context: b"simulation".to_vec(),
+            hole_cards_per_player: 20,
+            community_cards: vec![],
+            burns: 0,
+        };
+
+        let outcome = simulate_game::<_, Curve>(rng, 2, &DeckSpec::short_deck(), &rules);
+        assert!(matches!(
+            outcome,
+            Err(CardProtocolError::InvalidInput(InputError::InvalidDeal(36)))
+        ));
+    }
+}
@@ -0,0 +1,240 @@
+//! Proptest strategies for the types of the protocol, so that code built on it can be property
+//! tested against random but valid objects without playing a whole game.
+//!
+//! Every strategy draws a seed and derives its value with a ChaCha20 generator, so proptest
+//! replays failing cases exactly. Group elements have no simpler form, so the values do not
+//! shrink. Objects that come with a proof are produced by the protocol itself and always verify.
+
+use crate::crypto_primitives::utils::format::Hex;
+use crate::discrete_log_cards::{
+    Card, DLCards, MaskedCard, Parameters, PlayerSecretKey, PublicKey,
+};
+use crate::{BarnettSmartProtocol, Mask};
+
+use ark_ec::ProjectiveCurve;
+use ark_ff::{Field, UniformRand};
+use ark_std::fmt;
+use ark_std::ops::Range;
+use ark_std::rand::SeedableRng;
+use proof_essentials::utils::permutation::Permutation;
+use proof_essentials::zkp::proofs::schnorr_identification;
+use proptest::prelude::*;
+use rand_chacha::ChaCha20Rng;
+use std::sync::Arc;
+
+pub type KeyOwnershipProof<C> = schnorr_identification::proof::Proof<C>;
+
+/// A player of a table drawn by [`players`], with a key ownership proof for their seat.
+#[derive(Clone)]
+pub struct RegisteredPlayer<C: ProjectiveCurve> {
+    pub pk: PublicKey<C>,
+    pub sk: PlayerSecretKey<C>,
+    /// Proof for the seat of the player and the digest of the keys of the table, given with the
+    /// seat as `u64` for public information
+    pub proof: KeyOwnershipProof<C>,
+}
+
+impl<C: ProjectiveCurve> fmt::Debug for RegisteredPlayer<C> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("RegisteredPlayer")
+            .field("pk", &Hex(&self.pk))
+            .finish_non_exhaustive()
+    }
+}
+
+/// Values derived from a seed by `sample`. Seeds for which `sample` fails are skipped.
+fn seeded<T: fmt::Debug>(
+    sample: impl Fn(&mut ChaCha20Rng) -> Option<T>,
+) -> impl Strategy<Value = T> {
+    any::<[u8; 32]>().prop_filter_map("sampling failed", move |seed| {
+        sample(&mut ChaCha20Rng::from_seed(seed))
+    })
+}
+
+/// Any element of the group as an open card.
+pub fn card<C: ProjectiveCurve>() -> impl Strategy<Value = Card<C>> {
+    seeded(|rng| Some(Card::rand(rng)))
+}
+
+/// Any pair of group elements as a masked card, which need not be masked under any known key.
+pub fn masked_card<C: ProjectiveCurve>() -> impl Strategy<Value = MaskedCard<C>> {
+    seeded(|rng| Some(MaskedCard::rand(rng)))
+}
+
+/// Any element of a field, such as a masking factor.
+pub fn scalar<F: Field>() -> impl Strategy<Value = F> {
+    seeded(|rng| Some(F::rand(rng)))
+}
+
+/// A public key generated with `player_keygen`.
+pub fn public_key<C: ProjectiveCurve>(
+    pp: Arc<Parameters<C>>,
+) -> impl Strategy<Value = PublicKey<C>> {
+    seeded(move |rng| DLCards::<C>::player_keygen(rng, &pp).ok().map(|(pk, _)| pk))
+}
+
+/// The players of a table of a size in `num_of_players`, whose proofs are given in `context`.
+/// The list of players, in order, passes `compute_aggregate_key`.
+pub fn players<C: ProjectiveCurve>(
+    pp: Arc<Parameters<C>>,
+    context: Vec<u8>,
+    num_of_players: Range<usize>,
+) -> impl Strategy<Value = Vec<RegisteredPlayer<C>>> {
+    (num_of_players, any::<[u8; 32]>()).prop_filter_map(
+        "key generation failed",
+        move |(num_of_players, seed)| {
+            let rng = &mut ChaCha20Rng::from_seed(seed);
+            let keys = (0..num_of_players)
+                .map(|_| DLCards::<C>::player_keygen(rng, &pp))
+                .collect::<Result<Vec<_>, _>>()
+                .ok()?;
+            let public_keys = keys.iter().map(|(pk, _)| *pk).collect::<Vec<_>>();
+            let key_list_digest = DLCards::<C>::key_list_digest(&public_keys).ok()?;
+
+            keys.into_iter()
+                .enumerate()
+                .map(|(seat, (pk, sk))| {
+                    let proof = DLCards::<C>::prove_key_ownership(
+                        rng,
+                        &pp,
+                        &context,
+                        &pk,
+                        &sk,
+                        &(seat as u64),
+                        seat,
+                        &key_list_digest,
+                    )
+                    .ok()?;
+
+                    Some(RegisteredPlayer { pk, sk, proof })
+                })
+                .collect()
+        },
+    )
+}
+
+/// A deck of as many random cards as `pp` has, each masked under `shared_key`, as the open cards
+/// and the masked deck.
+pub fn masked_deck<C: ProjectiveCurve>(
+    pp: Arc<Parameters<C>>,
+    shared_key: PublicKey<C>,
+) -> impl Strategy<Value = (Vec<Card<C>>, Vec<MaskedCard<C>>)> {
+    seeded(move |rng| {
+        let cards = (0..pp.num_cards())
+            .map(|_| Card::rand(rng))
+            .collect::<Vec<_>>();
+        let masked = cards
+            .iter()
+            .map(|card| card.mask(&pp.enc_parameters, &shared_key, &C::ScalarField::rand(rng)))
+            .collect::<Result<Vec<_>, _>>()
+            .ok()?;
+
+        Some((cards, masked))
+    })
+}
+
+/// A uniformly random permutation of `size` positions.
+pub fn permutation(size: usize) -> impl Strategy<Value = Permutation> {
+    seeded(move |rng| Some(Permutation::new(rng, size)))
+}
+
+#[cfg(test)]
+mod test {
+    use super::{masked_deck, permutation, players};
+    use crate::crypto_primitives::permutation::check_permutation;
+    use crate::crypto_primitives::utils::rand::test_rng;
+    use crate::discrete_log_cards;
+    use crate::BarnettSmartProtocol;
+
+    use ark_std::Zero;
+    use proptest::prelude::*;
+    use proptest::test_runner::TestRunner;
+    use std::sync::Arc;
+
+    // Choose elliptic curve setting
+    type Curve = starknet_curve::Projective;
+
+    // Instantiate concrete type for our card protocol
+    type CardProtocol<'a> = discrete_log_cards::DLCards<'a, Curve>;
+    type PublicKey = discrete_log_cards::PublicKey<Curve>;
+
+    #[test]
+    fn test_generated_tables_are_valid() {
+        let rng = &mut test_rng();
+        let m = 2;
+        let n = 2;
+
+        let parameters = Arc::new(CardProtocol::setup(rng, m, n).unwrap());
+        let table = players(parameters.clone(), b"table".to_vec(), 1..4).prop_flat_map({
+            let parameters = parameters.clone();
+            move |players| {
+                let shared_key = players
+                    .iter()
+                    .fold(PublicKey::zero(), |acc, player| acc + player.pk);
+                let deck = masked_deck(parameters.clone(), shared_key);
+
+                (Just(players), deck)
+            }
+        });
+
+        let mut runner = TestRunner::new(ProptestConfig::with_cases(8));
+        runner
+            .run(&table, |(players, (cards, deck))| {
+                let key_proof_info = players
+                    .iter()
+                    .enumerate()
+                    .map(|(seat, player)| (player.pk, player.proof.clone(), seat as u64))
+                    .collect::<Vec<_>>();
+                let shared_key =
+                    CardProtocol::compute_aggregate_key(&parameters, b"table", &key_proof_info)
+                        .unwrap();
+
+                prop_assert_eq!(deck.len(), m * n);
+                for (card, masked_card) in cards.iter().zip(&deck) {
+                    let decryption_key = players
+                        .iter()
+                        .map(|player| {
+                            let (token, proof) = CardProtocol::compute_reveal_token(
+                                &mut test_rng(),
+                                &parameters,
+                                b"table",
+                                &player.sk,
+                                &player.pk,
+                                masked_card,
+                            )
+                            .unwrap();
+                            (token, proof, player.pk)
+                        })
+                        .collect::<Vec<_>>();
+                    let opened = CardProtocol::unmask(
+                        &parameters,
+                        b"table",
+                        players.len(),
+                        &decryption_key,
+                        masked_card,
+                    )
+                    .unwrap();
+                    prop_assert_eq!(&opened, card);
+                }
+                prop_assert_eq!(
+                    shared_key,
+                    players
+                        .iter()
+                        .fold(PublicKey::zero(), |acc, player| acc + player.pk)
+                );
+
+                Ok(())
+            })
+            .unwrap();
+    }
+
+    proptest! {
+        #[test]
+        fn test_generated_permutations_are_valid(
+            (size, shuffled) in (1usize..64)
+                .prop_flat_map(|size| (Just(size), permutation(size)))
+        ) {
+            prop_assert_eq!(Ok(()), check_permutation(&shuffled, size));
+        }
+    }
+}
@@ -0,0 +1,322 @@
+//! Consecutive hands at the same table.
+//!
+//! Keys are exchanged and proven once, and the same parameters and aggregate key serve every hand
+//! after that. Each hand starts from the same deterministic starting deck, so a proof from one
+//! hand would verify in any other if they shared a context. A [`TableSession`] therefore numbers
+//! the hands and binds every proof of a hand to [`hand_context`], derived from the session
+//! identifier and the hand number: a shuffle or reveal token from hand 3 is rejected in hand 7.
+
+use crate::crypto_primitives::utils::format::{Hex, HexBytes};
+use crate::discrete_log_cards::reveal::RevealProof;
+use crate::discrete_log_cards::session::ShuffleProof;
+use crate::discrete_log_cards::{
+    starting_deck, Card, DLCards, MaskedCard, Parameters, PublicKey, RevealToken,
+};
+use crate::error::{check_dimension, CardProtocolError, Dimension, InputError};
+use crate::BarnettSmartProtocol;
+
+use ark_ec::ProjectiveCurve;
+use ark_ff::Zero;
+use ark_std::fmt;
+
+const HAND_CONTEXT_DOMAIN: &'static [u8] = b"Hand Context";
+
+/// Context the proofs of hand number `hand` of the session `session_id` are bound to.
+pub fn hand_context(session_id: &[u8], hand: u64) -> Vec<u8> {
+    [
+        HAND_CONTEXT_DOMAIN,
+        &(session_id.len() as u64).to_le_bytes()[..],
+        session_id,
+        &hand.to_le_bytes()[..],
+    ]
+    .concat()
+}
+
+pub struct TableSession<C: ProjectiveCurve> {
+    session_id: Vec<u8>,
+    keys: Vec<PublicKey<C>>,
+    aggregate_key: PublicKey<C>,
+    cards: Vec<Card<C>>,
+    /// Number of the current hand, starting at 1; 0 before the first hand
+    hand: u64,
+    context: Vec<u8>,
+    deck: Vec<MaskedCard<C>>,
+    /// Seat of the player who shuffles next, `None` once the deck of the hand is shuffled
+    next_shuffler: Option<usize>,
+}
+
+/// The open cards and the deck of the hand are left out.
+impl<C: ProjectiveCurve> fmt::Debug for TableSession<C> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("TableSession")
+            .field("session_id", &HexBytes(&self.session_id))
+            .field("keys", &self.keys.iter().map(Hex).collect::<Vec<_>>())
+            .field("aggregate_key", &Hex(&self.aggregate_key))
+            .field("hand", &self.hand)
+            .field("next_shuffler", &self.next_shuffler)
+            .finish_non_exhaustive()
+    }
+}
+
+impl<C: ProjectiveCurve> TableSession<C> {
+    /// Play hands among the players with keys `keys`, in seat order, whose ownership has already
+    /// been proven. Every hand is played with the open `cards`.
+    pub fn new(
+        pp: &Parameters<C>,
+        session_id: &[u8],
+        keys: Vec<PublicKey<C>>,
+        cards: Vec<Card<C>>,
+    ) -> Result<Self, CardProtocolError> {
+        if keys.len() < 2 {
+            return Err(InputError::InvalidPlayerCount(keys.len()).into());
+        }
+
+        check_dimension(Dimension::Deck, pp.num_cards(), cards.len())?;
+
+        let aggregate_key = keys
+            .iter()
+            .fold(PublicKey::<C>::zero(), |acc, pk| acc + *pk);
+
+        Ok(Self {
+            session_id: session_id.to_vec(),
+            keys,
+            aggregate_key,
+            cards,
+            hand: 0,
+            context: Vec::new(),
+            deck: Vec::new(),
+            next_shuffler: None,
+        })
+    }
+
+    pub fn aggregate_key(&self) -> PublicKey<C> {
+        self.aggregate_key
+    }
+
+    /// Number of the current hand, 0 before the first one.
+    pub fn hand_number(&self) -> u64 {
+        self.hand
+    }
+
+    /// Context every proof of the current hand is made and verified under.
+    pub fn context(&self) -> &[u8] {
+        &self.context
+    }
+
+    /// The deck of the current hand: the starting deck before the first shuffle, the output of
+    /// the last shuffle afterwards.
+    pub fn deck(&self) -> &[MaskedCard<C>] {
+        &self.deck
+    }
+
+    /// Seat of the player who shuffles next, `None` once the deck is shuffled.
+    pub fn next_shuffler(&self) -> Option<usize> {
+        self.next_shuffler
+    }
+
+    /// Start the next hand with a fresh starting deck, abandoning the current one, and return its
+    /// number.
+    pub fn start_hand(&mut self, pp: &Parameters<C>) -> Result<u64, CardProtocolError> {
+        self.deck = starting_deck(pp, &self.aggregate_key, &self.cards)?;
+        self.hand += 1;
+        self.context = hand_context(&self.session_id, self.hand);
+        self.next_shuffler = Some(0);
+
+        Ok(self.hand)
+    }
+
+    /// Verify and apply the shuffle of `player`, who must be next in seat order.
+    pub fn receive_shuffle(
+        &mut self,
+        pp: &Parameters<C>,
+        player: usize,
+        deck: Vec<MaskedCard<C>>,
+        proof: &ShuffleProof<C>,
+    ) -> Result<(), CardProtocolError> {
+        if player >= self.keys.len() {
+            return Err(InputError::UnknownPlayer(player).into());
+        }
+
+        match self.next_shuffler {
+            Some(next) if next == player => {}
+            Some(_) => return Err(InputError::NotYourTurn(player).into()),
+            None => return Err(InputError::UnexpectedPhase.into()),
+        }
+
+        check_dimension(Dimension::ShuffledDeck, self.deck.len(), deck.len())?;
+
+        DLCards::<C>::verify_shuffle(
+            pp,
+            &self.context,
+            &self.aggregate_key,
+            &self.deck,
+            &deck,
+            proof,
+        )
+        .map_err(|source| CardProtocolError::verification_failed(player, source))?;
+
+        self.deck = deck;
+        self.next_shuffler = Some(player + 1).filter(|&next| next < self.keys.len());
+
+        Ok(())
+    }
+
+    /// Verify a reveal token of `player` for the card at `index` of the shuffled deck of the
+    /// current hand.
+    pub fn verify_reveal(
+        &self,
+        pp: &Parameters<C>,
+        player: usize,
+        index: usize,
+        token: &RevealToken<C>,
+        proof: &RevealProof<C>,
+    ) -> Result<(), CardProtocolError> {
+        let pk = self
+            .keys
+            .get(player)
+            .ok_or(CardProtocolError::InvalidInput(InputError::UnknownPlayer(
+                player,
+            )))?;
+
+        if self.hand == 0 || self.next_shuffler.is_some() {
+            return Err(InputError::UnexpectedPhase.into());
+        }
+
+        let masked_card = self.deck.get(index).ok_or(CardProtocolError::InvalidInput(
+            InputError::UnexpectedReveal(index),
+        ))?;
+
+        DLCards::<C>::verify_reveal(pp, &self.context, pk, token, masked_card, proof)
+            .map_err(|source| CardProtocolError::verification_failed(player, source))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{hand_context, TableSession};
+    use crate::crypto_primitives::utils::rand::test_rng;
+    use crate::discrete_log_cards;
+    use crate::discrete_log_cards::encoding::encode_standard_deck;
+    use crate::discrete_log_cards::test_helpers::setup_players;
+    use crate::error::{CardProtocolError, VerificationError};
+    use crate::BarnettSmartProtocol;
+
+    // Choose elliptic curve setting
+    type Curve = starknet_curve::Projective;
+
+    // Instantiate concrete type for our card protocol
+    type CardProtocol<'a> = discrete_log_cards::DLCards<'a, Curve>;
+
+    const SESSION_ID: &[u8] = b"table session test";
+
+    #[test]
+    fn test_consecutive_hands() {
+        let rng = &mut test_rng();
+        let m = 4;
+        let n = 13;
+        let num_of_players = 3;
+
+        let parameters = CardProtocol::setup(rng, m, n).unwrap();
+        let (keys, _) = setup_players(rng, &parameters, num_of_players).unwrap();
+        let public_keys = keys.iter().map(|(pk, _, _)| *pk).collect::<Vec<_>>();
+        let mut table = TableSession::new(
+            &parameters,
+            SESSION_ID,
+            public_keys,
+            encode_standard_deck(1).unwrap(),
+        )
+        .unwrap();
+
+        let mut first_shuffle = None;
+        let mut first_reveal = None;
+        for hand in 1..=3 {
+            assert_eq!(table.start_hand(&parameters), Ok(hand));
+            assert_eq!(table.context(), &hand_context(SESSION_ID, hand)[..]);
+
+            // The starting deck is the same every hand, but a shuffle from the first hand is not
+            // accepted in a later one
+            if let Some((deck, proof)) = &first_shuffle {
+                assert!(matches!(
+                    table.receive_shuffle(&parameters, 0, deck.clone(), proof),
+                    Err(CardProtocolError::VerificationFailed(
+                        VerificationError::PlayerMessage {
+                            player: 0,
+                            source: Some(_)
+                        }
+                    ))
+                ));
+            }
+
+            for player in 0..num_of_players {
+                let (deck, proof) = CardProtocol::shuffle_and_remask_with_rng(
+                    rng,
+                    &parameters,
+                    table.context(),
+                    &table.aggregate_key(),
+                    &table.deck().to_vec(),
+                )
+                .unwrap();
+                if hand == 1 && player == 0 {
+                    first_shuffle = Some((deck.clone(), proof.clone()));
+                }
+                table
+                    .receive_shuffle(&parameters, player, deck, &proof)
+                    .unwrap();
+            }
+            assert_eq!(table.next_shuffler(), None);
+
+            let (pk, sk, _) = &keys[1];
+            let (token, proof) = CardProtocol::compute_reveal_token(
+                rng,
+                &parameters,
+                table.context(),
+                sk,
+                pk,
+                &table.deck()[0],
+            )
+            .unwrap();
+            assert_eq!(
+                Ok(()),
+                table.verify_reveal(&parameters, 1, 0, &token, &proof)
+            );
+
+            match &first_reveal {
+                None => first_reveal = Some((table.deck()[0], token, proof)),
+                Some((masked_card, token, proof)) => {
+                    // A token from the first hand fails in this hand, even for the card it was
+                    // computed for
+                    assert!(matches!(
+                        table.verify_reveal(&parameters, 1, 0, token, proof),
+                        Err(CardProtocolError::VerificationFailed(
+                            VerificationError::PlayerMessage {
+                                player: 1,
+                                source: Some(_)
+                            }
+                        ))
+                    ));
+                    assert!(CardProtocol::verify_reveal(
+                        &parameters,
+                        table.context(),
+                        pk,
+                        token,
+                        masked_card,
+                        proof
+                    )
+                    .is_err());
+                    assert_eq!(
+                        Ok(()),
+                        CardProtocol::verify_reveal(
+                            &parameters,
+                            &hand_context(SESSION_ID, 1),
+                            pk,
+                            token,
+                            masked_card,
+                            proof
+                        )
+                    );
+                }
+            }
+        }
+        assert_eq!(table.hand_number(), 3);
+    }
+}
@@ -0,0 +1,372 @@
+//! Scaffolding for tests of code built on the protocol: players with their keys, a standard deck
+//! masked under their aggregate key, the reveal tokens that open a card, and complete games to
+//! replay.
+//!
+//! Not for production. The helpers take whatever generator the test passes, and the seeded
+//! fixtures derive every key from a public seed, so anybody can recompute the secrets of a table
+//! built by [`seeded_table`].
+
+use crate::crypto_primitives::utils::format::{Compact, Hex};
+use crate::crypto_primitives::utils::rand::sample_nonzero;
+use crate::discrete_log_cards::dkg::KeyOwnershipProof;
+use crate::discrete_log_cards::encoding::{encode_standard_deck, STANDARD_DECK_SIZE};
+use crate::discrete_log_cards::reveal::RevealProof;
+use crate::discrete_log_cards::session::{GameMessage, Session, SessionPhase};
+use crate::discrete_log_cards::{
+    check_dimension, Card, DLCards, MaskedCard, Parameters, PlayerSecretKey, PublicKey, RevealToken,
+};
+use crate::error::{CardProtocolError, Dimension, InputError};
+use crate::{BarnettSmartProtocol, Mask};
+
+use ark_ec::ProjectiveCurve;
+use ark_ff::UniformRand;
+use ark_std::fmt;
+use ark_std::rand::{CryptoRng, Rng, SeedableRng};
+use ark_std::Zero;
+use rand_chacha::ChaCha20Rng;
+
+/// A player set up by [`setup_players`]: their keys and the public information they prove
+/// ownership of their key with.
+pub type Player<C> = (
+    PublicKey<C>,
+    PlayerSecretKey<C>,
+    <C as ProjectiveCurve>::ScalarField,
+);
+
+/// Key ownership proofs of a table, as `compute_aggregate_key` takes them.
+pub type KeyProofInfo<C> = Vec<(
+    PublicKey<C>,
+    KeyOwnershipProof<C>,
+    <C as ProjectiveCurve>::ScalarField,
+)>;
+
+/// Reveal tokens of every player for one card, as `unmask` takes them.
+pub type DecryptionKey<C> = Vec<(RevealToken<C>, RevealProof<C>, PublicKey<C>)>;
+
+/// Generate keys for `num_of_players` players, each with random public information, and return
+/// them with the sum of their public keys.
+pub fn setup_players<R: Rng + CryptoRng, C: ProjectiveCurve>(
+    rng: &mut R,
+    parameters: &Parameters<C>,
+    num_of_players: usize,
+) -> Result<(Vec<Player<C>>, PublicKey<C>), CardProtocolError> {
+    let mut players = Vec::with_capacity(num_of_players);
+    let mut shared_key = PublicKey::<C>::zero();
+
+    for _ in 0..num_of_players {
+        let (pk, sk) = DLCards::<C>::player_keygen(rng, parameters)?;
+        shared_key = shared_key + pk;
+        players.push((pk, sk, C::ScalarField::rand(rng)));
+    }
+
+    Ok((players, shared_key))
+}
+
+/// Prove ownership of the key of every player, in `context`, each for their seat at the table.
+pub fn prove_keys<R: Rng + CryptoRng, C: ProjectiveCurve>(
+    rng: &mut R,
+    parameters: &Parameters<C>,
+    context: &[u8],
+    players: &[Player<C>],
+) -> Result<KeyProofInfo<C>, CardProtocolError> {
+    let keys = players.iter().map(|player| player.0).collect::<Vec<_>>();
+    let key_list_digest = DLCards::<C>::key_list_digest(&keys)?;
+
+    players
+        .iter()
+        .enumerate()
+        .map(|(seat, (pk, sk, info))| {
+            let proof = DLCards::<C>::prove_key_ownership(
+                rng,
+                parameters,
+                context,
+                pk,
+                sk,
+                info,
+                seat,
+                &key_list_digest,
+            )?;
+
+            Ok((*pk, proof, *info))
+        })
+        .collect()
+}
+
+/// The standard deck, in the order of `encode_standard_deck`, with each card masked under
+/// `shared_key` with a fresh masking factor. The parameters must be for a deck of 52 cards.
+pub fn masked_standard_deck<R: Rng + CryptoRng, C: ProjectiveCurve>(
+    rng: &mut R,
+    parameters: &Parameters<C>,
+    shared_key: &PublicKey<C>,
+) -> Result<(Vec<Card<C>>, Vec<MaskedCard<C>>), CardProtocolError> {
+    check_dimension(Dimension::Deck, parameters.num_cards(), STANDARD_DECK_SIZE)?;
+
+    let cards = encode_standard_deck::<C>(1)?;
+    let deck = cards
+        .iter()
+        .map(|card| {
+            let factor = sample_nonzero::<C::ScalarField, _>(rng)?;
+            card.mask(&parameters.enc_parameters, shared_key, &factor)
+        })
+        .collect::<Result<Vec<_>, _>>()?;
+
+    Ok((cards, deck))
+}
+
+/// The reveal token of every player for `masked_card`, with its proof, ready for `unmask`.
+pub fn reveal_tokens<R: Rng + CryptoRng, C: ProjectiveCurve>(
+    rng: &mut R,
+    parameters: &Parameters<C>,
+    context: &[u8],
+    players: &[Player<C>],
+    masked_card: &MaskedCard<C>,
+) -> Result<DecryptionKey<C>, CardProtocolError> {
+    players
+        .iter()
+        .map(|(pk, sk, _)| {
+            let (token, proof) =
+                DLCards::<C>::compute_reveal_token(rng, parameters, context, sk, pk, masked_card)?;
+
+            Ok((token, proof, *pk))
+        })
+        .collect()
+}
+
+/// A table ready to play: parameters for a standard deck, the players and their aggregate key, and
+/// the standard deck masked under it.
+pub struct TestTable<C: ProjectiveCurve> {
+    pub parameters: Parameters<C>,
+    pub players: Vec<Player<C>>,
+    pub shared_key: PublicKey<C>,
+    /// Open cards, position by position of `deck`
+    pub cards: Vec<Card<C>>,
+    pub deck: Vec<MaskedCard<C>>,
+}
+
+impl<C: ProjectiveCurve> fmt::Debug for TestTable<C> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let keys = self
+            .players
+            .iter()
+            .map(|(pk, _, _)| Hex(pk))
+            .collect::<Vec<_>>();
+
+        f.debug_struct("TestTable")
+            .field("parameters", &self.parameters)
+            .field("players", &keys)
+            .field("shared_key", &Hex(&self.shared_key))
+            .field("cards", &Compact(&self.cards))
+            .field("deck", &Compact(&self.deck))
+            .finish()
+    }
+}
+
+/// Set up a [`TestTable`] of `num_of_players` players with every value derived from `seed`, so
+/// that the same seed always gives the same table.
+pub fn seeded_table<C: ProjectiveCurve>(
+    seed: u64,
+    num_of_players: usize,
+) -> Result<TestTable<C>, CardProtocolError> {
+    let rng = &mut ChaCha20Rng::seed_from_u64(seed);
+
+    let parameters = DLCards::<C>::setup(rng, 4, 13)?;
+    let (players, shared_key) = setup_players(rng, &parameters, num_of_players)?;
+    let (cards, deck) = masked_standard_deck(rng, &parameters, &shared_key)?;
+
+    Ok(TestTable {
+        parameters,
+        players,
+        shared_key,
+        cards,
+        deck,
+    })
+}
+
+/// A game played to the end by a [`Session`] at a [`seeded_table`], with the messages of every
+/// player in the order they were sent.
+pub struct SeededGame<C: ProjectiveCurve> {
+    pub table: TestTable<C>,
+    /// Deck indices dealt to each seat
+    pub hands: Vec<Vec<usize>>,
+    pub messages: Vec<(usize, GameMessage<C>)>,
+    /// Cards each player saw in their hand, as positions in the open cards of the table
+    pub opened: Vec<Vec<usize>>,
+}
+
+impl<C: ProjectiveCurve> fmt::Debug for SeededGame<C> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("SeededGame")
+            .field("table", &self.table)
+            .field("hands", &self.hands)
+            .field("messages", &self.messages)
+            .field("opened", &self.opened)
+            .finish()
+    }
+}
+
+impl<C: ProjectiveCurve> SeededGame<C> {
+    /// The public information each player proved ownership of their key with, as the sessions of
+    /// the game take it.
+    pub fn player_info(&self) -> Vec<C::ScalarField> {
+        self.table.players.iter().map(|player| player.2).collect()
+    }
+
+    /// A session in the state the game started in.
+    pub fn new_session(
+        &self,
+        context: &[u8],
+    ) -> Result<Session<C, C::ScalarField>, CardProtocolError> {
+        Session::new(
+            &self.table.parameters,
+            context,
+            self.player_info(),
+            self.table.cards.clone(),
+            self.hands.clone(),
+        )
+    }
+}
+
+/// Play a game in `context` at the [`seeded_table`] of `seed`, dealing two cards to every player
+/// one at a time around the table. Every player sends their messages in seat order, and every
+/// value is derived from `seed`.
+pub fn seeded_game<C: ProjectiveCurve>(
+    seed: u64,
+    num_of_players: usize,
+    context: &[u8],
+) -> Result<SeededGame<C>, CardProtocolError> {
+    let table = seeded_table::<C>(seed, num_of_players)?;
+
+    // The table drew from the start of the stream of the seed
+    let rng = &mut ChaCha20Rng::seed_from_u64(seed);
+    rng.set_stream(1);
+
+    let hands = (0..num_of_players)
+        .map(|player| vec![player, num_of_players + player])
+        .collect();
+    let mut game = SeededGame {
+        table,
+        hands,
+        messages: Vec::new(),
+        opened: Vec::new(),
+    };
+
+    let parameters = &game.table.parameters;
+    let players = &game.table.players;
+    let mut session = game.new_session(context)?;
+    let mut messages = Vec::new();
+    let mut send = |session: &mut Session<C, C::ScalarField>,
+                    player: usize,
+                    message: GameMessage<C>|
+     -> Result<(), CardProtocolError> {
+        session.receive(parameters, player, message.clone())?;
+        messages.push((player, message));
+        Ok(())
+    };
+
+    for (player, (pk, _, _)) in players.iter().enumerate() {
+        send(&mut session, player, GameMessage::PublicKey(*pk))?;
+    }
+
+    for (player, (_, proof, _)) in prove_keys(rng, parameters, context, players)?
+        .into_iter()
+        .enumerate()
+    {
+        send(&mut session, player, GameMessage::KeyOwnership(proof))?;
+    }
+
+    let aggregate_key = session
+        .aggregate_key()
+        .ok_or(CardProtocolError::InvalidInput(InputError::UnexpectedPhase))?;
+    for player in 0..num_of_players {
+        let (deck, proof) = DLCards::<C>::shuffle_and_remask_with_rng(
+            rng,
+            parameters,
+            context,
+            &aggregate_key,
+            &session.deck().to_vec(),
+        )?;
+        send(&mut session, player, GameMessage::Shuffle { deck, proof })?;
+    }
+
+    let mut opened = Vec::new();
+    for phase in [SessionPhase::Deal, SessionPhase::Reveal] {
+        if phase == SessionPhase::Reveal {
+            opened = players
+                .iter()
+                .enumerate()
+                .map(|(player, (_, sk, _))| session.open_own_hand(player, sk))
+                .collect::<Result<_, _>>()?;
+        }
+
+        for (player, (pk, sk, _)) in players.iter().enumerate() {
+            let tokens = session
+                .due_reveals(player)?
+                .into_iter()
+                .map(|index| {
+                    let (token, proof) = DLCards::<C>::compute_reveal_token(
+                        rng,
+                        parameters,
+                        context,
+                        sk,
+                        pk,
+                        &session.deck()[index],
+                    )?;
+                    Ok((index, token, proof))
+                })
+                .collect::<Result<_, CardProtocolError>>()?;
+            send(&mut session, player, GameMessage::RevealTokens(tokens))?;
+        }
+    }
+
+    game.messages = messages;
+    game.opened = opened;
+
+    Ok(game)
+}
+
+#[cfg(test)]
+mod test {
+    use super::{prove_keys, reveal_tokens, seeded_table};
+    use crate::crypto_primitives::utils::rand::test_rng;
+    use crate::discrete_log_cards;
+    use crate::BarnettSmartProtocol;
+
+    // Choose elliptic curve setting
+    type Curve = starknet_curve::Projective;
+
+    // Instantiate concrete type for our card protocol
+    type CardProtocol<'a> = discrete_log_cards::DLCards<'a, Curve>;
+
+    #[test]
+    fn test_seeded_table() {
+        let rng = &mut test_rng();
+        let table = seeded_table::<Curve>(7, 3).unwrap();
+
+        let key_proof_info = prove_keys(rng, &table.parameters, b"", &table.players).unwrap();
+        assert_eq!(
+            Ok(table.shared_key),
+            CardProtocol::compute_aggregate_key(&table.parameters, b"", &key_proof_info)
+        );
+
+        for (card, masked_card) in table.cards.iter().zip(&table.deck) {
+            let decryption_key =
+                reveal_tokens(rng, &table.parameters, b"", &table.players, masked_card).unwrap();
+            assert_eq!(
+                Ok(*card),
+                CardProtocol::unmask(
+                    &table.parameters,
+                    b"",
+                    table.players.len(),
+                    &decryption_key,
+                    masked_card
+                )
+            );
+        }
+
+        // The same seed gives the same table
+        let again = seeded_table::<Curve>(7, 3).unwrap();
+        assert_eq!(again.shared_key, table.shared_key);
+        assert_eq!(again.deck, table.deck);
+        assert_ne!(seeded_table::<Curve>(8, 3).unwrap().deck, table.deck);
+    }
+}
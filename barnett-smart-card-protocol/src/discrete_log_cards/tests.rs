@@ -1,15 +1,35 @@
 #[cfg(test)]
 mod test {
+    use crate::crypto_primitives::ecies;
+    use crate::crypto_primitives::utils::format::Hex;
+    use crate::crypto_primitives::utils::rand::{test_rng, ExhaustedRng, RandomnessGuard, TestRng};
+    use crate::crypto_primitives::vrf::VrfSecretKey;
     use crate::discrete_log_cards;
-    use crate::error::CardProtocolError;
+    use crate::discrete_log_cards::encoding::{
+        decode_card, encode_standard_deck, DeckSpec, STANDARD_DECK_SIZE,
+    };
+    use crate::discrete_log_cards::test_helpers::setup_players;
+    use crate::error::{
+        Bound, CardProtocolError, Dimension, InputError, InternalError, VerificationError,
+    };
     use crate::BarnettSmartProtocol;
 
+    use ark_ec::ProjectiveCurve;
     use ark_ff::UniformRand;
-    use ark_std::{rand::Rng, Zero};
+    use ark_serialize::{CanonicalDeserialize, CanonicalSerialize};
+    use ark_std::{
+        rand::{CryptoRng, Rng, SeedableRng},
+        Zero,
+    };
     use proof_essentials::error::CryptoError;
+    use proof_essentials::homomorphic_encryption::{
+        el_gamal::ElGamal, HomomorphicEncryptionScheme,
+    };
     use proof_essentials::utils::permutation::Permutation;
     use proof_essentials::utils::rand::sample_vector;
-    use rand::thread_rng;
+    use proof_essentials::vector_commitment::pedersen::PedersenCommitment;
+    use proof_essentials::vector_commitment::HomomorphicCommitmentScheme;
+    use rand_chacha::ChaCha20Rng;
     use std::iter::Iterator;
 
     // Choose elliptic curve setting
@@ -20,209 +40,916 @@ mod test {
     type CardProtocol<'a> = discrete_log_cards::DLCards<'a, Curve>;
     type CardParameters = discrete_log_cards::Parameters<Curve>;
     type PublicKey = discrete_log_cards::PublicKey<Curve>;
-    type SecretKey = discrete_log_cards::PlayerSecretKey<Curve>;
 
     type Card = discrete_log_cards::Card<Curve>;
     type MaskedCard = discrete_log_cards::MaskedCard<Curve>;
-    type RevealToken = discrete_log_cards::RevealToken<Curve>;
-
-    /// Setup `n` players. We use a Scalar to represent player public information
-    fn setup_players<R: Rng>(
-        rng: &mut R,
-        parameters: &CardParameters,
-        num_of_players: usize,
-    ) -> (Vec<(PublicKey, SecretKey, Scalar)>, PublicKey) {
-        let mut players: Vec<(PublicKey, SecretKey, Scalar)> = Vec::with_capacity(num_of_players);
-        let mut expected_shared_key = PublicKey::zero();
-
-        for i in 0..num_of_players {
-            let (pk, sk) = CardProtocol::player_keygen(rng, &parameters).unwrap();
-            let player_info = Scalar::rand(rng);
-            players.push((pk, sk, player_info));
-            expected_shared_key = expected_shared_key + players[i].0
-        }
-
-        (players, expected_shared_key)
-    }
 
     #[test]
-    fn generate_and_verify_key() {
-        let rng = &mut thread_rng();
+    fn nothing_up_my_sleeve() {
+        let rng = &mut test_rng();
         let m = 4;
         let n = 13;
 
         let parameters = CardProtocol::setup(rng, m, n).unwrap();
+        assert_eq!(Ok(true), parameters.is_nothing_up_my_sleeve());
+
+        // The key is specific to the deck dimensions
+        let other_parameters = CardProtocol::setup(rng, 13, 4).unwrap();
+        assert_eq!(Ok(true), other_parameters.is_nothing_up_my_sleeve());
+        assert_ne!(
+            parameters.commit_parameters.g[0],
+            other_parameters.commit_parameters.g[0]
+        );
 
-        let (pk, sk) = CardProtocol::player_keygen(rng, &parameters).unwrap();
-        let player_name = b"Alice";
-
-        let p1_keyproof =
-            CardProtocol::prove_key_ownership(rng, &parameters, &pk, &sk, &player_name).unwrap();
-
+        // Old-style parameters with a commitment key sampled from setup randomness
+        let legacy_parameters = CardParameters::new(
+            m,
+            n,
+            ElGamal::<Curve>::setup(rng).unwrap(),
+            PedersenCommitment::<Curve>::setup(rng, n),
+            ElGamal::<Curve>::generator(rng).unwrap(),
+        );
+        assert_eq!(Ok(false), legacy_parameters.is_nothing_up_my_sleeve());
+
+        // Every generator is checked, not only the commitment key
+        let derived = CardParameters::derive(m, n).unwrap();
+        let sampled_encryption_generator = CardParameters::new(
+            m,
+            n,
+            ElGamal::<Curve>::setup(rng).unwrap(),
+            derived.commit_parameters,
+            derived.generator,
+        );
         assert_eq!(
-            Ok(()),
-            CardProtocol::verify_key_ownership(&parameters, &pk, &player_name, &p1_keyproof)
+            Ok(false),
+            sampled_encryption_generator.is_nothing_up_my_sleeve()
+        );
+        let derived = CardParameters::derive(m, n).unwrap();
+        let sampled_shuffle_generator = CardParameters::new(
+            m,
+            n,
+            derived.enc_parameters,
+            derived.commit_parameters,
+            ElGamal::<Curve>::generator(rng).unwrap(),
         );
-
-        let other_key = Scalar::rand(rng);
-        let wrong_proof =
-            CardProtocol::prove_key_ownership(rng, &parameters, &pk, &other_key, &player_name)
-                .unwrap();
-
         assert_eq!(
-            CardProtocol::verify_key_ownership(&parameters, &pk, &player_name, &wrong_proof),
-            Err(CryptoError::ProofVerificationError(String::from(
-                "Schnorr Identification"
-            )))
-        )
+            Ok(false),
+            sampled_shuffle_generator.is_nothing_up_my_sleeve()
+        );
     }
 
     #[test]
-    fn aggregate_keys() {
-        let rng = &mut thread_rng();
+    fn setup_does_not_depend_on_randomness() {
+        let parameters = CardProtocol::setup(&mut test_rng(), 4, 13).unwrap();
+        let other_parameters =
+            CardProtocol::setup(&mut ChaCha20Rng::from_seed([7; 32]), 4, 13).unwrap();
+
+        let mut bytes = Vec::new();
+        parameters.serialize(&mut bytes).unwrap();
+        let mut other_bytes = Vec::new();
+        other_parameters.serialize(&mut other_bytes).unwrap();
+        assert_eq!(bytes, other_bytes);
+
+        // The shuffle generator is not the encryption generator
+        assert_ne!(parameters.enc_parameters.generator, parameters.generator.0);
+    }
+
+    #[test]
+    fn test_parameters_serialization() {
+        let rng = &mut test_rng();
         let m = 4;
         let n = 13;
 
-        let num_of_players = 10;
+        let parameters = CardProtocol::setup(rng, m, n).unwrap();
+        let mut bytes = Vec::new();
+        parameters.serialize(&mut bytes).unwrap();
+        assert_eq!(bytes.len(), parameters.serialized_size());
+
+        // Parameters received from somebody else decode to the same, verifiable parameters
+        let decoded = CardParameters::deserialize(&bytes[..]).unwrap();
+        assert_eq!(decoded.num_cards(), m * n);
+        assert_eq!(Ok(()), CardProtocol::verify_parameters(&decoded));
+
+        let mut encoded = Vec::new();
+        decoded.serialize(&mut encoded).unwrap();
+        assert_eq!(encoded, bytes);
+    }
+
+    #[test]
+    fn test_parameters_debug() {
+        let rng = &mut test_rng();
+        let parameters = CardProtocol::setup(rng, 4, 13).unwrap();
+
+        let expected = format!(
+            concat!(
+                "Parameters {{ m: 4, n: 13, enc_parameters: {:?}, ",
+                "commit_parameters: {:?}, generator: {:?} }}"
+            ),
+            Hex(&parameters.enc_parameters),
+            Hex(&parameters.commit_parameters),
+            Hex(&parameters.generator)
+        );
+        assert_eq!(expected, format!("{:?}", parameters));
+    }
+
+    #[test]
+    fn test_deck_shape() {
+        assert_eq!(Ok((4, 13)), discrete_log_cards::deck_shape(52));
+        assert_eq!(Ok((8, 13)), discrete_log_cards::deck_shape(104));
+        assert_eq!(Ok((13, 24)), discrete_log_cards::deck_shape(312));
+        assert_eq!(Ok((1, 53)), discrete_log_cards::deck_shape(53));
+        assert_eq!(
+            discrete_log_cards::deck_shape(0),
+            Err(CardProtocolError::InvalidInput(
+                InputError::DimensionMismatch {
+                    dimension: Dimension::Deck,
+                    expected: Bound::AtLeast(1),
+                    got: 0
+                }
+            ))
+        );
 
+        let rng = &mut test_rng();
+        let parameters = discrete_log_cards::setup_for_deck::<_, Curve>(rng, 312).unwrap();
+        assert_eq!(parameters.num_cards(), 312);
+        assert_eq!((parameters.m, parameters.n), (13, 24));
+    }
+
+    /// Run setup, masking, a shuffle and unmasking for a deck of `m` rows of `n` columns, checking
+    /// every proof along the way.
+    fn play_deck<R: Rng + CryptoRng>(rng: &mut R, m: usize, n: usize) {
         let parameters = CardProtocol::setup(rng, m, n).unwrap();
+        assert_eq!(Ok(()), CardProtocol::verify_parameters(&parameters));
+        let (players, aggregate_key) = setup_players(rng, &parameters, 2).unwrap();
 
-        let (players, expected_shared_key) = setup_players(rng, &parameters, num_of_players);
+        let cards = (0..m * n).map(|_| Card::rand(rng)).collect::<Vec<_>>();
+        let deck = cards
+            .iter()
+            .map(|card| {
+                let (masked, proof) =
+                    CardProtocol::mask_with_rng(rng, &parameters, b"", &aggregate_key, card)
+                        .unwrap();
+                assert_eq!(
+                    Ok(()),
+                    CardProtocol::verify_mask(
+                        &parameters,
+                        b"",
+                        &aggregate_key,
+                        card,
+                        &masked,
+                        &proof
+                    )
+                );
+                masked
+            })
+            .collect::<Vec<_>>();
+
+        let (shuffled_deck, shuffle_proof) =
+            CardProtocol::shuffle_and_remask_with_rng(rng, &parameters, b"", &aggregate_key, &deck)
+                .unwrap();
+        assert_eq!(
+            Ok(()),
+            CardProtocol::verify_shuffle(
+                &parameters,
+                b"",
+                &aggregate_key,
+                &deck,
+                &shuffled_deck,
+                &shuffle_proof
+            ),
+            "shuffle of a {}x{} deck",
+            m,
+            n
+        );
 
-        let proofs = players
+        let opened = shuffled_deck
             .iter()
-            .map(|player| {
-                CardProtocol::prove_key_ownership(rng, &parameters, &player.0, &player.1, &player.2)
+            .map(|masked| {
+                let decryption_key = players
+                    .iter()
+                    .map(|(pk, sk, _)| {
+                        let (token, proof) = CardProtocol::compute_reveal_token(
+                            rng,
+                            &parameters,
+                            b"",
+                            sk,
+                            pk,
+                            masked,
+                        )
+                        .unwrap();
+                        (token, proof, *pk)
+                    })
+                    .collect::<Vec<_>>();
+                CardProtocol::unmask(&parameters, b"", players.len(), &decryption_key, masked)
                     .unwrap()
             })
             .collect::<Vec<_>>();
+        assert_eq!(opened.len(), cards.len());
+        assert!(cards.iter().all(|card| opened.contains(card)));
+    }
 
-        let key_proof_info = players
-            .iter()
-            .zip(proofs.iter())
-            .map(|(player, &proof)| (player.0, proof.clone(), player.2))
-            .collect::<Vec<(PublicKey, _, _)>>();
+    #[test]
+    fn test_deck_dimensions() {
+        let rng = &mut test_rng();
 
-        let test_aggregate =
-            CardProtocol::compute_aggregate_key(&parameters, &key_proof_info).unwrap();
+        // Single rows, square decks, more rows than columns, a prime side and a large deck all
+        // work end to end
+        for (m, n) in [(1, 4), (2, 2), (4, 13), (13, 4), (7, 7), (16, 26)] {
+            play_deck(rng, m, n);
+        }
 
-        assert_eq!(test_aggregate, expected_shared_key);
+        // Shapes the shuffle argument does not support fail at setup, not inside a proof
+        let max_rows = discrete_log_cards::MAX_DECK_SIZE / 2;
+        let mismatch = |dimension, expected, got| {
+            Some(CardProtocolError::InvalidInput(
+                InputError::DimensionMismatch {
+                    dimension,
+                    expected,
+                    got,
+                },
+            ))
+        };
+        for (m, n, error) in [
+            (1, 1, mismatch(Dimension::Columns, Bound::AtLeast(2), 1)),
+            (4, 1, mismatch(Dimension::Columns, Bound::AtLeast(2), 1)),
+            (0, 4, mismatch(Dimension::Rows, Bound::AtLeast(1), 0)),
+            (4, 0, mismatch(Dimension::Columns, Bound::AtLeast(2), 0)),
+            (
+                max_rows + 1,
+                2,
+                mismatch(Dimension::Rows, Bound::AtMost(max_rows), max_rows + 1),
+            ),
+            (
+                usize::MAX,
+                2,
+                mismatch(Dimension::Rows, Bound::AtMost(max_rows), usize::MAX),
+            ),
+        ] {
+            assert_eq!(CardProtocol::setup(rng, m, n).err(), error);
+        }
+        assert_eq!(discrete_log_cards::check_shape(max_rows, 2), Ok(()));
+        assert_eq!(
+            discrete_log_cards::setup_for_deck::<_, Curve>(rng, 1).err(),
+            mismatch(Dimension::Columns, Bound::AtLeast(2), 1)
+        );
 
-        let mut bad_key_proof_pairs = key_proof_info;
-        bad_key_proof_pairs[0].0 = PublicKey::zero();
+        // Decoded parameters claiming such a shape are refused before deriving a commitment key
+        // for it
+        let parameters = CardProtocol::setup(rng, 4, 13).unwrap();
+        let mut bytes = Vec::new();
+        parameters.serialize(&mut bytes).unwrap();
+        bytes[..8].copy_from_slice(&(1u64 << 40).to_le_bytes());
+        let decoded = CardParameters::deserialize(&bytes[..]).unwrap();
+        assert_eq!(
+            CardProtocol::verify_parameters(&decoded).err(),
+            mismatch(
+                Dimension::Rows,
+                Bound::AtMost(discrete_log_cards::MAX_DECK_SIZE / 13),
+                1 << 40
+            )
+        );
+    }
 
-        let test_fail_aggregate =
-            CardProtocol::compute_aggregate_key(&parameters, &bad_key_proof_pairs);
+    #[test]
+    fn test_two_deck_shoe() {
+        let rng = &mut test_rng();
+        let num_of_players = 3;
+
+        let shoe = encode_standard_deck::<Curve>(2).unwrap();
+        let parameters = discrete_log_cards::setup_for_deck(rng, shoe.len()).unwrap();
+        let (players, shared_key) = setup_players(rng, &parameters, num_of_players).unwrap();
+
+        let mut deck = discrete_log_cards::starting_deck(&parameters, &shared_key, &shoe).unwrap();
+        for _ in 0..num_of_players {
+            let (shuffled, proof) = CardProtocol::shuffle_and_remask_with_rng(
+                rng,
+                &parameters,
+                b"",
+                &shared_key,
+                &deck,
+            )
+            .unwrap();
+            assert_eq!(
+                Ok(()),
+                CardProtocol::verify_shuffle(
+                    &parameters,
+                    b"",
+                    &shared_key,
+                    &deck,
+                    &shuffled,
+                    &proof
+                )
+            );
+            deck = shuffled;
+        }
 
-        assert_eq!(
-            test_fail_aggregate,
-            Err(CardProtocolError::ProofVerificationError(
-                CryptoError::ProofVerificationError(String::from("Schnorr Identification"))
-            ))
-        )
+        // Reveal every card of the shoe: each of the 52 cards comes out exactly twice
+        let mut counts = vec![0; STANDARD_DECK_SIZE];
+        for masked in &deck {
+            let tokens = players
+                .iter()
+                .map(|(pk, sk, _)| {
+                    let (token, proof) =
+                        CardProtocol::compute_reveal_token(rng, &parameters, b"", sk, pk, masked)
+                            .unwrap();
+                    (token, proof, *pk)
+                })
+                .collect::<Vec<_>>();
+            let card =
+                CardProtocol::unmask(&parameters, b"", players.len(), &tokens, masked).unwrap();
+
+            counts[decode_card(&card, &shoe).unwrap().unwrap()] += 1;
+        }
+        assert_eq!(counts, vec![2; STANDARD_DECK_SIZE]);
     }
 
     #[test]
-    fn test_unmask() {
-        let rng = &mut thread_rng();
-        let m = 4;
-        let n = 13;
+    fn test_deck_with_jokers() {
+        let rng = &mut test_rng();
+        let num_of_players = 3;
+
+        let spec = DeckSpec::standard52().with_jokers(2);
+        let (parameters, encoded) =
+            discrete_log_cards::setup_for_spec::<_, Curve>(rng, &spec).unwrap();
+        assert_eq!(parameters.num_cards(), 54);
+        let (players, shared_key) = setup_players(rng, &parameters, num_of_players).unwrap();
+
+        let mut deck =
+            discrete_log_cards::starting_deck(&parameters, &shared_key, encoded.cards()).unwrap();
+        for _ in 0..num_of_players {
+            let (shuffled, proof) = CardProtocol::shuffle_and_remask_with_rng(
+                rng,
+                &parameters,
+                b"",
+                &shared_key,
+                &deck,
+            )
+            .unwrap();
+            assert_eq!(
+                Ok(()),
+                CardProtocol::verify_shuffle(
+                    &parameters,
+                    b"",
+                    &shared_key,
+                    &deck,
+                    &shuffled,
+                    &proof
+                )
+            );
+            deck = shuffled;
+        }
 
-        let num_of_players = 10;
+        // Every card, jokers included, comes out exactly once
+        let mut revealed = Vec::new();
+        for masked in &deck {
+            let tokens = players
+                .iter()
+                .map(|(pk, sk, _)| {
+                    let (token, proof) =
+                        CardProtocol::compute_reveal_token(rng, &parameters, b"", sk, pk, masked)
+                            .unwrap();
+                    (token, proof, *pk)
+                })
+                .collect::<Vec<_>>();
+            let card =
+                CardProtocol::unmask(&parameters, b"", players.len(), &tokens, masked).unwrap();
+
+            revealed.push(encoded.label(&card).unwrap().unwrap().to_string());
+        }
+        revealed.sort();
 
-        let parameters = CardProtocol::setup(rng, m, n).unwrap();
+        let mut labels = spec.labels().to_vec();
+        labels.sort();
+        assert_eq!(revealed, labels);
+        assert!(revealed.contains(&"Joker2".to_string()));
+    }
 
-        let (players, expected_shared_key) = setup_players(rng, &parameters, num_of_players);
+    #[test]
+    fn test_verify_parameters() {
+        let rng = &mut test_rng();
+        let m = 4;
+        let n = 13;
 
-        let card = Card::rand(rng);
-        let alpha = Scalar::rand(rng);
-        let (masked, _) =
-            CardProtocol::mask(rng, &parameters, &expected_shared_key, &card, &alpha).unwrap();
+        let parameters = CardProtocol::setup(rng, m, n).unwrap();
+        assert_eq!(Ok(()), CardProtocol::verify_parameters(&parameters));
+
+        // Sampled generators are flagged rather than silently accepted
+        let legacy_parameters = CardParameters::new(
+            m,
+            n,
+            ElGamal::<Curve>::setup(rng).unwrap(),
+            PedersenCommitment::<Curve>::setup(rng, n),
+            ElGamal::<Curve>::generator(rng).unwrap(),
+        );
+        assert_eq!(
+            CardProtocol::verify_parameters(&legacy_parameters),
+            Err(CardProtocolError::VerificationFailed(
+                VerificationError::UnverifiableParameters
+            ))
+        );
 
-        let decryption_key = players
+        let (players, expected_shared_key) = setup_players(rng, &legacy_parameters, 3).unwrap();
+        let keys = players.iter().map(|player| player.0).collect::<Vec<_>>();
+        let key_list_digest = CardProtocol::key_list_digest(&keys).unwrap();
+        let key_proof_info = players
             .iter()
-            .map(|player| {
-                let (token, proof) = CardProtocol::compute_reveal_token(
+            .enumerate()
+            .map(|(i, player)| {
+                let proof = CardProtocol::prove_key_ownership(
                     rng,
-                    &parameters,
-                    &player.1,
+                    &legacy_parameters,
+                    b"",
                     &player.0,
-                    &masked,
+                    &player.1,
+                    &player.2,
+                    i,
+                    &key_list_digest,
                 )
                 .unwrap();
-
-                (token, proof, player.0)
+                (player.0, proof, player.2)
             })
             .collect::<Vec<_>>();
 
-        let unmasked = CardProtocol::unmask(&parameters, &decryption_key, &masked).unwrap();
-
-        assert_eq!(card, unmasked);
+        // The aggregate key only checks the parameters when asked to
+        assert_eq!(
+            Ok(expected_shared_key),
+            CardProtocol::compute_aggregate_key(&legacy_parameters, b"", &key_proof_info)
+        );
+        assert_eq!(
+            CardProtocol::compute_aggregate_key_with_verified_parameters(
+                &legacy_parameters,
+                b"",
+                &key_proof_info
+            ),
+            Err(CardProtocolError::VerificationFailed(
+                VerificationError::UnverifiableParameters
+            ))
+        );
+    }
 
-        let mut bad_decryption_key = decryption_key;
-        bad_decryption_key[0].0 = RevealToken::rand(rng);
+    #[test]
+    fn test_duplicate_keys_are_rejected() {
+        let rng = &mut test_rng();
+        let m = 4;
+        let n = 13;
 
-        let failed_decryption = CardProtocol::unmask(&parameters, &bad_decryption_key, &masked);
+        let parameters = CardProtocol::setup(rng, m, n).unwrap();
+        let (players, _) = setup_players(rng, &parameters, 2).unwrap();
+
+        let prove_all = |rng: &mut TestRng, keys: &[PublicKey], owners: &[usize]| {
+            let key_list_digest = CardProtocol::key_list_digest(keys).unwrap();
+            owners
+                .iter()
+                .enumerate()
+                .map(|(i, &owner)| {
+                    let player = &players[owner];
+                    let proof = CardProtocol::prove_key_ownership(
+                        rng,
+                        &parameters,
+                        b"",
+                        &player.0,
+                        &player.1,
+                        &player.2,
+                        i,
+                        &key_list_digest,
+                    )
+                    .unwrap();
+                    (player.0, proof, player.2)
+                })
+                .collect::<Vec<_>>()
+        };
+
+        // Player 0 registers their key a second time, with a proof valid for that position
+        let owners = [0, 1, 0];
+        let keys = owners.iter().map(|&i| players[i].0).collect::<Vec<_>>();
+        let key_proof_info = prove_all(rng, &keys, &owners);
+        assert_eq!(
+            CardProtocol::compute_aggregate_key(&parameters, b"", &key_proof_info),
+            Err(CardProtocolError::InvalidInput(
+                InputError::DuplicatePlayerKey { index: 2 }
+            ))
+        );
 
+        // An exact copy of a registration, proof included
+        let mut copied = prove_all(rng, &keys[..2], &owners[..2]);
+        copied.insert(1, copied[0].clone());
         assert_eq!(
-            failed_decryption,
-            Err(CardProtocolError::ProofVerificationError(
-                CryptoError::ProofVerificationError(String::from("Chaum-Pedersen"))
+            CardProtocol::compute_aggregate_key(&parameters, b"", &copied),
+            Err(CardProtocolError::InvalidInput(
+                InputError::DuplicatePlayerKey { index: 1 }
             ))
-        )
+        );
     }
 
     #[test]
-    fn test_shuffle() {
-        let rng = &mut thread_rng();
+    fn test_zero_masking_factor_is_rejected() {
+        let rng = &mut test_rng();
         let m = 4;
         let n = 13;
 
-        let num_of_players = 10;
-
         let parameters = CardProtocol::setup(rng, m, n).unwrap();
+        let (_, aggregate_key) = setup_players(rng, &parameters, 3).unwrap();
 
-        let (_, aggregate_key) = setup_players(rng, &parameters, num_of_players);
+        let card = Card::rand(rng);
+        assert_eq!(
+            CardProtocol::mask(
+                rng,
+                &parameters,
+                b"",
+                &aggregate_key,
+                &card,
+                &Scalar::zero()
+            )
+            .err(),
+            Some(CardProtocolError::InvalidInput(
+                InputError::ZeroMaskingFactor
+            ))
+        );
 
         let deck: Vec<MaskedCard> = sample_vector(rng, m * n);
+        assert_eq!(
+            CardProtocol::remask(
+                rng,
+                &parameters,
+                b"",
+                &aggregate_key,
+                &deck[0],
+                &Scalar::zero()
+            )
+            .err(),
+            Some(CardProtocolError::InvalidInput(
+                InputError::ZeroMaskingFactor
+            ))
+        );
 
         let permutation = Permutation::new(rng, m * n);
-        let masking_factors: Vec<Scalar> = sample_vector(rng, m * n);
+        let mut masking_factors: Vec<Scalar> = sample_vector(rng, m * n);
+        masking_factors[7] = Scalar::zero();
+        assert_eq!(
+            CardProtocol::shuffle_and_remask(
+                rng,
+                &parameters,
+                b"",
+                &aggregate_key,
+                &deck,
+                &masking_factors,
+                &permutation,
+            )
+            .err(),
+            Some(CardProtocolError::InvalidInput(
+                InputError::ZeroMaskingFactor
+            ))
+        );
+
+        // The convenience variants sample their own factors
+        let (masked_card, masking_proof) =
+            CardProtocol::mask_with_rng(rng, &parameters, b"", &aggregate_key, &card).unwrap();
+        assert_eq!(
+            Ok(()),
+            CardProtocol::verify_mask(
+                &parameters,
+                b"",
+                &aggregate_key,
+                &card,
+                &masked_card,
+                &masking_proof
+            )
+        );
+
+        let (shuffled_deck, shuffle_proof) =
+            CardProtocol::shuffle_and_remask_with_rng(rng, &parameters, b"", &aggregate_key, &deck)
+                .unwrap();
+        assert_eq!(
+            Ok(()),
+            CardProtocol::verify_shuffle(
+                &parameters,
+                b"",
+                &aggregate_key,
+                &deck,
+                &shuffled_deck,
+                &shuffle_proof
+            )
+        );
+    }
+
+    #[test]
+    fn test_proofs_are_bound_to_context() {
+        let rng = &mut test_rng();
+        let m = 4;
+        let n = 13;
+
+        let game_a = b"game A";
+        let game_b = b"game B";
 
-        let (shuffled_deck, shuffle_proof) = CardProtocol::shuffle_and_remask(
+        let parameters = CardProtocol::setup(rng, m, n).unwrap();
+        let (players, aggregate_key) = setup_players(rng, &parameters, 3).unwrap();
+
+        let keys = players.iter().map(|player| player.0).collect::<Vec<_>>();
+        let key_list_digest = CardProtocol::key_list_digest(&keys).unwrap();
+        let player = &players[0];
+
+        let key_proof = CardProtocol::prove_key_ownership(
             rng,
             &parameters,
+            game_a,
+            &player.0,
+            &player.1,
+            &player.2,
+            0,
+            &key_list_digest,
+        )
+        .unwrap();
+        assert_eq!(
+            Ok(()),
+            CardProtocol::verify_key_ownership(
+                &parameters,
+                game_a,
+                &player.0,
+                &player.2,
+                0,
+                &key_list_digest,
+                &key_proof
+            )
+        );
+        assert_eq!(
+            CardProtocol::verify_key_ownership(
+                &parameters,
+                game_b,
+                &player.0,
+                &player.2,
+                0,
+                &key_list_digest,
+                &key_proof
+            ),
+            Err(CryptoError::ProofVerificationError(String::from(
+                "Schnorr Identification"
+            )))
+        );
+
+        let card = Card::rand(rng);
+        let (masked, masking_proof) =
+            CardProtocol::mask_with_rng(rng, &parameters, game_a, &aggregate_key, &card).unwrap();
+        assert!(CardProtocol::verify_mask(
+            &parameters,
+            game_b,
+            &aggregate_key,
+            &card,
+            &masked,
+            &masking_proof
+        )
+        .is_err());
+
+        let (token, reveal_proof) = CardProtocol::compute_reveal_token(
+            rng,
+            &parameters,
+            game_a,
+            &player.1,
+            &player.0,
+            &masked,
+        )
+        .unwrap();
+        assert_eq!(
+            Ok(()),
+            CardProtocol::verify_reveal(
+                &parameters,
+                game_a,
+                &player.0,
+                &token,
+                &masked,
+                &reveal_proof
+            )
+        );
+        assert_eq!(
+            CardProtocol::verify_reveal(
+                &parameters,
+                game_b,
+                &player.0,
+                &token,
+                &masked,
+                &reveal_proof
+            ),
+            Err(CryptoError::ProofVerificationError(String::from(
+                "Chaum-Pedersen"
+            )))
+        );
+
+        let deck: Vec<MaskedCard> = sample_vector(rng, m * n);
+        let (shuffled_deck, shuffle_proof) = CardProtocol::shuffle_and_remask_with_rng(
+            rng,
+            &parameters,
+            game_a,
             &aggregate_key,
             &deck,
-            &masking_factors,
-            &permutation,
         )
         .unwrap();
+        assert!(CardProtocol::verify_shuffle(
+            &parameters,
+            game_b,
+            &aggregate_key,
+            &deck,
+            &shuffled_deck,
+            &shuffle_proof
+        )
+        .is_err());
+    }
+
+    #[test]
+    fn test_randomness_guard() {
+        let rng = &mut test_rng();
+        let m = 4;
+        let n = 13;
+
+        let parameters = CardProtocol::setup(rng, m, n).unwrap();
+        let (_, aggregate_key) = setup_players(rng, &parameters, 3).unwrap();
+
+        let mut guard = RandomnessGuard::new();
+        let alpha = Scalar::rand(rng);
 
+        // The factor is accepted once, then caught when it is cloned for a second card
+        assert!(CardProtocol::mask_guarded(
+            rng,
+            &parameters,
+            b"",
+            &mut guard,
+            &aggregate_key,
+            &Card::rand(rng),
+            &alpha
+        )
+        .is_ok());
         assert_eq!(
-            Ok(()),
-            CardProtocol::verify_shuffle(
+            CardProtocol::mask_guarded(
+                rng,
                 &parameters,
+                b"",
+                &mut guard,
+                &aggregate_key,
+                &Card::rand(rng),
+                &alpha.clone()
+            )
+            .err(),
+            Some(CardProtocolError::InvalidInput(
+                InputError::ReusedMaskingFactor
+            ))
+        );
+
+        // Shuffles share the same record
+        let deck: Vec<MaskedCard> = sample_vector(rng, m * n);
+        let permutation = Permutation::new(rng, m * n);
+        let mut masking_factors: Vec<Scalar> = sample_vector(rng, m * n);
+        masking_factors[5] = alpha;
+        assert_eq!(
+            CardProtocol::shuffle_and_remask_guarded(
+                rng,
+                &parameters,
+                b"",
+                &mut guard,
                 &aggregate_key,
                 &deck,
-                &shuffled_deck,
-                &shuffle_proof
+                &masking_factors,
+                &permutation,
             )
+            .err(),
+            Some(CardProtocolError::InvalidInput(
+                InputError::ReusedMaskingFactor
+            ))
         );
 
-        let wrong_output: Vec<MaskedCard> = sample_vector(rng, m * n);
+        // In strict mode only internally sampled factors are accepted
+        let mut strict_guard = RandomnessGuard::strict();
+        assert_eq!(
+            CardProtocol::remask_guarded(
+                rng,
+                &parameters,
+                b"",
+                &mut strict_guard,
+                &aggregate_key,
+                &deck[0],
+                &Scalar::rand(rng)
+            )
+            .err(),
+            Some(CardProtocolError::InvalidInput(
+                InputError::CallerSuppliedRandomness
+            ))
+        );
+    }
+
+    #[test]
+    fn test_exhausted_rng_does_not_panic() {
+        let rng = &mut test_rng();
+        let m = 4;
+        let n = 13;
 
+        let parameters = CardProtocol::setup(rng, m, n).unwrap();
+        let (players, aggregate_key) = setup_players(rng, &parameters, 3).unwrap();
+        let player = &players[0];
+
+        let card = Card::rand(rng);
+        let deck: Vec<MaskedCard> = sample_vector(rng, m * n);
+        let failure = Some(CardProtocolError::Internal(
+            InternalError::RandomnessFailure,
+        ));
+
+        // Everything that samples secrets reports the broken generator
+        let exhausted = &mut ExhaustedRng;
         assert_eq!(
-            CardProtocol::verify_shuffle(
+            CardProtocol::mask_with_rng(exhausted, &parameters, b"", &aggregate_key, &card).err(),
+            failure
+        );
+        assert_eq!(
+            CardProtocol::remask_with_rng(exhausted, &parameters, b"", &aggregate_key, &deck[0])
+                .err(),
+            failure
+        );
+        assert_eq!(
+            CardProtocol::shuffle_and_remask_with_rng(
+                exhausted,
                 &parameters,
+                b"",
                 &aggregate_key,
-                &deck,
-                &wrong_output,
-                &shuffle_proof
+                &deck
+            )
+            .err(),
+            failure
+        );
+        assert_eq!(
+            ecies::encrypt_to::<Curve, _>(
+                exhausted,
+                &Curve::prime_subgroup_generator().into_affine(),
+                &player.0,
+                b"hello"
+            )
+            .err(),
+            failure
+        );
+        assert_eq!(VrfSecretKey::<Curve>::new(exhausted).err(), failure);
+
+        // Proofs take their nonces from the witness as well, so they stay sound and verify
+        let (token, reveal_proof) = CardProtocol::compute_reveal_token(
+            exhausted,
+            &parameters,
+            b"",
+            &player.1,
+            &player.0,
+            &deck[0],
+        )
+        .unwrap();
+        assert_eq!(
+            CardProtocol::verify_reveal(
+                &parameters,
+                b"",
+                &player.0,
+                &token,
+                &deck[0],
+                &reveal_proof
             ),
-            Err(CryptoError::ProofVerificationError(String::from(
-                "Hadamard Product (5.1)"
-            )))
+            Ok(())
+        );
+
+        let (masked, mask_proof) = CardProtocol::mask(
+            exhausted,
+            &parameters,
+            b"",
+            &aggregate_key,
+            &card,
+            &Scalar::rand(rng),
+        )
+        .unwrap();
+        assert_eq!(
+            CardProtocol::verify_mask(
+                &parameters,
+                b"",
+                &aggregate_key,
+                &card,
+                &masked,
+                &mask_proof
+            ),
+            Ok(())
+        );
+
+        let keys = players.iter().map(|player| player.0).collect::<Vec<_>>();
+        let key_list_digest = CardProtocol::key_list_digest(&keys).unwrap();
+        let key_proof = CardProtocol::prove_key_ownership(
+            exhausted,
+            &parameters,
+            b"",
+            &player.0,
+            &player.1,
+            &player.2,
+            0,
+            &key_list_digest,
         )
+        .unwrap();
+        assert_eq!(
+            CardProtocol::verify_key_ownership(
+                &parameters,
+                b"",
+                &player.0,
+                &player.2,
+                0,
+                &key_list_digest,
+                &key_proof
+            ),
+            Ok(())
+        );
     }
 }
@@ -0,0 +1,297 @@
+//! Timeouts an arbiter can check without trusting anybody's clock.
+//!
+//! Deadlines are sequence numbers in the event log of the game rather than points in time. When a
+//! phase starts waiting for a message from a player, their deadline is the sequence number of the
+//! first message of the phase plus the grace of the [`DeadlineRules`], which the players agree on
+//! by including them in the rules digest of the session identifier. A player is late once the log
+//! reaches their deadline without a message from them.
+//!
+//! A [`TimeoutClaim`] carries the signed messages of the log up to a point where the game waited
+//! for the accused player. [`TimeoutClaim::verify`] replays them to confirm that the accused had
+//! to act, checks that they are the first entries of the event log the arbiter holds, and that no
+//! later entry of that log comes from the accused. As with `MisbehaviorReport::Timeout`, no log
+//! proves that a message was never sent: the arbiter should let the accused answer with a signed
+//! message for a later slot and append it to the log before deciding.
+
+use crate::discrete_log_cards::abort::SignedMessage;
+use crate::discrete_log_cards::event_log::EventLog;
+use crate::discrete_log_cards::identity::PublicIdentity;
+use crate::discrete_log_cards::session::Session;
+use crate::discrete_log_cards::Parameters;
+use crate::error::{CardProtocolError, VerificationError};
+
+use ark_ec::ProjectiveCurve;
+use ark_ff::ToBytes;
+use ark_serialize::{CanonicalDeserialize, CanonicalSerialize};
+
+/// Deadlines the players agree on before the game.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, CanonicalSerialize, CanonicalDeserialize)]
+pub struct DeadlineRules {
+    /// Number of messages the log may grow by after a phase starts before a player it waits for
+    /// is late. With no grace, a player is late as soon as the game waits for them.
+    pub grace: u64,
+}
+
+impl DeadlineRules {
+    /// Canonical encoding of the rules, to be included in the rules digest the session identifier
+    /// is derived from.
+    pub fn to_bytes(&self) -> Result<Vec<u8>, CardProtocolError> {
+        let mut bytes = Vec::new();
+        self.serialize(&mut bytes)?;
+
+        Ok(bytes)
+    }
+}
+
+/// Claim that the player in seat `accused` missed their deadline. `messages` are the first entries
+/// of the event log, signed for the slots `0, 1, ...`, after which the game waited for the accused.
+#[derive(Clone, Debug, CanonicalSerialize, CanonicalDeserialize)]
+pub struct TimeoutClaim<C: ProjectiveCurve> {
+    pub accused: usize,
+    pub messages: Vec<SignedMessage<C>>,
+}
+
+impl<C: ProjectiveCurve> TimeoutClaim<C> {
+    /// Claim that `accused` is late, from the messages of `log` the game has accepted so far. Every
+    /// entry of the log must be signed.
+    pub fn new(accused: usize, log: &EventLog<C>) -> Result<Self, CardProtocolError> {
+        let messages = (0..log.len())
+            .map(|index| log.signed_message(index))
+            .collect::<Option<Vec<_>>>()
+            .ok_or(CardProtocolError::VerificationFailed(
+                VerificationError::UnfoundedBlame,
+            ))?;
+
+        Ok(Self { accused, messages })
+    }
+
+    /// Check the claim against `log`, the event log of the game known to the arbiter. `session`
+    /// is the game as it was started, before any message, and `identities[i]` is the identity of
+    /// the player in seat `i`. Returns the seat of the late player, or `UnfoundedBlame` if the
+    /// claim does not show that they missed their deadline.
+    pub fn verify<B>(
+        &self,
+        pp: &Parameters<C>,
+        session: &Session<C, B>,
+        identities: &[PublicIdentity<C>],
+        rules: &DeadlineRules,
+        log: &EventLog<C>,
+    ) -> Result<usize, CardProtocolError>
+    where
+        B: ToBytes + Clone + CanonicalSerialize + CanonicalDeserialize,
+    {
+        if log.context() != session.context() {
+            return Err(VerificationError::UnfoundedBlame.into());
+        }
+        log.verify_chain()?;
+
+        // The claim must be made of the first entries of the log, each signed by its sender
+        let mut prefix = EventLog::new(log.context());
+        for message in &self.messages {
+            message.verify(log.context(), identities)?;
+            prefix.append_signed(message.clone()).map_err(|_| {
+                CardProtocolError::VerificationFailed(VerificationError::UnfoundedBlame)
+            })?;
+        }
+        let logged =
+            log.entries()
+                .get(..prefix.len())
+                .ok_or(CardProtocolError::VerificationFailed(
+                    VerificationError::UnfoundedBlame,
+                ))?;
+        if logged.last().map(|entry| entry.digest)
+            != prefix.entries().last().map(|entry| entry.digest)
+        {
+            return Err(VerificationError::UnfoundedBlame.into());
+        }
+
+        let mut session = session.clone();
+        for message in &self.messages {
+            session
+                .receive(pp, message.sender, message.message.clone())
+                .map_err(|_| {
+                    CardProtocolError::VerificationFailed(VerificationError::UnfoundedBlame)
+                })?;
+        }
+
+        let deadline =
+            session
+                .deadline(self.accused, rules)
+                .ok_or(CardProtocolError::VerificationFailed(
+                    VerificationError::UnfoundedBlame,
+                ))?;
+
+        let answered = log.entries()[prefix.len()..]
+            .iter()
+            .any(|entry| entry.sender == self.accused);
+        if answered || (log.len() as u64) < deadline {
+            return Err(VerificationError::UnfoundedBlame.into());
+        }
+
+        Ok(self.accused)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{DeadlineRules, TimeoutClaim};
+    use crate::crypto_primitives::utils::rand::{test_rng, TestRng};
+    use crate::discrete_log_cards;
+    use crate::discrete_log_cards::abort::SignedMessage;
+    use crate::discrete_log_cards::encoding::encode_standard_deck;
+    use crate::discrete_log_cards::event_log::EventLog;
+    use crate::discrete_log_cards::identity::IdentityKey;
+    use crate::discrete_log_cards::session::{GameMessage, Session, SessionPhase};
+    use crate::discrete_log_cards::test_helpers::setup_players;
+    use crate::error::{CardProtocolError, VerificationError};
+    use crate::BarnettSmartProtocol;
+
+    // Choose elliptic curve setting
+    type Curve = starknet_curve::Projective;
+
+    // Instantiate concrete type for our card protocol
+    type CardProtocol<'a> = discrete_log_cards::DLCards<'a, Curve>;
+
+    const SESSION_ID: &[u8] = b"timeout test";
+
+    #[test]
+    fn test_timeout_claim() {
+        let rng = &mut test_rng();
+        let m = 4;
+        let n = 13;
+        let num_of_players = 3;
+
+        let parameters = CardProtocol::setup(rng, m, n).unwrap();
+        let (keys, _) = setup_players(rng, &parameters, num_of_players).unwrap();
+        let identity_keys = (0..num_of_players)
+            .map(|_| IdentityKey::new(rng).unwrap())
+            .collect::<Vec<_>>();
+        let identities = identity_keys
+            .iter()
+            .map(|identity| identity.verifying_key())
+            .collect::<Vec<_>>();
+        let hands = (0..num_of_players).map(|player| vec![player]).collect();
+
+        let start = Session::new(
+            &parameters,
+            SESSION_ID,
+            (0..num_of_players as u64).collect::<Vec<_>>(),
+            encode_standard_deck(1).unwrap(),
+            hands,
+        )
+        .unwrap();
+        let mut session = start.clone();
+        let mut log = EventLog::new(SESSION_ID);
+        let send = |session: &mut Session<Curve, u64>,
+                    log: &mut EventLog<Curve>,
+                    player: usize,
+                    message: GameMessage<Curve>| {
+            session
+                .receive(&parameters, player, message.clone())
+                .unwrap();
+            let signed = SignedMessage::sign(
+                &identity_keys[player],
+                SESSION_ID,
+                player,
+                log.len() as u64,
+                message,
+            )
+            .unwrap();
+            log.append_signed(signed).unwrap();
+        };
+
+        for (player, (pk, _, _)) in keys.iter().enumerate() {
+            send(&mut session, &mut log, player, GameMessage::PublicKey(*pk));
+        }
+        let digest = session.key_list_digest().unwrap().unwrap();
+        for (player, (pk, sk, _)) in keys.iter().enumerate() {
+            let proof = CardProtocol::prove_key_ownership(
+                rng,
+                &parameters,
+                SESSION_ID,
+                pk,
+                sk,
+                &(player as u64),
+                player,
+                &digest,
+            )
+            .unwrap();
+            send(
+                &mut session,
+                &mut log,
+                player,
+                GameMessage::KeyOwnership(proof),
+            );
+        }
+
+        // Player 0 shuffles, then player 1 stalls
+        let shuffle = |rng: &mut TestRng, session: &Session<Curve, u64>| {
+            let (deck, proof) = CardProtocol::shuffle_and_remask_with_rng(
+                rng,
+                &parameters,
+                SESSION_ID,
+                &session.aggregate_key().unwrap(),
+                &session.deck().to_vec(),
+            )
+            .unwrap();
+            GameMessage::Shuffle { deck, proof }
+        };
+        let message = shuffle(rng, &session);
+        send(&mut session, &mut log, 0, message);
+        assert_eq!(session.phase(), SessionPhase::Shuffle(1));
+
+        let rules = DeadlineRules { grace: 0 };
+        assert_eq!(session.deadline(1, &rules), Some(log.len() as u64));
+        assert_eq!(session.deadline(2, &rules), None);
+
+        let claim = TimeoutClaim::new(1, &log).unwrap();
+        assert_eq!(
+            claim.verify(&parameters, &start, &identities, &rules, &log),
+            Ok(1)
+        );
+
+        // Player 2 is not the one the game waits for
+        let wrong_claim = TimeoutClaim::new(2, &log).unwrap();
+        assert_eq!(
+            wrong_claim.verify(&parameters, &start, &identities, &rules, &log),
+            Err(CardProtocolError::VerificationFailed(
+                VerificationError::UnfoundedBlame
+            ))
+        );
+
+        // The deadline has not been reached under a longer grace, as nobody else can send a
+        // message in the meantime
+        assert_eq!(
+            claim.verify(
+                &parameters,
+                &start,
+                &identities,
+                &DeadlineRules { grace: 1 },
+                &log
+            ),
+            Err(CardProtocolError::VerificationFailed(
+                VerificationError::UnfoundedBlame
+            ))
+        );
+
+        // A claim that stops before the game waited for player 1 shows nothing
+        let mut early = claim.clone();
+        early.messages.pop();
+        assert_eq!(
+            early.verify(&parameters, &start, &identities, &rules, &log),
+            Err(CardProtocolError::VerificationFailed(
+                VerificationError::UnfoundedBlame
+            ))
+        );
+
+        // Player 1 answers with their shuffle, which the arbiter appends to the log
+        let message = shuffle(rng, &session);
+        send(&mut session, &mut log, 1, message);
+        assert_eq!(
+            claim.verify(&parameters, &start, &identities, &rules, &log),
+            Err(CardProtocolError::VerificationFailed(
+                VerificationError::UnfoundedBlame
+            ))
+        );
+    }
+}
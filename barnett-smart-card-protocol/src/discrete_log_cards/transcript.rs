@@ -0,0 +1,384 @@
+//! Offline verification of a complete game.
+//!
+//! A spectator, or an arbiter settling a dispute, who receives the ordered message log of a game
+//! can check every step of it without having taken part. A [`TranscriptVerifier`] replays the log
+//! through a fresh [`Session`]: keys are aggregated again, every shuffle proof is checked against
+//! the deck it claims to shuffle, and every reveal token against the card it reveals. Replay stops
+//! at the first message the session rejects and names its position in the log.
+//!
+//! Messages do not carry the seat of their sender. [`TranscriptVerifier::replay`] attributes each
+//! message to the first seat the session is waiting on that accepts it: key ownership proofs,
+//! shuffles and reveal tokens are bound to the key of their sender, so only public keys have to be
+//! listed in seat order. An `EventLog` records the senders, and is replayed with them by
+//! [`TranscriptVerifier::replay_log`] once its hash chain is checked.
+
+use crate::crypto_primitives::utils::format::Hex;
+use crate::discrete_log_cards::event_log::EventLog;
+use crate::discrete_log_cards::session::{GameMessage, Session, SessionPhase, Transition};
+use crate::discrete_log_cards::{Card, Parameters, PublicKey};
+use crate::error::{CardProtocolError, InputError};
+
+use ark_ec::ProjectiveCurve;
+use ark_ff::ToBytes;
+use ark_serialize::{CanonicalDeserialize, CanonicalSerialize};
+use ark_std::fmt;
+
+/// Outcome of a transcript that replayed without error.
+#[derive(Clone, PartialEq, Eq)]
+pub struct GameSummary<C: ProjectiveCurve> {
+    pub aggregate_key: PublicKey<C>,
+    /// Number of shuffles whose proof verified
+    pub shuffles: usize,
+    /// Cards opened by each seat, as positions in the open cards the game was started with
+    pub opened: Vec<Vec<usize>>,
+}
+
+impl<C: ProjectiveCurve> fmt::Debug for GameSummary<C> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("GameSummary")
+            .field("aggregate_key", &Hex(&self.aggregate_key))
+            .field("shuffles", &self.shuffles)
+            .field("opened", &self.opened)
+            .finish()
+    }
+}
+
+#[derive(Debug, PartialEq)]
+pub enum ReplayError {
+    /// The message at `index` in the transcript was rejected
+    InvalidMessage {
+        index: usize,
+        error: CardProtocolError,
+    },
+    /// Every message was valid, but the game stopped in this phase
+    Incomplete(SessionPhase),
+    /// The event log is for a game with another context
+    ContextMismatch,
+    /// The hash chain of the event log does not verify
+    InvalidLog(CardProtocolError),
+}
+
+pub struct TranscriptVerifier<C: ProjectiveCurve, B: CanonicalSerialize + CanonicalDeserialize> {
+    session: Session<C, B>,
+}
+
+impl<C, B> fmt::Debug for TranscriptVerifier<C, B>
+where
+    C: ProjectiveCurve,
+    B: CanonicalSerialize + CanonicalDeserialize,
+{
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("TranscriptVerifier")
+            .field("session", &self.session)
+            .finish()
+    }
+}
+
+impl<C, B> TranscriptVerifier<C, B>
+where
+    C: ProjectiveCurve,
+    B: ToBytes + Clone + CanonicalSerialize + CanonicalDeserialize,
+{
+    /// Verifier for the game described by the same arguments as `Session::new`.
+    pub fn new(
+        pp: &Parameters<C>,
+        context: &[u8],
+        player_info: Vec<B>,
+        cards: Vec<Card<C>>,
+        hands: Vec<Vec<usize>>,
+    ) -> Result<Self, CardProtocolError> {
+        Ok(Self {
+            session: Session::new(pp, context, player_info, cards, hands)?,
+        })
+    }
+
+    /// Replay `transcript`, the messages of the game in the order they were sent, from the start
+    /// of the game to the last reveal.
+    pub fn replay(
+        &self,
+        pp: &Parameters<C>,
+        transcript: &[GameMessage<C>],
+    ) -> Result<GameSummary<C>, ReplayError> {
+        self.replay_with(transcript, |session, message| {
+            receive_from_any(session, pp, message)
+        })
+    }
+
+    /// Replay the messages of `log`, each from the seat it records, once its hash chain is
+    /// verified.
+    pub fn replay_log(
+        &self,
+        pp: &Parameters<C>,
+        log: &EventLog<C>,
+    ) -> Result<GameSummary<C>, ReplayError> {
+        if log.context() != self.session.context() {
+            return Err(ReplayError::ContextMismatch);
+        }
+
+        log.verify_chain().map_err(ReplayError::InvalidLog)?;
+
+        self.replay_with(&log.messages(), |session, (player, message)| {
+            session.receive(pp, *player, message.clone())
+        })
+    }
+
+    fn replay_with<M, F>(
+        &self,
+        transcript: &[M],
+        mut receive: F,
+    ) -> Result<GameSummary<C>, ReplayError>
+    where
+        F: FnMut(&mut Session<C, B>, &M) -> Result<Transition, CardProtocolError>,
+    {
+        let mut session = self.session.clone();
+        let mut shuffles = 0;
+
+        for (index, message) in transcript.iter().enumerate() {
+            let transition = receive(&mut session, message)
+                .map_err(|error| ReplayError::InvalidMessage { index, error })?;
+
+            if let SessionPhase::Shuffle(_) = transition.from {
+                shuffles += 1;
+            }
+        }
+
+        if session.phase() != SessionPhase::Done {
+            return Err(ReplayError::Incomplete(session.phase()));
+        }
+
+        let opened = (0..session.num_of_players())
+            .map(|player| session.opened_hand(player))
+            .collect::<Option<Vec<_>>>()
+            .ok_or(ReplayError::Incomplete(SessionPhase::Done))?;
+
+        Ok(GameSummary {
+            aggregate_key: session
+                .aggregate_key()
+                .ok_or(ReplayError::Incomplete(SessionPhase::Done))?,
+            shuffles,
+            opened,
+        })
+    }
+}
+
+/// Apply `message` as sent by the first seat the session waits on that it is valid from. A
+/// rejected message leaves the session unchanged, so the seats can be tried one after the other.
+/// Fails with the error of the first seat tried.
+fn receive_from_any<C, B>(
+    session: &mut Session<C, B>,
+    pp: &Parameters<C>,
+    message: &GameMessage<C>,
+) -> Result<Transition, CardProtocolError>
+where
+    C: ProjectiveCurve,
+    B: ToBytes + Clone + CanonicalSerialize + CanonicalDeserialize,
+{
+    let mut first_error = None;
+
+    for player in 0..session.num_of_players() {
+        if !session.awaits(player) {
+            continue;
+        }
+
+        match session.receive(pp, player, message.clone()) {
+            Ok(transition) => return Ok(transition),
+            Err(error) => {
+                first_error.get_or_insert(error);
+            }
+        }
+    }
+
+    Err(first_error.unwrap_or(CardProtocolError::InvalidInput(InputError::UnexpectedPhase)))
+}
+
+#[cfg(test)]
+mod test {
+    use super::{ReplayError, TranscriptVerifier};
+    use crate::discrete_log_cards::event_log::EventLog;
+    use crate::discrete_log_cards::session::{GameMessage, SessionPhase};
+    use crate::discrete_log_cards::test_helpers::{seeded_game, SeededGame};
+    use crate::error::{CardProtocolError, InputError, VerificationError};
+
+    use ark_serialize::{CanonicalDeserialize, CanonicalSerialize};
+
+    // Choose elliptic curve setting
+    type Curve = starknet_curve::Projective;
+    type Scalar = starknet_curve::Fr;
+
+    type Verifier = TranscriptVerifier<Curve, Scalar>;
+
+    /// Written by `examples/transcript_fixture.rs` from the game of `FIXTURE_SEED`
+    const FIXTURE: &str = concat!(
+        env!("CARGO_MANIFEST_DIR"),
+        "/tests/vectors/starknet_transcript.bin"
+    );
+
+    const SESSION_ID: &[u8] = b"transcript test";
+    const FIXTURE_SEED: u64 = 7;
+    const NUM_OF_PLAYERS: usize = 3;
+
+    fn read_fixture() -> (Vec<u8>, Vec<GameMessage<Curve>>) {
+        let bytes = std::fs::read(FIXTURE).unwrap();
+        let transcript = Vec::<GameMessage<Curve>>::deserialize(&bytes[..]).unwrap();
+
+        (bytes, transcript)
+    }
+
+    fn verifier(game: &SeededGame<Curve>) -> Verifier {
+        TranscriptVerifier::new(
+            &game.table.parameters,
+            SESSION_ID,
+            game.player_info(),
+            game.table.cards.clone(),
+            game.hands.clone(),
+        )
+        .unwrap()
+    }
+
+    #[test]
+    fn test_replay_fixture() {
+        let game = seeded_game::<Curve>(FIXTURE_SEED, NUM_OF_PLAYERS, SESSION_ID).unwrap();
+        let (bytes, transcript) = read_fixture();
+        assert_eq!(transcript.len(), 5 * NUM_OF_PLAYERS);
+
+        let verifier = verifier(&game);
+        let summary = verifier
+            .replay(&game.table.parameters, &transcript)
+            .unwrap();
+        assert_eq!(summary.aggregate_key, game.table.shared_key);
+        assert_eq!(summary.shuffles, NUM_OF_PLAYERS);
+        assert_eq!(summary.opened, game.opened);
+
+        // The checked-in transcript is the game the seed plays
+        let mut regenerated = Vec::new();
+        game.messages
+            .iter()
+            .map(|(_, message)| message.clone())
+            .collect::<Vec<_>>()
+            .serialize(&mut regenerated)
+            .unwrap();
+        assert_eq!(regenerated, bytes);
+
+        // A transcript that stops before the cards are opened is not a complete game
+        assert_eq!(
+            verifier.replay(&game.table.parameters, &transcript[..4 * NUM_OF_PLAYERS]),
+            Err(ReplayError::Incomplete(SessionPhase::Reveal))
+        );
+
+        // Key ownership proofs are bound to their seat, so they can be sent in any order
+        let mut reordered = transcript.clone();
+        reordered[NUM_OF_PLAYERS..2 * NUM_OF_PLAYERS].reverse();
+        assert_eq!(
+            verifier.replay(&game.table.parameters, &reordered),
+            Ok(summary)
+        );
+    }
+
+    #[test]
+    fn test_replay_tampered_fixture() {
+        let game = seeded_game::<Curve>(FIXTURE_SEED, NUM_OF_PLAYERS, SESSION_ID).unwrap();
+        let (_, mut transcript) = read_fixture();
+        let verifier = verifier(&game);
+
+        // Player 1 swaps two cards of the deck they shuffled, and player 2 sends a token for a
+        // card they were not dealt. Replay stops at the shuffle
+        let shuffle_index = 2 * NUM_OF_PLAYERS + 1;
+        match &mut transcript[shuffle_index] {
+            GameMessage::Shuffle { deck, .. } => deck.swap(0, 1),
+            _ => panic!("the second shuffle is sent by player 1"),
+        }
+        let reveal_index = 4 * NUM_OF_PLAYERS + 2;
+        match &mut transcript[reveal_index] {
+            GameMessage::RevealTokens(tokens) => tokens[0].0 = 0,
+            _ => panic!("the last reveal is sent by player 2"),
+        }
+
+        assert!(matches!(
+            verifier.replay(&game.table.parameters, &transcript),
+            Err(ReplayError::InvalidMessage {
+                index,
+                error: CardProtocolError::VerificationFailed(VerificationError::PlayerMessage {
+                    player: 1,
+                    source: Some(_)
+                }),
+            }) if index == shuffle_index
+        ));
+
+        // Once the shuffle is restored, the replay reaches the forged reveal
+        transcript[shuffle_index] = read_fixture().1.remove(shuffle_index);
+        assert_eq!(
+            verifier.replay(&game.table.parameters, &transcript),
+            Err(ReplayError::InvalidMessage {
+                index: reveal_index,
+                error: CardProtocolError::InvalidInput(InputError::UnexpectedReveal(0)),
+            })
+        );
+
+        // A key ownership proof that verifies for no seat names the first seat still waited on
+        let (_, mut transcript) = read_fixture();
+        transcript[NUM_OF_PLAYERS] = transcript[NUM_OF_PLAYERS + 1].clone();
+        assert!(matches!(
+            verifier.replay(&game.table.parameters, &transcript),
+            Err(ReplayError::InvalidMessage {
+                index,
+                error: CardProtocolError::VerificationFailed(VerificationError::PlayerMessage {
+                    player: 0,
+                    source: Some(_)
+                }),
+            }) if index == NUM_OF_PLAYERS + 1
+        ));
+    }
+
+    #[test]
+    fn test_replay_event_log() {
+        let game = seeded_game::<Curve>(FIXTURE_SEED, NUM_OF_PLAYERS, SESSION_ID).unwrap();
+
+        let mut log = EventLog::new(SESSION_ID);
+        for (player, message) in game.messages.iter().cloned() {
+            log.append(player, message).unwrap();
+        }
+
+        let verifier = verifier(&game);
+        let parameters = &game.table.parameters;
+        assert_eq!(
+            verifier.replay_log(parameters, &log).unwrap().opened,
+            game.opened
+        );
+
+        // The serialized log ends with the digest of its last entry
+        let mut log_bytes = Vec::new();
+        log.serialize(&mut log_bytes).unwrap();
+        *log_bytes.last_mut().unwrap() ^= 1;
+        let tampered = EventLog::<Curve>::deserialize(&log_bytes[..]).unwrap();
+        assert_eq!(
+            verifier.replay_log(parameters, &tampered),
+            Err(ReplayError::InvalidLog(
+                CardProtocolError::VerificationFailed(VerificationError::BrokenChain(
+                    log.len() - 1
+                ))
+            ))
+        );
+
+        // A consistent chain over a log with a dropped shuffle has another head, and the replay
+        // rejects the next shuffle from the seat the log records
+        let mut messages = log.messages();
+        messages.remove(2 * NUM_OF_PLAYERS);
+        let mut rebuilt = EventLog::new(SESSION_ID);
+        for (player, message) in messages {
+            rebuilt.append(player, message).unwrap();
+        }
+        assert_ne!(rebuilt.head(), log.head());
+        assert_eq!(
+            verifier.replay_log(parameters, &rebuilt),
+            Err(ReplayError::InvalidMessage {
+                index: 2 * NUM_OF_PLAYERS,
+                error: CardProtocolError::InvalidInput(InputError::NotYourTurn(1)),
+            })
+        );
+
+        assert_eq!(
+            verifier.replay_log(parameters, &EventLog::new(b"another game")),
+            Err(ReplayError::ContextMismatch)
+        );
+    }
+}
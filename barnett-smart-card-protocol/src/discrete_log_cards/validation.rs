@@ -0,0 +1,389 @@
+//! Validation of points received from other players.
+//!
+//! A point that is not on the curve, or not in its prime-order subgroup, can leak information
+//! about secrets it is combined with (invalid-curve and small-subgroup attacks). `from_bytes` runs
+//! [`Validate::validate`] after decoding, so every value read through it is safe to pass to the
+//! protocol. `from_bytes_unchecked` skips all checks and is only meant for data read back from
+//! trusted storage.
+//!
+//! A length prefix is read before the elements it announces, so a peer could claim a huge vector
+//! to make the decoder allocate before anything is checked. [`vec_from_bytes`] and
+//! [`bounded_from_bytes`] enforce [`DecodingLimits`] and fail with `MessageTooLarge` instead, and
+//! messages that contain vectors of their own read them with [`read_vec`].
+
+use crate::discrete_log_cards::{Card, MaskedCard, Parameters, PublicKey, RevealToken};
+use crate::error::{CardProtocolError, InputError};
+
+use ark_ec::{AffineCurve, ProjectiveCurve};
+use ark_ff::{FpParameters, PrimeField, Zero};
+use ark_serialize::{CanonicalDeserialize, CanonicalSerialize, Read, SerializationError};
+use ark_std::vec::Vec;
+use proof_essentials::vector_commitment::HomomorphicCommitmentScheme;
+
+/// Longest vector decoded from another player, whatever the limits.
+pub const MAX_VECTOR_LENGTH: usize = 1 << 16;
+
+/// Largest message decoded from another player, whatever the limits.
+pub const MAX_MESSAGE_BYTES: usize = 1 << 24;
+
+/// Bounds on the messages decoded from other players. Both are capped by the global maximums.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct DecodingLimits {
+    max_vector_length: usize,
+    max_message_bytes: usize,
+}
+
+impl DecodingLimits {
+    pub fn new(max_vector_length: usize, max_message_bytes: usize) -> Self {
+        Self {
+            max_vector_length: max_vector_length.min(MAX_VECTOR_LENGTH),
+            max_message_bytes: max_message_bytes.min(MAX_MESSAGE_BYTES),
+        }
+    }
+
+    /// Limits for a game played with `pp`: no vector is longer than the deck.
+    pub fn for_parameters<C, Comm>(pp: &Parameters<C, Comm>) -> Self
+    where
+        C: ProjectiveCurve,
+        Comm: HomomorphicCommitmentScheme<C::ScalarField>,
+    {
+        Self::new(pp.num_cards(), MAX_MESSAGE_BYTES)
+    }
+
+    pub fn max_vector_length(&self) -> usize {
+        self.max_vector_length
+    }
+
+    pub fn max_message_bytes(&self) -> usize {
+        self.max_message_bytes
+    }
+
+    fn check_message(&self, bytes: &[u8]) -> Result<(), CardProtocolError> {
+        if bytes.len() > self.max_message_bytes {
+            return Err(InputError::MessageTooLarge(self.max_message_bytes).into());
+        }
+
+        Ok(())
+    }
+}
+
+impl Default for DecodingLimits {
+    fn default() -> Self {
+        Self::new(MAX_VECTOR_LENGTH, MAX_MESSAGE_BYTES)
+    }
+}
+
+pub trait Validate {
+    /// Check that every point is on the curve and in the prime-order subgroup.
+    fn validate(&self) -> Result<(), CardProtocolError>;
+}
+
+/// Check that `point` is on the curve and in the prime-order subgroup. Use it to validate a
+/// `PublicKey`, which is a bare curve point.
+///
+/// Decoding the compressed encoding of a point recomputes its `y` coordinate from `x`, so the round
+/// trip gives back the same point exactly when it is on the curve. Decoding also checks subgroup
+/// membership, but on curves with a cofactor the check is made again here, as `l * point == 0` for
+/// the order `l` of the subgroup, so that no curve backend has to be trusted with it.
+pub fn validate_point<G: AffineCurve>(point: &G) -> Result<(), CardProtocolError> {
+    let mut bytes = Vec::new();
+    point.serialize(&mut bytes)?;
+
+    match G::deserialize(&bytes[..]) {
+        Ok(decoded) if decoded == *point => {}
+        _ => return Err(InputError::InvalidPoint.into()),
+    }
+
+    let order = <G::ScalarField as PrimeField>::Params::MODULUS;
+    if G::COFACTOR != [1] && !point.mul(order).is_zero() {
+        return Err(InputError::InvalidPoint.into());
+    }
+
+    Ok(())
+}
+
+/// Validates a `Card` as well as a `RevealToken`, which share the same representation.
+impl<C: ProjectiveCurve> Validate for Card<C> {
+    fn validate(&self) -> Result<(), CardProtocolError> {
+        validate_point(&self.0)
+    }
+}
+
+impl<C: ProjectiveCurve> Validate for MaskedCard<C> {
+    fn validate(&self) -> Result<(), CardProtocolError> {
+        validate_point(&self.0)?;
+        validate_point(&self.1)
+    }
+}
+
+/// Validates an entry of the reveal-token bundle passed to `unmask`. The proof's points are already
+/// checked when it is decoded.
+impl<C: ProjectiveCurve, P> Validate for (RevealToken<C>, P, PublicKey<C>) {
+    fn validate(&self) -> Result<(), CardProtocolError> {
+        self.0.validate()?;
+        validate_point(&self.2)
+    }
+}
+
+impl<T: Validate> Validate for Vec<T> {
+    fn validate(&self) -> Result<(), CardProtocolError> {
+        self.iter().try_for_each(|element| element.validate())
+    }
+}
+
+/// Decode and validate a value received from another player. Vectors are decoded with
+/// [`vec_from_bytes`], which bounds their length.
+pub fn from_bytes<T: CanonicalDeserialize + Validate>(
+    bytes: &[u8],
+) -> Result<T, CardProtocolError> {
+    let value = T::deserialize(bytes)
+        .map_err(|_| CardProtocolError::InvalidInput(InputError::InvalidPoint))?;
+    value.validate()?;
+
+    Ok(value)
+}
+
+/// Decode and validate a vector, such as a deck or a bundle of reveal tokens, received from another
+/// player. The announced length is checked against `limits` before anything is allocated.
+pub fn vec_from_bytes<T: CanonicalDeserialize + Validate>(
+    bytes: &[u8],
+    limits: &DecodingLimits,
+) -> Result<Vec<T>, CardProtocolError> {
+    limits.check_message(bytes)?;
+
+    let mut reader = bytes;
+    let length = u64::deserialize(&mut reader)?;
+    if length > limits.max_vector_length as u64 {
+        return Err(InputError::MessageTooLarge(limits.max_vector_length).into());
+    }
+
+    let values = (0..length)
+        .map(|_| T::deserialize(&mut reader))
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(|_| CardProtocolError::InvalidInput(InputError::InvalidPoint))?;
+    values.validate()?;
+
+    Ok(values)
+}
+
+/// Read a vector of at most `max_length` elements, as written by its `CanonicalSerialize`
+/// implementation. A longer length prefix is refused before any element is decoded, so that
+/// decoding implementations can bound the vectors of a message.
+pub fn read_vec<T: CanonicalDeserialize, R: Read>(
+    mut reader: R,
+    max_length: usize,
+) -> Result<Vec<T>, SerializationError> {
+    let length = u64::deserialize(&mut reader)?;
+    if length > max_length as u64 {
+        return Err(SerializationError::InvalidData);
+    }
+
+    (0..length).map(|_| T::deserialize(&mut reader)).collect()
+}
+
+/// Decode a value with vector components that cannot be inspected before decoding, such as a
+/// shuffle proof. Every element takes up space in the message, so bounding its size bounds the
+/// number of elements. Points are checked as they are decoded.
+pub fn bounded_from_bytes<T: CanonicalDeserialize>(
+    bytes: &[u8],
+    limits: &DecodingLimits,
+) -> Result<T, CardProtocolError> {
+    limits.check_message(bytes)?;
+
+    T::deserialize(bytes).map_err(|_| CardProtocolError::InvalidInput(InputError::InvalidPoint))
+}
+
+/// Decode and validate a public key received from another player.
+pub fn public_key_from_bytes<G: AffineCurve>(bytes: &[u8]) -> Result<G, CardProtocolError> {
+    let pk = G::deserialize(bytes)
+        .map_err(|_| CardProtocolError::InvalidInput(InputError::InvalidPoint))?;
+    validate_point(&pk)?;
+
+    Ok(pk)
+}
+
+/// Decode a value without any check. Only use this for data that never left trusted storage.
+pub fn from_bytes_unchecked<T: CanonicalDeserialize>(bytes: &[u8]) -> Result<T, CardProtocolError> {
+    Ok(T::deserialize_unchecked(bytes)?)
+}
+
+/// Canonical encoding of a value, as read by [`from_bytes`].
+pub fn to_bytes<T: CanonicalSerialize>(value: &T) -> Result<Vec<u8>, CardProtocolError> {
+    let mut bytes = Vec::new();
+    value.serialize(&mut bytes)?;
+
+    Ok(bytes)
+}
+
+#[cfg(test)]
+mod test {
+    use crate::crypto_primitives::utils::rand::test_rng;
+    use crate::discrete_log_cards;
+    use crate::discrete_log_cards::validation::{
+        bounded_from_bytes, from_bytes, from_bytes_unchecked, public_key_from_bytes, to_bytes,
+        validate_point, vec_from_bytes, DecodingLimits, Validate, MAX_MESSAGE_BYTES,
+        MAX_VECTOR_LENGTH,
+    };
+    use crate::error::{CardProtocolError, InputError};
+    use crate::BarnettSmartProtocol;
+
+    use ark_ec::AffineCurve;
+    use ark_ff::{UniformRand, Zero};
+    use ark_serialize::CanonicalSerialize;
+    use proof_essentials::homomorphic_encryption::el_gamal;
+    use proof_essentials::utils::rand::sample_vector;
+    use rand::Rng;
+
+    // Choose elliptic curve setting
+    type Curve = starknet_curve::Projective;
+    type Affine = starknet_curve::Affine;
+    type Base = starknet_curve::Fq;
+
+    type CardProtocol<'a> = discrete_log_cards::DLCards<'a, Curve>;
+    type Card = discrete_log_cards::Card<Curve>;
+    type MaskedCard = discrete_log_cards::MaskedCard<Curve>;
+    type PublicKey = discrete_log_cards::PublicKey<Curve>;
+
+    #[test]
+    fn test_valid_points_are_accepted() {
+        let rng = &mut test_rng();
+
+        let card = Card::rand(rng);
+        let deck: Vec<MaskedCard> = sample_vector(rng, 52);
+        let pk = PublicKey::rand(rng);
+
+        assert_eq!(Ok(card), from_bytes(&to_bytes(&card).unwrap()));
+        assert_eq!(Ok(deck.clone()), from_bytes(&to_bytes(&deck).unwrap()));
+        assert_eq!(Ok(pk), public_key_from_bytes(&to_bytes(&pk).unwrap()));
+        assert_eq!(Ok(()), validate_point(&PublicKey::zero()));
+    }
+
+    #[test]
+    fn test_off_curve_points_are_rejected() {
+        let rng = &mut test_rng();
+
+        let off_curve = loop {
+            let point = Affine::new(Base::rand(rng), Base::rand(rng), false);
+            if !point.is_on_curve() {
+                break point;
+            }
+        };
+
+        assert_eq!(
+            el_gamal::Plaintext(off_curve).validate(),
+            Err(CardProtocolError::InvalidInput(InputError::InvalidPoint))
+        );
+        assert_eq!(
+            el_gamal::Ciphertext(Affine::rand(rng), off_curve).validate(),
+            Err(CardProtocolError::InvalidInput(InputError::InvalidPoint))
+        );
+
+        // Unchecked decoding of an uncompressed encoding keeps the invalid point, checked decoding
+        // refuses it
+        let mut bytes = Vec::new();
+        off_curve.serialize_uncompressed(&mut bytes).unwrap();
+        let decoded: Affine = from_bytes_unchecked(&bytes).unwrap();
+        assert_eq!(decoded, off_curve);
+        assert_eq!(
+            validate_point(&decoded),
+            Err(CardProtocolError::InvalidInput(InputError::InvalidPoint))
+        );
+
+        // An x coordinate with no point on the curve does not decode
+        let mut bytes = to_bytes(&Card::rand(rng)).unwrap();
+        let error = loop {
+            rng.fill(&mut bytes[..8]);
+            if let Err(error) = from_bytes::<Card>(&bytes) {
+                break error;
+            }
+        };
+        assert_eq!(
+            error,
+            CardProtocolError::InvalidInput(InputError::InvalidPoint)
+        );
+    }
+
+    #[test]
+    fn test_points_outside_the_subgroup_are_rejected() {
+        type Bls12Curve = ark_bls12_377::G1Projective;
+        type Bls12Affine = ark_bls12_377::G1Affine;
+
+        let rng = &mut test_rng();
+
+        // Points decoded from random bytes are on the curve, but without clearing the cofactor they
+        // are almost never in the prime-order subgroup
+        let outside = loop {
+            let mut bytes = vec![0u8; 64];
+            rng.fill(&mut bytes[..]);
+            if let Some(point) = Bls12Affine::from_random_bytes(&bytes) {
+                if !point.is_in_correct_subgroup_assuming_on_curve() {
+                    break point;
+                }
+            }
+        };
+
+        assert_eq!(
+            el_gamal::Plaintext::<Bls12Curve>(outside).validate(),
+            Err(CardProtocolError::InvalidInput(InputError::InvalidPoint))
+        );
+        assert_eq!(validate_point(&outside.mul_by_cofactor()), Ok(()));
+    }
+
+    #[test]
+    fn test_oversized_length_prefixes_are_rejected() {
+        let rng = &mut test_rng();
+        let m = 4;
+        let n = 13;
+
+        let parameters = CardProtocol::setup(rng, m, n).unwrap();
+        let limits = DecodingLimits::for_parameters(&parameters);
+        assert_eq!(limits.max_vector_length(), 52);
+
+        let deck: Vec<MaskedCard> = sample_vector(rng, 52);
+        let bytes = to_bytes(&deck).unwrap();
+        assert_eq!(Ok(deck), vec_from_bytes(&bytes, &limits));
+
+        let longer_deck: Vec<MaskedCard> = sample_vector(rng, 53);
+        assert_eq!(
+            vec_from_bytes::<MaskedCard>(&to_bytes(&longer_deck).unwrap(), &limits),
+            Err(CardProtocolError::InvalidInput(
+                InputError::MessageTooLarge(52)
+            ))
+        );
+
+        // Length prefixes claiming huge vectors, followed by a few elements or by garbage, fail
+        // before anything is allocated
+        for _ in 0..1000 {
+            let mut forged = bytes.clone();
+            let claimed = rng.gen_range(53..=u64::MAX);
+            forged[..8].copy_from_slice(&claimed.to_le_bytes());
+            forged.truncate(rng.gen_range(8..=forged.len()));
+            assert_eq!(
+                vec_from_bytes::<MaskedCard>(&forged, &limits),
+                Err(CardProtocolError::InvalidInput(
+                    InputError::MessageTooLarge(52)
+                ))
+            );
+            assert_eq!(
+                vec_from_bytes::<MaskedCard>(&forged, &DecodingLimits::default()).err(),
+                Some(if claimed > MAX_VECTOR_LENGTH as u64 {
+                    CardProtocolError::InvalidInput(InputError::MessageTooLarge(MAX_VECTOR_LENGTH))
+                } else {
+                    CardProtocolError::InvalidInput(InputError::InvalidPoint)
+                })
+            );
+        }
+
+        // Limits cannot be raised above the global caps
+        let generous = DecodingLimits::new(usize::MAX, usize::MAX);
+        assert_eq!(generous, DecodingLimits::default());
+
+        let message = vec![0u8; MAX_MESSAGE_BYTES + 1];
+        assert_eq!(
+            bounded_from_bytes::<Vec<MaskedCard>>(&message, &generous),
+            Err(CardProtocolError::InvalidInput(
+                InputError::MessageTooLarge(MAX_MESSAGE_BYTES)
+            ))
+        );
+    }
+}
@@ -0,0 +1,573 @@
+//! An observer that checks a game as it is played.
+//!
+//! A [`Watchdog`] takes no part in the game. It is fed every signed message broadcast at the
+//! table, in order, and verifies each of them with a [`Session`] of its own, which holds the keys,
+//! the current deck and the hands of the deal plan. A copy of a message it has already accepted,
+//! as a network may deliver twice or late, is recognised by its sender and slot and dropped before
+//! anything is verified, and a message the player already sent, signed again for another slot, is
+//! dropped as well.
+//!
+//! The first invalid message stops the watchdog and raises a [`Violation`] naming the player at
+//! fault. When the protocol has a report for that kind of misbehavior, a bad key proof, shuffle
+//! or reveal token, or two different messages for the same slot, the violation comes with a
+//! `MisbehaviorReport` an arbiter can check against [`Watchdog::record`]. Messages sent out of
+//! turn or in the wrong phase are flagged without a report.
+
+use crate::discrete_log_cards::abort::{GameRecord, MisbehaviorReport, SignedMessage};
+use crate::discrete_log_cards::deal::DealPlan;
+use crate::discrete_log_cards::identity::PublicIdentity;
+use crate::discrete_log_cards::session::{GameMessage, Session, SessionPhase};
+use crate::discrete_log_cards::{Card, DLCards, Parameters};
+use crate::error::{CardProtocolError, Dimension, InputError, VerificationError};
+use crate::BarnettSmartProtocol;
+
+use ark_ec::ProjectiveCurve;
+use ark_ff::ToBytes;
+use ark_serialize::{CanonicalDeserialize, CanonicalSerialize};
+use std::collections::HashMap;
+use std::fmt;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum WatchdogStatus {
+    /// Every message so far is valid, and the game is in this phase
+    Healthy(SessionPhase),
+    /// The player in this seat sent an invalid message
+    Violation(usize),
+}
+
+/// The first invalid message seen by a [`Watchdog`].
+#[derive(Debug)]
+pub struct Violation<C: ProjectiveCurve> {
+    pub message: SignedMessage<C>,
+    /// Why the message was rejected
+    pub error: CardProtocolError,
+    /// Evidence of the misbehavior, if the protocol has a report for it
+    pub report: Option<MisbehaviorReport<C>>,
+}
+
+impl<C: ProjectiveCurve> Violation<C> {
+    /// Seat of the player at fault.
+    pub fn player(&self) -> usize {
+        self.message.sender
+    }
+}
+
+pub struct Watchdog<C: ProjectiveCurve, B: CanonicalSerialize + CanonicalDeserialize> {
+    identities: Vec<PublicIdentity<C>>,
+    session: Session<C, B>,
+    /// Accepted messages, by sender and slot
+    accepted: HashMap<(usize, u64), SignedMessage<C>>,
+    /// The last accepted shuffle, whose output the next shuffle and the reveal tokens refer to
+    last_shuffle: Option<SignedMessage<C>>,
+    violation: Option<Violation<C>>,
+}
+
+/// Accepted messages are counted rather than printed.
+impl<C, B> fmt::Debug for Watchdog<C, B>
+where
+    C: ProjectiveCurve,
+    B: CanonicalSerialize + CanonicalDeserialize,
+{
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Watchdog")
+            .field("identities", &self.identities)
+            .field("session", &self.session)
+            .field("accepted", &self.accepted.len())
+            .field("violation", &self.violation)
+            .finish_non_exhaustive()
+    }
+}
+
+impl<C, B> Watchdog<C, B>
+where
+    C: ProjectiveCurve,
+    B: ToBytes + Clone + CanonicalSerialize + CanonicalDeserialize,
+{
+    /// Watch a game dealt with `plan`, where `identities[i]` is the identity of the player in seat
+    /// `i`. The other arguments are those of `Session::new`.
+    pub fn new(
+        pp: &Parameters<C>,
+        context: &[u8],
+        identities: Vec<PublicIdentity<C>>,
+        player_info: Vec<B>,
+        cards: Vec<Card<C>>,
+        plan: &DealPlan,
+    ) -> Result<Self, CardProtocolError> {
+        let num_of_players = player_info.len();
+        if identities.len() != num_of_players || plan.num_players() != num_of_players {
+            return Err(InputError::InvalidPlayerCount(num_of_players).into());
+        }
+        plan.validate(cards.len())?;
+
+        Ok(Self {
+            identities,
+            session: Session::new(pp, context, player_info, cards, plan.hands())?,
+            accepted: HashMap::new(),
+            last_shuffle: None,
+            violation: None,
+        })
+    }
+
+    pub fn status(&self) -> WatchdogStatus {
+        match &self.violation {
+            Some(violation) => WatchdogStatus::Violation(violation.player()),
+            None => WatchdogStatus::Healthy(self.session.phase()),
+        }
+    }
+
+    pub fn violation(&self) -> Option<&Violation<C>> {
+        self.violation.as_ref()
+    }
+
+    /// Take the violation, with the error it holds, out of a watchdog that is no longer needed.
+    pub fn into_violation(self) -> Option<Violation<C>> {
+        self.violation
+    }
+
+    pub fn session(&self) -> &Session<C, B> {
+        &self.session
+    }
+
+    /// Public record of the game to check reports against, once every player has published their
+    /// key.
+    pub fn record(&self) -> Option<GameRecord<C, B>> {
+        let keys = (0..self.session.num_of_players())
+            .map(|player| self.session.public_key(player))
+            .collect::<Option<Vec<_>>>()?;
+
+        Some(GameRecord {
+            context: self.session.context().to_vec(),
+            identities: self.identities.clone(),
+            keys,
+            player_info: self.session.player_info().to_vec(),
+            cards: self.session.cards().to_vec(),
+        })
+    }
+
+    /// Check the next message broadcast at the table. Returns the violation the first time a
+    /// message is invalid, and `None` otherwise, including for every message after the first
+    /// violation. A message whose signature does not verify cannot be held against its claimed
+    /// sender: it is rejected with an error and the watchdog carries on.
+    pub fn observe(
+        &mut self,
+        pp: &Parameters<C>,
+        message: SignedMessage<C>,
+    ) -> Result<Option<&Violation<C>>, CardProtocolError> {
+        if self.violation.is_some() {
+            return Ok(None);
+        }
+
+        let slot = (message.sender, message.slot);
+        if let Some(accepted) = self.accepted.get(&slot) {
+            if message_bytes(&accepted.message)? == message_bytes(&message.message)? {
+                return Ok(None);
+            }
+        }
+
+        message.verify(self.session.context(), &self.identities)?;
+
+        if let Some(accepted) = self.accepted.get(&slot) {
+            let error =
+                CardProtocolError::InvalidInput(InputError::DuplicateMessage(message.sender));
+            let report = MisbehaviorReport::Equivocation {
+                msg_a: accepted.clone(),
+                msg_b: message.clone(),
+            };
+
+            return Ok(self.flag(message, error, Some(report)));
+        }
+
+        match self
+            .session
+            .receive(pp, message.sender, message.message.clone())
+        {
+            Ok(_) => {
+                if let GameMessage::Shuffle { .. } = message.message {
+                    self.last_shuffle = Some(message.clone());
+                }
+                self.accepted.insert(slot, message);
+
+                Ok(None)
+            }
+            Err(error) => {
+                let bytes = message_bytes(&message.message)?;
+                for accepted in self.accepted.values() {
+                    if accepted.sender == message.sender
+                        && message_bytes(&accepted.message)? == bytes
+                    {
+                        return Ok(None);
+                    }
+                }
+
+                let report = self.report(pp, &message, &error);
+                Ok(self.flag(message, error, report))
+            }
+        }
+    }
+
+    fn flag(
+        &mut self,
+        message: SignedMessage<C>,
+        error: CardProtocolError,
+        report: Option<MisbehaviorReport<C>>,
+    ) -> Option<&Violation<C>> {
+        self.violation = Some(Violation {
+            message,
+            error,
+            report,
+        });
+
+        self.violation.as_ref()
+    }
+
+    /// Report for a message the session rejected with `error`, if it shows an invalid proof.
+    fn report(
+        &self,
+        pp: &Parameters<C>,
+        message: &SignedMessage<C>,
+        error: &CardProtocolError,
+    ) -> Option<MisbehaviorReport<C>> {
+        if !matches!(
+            error,
+            CardProtocolError::VerificationFailed(VerificationError::PlayerMessage { .. })
+                | CardProtocolError::InvalidInput(InputError::DimensionMismatch {
+                    dimension: Dimension::ShuffledDeck,
+                    ..
+                })
+        ) {
+            return None;
+        }
+
+        match &message.message {
+            GameMessage::KeyOwnership(_) => Some(MisbehaviorReport::BadKeyProof {
+                proof: message.clone(),
+            }),
+            GameMessage::Shuffle { .. } => Some(MisbehaviorReport::BadShuffle {
+                previous: self.last_shuffle.clone(),
+                shuffle: message.clone(),
+            }),
+            GameMessage::RevealTokens(tokens) => {
+                // The session does not say which token failed, so look for it
+                let pk = self.session.public_key(message.sender)?;
+                let deck = self.session.deck();
+                let (index, _, _) = tokens.iter().find(|(index, token, proof)| {
+                    deck.get(*index).map_or(false, |masked_card| {
+                        DLCards::<C>::verify_reveal(
+                            pp,
+                            self.session.context(),
+                            &pk,
+                            token,
+                            masked_card,
+                            proof,
+                        )
+                        .is_err()
+                    })
+                })?;
+
+                Some(MisbehaviorReport::BadRevealToken {
+                    last_shuffle: self.last_shuffle.clone()?,
+                    tokens: message.clone(),
+                    index: *index,
+                })
+            }
+            GameMessage::PublicKey(_) => None,
+        }
+    }
+}
+
+fn message_bytes<C: ProjectiveCurve>(
+    message: &GameMessage<C>,
+) -> Result<Vec<u8>, CardProtocolError> {
+    let mut bytes = Vec::new();
+    message.serialize(&mut bytes)?;
+
+    Ok(bytes)
+}
+
+#[cfg(test)]
+mod test {
+    use super::{Watchdog, WatchdogStatus};
+    use crate::crypto_primitives::utils::rand::test_rng;
+    use crate::discrete_log_cards;
+    use crate::discrete_log_cards::abort::{MisbehaviorReport, SignedMessage};
+    use crate::discrete_log_cards::deal::DealPlan;
+    use crate::discrete_log_cards::encoding::encode_standard_deck;
+    use crate::discrete_log_cards::identity::IdentityKey;
+    use crate::discrete_log_cards::session::{GameMessage, Session, SessionPhase};
+    use crate::discrete_log_cards::test_helpers::setup_players;
+    use crate::error::{CardProtocolError, Dimension, VerificationError};
+    use crate::BarnettSmartProtocol;
+
+    // Choose elliptic curve setting
+    type Curve = starknet_curve::Projective;
+
+    // Instantiate concrete type for our card protocol
+    type CardProtocol<'a> = discrete_log_cards::DLCards<'a, Curve>;
+
+    const SESSION_ID: &[u8] = b"watchdog test";
+
+    #[test]
+    fn test_watch_clean_and_tampered_games() {
+        let rng = &mut test_rng();
+        let m = 4;
+        let n = 13;
+        let num_of_players = 3;
+
+        let parameters = CardProtocol::setup(rng, m, n).unwrap();
+        let (keys, _) = setup_players(rng, &parameters, num_of_players).unwrap();
+        let identity_keys = (0..num_of_players)
+            .map(|_| IdentityKey::new(rng).unwrap())
+            .collect::<Vec<_>>();
+        let identities = identity_keys
+            .iter()
+            .map(|identity| identity.verifying_key())
+            .collect::<Vec<_>>();
+        let plan = DealPlan::texas_holdem(num_of_players).unwrap();
+        let new_watchdog = || {
+            Watchdog::new(
+                &parameters,
+                SESSION_ID,
+                identities.clone(),
+                (0..num_of_players as u64).collect(),
+                encode_standard_deck(1).unwrap(),
+                &plan,
+            )
+            .unwrap()
+        };
+
+        // The players run the game, signing every message for the next slot
+        let mut session = Session::new(
+            &parameters,
+            SESSION_ID,
+            (0..num_of_players as u64).collect(),
+            encode_standard_deck(1).unwrap(),
+            plan.hands(),
+        )
+        .unwrap();
+        let mut stream = Vec::new();
+        let send = |session: &mut Session<Curve, u64>,
+                    stream: &mut Vec<SignedMessage<Curve>>,
+                    player: usize,
+                    message: GameMessage<Curve>| {
+            session
+                .receive(&parameters, player, message.clone())
+                .unwrap();
+            let slot = stream.len() as u64;
+            let signed =
+                SignedMessage::sign(&identity_keys[player], SESSION_ID, player, slot, message)
+                    .unwrap();
+            stream.push(signed);
+        };
+
+        for (player, (pk, _, _)) in keys.iter().enumerate() {
+            send(
+                &mut session,
+                &mut stream,
+                player,
+                GameMessage::PublicKey(*pk),
+            );
+        }
+        let digest = session.key_list_digest().unwrap().unwrap();
+        for (player, (pk, sk, _)) in keys.iter().enumerate() {
+            let proof = CardProtocol::prove_key_ownership(
+                rng,
+                &parameters,
+                SESSION_ID,
+                pk,
+                sk,
+                &(player as u64),
+                player,
+                &digest,
+            )
+            .unwrap();
+            send(
+                &mut session,
+                &mut stream,
+                player,
+                GameMessage::KeyOwnership(proof),
+            );
+        }
+        for player in 0..num_of_players {
+            let (deck, proof) = CardProtocol::shuffle_and_remask_with_rng(
+                rng,
+                &parameters,
+                SESSION_ID,
+                &session.aggregate_key().unwrap(),
+                &session.deck().to_vec(),
+            )
+            .unwrap();
+            send(
+                &mut session,
+                &mut stream,
+                player,
+                GameMessage::Shuffle { deck, proof },
+            );
+        }
+        while session.phase() != SessionPhase::Done {
+            for player in 0..num_of_players {
+                let (pk, sk, _) = &keys[player];
+                let tokens = session
+                    .due_reveals(player)
+                    .unwrap()
+                    .into_iter()
+                    .map(|index| {
+                        let (token, proof) = CardProtocol::compute_reveal_token(
+                            rng,
+                            &parameters,
+                            SESSION_ID,
+                            sk,
+                            pk,
+                            &session.deck()[index],
+                        )
+                        .unwrap();
+                        (index, token, proof)
+                    })
+                    .collect::<Vec<_>>();
+                if !tokens.is_empty() {
+                    send(
+                        &mut session,
+                        &mut stream,
+                        player,
+                        GameMessage::RevealTokens(tokens),
+                    );
+                }
+            }
+        }
+
+        // The clean game streams through, with some messages delivered twice and some again later
+        let mut watchdog = new_watchdog();
+        for (position, message) in stream.iter().enumerate() {
+            assert!(watchdog
+                .observe(&parameters, message.clone())
+                .unwrap()
+                .is_none());
+            if position % 2 == 0 {
+                assert!(watchdog
+                    .observe(&parameters, message.clone())
+                    .unwrap()
+                    .is_none());
+            }
+            if position >= 3 {
+                assert!(watchdog
+                    .observe(&parameters, stream[position - 3].clone())
+                    .unwrap()
+                    .is_none());
+            }
+        }
+        assert_eq!(
+            watchdog.status(),
+            WatchdogStatus::Healthy(SessionPhase::Done)
+        );
+
+        // Nor does a message sent again for a new slot raise an alarm
+        let resent = SignedMessage::sign(
+            &identity_keys[0],
+            SESSION_ID,
+            0,
+            stream.len() as u64,
+            stream[0].message.clone(),
+        )
+        .unwrap();
+        assert!(watchdog.observe(&parameters, resent).unwrap().is_none());
+
+        // A forged message is rejected without blaming the player it claims to come from
+        let forged = SignedMessage::sign(
+            &identity_keys[1],
+            SESSION_ID,
+            2,
+            2,
+            GameMessage::PublicKey(keys[0].0),
+        )
+        .unwrap();
+        assert_eq!(
+            watchdog.observe(&parameters, forged).err(),
+            Some(CardProtocolError::VerificationFailed(
+                VerificationError::InvalidSignature
+            ))
+        );
+        assert_eq!(
+            watchdog.status(),
+            WatchdogStatus::Healthy(SessionPhase::Done)
+        );
+
+        // Player 2 signs another key for the slot of their key
+        let other_key = SignedMessage::sign(
+            &identity_keys[2],
+            SESSION_ID,
+            2,
+            2,
+            GameMessage::PublicKey(keys[0].0),
+        )
+        .unwrap();
+        let report = watchdog
+            .observe(&parameters, other_key)
+            .unwrap()
+            .unwrap()
+            .report
+            .clone()
+            .unwrap();
+        assert_eq!(watchdog.status(), WatchdogStatus::Violation(2));
+        let record = watchdog.record().unwrap();
+        assert_eq!(report.verify(&parameters, &record), Ok(2));
+
+        // In another run, the first player to deal swaps two of their reveal tokens
+        let position = stream
+            .iter()
+            .position(|message| matches!(message.message, GameMessage::RevealTokens(_)))
+            .unwrap();
+        let original = &stream[position];
+        let tokens = match &original.message {
+            GameMessage::RevealTokens(tokens) => tokens.clone(),
+            _ => unreachable!(),
+        };
+        let mut swapped = tokens.clone();
+        swapped[0].1 = tokens[1].1;
+        swapped[0].2 = tokens[1].2.clone();
+        swapped[1].1 = tokens[0].1;
+        swapped[1].2 = tokens[0].2.clone();
+        let tampered = SignedMessage::sign(
+            &identity_keys[original.sender],
+            SESSION_ID,
+            original.sender,
+            original.slot,
+            GameMessage::RevealTokens(swapped),
+        )
+        .unwrap();
+
+        let mut watchdog = new_watchdog();
+        for message in &stream[..position] {
+            assert!(watchdog
+                .observe(&parameters, message.clone())
+                .unwrap()
+                .is_none());
+        }
+        let violation = watchdog.observe(&parameters, tampered).unwrap().unwrap();
+        assert_eq!(violation.player(), original.sender);
+        assert!(matches!(
+            violation.error,
+            CardProtocolError::VerificationFailed(VerificationError::PlayerMessage {
+                player,
+                source: Some(_)
+            }) if player == original.sender
+        ));
+        let report = violation.report.clone().unwrap();
+        assert!(matches!(
+            report,
+            MisbehaviorReport::BadRevealToken { index, .. } if index == tokens[0].0
+        ));
+        let record = watchdog.record().unwrap();
+        assert_eq!(report.verify(&parameters, &record), Ok(original.sender));
+
+        // Only the first violation is raised
+        for message in &stream[position..] {
+            assert!(watchdog
+                .observe(&parameters, message.clone())
+                .unwrap()
+                .is_none());
+        }
+        assert_eq!(
+            watchdog.status(),
+            WatchdogStatus::Violation(original.sender)
+        );
+    }
+}
@@ -1,18 +1,1059 @@
-use proof_essentials::error::CryptoError;
+use ark_serialize::SerializationError;
+use ark_std::boxed::Box;
+use ark_std::fmt;
+use ark_std::string::{String, ToString};
+use ark_std::vec::Vec;
+pub use proof_essentials::error::CryptoError;
 use thiserror::Error;
 
-/// This is an error that could occur when running a cryptographic primitive
+/// This is an error that could occur when running a cryptographic primitive.
+///
+/// Errors are sorted by where the fault lies, for callers that need to react to them rather than
+/// report them, with the detail nested in each category:
+///
+/// - [`InvalidInput`](Self::InvalidInput): the input was malformed or not allowed at this point.
+///   The caller passed values of the wrong shape, or a message broke the rules of the protocol
+///   before any proof was checked. When the input came from a peer, the peer sent something a
+///   correct implementation never sends.
+/// - [`VerificationFailed`](Self::VerificationFailed): the input was well formed, but a proof,
+///   signature, commitment or other public check on it does not hold. When the input came from a
+///   peer, the peer cheated or relayed someone who did.
+/// - [`Internal`](Self::Internal): neither the caller nor a peer is at fault. The environment
+///   failed, such as the randomness source, I/O or the transport, or an invariant of the crate
+///   broke.
+///
+/// A new error is placed in a category as it is added, and an existing error only moves to
+/// another category in a breaking release. The messages describe the error itself and leave its
+/// cause to [`source`]: a report that walks the chain, such as the one of `anyhow`, shows both
+/// without repeating either. The three categories are fixed, while new errors may be added to
+/// them in minor releases.
+///
+/// [`source`]: std::error::Error::source
 #[derive(Error, Debug, PartialEq)]
 pub enum CardProtocolError {
-    #[error("Failed to verify proof")]
+    #[error(transparent)]
+    InvalidInput(#[from] InputError),
+
+    #[error(transparent)]
+    VerificationFailed(#[from] VerificationError),
+
+    #[error(transparent)]
+    Internal(#[from] InternalError),
+}
+
+/// Input that is malformed, or not allowed at this point of the protocol: the
+/// [`InvalidInput`](CardProtocolError::InvalidInput) category.
+#[derive(Error, Debug, PartialEq)]
+#[non_exhaustive]
+pub enum InputError {
+    #[error("Failed to decode: {0}")]
+    SerializationError(String),
+
+    #[error("Derived an invalid key")]
+    KeyDerivationError,
+
+    #[error("Message not expected in the current phase")]
+    UnexpectedPhase,
+
+    #[error("Unknown player {0}")]
+    UnknownPlayer(usize),
+
+    #[error("Duplicate message from player {0}")]
+    DuplicateMessage(usize),
+
+    #[error("Not enough qualified players")]
+    NotEnoughPlayers,
+
+    #[error("Invalid threshold {0} for {1} shares")]
+    InvalidThreshold(usize, usize),
+
+    #[error("Expected at least {0} shares, got {1}")]
+    NotEnoughShares(usize, usize),
+
+    #[error("Share {0} was provided more than once")]
+    DuplicateShare(u64),
+
+    #[error("Shares belong to different keys")]
+    MixedShares,
+
+    #[error("Decoded value exceeds the maximum of {0}")]
+    ValueOutOfRange(u64),
+
+    #[error("Commitment key of length {0} cannot commit to {1} elements")]
+    CommitmentLengthError(usize, usize),
+
+    #[error("Masking factor must not be zero")]
+    ZeroMaskingFactor,
+
+    /// The size `got` of an input, or of a dimension of the deck, is not what the operation
+    /// `expected`.
+    #[error("Expected {expected} {dimension}, got {got}")]
+    DimensionMismatch {
+        dimension: Dimension,
+        expected: Bound,
+        got: usize,
+    },
+
+    #[error("Permutation does not rearrange every position exactly once")]
+    InvalidPermutation,
+
+    #[error("Player {0} registered the identity as public key")]
+    IdentityPublicKey(usize),
+
+    #[error("Point is not in the prime-order subgroup of the curve")]
+    InvalidPoint,
+
+    #[error("Encoded value is not reduced")]
+    NonCanonicalEncoding,
+
+    #[error("Expected {expected} bytes, got {length}")]
+    EncodingLengthError { expected: usize, length: usize },
+
+    #[error("Player {index} registered the public key of an earlier player")]
+    DuplicatePlayerKey { index: usize },
+
+    #[error("Masking factor was already used in this session")]
+    ReusedMaskingFactor,
+
+    #[error("Caller-supplied randomness is not accepted in strict mode")]
+    CallerSuppliedRandomness,
+
+    #[error("Message exceeds the limit of {0}")]
+    MessageTooLarge(usize),
+
+    #[error("Secret key is zero or not below the group order")]
+    InvalidSecretKey,
+
+    #[error("It is not the turn of player {0}")]
+    NotYourTurn(usize),
+
+    #[error("Card {0} is not due to be revealed")]
+    UnexpectedReveal(usize),
+
+    #[error("Cannot seat {0} players")]
+    InvalidPlayerCount(usize),
+
+    #[error("Deal does not fit a deck of {0} cards")]
+    InvalidDeal(usize),
+
+    #[error("Card {0} was burned")]
+    BurnedCard(usize),
+
+    #[error("Card {0} was discarded")]
+    DiscardedCard(usize),
+
+    #[error("Card {index} is not in the hand of player {seat}")]
+    CardNotInHand { seat: usize, index: usize },
+
+    #[error("Players {0:?} did not reveal their contribution")]
+    MissingReveals(Vec<usize>),
+
+    #[error("Expected a draw from position {expected}, got {position}")]
+    UnexpectedPosition { expected: usize, position: usize },
+
+    #[error("Sum of the opened amounts overflows")]
+    AmountOverflow,
+
+    #[error("Label {0} appears more than once in the deck")]
+    DuplicateLabel(String),
+
+    #[error("Invalid card {0}")]
+    InvalidCardLabel(String),
+
+    #[error("Card is not in the deck")]
+    UnknownCard,
+
+    #[error("Unsupported snapshot version {0}")]
+    UnsupportedSnapshotVersion(u32),
+
+    #[error("Snapshot does not describe a consistent game")]
+    InvalidSnapshot,
+
+    #[error("Card {0} cannot become less visible")]
+    IllegalTransition(usize),
+}
+
+/// Input that is well formed but fails a proof or another public check: the
+/// [`VerificationFailed`](CardProtocolError::VerificationFailed) category.
+#[derive(Error, Debug, PartialEq)]
+#[non_exhaustive]
+pub enum VerificationError {
+    /// A proof of the underlying proof system does not verify. The error of the proof system is
+    /// the source of this one.
+    #[error("Proof verification failed")]
     ProofVerificationError(#[from] CryptoError),
 
-    #[error("IoError: {0}")]
+    /// A shuffle proof does not verify. The part of the argument that failed is the source.
+    #[error("Shuffle proof verification failed")]
+    ShuffleVerificationFailed(#[source] ShuffleVerificationFailure),
+
+    /// A message from `player` failed verification. `source` is the error of the proof or check
+    /// that failed, when it reports one.
+    #[error("Message from player {player} failed verification")]
+    PlayerMessage {
+        player: usize,
+        #[source]
+        source: Option<Box<CardProtocolError>>,
+    },
+
+    /// The proof at `index` of a list of key registrations does not verify. `public_key` is the
+    /// canonical encoding of the key it was given for, and `source` the error of the proof system.
+    #[error("Key ownership proof {index} does not verify")]
+    InvalidKeyOwnershipProof {
+        index: usize,
+        public_key: Vec<u8>,
+        source: OperationError,
+    },
+
+    /// The token at `index` of a decryption key does not verify. `public_key` is the canonical
+    /// encoding of the key of the player who issued it, and `source` the error of the proof system.
+    #[error("Reveal token {index} does not verify")]
+    InvalidRevealToken {
+        index: usize,
+        public_key: Vec<u8>,
+        source: OperationError,
+    },
+
+    #[error("Share {0} is corrupted")]
+    CorruptedShare(u64),
+
+    #[error("Failed to decrypt")]
+    DecryptionError,
+
+    #[error("Invalid signature")]
+    InvalidSignature,
+
+    #[error("Certificate expired at {0}")]
+    CertificateExpired(u64),
+
+    #[error("Certificate was issued for another session")]
+    CertificateSessionMismatch,
+
+    #[error("Parameters were not derived deterministically and cannot be verified")]
+    UnverifiableParameters,
+
+    #[error("Evidence does not show misbehavior")]
+    UnfoundedBlame,
+
+    #[error("Opening does not match the commitment")]
+    InvalidOpening,
+
+    #[error("Opened amounts do not add up to the pot")]
+    PotMismatch,
+
+    #[error("Log entry {0} does not extend the chain before it")]
+    BrokenChain(usize),
+
+    #[error("Conformance case {case:?} does not check out at {field}")]
+    ConformanceMismatch { case: String, field: String },
+}
+
+/// A failure of the environment or of the crate itself: the
+/// [`Internal`](CardProtocolError::Internal) category.
+#[derive(Error, Debug, PartialEq)]
+#[non_exhaustive]
+pub enum InternalError {
+    #[error("I/O error: {0}")]
     IoError(String),
+
+    /// Encoding a value failed, such as when the buffer written to has no room left
+    #[error("Failed to encode: {0}")]
+    SerializationError(String),
+
+    #[error("Failed to hash to a curve point")]
+    HashToCurveError,
+
+    #[error("Failed to encrypt")]
+    EncryptionError,
+
+    #[error("Randomness source produced degenerate output")]
+    RandomnessFailure,
+
+    #[error("Transport failed: {0}")]
+    TransportError(String),
+}
+
+/// The part of a shuffle argument that does not hold, with the section of the Bayer-Groth
+/// argument it is specified in.
+#[derive(Error, Clone, Copy, Debug, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum ShuffleVerificationFailure {
+    /// The Hadamard product argument for the committed permutation
+    #[error("Hadamard Product (5.1)")]
+    HadamardProduct,
+
+    /// The zero argument the Hadamard product argument reduces to
+    #[error("Zero Argument (5.2)")]
+    ZeroArgument,
+
+    /// The single value product argument for the product of the committed values
+    #[error("Single Value Product (5.3)")]
+    SingleValueProduct,
+
+    /// The multi-exponentiation argument for the remasked ciphertexts
+    #[error("Multi-Exponentiation (4)")]
+    MultiExponentiation,
+
+    /// The ciphertexts of the decks are not consistent with the commitments of the argument
+    #[error("Ciphertext Consistency")]
+    CiphertextConsistency,
+
+    /// A deck does not have as many cards as the parameters, so no proof can hold for it
+    #[error("Expected {expected} {dimension}, got {got}")]
+    DeckSize {
+        dimension: Dimension,
+        expected: usize,
+        got: usize,
+    },
+
+    /// The transcript of the argument could not be derived, or the proof system reported a
+    /// failure of a part this crate does not recognise
+    #[error("Transcript")]
+    Transcript,
+}
+
+/// The operation of the protocol in which a proof failed to verify.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum Operation {
+    /// Checking the key ownership proofs of the players before aggregating their keys
+    DuringKeyAggregation,
+    /// Checking the reveal tokens of a card before opening it. The position of the card in the
+    /// deck is known to callers that open a card of a deck, see [`CardProtocolError::at_card`].
+    DuringUnmask { card_index: Option<usize> },
+    /// Checking the argument that a deck is a shuffle of another
+    DuringShuffleVerify,
+}
+
+impl fmt::Display for Operation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::DuringKeyAggregation => f.write_str("During key aggregation"),
+            Self::DuringUnmask {
+                card_index: Some(card_index),
+            } => write!(f, "During unmask of card {}", card_index),
+            Self::DuringUnmask { card_index: None } => f.write_str("During unmask"),
+            Self::DuringShuffleVerify => f.write_str("During shuffle verification"),
+        }
+    }
+}
+
+/// An error of the proof system, with the operation it happened in. The error of the proof system
+/// is kept as it is, as the source of this one, so that a report of the error shows both.
+#[derive(Error, Debug, PartialEq)]
+#[error("{operation}")]
+pub struct OperationError {
+    pub operation: Operation,
+    pub source: CryptoError,
+}
+
+impl OperationError {
+    pub fn new(operation: Operation, source: CryptoError) -> Self {
+        Self { operation, source }
+    }
+}
+
+impl From<CryptoError> for ShuffleVerificationFailure {
+    /// Each argument of the proof system reports its failure under a fixed name, which identifies
+    /// the part that failed. A name the proof system does not give any of them, or an error that
+    /// is not a failed verification, is a failure of the transcript.
+    fn from(error: CryptoError) -> Self {
+        match &error {
+            CryptoError::ProofVerificationError(name) => match name.as_str() {
+                "Hadamard Product (5.1)" => Self::HadamardProduct,
+                "Zero Argument (5.2)" => Self::ZeroArgument,
+                "Single Value Product Argument (5.3)" => Self::SingleValueProduct,
+                "Multi-exponentiation" => Self::MultiExponentiation,
+                "Shuffle" => Self::CiphertextConsistency,
+                _ => Self::Transcript,
+            },
+            _ => Self::Transcript,
+        }
+    }
+}
+
+impl CardProtocolError {
+    /// A message from `player` failed verification because of `source`.
+    pub fn verification_failed(player: usize, source: impl Into<CardProtocolError>) -> Self {
+        Self::VerificationFailed(VerificationError::PlayerMessage {
+            player,
+            source: Some(Box::new(source.into())),
+        })
+    }
+
+    /// The operation in which a proof failed, for the errors of proofs that do not verify.
+    pub fn operation(&self) -> Option<Operation> {
+        match self {
+            Self::VerificationFailed(
+                VerificationError::InvalidKeyOwnershipProof { source, .. }
+                | VerificationError::InvalidRevealToken { source, .. },
+            ) => Some(source.operation),
+            Self::VerificationFailed(VerificationError::ShuffleVerificationFailed(_)) => {
+                Some(Operation::DuringShuffleVerify)
+            }
+            Self::VerificationFailed(VerificationError::PlayerMessage {
+                source: Some(source),
+                ..
+            }) => source.operation(),
+            _ => None,
+        }
+    }
+
+    /// Record that the error happened while opening the card at `card_index` of a deck. Errors
+    /// of other operations are returned unchanged.
+    pub fn at_card(mut self, card_index: usize) -> Self {
+        if let Self::VerificationFailed(VerificationError::InvalidRevealToken { source, .. }) =
+            &mut self
+        {
+            if let Operation::DuringUnmask { card_index: index } = &mut source.operation {
+                *index = Some(card_index);
+            }
+        }
+
+        self
+    }
+}
+
+/// Stable number of every error of [`CardProtocolError`], for bindings and services that report
+/// errors to other languages. The numbers are part of the API: a new variant gets the next unused
+/// number, and the number of an existing variant never changes or gets reused, even if the
+/// variant is removed.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+#[non_exhaustive]
+#[repr(u32)]
+pub enum ErrorCode {
+    ProofVerificationError = 1,
+    IoError = 2,
+    SerializationError = 3,
+    HashToCurveError = 4,
+    KeyDerivationError = 5,
+    UnexpectedPhase = 6,
+    UnknownPlayer = 7,
+    DuplicateMessage = 8,
+    NotEnoughPlayers = 9,
+    InvalidThreshold = 10,
+    NotEnoughShares = 11,
+    CorruptedShare = 12,
+    DuplicateShare = 13,
+    MixedShares = 14,
+    ValueOutOfRange = 15,
+    CommitmentLengthError = 16,
+    // 17 was InvalidShape, now a DimensionMismatch
+    ZeroMaskingFactor = 18,
+    EncryptionError = 19,
+    DecryptionError = 20,
+    // 21 was LengthMismatch, now a DimensionMismatch
+    DimensionMismatch = 22,
+    InvalidPermutation = 23,
+    IdentityPublicKey = 24,
+    InvalidPoint = 25,
+    NonCanonicalEncoding = 26,
+    EncodingLengthError = 27,
+    RandomnessFailure = 28,
+    DuplicatePlayerKey = 29,
+    ReusedMaskingFactor = 30,
+    CallerSuppliedRandomness = 31,
+    InvalidSignature = 32,
+    CertificateExpired = 33,
+    CertificateSessionMismatch = 34,
+    UnverifiableParameters = 35,
+    MessageTooLarge = 36,
+    InvalidSecretKey = 37,
+    NotYourTurn = 38,
+    UnexpectedReveal = 39,
+    InvalidPlayerCount = 40,
+    // 41 was DeckSizeMismatch, now a DimensionMismatch
+    VerificationFailed = 42,
+    InvalidKeyOwnershipProof = 43,
+    InvalidRevealToken = 44,
+    InvalidDeal = 45,
+    BurnedCard = 46,
+    DiscardedCard = 47,
+    CardNotInHand = 48,
+    UnfoundedBlame = 49,
+    MissingReveals = 50,
+    UnexpectedPosition = 51,
+    InvalidOpening = 52,
+    PotMismatch = 53,
+    AmountOverflow = 54,
+    DuplicateLabel = 55,
+    InvalidCardLabel = 56,
+    UnknownCard = 57,
+    BrokenChain = 58,
+    UnsupportedSnapshotVersion = 59,
+    InvalidSnapshot = 60,
+    IllegalTransition = 61,
+    TransportError = 62,
+    ConformanceMismatch = 63,
+    ShuffleVerificationFailed = 64,
+    // 65 was UnsupportedShape, now a DimensionMismatch
+}
+
+impl ErrorCode {
+    /// The number of the code.
+    pub fn value(self) -> u32 {
+        self as u32
+    }
+
+    /// The name of the code, which is the name of the error variant it stands for.
+    pub fn name(self) -> &'static str {
+        match self {
+            Self::ProofVerificationError => "ProofVerificationError",
+            Self::IoError => "IoError",
+            Self::SerializationError => "SerializationError",
+            Self::HashToCurveError => "HashToCurveError",
+            Self::KeyDerivationError => "KeyDerivationError",
+            Self::UnexpectedPhase => "UnexpectedPhase",
+            Self::UnknownPlayer => "UnknownPlayer",
+            Self::DuplicateMessage => "DuplicateMessage",
+            Self::NotEnoughPlayers => "NotEnoughPlayers",
+            Self::InvalidThreshold => "InvalidThreshold",
+            Self::NotEnoughShares => "NotEnoughShares",
+            Self::CorruptedShare => "CorruptedShare",
+            Self::DuplicateShare => "DuplicateShare",
+            Self::MixedShares => "MixedShares",
+            Self::ValueOutOfRange => "ValueOutOfRange",
+            Self::CommitmentLengthError => "CommitmentLengthError",
+            Self::ZeroMaskingFactor => "ZeroMaskingFactor",
+            Self::EncryptionError => "EncryptionError",
+            Self::DecryptionError => "DecryptionError",
+            Self::DimensionMismatch => "DimensionMismatch",
+            Self::InvalidPermutation => "InvalidPermutation",
+            Self::IdentityPublicKey => "IdentityPublicKey",
+            Self::InvalidPoint => "InvalidPoint",
+            Self::NonCanonicalEncoding => "NonCanonicalEncoding",
+            Self::EncodingLengthError => "EncodingLengthError",
+            Self::RandomnessFailure => "RandomnessFailure",
+            Self::DuplicatePlayerKey => "DuplicatePlayerKey",
+            Self::ReusedMaskingFactor => "ReusedMaskingFactor",
+            Self::CallerSuppliedRandomness => "CallerSuppliedRandomness",
+            Self::InvalidSignature => "InvalidSignature",
+            Self::CertificateExpired => "CertificateExpired",
+            Self::CertificateSessionMismatch => "CertificateSessionMismatch",
+            Self::UnverifiableParameters => "UnverifiableParameters",
+            Self::MessageTooLarge => "MessageTooLarge",
+            Self::InvalidSecretKey => "InvalidSecretKey",
+            Self::NotYourTurn => "NotYourTurn",
+            Self::UnexpectedReveal => "UnexpectedReveal",
+            Self::InvalidPlayerCount => "InvalidPlayerCount",
+            Self::VerificationFailed => "VerificationFailed",
+            Self::InvalidKeyOwnershipProof => "InvalidKeyOwnershipProof",
+            Self::InvalidRevealToken => "InvalidRevealToken",
+            Self::InvalidDeal => "InvalidDeal",
+            Self::BurnedCard => "BurnedCard",
+            Self::DiscardedCard => "DiscardedCard",
+            Self::CardNotInHand => "CardNotInHand",
+            Self::UnfoundedBlame => "UnfoundedBlame",
+            Self::MissingReveals => "MissingReveals",
+            Self::UnexpectedPosition => "UnexpectedPosition",
+            Self::InvalidOpening => "InvalidOpening",
+            Self::PotMismatch => "PotMismatch",
+            Self::AmountOverflow => "AmountOverflow",
+            Self::DuplicateLabel => "DuplicateLabel",
+            Self::InvalidCardLabel => "InvalidCardLabel",
+            Self::UnknownCard => "UnknownCard",
+            Self::BrokenChain => "BrokenChain",
+            Self::UnsupportedSnapshotVersion => "UnsupportedSnapshotVersion",
+            Self::InvalidSnapshot => "InvalidSnapshot",
+            Self::IllegalTransition => "IllegalTransition",
+            Self::TransportError => "TransportError",
+            Self::ConformanceMismatch => "ConformanceMismatch",
+            Self::ShuffleVerificationFailed => "ShuffleVerificationFailed",
+        }
+    }
+}
+
+impl CardProtocolError {
+    /// The stable code of the error.
+    pub fn code(&self) -> ErrorCode {
+        match self {
+            Self::InvalidInput(error) => error.code(),
+            Self::VerificationFailed(error) => error.code(),
+            Self::Internal(error) => error.code(),
+        }
+    }
+}
+
+impl InputError {
+    /// The stable code of the error.
+    pub fn code(&self) -> ErrorCode {
+        match self {
+            Self::SerializationError(..) => ErrorCode::SerializationError,
+            Self::KeyDerivationError => ErrorCode::KeyDerivationError,
+            Self::UnexpectedPhase => ErrorCode::UnexpectedPhase,
+            Self::UnknownPlayer(..) => ErrorCode::UnknownPlayer,
+            Self::DuplicateMessage(..) => ErrorCode::DuplicateMessage,
+            Self::NotEnoughPlayers => ErrorCode::NotEnoughPlayers,
+            Self::InvalidThreshold(..) => ErrorCode::InvalidThreshold,
+            Self::NotEnoughShares(..) => ErrorCode::NotEnoughShares,
+            Self::DuplicateShare(..) => ErrorCode::DuplicateShare,
+            Self::MixedShares => ErrorCode::MixedShares,
+            Self::ValueOutOfRange(..) => ErrorCode::ValueOutOfRange,
+            Self::CommitmentLengthError(..) => ErrorCode::CommitmentLengthError,
+            Self::ZeroMaskingFactor => ErrorCode::ZeroMaskingFactor,
+            Self::DimensionMismatch { .. } => ErrorCode::DimensionMismatch,
+            Self::InvalidPermutation => ErrorCode::InvalidPermutation,
+            Self::IdentityPublicKey(..) => ErrorCode::IdentityPublicKey,
+            Self::InvalidPoint => ErrorCode::InvalidPoint,
+            Self::NonCanonicalEncoding => ErrorCode::NonCanonicalEncoding,
+            Self::EncodingLengthError { .. } => ErrorCode::EncodingLengthError,
+            Self::DuplicatePlayerKey { .. } => ErrorCode::DuplicatePlayerKey,
+            Self::ReusedMaskingFactor => ErrorCode::ReusedMaskingFactor,
+            Self::CallerSuppliedRandomness => ErrorCode::CallerSuppliedRandomness,
+            Self::MessageTooLarge(..) => ErrorCode::MessageTooLarge,
+            Self::InvalidSecretKey => ErrorCode::InvalidSecretKey,
+            Self::NotYourTurn(..) => ErrorCode::NotYourTurn,
+            Self::UnexpectedReveal(..) => ErrorCode::UnexpectedReveal,
+            Self::InvalidPlayerCount(..) => ErrorCode::InvalidPlayerCount,
+            Self::InvalidDeal(..) => ErrorCode::InvalidDeal,
+            Self::BurnedCard(..) => ErrorCode::BurnedCard,
+            Self::DiscardedCard(..) => ErrorCode::DiscardedCard,
+            Self::CardNotInHand { .. } => ErrorCode::CardNotInHand,
+            Self::MissingReveals(..) => ErrorCode::MissingReveals,
+            Self::UnexpectedPosition { .. } => ErrorCode::UnexpectedPosition,
+            Self::AmountOverflow => ErrorCode::AmountOverflow,
+            Self::DuplicateLabel(..) => ErrorCode::DuplicateLabel,
+            Self::InvalidCardLabel(..) => ErrorCode::InvalidCardLabel,
+            Self::UnknownCard => ErrorCode::UnknownCard,
+            Self::UnsupportedSnapshotVersion(..) => ErrorCode::UnsupportedSnapshotVersion,
+            Self::InvalidSnapshot => ErrorCode::InvalidSnapshot,
+            Self::IllegalTransition(..) => ErrorCode::IllegalTransition,
+        }
+    }
+}
+
+impl VerificationError {
+    /// The stable code of the error.
+    pub fn code(&self) -> ErrorCode {
+        match self {
+            Self::ProofVerificationError(..) => ErrorCode::ProofVerificationError,
+            Self::ShuffleVerificationFailed(..) => ErrorCode::ShuffleVerificationFailed,
+            Self::PlayerMessage { .. } => ErrorCode::VerificationFailed,
+            Self::InvalidKeyOwnershipProof { .. } => ErrorCode::InvalidKeyOwnershipProof,
+            Self::InvalidRevealToken { .. } => ErrorCode::InvalidRevealToken,
+            Self::CorruptedShare(..) => ErrorCode::CorruptedShare,
+            Self::DecryptionError => ErrorCode::DecryptionError,
+            Self::InvalidSignature => ErrorCode::InvalidSignature,
+            Self::CertificateExpired(..) => ErrorCode::CertificateExpired,
+            Self::CertificateSessionMismatch => ErrorCode::CertificateSessionMismatch,
+            Self::UnverifiableParameters => ErrorCode::UnverifiableParameters,
+            Self::UnfoundedBlame => ErrorCode::UnfoundedBlame,
+            Self::InvalidOpening => ErrorCode::InvalidOpening,
+            Self::PotMismatch => ErrorCode::PotMismatch,
+            Self::BrokenChain(..) => ErrorCode::BrokenChain,
+            Self::ConformanceMismatch { .. } => ErrorCode::ConformanceMismatch,
+        }
+    }
+}
+
+impl InternalError {
+    /// The stable code of the error.
+    pub fn code(&self) -> ErrorCode {
+        match self {
+            Self::IoError(..) => ErrorCode::IoError,
+            Self::SerializationError(..) => ErrorCode::SerializationError,
+            Self::HashToCurveError => ErrorCode::HashToCurveError,
+            Self::EncryptionError => ErrorCode::EncryptionError,
+            Self::RandomnessFailure => ErrorCode::RandomnessFailure,
+            Self::TransportError(..) => ErrorCode::TransportError,
+        }
+    }
+}
+
+/// Stable codes for errors of the proof system, which reach callers of the protocol as
+/// [`VerificationError::ProofVerificationError`].
+pub trait CryptoErrorCode {
+    /// The stable code of the error.
+    fn code(&self) -> ErrorCode;
+}
+
+impl CryptoErrorCode for CryptoError {
+    fn code(&self) -> ErrorCode {
+        ErrorCode::ProofVerificationError
+    }
+}
+
+/// The size of an input that did not match what the operation expects, as reported by
+/// [`InputError::DimensionMismatch`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum Dimension {
+    /// Cards in the deck given to an operation
+    Deck,
+    /// Cards in the deck that a shuffle produced
+    ShuffledDeck,
+    /// Masking factors given to a shuffle
+    MaskingFactors,
+    /// Positions rearranged by a permutation
+    Permutation,
+    /// Reveal tokens given to open a card
+    RevealTokens,
+    /// Public keys of the players at a table
+    PublicKeys,
+    /// Scalars given to a multi-scalar multiplication, one per base
+    Scalars,
+    /// Elements arranged in a matrix
+    MatrixElements,
+    /// Rows of the matrix a deck is arranged in
+    Rows,
+    /// Columns of the matrix a deck is arranged in
+    Columns,
+}
+
+impl fmt::Display for Dimension {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            Self::Deck => "cards in the deck",
+            Self::ShuffledDeck => "cards in the shuffled deck",
+            Self::MaskingFactors => "masking factors",
+            Self::Permutation => "permuted positions",
+            Self::RevealTokens => "reveal tokens",
+            Self::PublicKeys => "public keys",
+            Self::Scalars => "scalars",
+            Self::MatrixElements => "matrix elements",
+            Self::Rows => "rows",
+            Self::Columns => "columns",
+        };
+
+        f.write_str(name)
+    }
+}
+
+/// The sizes an operation accepts for a [`Dimension`], as reported by
+/// [`InputError::DimensionMismatch`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Bound {
+    /// Exactly this many
+    Exactly(usize),
+    /// This many or more
+    AtLeast(usize),
+    /// This many or fewer
+    AtMost(usize),
 }
 
+impl fmt::Display for Bound {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Exactly(size) => write!(f, "{}", size),
+            Self::AtLeast(size) => write!(f, "at least {}", size),
+            Self::AtMost(size) => write!(f, "at most {}", size),
+        }
+    }
+}
+
+/// Check that `got` elements were given where the operation expects exactly `expected`.
+pub(crate) fn check_dimension(
+    dimension: Dimension,
+    expected: usize,
+    got: usize,
+) -> Result<(), CardProtocolError> {
+    if expected != got {
+        return Err(InputError::DimensionMismatch {
+            dimension,
+            expected: Bound::Exactly(expected),
+            got,
+        }
+        .into());
+    }
+
+    Ok(())
+}
+
+impl From<CryptoError> for CardProtocolError {
+    fn from(err: CryptoError) -> Self {
+        Self::VerificationFailed(VerificationError::ProofVerificationError(err))
+    }
+}
+
+impl From<ShuffleVerificationFailure> for CardProtocolError {
+    /// A deck of the wrong size is malformed input rather than a proof that fails.
+    fn from(failure: ShuffleVerificationFailure) -> Self {
+        match failure {
+            ShuffleVerificationFailure::DeckSize {
+                dimension,
+                expected,
+                got,
+            } => Self::InvalidInput(InputError::DimensionMismatch {
+                dimension,
+                expected: Bound::Exactly(expected),
+                got,
+            }),
+            failure => {
+                Self::VerificationFailed(VerificationError::ShuffleVerificationFailed(failure))
+            }
+        }
+    }
+}
+
+#[cfg(feature = "std")]
 impl From<std::io::Error> for CardProtocolError {
     fn from(err: std::io::Error) -> Self {
-        Self::IoError(err.to_string())
+        Self::Internal(InternalError::IoError(err.to_string()))
+    }
+}
+
+impl From<SerializationError> for CardProtocolError {
+    /// Running out of room happens while encoding, into a buffer the crate or the caller chose,
+    /// and is internal. The other errors report bytes that do not decode.
+    fn from(err: SerializationError) -> Self {
+        match err {
+            SerializationError::NotEnoughSpace => {
+                Self::Internal(InternalError::SerializationError(err.to_string()))
+            }
+            _ => Self::InvalidInput(InputError::SerializationError(err.to_string())),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use std::error::Error;
+
+    // Messages of an error and of all its sources, as a report of the error would print them
+    fn chain(error: &dyn Error) -> Vec<String> {
+        let mut messages = vec![error.to_string()];
+        let mut source = error.source();
+        while let Some(error) = source {
+            messages.push(error.to_string());
+            source = error.source();
+        }
+
+        messages
+    }
+
+    #[test]
+    fn proof_verification_error_chains_to_crypto_error() {
+        let cause = || CryptoError::ProofVerificationError(String::from("Schnorr Identification"));
+        let error = CardProtocolError::from(cause());
+
+        assert_eq!(
+            vec![
+                String::from("Proof verification failed"),
+                cause().to_string()
+            ],
+            chain(&error)
+        );
+        assert!(cause().to_string().contains("Schnorr Identification"));
+    }
+
+    #[test]
+    fn failed_proofs_keep_their_operation_and_cause() {
+        let cause = || CryptoError::ProofVerificationError(String::from("Chaum-Pedersen"));
+        let error = CardProtocolError::VerificationFailed(VerificationError::InvalidRevealToken {
+            index: 1,
+            public_key: vec![],
+            source: OperationError::new(Operation::DuringUnmask { card_index: None }, cause()),
+        })
+        .at_card(3);
+
+        assert_eq!(
+            vec![
+                String::from("Reveal token 1 does not verify"),
+                String::from("During unmask of card 3"),
+                cause().to_string()
+            ],
+            chain(&error)
+        );
+        assert_eq!(
+            error.operation(),
+            Some(Operation::DuringUnmask {
+                card_index: Some(3)
+            })
+        );
+
+        // The cause is the error of the proof system itself, not a description of it
+        let source = error.source().and_then(|source| source.source()).unwrap();
+        assert!(matches!(
+            source.downcast_ref::<CryptoError>(),
+            Some(CryptoError::ProofVerificationError(name)) if name == "Chaum-Pedersen"
+        ));
+
+        // Only the position of a card being opened is recorded
+        let error =
+            CardProtocolError::VerificationFailed(VerificationError::InvalidKeyOwnershipProof {
+                index: 0,
+                public_key: vec![],
+                source: OperationError::new(Operation::DuringKeyAggregation, cause()),
+            });
+        assert_eq!(
+            error.at_card(3).operation(),
+            Some(Operation::DuringKeyAggregation)
+        );
+    }
+
+    #[test]
+    fn error_codes_are_pinned() {
+        // Append only: a failure here means an existing code changed
+        let pinned = [
+            (ErrorCode::ProofVerificationError, 1),
+            (ErrorCode::IoError, 2),
+            (ErrorCode::SerializationError, 3),
+            (ErrorCode::HashToCurveError, 4),
+            (ErrorCode::KeyDerivationError, 5),
+            (ErrorCode::UnexpectedPhase, 6),
+            (ErrorCode::UnknownPlayer, 7),
+            (ErrorCode::DuplicateMessage, 8),
+            (ErrorCode::NotEnoughPlayers, 9),
+            (ErrorCode::InvalidThreshold, 10),
+            (ErrorCode::NotEnoughShares, 11),
+            (ErrorCode::CorruptedShare, 12),
+            (ErrorCode::DuplicateShare, 13),
+            (ErrorCode::MixedShares, 14),
+            (ErrorCode::ValueOutOfRange, 15),
+            (ErrorCode::CommitmentLengthError, 16),
+            (ErrorCode::ZeroMaskingFactor, 18),
+            (ErrorCode::EncryptionError, 19),
+            (ErrorCode::DecryptionError, 20),
+            (ErrorCode::DimensionMismatch, 22),
+            (ErrorCode::InvalidPermutation, 23),
+            (ErrorCode::IdentityPublicKey, 24),
+            (ErrorCode::InvalidPoint, 25),
+            (ErrorCode::NonCanonicalEncoding, 26),
+            (ErrorCode::EncodingLengthError, 27),
+            (ErrorCode::RandomnessFailure, 28),
+            (ErrorCode::DuplicatePlayerKey, 29),
+            (ErrorCode::ReusedMaskingFactor, 30),
+            (ErrorCode::CallerSuppliedRandomness, 31),
+            (ErrorCode::InvalidSignature, 32),
+            (ErrorCode::CertificateExpired, 33),
+            (ErrorCode::CertificateSessionMismatch, 34),
+            (ErrorCode::UnverifiableParameters, 35),
+            (ErrorCode::MessageTooLarge, 36),
+            (ErrorCode::InvalidSecretKey, 37),
+            (ErrorCode::NotYourTurn, 38),
+            (ErrorCode::UnexpectedReveal, 39),
+            (ErrorCode::InvalidPlayerCount, 40),
+            (ErrorCode::VerificationFailed, 42),
+            (ErrorCode::InvalidKeyOwnershipProof, 43),
+            (ErrorCode::InvalidRevealToken, 44),
+            (ErrorCode::InvalidDeal, 45),
+            (ErrorCode::BurnedCard, 46),
+            (ErrorCode::DiscardedCard, 47),
+            (ErrorCode::CardNotInHand, 48),
+            (ErrorCode::UnfoundedBlame, 49),
+            (ErrorCode::MissingReveals, 50),
+            (ErrorCode::UnexpectedPosition, 51),
+            (ErrorCode::InvalidOpening, 52),
+            (ErrorCode::PotMismatch, 53),
+            (ErrorCode::AmountOverflow, 54),
+            (ErrorCode::DuplicateLabel, 55),
+            (ErrorCode::InvalidCardLabel, 56),
+            (ErrorCode::UnknownCard, 57),
+            (ErrorCode::BrokenChain, 58),
+            (ErrorCode::UnsupportedSnapshotVersion, 59),
+            (ErrorCode::InvalidSnapshot, 60),
+            (ErrorCode::IllegalTransition, 61),
+            (ErrorCode::TransportError, 62),
+            (ErrorCode::ConformanceMismatch, 63),
+            (ErrorCode::ShuffleVerificationFailed, 64),
+        ];
+
+        for (code, value) in pinned.iter() {
+            assert_eq!(code.value(), *value, "{:?} was renumbered", code);
+            assert_eq!(code.name(), format!("{:?}", code));
+        }
+
+        assert_eq!(
+            CardProtocolError::InvalidInput(InputError::DimensionMismatch {
+                dimension: Dimension::Deck,
+                expected: Bound::Exactly(52),
+                got: 51
+            })
+            .code(),
+            ErrorCode::DimensionMismatch
+        );
+        let cause = CryptoError::ProofVerificationError(String::from("Shuffle"));
+        assert_eq!(cause.code(), ErrorCode::ProofVerificationError);
+        assert_eq!(
+            CardProtocolError::from(cause).code(),
+            ErrorCode::ProofVerificationError
+        );
+    }
+
+    #[test]
+    fn shuffle_failures_are_recognised() {
+        let failure = |name: &str| {
+            ShuffleVerificationFailure::from(CryptoError::ProofVerificationError(String::from(
+                name,
+            )))
+        };
+
+        assert_eq!(
+            failure("Hadamard Product (5.1)"),
+            ShuffleVerificationFailure::HadamardProduct
+        );
+        assert_eq!(
+            failure("Zero Argument (5.2)"),
+            ShuffleVerificationFailure::ZeroArgument
+        );
+        assert_eq!(
+            failure("Single Value Product Argument (5.3)"),
+            ShuffleVerificationFailure::SingleValueProduct
+        );
+        assert_eq!(
+            failure("Multi-exponentiation"),
+            ShuffleVerificationFailure::MultiExponentiation
+        );
+        assert_eq!(failure("Schnorr"), ShuffleVerificationFailure::Transcript);
+        assert_eq!(
+            failure("Shuffle"),
+            ShuffleVerificationFailure::CiphertextConsistency
+        );
+
+        // Only the names of the arguments themselves identify them
+        assert_eq!(failure("Zero"), ShuffleVerificationFailure::Transcript);
+        assert_eq!(
+            failure("hadamard product (5.1)"),
+            ShuffleVerificationFailure::Transcript
+        );
+        assert_eq!(
+            failure("Multi-exponentiation of the Schnorr proof"),
+            ShuffleVerificationFailure::Transcript
+        );
+
+        // Logs keep the names the proof system used
+        assert_eq!(
+            ShuffleVerificationFailure::HadamardProduct.to_string(),
+            "Hadamard Product (5.1)"
+        );
+        assert_eq!(
+            chain(&CardProtocolError::from(
+                ShuffleVerificationFailure::ZeroArgument
+            )),
+            vec![
+                String::from("Shuffle proof verification failed"),
+                String::from("Zero Argument (5.2)")
+            ]
+        );
+    }
+
+    #[test]
+    fn environment_failures_are_internal() {
+        assert!(matches!(
+            CardProtocolError::from(std::io::Error::from(std::io::ErrorKind::BrokenPipe)),
+            CardProtocolError::Internal(InternalError::IoError(_))
+        ));
+        assert!(matches!(
+            CardProtocolError::from(SerializationError::NotEnoughSpace),
+            CardProtocolError::Internal(InternalError::SerializationError(_))
+        ));
+        assert!(matches!(
+            CardProtocolError::from(SerializationError::InvalidData),
+            CardProtocolError::InvalidInput(InputError::SerializationError(_))
+        ));
+    }
+
+    #[test]
+    fn errors_without_cause_end_the_chain() {
+        let error = CardProtocolError::from(SerializationError::NotEnoughSpace);
+
+        assert_eq!(
+            vec![format!(
+                "Failed to encode: {}",
+                SerializationError::NotEnoughSpace
+            )],
+            chain(&error)
+        );
     }
 }
@@ -0,0 +1,378 @@
+//! Encoding of reveal tokens and their proofs over the Starknet curve as arrays of field elements,
+//! for verifiers written in Cairo.
+//!
+//! The base field of the Starknet curve is the field of Cairo's `felt252`, and its group order is
+//! smaller than the modulus, so every value maps to field elements without reduction:
+//! - a point is two felts, its affine coordinates `x` then `y`, with the identity encoded as
+//!   `(0, 0)`, which is not on the curve. Points are never compressed, so the contract does not
+//!   have to take square roots;
+//! - a scalar is one felt;
+//! - a byte string is laid out as Cairo serializes a `ByteArray`: the number of full 31-byte
+//!   words, the words, the remaining bytes as one felt, and their count. Words are big-endian.
+//!
+//! A reveal is the 13 felts
+//!
+//! | offset | content                                   |
+//! |--------|-------------------------------------------|
+//! | 0      | public key `pk` of the player             |
+//! | 2      | first component `c1` of the masked card   |
+//! | 4      | second component `c2` of the masked card  |
+//! | 6      | reveal token `t = c1^sk`                  |
+//! | 8      | commitment `a = g^w` of the proof         |
+//! | 10     | commitment `b = c1^w` of the proof        |
+//! | 12     | response `r` of the proof                 |
+//!
+//! where `g` is the generator of the encryption parameters. The inputs of an unmask operation are
+//!
+//! | offset     | content                                                  |
+//! |------------|----------------------------------------------------------|
+//! | 0          | generator `g`                                            |
+//! | 2          | masked card `c1`, `c2`                                   |
+//! | 6          | opened card `c2 - (t_1 + ... + t_k)`                     |
+//! | 8          | number of reveals `k`                                    |
+//! | 9          | `k` times: `pk`, `t`, `a`, `b`, `r` (9 felts)            |
+//! | 9 + 9k     | seed of the Fiat-Shamir transcript, as a `ByteArray`     |
+//!
+//! As for `discrete_log_cards::evm`, the challenge is not part of the encoding: the verifier
+//! recomputes it from the transcript seed and the statement as `proof_essentials` does. Nothing
+//! is verified on export, so invalid proofs can be exported as test cases.
+
+use crate::discrete_log_cards::{
+    reveal_transcript_seed, MaskedCard, Parameters, PublicKey, RevealToken,
+};
+use crate::error::CardProtocolError;
+
+use ark_ec::{AffineCurve, ProjectiveCurve};
+use ark_ff::{BigInteger, PrimeField, Zero};
+use ark_serialize::{CanonicalDeserialize, CanonicalSerialize};
+use proof_essentials::zkp::proofs::chaum_pedersen_dl_equality;
+use starknet_curve::{Affine, Fq, Fr, Projective};
+
+/// A Cairo `felt252`, which is an element of the base field of the Starknet curve.
+pub type Felt = Fq;
+
+/// Number of felts of an encoded reveal.
+pub const REVEAL_FELTS: usize = 13;
+
+/// Number of bytes in a word of a Cairo `ByteArray`.
+pub const BYTES31: usize = 31;
+
+pub type RevealProof = chaum_pedersen_dl_equality::proof::Proof<Projective>;
+
+/// Encode a point as `[x, y]`, with the identity as `[0, 0]`.
+pub fn point_felts(point: &Affine) -> [Felt; 2] {
+    if point.is_zero() {
+        return [Felt::zero(), Felt::zero()];
+    }
+
+    [point.x, point.y]
+}
+
+/// Encode a scalar, which is smaller than the modulus of the felt field.
+pub fn scalar_felt(scalar: &Fr) -> Felt {
+    Felt::from_be_bytes_mod_order(&scalar.into_repr().to_bytes_be())
+}
+
+/// Encode bytes as Cairo serializes a `ByteArray`.
+pub fn byte_array_felts(bytes: &[u8]) -> Vec<Felt> {
+    let words = bytes.chunks_exact(BYTES31);
+    let pending = words.remainder();
+
+    let mut felts = vec![Felt::from(words.len() as u64)];
+    felts.extend(words.map(Felt::from_be_bytes_mod_order));
+    felts.push(Felt::from_be_bytes_mod_order(pending));
+    felts.push(Felt::from(pending.len() as u64));
+
+    felts
+}
+
+/// Hexadecimal form of a felt, as Starknet tooling prints it: `0x` and no leading zeros.
+pub fn felt_to_hex(felt: &Felt) -> String {
+    let hex = felt
+        .into_repr()
+        .to_bytes_be()
+        .iter()
+        .map(|byte| format!("{:02x}", byte))
+        .collect::<String>();
+
+    match hex.trim_start_matches('0') {
+        "" => "0x0".to_string(),
+        digits => format!("0x{}", digits),
+    }
+}
+
+/// Seed of the transcript reveal proofs in `context` are bound to, as a `ByteArray`.
+pub fn transcript_seed_felts(context: &[u8]) -> Result<Vec<Felt>, CardProtocolError> {
+    Ok(byte_array_felts(&reveal_transcript_seed(context)?))
+}
+
+/// Encode the reveal of `masked_card` by the owner of `pk` in the layout described in the module
+/// documentation.
+pub fn export_reveal(
+    pk: &PublicKey<Projective>,
+    masked_card: &MaskedCard<Projective>,
+    token: &RevealToken<Projective>,
+    proof: &RevealProof,
+) -> Result<Vec<Felt>, CardProtocolError> {
+    let (a, b, r) = proof_components(proof)?;
+
+    let mut felts = Vec::with_capacity(REVEAL_FELTS);
+    for point in [pk, &masked_card.0, &masked_card.1, &token.0, &a, &b] {
+        felts.extend_from_slice(&point_felts(point));
+    }
+    felts.push(scalar_felt(&r));
+
+    Ok(felts)
+}
+
+/// Encode everything a verifier needs to check the opening of `masked_card` in `context` from
+/// `decryption_key`, the reveal tokens of every player with their proofs and public keys.
+pub fn export_unmask(
+    pp: &Parameters<Projective>,
+    context: &[u8],
+    decryption_key: &[(RevealToken<Projective>, RevealProof, PublicKey<Projective>)],
+    masked_card: &MaskedCard<Projective>,
+) -> Result<Vec<Felt>, CardProtocolError> {
+    let aggregate_token = decryption_key
+        .iter()
+        .fold(Projective::zero(), |acc, (token, _, _)| {
+            acc + token.0.into_projective()
+        });
+    let card = (masked_card.1.into_projective() - aggregate_token).into_affine();
+
+    let mut felts = Vec::new();
+    for point in [
+        &pp.encryption_generator(),
+        &masked_card.0,
+        &masked_card.1,
+        &card,
+    ] {
+        felts.extend_from_slice(&point_felts(point));
+    }
+
+    felts.push(Felt::from(decryption_key.len() as u64));
+    for (token, proof, pk) in decryption_key {
+        let (a, b, r) = proof_components(proof)?;
+        for point in [pk, &token.0, &a, &b] {
+            felts.extend_from_slice(&point_felts(point));
+        }
+        felts.push(scalar_felt(&r));
+    }
+
+    felts.extend(transcript_seed_felts(context)?);
+
+    Ok(felts)
+}
+
+/// The proof keeps its components private; its canonical encoding is the commitments `a` and `b`
+/// followed by the response `r`.
+fn proof_components(proof: &RevealProof) -> Result<(Affine, Affine, Fr), CardProtocolError> {
+    let mut bytes = Vec::new();
+    proof.serialize(&mut bytes)?;
+
+    Ok(CanonicalDeserialize::deserialize(&bytes[..])?)
+}
+
+#[cfg(test)]
+mod test {
+    use super::{
+        byte_array_felts, export_reveal, export_unmask, felt_to_hex, point_felts, proof_components,
+        scalar_felt, transcript_seed_felts, Felt, REVEAL_FELTS,
+    };
+    use crate::crypto_primitives::utils::rand::test_rng;
+    use crate::discrete_log_cards;
+    use crate::discrete_log_cards::test_helpers::{reveal_tokens, setup_players};
+    use crate::BarnettSmartProtocol;
+
+    use ark_ff::{PrimeField, Zero};
+    use ark_std::UniformRand;
+    use rand::SeedableRng;
+    use rand_chacha::ChaCha20Rng;
+
+    // Choose elliptic curve setting
+    type Curve = starknet_curve::Projective;
+
+    // Instantiate concrete type for our card protocol
+    type CardProtocol<'a> = discrete_log_cards::DLCards<'a, Curve>;
+    type MaskedCard = discrete_log_cards::MaskedCard<Curve>;
+
+    const SESSION_ID: &[u8] = b"cairo export test";
+
+    const FIXTURES: &str = concat!(
+        env!("CARGO_MANIFEST_DIR"),
+        "/tests/vectors/cairo_reveal_proofs.json"
+    );
+
+    /// Seed and context of `examples/cairo_fixtures.rs`, which draws the parameters first
+    const FIXTURE_SEED: [u8; 32] = *b"cairo reveal proof fixtures v1..";
+    const FIXTURE_CONTEXT: &[u8] = b"cairo fixtures";
+
+    /// The felts of the last list on a line of the fixture file, as written by `felt_to_hex`.
+    fn felts_on(line: &str) -> Vec<String> {
+        let list = &line[line.rfind('[').unwrap() + 1..line.rfind(']').unwrap()];
+
+        list.split(',')
+            .map(|felt| felt.trim().trim_matches('"').to_string())
+            .filter(|felt| !felt.is_empty())
+            .collect()
+    }
+
+    fn from_hex(felt: &str) -> Felt {
+        let digits = felt.trim_start_matches("0x");
+        let digits = format!("{:0>1$}", digits, digits.len() + digits.len() % 2);
+        let bytes = (0..digits.len())
+            .step_by(2)
+            .map(|i| u8::from_str_radix(&digits[i..i + 2], 16).unwrap())
+            .collect::<Vec<_>>();
+
+        Felt::from_be_bytes_mod_order(&bytes)
+    }
+
+    fn to_hex(felts: &[Felt]) -> Vec<String> {
+        felts.iter().map(felt_to_hex).collect()
+    }
+
+    #[test]
+    fn test_felt_encodings() {
+        assert_eq!(byte_array_felts(b""), vec![Felt::zero(); 3]);
+
+        // One full word and two pending bytes
+        let bytes = (0u8..33).collect::<Vec<_>>();
+        assert_eq!(
+            byte_array_felts(&bytes),
+            vec![
+                Felt::from(1u64),
+                Felt::from_be_bytes_mod_order(&bytes[..31]),
+                Felt::from(31u64 * 256 + 32),
+                Felt::from(2u64),
+            ]
+        );
+
+        assert_eq!(felt_to_hex(&Felt::zero()), "0x0");
+        assert_eq!(felt_to_hex(&Felt::from(0x1abu64)), "0x1ab");
+        assert_eq!(
+            point_felts(&starknet_curve::Affine::zero()),
+            [Felt::zero(); 2]
+        );
+    }
+
+    #[test]
+    fn test_reveal_export() {
+        let rng = &mut test_rng();
+        let m = 4;
+        let n = 13;
+
+        let parameters = CardProtocol::setup(rng, m, n).unwrap();
+        let (pk, sk) = CardProtocol::player_keygen(rng, &parameters).unwrap();
+        let masked = MaskedCard::rand(rng);
+
+        let (token, proof) =
+            CardProtocol::compute_reveal_token(rng, &parameters, SESSION_ID, &sk, &pk, &masked)
+                .unwrap();
+        let felts = export_reveal(&pk, &masked, &token, &proof).unwrap();
+        assert_eq!(felts.len(), REVEAL_FELTS);
+        assert_eq!(&felts[..2], &point_felts(&pk)[..]);
+        assert_eq!(&felts[2..4], &point_felts(&masked.0)[..]);
+        assert_eq!(&felts[6..8], &point_felts(&token.0)[..]);
+
+        // Coordinates are exported as they are, so the contract reads back the same point
+        let token_point = starknet_curve::Affine::new(felts[6], felts[7], false);
+        assert_eq!(token_point, token.0);
+
+        let (a, b, r) = proof_components(&proof).unwrap();
+        assert_eq!(&felts[8..10], &point_felts(&a)[..]);
+        assert_eq!(&felts[10..12], &point_felts(&b)[..]);
+        assert_eq!(felts[12], scalar_felt(&r));
+    }
+
+    #[test]
+    fn test_unmask_export() {
+        let rng = &mut test_rng();
+        let m = 4;
+        let n = 13;
+        let num_of_players = 3;
+
+        let parameters = CardProtocol::setup(rng, m, n).unwrap();
+        let (keys, _) = setup_players(rng, &parameters, num_of_players).unwrap();
+        let masked = MaskedCard::rand(rng);
+
+        let decryption_key = reveal_tokens(rng, &parameters, SESSION_ID, &keys, &masked).unwrap();
+        let card = CardProtocol::unmask(
+            &parameters,
+            SESSION_ID,
+            num_of_players,
+            &decryption_key,
+            &masked,
+        )
+        .unwrap();
+
+        let felts = export_unmask(&parameters, SESSION_ID, &decryption_key, &masked).unwrap();
+        let seed = transcript_seed_felts(SESSION_ID).unwrap();
+        assert_eq!(felts.len(), 9 + 9 * num_of_players + seed.len());
+        assert_eq!(
+            &felts[..2],
+            &point_felts(&parameters.encryption_generator())[..]
+        );
+        assert_eq!(&felts[6..8], &point_felts(&card.0)[..]);
+        assert_eq!(felts[8], Felt::from(num_of_players as u64));
+        assert_eq!(&felts[9..11], &point_felts(&keys[0].0)[..]);
+        assert_eq!(&felts[9 + 9 * num_of_players..], &seed[..]);
+    }
+
+    #[test]
+    fn test_checked_in_fixtures() {
+        let fixtures = std::fs::read_to_string(FIXTURES).unwrap();
+        let rng = &mut ChaCha20Rng::from_seed(FIXTURE_SEED);
+        let parameters = CardProtocol::setup(rng, 4, 13).unwrap();
+
+        let line = |key: &str| {
+            fixtures
+                .lines()
+                .find(|line| line.trim_start().starts_with(&format!("\"{}\"", key)))
+                .unwrap()
+        };
+        assert_eq!(
+            felts_on(line("generator")),
+            to_hex(&point_felts(&parameters.encryption_generator()))
+        );
+        assert_eq!(
+            felts_on(line("transcript_seed")),
+            to_hex(&transcript_seed_felts(FIXTURE_CONTEXT).unwrap())
+        );
+
+        let cases = fixtures
+            .lines()
+            .filter(|line| line.contains("\"felts\""))
+            .map(|line| {
+                let valid = line.contains("\"valid\": true");
+                let felts = felts_on(line).iter().map(|felt| from_hex(felt)).collect();
+                (valid, felts)
+            })
+            .collect::<Vec<(bool, Vec<Felt>)>>();
+
+        // Six reveals, then two unmasks, each list led by its valid case
+        assert_eq!(cases.len(), 8);
+        let valid = cases.iter().filter(|(valid, _)| *valid).count();
+        assert_eq!(valid, 2);
+        assert!(cases[0].0 && cases[6].0);
+
+        let on_curve = |felts: &[Felt]| starknet_curve::Affine::new(felts[0], felts[1], false);
+        for (_, felts) in &cases[..6] {
+            assert_eq!(felts.len(), REVEAL_FELTS);
+        }
+        for offset in (0..REVEAL_FELTS - 1).step_by(2) {
+            assert!(on_curve(&cases[0].1[offset..]).is_on_curve());
+        }
+
+        // The last invalid reveal moves the token off the curve
+        assert!(!on_curve(&cases[5].1[6..]).is_on_curve());
+
+        // Both unmasks are for three players, over the transcript seed of the fixtures
+        let seed = transcript_seed_felts(FIXTURE_CONTEXT).unwrap();
+        for (_, felts) in &cases[6..] {
+            assert_eq!(felts.len(), 9 + 9 * 3 + seed.len());
+            assert_eq!(felts[8], Felt::from(3u64));
+            assert_eq!(&felts[9 + 9 * 3..], &seed[..]);
+        }
+    }
+}
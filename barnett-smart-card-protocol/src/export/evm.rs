@@ -0,0 +1,293 @@
+//! Calldata for Solidity verifiers of reveal proofs over the G1 group of BN254.
+//!
+//! Values are encoded as in `discrete_log_cards::evm`, the format of the `alt_bn128` precompiles:
+//! every field element is a 32-byte big-endian word, a point is its uncompressed coordinates `x`
+//! then `y` with the identity as `(0, 0)`, and a scalar is one word smaller than the group order.
+//!
+//! Everything needed to check the reveal of a masked card by one player is the packed struct of
+//! 416 bytes
+//!
+//! | offset | length | content                                             |
+//! |--------|--------|-----------------------------------------------------|
+//! | 0      | 32     | `x` of the first component `c1` of the masked card  |
+//! | 32     | 32     | `y` of `c1`                                         |
+//! | 64     | 32     | `x` of the second component `c2` of the masked card |
+//! | 96     | 32     | `y` of `c2`                                         |
+//! | 128    | 32     | `x` of the reveal token `t = c1^sk`                 |
+//! | 160    | 32     | `y` of `t`                                          |
+//! | 192    | 32     | `x` of the public key `pk = g^sk`                   |
+//! | 224    | 32     | `y` of `pk`                                         |
+//! | 256    | 32     | `x` of the commitment `a = g^w`                     |
+//! | 288    | 32     | `y` of `a`                                          |
+//! | 320    | 32     | `x` of the commitment `b = c1^w`                    |
+//! | 352    | 32     | `y` of `b`                                          |
+//! | 384    | 32     | response `r` of the proof                           |
+//!
+//! which a contract can read with `abi.decode(data, (uint256[2], uint256[2], uint256[2],
+//! uint256[2], uint256[2], uint256[2], uint256))`. The Fiat-Shamir challenge is not part of the
+//! calldata: the verifier recomputes it from the statement. Nothing is verified on encoding, so
+//! invalid proofs can be encoded as test cases; decoding checks that every point is on the curve.
+//!
+//! `tests/vectors/bn254_reveal_layout.txt` holds calldata for known points, to develop the
+//! Solidity decoder against. `tests/vectors/bn254_reveal_verification.txt` holds calldata of
+//! valid proofs, each followed by entries that break it in one way, which a verifier must reject.
+
+use crate::crypto_primitives::utils::format::{Compact, Hex};
+use crate::discrete_log_cards::evm::{
+    decode_point, decode_scalar, encode_point, encode_scalar, proof_components,
+    proof_from_components, RevealProof, POINT_LENGTH, SCALAR_LENGTH,
+};
+use crate::discrete_log_cards::{MaskedCard, PublicKey, RevealToken};
+use crate::error::{CardProtocolError, InputError};
+
+use ark_bn254::G1Projective;
+use proof_essentials::homomorphic_encryption::el_gamal;
+use std::fmt;
+
+/// Length in bytes of the calldata of a reveal verification.
+pub const REVEAL_VERIFICATION_LENGTH: usize = 6 * POINT_LENGTH + SCALAR_LENGTH;
+
+/// The statement and proof of the reveal of a masked card, as decoded from calldata.
+#[derive(Clone)]
+pub struct RevealVerification {
+    pub masked_card: MaskedCard<G1Projective>,
+    pub token: RevealToken<G1Projective>,
+    pub pk: PublicKey<G1Projective>,
+    pub proof: RevealProof,
+}
+
+impl fmt::Debug for RevealVerification {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("RevealVerification")
+            .field("masked_card", &Compact(&self.masked_card))
+            .field("token", &Compact(&self.token))
+            .field("pk", &Hex(&self.pk))
+            .field("proof", &Compact(&self.proof))
+            .finish()
+    }
+}
+
+/// Encode the reveal of `masked_card` by the owner of `pk` in the layout described in the module
+/// documentation.
+pub fn encode_reveal_verification(
+    masked_card: &MaskedCard<G1Projective>,
+    token: &RevealToken<G1Projective>,
+    pk: &PublicKey<G1Projective>,
+    proof: &RevealProof,
+) -> Result<Vec<u8>, CardProtocolError> {
+    let (a, b, r) = proof_components(proof)?;
+
+    let mut bytes = Vec::with_capacity(REVEAL_VERIFICATION_LENGTH);
+    for point in [&masked_card.0, &masked_card.1, &token.0, pk, &a, &b] {
+        bytes.extend_from_slice(&encode_point(point));
+    }
+    bytes.extend_from_slice(&encode_scalar(&r));
+
+    Ok(bytes)
+}
+
+/// Decode calldata produced by [`encode_reveal_verification`].
+pub fn decode_reveal_verification(bytes: &[u8]) -> Result<RevealVerification, CardProtocolError> {
+    if bytes.len() != REVEAL_VERIFICATION_LENGTH {
+        return Err(InputError::EncodingLengthError {
+            expected: REVEAL_VERIFICATION_LENGTH,
+            length: bytes.len(),
+        }
+        .into());
+    }
+
+    let (points, r) = bytes.split_at(6 * POINT_LENGTH);
+    let points = points
+        .chunks_exact(POINT_LENGTH)
+        .map(decode_point)
+        .collect::<Result<Vec<_>, _>>()?;
+    let r = decode_scalar(r)?;
+
+    Ok(RevealVerification {
+        masked_card: el_gamal::Ciphertext(points[0], points[1]),
+        token: el_gamal::Plaintext(points[2]),
+        pk: points[3],
+        proof: proof_from_components(&points[4], &points[5], &r)?,
+    })
+}
+
+#[cfg(test)]
+mod test {
+    use super::{
+        decode_reveal_verification, encode_reveal_verification, REVEAL_VERIFICATION_LENGTH,
+    };
+    use crate::crypto_primitives::utils::rand::test_rng;
+    use crate::discrete_log_cards;
+    use crate::discrete_log_cards::evm::proof_from_components;
+    use crate::error::{CardProtocolError, InputError};
+    use crate::BarnettSmartProtocol;
+
+    use ark_bn254::{Fr, G1Affine};
+    use ark_ec::{AffineCurve, ProjectiveCurve};
+    use ark_ff::PrimeField;
+    use ark_std::UniformRand;
+    use proof_essentials::homomorphic_encryption::el_gamal;
+    use std::str::FromStr;
+
+    // Choose elliptic curve setting
+    type Curve = ark_bn254::G1Projective;
+
+    // Instantiate concrete type for our card protocol
+    type CardProtocol<'a> = discrete_log_cards::DLCards<'a, Curve>;
+    type MaskedCard = discrete_log_cards::MaskedCard<Curve>;
+
+    const REVEAL_LAYOUT_VECTORS: &str = include_str!("../../tests/vectors/bn254_reveal_layout.txt");
+    const REVEAL_VERIFICATION_VECTORS: &str =
+        include_str!("../../tests/vectors/bn254_reveal_verification.txt");
+
+    fn to_hex(bytes: &[u8]) -> String {
+        bytes.iter().map(|b| format!("{:02x}", b)).collect()
+    }
+
+    fn from_hex(hex: &str) -> Vec<u8> {
+        (0..hex.len())
+            .step_by(2)
+            .map(|i| u8::from_str_radix(&hex[i..i + 2], 16).unwrap())
+            .collect()
+    }
+
+    fn multiple(point: &G1Affine, scalar: &Fr) -> G1Affine {
+        point.mul(scalar.into_repr()).into_affine()
+    }
+
+    #[test]
+    fn test_reveal_verification_round_trip() {
+        let rng = &mut test_rng();
+        let m = 4;
+        let n = 13;
+
+        let parameters = CardProtocol::setup(rng, m, n).unwrap();
+        let (pk, sk) = CardProtocol::player_keygen(rng, &parameters).unwrap();
+        let masked = MaskedCard::rand(rng);
+
+        let (token, proof) =
+            CardProtocol::compute_reveal_token(rng, &parameters, b"", &sk, &pk, &masked).unwrap();
+        let bytes = encode_reveal_verification(&masked, &token, &pk, &proof).unwrap();
+        assert_eq!(bytes.len(), REVEAL_VERIFICATION_LENGTH);
+
+        let decoded = decode_reveal_verification(&bytes).unwrap();
+        assert_eq!(decoded.masked_card, masked);
+        assert_eq!(decoded.token, token);
+        assert_eq!(decoded.pk, pk);
+        assert_eq!(
+            Ok(()),
+            CardProtocol::verify_reveal(
+                &parameters,
+                b"",
+                &decoded.pk,
+                &decoded.token,
+                &decoded.masked_card,
+                &decoded.proof
+            )
+        );
+
+        // Calldata that is truncated, off the curve or not reduced is rejected
+        assert!(matches!(
+            decode_reveal_verification(&bytes[1..]),
+            Err(CardProtocolError::InvalidInput(
+                InputError::EncodingLengthError { .. }
+            ))
+        ));
+        let mut off_curve = bytes.clone();
+        off_curve[63] ^= 1;
+        assert!(matches!(
+            decode_reveal_verification(&off_curve),
+            Err(CardProtocolError::InvalidInput(InputError::InvalidPoint))
+        ));
+        let mut unreduced = bytes.clone();
+        unreduced[384..].copy_from_slice(&[0xff; 32]);
+        assert!(matches!(
+            decode_reveal_verification(&unreduced),
+            Err(CardProtocolError::InvalidInput(
+                InputError::NonCanonicalEncoding
+            ))
+        ));
+    }
+
+    #[test]
+    fn test_reveal_layout_vectors() {
+        let g = G1Affine::prime_subgroup_generator();
+
+        for line in REVEAL_LAYOUT_VECTORS
+            .lines()
+            .filter(|line| !line.starts_with('#') && !line.is_empty())
+        {
+            let fields = line.split(' ').collect::<Vec<_>>();
+            let scalars = fields[1..6]
+                .iter()
+                .map(|scalar| Fr::from_str(scalar).unwrap())
+                .collect::<Vec<_>>();
+            let (c1, c2, sk, w, r) = (scalars[0], scalars[1], scalars[2], scalars[3], scalars[4]);
+
+            let masked = el_gamal::Ciphertext(multiple(&g, &c1), multiple(&g, &c2));
+            let token = el_gamal::Plaintext(multiple(&g, &(c1 * sk)));
+            let pk = multiple(&g, &sk);
+            let proof =
+                proof_from_components(&multiple(&g, &w), &multiple(&g, &(c1 * w)), &r).unwrap();
+
+            let bytes = encode_reveal_verification(&masked, &token, &pk, &proof).unwrap();
+            assert_eq!(to_hex(&bytes), fields[6], "case {}", fields[0]);
+
+            let decoded = decode_reveal_verification(&bytes).unwrap();
+            assert_eq!(decoded.masked_card, masked, "case {}", fields[0]);
+            assert_eq!(decoded.token, token, "case {}", fields[0]);
+            assert_eq!(decoded.pk, pk, "case {}", fields[0]);
+        }
+    }
+
+    #[test]
+    fn test_reveal_verification_vectors() {
+        // Written by `cargo run --example bn254_fixtures --features bn254`
+        let rng = &mut test_rng();
+        let parameters = CardProtocol::setup(rng, 4, 13).unwrap();
+
+        let (mut valid, mut invalid) = (0, 0);
+        for line in REVEAL_VERIFICATION_VECTORS
+            .lines()
+            .filter(|line| !line.starts_with('#') && !line.is_empty())
+        {
+            let fields = line.split(' ').collect::<Vec<_>>();
+            let bytes = from_hex(fields[2]);
+            let decoded = decode_reveal_verification(&bytes).unwrap();
+            assert_eq!(
+                encode_reveal_verification(
+                    &decoded.masked_card,
+                    &decoded.token,
+                    &decoded.pk,
+                    &decoded.proof
+                )
+                .unwrap(),
+                bytes,
+                "case {}",
+                fields[0]
+            );
+
+            let verified = CardProtocol::verify_reveal(
+                &parameters,
+                b"",
+                &decoded.pk,
+                &decoded.token,
+                &decoded.masked_card,
+                &decoded.proof,
+            );
+            match fields[1] {
+                "valid" => {
+                    assert_eq!(Ok(()), verified, "case {}", fields[0]);
+                    valid += 1;
+                }
+                "invalid" => {
+                    assert!(verified.is_err(), "case {}", fields[0]);
+                    invalid += 1;
+                }
+                expected => panic!("unknown expectation {}", expected),
+            }
+        }
+
+        assert!(valid > 0 && invalid > 0);
+    }
+}
@@ -0,0 +1,5 @@
+//! Encodings of protocol values for verifiers that do not run this crate, such as contracts.
+
+pub mod cairo;
+#[cfg(feature = "bn254")]
+pub mod evm;
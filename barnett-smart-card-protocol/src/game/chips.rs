@@ -0,0 +1,195 @@
+//! Hidden bets.
+//!
+//! In variants where bets stay hidden until the showdown, players commit to their bet amounts
+//! with Pedersen commitments and open them later. Commitments add up homomorphically, so the pot
+//! is committed as the sum of the bets without anybody learning them, and once the bets are
+//! opened, [`verify_conservation`] checks that the opened amounts account for the whole pot.
+//!
+//! Commitments hide and bind scalars, not integers, so a sum is only meaningful as long as it does
+//! not wrap around the order of the scalar field. Amounts are `u64` and openings are summed as
+//! integers that must fit in a `u64` as well, otherwise the check fails with `AmountOverflow`. As
+//! every supported scalar field has far more than 2^64 elements, a commitment to the sum then
+//! matches the pot only if the amounts add up as integers. Nothing else limits the amounts: a
+//! bet larger than the stack of the player, or a pot of zero, is for the caller to reject.
+//!
+//! The commitment key is derived from a public label, so nobody knows a discrete log relation
+//! between its generators that would let them open a commitment to two amounts.
+
+use crate::crypto_primitives::commitment::HomomorphicCommitment;
+use crate::error::{CardProtocolError, InputError, VerificationError};
+
+use ark_ec::ProjectiveCurve;
+use ark_ff::{UniformRand, Zero};
+use ark_serialize::{CanonicalDeserialize, CanonicalSerialize};
+use ark_std::rand::{CryptoRng, Rng};
+use proof_essentials::vector_commitment::pedersen::{self, PedersenCommitment};
+use proof_essentials::vector_commitment::HomomorphicCommitmentScheme;
+
+pub use proof_essentials::vector_commitment::pedersen::Commitment;
+
+const CHIPS_COMMIT_KEY_LABEL: &'static [u8] = b"Chips";
+
+/// The amount committed to and the randomness it was committed under.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, CanonicalSerialize, CanonicalDeserialize)]
+pub struct Opening<C: ProjectiveCurve> {
+    pub amount: u64,
+    pub randomness: C::ScalarField,
+}
+
+fn commit_key<C: ProjectiveCurve>() -> Result<pedersen::CommitKey<C>, CardProtocolError> {
+    PedersenCommitment::<C>::derive_commit_key(CHIPS_COMMIT_KEY_LABEL, 1)
+}
+
+fn commit<C: ProjectiveCurve>(
+    amount: u64,
+    randomness: C::ScalarField,
+) -> Result<Commitment<C>, CardProtocolError> {
+    let commitment = PedersenCommitment::<C>::commit(
+        &commit_key()?,
+        &vec![C::ScalarField::from(amount)],
+        randomness,
+    )?;
+
+    Ok(commitment)
+}
+
+/// Commit to a bet of `amount` chips.
+pub fn commit_bet<C: ProjectiveCurve, R: Rng + CryptoRng>(
+    rng: &mut R,
+    amount: u64,
+) -> Result<(Commitment<C>, Opening<C>), CardProtocolError> {
+    let randomness = C::ScalarField::rand(rng);
+
+    Ok((commit(amount, randomness)?, Opening { amount, randomness }))
+}
+
+/// Commitment to the pot holding the committed bets.
+pub fn aggregate_pot<C: ProjectiveCurve>(
+    bets: &[Commitment<C>],
+) -> Result<Commitment<C>, CardProtocolError> {
+    bets.iter()
+        .try_fold(commit(0, C::ScalarField::zero())?, |pot, bet| {
+            Ok(PedersenCommitment::<C>::add(&pot, bet))
+        })
+}
+
+/// Check that `opening` opens the bet `commitment`.
+pub fn verify_opening<C: ProjectiveCurve>(
+    commitment: &Commitment<C>,
+    opening: &Opening<C>,
+) -> Result<(), CardProtocolError> {
+    if commit(opening.amount, opening.randomness)? != *commitment {
+        return Err(VerificationError::InvalidOpening.into());
+    }
+
+    Ok(())
+}
+
+/// Check that the opened amounts add up to the committed pot. Each opening should also be checked
+/// against its own bet with [`verify_opening`], to attribute a mismatch to a player.
+pub fn verify_conservation<C: ProjectiveCurve>(
+    pot_commitment: &Commitment<C>,
+    openings: &[Opening<C>],
+) -> Result<(), CardProtocolError> {
+    let amount = openings
+        .iter()
+        .try_fold(0u64, |sum, opening| sum.checked_add(opening.amount))
+        .ok_or(CardProtocolError::InvalidInput(InputError::AmountOverflow))?;
+    let randomness = openings
+        .iter()
+        .map(|opening| opening.randomness)
+        .sum::<C::ScalarField>();
+
+    if commit(amount, randomness)? != *pot_commitment {
+        return Err(VerificationError::PotMismatch.into());
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use super::{aggregate_pot, commit_bet, verify_conservation, verify_opening, Opening};
+    use crate::crypto_primitives::utils::rand::test_rng;
+    use crate::error::{CardProtocolError, InputError, VerificationError};
+
+    use ark_ff::Zero;
+
+    // Choose elliptic curve setting
+    type Curve = starknet_curve::Projective;
+    type Scalar = starknet_curve::Fr;
+
+    #[test]
+    fn test_pot_conservation() {
+        let rng = &mut test_rng();
+        let amounts = [20u64, 50, 50, 0];
+
+        let (bets, openings): (Vec<_>, Vec<_>) = amounts
+            .iter()
+            .map(|&amount| commit_bet::<Curve, _>(rng, amount).unwrap())
+            .unzip();
+        let pot = aggregate_pot(&bets).unwrap();
+
+        for (bet, opening) in bets.iter().zip(openings.iter()) {
+            assert_eq!(Ok(()), verify_opening(bet, opening));
+        }
+        assert_eq!(Ok(()), verify_conservation(&pot, &openings));
+
+        // Player 1 committed 50 chips but claims to have bet 30
+        let mut forged = openings.clone();
+        forged[1].amount = 30;
+        assert_eq!(
+            verify_opening(&bets[1], &forged[1]),
+            Err(CardProtocolError::VerificationFailed(
+                VerificationError::InvalidOpening
+            ))
+        );
+        assert_eq!(
+            verify_conservation(&pot, &forged),
+            Err(CardProtocolError::VerificationFailed(
+                VerificationError::PotMismatch
+            ))
+        );
+
+        // Moving chips from one opening to another keeps the total but not the bets
+        forged[2].amount = 70;
+        assert_eq!(Ok(()), verify_conservation(&pot, &forged));
+        assert_eq!(
+            verify_opening(&bets[2], &forged[2]),
+            Err(CardProtocolError::VerificationFailed(
+                VerificationError::InvalidOpening
+            ))
+        );
+
+        // Every bet has to be opened
+        assert_eq!(
+            verify_conservation(&pot, &openings[..3]),
+            Err(CardProtocolError::VerificationFailed(
+                VerificationError::PotMismatch
+            ))
+        );
+        assert_eq!(
+            Ok(()),
+            verify_conservation(&aggregate_pot::<Curve>(&[]).unwrap(), &[])
+        );
+    }
+
+    #[test]
+    fn test_overflowing_openings() {
+        let openings = [
+            Opening::<Curve> {
+                amount: u64::MAX,
+                randomness: Scalar::zero(),
+            },
+            Opening::<Curve> {
+                amount: 1,
+                randomness: Scalar::zero(),
+            },
+        ];
+
+        assert_eq!(
+            verify_conservation(&aggregate_pot(&[]).unwrap(), &openings),
+            Err(CardProtocolError::InvalidInput(InputError::AmountOverflow))
+        );
+    }
+}
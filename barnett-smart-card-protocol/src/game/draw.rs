@@ -0,0 +1,224 @@
+//! The draw of five-card draw: each player in turn discards some of their cards and receives as
+//! many replacements from the top of the remaining deck.
+//!
+//! The position of the top of the deck is part of the shared [`DrawRound`], so every player agrees
+//! on which positions replace which discards without further messages, and anyone can check that
+//! a replacement came from the right place. Discarded cards stay masked for good.
+
+use crate::discrete_log_cards::deal::DealPlan;
+use crate::error::{CardProtocolError, InputError};
+
+/// Reveal tokens needed to deal the card at `index` to `owner`: one from each player in `from`.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct RevealRequest {
+    pub index: usize,
+    pub owner: usize,
+    pub from: Vec<usize>,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct DrawRound {
+    num_players: usize,
+    num_cards: usize,
+    /// Position of the next undealt card
+    next: usize,
+    /// The player whose turn it is to draw
+    turn: usize,
+    /// Positions of the cards in each player's hand
+    hands: Vec<Vec<usize>>,
+    discarded: Vec<usize>,
+    burned: Vec<usize>,
+}
+
+impl DrawRound {
+    /// Start the draw after dealing `plan` from a deck of `num_cards` cards. Replacements are
+    /// dealt from the first position `plan` does not use.
+    pub fn new(plan: &DealPlan, num_cards: usize) -> Result<Self, CardProtocolError> {
+        plan.validate(num_cards)?;
+
+        Ok(Self {
+            num_players: plan.num_players(),
+            num_cards,
+            next: plan.num_dealt(),
+            turn: 0,
+            hands: plan.hands(),
+            discarded: Vec::new(),
+            burned: plan.burned(),
+        })
+    }
+
+    /// Position of the next undealt card.
+    pub fn next_position(&self) -> usize {
+        self.next
+    }
+
+    /// The player whose turn it is to draw, or `None` once everyone has drawn.
+    pub fn turn(&self) -> Option<usize> {
+        (self.turn < self.num_players).then(|| self.turn)
+    }
+
+    /// Positions of the cards `seat` currently holds.
+    pub fn hand(&self, seat: usize) -> &[usize] {
+        self.hands.get(seat).map_or(&[], Vec::as_slice)
+    }
+
+    pub fn discarded(&self) -> &[usize] {
+        &self.discarded
+    }
+
+    /// Check that the card at `index` may be revealed: discarded and burned cards never are.
+    pub fn check_revealable(&self, index: usize) -> Result<(), CardProtocolError> {
+        if self.discarded.contains(&index) {
+            return Err(InputError::DiscardedCard(index).into());
+        }
+
+        if self.burned.contains(&index) {
+            return Err(InputError::BurnedCard(index).into());
+        }
+
+        Ok(())
+    }
+
+    /// Discard the cards of `seat` at `discarded_indices` and replace them with the next undealt
+    /// cards, in order. Standing pat is drawing with no discards. Returns the reveal tokens needed
+    /// to deal the replacements to `seat`.
+    pub fn draw_replace(
+        &mut self,
+        seat: usize,
+        discarded_indices: &[usize],
+    ) -> Result<Vec<RevealRequest>, CardProtocolError> {
+        if seat >= self.num_players {
+            return Err(InputError::UnknownPlayer(seat).into());
+        }
+
+        if self.turn() != Some(seat) {
+            return Err(InputError::NotYourTurn(seat).into());
+        }
+
+        let mut hand = self.hands[seat].clone();
+        for &index in discarded_indices {
+            match hand.iter().position(|&card| card == index) {
+                Some(position) => hand.remove(position),
+                None => return Err(InputError::CardNotInHand { seat, index }.into()),
+            };
+        }
+
+        let next = self.next + discarded_indices.len();
+        if next > self.num_cards {
+            return Err(InputError::InvalidDeal(self.num_cards).into());
+        }
+
+        let replacements = (self.next..next).collect::<Vec<_>>();
+        hand.extend_from_slice(&replacements);
+
+        self.hands[seat] = hand;
+        self.discarded.extend_from_slice(discarded_indices);
+        self.next = next;
+        self.turn += 1;
+
+        let from = (0..self.num_players)
+            .filter(|&player| player != seat)
+            .collect::<Vec<_>>();
+
+        Ok(replacements
+            .into_iter()
+            .map(|index| RevealRequest {
+                index,
+                owner: seat,
+                from: from.clone(),
+            })
+            .collect())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{DrawRound, RevealRequest};
+    use crate::discrete_log_cards::deal::DealPlan;
+    use crate::error::{CardProtocolError, InputError};
+
+    use std::collections::HashSet;
+
+    #[test]
+    fn test_draw_replace() {
+        let num_cards = 52;
+
+        // Five cards each, dealt one at a time around the table
+        let plan = DealPlan::new(2, 5, &[], 0).unwrap();
+        let mut round = DrawRound::new(&plan, num_cards).unwrap();
+        assert_eq!(round.next_position(), 10);
+
+        // Player 1 cannot draw before player 0, nor discard a card they do not hold
+        assert_eq!(
+            round.draw_replace(1, &[1]),
+            Err(CardProtocolError::InvalidInput(InputError::NotYourTurn(1)))
+        );
+        assert_eq!(
+            round.draw_replace(0, &[1]),
+            Err(CardProtocolError::InvalidInput(InputError::CardNotInHand {
+                seat: 0,
+                index: 1
+            }))
+        );
+        assert_eq!(
+            round.draw_replace(0, &[0, 0]),
+            Err(CardProtocolError::InvalidInput(InputError::CardNotInHand {
+                seat: 0,
+                index: 0
+            }))
+        );
+
+        let requests = round.draw_replace(0, &[0, 4, 8]).unwrap();
+        assert_eq!(
+            requests,
+            (10..13)
+                .map(|index| RevealRequest {
+                    index,
+                    owner: 0,
+                    from: vec![1]
+                })
+                .collect::<Vec<_>>()
+        );
+        assert_eq!(round.hand(0), &[2, 6, 10, 11, 12]);
+
+        let requests = round.draw_replace(1, &[9]).unwrap();
+        assert_eq!(requests.len(), 1);
+        assert_eq!(requests[0].index, 13);
+        assert_eq!(round.hand(1), &[1, 3, 5, 7, 13]);
+        assert_eq!(round.turn(), None);
+        assert_eq!(round.next_position(), 14);
+
+        // No position is dealt twice, and discards are never revealed
+        let dealt = round
+            .hand(0)
+            .iter()
+            .chain(round.hand(1))
+            .chain(round.discarded())
+            .collect::<Vec<_>>();
+        assert_eq!(dealt.iter().collect::<HashSet<_>>().len(), dealt.len());
+        assert_eq!(dealt.len(), 14);
+
+        for &index in round.discarded() {
+            assert_eq!(
+                round.check_revealable(index),
+                Err(CardProtocolError::InvalidInput(InputError::DiscardedCard(
+                    index
+                )))
+            );
+        }
+        assert_eq!(Ok(()), round.check_revealable(13));
+    }
+
+    #[test]
+    fn test_draw_past_the_end_of_the_deck() {
+        // Ten cards each for five players leave two undealt cards
+        let plan = DealPlan::new(5, 10, &[], 0).unwrap();
+        let mut round = DrawRound::new(&plan, 52).unwrap();
+
+        round.draw_replace(0, &[0, 5]).unwrap();
+        assert_eq!(
+            round.draw_replace(1, &[1]),
+            Err(CardProtocolError::InvalidInput(InputError::InvalidDeal(52)))
+        );
+    }
+}
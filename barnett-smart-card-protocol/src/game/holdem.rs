@@ -0,0 +1,953 @@
+//! A Texas Hold'em hand on top of `DLCards`.
+//!
+//! [`HoldemTable`] runs the rounds every integrator would otherwise rebuild. Players publish their
+//! keys and prove ownership of them, then shuffle the standard deck in seat order. Hole cards are
+//! dealt by revealing them to their owner only, and community cards are revealed to everyone street
+//! by street. The hands still in play are opened at showdown. Every message is verified with the
+//! corresponding protocol call before it changes the state. Betting stays with the application: the
+//! table asks for a betting round after each deal and is told which players folded.
+//!
+//! The deck is laid out by `DealPlan::texas_holdem`, as in a live game: hole cards are dealt one at
+//! a time around the table, then a card is burned before each of the flop, turn and river.
+
+use crate::crypto_primitives::utils::ct::ct_position;
+use crate::crypto_primitives::utils::format::{Compact, Hex, HexBytes, IndexedTokens};
+use crate::discrete_log_cards::deal::DealPlan;
+use crate::discrete_log_cards::dkg::KeyOwnershipProof;
+use crate::discrete_log_cards::encoding::CardLookup;
+use crate::discrete_log_cards::reveal::reveal_token;
+use crate::discrete_log_cards::{
+    starting_deck, Card, DLCards, MaskedCard, Parameters, PlayerSecretKey, PublicKey, RevealToken,
+};
+use crate::error::{check_dimension, CardProtocolError, Dimension, InputError};
+use crate::playing_card::ClassicPlayingCard;
+use crate::{BarnettSmartProtocol, KeyListDigest, Reveal};
+
+use ark_ec::ProjectiveCurve;
+use ark_ff::{ToBytes, Zero};
+use ark_std::fmt;
+use proof_essentials::homomorphic_encryption::el_gamal::ElGamal;
+use proof_essentials::vector_commitment::pedersen::PedersenCommitment;
+use proof_essentials::zkp::{arguments::shuffle, proofs::chaum_pedersen_dl_equality};
+
+/// Number of cards in the standard deck the table plays with.
+pub const DECK_SIZE: usize = 52;
+
+pub type ShuffleProof<C> =
+    shuffle::proof::Proof<<C as ProjectiveCurve>::ScalarField, ElGamal<C>, PedersenCommitment<C>>;
+
+pub type RevealProof<C> = chaum_pedersen_dl_equality::proof::Proof<C>;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Street {
+    Preflop,
+    Flop,
+    Turn,
+    River,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum TablePhase {
+    /// Players publish their public keys
+    KeyExchange,
+    /// Players prove ownership of their key for the complete key list
+    KeyProofs,
+    /// The player in this seat shuffles next
+    Shuffle(usize),
+    /// Reveal tokens for the cards of this street are collected: hole cards before the flop,
+    /// community cards afterwards
+    Deal(Street),
+    /// The application runs the betting round of this street
+    Betting(Street),
+    /// Players still in the hand open their hole cards
+    Showdown,
+    /// After the hand, players open the burned cards for audit
+    OpenBurns,
+    Done,
+}
+
+/// A message broadcast by a player.
+#[derive(Clone)]
+pub enum TableMessage<C: ProjectiveCurve> {
+    PublicKey(PublicKey<C>),
+    KeyProof(KeyOwnershipProof<C>),
+    Shuffle {
+        deck: Vec<MaskedCard<C>>,
+        proof: ShuffleProof<C>,
+    },
+    /// Reveal tokens, each for the card at the given deck index
+    RevealTokens(Vec<(usize, RevealToken<C>, RevealProof<C>)>),
+}
+
+/// Something the table is waiting for.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Action {
+    /// Broadcast a public key
+    SendPublicKey { player: usize },
+    /// Prove ownership of the key at position `player` of the list with this digest
+    ProveKeyOwnership {
+        player: usize,
+        key_list_digest: KeyListDigest,
+    },
+    /// Shuffle and remask the current deck
+    Shuffle { player: usize },
+    /// Broadcast reveal tokens for the cards at these deck indices
+    Reveal { player: usize, indices: Vec<usize> },
+    /// Run the betting round of `street` among the `active` players, then call `end_betting`
+    Bet { street: Street, active: Vec<usize> },
+}
+
+impl<C: ProjectiveCurve> fmt::Debug for TableMessage<C> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::PublicKey(pk) => f.debug_tuple("PublicKey").field(&Hex(pk)).finish(),
+            Self::KeyProof(proof) => f.debug_tuple("KeyProof").field(&Compact(proof)).finish(),
+            Self::Shuffle { deck, proof } => f
+                .debug_struct("Shuffle")
+                .field("deck", &Compact(deck))
+                .field("proof", &Compact(proof))
+                .finish(),
+            Self::RevealTokens(tokens) => f
+                .debug_tuple("RevealTokens")
+                .field(&IndexedTokens(tokens))
+                .finish(),
+        }
+    }
+}
+
+/// Local view of a hand of Texas Hold'em. Every player feeds it the same messages in the same order
+/// and therefore reaches the same state.
+pub struct HoldemTable<C: ProjectiveCurve, B> {
+    phase: TablePhase,
+    context: Vec<u8>,
+    player_info: Vec<B>,
+    plan: DealPlan,
+    keys: Vec<Option<PublicKey<C>>>,
+    key_list_digest: Option<KeyListDigest>,
+    proven: Vec<bool>,
+    aggregate_key: Option<PublicKey<C>>,
+    lookup: CardLookup<C>,
+    deck: Vec<MaskedCard<C>>,
+    /// Verified reveal tokens, by deck index and then by player
+    tokens: Vec<Vec<Option<RevealToken<C>>>>,
+    folded: Vec<bool>,
+    /// Number of streets of community cards dealt so far, including the one being dealt, and thus
+    /// of burns
+    streets_dealt: usize,
+    community: Vec<ClassicPlayingCard>,
+    hands: Vec<Option<Vec<ClassicPlayingCard>>>,
+    burned_cards: Option<Vec<ClassicPlayingCard>>,
+}
+
+/// Only the public keys and the cards opened so far are printed, not the deck or the tokens.
+impl<C: ProjectiveCurve, B> fmt::Debug for HoldemTable<C, B> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let keys = self.keys.iter().map(|key| key.as_ref().map(Hex));
+
+        f.debug_struct("HoldemTable")
+            .field("phase", &self.phase)
+            .field("context", &HexBytes(&self.context))
+            .field("keys", &keys.collect::<Vec<_>>())
+            .field("aggregate_key", &self.aggregate_key.as_ref().map(Hex))
+            .field("folded", &self.folded)
+            .field("community", &self.community)
+            .field("hands", &self.hands)
+            .finish_non_exhaustive()
+    }
+}
+
+impl<C: ProjectiveCurve, B: ToBytes + Clone> HoldemTable<C, B> {
+    /// Seat the players described by `player_info`. A player's index in this vector is their seat
+    /// and the index used in every message. Proofs are checked against the session `context`.
+    pub fn new(
+        pp: &Parameters<C>,
+        context: &[u8],
+        player_info: Vec<B>,
+    ) -> Result<Self, CardProtocolError> {
+        let num_of_players = player_info.len();
+        let plan = DealPlan::texas_holdem(num_of_players)?;
+        if num_of_players < 2 || plan.validate(DECK_SIZE).is_err() {
+            return Err(InputError::InvalidPlayerCount(num_of_players).into());
+        }
+
+        check_dimension(Dimension::Deck, DECK_SIZE, pp.num_cards())?;
+
+        Ok(Self {
+            phase: TablePhase::KeyExchange,
+            context: context.to_vec(),
+            player_info,
+            plan,
+            keys: vec![None; num_of_players],
+            key_list_digest: None,
+            proven: vec![false; num_of_players],
+            aggregate_key: None,
+            lookup: CardLookup::standard()?,
+            deck: Vec::new(),
+            tokens: vec![vec![None; num_of_players]; DECK_SIZE],
+            folded: vec![false; num_of_players],
+            streets_dealt: 0,
+            community: Vec::new(),
+            hands: vec![None; num_of_players],
+            burned_cards: None,
+        })
+    }
+
+    pub fn phase(&self) -> TablePhase {
+        self.phase
+    }
+
+    pub fn num_of_players(&self) -> usize {
+        self.player_info.len()
+    }
+
+    pub fn aggregate_key(&self) -> Option<PublicKey<C>> {
+        self.aggregate_key
+    }
+
+    /// The current deck: the starting deck before the first shuffle, the output of the last
+    /// shuffle afterwards.
+    pub fn deck(&self) -> &[MaskedCard<C>] {
+        &self.deck
+    }
+
+    /// Deck indices of the hole cards of `player`.
+    pub fn hole_card_indices(&self, player: usize) -> Vec<usize> {
+        self.plan.hole_cards(player).to_vec()
+    }
+
+    /// Deck indices of the community cards dealt on `street`.
+    pub fn community_indices(&self, street: Street) -> Vec<usize> {
+        match street {
+            Street::Preflop => Vec::new(),
+            Street::Flop => self.plan.community(0).to_vec(),
+            Street::Turn => self.plan.community(1).to_vec(),
+            Street::River => self.plan.community(2).to_vec(),
+        }
+    }
+
+    pub fn deal_plan(&self) -> &DealPlan {
+        &self.plan
+    }
+
+    /// Community cards opened so far.
+    pub fn community_cards(&self) -> &[ClassicPlayingCard] {
+        &self.community
+    }
+
+    /// Hole cards `player` opened at showdown.
+    pub fn shown_hand(&self, player: usize) -> Option<&[ClassicPlayingCard]> {
+        self.hands.get(player)?.as_deref()
+    }
+
+    /// Deck indices of the cards burned so far. A card is burned before each street is dealt, so
+    /// a hand that ends early burns fewer cards.
+    pub fn burned_indices(&self) -> Vec<usize> {
+        (0..self.streets_dealt)
+            .flat_map(|street| self.plan.burns(street).to_vec())
+            .collect()
+    }
+
+    /// Burned cards opened for audit.
+    pub fn burned_cards(&self) -> Option<&[ClassicPlayingCard]> {
+        self.burned_cards.as_deref()
+    }
+
+    /// Players who have not folded.
+    pub fn active_players(&self) -> Vec<usize> {
+        (0..self.num_of_players())
+            .filter(|&player| !self.folded[player])
+            .collect()
+    }
+
+    /// Everything the table is waiting for before it can move on.
+    pub fn next_actions(&self) -> Vec<Action> {
+        let players = 0..self.num_of_players();
+        match self.phase {
+            TablePhase::KeyExchange => players
+                .filter(|&player| self.keys[player].is_none())
+                .map(|player| Action::SendPublicKey { player })
+                .collect(),
+            TablePhase::KeyProofs => match self.key_list_digest {
+                Some(key_list_digest) => players
+                    .filter(|&player| !self.proven[player])
+                    .map(|player| Action::ProveKeyOwnership {
+                        player,
+                        key_list_digest,
+                    })
+                    .collect(),
+                None => Vec::new(),
+            },
+            TablePhase::Shuffle(player) => vec![Action::Shuffle { player }],
+            TablePhase::Deal(_) | TablePhase::Showdown | TablePhase::OpenBurns => players
+                .filter_map(|player| {
+                    let indices = self.due_reveals(player);
+                    (!indices.is_empty()).then(|| Action::Reveal { player, indices })
+                })
+                .collect(),
+            TablePhase::Betting(street) => vec![Action::Bet {
+                street,
+                active: self.active_players(),
+            }],
+            TablePhase::Done => Vec::new(),
+        }
+    }
+
+    /// Verify and apply a message broadcast by `player`. A rejected message leaves the table
+    /// unchanged.
+    pub fn receive(
+        &mut self,
+        pp: &Parameters<C>,
+        player: usize,
+        message: TableMessage<C>,
+    ) -> Result<(), CardProtocolError> {
+        if player >= self.num_of_players() {
+            return Err(InputError::UnknownPlayer(player).into());
+        }
+
+        match (self.phase, message) {
+            (TablePhase::KeyExchange, TableMessage::PublicKey(pk)) => self.receive_key(player, pk),
+            (TablePhase::KeyProofs, TableMessage::KeyProof(proof)) => {
+                self.receive_key_proof(pp, player, proof)
+            }
+            (TablePhase::Shuffle(shuffler), TableMessage::Shuffle { deck, proof }) => {
+                if player != shuffler {
+                    return Err(InputError::NotYourTurn(player).into());
+                }
+                self.receive_shuffle(pp, deck, proof)
+            }
+            (TablePhase::Deal(_), TableMessage::RevealTokens(tokens))
+            | (TablePhase::Showdown, TableMessage::RevealTokens(tokens))
+            | (TablePhase::OpenBurns, TableMessage::RevealTokens(tokens)) => {
+                self.receive_tokens(pp, player, tokens)
+            }
+            _ => Err(CardProtocolError::InvalidInput(InputError::UnexpectedPhase)),
+        }
+    }
+
+    /// Close the betting round of `street`. Folded players take no further part in the hand but
+    /// still help reveal the community cards, which are masked under their key too. If a single
+    /// player is left the hand ends without a showdown.
+    pub fn end_betting(
+        &mut self,
+        street: Street,
+        folded: &[usize],
+    ) -> Result<(), CardProtocolError> {
+        if self.phase != TablePhase::Betting(street) {
+            return Err(InputError::UnexpectedPhase.into());
+        }
+
+        if let Some(&player) = folded
+            .iter()
+            .find(|&&player| player >= self.num_of_players())
+        {
+            return Err(InputError::UnknownPlayer(player).into());
+        }
+
+        for &player in folded {
+            self.folded[player] = true;
+        }
+
+        self.phase = if self.active_players().len() <= 1 {
+            TablePhase::Done
+        } else {
+            match street {
+                Street::Preflop => TablePhase::Deal(Street::Flop),
+                Street::Flop => TablePhase::Deal(Street::Turn),
+                Street::Turn => TablePhase::Deal(Street::River),
+                Street::River => TablePhase::Showdown,
+            }
+        };
+
+        if let TablePhase::Deal(_) = self.phase {
+            self.streets_dealt += 1;
+        }
+
+        Ok(())
+    }
+
+    /// Once the hand is over, ask every player to reveal the burned cards, so anyone can check
+    /// that they were neither revealed nor used during the hand. This step is optional.
+    pub fn open_burns(&mut self) -> Result<(), CardProtocolError> {
+        if self.phase != TablePhase::Done || self.burned_cards.is_some() {
+            return Err(InputError::UnexpectedPhase.into());
+        }
+
+        if self.burned_indices().is_empty() {
+            self.burned_cards = Some(Vec::new());
+        } else {
+            self.phase = TablePhase::OpenBurns;
+        }
+
+        Ok(())
+    }
+
+    /// Open the hole cards of `player` with their own secret key, once every other player has
+    /// revealed them. Only the owner can do this; the cards are looked up in constant time.
+    pub fn open_own_hole_cards(
+        &self,
+        player: usize,
+        sk: &PlayerSecretKey<C>,
+    ) -> Result<Vec<ClassicPlayingCard>, CardProtocolError> {
+        if player >= self.num_of_players() {
+            return Err(InputError::UnknownPlayer(player).into());
+        }
+
+        self.hole_card_indices(player)
+            .into_iter()
+            .map(|index| {
+                let mut tokens = self.tokens[index].clone();
+                tokens[player] = Some(reveal_token(sk, &self.deck[index]));
+                let card = self.open_card(index, &tokens)?;
+
+                ct_position(&card, self.lookup.cards())?
+                    .and_then(ClassicPlayingCard::from_standard_position)
+                    .ok_or(CardProtocolError::InvalidInput(InputError::UnknownCard))
+            })
+            .collect()
+    }
+
+    fn receive_key(&mut self, player: usize, pk: PublicKey<C>) -> Result<(), CardProtocolError> {
+        if self.keys[player].is_some() {
+            return Err(InputError::DuplicateMessage(player).into());
+        }
+
+        if pk.is_zero() {
+            return Err(InputError::IdentityPublicKey(player).into());
+        }
+
+        if self.keys.iter().flatten().any(|other| *other == pk) {
+            return Err(InputError::DuplicatePlayerKey { index: player }.into());
+        }
+
+        self.keys[player] = Some(pk);
+
+        if self.keys.iter().all(Option::is_some) {
+            let keys = self.keys.iter().flatten().copied().collect::<Vec<_>>();
+            self.key_list_digest = Some(DLCards::<C>::key_list_digest(&keys)?);
+            self.phase = TablePhase::KeyProofs;
+        }
+
+        Ok(())
+    }
+
+    fn receive_key_proof(
+        &mut self,
+        pp: &Parameters<C>,
+        player: usize,
+        proof: KeyOwnershipProof<C>,
+    ) -> Result<(), CardProtocolError> {
+        if self.proven[player] {
+            return Err(InputError::DuplicateMessage(player).into());
+        }
+
+        let key_list_digest = self
+            .key_list_digest
+            .ok_or(CardProtocolError::InvalidInput(InputError::UnexpectedPhase))?;
+        let pk = self.keys[player].ok_or(CardProtocolError::InvalidInput(
+            InputError::UnknownPlayer(player),
+        ))?;
+
+        DLCards::<C>::verify_key_ownership(
+            pp,
+            &self.context,
+            &pk,
+            &self.player_info[player],
+            player,
+            &key_list_digest,
+            &proof,
+        )?;
+
+        self.proven[player] = true;
+
+        if self.proven.iter().all(|&proven| proven) {
+            let aggregate_key = self
+                .keys
+                .iter()
+                .flatten()
+                .fold(PublicKey::<C>::zero(), |acc, pk| acc + *pk);
+
+            self.deck = starting_deck(pp, &aggregate_key, self.lookup.cards())?;
+            self.aggregate_key = Some(aggregate_key);
+            self.phase = TablePhase::Shuffle(0);
+        }
+
+        Ok(())
+    }
+
+    fn receive_shuffle(
+        &mut self,
+        pp: &Parameters<C>,
+        deck: Vec<MaskedCard<C>>,
+        proof: ShuffleProof<C>,
+    ) -> Result<(), CardProtocolError> {
+        let aggregate_key = self
+            .aggregate_key
+            .ok_or(CardProtocolError::InvalidInput(InputError::UnexpectedPhase))?;
+
+        check_dimension(Dimension::Deck, DECK_SIZE, deck.len())?;
+
+        DLCards::<C>::verify_shuffle(pp, &self.context, &aggregate_key, &self.deck, &deck, &proof)?;
+
+        self.deck = deck;
+        self.phase = match self.phase {
+            TablePhase::Shuffle(shuffler) if shuffler + 1 < self.num_of_players() => {
+                TablePhase::Shuffle(shuffler + 1)
+            }
+            _ => TablePhase::Deal(Street::Preflop),
+        };
+
+        Ok(())
+    }
+
+    fn receive_tokens(
+        &mut self,
+        pp: &Parameters<C>,
+        player: usize,
+        tokens: Vec<(usize, RevealToken<C>, RevealProof<C>)>,
+    ) -> Result<(), CardProtocolError> {
+        let pk = self.keys[player].ok_or(CardProtocolError::InvalidInput(
+            InputError::UnknownPlayer(player),
+        ))?;
+        let mut due = self.due_reveals(player);
+
+        // Check every token before storing any, so that a rejected message changes nothing
+        for (index, token, proof) in &tokens {
+            if self.phase != TablePhase::OpenBurns && self.plan.is_burned(*index) {
+                return Err(InputError::BurnedCard(*index).into());
+            }
+
+            match due.iter().position(|due_index| due_index == index) {
+                Some(position) => due.swap_remove(position),
+                None => return Err(InputError::UnexpectedReveal(*index).into()),
+            };
+
+            DLCards::<C>::verify_reveal(pp, &self.context, &pk, token, &self.deck[*index], proof)?;
+        }
+
+        for (index, token, _) in tokens {
+            self.tokens[index][player] = Some(token);
+        }
+
+        if (0..self.num_of_players()).all(|player| self.due_reveals(player).is_empty()) {
+            self.complete_deal()?;
+        }
+
+        Ok(())
+    }
+
+    /// Open the cards of the completed deal and move on to the next betting round.
+    fn complete_deal(&mut self) -> Result<(), CardProtocolError> {
+        match self.phase {
+            TablePhase::Deal(street) => {
+                for index in self.community_indices(street) {
+                    let card = self.open_public(index)?;
+                    self.community.push(card);
+                }
+
+                self.phase = TablePhase::Betting(street);
+            }
+            TablePhase::Showdown => {
+                for player in self.active_players() {
+                    let hand = self
+                        .hole_card_indices(player)
+                        .into_iter()
+                        .map(|index| self.open_public(index))
+                        .collect::<Result<Vec<_>, _>>()?;
+                    self.hands[player] = Some(hand);
+                }
+
+                self.phase = TablePhase::Done;
+            }
+            TablePhase::OpenBurns => {
+                let burned_cards = self
+                    .burned_indices()
+                    .into_iter()
+                    .map(|index| self.open_public(index))
+                    .collect::<Result<Vec<_>, _>>()?;
+                self.burned_cards = Some(burned_cards);
+
+                self.phase = TablePhase::Done;
+            }
+            _ => return Err(InputError::UnexpectedPhase.into()),
+        }
+
+        Ok(())
+    }
+
+    /// Deck indices `player` still has to reveal in the current phase.
+    fn due_reveals(&self, player: usize) -> Vec<usize> {
+        let indices = match self.phase {
+            TablePhase::Deal(Street::Preflop) => (0..self.num_of_players())
+                .filter(|&owner| owner != player)
+                .flat_map(|owner| self.hole_card_indices(owner))
+                .collect(),
+            TablePhase::Deal(street) => self.community_indices(street),
+            TablePhase::Showdown if !self.folded[player] => self.hole_card_indices(player),
+            TablePhase::OpenBurns => self.burned_indices(),
+            _ => Vec::new(),
+        };
+
+        indices
+            .into_iter()
+            .filter(|&index| self.tokens[index][player].is_none())
+            .collect()
+    }
+
+    /// Publicly open the card at `index`. Every token was verified, so a point that is not a card
+    /// of the deck means the deck itself was corrupted.
+    fn open_public(&self, index: usize) -> Result<ClassicPlayingCard, CardProtocolError> {
+        let card = self.open_card(index, &self.tokens[index])?;
+
+        self.lookup
+            .classic(&card)
+            .ok_or(CardProtocolError::InvalidInput(InputError::UnknownCard))
+    }
+
+    /// Unmask the card at `index` from one token per player. The tokens were verified when they
+    /// were received.
+    fn open_card(
+        &self,
+        index: usize,
+        tokens: &[Option<RevealToken<C>>],
+    ) -> Result<Card<C>, CardProtocolError> {
+        let aggregate_token = tokens
+            .iter()
+            .try_fold(RevealToken::<C>::zero(), |acc, token| {
+                token
+                    .map(|token| acc + token)
+                    .ok_or(CardProtocolError::InvalidInput(InputError::UnexpectedPhase))
+            })?;
+
+        aggregate_token.reveal(&self.deck[index])
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{Action, HoldemTable, Street, TableMessage, TablePhase};
+    use crate::crypto_primitives::utils::rand::test_rng;
+    use crate::discrete_log_cards;
+    use crate::discrete_log_cards::test_helpers::{setup_players, Player};
+    use crate::error::{CardProtocolError, InputError};
+    use crate::BarnettSmartProtocol;
+
+    use ark_std::rand::{CryptoRng, Rng};
+    use std::collections::HashSet;
+
+    // Choose elliptic curve setting
+    type Curve = starknet_curve::Projective;
+
+    // Instantiate concrete type for our card protocol
+    type CardProtocol<'a> = discrete_log_cards::DLCards<'a, Curve>;
+    type CardParameters = discrete_log_cards::Parameters<Curve>;
+
+    const SESSION_ID: &[u8] = b"holdem test: hand 1";
+
+    /// The message `player` sends to satisfy `action`.
+    fn respond<R: Rng + CryptoRng>(
+        rng: &mut R,
+        parameters: &CardParameters,
+        table: &HoldemTable<Curve, u64>,
+        keys: &[Player<Curve>],
+        action: &Action,
+    ) -> (usize, TableMessage<Curve>) {
+        match action {
+            Action::SendPublicKey { player } => (*player, TableMessage::PublicKey(keys[*player].0)),
+            Action::ProveKeyOwnership {
+                player,
+                key_list_digest,
+            } => {
+                let (pk, sk, _) = &keys[*player];
+                let proof = CardProtocol::prove_key_ownership(
+                    rng,
+                    parameters,
+                    SESSION_ID,
+                    pk,
+                    sk,
+                    &(*player as u64),
+                    *player,
+                    key_list_digest,
+                )
+                .unwrap();
+                (*player, TableMessage::KeyProof(proof))
+            }
+            Action::Shuffle { player } => {
+                let (deck, proof) = CardProtocol::shuffle_and_remask_with_rng(
+                    rng,
+                    parameters,
+                    SESSION_ID,
+                    &table.aggregate_key().unwrap(),
+                    &table.deck().to_vec(),
+                )
+                .unwrap();
+                (*player, TableMessage::Shuffle { deck, proof })
+            }
+            Action::Reveal { player, indices } => {
+                let (pk, sk, _) = &keys[*player];
+                let tokens = indices
+                    .iter()
+                    .map(|&index| {
+                        let (token, proof) = CardProtocol::compute_reveal_token(
+                            rng,
+                            parameters,
+                            SESSION_ID,
+                            sk,
+                            pk,
+                            &table.deck()[index],
+                        )
+                        .unwrap();
+                        (index, token, proof)
+                    })
+                    .collect();
+                (*player, TableMessage::RevealTokens(tokens))
+            }
+            Action::Bet { .. } => unreachable!("betting is driven by the test"),
+        }
+    }
+
+    /// Deliver the messages answering every pending action, until betting is required.
+    fn run_until_betting<R: Rng + CryptoRng>(
+        rng: &mut R,
+        parameters: &CardParameters,
+        table: &mut HoldemTable<Curve, u64>,
+        keys: &[Player<Curve>],
+    ) {
+        loop {
+            let actions = table.next_actions();
+            if actions.is_empty() || matches!(actions[0], Action::Bet { .. }) {
+                return;
+            }
+
+            for action in actions {
+                let (player, message) = respond(rng, parameters, table, keys, &action);
+                table.receive(parameters, player, message).unwrap();
+            }
+        }
+    }
+
+    #[test]
+    fn test_four_player_hand() {
+        let rng = &mut test_rng();
+        let m = 4;
+        let n = 13;
+        let num_of_players = 4;
+
+        let parameters = CardProtocol::setup(rng, m, n).unwrap();
+        let (keys, _) = setup_players(rng, &parameters, num_of_players).unwrap();
+        let mut table = HoldemTable::new(
+            &parameters,
+            SESSION_ID,
+            (0..num_of_players as u64).collect(),
+        )
+        .unwrap();
+
+        // Keys, proofs and the shuffle chain, up to the dealing of the hole cards
+        run_until_betting(rng, &parameters, &mut table, &keys);
+        assert_eq!(
+            table.next_actions(),
+            vec![Action::Bet {
+                street: Street::Preflop,
+                active: vec![0, 1, 2, 3]
+            }]
+        );
+
+        // Every player sees their own hole cards, and nobody holds the same card
+        let hole_cards = (0..num_of_players)
+            .map(|player| table.open_own_hole_cards(player, &keys[player].1).unwrap())
+            .collect::<Vec<_>>();
+        let dealt = hole_cards.iter().flatten().collect::<HashSet<_>>();
+        assert_eq!(dealt.len(), 2 * num_of_players);
+
+        // Betting is the application's business: player 2 folds before the flop
+        table.end_betting(Street::Preflop, &[2]).unwrap();
+        for street in [Street::Flop, Street::Turn, Street::River] {
+            assert_eq!(table.phase(), TablePhase::Deal(street));
+
+            // The card burned before the street is never revealed during the hand
+            let burned = *table.burned_indices().last().unwrap();
+            let (player, message) = respond(
+                rng,
+                &parameters,
+                &table,
+                &keys,
+                &Action::Reveal {
+                    player: 0,
+                    indices: vec![burned],
+                },
+            );
+            assert_eq!(
+                table.receive(&parameters, player, message),
+                Err(CardProtocolError::InvalidInput(InputError::BurnedCard(
+                    burned
+                )))
+            );
+
+            run_until_betting(rng, &parameters, &mut table, &keys);
+            table.end_betting(street, &[]).unwrap();
+        }
+
+        // Folded players still reveal community cards but not their hand
+        assert_eq!(table.phase(), TablePhase::Showdown);
+        assert_eq!(table.next_actions().len(), 3);
+        run_until_betting(rng, &parameters, &mut table, &keys);
+        assert_eq!(table.phase(), TablePhase::Done);
+
+        let community = table.community_cards().to_vec();
+        assert_eq!(community.len(), 5);
+        assert!(community.iter().all(|card| !dealt.contains(card)));
+        assert_eq!(community.iter().collect::<HashSet<_>>().len(), 5);
+
+        for player in [0, 1, 3] {
+            assert_eq!(
+                table.shown_hand(player),
+                Some(hole_cards[player].as_slice())
+            );
+        }
+        assert_eq!(table.shown_hand(2), None);
+
+        // After the hand, the three burned cards are opened for audit
+        assert_eq!(table.burned_indices(), table.deal_plan().burned());
+        table.open_burns().unwrap();
+        assert_eq!(table.phase(), TablePhase::OpenBurns);
+        run_until_betting(rng, &parameters, &mut table, &keys);
+        assert_eq!(table.phase(), TablePhase::Done);
+
+        let burned_cards = table.burned_cards().unwrap();
+        assert_eq!(burned_cards.len(), 3);
+        assert!(burned_cards
+            .iter()
+            .all(|card| !dealt.contains(card) && !community.contains(card)));
+        assert_eq!(
+            table.open_burns(),
+            Err(CardProtocolError::InvalidInput(InputError::UnexpectedPhase))
+        );
+    }
+
+    #[test]
+    fn test_out_of_turn_messages_are_rejected() {
+        let rng = &mut test_rng();
+        let m = 4;
+        let n = 13;
+        let num_of_players = 3;
+
+        let parameters = CardProtocol::setup(rng, m, n).unwrap();
+        let (keys, _) = setup_players(rng, &parameters, num_of_players).unwrap();
+        let mut table = HoldemTable::new(
+            &parameters,
+            SESSION_ID,
+            (0..num_of_players as u64).collect(),
+        )
+        .unwrap();
+
+        // Keys only once, and no proofs before all keys are known
+        table
+            .receive(&parameters, 0, TableMessage::PublicKey(keys[0].0))
+            .unwrap();
+        assert_eq!(
+            table.receive(&parameters, 0, TableMessage::PublicKey(keys[0].0)),
+            Err(CardProtocolError::InvalidInput(
+                InputError::DuplicateMessage(0)
+            ))
+        );
+        assert_eq!(
+            table.receive(&parameters, 1, TableMessage::PublicKey(keys[0].0)),
+            Err(CardProtocolError::InvalidInput(
+                InputError::DuplicatePlayerKey { index: 1 }
+            ))
+        );
+        assert_eq!(
+            table.receive(&parameters, 5, TableMessage::PublicKey(keys[1].0)),
+            Err(CardProtocolError::InvalidInput(InputError::UnknownPlayer(
+                5
+            )))
+        );
+
+        // Up to the first shuffle
+        while let TablePhase::KeyExchange | TablePhase::KeyProofs = table.phase() {
+            let action = table.next_actions().remove(0);
+            let (player, message) = respond(rng, &parameters, &table, &keys, &action);
+            table.receive(&parameters, player, message).unwrap();
+        }
+        assert_eq!(table.phase(), TablePhase::Shuffle(0));
+
+        // Player 1 cannot shuffle before player 0
+        let (_, message) = respond(
+            rng,
+            &parameters,
+            &table,
+            &keys,
+            &Action::Shuffle { player: 1 },
+        );
+        assert_eq!(
+            table.receive(&parameters, 1, message.clone()),
+            Err(CardProtocolError::InvalidInput(InputError::NotYourTurn(1)))
+        );
+        assert_eq!(
+            table.end_betting(Street::Preflop, &[]),
+            Err(CardProtocolError::InvalidInput(InputError::UnexpectedPhase))
+        );
+        table.receive(&parameters, 0, message).unwrap();
+
+        // A shuffle of a deck other than the current one does not verify
+        let (_, stale) = respond(
+            rng,
+            &parameters,
+            &table,
+            &keys,
+            &Action::Shuffle { player: 1 },
+        );
+        let (_, message) = respond(
+            rng,
+            &parameters,
+            &table,
+            &keys,
+            &Action::Shuffle { player: 1 },
+        );
+        table.receive(&parameters, 1, message).unwrap();
+        assert!(table.receive(&parameters, 2, stale).is_err());
+
+        run_until_betting(rng, &parameters, &mut table, &keys);
+        assert_eq!(table.phase(), TablePhase::Betting(Street::Preflop));
+
+        // Tokens for the flop are not due yet
+        table.end_betting(Street::Preflop, &[]).unwrap();
+        let flop = table.community_indices(Street::Flop);
+        let turn = table.community_indices(Street::Turn);
+        let (_, message) = respond(
+            rng,
+            &parameters,
+            &table,
+            &keys,
+            &Action::Reveal {
+                player: 0,
+                indices: turn.clone(),
+            },
+        );
+        assert_eq!(
+            table.receive(&parameters, 0, message),
+            Err(CardProtocolError::InvalidInput(
+                InputError::UnexpectedReveal(turn[0])
+            ))
+        );
+
+        // A token computed with another key is rejected, and nothing is stored
+        let (_, message) = respond(
+            rng,
+            &parameters,
+            &table,
+            &keys,
+            &Action::Reveal {
+                player: 1,
+                indices: flop.clone(),
+            },
+        );
+        assert!(table.receive(&parameters, 0, message).is_err());
+        assert_eq!(
+            table.next_actions()[0],
+            Action::Reveal {
+                player: 0,
+                indices: flop
+            }
+        );
+    }
+}
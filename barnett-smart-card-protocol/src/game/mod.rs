@@ -0,0 +1,12 @@
+//! Game orchestration built on the card protocol. Everything here is deterministic and independent
+//! of the transport: players feed the same broadcast messages to their local copy of a table and
+//! ask it which actions are due.
+
+pub mod chips;
+pub mod draw;
+pub mod holdem;
+pub mod randomness;
+pub mod shoe;
+pub mod showdown;
+pub mod simultaneous;
+pub mod zones;
@@ -0,0 +1,335 @@
+//! Shared randomness for choosing seats and the button before the game starts.
+//!
+//! Every player commits to a random 32-byte contribution, and reveals it only once everyone has
+//! committed. Nobody can choose their contribution after seeing the others', so the combined seed
+//! is uniform as long as one player is honest. Commitments bind the player's index and the
+//! session context, so copying another player's commitment gains nothing: the copy cannot be
+//! opened. A player who does not reveal, or reveals a value that does not open their commitment,
+//! stalls the round and is named when it is finalized.
+//!
+//! The seed drives `Permutation::from_seed` to seat the players with [`derive_seating`], or picks
+//! the button directly.
+
+use crate::crypto_primitives::permutation::{combine_contributions, FromSeed};
+use crate::discrete_log_cards::PublicKey;
+use crate::error::{CardProtocolError, InputError, VerificationError};
+
+use ark_ec::ProjectiveCurve;
+use ark_serialize::CanonicalSerialize;
+use blake2::{Blake2s, Digest};
+use proof_essentials::utils::permutation::Permutation;
+
+const CONTRIBUTION_COMMITMENT_SEED: &'static [u8] = b"Randomness Contribution";
+
+/// Hash commitment to a contribution.
+pub type ContributionCommitment = [u8; 32];
+
+/// Commit to the contribution that `player` will reveal in the game `context`.
+pub fn commit_to_contribution(
+    context: &[u8],
+    player: usize,
+    contribution: &[u8; 32],
+) -> ContributionCommitment {
+    let mut hasher = Blake2s::new();
+    hasher.update(CONTRIBUTION_COMMITMENT_SEED);
+    hasher.update(&(context.len() as u64).to_le_bytes());
+    hasher.update(context);
+    hasher.update(&(player as u64).to_le_bytes());
+    hasher.update(contribution);
+
+    let mut commitment = [0u8; 32];
+    commitment.copy_from_slice(&hasher.finalize());
+
+    commitment
+}
+
+/// Seat the players with keys `player_keys` in an order drawn from the shared `seed`. The keys are
+/// sorted by their encoding before they are shuffled, so every player computes the same seating
+/// whatever order they received the keys in. Fails if two players have the same key.
+pub fn derive_seating<C: ProjectiveCurve>(
+    seed: &[u8; 32],
+    player_keys: &[PublicKey<C>],
+) -> Result<Vec<PublicKey<C>>, CardProtocolError> {
+    let mut encoded = player_keys
+        .iter()
+        .map(|pk| -> Result<_, CardProtocolError> {
+            let mut bytes = Vec::new();
+            pk.serialize(&mut bytes)?;
+            Ok((bytes, *pk))
+        })
+        .collect::<Result<Vec<_>, _>>()?;
+
+    for (index, (bytes, _)) in encoded.iter().enumerate() {
+        if encoded[..index].iter().any(|(other, _)| other == bytes) {
+            return Err(InputError::DuplicatePlayerKey { index }.into());
+        }
+    }
+
+    encoded.sort_by(|(a, _), (b, _)| a.cmp(b));
+    let sorted = encoded.into_iter().map(|(_, pk)| pk).collect::<Vec<_>>();
+
+    Ok(Permutation::from_seed(*seed, sorted.len()).permute_array(&sorted))
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct RandomnessRound {
+    context: Vec<u8>,
+    commitments: Vec<Option<ContributionCommitment>>,
+    contributions: Vec<Option<[u8; 32]>>,
+}
+
+impl RandomnessRound {
+    pub fn new(context: &[u8], num_players: usize) -> Result<Self, CardProtocolError> {
+        if num_players == 0 {
+            return Err(InputError::InvalidPlayerCount(num_players).into());
+        }
+
+        Ok(Self {
+            context: context.to_vec(),
+            commitments: vec![None; num_players],
+            contributions: vec![None; num_players],
+        })
+    }
+
+    /// Whether every player has committed, after which contributions may be revealed.
+    pub fn is_committed(&self) -> bool {
+        self.commitments.iter().all(Option::is_some)
+    }
+
+    /// Record the commitment of `player`. A player commits once; a second commitment, even to
+    /// the same value, is rejected and the first one stands.
+    pub fn commit(
+        &mut self,
+        player: usize,
+        commitment: ContributionCommitment,
+    ) -> Result<(), CardProtocolError> {
+        let slot = self
+            .commitments
+            .get_mut(player)
+            .ok_or(CardProtocolError::InvalidInput(InputError::UnknownPlayer(
+                player,
+            )))?;
+
+        if slot.is_some() {
+            return Err(InputError::DuplicateMessage(player).into());
+        }
+
+        *slot = Some(commitment);
+
+        Ok(())
+    }
+
+    /// Check the contribution of `player` against their commitment. A contribution that does not
+    /// open the commitment is rejected and blamed on `player`.
+    pub fn reveal(
+        &mut self,
+        player: usize,
+        contribution: [u8; 32],
+    ) -> Result<(), CardProtocolError> {
+        if !self.is_committed() {
+            return Err(InputError::UnexpectedPhase.into());
+        }
+
+        let commitment = match self.commitments.get(player) {
+            Some(Some(commitment)) => *commitment,
+            _ => return Err(InputError::UnknownPlayer(player).into()),
+        };
+
+        if self.contributions[player].is_some() {
+            return Err(InputError::DuplicateMessage(player).into());
+        }
+
+        if commit_to_contribution(&self.context, player, &contribution) != commitment {
+            return Err(VerificationError::PlayerMessage {
+                player,
+                source: None,
+            }
+            .into());
+        }
+
+        self.contributions[player] = Some(contribution);
+
+        Ok(())
+    }
+
+    /// Players who have not revealed a valid contribution yet, in increasing order.
+    pub fn missing_reveals(&self) -> Vec<usize> {
+        self.contributions
+            .iter()
+            .enumerate()
+            .filter(|(_, contribution)| contribution.is_none())
+            .map(|(player, _)| player)
+            .collect()
+    }
+
+    /// Combine the contributions, in seat order, into the shared seed. Fails with the players who
+    /// have not revealed a valid contribution.
+    pub fn finalize(&self) -> Result<[u8; 32], CardProtocolError> {
+        let missing = self.missing_reveals();
+        if !missing.is_empty() {
+            return Err(InputError::MissingReveals(missing).into());
+        }
+
+        let contributions = self
+            .contributions
+            .iter()
+            .flatten()
+            .copied()
+            .collect::<Vec<_>>();
+
+        Ok(combine_contributions(&contributions))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{commit_to_contribution, derive_seating, RandomnessRound};
+    use crate::crypto_primitives::permutation::combine_contributions;
+    use crate::crypto_primitives::utils::rand::test_rng;
+    use crate::discrete_log_cards;
+    use crate::discrete_log_cards::test_helpers::setup_players;
+    use crate::error::{CardProtocolError, InputError, VerificationError};
+    use crate::BarnettSmartProtocol;
+
+    use rand::Rng;
+
+    // Choose elliptic curve setting
+    type Curve = starknet_curve::Projective;
+
+    // Instantiate concrete type for our card protocol
+    type CardProtocol<'a> = discrete_log_cards::DLCards<'a, Curve>;
+
+    const SESSION_ID: &[u8] = b"randomness test";
+
+    #[test]
+    fn test_randomness_round() {
+        let rng = &mut test_rng();
+        let num_of_players = 4;
+
+        let contributions = (0..num_of_players)
+            .map(|_| rng.gen::<[u8; 32]>())
+            .collect::<Vec<_>>();
+        let mut round = RandomnessRound::new(SESSION_ID, num_of_players).unwrap();
+
+        for (player, contribution) in contributions.iter().enumerate() {
+            // Nobody reveals before everyone has committed
+            assert_eq!(
+                round.reveal(player, *contribution),
+                Err(CardProtocolError::InvalidInput(InputError::UnexpectedPhase))
+            );
+
+            let commitment = commit_to_contribution(SESSION_ID, player, contribution);
+            round.commit(player, commitment).unwrap();
+            assert_eq!(
+                round.commit(player, commitment),
+                Err(CardProtocolError::InvalidInput(
+                    InputError::DuplicateMessage(player)
+                ))
+            );
+        }
+
+        // Player 2 tries to change their contribution after seeing the others
+        for player in [0, 1, 3] {
+            round.reveal(player, contributions[player]).unwrap();
+        }
+        assert_eq!(
+            round.reveal(2, rng.gen()),
+            Err(CardProtocolError::VerificationFailed(
+                VerificationError::PlayerMessage {
+                    player: 2,
+                    source: None
+                }
+            ))
+        );
+        assert_eq!(
+            round.finalize(),
+            Err(CardProtocolError::InvalidInput(InputError::MissingReveals(
+                vec![2]
+            )))
+        );
+
+        // Only the committed contribution is accepted
+        round.reveal(2, contributions[2]).unwrap();
+        let seed = round.finalize().unwrap();
+
+        let mut other = RandomnessRound::new(SESSION_ID, num_of_players).unwrap();
+        for (player, contribution) in contributions.iter().enumerate() {
+            other
+                .commit(
+                    player,
+                    commit_to_contribution(SESSION_ID, player, contribution),
+                )
+                .unwrap();
+        }
+        for (player, contribution) in contributions.iter().enumerate() {
+            other.reveal(player, *contribution).unwrap();
+        }
+        assert_eq!(other.finalize().unwrap(), seed);
+    }
+
+    #[test]
+    fn test_copied_commitment_cannot_be_opened() {
+        let rng = &mut test_rng();
+        let contribution = rng.gen::<[u8; 32]>();
+
+        let mut round = RandomnessRound::new(SESSION_ID, 2).unwrap();
+        let commitment = commit_to_contribution(SESSION_ID, 0, &contribution);
+        round.commit(0, commitment).unwrap();
+        round.commit(1, commitment).unwrap();
+
+        round.reveal(0, contribution).unwrap();
+        assert_eq!(
+            round.reveal(1, contribution),
+            Err(CardProtocolError::VerificationFailed(
+                VerificationError::PlayerMessage {
+                    player: 1,
+                    source: None
+                }
+            ))
+        );
+        assert_eq!(
+            round.finalize(),
+            Err(CardProtocolError::InvalidInput(InputError::MissingReveals(
+                vec![1]
+            )))
+        );
+    }
+
+    #[test]
+    fn test_derive_seating() {
+        let rng = &mut test_rng();
+        let m = 4;
+        let n = 13;
+        let num_of_players = 8;
+
+        let parameters = CardProtocol::setup(rng, m, n).unwrap();
+        let (players, _) = setup_players(rng, &parameters, num_of_players).unwrap();
+        let keys = players.iter().map(|(pk, _, _)| *pk).collect::<Vec<_>>();
+        let seed = combine_contributions(&[[1u8; 32], [2u8; 32], [3u8; 32]]);
+
+        // Every player seats the table the same way, whatever order they hold the keys in
+        let seating = derive_seating(&seed, &keys).unwrap();
+        for shift in 1..num_of_players {
+            let mut received = keys.clone();
+            received.rotate_left(shift);
+            assert_eq!(derive_seating(&seed, &received).unwrap(), seating);
+        }
+        let mut seated = seating.clone();
+        seated.sort_by_key(|pk| keys.iter().position(|key| key == pk));
+        assert_eq!(seated, keys);
+
+        // A single bit of the seed reshuffles the table
+        let mut other_seed = seed;
+        other_seed[0] ^= 1;
+        assert_ne!(derive_seating(&other_seed, &keys).unwrap(), seating);
+
+        let mut duplicated = keys.clone();
+        duplicated[5] = duplicated[2];
+        assert_eq!(
+            derive_seating(&seed, &duplicated),
+            Err(CardProtocolError::InvalidInput(
+                InputError::DuplicatePlayerKey { index: 5 }
+            ))
+        );
+    }
+}
@@ -0,0 +1,259 @@
+//! Cards drawn one at a time from the top of the shuffled deck, as in blackjack.
+//!
+//! Games that do not deal everything up front cannot fix the destination of every position in
+//! advance like a `DealPlan`. A [`Shoe`] instead keeps the position of the next undealt card in
+//! the shared state: every draw takes that position and advances it by one, so all players agree
+//! on which card to reveal to whom without further messages. A draw announced by another node is
+//! replayed with [`Shoe::apply_draw`], which refuses any position but the next one, so no card is
+//! skipped or dealt twice.
+//!
+//! The shoe holds public data only and is serializable, so a server can persist it between
+//! actions.
+
+use crate::crypto_primitives::utils::format::Compact;
+use crate::discrete_log_cards::MaskedCard;
+use crate::error::{CardProtocolError, InputError};
+
+use ark_ec::ProjectiveCurve;
+use ark_serialize::{CanonicalDeserialize, CanonicalSerialize};
+use ark_std::fmt;
+
+/// A card drawn from the shoe. Reveal tokens for the card at `index` go to `owner`, or to every
+/// player for a community card.
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub struct ShoeDraw<C: ProjectiveCurve> {
+    pub index: usize,
+    pub masked_card: MaskedCard<C>,
+    pub owner: Option<usize>,
+}
+
+impl<C: ProjectiveCurve> fmt::Debug for ShoeDraw<C> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("ShoeDraw")
+            .field("index", &self.index)
+            .field("masked_card", &Compact(&self.masked_card))
+            .field("owner", &self.owner)
+            .finish()
+    }
+}
+
+#[derive(Clone, CanonicalSerialize, CanonicalDeserialize)]
+pub struct Shoe<C: ProjectiveCurve> {
+    num_players: usize,
+    deck: Vec<MaskedCard<C>>,
+    /// Owner of every position drawn so far, `None` for community cards. Its length is the
+    /// position of the next undealt card.
+    owners: Vec<Option<usize>>,
+}
+
+impl<C: ProjectiveCurve> fmt::Debug for Shoe<C> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Shoe")
+            .field("num_players", &self.num_players)
+            .field("deck", &Compact(&self.deck))
+            .field("owners", &self.owners)
+            .finish()
+    }
+}
+
+impl<C: ProjectiveCurve> Shoe<C> {
+    /// Draw from the final shuffled `deck` of a game among `num_players` players.
+    pub fn new(num_players: usize, deck: Vec<MaskedCard<C>>) -> Result<Self, CardProtocolError> {
+        if num_players == 0 {
+            return Err(InputError::InvalidPlayerCount(num_players).into());
+        }
+
+        Ok(Self {
+            num_players,
+            deck,
+            owners: Vec::new(),
+        })
+    }
+
+    /// Position of the next undealt card.
+    pub fn next_position(&self) -> usize {
+        self.owners.len()
+    }
+
+    pub fn remaining(&self) -> usize {
+        self.deck.len() - self.owners.len()
+    }
+
+    /// Positions of the cards drawn for `seat`, in the order they were drawn.
+    pub fn hand(&self, seat: usize) -> Vec<usize> {
+        self.positions(Some(seat))
+    }
+
+    /// Positions of the community cards, in the order they were drawn.
+    pub fn community(&self) -> Vec<usize> {
+        self.positions(None)
+    }
+
+    /// Draw the next card for `seat`.
+    pub fn draw_next_for(&mut self, seat: usize) -> Result<ShoeDraw<C>, CardProtocolError> {
+        if seat >= self.num_players {
+            return Err(InputError::UnknownPlayer(seat).into());
+        }
+
+        self.draw(Some(seat))
+    }
+
+    /// Draw the next card face up for everyone, such as the dealer's card in blackjack.
+    pub fn draw_next_community(&mut self) -> Result<ShoeDraw<C>, CardProtocolError> {
+        self.draw(None)
+    }
+
+    /// Replay a draw from position `index` for `owner` announced by another node. Only the next
+    /// undealt position is accepted.
+    pub fn apply_draw(
+        &mut self,
+        index: usize,
+        owner: Option<usize>,
+    ) -> Result<ShoeDraw<C>, CardProtocolError> {
+        let expected = self.next_position();
+        if index != expected {
+            return Err(InputError::UnexpectedPosition {
+                expected,
+                position: index,
+            }
+            .into());
+        }
+
+        match owner {
+            Some(seat) => self.draw_next_for(seat),
+            None => self.draw_next_community(),
+        }
+    }
+
+    fn draw(&mut self, owner: Option<usize>) -> Result<ShoeDraw<C>, CardProtocolError> {
+        let index = self.next_position();
+        let masked_card = *self.deck.get(index).ok_or(CardProtocolError::InvalidInput(
+            InputError::InvalidDeal(self.deck.len()),
+        ))?;
+
+        self.owners.push(owner);
+
+        Ok(ShoeDraw {
+            index,
+            masked_card,
+            owner,
+        })
+    }
+
+    fn positions(&self, owner: Option<usize>) -> Vec<usize> {
+        self.owners
+            .iter()
+            .enumerate()
+            .filter(|(_, drawn_for)| **drawn_for == owner)
+            .map(|(index, _)| index)
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::Shoe;
+    use crate::crypto_primitives::utils::rand::test_rng;
+    use crate::discrete_log_cards;
+    use crate::error::{CardProtocolError, InputError};
+
+    use ark_serialize::{CanonicalDeserialize, CanonicalSerialize};
+    use ark_std::UniformRand;
+
+    // Choose elliptic curve setting
+    type Curve = starknet_curve::Projective;
+
+    type MaskedCard = discrete_log_cards::MaskedCard<Curve>;
+
+    #[test]
+    fn test_blackjack_hand() {
+        let rng = &mut test_rng();
+        let num_of_players = 3;
+
+        let deck = (0..52).map(|_| MaskedCard::rand(rng)).collect::<Vec<_>>();
+        let mut shoe = Shoe::new(num_of_players, deck.clone()).unwrap();
+        let mut replica = shoe.clone();
+
+        // Two cards to every player and one face up for the dealer
+        for _ in 0..2 {
+            for seat in 0..num_of_players {
+                let draw = shoe.draw_next_for(seat).unwrap();
+                assert_eq!(draw.masked_card, deck[draw.index]);
+                replica.apply_draw(draw.index, draw.owner).unwrap();
+            }
+        }
+        let up_card = shoe.draw_next_community().unwrap();
+        assert_eq!(up_card.index, 6);
+        replica.apply_draw(up_card.index, None).unwrap();
+
+        // Player 0 hits twice, player 1 stands and player 2 hits once. The table is persisted
+        // between actions
+        for seat in [0, 0, 2] {
+            let mut bytes = Vec::new();
+            shoe.serialize(&mut bytes).unwrap();
+            shoe = Shoe::<Curve>::deserialize(&bytes[..]).unwrap();
+
+            let draw = shoe.draw_next_for(seat).unwrap();
+
+            // A replica cannot skip the card or deal it twice
+            assert_eq!(
+                replica.apply_draw(draw.index + 1, draw.owner).err(),
+                Some(CardProtocolError::InvalidInput(
+                    InputError::UnexpectedPosition {
+                        expected: draw.index,
+                        position: draw.index + 1
+                    }
+                ))
+            );
+            replica.apply_draw(draw.index, draw.owner).unwrap();
+            assert_eq!(
+                replica.apply_draw(draw.index, draw.owner).err(),
+                Some(CardProtocolError::InvalidInput(
+                    InputError::UnexpectedPosition {
+                        expected: draw.index + 1,
+                        position: draw.index
+                    }
+                ))
+            );
+        }
+
+        // The dealer draws to their hand
+        let hole_card = shoe.draw_next_community().unwrap();
+        replica.apply_draw(hole_card.index, None).unwrap();
+
+        assert_eq!(shoe.hand(0), vec![0, 3, 7, 8]);
+        assert_eq!(shoe.hand(1), vec![1, 4]);
+        assert_eq!(shoe.hand(2), vec![2, 5, 9]);
+        assert_eq!(shoe.community(), vec![6, 10]);
+        assert_eq!(shoe.next_position(), 11);
+        assert_eq!(shoe.remaining(), 41);
+
+        for seat in 0..num_of_players {
+            assert_eq!(replica.hand(seat), shoe.hand(seat));
+        }
+        assert_eq!(replica.community(), shoe.community());
+
+        assert_eq!(
+            shoe.draw_next_for(num_of_players).err(),
+            Some(CardProtocolError::InvalidInput(InputError::UnknownPlayer(
+                num_of_players
+            )))
+        );
+    }
+
+    #[test]
+    fn test_empty_shoe() {
+        let rng = &mut test_rng();
+
+        let deck = (0..2).map(|_| MaskedCard::rand(rng)).collect::<Vec<_>>();
+        let mut shoe = Shoe::<Curve>::new(1, deck).unwrap();
+
+        shoe.draw_next_for(0).unwrap();
+        shoe.draw_next_community().unwrap();
+        assert_eq!(
+            shoe.draw_next_for(0).err(),
+            Some(CardProtocolError::InvalidInput(InputError::InvalidDeal(2)))
+        );
+        assert_eq!(shoe.next_position(), 2);
+    }
+}
@@ -0,0 +1,352 @@
+//! Showdown for games whose hole cards were dealt privately.
+//!
+//! When every player sends their reveal tokens for a hole card to its owner only, nobody else can
+//! open that card. At showdown each contested hand therefore needs a token from every player,
+//! including those who folded. A [`Showdown`] collects and verifies these tokens for the hole cards
+//! of the contested seats only, so folded hands are never opened. A player who holds back a token
+//! stalls the showdown for everyone. `finalize` then names that player and the cards they failed
+//! to contribute to, and opens the hands that are complete.
+//!
+//! Opened cards are public, so they are resolved with a [`CardLookup`] of the shoe the game is
+//! played with. Every token is verified on receipt, so a point the lookup does not know means the
+//! deck was corrupted and is reported as `UnknownCard`.
+
+use crate::crypto_primitives::utils::format::{Compact, Hex, HexBytes};
+use crate::discrete_log_cards::deal::DealPlan;
+use crate::discrete_log_cards::encoding::CardLookup;
+use crate::discrete_log_cards::reveal::RevealProof;
+use crate::discrete_log_cards::{Card, DLCards, MaskedCard, Parameters, PublicKey, RevealToken};
+use crate::error::{CardProtocolError, InputError};
+use crate::playing_card::ClassicPlayingCard;
+use crate::{BarnettSmartProtocol, Reveal};
+
+use ark_ec::ProjectiveCurve;
+use ark_ff::Zero;
+use std::collections::BTreeMap;
+use std::fmt;
+
+/// Evidence that `player` did not contribute the reveal tokens for the cards at `missing`.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Blame {
+    pub player: usize,
+    pub missing: Vec<usize>,
+}
+
+/// Result of a showdown.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ShowdownOutcome {
+    /// Opened hole cards of the contested seats whose tokens are all in, by seat
+    pub hands: BTreeMap<usize, Vec<ClassicPlayingCard>>,
+    /// Players who withheld tokens, in increasing order; empty if the showdown completed
+    pub blame: Vec<Blame>,
+}
+
+pub struct Showdown<C: ProjectiveCurve> {
+    context: Vec<u8>,
+    keys: Vec<PublicKey<C>>,
+    deck: Vec<MaskedCard<C>>,
+    /// Deck indices of the hole cards of each contested seat
+    contested: BTreeMap<usize, Vec<usize>>,
+    /// Verified reveal tokens, by deck index and then by player
+    tokens: BTreeMap<usize, Vec<Option<RevealToken<C>>>>,
+}
+
+/// The deck is left out, and the tokens are printed by deck index and then by player.
+impl<C: ProjectiveCurve> fmt::Debug for Showdown<C> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let tokens = self
+            .tokens
+            .iter()
+            .map(|(index, tokens)| (index, Compact(tokens)));
+
+        f.debug_struct("Showdown")
+            .field("context", &HexBytes(&self.context))
+            .field("keys", &self.keys.iter().map(Hex).collect::<Vec<_>>())
+            .field("contested", &self.contested)
+            .field("tokens", &tokens.collect::<BTreeMap<_, _>>())
+            .finish_non_exhaustive()
+    }
+}
+
+impl<C: ProjectiveCurve> Showdown<C> {
+    /// Start the showdown of the `contested` seats, for the final `deck` dealt according to
+    /// `plan`. `keys[i]` is the public key of the player in seat `i`.
+    pub fn new(
+        context: &[u8],
+        keys: Vec<PublicKey<C>>,
+        deck: Vec<MaskedCard<C>>,
+        plan: &DealPlan,
+        contested: &[usize],
+    ) -> Result<Self, CardProtocolError> {
+        if keys.len() != plan.num_players() {
+            return Err(InputError::InvalidPlayerCount(keys.len()).into());
+        }
+        plan.validate(deck.len())?;
+
+        let mut hands = BTreeMap::new();
+        let mut tokens = BTreeMap::new();
+        for &seat in contested {
+            if seat >= keys.len() {
+                return Err(InputError::UnknownPlayer(seat).into());
+            }
+
+            let indices = plan.hole_cards(seat).to_vec();
+            for &index in &indices {
+                tokens.insert(index, vec![None; keys.len()]);
+            }
+            hands.insert(seat, indices);
+        }
+
+        Ok(Self {
+            context: context.to_vec(),
+            keys,
+            deck,
+            contested: hands,
+            tokens,
+        })
+    }
+
+    /// Deck indices `player` still has to send reveal tokens for.
+    pub fn due_reveals(&self, player: usize) -> Vec<usize> {
+        self.tokens
+            .iter()
+            .filter(|(_, tokens)| matches!(tokens.get(player), Some(None)))
+            .map(|(&index, _)| index)
+            .collect()
+    }
+
+    pub fn is_complete(&self) -> bool {
+        self.tokens.values().flatten().all(Option::is_some)
+    }
+
+    /// Verify and record the reveal tokens of `player`. Tokens for cards that are not contested, or
+    /// that `player` already contributed to, are rejected, as is the whole message if any proof
+    /// fails.
+    pub fn receive(
+        &mut self,
+        pp: &Parameters<C>,
+        player: usize,
+        tokens: Vec<(usize, RevealToken<C>, RevealProof<C>)>,
+    ) -> Result<(), CardProtocolError> {
+        let pk = *self
+            .keys
+            .get(player)
+            .ok_or(CardProtocolError::InvalidInput(InputError::UnknownPlayer(
+                player,
+            )))?;
+        let mut due = self.due_reveals(player);
+
+        // Check every token before storing any, so that a rejected message changes nothing
+        for (index, token, proof) in &tokens {
+            match due.iter().position(|due_index| due_index == index) {
+                Some(position) => due.swap_remove(position),
+                None => return Err(InputError::UnexpectedReveal(*index).into()),
+            };
+
+            DLCards::<C>::verify_reveal(pp, &self.context, &pk, token, &self.deck[*index], proof)
+                .map_err(|source| CardProtocolError::verification_failed(player, source))?;
+        }
+
+        for (index, token, _) in tokens {
+            if let Some(slot) = self.tokens.get_mut(&index) {
+                slot[player] = Some(token);
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Open every contested hand whose tokens are all in, and blame the players whose tokens are
+    /// missing. Call this once the deadline for contributions has passed; a folded player who
+    /// refuses to help open an opponent's hand is blamed like anyone else. Cards are resolved with
+    /// `lookup`.
+    pub fn finalize(&self, lookup: &CardLookup<C>) -> Result<ShowdownOutcome, CardProtocolError> {
+        let blame = (0..self.keys.len())
+            .filter_map(|player| {
+                let missing = self.due_reveals(player);
+                (!missing.is_empty()).then(|| Blame { player, missing })
+            })
+            .collect();
+
+        let mut hands = BTreeMap::new();
+        for (&seat, indices) in &self.contested {
+            if let Some(cards) = indices
+                .iter()
+                .map(|&index| self.open_card(index))
+                .collect::<Option<Result<Vec<_>, _>>>()
+            {
+                let cards = cards?
+                    .iter()
+                    .map(|card| {
+                        lookup
+                            .classic(card)
+                            .ok_or(CardProtocolError::InvalidInput(InputError::UnknownCard))
+                    })
+                    .collect::<Result<Vec<_>, _>>()?;
+                hands.insert(seat, cards);
+            }
+        }
+
+        Ok(ShowdownOutcome { hands, blame })
+    }
+
+    /// Unmask the card at `index`, or `None` if a token is missing.
+    fn open_card(&self, index: usize) -> Option<Result<Card<C>, CardProtocolError>> {
+        let aggregate_token = self.tokens[&index]
+            .iter()
+            .try_fold(RevealToken::<C>::zero(), |acc, token| {
+                token.map(|token| acc + token)
+            })?;
+
+        Some(aggregate_token.reveal(&self.deck[index]))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{Blame, Showdown};
+    use crate::crypto_primitives::utils::rand::test_rng;
+    use crate::discrete_log_cards;
+    use crate::discrete_log_cards::deal::DealPlan;
+    use crate::discrete_log_cards::encoding::CardLookup;
+    use crate::discrete_log_cards::reveal::reveal_cards;
+    use crate::discrete_log_cards::starting_deck;
+    use crate::discrete_log_cards::test_helpers::setup_players;
+    use crate::error::{CardProtocolError, InputError, VerificationError};
+    use crate::playing_card::ClassicPlayingCard;
+    use crate::BarnettSmartProtocol;
+
+    // Choose elliptic curve setting
+    type Curve = starknet_curve::Projective;
+
+    // Instantiate concrete type for our card protocol
+    type CardProtocol<'a> = discrete_log_cards::DLCards<'a, Curve>;
+
+    const SESSION_ID: &[u8] = b"showdown test";
+
+    #[test]
+    fn test_showdown() {
+        let rng = &mut test_rng();
+        let m = 4;
+        let n = 13;
+        let num_of_players = 3;
+
+        let parameters = CardProtocol::setup(rng, m, n).unwrap();
+        let (keys, aggregate_key) = setup_players(rng, &parameters, num_of_players).unwrap();
+        let public_keys = keys.iter().map(|(pk, _, _)| *pk).collect::<Vec<_>>();
+
+        // Cards in the clear, masked under the aggregate key
+        let lookup = CardLookup::standard().unwrap();
+        let deck = starting_deck(&parameters, &aggregate_key, lookup.cards()).unwrap();
+        let plan = DealPlan::texas_holdem(num_of_players).unwrap();
+
+        // Seat 1 folded: seats 0 and 2 are contested
+        let contested = [0, 2];
+        let mut showdown = Showdown::new(
+            SESSION_ID,
+            public_keys.clone(),
+            deck.clone(),
+            &plan,
+            &contested,
+        )
+        .unwrap();
+        let mut contested_indices = [plan.hole_cards(0), plan.hole_cards(2)].concat();
+        contested_indices.sort_unstable();
+
+        // The folded hand is not opened, and tokens must be made with the sender's key
+        let (_, sk, _) = &keys[0];
+        let folded = reveal_cards(
+            rng,
+            &parameters,
+            SESSION_ID,
+            &plan,
+            plan.hole_cards(1),
+            sk,
+            &public_keys[0],
+            &deck,
+        )
+        .unwrap();
+        assert_eq!(
+            showdown.receive(&parameters, 0, folded),
+            Err(CardProtocolError::InvalidInput(
+                InputError::UnexpectedReveal(plan.hole_cards(1)[0])
+            ))
+        );
+        let forged = reveal_cards(
+            rng,
+            &parameters,
+            SESSION_ID,
+            &plan,
+            &contested_indices,
+            sk,
+            &public_keys[0],
+            &deck,
+        )
+        .unwrap();
+        assert!(matches!(
+            showdown.receive(&parameters, 2, forged),
+            Err(CardProtocolError::VerificationFailed(
+                VerificationError::PlayerMessage {
+                    player: 2,
+                    source: Some(_)
+                }
+            ))
+        ));
+
+        // The folded player withholds their tokens
+        for player in [0, 2] {
+            let (pk, sk, _) = &keys[player];
+            let tokens = reveal_cards(
+                rng,
+                &parameters,
+                SESSION_ID,
+                &plan,
+                &showdown.due_reveals(player),
+                sk,
+                pk,
+                &deck,
+            )
+            .unwrap();
+            showdown.receive(&parameters, player, tokens).unwrap();
+        }
+        assert!(!showdown.is_complete());
+
+        let outcome = showdown.finalize(&lookup).unwrap();
+        assert!(outcome.hands.is_empty());
+        assert_eq!(
+            outcome.blame,
+            vec![Blame {
+                player: 1,
+                missing: contested_indices.clone()
+            }]
+        );
+
+        // Once they contribute, both contested hands open and nobody is blamed
+        let (pk, sk, _) = &keys[1];
+        let tokens = reveal_cards(
+            rng,
+            &parameters,
+            SESSION_ID,
+            &plan,
+            &contested_indices,
+            sk,
+            pk,
+            &deck,
+        )
+        .unwrap();
+        showdown.receive(&parameters, 1, tokens).unwrap();
+        assert!(showdown.is_complete());
+
+        let outcome = showdown.finalize(&lookup).unwrap();
+        assert!(outcome.blame.is_empty());
+        assert_eq!(outcome.hands.keys().copied().collect::<Vec<_>>(), contested);
+        for seat in contested {
+            let expected = plan
+                .hole_cards(seat)
+                .iter()
+                .map(|&index| ClassicPlayingCard::from_standard_position(index).unwrap())
+                .collect::<Vec<_>>();
+            assert_eq!(outcome.hands[&seat], expected);
+        }
+    }
+}
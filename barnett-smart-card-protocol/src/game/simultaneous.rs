@@ -0,0 +1,329 @@
+//! Cards played at the same time, as in Goofspiel or when every player leads a card at once.
+//!
+//! Over a network somebody always receives the others' choices first. In a [`SimultaneousPlay`]
+//! every player therefore commits to the position of the card they play, hashed with a random salt,
+//! and opens their choice only once everyone has committed, after which the chosen cards are
+//! revealed as usual. Commitments bind the session context, the round and the seat, so a
+//! commitment copied from another player or replayed from another round cannot be opened.
+//!
+//! An opening that does not match the commitment is rejected and kept as [`OpeningEvidence`],
+//! which anyone can check against the commitment. A player who committed but does not open is
+//! named when the round is finalized.
+
+use crate::error::{CardProtocolError, InputError, VerificationError};
+
+use blake2::{Blake2s, Digest};
+
+const PLAY_COMMITMENT_SEED: &'static [u8] = b"Simultaneous Play";
+
+/// Hash commitment to the position of a played card.
+pub type PlayCommitment = [u8; 32];
+
+/// Commit to playing the card at `index` of the deck as `player`, in round `round` of the game
+/// `context`. `salt` must be random and kept secret until the opening.
+pub fn commit_to_play(
+    context: &[u8],
+    round: u64,
+    player: usize,
+    index: usize,
+    salt: &[u8; 32],
+) -> PlayCommitment {
+    let mut hasher = Blake2s::new();
+    hasher.update(PLAY_COMMITMENT_SEED);
+    hasher.update(&(context.len() as u64).to_le_bytes());
+    hasher.update(context);
+    hasher.update(&round.to_le_bytes());
+    hasher.update(&(player as u64).to_le_bytes());
+    hasher.update(&(index as u64).to_le_bytes());
+    hasher.update(salt);
+
+    let mut commitment = [0u8; 32];
+    commitment.copy_from_slice(&hasher.finalize());
+
+    commitment
+}
+
+/// An opening of `player` that does not match their commitment.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct OpeningEvidence {
+    pub round: u64,
+    pub player: usize,
+    pub commitment: PlayCommitment,
+    pub index: usize,
+    pub salt: [u8; 32],
+}
+
+impl OpeningEvidence {
+    /// Check the evidence in the game `context`. Returns the seat of the player at fault, or
+    /// `UnfoundedBlame` if the opening does match the commitment.
+    pub fn verify(&self, context: &[u8]) -> Result<usize, CardProtocolError> {
+        let opened = commit_to_play(context, self.round, self.player, self.index, &self.salt);
+        if opened == self.commitment {
+            return Err(VerificationError::UnfoundedBlame.into());
+        }
+
+        Ok(self.player)
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct SimultaneousPlay {
+    context: Vec<u8>,
+    round: u64,
+    /// Positions of the cards each player may play
+    hands: Vec<Vec<usize>>,
+    commitments: Vec<Option<PlayCommitment>>,
+    plays: Vec<Option<usize>>,
+    evidence: Vec<OpeningEvidence>,
+}
+
+impl SimultaneousPlay {
+    /// Start round `round` of the game `context`, in which player `i` plays one of the cards at
+    /// the positions `hands[i]`.
+    pub fn new(
+        context: &[u8],
+        round: u64,
+        hands: Vec<Vec<usize>>,
+    ) -> Result<Self, CardProtocolError> {
+        let num_players = hands.len();
+        if num_players == 0 {
+            return Err(InputError::InvalidPlayerCount(num_players).into());
+        }
+
+        Ok(Self {
+            context: context.to_vec(),
+            round,
+            hands,
+            commitments: vec![None; num_players],
+            plays: vec![None; num_players],
+            evidence: Vec::new(),
+        })
+    }
+
+    pub fn round(&self) -> u64 {
+        self.round
+    }
+
+    /// Whether every player has committed, after which plays may be opened.
+    pub fn is_committed(&self) -> bool {
+        self.commitments.iter().all(Option::is_some)
+    }
+
+    /// Record the commitment of `player`. A player commits once; a second commitment is rejected
+    /// and the first one stands.
+    pub fn commit(
+        &mut self,
+        player: usize,
+        commitment: PlayCommitment,
+    ) -> Result<(), CardProtocolError> {
+        let slot = self
+            .commitments
+            .get_mut(player)
+            .ok_or(CardProtocolError::InvalidInput(InputError::UnknownPlayer(
+                player,
+            )))?;
+
+        if slot.is_some() {
+            return Err(InputError::DuplicateMessage(player).into());
+        }
+
+        *slot = Some(commitment);
+
+        Ok(())
+    }
+
+    /// Open the play of `player`: the position `index` of their card and the salt it was committed
+    /// with. An opening that does not match the commitment is rejected and kept as evidence.
+    pub fn open(
+        &mut self,
+        player: usize,
+        index: usize,
+        salt: [u8; 32],
+    ) -> Result<(), CardProtocolError> {
+        if !self.is_committed() {
+            return Err(InputError::UnexpectedPhase.into());
+        }
+
+        let commitment = match self.commitments.get(player) {
+            Some(Some(commitment)) => *commitment,
+            _ => return Err(InputError::UnknownPlayer(player).into()),
+        };
+
+        if self.plays[player].is_some() {
+            return Err(InputError::DuplicateMessage(player).into());
+        }
+
+        if commit_to_play(&self.context, self.round, player, index, &salt) != commitment {
+            self.evidence.push(OpeningEvidence {
+                round: self.round,
+                player,
+                commitment,
+                index,
+                salt,
+            });
+
+            return Err(VerificationError::PlayerMessage {
+                player,
+                source: None,
+            }
+            .into());
+        }
+
+        if !self.hands[player].contains(&index) {
+            return Err(InputError::CardNotInHand {
+                seat: player,
+                index,
+            }
+            .into());
+        }
+
+        self.plays[player] = Some(index);
+
+        Ok(())
+    }
+
+    /// Openings rejected so far, in the order they were received.
+    pub fn evidence(&self) -> &[OpeningEvidence] {
+        &self.evidence
+    }
+
+    /// Players who have not opened a valid play yet, in increasing order.
+    pub fn missing_openings(&self) -> Vec<usize> {
+        self.plays
+            .iter()
+            .enumerate()
+            .filter(|(_, play)| play.is_none())
+            .map(|(player, _)| player)
+            .collect()
+    }
+
+    /// The positions of the played cards, by seat, to be revealed next. Fails with the players who
+    /// have not opened a valid play.
+    pub fn finalize(&self) -> Result<Vec<usize>, CardProtocolError> {
+        let missing = self.missing_openings();
+        if !missing.is_empty() {
+            return Err(InputError::MissingReveals(missing).into());
+        }
+
+        Ok(self.plays.iter().flatten().copied().collect())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{commit_to_play, SimultaneousPlay};
+    use crate::crypto_primitives::utils::rand::test_rng;
+    use crate::error::{CardProtocolError, InputError, VerificationError};
+
+    use rand::Rng;
+
+    const SESSION_ID: &[u8] = b"simultaneous play test";
+
+    #[test]
+    fn test_three_player_round() {
+        let rng = &mut test_rng();
+        let num_of_players = 3;
+        let round = 4;
+
+        // Four cards each, dealt one at a time around the table
+        let hands = (0..num_of_players)
+            .map(|player| (0..4).map(|k| k * num_of_players + player).collect())
+            .collect::<Vec<Vec<_>>>();
+        let choices = [hands[0][2], hands[1][0], hands[2][3]];
+        let salts = (0..num_of_players)
+            .map(|_| rng.gen::<[u8; 32]>())
+            .collect::<Vec<_>>();
+
+        let mut play = SimultaneousPlay::new(SESSION_ID, round, hands.clone()).unwrap();
+        for player in 0..num_of_players {
+            // Nobody opens before everyone has committed
+            assert_eq!(
+                play.open(player, choices[player], salts[player]),
+                Err(CardProtocolError::InvalidInput(InputError::UnexpectedPhase))
+            );
+
+            let commitment =
+                commit_to_play(SESSION_ID, round, player, choices[player], &salts[player]);
+            play.commit(player, commitment).unwrap();
+            assert_eq!(
+                play.commit(player, commitment),
+                Err(CardProtocolError::InvalidInput(
+                    InputError::DuplicateMessage(player)
+                ))
+            );
+        }
+
+        // Player 1 sees the card of player 0 and tries to play another one
+        play.open(0, choices[0], salts[0]).unwrap();
+        let other = hands[1][3];
+        assert_eq!(
+            play.open(1, other, salts[1]),
+            Err(CardProtocolError::VerificationFailed(
+                VerificationError::PlayerMessage {
+                    player: 1,
+                    source: None
+                }
+            ))
+        );
+        assert_eq!(play.evidence().len(), 1);
+        assert_eq!(play.evidence()[0].index, other);
+        assert_eq!(play.evidence()[0].verify(SESSION_ID), Ok(1));
+
+        // Player 2 has not opened yet
+        assert_eq!(
+            play.finalize(),
+            Err(CardProtocolError::InvalidInput(InputError::MissingReveals(
+                vec![1, 2]
+            )))
+        );
+
+        play.open(1, choices[1], salts[1]).unwrap();
+        play.open(2, choices[2], salts[2]).unwrap();
+        assert_eq!(
+            play.open(2, choices[2], salts[2]),
+            Err(CardProtocolError::InvalidInput(
+                InputError::DuplicateMessage(2)
+            ))
+        );
+        assert_eq!(play.finalize(), Ok(choices.to_vec()));
+
+        // A commitment only opens in its round and for its player
+        let mut next = SimultaneousPlay::new(SESSION_ID, round + 1, hands.clone()).unwrap();
+        for player in 0..num_of_players {
+            let commitment =
+                commit_to_play(SESSION_ID, round, player, choices[player], &salts[player]);
+            next.commit(player, commitment).unwrap();
+        }
+        assert_eq!(
+            next.open(0, choices[0], salts[0]),
+            Err(CardProtocolError::VerificationFailed(
+                VerificationError::PlayerMessage {
+                    player: 0,
+                    source: None
+                }
+            ))
+        );
+
+        // A valid opening of a card the player does not hold is not a play
+        let salt = rng.gen::<[u8; 32]>();
+        let mut stolen = SimultaneousPlay::new(SESSION_ID, round, hands).unwrap();
+        for player in 0..num_of_players {
+            let index = if player == 0 {
+                choices[1]
+            } else {
+                choices[player]
+            };
+            let salt = if player == 0 { salt } else { salts[player] };
+            let commitment = commit_to_play(SESSION_ID, round, player, index, &salt);
+            stolen.commit(player, commitment).unwrap();
+        }
+        assert_eq!(
+            stolen.open(0, choices[1], salt),
+            Err(CardProtocolError::InvalidInput(InputError::CardNotInHand {
+                seat: 0,
+                index: choices[1]
+            }))
+        );
+        assert!(stolen.evidence().is_empty());
+    }
+}
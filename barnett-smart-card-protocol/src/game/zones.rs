@@ -0,0 +1,400 @@
+//! Visibility of every card of the deck, for games that mix face-down piles, private hands and
+//! face-up cards, such as the dummy hand of bridge or the tableau of solitaire variants.
+//!
+//! [`Zones`] records, for every position of the shuffled deck, who has seen the card: nobody, one
+//! seat, or everyone. Cards only ever become more visible. Dealing a hidden card to a seat asks
+//! the other players for their reveal tokens, opening a card to everyone unmasks it from a token of
+//! every player, and leaving a hidden card hidden needs nothing. A card that a seat has seen can
+//! still be opened to everyone, but it can neither be hidden again nor given to another seat, and
+//! a card that is face up stays face up.
+
+use crate::crypto_primitives::utils::format::{Compact, Hex};
+use crate::discrete_log_cards::reveal::RevealProof;
+use crate::discrete_log_cards::{Card, DLCards, MaskedCard, Parameters, PublicKey, RevealToken};
+use crate::error::{CardProtocolError, InputError};
+use crate::game::draw::RevealRequest;
+use crate::{BarnettSmartProtocol, Reveal};
+
+use ark_ec::ProjectiveCurve;
+use ark_ff::Zero;
+use ark_std::fmt;
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum Visibility<C: ProjectiveCurve> {
+    /// Nobody has seen the card
+    Hidden,
+    /// Only the player in this seat has seen the card
+    PrivateTo(usize),
+    /// The card is face up for everyone
+    PublicRevealed(Card<C>),
+}
+
+impl<C: ProjectiveCurve> fmt::Debug for Visibility<C> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Hidden => f.write_str("Hidden"),
+            Self::PrivateTo(seat) => f.debug_tuple("PrivateTo").field(seat).finish(),
+            Self::PublicRevealed(card) => f
+                .debug_tuple("PublicRevealed")
+                .field(&Compact(card))
+                .finish(),
+        }
+    }
+}
+
+pub struct Zones<C: ProjectiveCurve> {
+    /// Keys of the players, in seat order
+    keys: Vec<PublicKey<C>>,
+    deck: Vec<MaskedCard<C>>,
+    visibility: Vec<Visibility<C>>,
+}
+
+impl<C: ProjectiveCurve> fmt::Debug for Zones<C> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Zones")
+            .field("keys", &self.keys.iter().map(Hex).collect::<Vec<_>>())
+            .field("deck", &Compact(&self.deck))
+            .field("visibility", &self.visibility)
+            .finish()
+    }
+}
+
+impl<C: ProjectiveCurve> Zones<C> {
+    /// Track the final shuffled `deck` of a game among the players with keys `keys`, in seat
+    /// order. Every card starts hidden.
+    pub fn new(
+        keys: Vec<PublicKey<C>>,
+        deck: Vec<MaskedCard<C>>,
+    ) -> Result<Self, CardProtocolError> {
+        if keys.is_empty() {
+            return Err(InputError::InvalidPlayerCount(keys.len()).into());
+        }
+
+        Ok(Self {
+            keys,
+            visibility: vec![Visibility::Hidden; deck.len()],
+            deck,
+        })
+    }
+
+    /// Visibility of the card at `index`, or `None` past the end of the deck.
+    pub fn visibility(&self, index: usize) -> Option<Visibility<C>> {
+        self.visibility.get(index).copied()
+    }
+
+    /// Positions of the cards nobody has seen.
+    pub fn hidden(&self) -> Vec<usize> {
+        self.positions(|visibility| *visibility == Visibility::Hidden)
+    }
+
+    /// Positions of the cards only `seat` has seen.
+    pub fn private_to(&self, seat: usize) -> Vec<usize> {
+        self.positions(|visibility| *visibility == Visibility::PrivateTo(seat))
+    }
+
+    /// Positions of the face-up cards, with the cards.
+    pub fn public(&self) -> Vec<(usize, Card<C>)> {
+        self.visibility
+            .iter()
+            .enumerate()
+            .filter_map(|(index, visibility)| match visibility {
+                Visibility::PublicRevealed(card) => Some((index, *card)),
+                _ => None,
+            })
+            .collect()
+    }
+
+    /// Keep the card at `index` hidden, as when it moves between face-down piles. Nothing is
+    /// revealed, and a card that somebody has seen cannot be hidden again.
+    pub fn keep_hidden(&self, index: usize) -> Result<(), CardProtocolError> {
+        match self.get(index)? {
+            Visibility::Hidden => Ok(()),
+            _ => Err(CardProtocolError::InvalidInput(
+                InputError::IllegalTransition(index),
+            )),
+        }
+    }
+
+    /// Deal the hidden card at `index` to `seat`. Returns the reveal tokens `seat` needs from the
+    /// other players to see it.
+    pub fn deal_private(
+        &mut self,
+        index: usize,
+        seat: usize,
+    ) -> Result<RevealRequest, CardProtocolError> {
+        if seat >= self.keys.len() {
+            return Err(InputError::UnknownPlayer(seat).into());
+        }
+
+        if self.get(index)? != Visibility::Hidden {
+            return Err(InputError::IllegalTransition(index).into());
+        }
+
+        self.visibility[index] = Visibility::PrivateTo(seat);
+
+        Ok(RevealRequest {
+            index,
+            owner: seat,
+            from: (0..self.keys.len())
+                .filter(|&player| player != seat)
+                .collect(),
+        })
+    }
+
+    /// Turn the card at `index` face up from `tokens`, one reveal token of every player with the
+    /// seat it comes from. The card may be hidden or private to a seat.
+    pub fn reveal_public(
+        &mut self,
+        pp: &Parameters<C>,
+        context: &[u8],
+        index: usize,
+        tokens: &[(usize, RevealToken<C>, RevealProof<C>)],
+    ) -> Result<Card<C>, CardProtocolError> {
+        if let Visibility::PublicRevealed(_) = self.get(index)? {
+            return Err(InputError::IllegalTransition(index).into());
+        }
+
+        let mut seen = vec![false; self.keys.len()];
+        let mut aggregate_token = RevealToken::<C>::zero();
+        for (player, token, proof) in tokens {
+            match seen.get_mut(*player) {
+                Some(seen) if !*seen => *seen = true,
+                Some(_) => return Err(InputError::DuplicateMessage(*player).into()),
+                None => return Err(InputError::UnknownPlayer(*player).into()),
+            }
+
+            DLCards::<C>::verify_reveal(
+                pp,
+                context,
+                &self.keys[*player],
+                token,
+                &self.deck[index],
+                proof,
+            )
+            .map_err(|source| CardProtocolError::verification_failed(*player, source))?;
+
+            aggregate_token = aggregate_token + *token;
+        }
+
+        let missing = (0..self.keys.len())
+            .filter(|&player| !seen[player])
+            .collect::<Vec<_>>();
+        if !missing.is_empty() {
+            return Err(InputError::MissingReveals(missing).into());
+        }
+
+        let card = aggregate_token.reveal(&self.deck[index])?;
+        self.visibility[index] = Visibility::PublicRevealed(card);
+
+        Ok(card)
+    }
+
+    fn get(&self, index: usize) -> Result<Visibility<C>, CardProtocolError> {
+        self.visibility(index)
+            .ok_or(CardProtocolError::InvalidInput(InputError::InvalidDeal(
+                self.deck.len(),
+            )))
+    }
+
+    fn positions(&self, filter: impl Fn(&Visibility<C>) -> bool) -> Vec<usize> {
+        self.visibility
+            .iter()
+            .enumerate()
+            .filter(|(_, visibility)| filter(visibility))
+            .map(|(index, _)| index)
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{Visibility, Zones};
+    use crate::crypto_primitives::utils::rand::test_rng;
+    use crate::discrete_log_cards;
+    use crate::discrete_log_cards::encoding::encode_standard_deck;
+    use crate::discrete_log_cards::starting_deck;
+    use crate::discrete_log_cards::test_helpers::setup_players;
+    use crate::error::{CardProtocolError, InputError};
+    use crate::game::draw::RevealRequest;
+    use crate::BarnettSmartProtocol;
+
+    use std::collections::HashSet;
+
+    // Choose elliptic curve setting
+    type Curve = starknet_curve::Projective;
+
+    // Instantiate concrete type for our card protocol
+    type CardProtocol<'a> = discrete_log_cards::DLCards<'a, Curve>;
+
+    const SESSION_ID: &[u8] = b"zones test";
+
+    fn assert_invariants(zones: &Zones<Curve>, num_of_players: usize, num_cards: usize) {
+        let mut positions = zones.hidden();
+        for seat in 0..num_of_players {
+            positions.extend(zones.private_to(seat));
+        }
+        let public = zones.public();
+        positions.extend(public.iter().map(|(index, _)| *index));
+
+        // Every card is in exactly one zone, and face-up cards are all different
+        assert_eq!(positions.len(), num_cards);
+        assert_eq!(positions.iter().collect::<HashSet<_>>().len(), num_cards);
+        assert_eq!(
+            public
+                .iter()
+                .map(|(_, card)| card)
+                .collect::<HashSet<_>>()
+                .len(),
+            public.len()
+        );
+    }
+
+    #[test]
+    fn test_scripted_game() {
+        let rng = &mut test_rng();
+        let m = 4;
+        let n = 13;
+        let num_of_players = 3;
+
+        let parameters = CardProtocol::setup(rng, m, n).unwrap();
+        let (keys, aggregate_key) = setup_players(rng, &parameters, num_of_players).unwrap();
+        let public_keys = keys.iter().map(|(pk, _, _)| *pk).collect::<Vec<_>>();
+
+        let cards = encode_standard_deck(1).unwrap();
+        let (deck, _) = CardProtocol::shuffle_and_remask_with_rng(
+            rng,
+            &parameters,
+            SESSION_ID,
+            &aggregate_key,
+            &starting_deck(&parameters, &aggregate_key, &cards).unwrap(),
+        )
+        .unwrap();
+        let mut zones = Zones::new(public_keys, deck.clone()).unwrap();
+        assert_invariants(&zones, num_of_players, cards.len());
+
+        let mut tokens_for = |index: usize, players: Vec<usize>| {
+            players
+                .into_iter()
+                .map(|player| {
+                    let (pk, sk, _) = &keys[player];
+                    let (token, proof) = CardProtocol::compute_reveal_token(
+                        rng,
+                        &parameters,
+                        SESSION_ID,
+                        sk,
+                        pk,
+                        &deck[index],
+                    )
+                    .unwrap();
+                    (player, token, proof)
+                })
+                .collect::<Vec<_>>()
+        };
+
+        // A hand for every seat, and the dummy hand of seat 2 laid face up
+        for index in 0..6 {
+            let seat = index % num_of_players;
+            assert_eq!(
+                zones.deal_private(index, seat),
+                Ok(RevealRequest {
+                    index,
+                    owner: seat,
+                    from: (0..num_of_players).filter(|&p| p != seat).collect(),
+                })
+            );
+        }
+        assert_eq!(zones.private_to(2), vec![2, 5]);
+        for index in zones.private_to(2) {
+            let card = zones
+                .reveal_public(
+                    &parameters,
+                    SESSION_ID,
+                    index,
+                    &tokens_for(index, vec![0, 1, 2]),
+                )
+                .unwrap();
+            assert!(cards.contains(&card));
+            assert_eq!(
+                zones.visibility(index),
+                Some(Visibility::PublicRevealed(card))
+            );
+        }
+        assert_invariants(&zones, num_of_players, cards.len());
+
+        // A card turned from the face-down stock needs a token of every player
+        let stock_card = 10;
+        assert_eq!(Ok(()), zones.keep_hidden(stock_card));
+        let mut tokens = tokens_for(stock_card, vec![2, 0, 1]);
+        let last = tokens.pop().unwrap();
+        assert_eq!(
+            zones.reveal_public(&parameters, SESSION_ID, stock_card, &tokens),
+            Err(CardProtocolError::InvalidInput(InputError::MissingReveals(
+                vec![1]
+            )))
+        );
+        tokens.push(tokens[0].clone());
+        assert_eq!(
+            zones.reveal_public(&parameters, SESSION_ID, stock_card, &tokens),
+            Err(CardProtocolError::InvalidInput(
+                InputError::DuplicateMessage(2)
+            ))
+        );
+        tokens.pop();
+        tokens.push(last);
+        zones
+            .reveal_public(&parameters, SESSION_ID, stock_card, &tokens)
+            .unwrap();
+        assert_eq!(zones.hidden().len(), cards.len() - 7);
+        assert_invariants(&zones, num_of_players, cards.len());
+
+        // Cards never become less visible
+        assert_eq!(
+            zones.keep_hidden(0),
+            Err(CardProtocolError::InvalidInput(
+                InputError::IllegalTransition(0)
+            ))
+        );
+        assert_eq!(
+            zones.deal_private(0, 1),
+            Err(CardProtocolError::InvalidInput(
+                InputError::IllegalTransition(0)
+            ))
+        );
+        assert_eq!(
+            zones.keep_hidden(stock_card),
+            Err(CardProtocolError::InvalidInput(
+                InputError::IllegalTransition(stock_card)
+            ))
+        );
+        assert_eq!(
+            zones.deal_private(stock_card, 0),
+            Err(CardProtocolError::InvalidInput(
+                InputError::IllegalTransition(stock_card)
+            ))
+        );
+        assert_eq!(
+            zones.reveal_public(
+                &parameters,
+                SESSION_ID,
+                stock_card,
+                &tokens_for(stock_card, vec![0, 1, 2])
+            ),
+            Err(CardProtocolError::InvalidInput(
+                InputError::IllegalTransition(stock_card)
+            ))
+        );
+        assert_eq!(
+            zones.deal_private(11, num_of_players),
+            Err(CardProtocolError::InvalidInput(InputError::UnknownPlayer(
+                num_of_players
+            )))
+        );
+        assert_eq!(
+            zones.keep_hidden(cards.len()),
+            Err(CardProtocolError::InvalidInput(InputError::InvalidDeal(
+                cards.len()
+            )))
+        );
+        assert_invariants(&zones, num_of_players, cards.len());
+    }
+}
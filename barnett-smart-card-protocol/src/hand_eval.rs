@@ -0,0 +1,231 @@
+//! Poker hand evaluation over opened cards.
+//!
+//! Cards are [`ClassicPlayingCard`]s, so an opened card goes through
+//! [`ClassicPlayingCard::try_from_card`] before being evaluated. A [`HandRank`] orders hands from
+//! high card up to straight flush and, within a category, by the ranks that break ties, so
+//! comparing two ranks with `Ord` decides a showdown. Suits never break ties.
+
+use crate::playing_card::{ClassicPlayingCard, Rank};
+
+/// Categories of poker hands, from lowest to highest.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum HandCategory {
+    HighCard,
+    OnePair,
+    TwoPair,
+    ThreeOfAKind,
+    Straight,
+    Flush,
+    FullHouse,
+    FourOfAKind,
+    StraightFlush,
+}
+
+/// Strength of a five-card hand. Ranks compare by category first and then by the ranks that
+/// break ties within it, most significant first.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct HandRank {
+    category: HandCategory,
+    /// Ranks of the groups of equal cards, larger groups first, then higher ranks first. A
+    /// straight is only described by its highest card, which is the five in an ace-low straight.
+    /// Unused entries are `Two`, the same for every hand of the category.
+    tiebreak: [Rank; 5],
+}
+
+impl HandRank {
+    pub fn category(&self) -> HandCategory {
+        self.category
+    }
+}
+
+/// Rank of the best five-card hand among seven cards, such as two hole cards and the board.
+pub fn evaluate_7(cards: &[ClassicPlayingCard; 7]) -> HandRank {
+    let mut best = evaluate_5(&[cards[0], cards[1], cards[2], cards[3], cards[4]]);
+
+    // Every five-card hand leaves out two of the seven cards
+    for skip_a in 0..7 {
+        for skip_b in skip_a + 1..7 {
+            let mut hand = [cards[0]; 5];
+            let kept = (0..7).filter(|&i| i != skip_a && i != skip_b);
+            for (card, i) in hand.iter_mut().zip(kept) {
+                *card = cards[i];
+            }
+
+            best = best.max(evaluate_5(&hand));
+        }
+    }
+
+    best
+}
+
+/// Rank of a five-card hand.
+pub fn evaluate_5(cards: &[ClassicPlayingCard; 5]) -> HandRank {
+    let is_flush = cards.iter().all(|card| card.suit() == cards[0].suit());
+
+    // Groups of equal ranks as (size, rank), larger groups first, then higher ranks first
+    let mut counts = [0usize; 13];
+    for card in cards {
+        counts[card.rank() as usize] += 1;
+    }
+    let mut groups = Rank::VALUES
+        .iter()
+        .filter(|rank| counts[**rank as usize] > 0)
+        .map(|rank| (counts[*rank as usize], *rank))
+        .collect::<Vec<_>>();
+    groups.sort_unstable_by(|a, b| b.cmp(a));
+
+    let straight_high = if groups.len() == 5 {
+        let (high, low) = (groups[0].1, groups[4].1);
+        if high as usize - low as usize == 4 {
+            Some(high)
+        } else if high == Rank::Ace && groups[1].1 == Rank::Five {
+            // The wheel, A-2-3-4-5, where the ace plays low
+            Some(Rank::Five)
+        } else {
+            None
+        }
+    } else {
+        None
+    };
+
+    let mut tiebreak = [Rank::Two; 5];
+    if let Some(high) = straight_high {
+        tiebreak[0] = high;
+    } else {
+        for (slot, (_, rank)) in tiebreak.iter_mut().zip(&groups) {
+            *slot = *rank;
+        }
+    }
+
+    let category = match (straight_high.is_some(), is_flush, groups[0].0, groups.len()) {
+        (true, true, _, _) => HandCategory::StraightFlush,
+        (_, _, 4, _) => HandCategory::FourOfAKind,
+        (_, _, 3, 2) => HandCategory::FullHouse,
+        (_, true, _, _) => HandCategory::Flush,
+        (true, _, _, _) => HandCategory::Straight,
+        (_, _, 3, _) => HandCategory::ThreeOfAKind,
+        (_, _, 2, 3) => HandCategory::TwoPair,
+        (_, _, 2, _) => HandCategory::OnePair,
+        _ => HandCategory::HighCard,
+    };
+
+    HandRank { category, tiebreak }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{evaluate_5, evaluate_7, HandCategory};
+    use crate::playing_card::ClassicPlayingCard;
+
+    use std::cmp::Ordering;
+
+    /// Cards from labels such as `"As Kd"`.
+    fn cards<const N: usize>(labels: &str) -> [ClassicPlayingCard; N] {
+        let cards = labels
+            .split_whitespace()
+            .map(|label| label.parse().unwrap())
+            .collect::<Vec<_>>();
+
+        cards.try_into().unwrap()
+    }
+
+    fn compare_7(a: &str, b: &str) -> Ordering {
+        evaluate_7(&cards(a)).cmp(&evaluate_7(&cards(b)))
+    }
+
+    #[test]
+    fn test_categories() {
+        let hands = [
+            ("2c 7d 9h Js Kc", HandCategory::HighCard),
+            ("2c 2d 9h Js Kc", HandCategory::OnePair),
+            ("2c 2d 9h 9s Kc", HandCategory::TwoPair),
+            ("2c 2d 2h Js Kc", HandCategory::ThreeOfAKind),
+            ("9c Td Jh Qs Kc", HandCategory::Straight),
+            ("2c 7c 9c Jc Kc", HandCategory::Flush),
+            ("2c 2d 2h Ks Kc", HandCategory::FullHouse),
+            ("2c 2d 2h 2s Kc", HandCategory::FourOfAKind),
+            ("9c Tc Jc Qc Kc", HandCategory::StraightFlush),
+        ];
+
+        for window in hands.windows(2) {
+            let (low, low_category) = window[0];
+            let (high, high_category) = window[1];
+            assert_eq!(evaluate_5(&cards(low)).category(), low_category);
+            assert_eq!(evaluate_5(&cards(high)).category(), high_category);
+            assert!(evaluate_5(&cards(low)) < evaluate_5(&cards(high)));
+        }
+    }
+
+    #[test]
+    fn test_straights() {
+        // The wheel is a five-high straight, below six-high and far below broadway
+        let wheel = evaluate_5(&cards("Ac 2d 3h 4s 5c"));
+        assert_eq!(wheel.category(), HandCategory::Straight);
+        assert!(wheel < evaluate_5(&cards("2c 3d 4h 5s 6c")));
+        assert!(wheel < evaluate_5(&cards("Tc Jd Qh Ks Ac")));
+        assert!(wheel > evaluate_5(&cards("Ac Ad Ah Ks Qc")));
+
+        // The ace does not wrap around
+        assert_eq!(
+            evaluate_5(&cards("Qc Kd Ah 2s 3c")).category(),
+            HandCategory::HighCard
+        );
+
+        let steel_wheel = evaluate_5(&cards("Ah 2h 3h 4h 5h"));
+        assert_eq!(steel_wheel.category(), HandCategory::StraightFlush);
+        assert!(steel_wheel < evaluate_5(&cards("2h 3h 4h 5h 6h")));
+        assert!(steel_wheel > evaluate_5(&cards("Ac Ad Ah As Kc")));
+
+        // Seven cards holding both a wheel and a six-high straight play the higher one
+        assert_eq!(
+            compare_7("Ac 2d 3h 4s 5c 6d Kh", "2c 3d 4h 5s 6c Jd Kh"),
+            Ordering::Equal
+        );
+    }
+
+    #[test]
+    fn test_tiebreaks() {
+        // Full house beats flush, and full houses compare trips first
+        assert_eq!(
+            compare_7("Kh Kd Ks 2c 2h 7h 9h", "Ah Qh 3h 7h 9h Kc 2d"),
+            Ordering::Greater
+        );
+        assert_eq!(
+            compare_7("Kh Kd Ks 2c 2h 7c 9d", "Qh Qd Qs Ac Ah 7c 9d"),
+            Ordering::Greater
+        );
+
+        // Two sets make a full house with the higher set as trips
+        let two_sets = evaluate_7(&cards("9c 9d 9h 4c 4d 4h Ks"));
+        assert_eq!(two_sets.category(), HandCategory::FullHouse);
+        assert_eq!(two_sets, evaluate_5(&cards("9c 9d 9h 4c 4d")));
+
+        // Flushes compare card by card, from the highest
+        assert_eq!(
+            compare_7("Ah Kh 9h 5h 3h 2c 2d", "Ah Kh 9h 5h 2h Qc Qd"),
+            Ordering::Greater
+        );
+
+        // Kickers break ties between equal pairs
+        assert_eq!(
+            compare_7("Ac Ad Kh 7s 5c 3d 2h", "Ac Ad Qh 7s 5c 3d 2h"),
+            Ordering::Greater
+        );
+
+        // Three pairs play the best two with the best remaining kicker
+        assert_eq!(
+            evaluate_7(&cards("Kc Kd 9h 9s 4c 4d 2h")),
+            evaluate_5(&cards("Kc Kd 9h 9s 4c"))
+        );
+
+        // When the board plays, suits do not break the tie
+        assert_eq!(
+            compare_7("2c 3d Ah Kh Qh Jh Th", "2s 3s Ac Kc Qc Jc Tc"),
+            Ordering::Equal
+        );
+        assert_eq!(
+            compare_7("2c 3d As Ks Qd Jc 9h", "4c 5d As Ks Qd Jc 9h"),
+            Ordering::Equal
+        );
+    }
+}
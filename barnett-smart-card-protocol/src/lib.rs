@@ -1,17 +1,50 @@
-use crate::error::CardProtocolError;
+// Panics in a library embedded in a long-running server are unacceptable: report failures instead.
+// Data from callers is checked with errors; `debug_assert!` is only for invariants the crate keeps
+#![cfg_attr(not(test), deny(clippy::unwrap_used))]
+// Without `std`, only the primitives and the protocol are built, for verifiers that only have alloc
+#![cfg_attr(not(feature = "std"), no_std)]
+
+#[cfg(not(feature = "alloc"))]
+compile_error!("enable the `alloc` feature, or `std`, which implies it");
+
+use crate::crypto_primitives::permutation::sample_permutation;
+use crate::crypto_primitives::utils::rand::{
+    sample_nonzero, sample_nonzero_vector, RandomnessGuard,
+};
+use crate::crypto_primitives::utils::secret::{SecretScalar, SecretScalars};
+use crate::error::{CardProtocolError, ShuffleVerificationFailure};
 
 use ark_ff::{Field, ToBytes};
 use ark_serialize::{CanonicalDeserialize, CanonicalSerialize};
-use ark_std::rand::Rng;
+use ark_std::hash::Hash;
+use ark_std::ops::{Add, Mul};
+use ark_std::rand::{CryptoRng, Rng};
+use ark_std::vec::Vec;
+use blake2::{Blake2s, Digest};
 use proof_essentials::error::CryptoError;
 use proof_essentials::homomorphic_encryption::HomomorphicEncryptionScheme;
 use proof_essentials::utils::permutation::Permutation;
 use proof_essentials::vector_commitment::HomomorphicCommitmentScheme;
-use std::hash::Hash;
-use std::ops::{Add, Mul};
 
+#[cfg(feature = "conformance")]
+pub mod conformance;
+pub mod crypto_primitives;
 pub mod discrete_log_cards;
 pub mod error;
+#[cfg(feature = "std")]
+pub mod export;
+#[cfg(feature = "game")]
+pub mod game;
+#[cfg(feature = "hand-eval")]
+pub mod hand_eval;
+pub mod playing_card;
+#[cfg(feature = "proto")]
+pub mod proto;
+#[cfg(feature = "rpc")]
+pub mod rpc;
+mod telemetry;
+#[cfg(feature = "wasm")]
+pub mod wasm;
 
 pub trait Mask<Scalar: Field, Enc: HomomorphicEncryptionScheme<Scalar>> {
     fn mask(
@@ -35,9 +68,25 @@ pub trait Reveal<F: Field, Enc: HomomorphicEncryptionScheme<F>> {
     fn reveal(&self, cipher: &Enc::Ciphertext) -> Result<Enc::Plaintext, CardProtocolError>;
 }
 
+const KEY_LIST_SEED: &'static [u8] = b"Key List";
+
+/// Digest of the ordered list of the players' public keys, see
+/// [`BarnettSmartProtocol::key_list_digest`].
+pub type KeyListDigest = [u8; 32];
+
+/// Canonical encoding of a card from a human-readable label, so that independent applications
+/// agree on which group element stands for which card.
+pub trait FromLabel: Sized {
+    fn from_label(label: &[u8]) -> Result<Self, CardProtocolError>;
+}
+
 /// Mental Poker protocol based on the one described by Barnett and Smart (2003).
 /// The protocol has been modified to make use of the argument of a correct shuffle presented
 /// by Bayer and Groth (2014).
+///
+/// Every proof is bound to a `context`, which should uniquely identify the game (for example its
+/// `discrete_log_cards::session_id::SessionId`). Proofs only verify under the context they were
+/// produced for, so a proof from one game cannot be replayed in another.
 pub trait BarnettSmartProtocol {
     // Cryptography
     type Scalar: Field;
@@ -70,55 +119,141 @@ pub trait BarnettSmartProtocol {
     type ZKProofReveal: CanonicalDeserialize + CanonicalSerialize;
     type ZKProofShuffle: CanonicalDeserialize + CanonicalSerialize;
 
-    /// Randomly produce the scheme parameters
-    fn setup<R: Rng>(
+    /// Produce the scheme parameters for a deck of `m` rows of `n` columns. Parameters that could
+    /// hide a trapdoor are derived from public labels, so that `verify_parameters` can check
+    /// them, and anything else may be sampled from `rng`. Shapes the shuffle argument does not
+    /// support are rejected here.
+    fn setup<R: Rng + CryptoRng>(
         rng: &mut R,
         m: usize,
         n: usize,
     ) -> Result<Self::Parameters, CardProtocolError>;
 
+    /// Check that the parameters hide no trapdoor, i.e. that nobody can know a relation between
+    /// the generators the shuffle argument relies on. Parameters whose generators were sampled
+    /// rather than derived cannot be checked and are rejected as unverifiable, and so are
+    /// parameters for a shape that `setup` would have rejected.
+    fn verify_parameters(pp: &Self::Parameters) -> Result<(), CardProtocolError>;
+
     /// Generate keys for a player.
-    fn player_keygen<R: Rng>(
+    fn player_keygen<R: Rng + CryptoRng>(
         rng: &mut R,
         pp: &Self::Parameters,
     ) -> Result<(Self::PlayerPublicKey, Self::PlayerSecretKey), CardProtocolError>;
 
-    /// Prove in zero knowledge that the owner of a public key `pk` knows the corresponding secret key `sk`
-    fn prove_key_ownership<B: ToBytes, R: Rng>(
+    /// Digest of the public keys of all players, in the order in which they are passed to
+    /// `compute_aggregate_key`.
+    fn key_list_digest(keys: &[Self::PlayerPublicKey]) -> Result<KeyListDigest, CardProtocolError> {
+        let mut bytes = Vec::new();
+        (keys.len() as u64).serialize(&mut bytes)?;
+        for key in keys {
+            key.serialize(&mut bytes)?;
+        }
+
+        let mut hasher = Blake2s::new();
+        hasher.update(KEY_LIST_SEED);
+        hasher.update(&bytes);
+
+        let mut digest = [0u8; 32];
+        digest.copy_from_slice(&hasher.finalize());
+
+        Ok(digest)
+    }
+
+    /// Prove in zero knowledge that the owner of a public key `pk` knows the corresponding secret key `sk`.
+    /// The proof is bound to the player's index in the list of all public keys and to the digest
+    /// of that list, so it can only be produced once every key is known.
+    fn prove_key_ownership<B: ToBytes, R: Rng + CryptoRng>(
         rng: &mut R,
         pp: &Self::Parameters,
+        context: &[u8],
         pk: &Self::PlayerPublicKey,
         sk: &Self::PlayerSecretKey,
         player_public_info: &B,
+        player_index: usize,
+        key_list_digest: &KeyListDigest,
     ) -> Result<Self::ZKProofKeyOwnership, CryptoError>;
 
     /// Verify a proof od key ownership
     fn verify_key_ownership<B: ToBytes>(
         pp: &Self::Parameters,
+        context: &[u8],
         pk: &Self::PlayerPublicKey,
         player_public_info: &B,
+        player_index: usize,
+        key_list_digest: &KeyListDigest,
         proof: &Self::ZKProofKeyOwnership,
     ) -> Result<(), CryptoError>;
 
-    /// Use all the public keys and zk-proofs to compute a verified aggregate public key
+    /// Use all the public keys and zk-proofs to compute a verified aggregate public key. The
+    /// proof at position `i` must have been produced for index `i` and the digest of the keys in
+    /// the given order.
+    ///
+    /// Rogue-key attacks, where a player picks their key as a function of the others' so as to
+    /// control the aggregate, require registering a key whose secret the attacker does not know.
+    /// Every key must come with a proof of knowledge of its secret key, and because each proof
+    /// commits to the complete list of keys it cannot be reused for another list or position. The
+    /// identity is rejected outright, as it contributes nothing to the aggregate, and so is a key
+    /// that appears more than once, as it would count twice.
     fn compute_aggregate_key<B: ToBytes>(
         pp: &Self::Parameters,
+        context: &[u8],
         player_keys_proof_info: &Vec<(Self::PlayerPublicKey, Self::ZKProofKeyOwnership, B)>,
     ) -> Result<Self::AggregatePublicKey, CardProtocolError>;
 
-    /// Use the shared public key and a (private) random scalar `alpha` to mask a card.
-    /// Returns a masked card and a zk-proof that the masking operation was applied correctly.
-    fn mask<R: Rng>(
+    /// Like `compute_aggregate_key`, but first check with `verify_parameters` that the parameters
+    /// can be trusted, for players who did not produce them themselves.
+    fn compute_aggregate_key_with_verified_parameters<B: ToBytes>(
+        pp: &Self::Parameters,
+        context: &[u8],
+        player_keys_proof_info: &Vec<(Self::PlayerPublicKey, Self::ZKProofKeyOwnership, B)>,
+    ) -> Result<Self::AggregatePublicKey, CardProtocolError> {
+        Self::verify_parameters(pp)?;
+        Self::compute_aggregate_key(pp, context, player_keys_proof_info)
+    }
+
+    /// Use the shared public key and a (private) random scalar `alpha` to mask a card. `alpha` must
+    /// not be zero. Returns a masked card and a zk-proof that the masking operation was applied correctly.
+    fn mask<R: Rng + CryptoRng>(
         rng: &mut R,
         pp: &Self::Parameters,
+        context: &[u8],
         shared_key: &Self::AggregatePublicKey,
         original_card: &Self::Card,
         alpha: &Self::Scalar,
     ) -> Result<(Self::MaskedCard, Self::ZKProofMasking), CardProtocolError>;
 
+    /// Mask a card with a freshly sampled masking factor, which is wiped once the proof is produced.
+    fn mask_with_rng<R: Rng + CryptoRng>(
+        rng: &mut R,
+        pp: &Self::Parameters,
+        context: &[u8],
+        shared_key: &Self::AggregatePublicKey,
+        original_card: &Self::Card,
+    ) -> Result<(Self::MaskedCard, Self::ZKProofMasking), CardProtocolError> {
+        let alpha = SecretScalar::new(sample_nonzero(rng)?);
+        Self::mask(rng, pp, context, shared_key, original_card, &alpha)
+    }
+
+    /// Mask a card after checking `alpha` against `guard`, which rejects zero factors and factors
+    /// already used in the session.
+    fn mask_guarded<R: Rng + CryptoRng>(
+        rng: &mut R,
+        pp: &Self::Parameters,
+        context: &[u8],
+        guard: &mut RandomnessGuard,
+        shared_key: &Self::AggregatePublicKey,
+        original_card: &Self::Card,
+        alpha: &Self::Scalar,
+    ) -> Result<(Self::MaskedCard, Self::ZKProofMasking), CardProtocolError> {
+        guard.register(alpha)?;
+        Self::mask(rng, pp, context, shared_key, original_card, alpha)
+    }
+
     /// Verify a proof of masking
     fn verify_mask(
         pp: &Self::Parameters,
+        context: &[u8],
         shared_key: &Self::AggregatePublicKey,
         card: &Self::Card,
         masked_card: &Self::MaskedCard,
@@ -126,18 +261,48 @@ pub trait BarnettSmartProtocol {
     ) -> Result<(), CryptoError>;
 
     /// Use the shared public key and a (private) random scalar `alpha` to remask a masked card.
-    /// Returns a masked card and a zk-proof that the remasking operation was applied correctly.
-    fn remask<R: Rng>(
+    /// `alpha` must not be zero, otherwise the remasked card is identical to the original. Returns a masked card and a zk-proof that the remasking operation was applied correctly.
+    fn remask<R: Rng + CryptoRng>(
         rng: &mut R,
         pp: &Self::Parameters,
+        context: &[u8],
         shared_key: &Self::AggregatePublicKey,
         original_masked: &Self::MaskedCard,
         alpha: &Self::Scalar,
     ) -> Result<(Self::MaskedCard, Self::ZKProofRemasking), CardProtocolError>;
 
+    /// Remask a masked card with a freshly sampled masking factor, which is wiped once the proof is
+    /// produced.
+    fn remask_with_rng<R: Rng + CryptoRng>(
+        rng: &mut R,
+        pp: &Self::Parameters,
+        context: &[u8],
+        shared_key: &Self::AggregatePublicKey,
+        original_masked: &Self::MaskedCard,
+    ) -> Result<(Self::MaskedCard, Self::ZKProofRemasking), CardProtocolError> {
+        let alpha = SecretScalar::new(sample_nonzero(rng)?);
+        Self::remask(rng, pp, context, shared_key, original_masked, &alpha)
+    }
+
+    /// Remask a masked card after checking `alpha` against `guard`, which rejects zero factors and
+    /// factors already used in the session.
+    fn remask_guarded<R: Rng + CryptoRng>(
+        rng: &mut R,
+        pp: &Self::Parameters,
+        context: &[u8],
+        guard: &mut RandomnessGuard,
+        shared_key: &Self::AggregatePublicKey,
+        original_masked: &Self::MaskedCard,
+        alpha: &Self::Scalar,
+    ) -> Result<(Self::MaskedCard, Self::ZKProofRemasking), CardProtocolError> {
+        guard.register(alpha)?;
+        Self::remask(rng, pp, context, shared_key, original_masked, alpha)
+    }
+
     /// Verify a proof of remasking
     fn verify_remask(
         pp: &Self::Parameters,
+        context: &[u8],
         shared_key: &Self::AggregatePublicKey,
         original_masked: &Self::MaskedCard,
         remasked: &Self::MaskedCard,
@@ -147,9 +312,10 @@ pub trait BarnettSmartProtocol {
     /// Players can use this function to compute their reveal token for a given masked card.
     /// The token is accompanied by a proof that it is a valid reveal for the specified card issued
     /// by the player who ran the computation.
-    fn compute_reveal_token<R: Rng>(
+    fn compute_reveal_token<R: Rng + CryptoRng>(
         rng: &mut R,
         pp: &Self::Parameters,
+        context: &[u8],
         sk: &Self::PlayerSecretKey,
         pk: &Self::PlayerPublicKey,
         masked_card: &Self::MaskedCard,
@@ -158,6 +324,7 @@ pub trait BarnettSmartProtocol {
     /// Verify a proof of correctly computed reveal token
     fn verify_reveal(
         pp: &Self::Parameters,
+        context: &[u8],
         pk: &Self::PlayerPublicKey,
         reveal_token: &Self::RevealToken,
         masked_card: &Self::MaskedCard,
@@ -165,9 +332,12 @@ pub trait BarnettSmartProtocol {
     ) -> Result<(), CryptoError>;
 
     /// After collecting all the necessary reveal tokens and proofs that these are correctly issued,
-    /// players can unmask a masked card to recover the underlying card.
+    /// players can unmask a masked card to recover the underlying card. `decryption_key` holds
+    /// one token for each of the `num_of_players` players at the table.
     fn unmask(
         pp: &Self::Parameters,
+        context: &[u8],
+        num_of_players: usize,
         decryption_key: &Vec<(
             Self::RevealToken,
             Self::ZKProofReveal,
@@ -177,22 +347,70 @@ pub trait BarnettSmartProtocol {
     ) -> Result<Self::Card, CardProtocolError>;
 
     /// Shuffle and remask a deck of masked cards using a player-chosen permutation and vector of
-    /// masking factors.
-    fn shuffle_and_remask<R: Rng>(
+    /// non-zero masking factors.
+    fn shuffle_and_remask<R: Rng + CryptoRng>(
         rng: &mut R,
         pp: &Self::Parameters,
+        context: &[u8],
         shared_key: &Self::AggregatePublicKey,
         deck: &Vec<Self::MaskedCard>,
         masking_factors: &Vec<Self::Scalar>,
         permutation: &Permutation,
     ) -> Result<(Vec<Self::MaskedCard>, Self::ZKProofShuffle), CardProtocolError>;
 
+    /// Shuffle and remask a deck of masked cards using a fresh random permutation and fresh
+    /// masking factors. The masking factors are wiped once the proof is produced.
+    fn shuffle_and_remask_with_rng<R: Rng + CryptoRng>(
+        rng: &mut R,
+        pp: &Self::Parameters,
+        context: &[u8],
+        shared_key: &Self::AggregatePublicKey,
+        deck: &Vec<Self::MaskedCard>,
+    ) -> Result<(Vec<Self::MaskedCard>, Self::ZKProofShuffle), CardProtocolError> {
+        let permutation = sample_permutation(rng, deck.len())?;
+        let masking_factors = SecretScalars::new(sample_nonzero_vector(rng, deck.len())?);
+        Self::shuffle_and_remask(
+            rng,
+            pp,
+            context,
+            shared_key,
+            deck,
+            &masking_factors,
+            &permutation,
+        )
+    }
+
+    /// Shuffle and remask a deck after checking the masking factors against `guard`, which rejects
+    /// zero factors and factors already used in the session, including within the deck itself.
+    fn shuffle_and_remask_guarded<R: Rng + CryptoRng>(
+        rng: &mut R,
+        pp: &Self::Parameters,
+        context: &[u8],
+        guard: &mut RandomnessGuard,
+        shared_key: &Self::AggregatePublicKey,
+        deck: &Vec<Self::MaskedCard>,
+        masking_factors: &Vec<Self::Scalar>,
+        permutation: &Permutation,
+    ) -> Result<(Vec<Self::MaskedCard>, Self::ZKProofShuffle), CardProtocolError> {
+        guard.register_all(masking_factors)?;
+        Self::shuffle_and_remask(
+            rng,
+            pp,
+            context,
+            shared_key,
+            deck,
+            masking_factors,
+            permutation,
+        )
+    }
+
     /// Verify a proof of correct shuffle
     fn verify_shuffle(
         pp: &Self::Parameters,
+        context: &[u8],
         shared_key: &Self::AggregatePublicKey,
         original_deck: &Vec<Self::MaskedCard>,
         shuffled_deck: &Vec<Self::MaskedCard>,
         proof: &Self::ZKProofShuffle,
-    ) -> Result<(), CryptoError>;
+    ) -> Result<(), ShuffleVerificationFailure>;
 }
@@ -0,0 +1,245 @@
+//! The 52 cards of a standard deck.
+//!
+//! A [`ClassicPlayingCard`] is the rank and suit behind a position of
+//! `discrete_log_cards::encoding::standard_deck_labels`, and maps one to one onto the canonical
+//! encoding of its label. Cards print as `Q♥` and parse from that form or from their label, such
+//! as `Qh`.
+
+use crate::discrete_log_cards::encoding::{
+    decode_card, encode_standard_deck, STANDARD_DECK_SIZE, STANDARD_RANKS, STANDARD_SUITS,
+};
+use crate::discrete_log_cards::Card;
+use crate::error::{CardProtocolError, InputError};
+use crate::FromLabel;
+
+use ark_ec::ProjectiveCurve;
+use ark_std::fmt;
+use ark_std::format;
+use ark_std::str::FromStr;
+use ark_std::string::{String, ToString};
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum Suit {
+    Club,
+    Diamond,
+    Heart,
+    Spade,
+}
+
+impl Suit {
+    /// Suits in the order of the standard deck labels.
+    pub const VALUES: [Self; 4] = [Self::Club, Self::Diamond, Self::Heart, Self::Spade];
+
+    const SYMBOLS: [char; 4] = ['♣', '♦', '♥', '♠'];
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum Rank {
+    Two,
+    Three,
+    Four,
+    Five,
+    Six,
+    Seven,
+    Eight,
+    Nine,
+    Ten,
+    Jack,
+    Queen,
+    King,
+    Ace,
+}
+
+impl Rank {
+    /// Ranks from lowest to highest, in the order of the standard deck labels.
+    pub const VALUES: [Self; 13] = [
+        Self::Two,
+        Self::Three,
+        Self::Four,
+        Self::Five,
+        Self::Six,
+        Self::Seven,
+        Self::Eight,
+        Self::Nine,
+        Self::Ten,
+        Self::Jack,
+        Self::Queen,
+        Self::King,
+        Self::Ace,
+    ];
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Hash)]
+pub struct ClassicPlayingCard {
+    rank: Rank,
+    suit: Suit,
+}
+
+impl ClassicPlayingCard {
+    pub fn new(rank: Rank, suit: Suit) -> Self {
+        Self { rank, suit }
+    }
+
+    /// The card at `position` of the standard deck labels, which are ordered by rank and then by
+    /// suit, or `None` past the end of the deck.
+    pub fn from_standard_position(position: usize) -> Option<Self> {
+        if position >= STANDARD_DECK_SIZE {
+            return None;
+        }
+
+        Some(Self::new(
+            Rank::VALUES[position / Suit::VALUES.len()],
+            Suit::VALUES[position % Suit::VALUES.len()],
+        ))
+    }
+
+    /// Position of the card in the standard deck labels.
+    pub fn standard_position(&self) -> usize {
+        self.rank as usize * Suit::VALUES.len() + self.suit as usize
+    }
+
+    pub fn rank(&self) -> Rank {
+        self.rank
+    }
+
+    pub fn suit(&self) -> Suit {
+        self.suit
+    }
+
+    /// Label the card is encoded from, such as `Qh`.
+    pub fn label(&self) -> String {
+        format!(
+            "{}{}",
+            STANDARD_RANKS[self.rank as usize], STANDARD_SUITS[self.suit as usize]
+        )
+    }
+
+    /// Canonical encoding of the card. Encodings do not depend on the protocol parameters, so the
+    /// card has the same encoding at every table using the curve.
+    pub fn to_card<C: ProjectiveCurve>(&self) -> Result<Card<C>, CardProtocolError> {
+        Card::<C>::from_label(self.label().as_bytes())
+    }
+
+    /// The card an opened card stands for, looked up in constant time among the encodings of the
+    /// standard deck.
+    pub fn try_from_card<C: ProjectiveCurve>(card: &Card<C>) -> Result<Self, CardProtocolError> {
+        decode_card(card, &encode_standard_deck::<C>(1)?)?
+            .and_then(Self::from_standard_position)
+            .ok_or(CardProtocolError::InvalidInput(InputError::UnknownCard))
+    }
+}
+
+impl fmt::Display for ClassicPlayingCard {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let rank = match self.rank {
+            Rank::Ten => "10",
+            rank => STANDARD_RANKS[rank as usize],
+        };
+
+        write!(f, "{}{}", rank, Suit::SYMBOLS[self.suit as usize])
+    }
+}
+
+impl fmt::Debug for ClassicPlayingCard {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt::Display::fmt(self, f)
+    }
+}
+
+/// Parse a card as printed, such as `10♥`, or from its label, such as `Th`.
+impl FromStr for ClassicPlayingCard {
+    type Err = CardProtocolError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let invalid =
+            || CardProtocolError::InvalidInput(InputError::InvalidCardLabel(s.to_string()));
+
+        let mut chars = s.chars();
+        let suit = chars.next_back().ok_or_else(invalid)?;
+        let suit = Suit::SYMBOLS
+            .iter()
+            .position(|symbol| *symbol == suit)
+            .or_else(|| {
+                STANDARD_SUITS
+                    .iter()
+                    .position(|label| label.chars().eq([suit]))
+            })
+            .ok_or_else(invalid)?;
+
+        let rank = match chars.as_str() {
+            "10" => "T",
+            rank => rank,
+        };
+        let rank = STANDARD_RANKS
+            .iter()
+            .position(|label| *label == rank)
+            .ok_or_else(invalid)?;
+
+        Ok(Self::new(Rank::VALUES[rank], Suit::VALUES[suit]))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{ClassicPlayingCard, Rank, Suit};
+    use crate::discrete_log_cards;
+    use crate::discrete_log_cards::encoding::{encode_standard_deck, standard_deck_labels};
+    use crate::error::{CardProtocolError, InputError};
+    use crate::FromLabel;
+
+    use std::collections::HashSet;
+
+    // Choose elliptic curve setting
+    type Curve = starknet_curve::Projective;
+
+    type Card = discrete_log_cards::Card<Curve>;
+
+    #[test]
+    fn test_round_trips() {
+        let deck = encode_standard_deck::<Curve>(1).unwrap();
+        let labels = standard_deck_labels();
+        let mut printed = HashSet::new();
+
+        for position in 0..52 {
+            let card = ClassicPlayingCard::from_standard_position(position).unwrap();
+            assert_eq!(card.standard_position(), position);
+            assert_eq!(card.label(), labels[position]);
+
+            let encoding = card.to_card::<Curve>().unwrap();
+            assert_eq!(encoding, deck[position]);
+            assert_eq!(Ok(card), ClassicPlayingCard::try_from_card(&encoding));
+
+            assert_eq!(Ok(card), card.to_string().parse());
+            assert_eq!(Ok(card), card.label().parse());
+            printed.insert(card.to_string());
+        }
+        assert_eq!(printed.len(), 52);
+        assert_eq!(ClassicPlayingCard::from_standard_position(52), None);
+    }
+
+    #[test]
+    fn test_display() {
+        let queen = ClassicPlayingCard::new(Rank::Queen, Suit::Heart);
+        assert_eq!(queen.to_string(), "Q♥");
+        assert_eq!(format!("{:?}", queen), "Q♥");
+        assert_eq!(
+            ClassicPlayingCard::new(Rank::Ten, Suit::Club).to_string(),
+            "10♣"
+        );
+        assert_eq!(Ok(queen), "Q♥".parse());
+
+        for invalid in ["", "Q", "1h", "Qx", "T♥♥", "Joker"] {
+            assert_eq!(
+                invalid.parse::<ClassicPlayingCard>(),
+                Err(CardProtocolError::InvalidInput(
+                    InputError::InvalidCardLabel(invalid.to_string())
+                ))
+            );
+        }
+
+        assert_eq!(
+            ClassicPlayingCard::try_from_card(&Card::from_label(b"Joker").unwrap()),
+            Err(CardProtocolError::InvalidInput(InputError::UnknownCard))
+        );
+    }
+}
@@ -0,0 +1,582 @@
+//! Protobuf messages for the protocol, for services that talk over gRPC.
+//!
+//! The definitions are under `proto`, and [`v1`] holds the types prost generates from them.
+//! Conversions from native types encode every value canonically. Conversions to native types are
+//! the checks of the `validation` module applied field by field: points must be exactly one
+//! compressed encoding long and in the prime-order subgroup, proofs must decode from exactly their
+//! encoding, vectors are bounded by `MAX_VECTOR_LENGTH` and required fields must be present. As
+//! prost allocates while decoding, bound the size of a message before calling `decode` on it.
+//!
+//! A `PublicKey` is a bare curve point, for which `TryFrom` cannot be implemented: keys convert
+//! with [`v1::PublicKey::from_point`] and [`v1::PublicKey::to_point`].
+
+use crate::crypto_primitives::signature::Signature;
+use crate::discrete_log_cards::abort::SignedMessage;
+use crate::discrete_log_cards::dkg::KeyOwnershipProof;
+use crate::discrete_log_cards::session::{GameMessage, RevealProof, ShuffleProof};
+use crate::discrete_log_cards::validation::{
+    bounded_from_bytes, public_key_from_bytes, to_bytes, DecodingLimits, MAX_VECTOR_LENGTH,
+};
+use crate::discrete_log_cards::{Card, MaskedCard, RevealToken};
+use crate::error::{CardProtocolError, InputError};
+
+use ark_ec::{AffineCurve, ProjectiveCurve};
+use ark_serialize::{CanonicalDeserialize, CanonicalSerialize};
+use proof_essentials::homomorphic_encryption::el_gamal;
+use std::convert::{TryFrom, TryInto};
+
+#[allow(clippy::all)]
+pub mod v1 {
+    //! Types generated from `proto/cards.proto` and `proto/messages.proto`.
+
+    include!(concat!(env!("OUT_DIR"), "/mental_poker.v1.rs"));
+}
+
+impl v1::PublicKey {
+    pub fn from_point<G: AffineCurve>(point: &G) -> Result<Self, CardProtocolError> {
+        Ok(Self {
+            point: to_bytes(point)?,
+        })
+    }
+
+    pub fn to_point<G: AffineCurve>(&self) -> Result<G, CardProtocolError> {
+        point(&self.point)
+    }
+}
+
+impl<C: ProjectiveCurve> TryFrom<&Card<C>> for v1::Card {
+    type Error = CardProtocolError;
+
+    fn try_from(card: &Card<C>) -> Result<Self, Self::Error> {
+        Ok(Self {
+            point: to_bytes(&card.0)?,
+        })
+    }
+}
+
+impl<C: ProjectiveCurve> TryFrom<v1::Card> for Card<C> {
+    type Error = CardProtocolError;
+
+    fn try_from(card: v1::Card) -> Result<Self, Self::Error> {
+        Ok(el_gamal::Plaintext(point(&card.point)?))
+    }
+}
+
+impl<C: ProjectiveCurve> TryFrom<&MaskedCard<C>> for v1::MaskedCard {
+    type Error = CardProtocolError;
+
+    fn try_from(masked_card: &MaskedCard<C>) -> Result<Self, Self::Error> {
+        Ok(Self {
+            c1: to_bytes(&masked_card.0)?,
+            c2: to_bytes(&masked_card.1)?,
+        })
+    }
+}
+
+impl<C: ProjectiveCurve> TryFrom<v1::MaskedCard> for MaskedCard<C> {
+    type Error = CardProtocolError;
+
+    fn try_from(masked_card: v1::MaskedCard) -> Result<Self, Self::Error> {
+        Ok(el_gamal::Ciphertext(
+            point(&masked_card.c1)?,
+            point(&masked_card.c2)?,
+        ))
+    }
+}
+
+impl<C: ProjectiveCurve> TryFrom<&[MaskedCard<C>]> for v1::Deck {
+    type Error = CardProtocolError;
+
+    fn try_from(deck: &[MaskedCard<C>]) -> Result<Self, Self::Error> {
+        Ok(Self {
+            cards: deck
+                .iter()
+                .map(v1::MaskedCard::try_from)
+                .collect::<Result<_, _>>()?,
+        })
+    }
+}
+
+impl<C: ProjectiveCurve> TryFrom<v1::Deck> for Vec<MaskedCard<C>> {
+    type Error = CardProtocolError;
+
+    fn try_from(deck: v1::Deck) -> Result<Self, Self::Error> {
+        check_length(deck.cards.len())?;
+
+        deck.cards.into_iter().map(MaskedCard::try_from).collect()
+    }
+}
+
+impl<C: ProjectiveCurve> TryFrom<&KeyOwnershipProof<C>> for v1::KeyOwnershipProof {
+    type Error = CardProtocolError;
+
+    fn try_from(proof: &KeyOwnershipProof<C>) -> Result<Self, Self::Error> {
+        Ok(Self {
+            encoding: to_bytes(proof)?,
+        })
+    }
+}
+
+impl<C: ProjectiveCurve> TryFrom<v1::KeyOwnershipProof> for KeyOwnershipProof<C> {
+    type Error = CardProtocolError;
+
+    fn try_from(proof: v1::KeyOwnershipProof) -> Result<Self, Self::Error> {
+        encoding(&proof.encoding)
+    }
+}
+
+impl<C: ProjectiveCurve> TryFrom<&ShuffleProof<C>> for v1::ShuffleProof {
+    type Error = CardProtocolError;
+
+    fn try_from(proof: &ShuffleProof<C>) -> Result<Self, Self::Error> {
+        Ok(Self {
+            encoding: to_bytes(proof)?,
+        })
+    }
+}
+
+impl<C: ProjectiveCurve> TryFrom<v1::ShuffleProof> for ShuffleProof<C> {
+    type Error = CardProtocolError;
+
+    fn try_from(proof: v1::ShuffleProof) -> Result<Self, Self::Error> {
+        encoding(&proof.encoding)
+    }
+}
+
+impl<C: ProjectiveCurve> TryFrom<&RevealProof<C>> for v1::RevealProof {
+    type Error = CardProtocolError;
+
+    fn try_from(proof: &RevealProof<C>) -> Result<Self, Self::Error> {
+        Ok(Self {
+            encoding: to_bytes(proof)?,
+        })
+    }
+}
+
+impl<C: ProjectiveCurve> TryFrom<v1::RevealProof> for RevealProof<C> {
+    type Error = CardProtocolError;
+
+    fn try_from(proof: v1::RevealProof) -> Result<Self, Self::Error> {
+        encoding(&proof.encoding)
+    }
+}
+
+/// The reveal tokens of a `GameMessage::RevealTokens`, each for the card at the given deck index.
+impl<C: ProjectiveCurve> TryFrom<&[(usize, RevealToken<C>, RevealProof<C>)]>
+    for v1::RevealTokenBundle
+{
+    type Error = CardProtocolError;
+
+    fn try_from(tokens: &[(usize, RevealToken<C>, RevealProof<C>)]) -> Result<Self, Self::Error> {
+        let tokens = tokens
+            .iter()
+            .map(
+                |(index, token, proof)| -> Result<v1::RevealToken, CardProtocolError> {
+                    Ok(v1::RevealToken {
+                        card_index: *index as u64,
+                        token: to_bytes(&token.0)?,
+                        proof: Some(proof.try_into()?),
+                    })
+                },
+            )
+            .collect::<Result<_, _>>()?;
+
+        Ok(Self { tokens })
+    }
+}
+
+impl<C: ProjectiveCurve> TryFrom<v1::RevealTokenBundle>
+    for Vec<(usize, RevealToken<C>, RevealProof<C>)>
+{
+    type Error = CardProtocolError;
+
+    fn try_from(bundle: v1::RevealTokenBundle) -> Result<Self, Self::Error> {
+        check_length(bundle.tokens.len())?;
+
+        bundle
+            .tokens
+            .into_iter()
+            .map(|token| {
+                Ok::<_, CardProtocolError>((
+                    to_usize(token.card_index)?,
+                    el_gamal::Plaintext(point(&token.token)?),
+                    required(token.proof, "proof")?.try_into()?,
+                ))
+            })
+            .collect()
+    }
+}
+
+impl<C: ProjectiveCurve> TryFrom<&GameMessage<C>> for v1::GameMessage {
+    type Error = CardProtocolError;
+
+    fn try_from(message: &GameMessage<C>) -> Result<Self, Self::Error> {
+        let kind = match message {
+            GameMessage::PublicKey(pk) => {
+                v1::game_message::Kind::PublicKey(v1::PublicKey::from_point(pk)?)
+            }
+            GameMessage::KeyOwnership(proof) => {
+                v1::game_message::Kind::KeyOwnership(proof.try_into()?)
+            }
+            GameMessage::Shuffle { deck, proof } => v1::game_message::Kind::Shuffle(v1::Shuffle {
+                deck: Some(deck.as_slice().try_into()?),
+                proof: Some(proof.try_into()?),
+            }),
+            GameMessage::RevealTokens(tokens) => {
+                v1::game_message::Kind::RevealTokens(tokens.as_slice().try_into()?)
+            }
+        };
+
+        Ok(Self { kind: Some(kind) })
+    }
+}
+
+impl<C: ProjectiveCurve> TryFrom<v1::GameMessage> for GameMessage<C> {
+    type Error = CardProtocolError;
+
+    fn try_from(message: v1::GameMessage) -> Result<Self, Self::Error> {
+        Ok(match required(message.kind, "kind")? {
+            v1::game_message::Kind::PublicKey(pk) => GameMessage::PublicKey(pk.to_point()?),
+            v1::game_message::Kind::KeyOwnership(proof) => {
+                GameMessage::KeyOwnership(proof.try_into()?)
+            }
+            v1::game_message::Kind::Shuffle(shuffle) => GameMessage::Shuffle {
+                deck: required(shuffle.deck, "deck")?.try_into()?,
+                proof: required(shuffle.proof, "proof")?.try_into()?,
+            },
+            v1::game_message::Kind::RevealTokens(bundle) => {
+                GameMessage::RevealTokens(bundle.try_into()?)
+            }
+        })
+    }
+}
+
+impl<C: ProjectiveCurve> TryFrom<&SignedMessage<C>> for v1::SignedMessage {
+    type Error = CardProtocolError;
+
+    fn try_from(message: &SignedMessage<C>) -> Result<Self, Self::Error> {
+        Ok(Self {
+            sender: message.sender as u64,
+            slot: message.slot,
+            message: Some((&message.message).try_into()?),
+            signature: to_bytes(&message.signature)?,
+        })
+    }
+}
+
+/// The signature is only decoded. Check it with [`SignedMessage::verify`].
+impl<C: ProjectiveCurve> TryFrom<v1::SignedMessage> for SignedMessage<C> {
+    type Error = CardProtocolError;
+
+    fn try_from(message: v1::SignedMessage) -> Result<Self, Self::Error> {
+        Ok(Self {
+            sender: to_usize(message.sender)?,
+            slot: message.slot,
+            message: required(message.message, "message")?.try_into()?,
+            signature: encoding::<Signature<C>>(&message.signature)?,
+        })
+    }
+}
+
+/// Decode a point from exactly its compressed encoding, and check it.
+fn point<G: AffineCurve>(bytes: &[u8]) -> Result<G, CardProtocolError> {
+    let expected = G::prime_subgroup_generator().serialized_size();
+    if bytes.len() != expected {
+        return Err(InputError::SerializationError(format!(
+            "Expected a point of {} bytes, got {}",
+            expected,
+            bytes.len()
+        ))
+        .into());
+    }
+
+    public_key_from_bytes(bytes)
+}
+
+/// Decode a value from exactly its canonical encoding. Points are checked as they are decoded.
+fn encoding<T: CanonicalSerialize + CanonicalDeserialize>(
+    bytes: &[u8],
+) -> Result<T, CardProtocolError> {
+    let value = bounded_from_bytes::<T>(bytes, &DecodingLimits::default())?;
+    if value.serialized_size() != bytes.len() {
+        return Err(InputError::SerializationError(String::from(
+            "Trailing bytes after the encoding",
+        ))
+        .into());
+    }
+
+    Ok(value)
+}
+
+fn check_length(length: usize) -> Result<(), CardProtocolError> {
+    if length > MAX_VECTOR_LENGTH {
+        return Err(InputError::MessageTooLarge(MAX_VECTOR_LENGTH).into());
+    }
+
+    Ok(())
+}
+
+fn to_usize(value: u64) -> Result<usize, CardProtocolError> {
+    usize::try_from(value).map_err(|_| {
+        CardProtocolError::InvalidInput(InputError::ValueOutOfRange(usize::MAX as u64))
+    })
+}
+
+fn required<T>(field: Option<T>, name: &str) -> Result<T, CardProtocolError> {
+    field.ok_or_else(|| {
+        CardProtocolError::InvalidInput(InputError::SerializationError(format!(
+            "Missing field {}",
+            name
+        )))
+    })
+}
+
+#[cfg(test)]
+mod test {
+    use super::v1;
+    use crate::crypto_primitives::signature::SigningKey;
+    use crate::crypto_primitives::utils::rand::test_rng;
+    use crate::discrete_log_cards::abort::SignedMessage;
+    use crate::discrete_log_cards::dkg::KeyOwnershipProof;
+    use crate::discrete_log_cards::encoding::encode_standard_deck;
+    use crate::discrete_log_cards::session::{GameMessage, RevealProof};
+    use crate::discrete_log_cards::validation::to_bytes;
+    use crate::discrete_log_cards::{self, starting_deck, Card, MaskedCard, RevealToken};
+    use crate::error::{CardProtocolError, InputError};
+    use crate::BarnettSmartProtocol;
+
+    use ark_ec::ProjectiveCurve;
+    use ark_serialize::CanonicalSerialize;
+    use proof_essentials::homomorphic_encryption::el_gamal;
+    use prost::Message;
+    use std::convert::TryFrom;
+
+    // Choose elliptic curve setting
+    type Curve = starknet_curve::Projective;
+
+    // Instantiate concrete type for our card protocol
+    type CardProtocol<'a> = discrete_log_cards::DLCards<'a, Curve>;
+
+    type Point = <Curve as ProjectiveCurve>::Affine;
+
+    type Tokens = Vec<(usize, RevealToken<Curve>, RevealProof<Curve>)>;
+
+    const SESSION_ID: &[u8] = b"proto test";
+
+    /// Two masked cards built from the first four cards of the standard deck, as encoded in
+    /// `tests/vectors/starknet_deck.binpb`.
+    const STARKNET_DECK: &[u8] = include_bytes!("../tests/vectors/starknet_deck.binpb");
+
+    /// Encode a native value, send it through the wire format and convert it back.
+    fn round_trip<N, P>(value: &N) -> N
+    where
+        for<'a> P: TryFrom<&'a N, Error = CardProtocolError>,
+        P: Message + Default,
+        N: TryFrom<P, Error = CardProtocolError>,
+    {
+        let bytes = P::try_from(value).unwrap().encode_to_vec();
+
+        N::try_from(P::decode(&bytes[..]).unwrap()).unwrap()
+    }
+
+    #[test]
+    fn test_round_trips() {
+        let rng = &mut test_rng();
+        let m = 4;
+        let n = 13;
+
+        let parameters = CardProtocol::setup(rng, m, n).unwrap();
+        let (pk, sk) = CardProtocol::player_keygen(rng, &parameters).unwrap();
+        let digest = CardProtocol::key_list_digest(&[pk]).unwrap();
+        let info = b"alice".to_vec();
+        let key_proof = CardProtocol::prove_key_ownership(
+            rng,
+            &parameters,
+            SESSION_ID,
+            &pk,
+            &sk,
+            &info,
+            0,
+            &digest,
+        )
+        .unwrap();
+        let shared_key = CardProtocol::compute_aggregate_key(
+            &parameters,
+            SESSION_ID,
+            &vec![(pk, key_proof.clone(), info)],
+        )
+        .unwrap();
+
+        let cards = encode_standard_deck::<Curve>(1).unwrap();
+        let deck = starting_deck(&parameters, &shared_key, &cards).unwrap();
+        let (shuffled, shuffle_proof) = CardProtocol::shuffle_and_remask_with_rng(
+            rng,
+            &parameters,
+            SESSION_ID,
+            &shared_key,
+            &deck,
+        )
+        .unwrap();
+        let (token, reveal_proof) = CardProtocol::compute_reveal_token(
+            rng,
+            &parameters,
+            SESSION_ID,
+            &sk,
+            &pk,
+            &shuffled[3],
+        )
+        .unwrap();
+
+        let key = v1::PublicKey::from_point(&pk).unwrap();
+        let key = v1::PublicKey::decode(&key.encode_to_vec()[..]).unwrap();
+        assert_eq!(pk, key.to_point().unwrap());
+        assert_eq!(cards[7], round_trip::<_, v1::Card>(&cards[7]));
+        assert_eq!(shuffled[0], round_trip::<_, v1::MaskedCard>(&shuffled[0]));
+        assert_eq!(
+            to_bytes(&key_proof).unwrap(),
+            to_bytes(&round_trip::<_, v1::KeyOwnershipProof>(&key_proof)).unwrap()
+        );
+        assert_eq!(
+            to_bytes(&shuffle_proof).unwrap(),
+            to_bytes(&round_trip::<_, v1::ShuffleProof>(&shuffle_proof)).unwrap()
+        );
+        assert_eq!(
+            to_bytes(&reveal_proof).unwrap(),
+            to_bytes(&round_trip::<_, v1::RevealProof>(&reveal_proof)).unwrap()
+        );
+
+        let encoded = v1::Deck::try_from(shuffled.as_slice())
+            .unwrap()
+            .encode_to_vec();
+        let decoded =
+            Vec::<MaskedCard<Curve>>::try_from(v1::Deck::decode(&encoded[..]).unwrap()).unwrap();
+        assert_eq!(shuffled, decoded);
+
+        let tokens: Tokens = vec![(3, token, reveal_proof)];
+        let encoded = v1::RevealTokenBundle::try_from(tokens.as_slice())
+            .unwrap()
+            .encode_to_vec();
+        let decoded =
+            Tokens::try_from(v1::RevealTokenBundle::decode(&encoded[..]).unwrap()).unwrap();
+        assert_eq!(to_bytes(&tokens).unwrap(), to_bytes(&decoded).unwrap());
+
+        // Every kind of game message, and the signed envelope
+        let identity = SigningKey::<Curve>::new(rng).unwrap();
+        let messages = vec![
+            GameMessage::PublicKey(pk),
+            GameMessage::KeyOwnership(key_proof),
+            GameMessage::Shuffle {
+                deck: shuffled,
+                proof: shuffle_proof,
+            },
+            GameMessage::RevealTokens(tokens),
+        ];
+        for (slot, message) in messages.into_iter().enumerate() {
+            assert_eq!(
+                to_bytes(&message).unwrap(),
+                to_bytes(&round_trip::<_, v1::GameMessage>(&message)).unwrap()
+            );
+
+            let signed =
+                SignedMessage::sign(&identity, SESSION_ID, 0, slot as u64, message).unwrap();
+            let decoded = round_trip::<_, v1::SignedMessage>(&signed);
+            assert_eq!(to_bytes(&signed).unwrap(), to_bytes(&decoded).unwrap());
+            assert_eq!(
+                Ok(()),
+                decoded.verify(SESSION_ID, &[identity.verifying_key()])
+            );
+        }
+    }
+
+    #[test]
+    fn test_invalid_messages() {
+        let rng = &mut test_rng();
+        let m = 4;
+        let n = 13;
+
+        let parameters = CardProtocol::setup(rng, m, n).unwrap();
+        let (pk, sk) = CardProtocol::player_keygen(rng, &parameters).unwrap();
+        let digest = CardProtocol::key_list_digest(&[pk]).unwrap();
+        let key_proof = CardProtocol::prove_key_ownership(
+            rng,
+            &parameters,
+            SESSION_ID,
+            &pk,
+            &sk,
+            &b"alice".to_vec(),
+            0,
+            &digest,
+        )
+        .unwrap();
+        let key = v1::PublicKey::from_point(&pk).unwrap();
+
+        // Points must be exactly one valid compressed encoding long
+        let mut long = key.clone();
+        long.point.push(0);
+        assert!(long.to_point::<Point>().is_err());
+        let mut short = key.clone();
+        short.point.pop();
+        assert!(short.to_point::<Point>().is_err());
+        let invalid = v1::Card {
+            point: vec![0xff; key.point.len()],
+        };
+        assert_eq!(
+            Card::<Curve>::try_from(invalid),
+            Err(CardProtocolError::InvalidInput(InputError::InvalidPoint))
+        );
+
+        // Proofs must decode from exactly their encoding
+        let mut proof = v1::KeyOwnershipProof::try_from(&key_proof).unwrap();
+        proof.encoding.push(0);
+        assert!(KeyOwnershipProof::<Curve>::try_from(proof).is_err());
+
+        // Required fields must be present
+        assert!(GameMessage::<Curve>::try_from(v1::GameMessage { kind: None }).is_err());
+        let shuffle = v1::GameMessage {
+            kind: Some(v1::game_message::Kind::Shuffle(v1::Shuffle {
+                deck: Some(v1::Deck { cards: vec![] }),
+                proof: None,
+            })),
+        };
+        assert!(GameMessage::<Curve>::try_from(shuffle).is_err());
+        let bundle = v1::RevealTokenBundle {
+            tokens: vec![v1::RevealToken {
+                card_index: 0,
+                token: key.point.clone(),
+                proof: None,
+            }],
+        };
+        assert!(Tokens::try_from(bundle).is_err());
+        let signed = v1::SignedMessage {
+            sender: 0,
+            slot: 0,
+            message: Some(v1::GameMessage {
+                kind: Some(v1::game_message::Kind::PublicKey(key)),
+            }),
+            signature: vec![],
+        };
+        assert!(SignedMessage::<Curve>::try_from(signed).is_err());
+    }
+
+    #[test]
+    fn test_deck_fixture() {
+        let cards = encode_standard_deck::<Curve>(1).unwrap();
+        let expected = vec![
+            el_gamal::Ciphertext(cards[0].0, cards[1].0),
+            el_gamal::Ciphertext(cards[2].0, cards[3].0),
+        ];
+
+        let deck = v1::Deck::decode(STARKNET_DECK).unwrap();
+        assert_eq!(deck.cards.len(), 2);
+        let mut point = Vec::new();
+        cards[0].0.serialize(&mut point).unwrap();
+        assert_eq!(deck.cards[0].c1, point);
+
+        let decoded = Vec::<MaskedCard<Curve>>::try_from(deck).unwrap();
+        assert_eq!(decoded, expected);
+
+        // The wire format is stable
+        let encoded = v1::Deck::try_from(expected.as_slice()).unwrap();
+        assert_eq!(encoded.encode_to_vec(), STARKNET_DECK);
+    }
+}
@@ -0,0 +1,880 @@
+//! JSON-RPC 2.0 server holding the keys of one player, built with the `rpc` feature and run as
+//! the `mental-poker-rpc` binary.
+//!
+//! Every method is one call of [`BarnettSmartProtocol`] on the starknet curve, named after it and
+//! taking named parameters. Keys, cards, masked cards, tokens and proofs are passed as hex strings
+//! of their canonical encoding, decks as arrays of masked cards, and contexts and player
+//! information as hex strings of raw bytes. The server keeps the parameters adopted by `setup` or
+//! `verify_parameters` and the key pair generated by `player_keygen`; the secret key never leaves
+//! it.
+//!
+//! Errors carry one of the codes below, and the name of the `CardProtocolError` variant in the
+//! `error` field of their data. Errors of the protocol also carry its stable
+//! [`ErrorCode`](crate::error::ErrorCode) number in the `error_code` field, which is what clients
+//! should branch on. The code of an error of the protocol follows its category, the variant of
+//! [`CardProtocolError`] it is nested in:
+//!
+//! | code   | meaning                                                                       |
+//! |--------|-------------------------------------------------------------------------------|
+//! | -32001 | `InvalidInput`: an argument is malformed or not allowed at this point         |
+//! | -32002 | `VerificationFailed`: a proof or another check on the arguments does not hold |
+//! | -32003 | `Internal`: the server failed rather than the caller, such as its randomness  |
+//! | -32004 | parameters or keys are needed but not set yet                                 |
+
+use crate::discrete_log_cards::dkg::KeyOwnershipProof;
+use crate::discrete_log_cards::session::{RevealProof, ShuffleProof};
+use crate::discrete_log_cards::validation::{
+    bounded_from_bytes, from_bytes, public_key_from_bytes, to_bytes, DecodingLimits, Validate,
+};
+use crate::discrete_log_cards::{
+    Card, DLCards, MaskedCard, Parameters, PlayerSecretKey, PublicKey, RevealToken,
+};
+use crate::error::{CardProtocolError, InputError, ShuffleVerificationFailure};
+use crate::{BarnettSmartProtocol, KeyListDigest};
+
+use ark_serialize::{CanonicalDeserialize, CanonicalSerialize};
+use jsonrpc_core::{Error, ErrorCode, IoHandler, Params, Value};
+use jsonrpc_http_server::{Server, ServerBuilder};
+use proof_essentials::error::CryptoError;
+use rand::thread_rng;
+use serde::de::DeserializeOwned;
+use serde::Deserialize;
+use serde_json::json;
+use std::convert::TryFrom;
+use std::net::SocketAddr;
+use std::sync::{Arc, Mutex};
+
+type Curve = starknet_curve::Projective;
+type CardProtocol<'a> = DLCards<'a, Curve>;
+
+pub const INVALID_INPUT: i64 = -32001;
+pub const VERIFICATION_FAILED: i64 = -32002;
+pub const INTERNAL_ERROR: i64 = -32003;
+pub const NOT_READY: i64 = -32004;
+
+/// State of the player the server acts for.
+#[derive(Default)]
+struct Player {
+    pp: Option<Parameters<Curve>>,
+    keys: Option<(PublicKey<Curve>, PlayerSecretKey<Curve>)>,
+}
+
+impl Player {
+    fn parameters(&self) -> Result<&Parameters<Curve>, RpcError> {
+        self.pp.as_ref().ok_or(RpcError::NoParameters)
+    }
+
+    fn keys(&self) -> Result<&(PublicKey<Curve>, PlayerSecretKey<Curve>), RpcError> {
+        self.keys.as_ref().ok_or(RpcError::NoKeys)
+    }
+}
+
+enum RpcError {
+    NoParameters,
+    NoKeys,
+    Protocol(CardProtocolError),
+}
+
+impl From<CardProtocolError> for RpcError {
+    fn from(error: CardProtocolError) -> Self {
+        Self::Protocol(error)
+    }
+}
+
+impl From<CryptoError> for RpcError {
+    fn from(error: CryptoError) -> Self {
+        Self::Protocol(error.into())
+    }
+}
+
+impl From<ShuffleVerificationFailure> for RpcError {
+    fn from(error: ShuffleVerificationFailure) -> Self {
+        Self::Protocol(error.into())
+    }
+}
+
+impl From<RpcError> for Error {
+    fn from(error: RpcError) -> Self {
+        let (code, data, message) = match error {
+            RpcError::NoParameters => (
+                NOT_READY,
+                json!({ "error": "NoParameters" }),
+                "No parameters: call setup or verify_parameters first".to_string(),
+            ),
+            RpcError::NoKeys => (
+                NOT_READY,
+                json!({ "error": "NoKeys" }),
+                "No key pair: call player_keygen first".to_string(),
+            ),
+            RpcError::Protocol(error) => (
+                error_code(&error),
+                json!({ "error": error.code().name(), "error_code": error.code().value() }),
+                error.to_string(),
+            ),
+        };
+
+        Error {
+            code: ErrorCode::ServerError(code),
+            message,
+            data: Some(data),
+        }
+    }
+}
+
+fn error_code(error: &CardProtocolError) -> i64 {
+    match error {
+        CardProtocolError::InvalidInput(_) => INVALID_INPUT,
+        CardProtocolError::VerificationFailed(_) => VERIFICATION_FAILED,
+        CardProtocolError::Internal(_) => INTERNAL_ERROR,
+    }
+}
+
+/// Start serving on `address`. The server runs on its own threads until it is closed or dropped.
+pub fn start(address: &SocketAddr) -> Result<Server, CardProtocolError> {
+    Ok(ServerBuilder::new(handler()).start_http(address)?)
+}
+
+/// The methods of the server, for embedding them in another transport.
+pub fn handler() -> IoHandler {
+    let player = Arc::new(Mutex::new(Player::default()));
+    let mut io = IoHandler::new();
+
+    method(&mut io, &player, "setup", |player, params: SetupParams| {
+        let pp = CardProtocol::setup(&mut thread_rng(), params.m, params.n)?;
+        let parameters = encode(&pp)?;
+        player.pp = Some(pp);
+
+        Ok(json!({ "parameters": parameters }))
+    });
+
+    method(
+        &mut io,
+        &player,
+        "verify_parameters",
+        |player, params: ParametersParams| {
+            let bytes = decode_hex(&params.parameters)?;
+            let pp = bounded_from_bytes::<Parameters<Curve>>(&bytes, &DecodingLimits::default())?;
+            CardProtocol::verify_parameters(&pp)?;
+            player.pp = Some(pp);
+
+            Ok(Value::Null)
+        },
+    );
+
+    method(&mut io, &player, "player_keygen", |player, _: Value| {
+        let (pk, sk) = CardProtocol::player_keygen(&mut thread_rng(), player.parameters()?)?;
+        let public_key = encode(&pk)?;
+        player.keys = Some((pk, sk));
+
+        Ok(json!({ "public_key": public_key }))
+    });
+
+    method(
+        &mut io,
+        &player,
+        "key_list_digest",
+        |_, params: KeyListParams| {
+            let keys = params
+                .keys
+                .iter()
+                .map(|pk| decode_public_key(pk))
+                .collect::<Result<Vec<_>, _>>()?;
+            let digest = CardProtocol::key_list_digest(&keys)?;
+
+            Ok(json!({ "key_list_digest": hex::encode(digest) }))
+        },
+    );
+
+    method(
+        &mut io,
+        &player,
+        "prove_key_ownership",
+        |player, params: ProveKeyParams| {
+            let (pk, sk) = player.keys()?;
+            let proof = CardProtocol::prove_key_ownership(
+                &mut thread_rng(),
+                player.parameters()?,
+                &decode_hex(&params.context)?,
+                pk,
+                sk,
+                &decode_hex(&params.player_info)?,
+                params.player_index,
+                &decode_digest(&params.key_list_digest)?,
+            )?;
+
+            Ok(json!({ "proof": encode(&proof)? }))
+        },
+    );
+
+    method(
+        &mut io,
+        &player,
+        "verify_key_ownership",
+        |player, params: VerifyKeyParams| {
+            let pp = player.parameters()?;
+            CardProtocol::verify_key_ownership(
+                pp,
+                &decode_hex(&params.context)?,
+                &decode_public_key(&params.public_key)?,
+                &decode_hex(&params.player_info)?,
+                params.player_index,
+                &decode_digest(&params.key_list_digest)?,
+                &decode_proof::<KeyOwnershipProof<Curve>>(pp, &params.proof)?,
+            )?;
+
+            Ok(Value::Null)
+        },
+    );
+
+    method(
+        &mut io,
+        &player,
+        "compute_aggregate_key",
+        |player, params: AggregateKeyParams| {
+            let pp = player.parameters()?;
+            let players = params
+                .players
+                .iter()
+                .map(|info| {
+                    Ok::<_, CardProtocolError>((
+                        decode_public_key(&info.public_key)?,
+                        decode_proof::<KeyOwnershipProof<Curve>>(pp, &info.proof)?,
+                        decode_hex(&info.player_info)?,
+                    ))
+                })
+                .collect::<Result<Vec<_>, _>>()?;
+            let shared_key =
+                CardProtocol::compute_aggregate_key(pp, &decode_hex(&params.context)?, &players)?;
+
+            Ok(json!({ "shared_key": encode(&shared_key)? }))
+        },
+    );
+
+    method(&mut io, &player, "mask", |player, params: MaskParams| {
+        let (masked_card, proof) = CardProtocol::mask_with_rng(
+            &mut thread_rng(),
+            player.parameters()?,
+            &decode_hex(&params.context)?,
+            &decode_public_key(&params.shared_key)?,
+            &decode::<Card<Curve>>(&params.card)?,
+        )?;
+
+        Ok(json!({ "masked_card": encode(&masked_card)?, "proof": encode(&proof)? }))
+    });
+
+    method(
+        &mut io,
+        &player,
+        "verify_mask",
+        |player, params: VerifyMaskParams| {
+            let pp = player.parameters()?;
+            CardProtocol::verify_mask(
+                pp,
+                &decode_hex(&params.context)?,
+                &decode_public_key(&params.shared_key)?,
+                &decode::<Card<Curve>>(&params.card)?,
+                &decode::<MaskedCard<Curve>>(&params.masked_card)?,
+                &decode_proof::<RevealProof<Curve>>(pp, &params.proof)?,
+            )?;
+
+            Ok(Value::Null)
+        },
+    );
+
+    method(
+        &mut io,
+        &player,
+        "remask",
+        |player, params: RemaskParams| {
+            let (masked_card, proof) = CardProtocol::remask_with_rng(
+                &mut thread_rng(),
+                player.parameters()?,
+                &decode_hex(&params.context)?,
+                &decode_public_key(&params.shared_key)?,
+                &decode::<MaskedCard<Curve>>(&params.masked_card)?,
+            )?;
+
+            Ok(json!({ "masked_card": encode(&masked_card)?, "proof": encode(&proof)? }))
+        },
+    );
+
+    method(
+        &mut io,
+        &player,
+        "verify_remask",
+        |player, params: VerifyRemaskParams| {
+            let pp = player.parameters()?;
+            CardProtocol::verify_remask(
+                pp,
+                &decode_hex(&params.context)?,
+                &decode_public_key(&params.shared_key)?,
+                &decode::<MaskedCard<Curve>>(&params.original_masked)?,
+                &decode::<MaskedCard<Curve>>(&params.remasked)?,
+                &decode_proof::<RevealProof<Curve>>(pp, &params.proof)?,
+            )?;
+
+            Ok(Value::Null)
+        },
+    );
+
+    method(
+        &mut io,
+        &player,
+        "compute_reveal_token",
+        |player, params: RevealParams| {
+            let (pk, sk) = player.keys()?;
+            let (token, proof) = CardProtocol::compute_reveal_token(
+                &mut thread_rng(),
+                player.parameters()?,
+                &decode_hex(&params.context)?,
+                sk,
+                pk,
+                &decode::<MaskedCard<Curve>>(&params.masked_card)?,
+            )?;
+
+            Ok(json!({ "token": encode(&token)?, "proof": encode(&proof)? }))
+        },
+    );
+
+    method(
+        &mut io,
+        &player,
+        "verify_reveal",
+        |player, params: VerifyRevealParams| {
+            let pp = player.parameters()?;
+            CardProtocol::verify_reveal(
+                pp,
+                &decode_hex(&params.context)?,
+                &decode_public_key(&params.public_key)?,
+                &decode::<RevealToken<Curve>>(&params.token)?,
+                &decode::<MaskedCard<Curve>>(&params.masked_card)?,
+                &decode_proof::<RevealProof<Curve>>(pp, &params.proof)?,
+            )?;
+
+            Ok(Value::Null)
+        },
+    );
+
+    method(
+        &mut io,
+        &player,
+        "unmask",
+        |player, params: UnmaskParams| {
+            let pp = player.parameters()?;
+            let decryption_key = params
+                .decryption_key
+                .iter()
+                .map(|share| {
+                    Ok::<_, CardProtocolError>((
+                        decode::<RevealToken<Curve>>(&share.token)?,
+                        decode_proof::<RevealProof<Curve>>(pp, &share.proof)?,
+                        decode_public_key(&share.public_key)?,
+                    ))
+                })
+                .collect::<Result<Vec<_>, _>>()?;
+            let card = CardProtocol::unmask(
+                pp,
+                &decode_hex(&params.context)?,
+                params.num_of_players,
+                &decryption_key,
+                &decode::<MaskedCard<Curve>>(&params.masked_card)?,
+            )?;
+
+            Ok(json!({ "card": encode(&card)? }))
+        },
+    );
+
+    method(
+        &mut io,
+        &player,
+        "shuffle_and_remask",
+        |player, params: ShuffleParams| {
+            let pp = player.parameters()?;
+            let (deck, proof) = CardProtocol::shuffle_and_remask_with_rng(
+                &mut thread_rng(),
+                pp,
+                &decode_hex(&params.context)?,
+                &decode_public_key(&params.shared_key)?,
+                &decode_deck(pp, &params.deck)?,
+            )?;
+
+            Ok(json!({ "deck": encode_deck(&deck)?, "proof": encode(&proof)? }))
+        },
+    );
+
+    method(
+        &mut io,
+        &player,
+        "verify_shuffle",
+        |player, params: VerifyShuffleParams| {
+            let pp = player.parameters()?;
+            CardProtocol::verify_shuffle(
+                pp,
+                &decode_hex(&params.context)?,
+                &decode_public_key(&params.shared_key)?,
+                &decode_deck(pp, &params.original_deck)?,
+                &decode_deck(pp, &params.shuffled_deck)?,
+                &decode_proof::<ShuffleProof<Curve>>(pp, &params.proof)?,
+            )?;
+
+            Ok(Value::Null)
+        },
+    );
+
+    io
+}
+
+/// Register `name`, which decodes its parameters into `P` and runs `f` on the player. Calls are
+/// served one at a time.
+fn method<P, F>(io: &mut IoHandler, player: &Arc<Mutex<Player>>, name: &str, f: F)
+where
+    P: DeserializeOwned,
+    F: Fn(&mut Player, P) -> Result<Value, RpcError> + Send + Sync + 'static,
+{
+    let player = player.clone();
+    io.add_sync_method(name, move |params: Params| {
+        let params = params.parse::<P>()?;
+        let mut player = player.lock().map_err(|_| Error::internal_error())?;
+
+        f(&mut player, params).map_err(Error::from)
+    });
+}
+
+fn decode_hex(value: &str) -> Result<Vec<u8>, CardProtocolError> {
+    hex::decode(value.trim_start_matches("0x")).map_err(|error| {
+        CardProtocolError::InvalidInput(InputError::SerializationError(error.to_string()))
+    })
+}
+
+fn decode<T: CanonicalDeserialize + Validate>(value: &str) -> Result<T, CardProtocolError> {
+    from_bytes(&decode_hex(value)?)
+}
+
+fn decode_public_key(value: &str) -> Result<PublicKey<Curve>, CardProtocolError> {
+    public_key_from_bytes(&decode_hex(value)?)
+}
+
+fn decode_proof<T: CanonicalDeserialize>(
+    pp: &Parameters<Curve>,
+    value: &str,
+) -> Result<T, CardProtocolError> {
+    bounded_from_bytes(&decode_hex(value)?, &DecodingLimits::for_parameters(pp))
+}
+
+fn decode_digest(value: &str) -> Result<KeyListDigest, CardProtocolError> {
+    KeyListDigest::try_from(decode_hex(value)?.as_slice()).map_err(|_| {
+        CardProtocolError::InvalidInput(InputError::SerializationError(
+            "a key list digest is 32 bytes".to_string(),
+        ))
+    })
+}
+
+fn decode_deck(
+    pp: &Parameters<Curve>,
+    deck: &[String],
+) -> Result<Vec<MaskedCard<Curve>>, CardProtocolError> {
+    if deck.len() > pp.num_cards() {
+        return Err(InputError::MessageTooLarge(pp.num_cards()).into());
+    }
+
+    deck.iter().map(|card| decode(card)).collect()
+}
+
+fn encode<T: CanonicalSerialize>(value: &T) -> Result<String, CardProtocolError> {
+    Ok(hex::encode(to_bytes(value)?))
+}
+
+fn encode_deck(deck: &[MaskedCard<Curve>]) -> Result<Vec<String>, CardProtocolError> {
+    deck.iter().map(encode).collect()
+}
+
+#[derive(Deserialize)]
+struct SetupParams {
+    m: usize,
+    n: usize,
+}
+
+#[derive(Deserialize)]
+struct ParametersParams {
+    parameters: String,
+}
+
+#[derive(Deserialize)]
+struct KeyListParams {
+    keys: Vec<String>,
+}
+
+#[derive(Deserialize)]
+struct ProveKeyParams {
+    context: String,
+    player_info: String,
+    player_index: usize,
+    key_list_digest: String,
+}
+
+#[derive(Deserialize)]
+struct VerifyKeyParams {
+    context: String,
+    public_key: String,
+    player_info: String,
+    player_index: usize,
+    key_list_digest: String,
+    proof: String,
+}
+
+#[derive(Deserialize)]
+struct PlayerKeyInfo {
+    public_key: String,
+    proof: String,
+    player_info: String,
+}
+
+#[derive(Deserialize)]
+struct AggregateKeyParams {
+    context: String,
+    players: Vec<PlayerKeyInfo>,
+}
+
+#[derive(Deserialize)]
+struct MaskParams {
+    context: String,
+    shared_key: String,
+    card: String,
+}
+
+#[derive(Deserialize)]
+struct VerifyMaskParams {
+    context: String,
+    shared_key: String,
+    card: String,
+    masked_card: String,
+    proof: String,
+}
+
+#[derive(Deserialize)]
+struct RemaskParams {
+    context: String,
+    shared_key: String,
+    masked_card: String,
+}
+
+#[derive(Deserialize)]
+struct VerifyRemaskParams {
+    context: String,
+    shared_key: String,
+    original_masked: String,
+    remasked: String,
+    proof: String,
+}
+
+#[derive(Deserialize)]
+struct RevealParams {
+    context: String,
+    masked_card: String,
+}
+
+#[derive(Deserialize)]
+struct VerifyRevealParams {
+    context: String,
+    public_key: String,
+    token: String,
+    masked_card: String,
+    proof: String,
+}
+
+#[derive(Deserialize)]
+struct RevealShare {
+    token: String,
+    proof: String,
+    public_key: String,
+}
+
+#[derive(Deserialize)]
+struct UnmaskParams {
+    context: String,
+    num_of_players: usize,
+    decryption_key: Vec<RevealShare>,
+    masked_card: String,
+}
+
+#[derive(Deserialize)]
+struct ShuffleParams {
+    context: String,
+    shared_key: String,
+    deck: Vec<String>,
+}
+
+#[derive(Deserialize)]
+struct VerifyShuffleParams {
+    context: String,
+    shared_key: String,
+    original_deck: Vec<String>,
+    shuffled_deck: Vec<String>,
+    proof: String,
+}
+
+#[cfg(test)]
+mod test {
+    use super::{start, RpcError, INTERNAL_ERROR, INVALID_INPUT, NOT_READY, VERIFICATION_FAILED};
+    use crate::discrete_log_cards::encoding::encode_standard_deck;
+    use crate::discrete_log_cards::{starting_deck, Card, Parameters, PublicKey};
+    use crate::error::{CardProtocolError, ErrorCode, InternalError};
+
+    use ark_serialize::{CanonicalDeserialize, CanonicalSerialize};
+    use serde_json::{json, Value};
+    use std::io::{Read, Write};
+    use std::net::{SocketAddr, TcpStream};
+
+    // Choose elliptic curve setting
+    type Curve = starknet_curve::Projective;
+
+    const CONTEXT: &str = "72706320746573742068616e64";
+
+    /// Post a call over HTTP and return its result or its error object.
+    fn call(address: &SocketAddr, method: &str, params: Value) -> Result<Value, Value> {
+        let body = json!({ "jsonrpc": "2.0", "id": 1, "method": method, "params": params });
+        let body = body.to_string();
+
+        let mut stream = TcpStream::connect(address).unwrap();
+        write!(
+            stream,
+            "POST / HTTP/1.1\r\nHost: {}\r\nContent-Type: application/json\r\n\
+             Content-Length: {}\r\nConnection: close\r\n\r\n{}",
+            address,
+            body.len(),
+            body
+        )
+        .unwrap();
+        let mut response = String::new();
+        stream.read_to_string(&mut response).unwrap();
+
+        let (_, body) = response.split_once("\r\n\r\n").unwrap();
+        let mut response: Value = serde_json::from_str(body).unwrap();
+        match response.get("error") {
+            Some(error) => Err(error.clone()),
+            None => Ok(response["result"].take()),
+        }
+    }
+
+    fn to_hex<T: CanonicalSerialize>(value: &T) -> String {
+        let mut bytes = Vec::new();
+        value.serialize(&mut bytes).unwrap();
+
+        hex::encode(bytes)
+    }
+
+    fn from_hex<T: CanonicalDeserialize>(value: &Value) -> T {
+        let bytes = hex::decode(value.as_str().unwrap()).unwrap();
+
+        T::deserialize(&bytes[..]).unwrap()
+    }
+
+    #[test]
+    fn test_two_player_hand() {
+        let localhost = "127.0.0.1:0".parse().unwrap();
+        let servers = [start(&localhost).unwrap(), start(&localhost).unwrap()];
+        let players = [*servers[0].address(), *servers[1].address()];
+        let info = [hex::encode("alice"), hex::encode("bob")];
+
+        // The first player runs the setup and the second checks the parameters
+        let parameters = call(&players[0], "setup", json!({ "m": 4, "n": 13 })).unwrap();
+        let parameters = parameters["parameters"].clone();
+        call(
+            &players[1],
+            "verify_parameters",
+            json!({ "parameters": parameters }),
+        )
+        .unwrap();
+
+        let keys = players
+            .iter()
+            .map(|player| call(player, "player_keygen", json!({})).unwrap()["public_key"].take())
+            .collect::<Vec<_>>();
+        let digest = call(&players[0], "key_list_digest", json!({ "keys": keys })).unwrap();
+        let digest = digest["key_list_digest"].clone();
+
+        let key_info = players
+            .iter()
+            .enumerate()
+            .map(|(index, player)| {
+                let proof = call(
+                    player,
+                    "prove_key_ownership",
+                    json!({
+                        "context": CONTEXT,
+                        "player_info": info[index],
+                        "player_index": index,
+                        "key_list_digest": digest,
+                    }),
+                )
+                .unwrap();
+                json!({
+                    "public_key": keys[index],
+                    "proof": proof["proof"],
+                    "player_info": info[index],
+                })
+            })
+            .collect::<Vec<_>>();
+        let shared_keys = players
+            .iter()
+            .map(|player| {
+                call(
+                    player,
+                    "compute_aggregate_key",
+                    json!({ "context": CONTEXT, "players": key_info }),
+                )
+                .unwrap()["shared_key"]
+                    .take()
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(shared_keys[0], shared_keys[1]);
+        let shared_key = shared_keys[0].clone();
+
+        // Every player can compute the starting deck
+        let pp: Parameters<Curve> = from_hex(&parameters);
+        let cards = encode_standard_deck::<Curve>(1).unwrap();
+        let mut deck = starting_deck(&pp, &from_hex::<PublicKey<Curve>>(&shared_key), &cards)
+            .unwrap()
+            .iter()
+            .map(|masked_card| Value::from(to_hex(masked_card)))
+            .collect::<Vec<_>>();
+
+        // Both players shuffle, and the other one checks the shuffle
+        for (shuffler, verifier) in [(0, 1), (1, 0)] {
+            let shuffled = call(
+                &players[shuffler],
+                "shuffle_and_remask",
+                json!({ "context": CONTEXT, "shared_key": shared_key, "deck": deck }),
+            )
+            .unwrap();
+            call(
+                &players[verifier],
+                "verify_shuffle",
+                json!({
+                    "context": CONTEXT,
+                    "shared_key": shared_key,
+                    "original_deck": deck,
+                    "shuffled_deck": shuffled["deck"],
+                    "proof": shuffled["proof"],
+                }),
+            )
+            .unwrap();
+
+            // The proof does not hold for the decks swapped
+            let error = call(
+                &players[verifier],
+                "verify_shuffle",
+                json!({
+                    "context": CONTEXT,
+                    "shared_key": shared_key,
+                    "original_deck": shuffled["deck"],
+                    "shuffled_deck": deck,
+                    "proof": shuffled["proof"],
+                }),
+            )
+            .unwrap_err();
+            assert_eq!(error["code"], VERIFICATION_FAILED);
+            assert_eq!(error["data"]["error"], "ShuffleVerificationFailed");
+
+            deck = shuffled["deck"].as_array().unwrap().clone();
+        }
+
+        // The first card goes to the first player and the second to the second player. The other
+        // player sends their token, checked by the owner, who adds their own and opens the card.
+        let mut dealt = Vec::new();
+        for (owner, other) in [(0, 1), (1, 0)] {
+            let masked_card = deck[owner].clone();
+            let shares = [other, owner]
+                .iter()
+                .map(|&player| {
+                    let reveal = call(
+                        &players[player],
+                        "compute_reveal_token",
+                        json!({ "context": CONTEXT, "masked_card": masked_card }),
+                    )
+                    .unwrap();
+                    call(
+                        &players[owner],
+                        "verify_reveal",
+                        json!({
+                            "context": CONTEXT,
+                            "public_key": keys[player],
+                            "token": reveal["token"],
+                            "masked_card": masked_card,
+                            "proof": reveal["proof"],
+                        }),
+                    )
+                    .unwrap();
+                    json!({
+                        "token": reveal["token"],
+                        "proof": reveal["proof"],
+                        "public_key": keys[player],
+                    })
+                })
+                .collect::<Vec<_>>();
+
+            let card = call(
+                &players[owner],
+                "unmask",
+                json!({
+                    "context": CONTEXT,
+                    "num_of_players": keys.len(),
+                    "decryption_key": shares,
+                    "masked_card": masked_card,
+                }),
+            )
+            .unwrap();
+            dealt.push(from_hex::<Card<Curve>>(&card["card"]));
+        }
+
+        assert_ne!(dealt[0], dealt[1]);
+        assert!(dealt.iter().all(|card| cards.contains(card)));
+    }
+
+    #[test]
+    fn test_errors() {
+        let server = start(&"127.0.0.1:0".parse().unwrap()).unwrap();
+        let address = server.address();
+
+        let error = call(address, "player_keygen", json!({})).unwrap_err();
+        assert_eq!(error["code"], NOT_READY);
+        assert_eq!(error["data"]["error"], "NoParameters");
+
+        call(address, "setup", json!({ "m": 4, "n": 13 })).unwrap();
+        let error = call(
+            address,
+            "compute_reveal_token",
+            json!({ "context": CONTEXT, "masked_card": "00" }),
+        )
+        .unwrap_err();
+        assert_eq!(error["code"], NOT_READY);
+        assert_eq!(error["data"]["error"], "NoKeys");
+        assert_eq!(error["data"]["error_code"], Value::Null);
+
+        let error = call(address, "key_list_digest", json!({ "keys": ["not hex"] })).unwrap_err();
+        assert_eq!(error["code"], INVALID_INPUT);
+        assert_eq!(error["data"]["error"], "SerializationError");
+        assert_eq!(
+            error["data"]["error_code"],
+            ErrorCode::SerializationError.value()
+        );
+
+        let error = call(address, "key_list_digest", json!({ "keys": ["0102"] })).unwrap_err();
+        assert_eq!(error["code"], INVALID_INPUT);
+        assert_eq!(error["data"]["error"], "InvalidPoint");
+        assert_eq!(error["data"]["error_code"], ErrorCode::InvalidPoint.value());
+    }
+
+    #[test]
+    fn test_internal_errors() {
+        // The environment failing is not the fault of the caller
+        let error = jsonrpc_core::Error::from(RpcError::Protocol(CardProtocolError::Internal(
+            InternalError::RandomnessFailure,
+        )));
+        assert_eq!(error.code.code(), INTERNAL_ERROR);
+
+        let data = error.data.unwrap();
+        assert_eq!(data["error"], "RandomnessFailure");
+        assert_eq!(data["error_code"], ErrorCode::RandomnessFailure.value());
+    }
+}
@@ -0,0 +1,261 @@
+//! Spans and events emitted with the `tracing` feature.
+//!
+//! The expensive operations of the protocol run in an info-level span named after them, carrying
+//! the deck size, the number of players and the size of the proof in bytes where they apply. A
+//! failed verification emits a debug-level event with the typed error, and never any witness
+//! data. Without the feature the macros below expand to nothing, and their arguments are not
+//! evaluated.
+
+/// Guard standing in for an entered span when the `tracing` feature is off.
+#[cfg(not(feature = "tracing"))]
+pub(crate) struct NoSpan;
+
+/// Enter an info-level span until the end of the enclosing block. Bind the guard it returns.
+#[cfg(feature = "tracing")]
+macro_rules! span {
+    ($name:literal $(, $($fields:tt)*)?) => {
+        tracing::info_span!($name $(, $($fields)*)?).entered()
+    };
+}
+#[cfg(not(feature = "tracing"))]
+macro_rules! span {
+    ($($arguments:tt)*) => {
+        $crate::telemetry::NoSpan
+    };
+}
+
+/// Record a field declared `tracing::field::Empty` on a span entered with `span!`.
+#[cfg(feature = "tracing")]
+macro_rules! record {
+    ($span:expr, $field:literal, $value:expr) => {
+        $span.record($field, $value);
+    };
+}
+#[cfg(not(feature = "tracing"))]
+macro_rules! record {
+    ($($arguments:tt)*) => {};
+}
+
+/// Emit a debug-level event with the error of `$result`, if it is one, and any further fields.
+#[cfg(feature = "tracing")]
+macro_rules! rejected {
+    ($result:expr, $message:literal $(, $($fields:tt)*)?) => {
+        if let Err(error) = &$result {
+            tracing::debug!($($($fields)*,)? ?error, $message);
+        }
+    };
+}
+#[cfg(not(feature = "tracing"))]
+macro_rules! rejected {
+    ($($arguments:tt)*) => {};
+}
+
+pub(crate) use {record, rejected, span};
+
+#[cfg(all(test, feature = "tracing"))]
+mod test {
+    use crate::crypto_primitives::utils::rand::test_rng;
+    use crate::discrete_log_cards;
+    use crate::discrete_log_cards::test_helpers::{prove_keys, reveal_tokens, setup_players};
+    use crate::BarnettSmartProtocol;
+
+    use ark_std::UniformRand;
+    use std::collections::BTreeMap;
+    use std::fmt::Debug;
+    use std::sync::{Arc, Mutex};
+    use tracing::field::{Field, Visit};
+    use tracing::span::{Attributes, Id, Record};
+    use tracing::{Event, Metadata, Subscriber};
+
+    // Choose elliptic curve setting
+    type Curve = starknet_curve::Projective;
+
+    // Instantiate concrete type for our card protocol
+    type CardProtocol<'a> = discrete_log_cards::DLCards<'a, Curve>;
+    type MaskedCard = discrete_log_cards::MaskedCard<Curve>;
+
+    const SESSION_ID: &[u8] = b"tracing test";
+
+    /// Fields of a span or an event, formatted with `Debug`
+    type Fields = BTreeMap<String, String>;
+
+    #[derive(Default)]
+    struct Recorded {
+        spans: Vec<(String, Fields)>,
+        events: Vec<Fields>,
+    }
+
+    /// Subscriber keeping the name and fields of every span, and the fields of every event
+    #[derive(Default)]
+    struct TestSubscriber(Arc<Mutex<Recorded>>);
+
+    struct FieldVisitor<'a>(&'a mut Fields);
+
+    impl Visit for FieldVisitor<'_> {
+        fn record_debug(&mut self, field: &Field, value: &dyn Debug) {
+            self.0
+                .insert(field.name().to_string(), format!("{:?}", value));
+        }
+    }
+
+    impl Subscriber for TestSubscriber {
+        fn enabled(&self, _: &Metadata<'_>) -> bool {
+            true
+        }
+
+        fn new_span(&self, span: &Attributes<'_>) -> Id {
+            let mut fields = Fields::new();
+            span.record(&mut FieldVisitor(&mut fields));
+
+            let mut recorded = self.0.lock().unwrap();
+            recorded
+                .spans
+                .push((span.metadata().name().to_string(), fields));
+
+            Id::from_u64(recorded.spans.len() as u64)
+        }
+
+        fn record(&self, span: &Id, values: &Record<'_>) {
+            let mut recorded = self.0.lock().unwrap();
+            let (_, fields) = &mut recorded.spans[span.into_u64() as usize - 1];
+            values.record(&mut FieldVisitor(fields));
+        }
+
+        fn record_follows_from(&self, _: &Id, _: &Id) {}
+
+        fn event(&self, event: &Event<'_>) {
+            let mut fields = Fields::new();
+            event.record(&mut FieldVisitor(&mut fields));
+            self.0.lock().unwrap().events.push(fields);
+        }
+
+        fn enter(&self, _: &Id) {}
+
+        fn exit(&self, _: &Id) {}
+    }
+
+    #[test]
+    fn test_spans_and_events() {
+        let rng = &mut test_rng();
+        let m = 4;
+        let n = 13;
+        let num_of_players = 2;
+
+        let subscriber = TestSubscriber::default();
+        let recorded = subscriber.0.clone();
+
+        tracing::subscriber::with_default(subscriber, || {
+            let parameters = CardProtocol::setup(rng, m, n).unwrap();
+            let (keys, _) = setup_players(rng, &parameters, num_of_players).unwrap();
+            let key_proof_info = prove_keys(rng, &parameters, SESSION_ID, &keys).unwrap();
+            let shared_key =
+                CardProtocol::compute_aggregate_key(&parameters, SESSION_ID, &key_proof_info)
+                    .unwrap();
+
+            let deck = (0..m * n)
+                .map(|_| MaskedCard::rand(rng))
+                .collect::<Vec<_>>();
+            let (shuffled, proof) = CardProtocol::shuffle_and_remask_with_rng(
+                rng,
+                &parameters,
+                SESSION_ID,
+                &shared_key,
+                &deck,
+            )
+            .unwrap();
+            assert_eq!(
+                Ok(()),
+                CardProtocol::verify_shuffle(
+                    &parameters,
+                    SESSION_ID,
+                    &shared_key,
+                    &deck,
+                    &shuffled,
+                    &proof
+                )
+            );
+            assert!(CardProtocol::verify_shuffle(
+                &parameters,
+                SESSION_ID,
+                &shared_key,
+                &shuffled,
+                &deck,
+                &proof
+            )
+            .is_err());
+
+            let decryption_key =
+                reveal_tokens(rng, &parameters, SESSION_ID, &keys, &shuffled[0]).unwrap();
+            assert!(CardProtocol::unmask(
+                &parameters,
+                SESSION_ID,
+                keys.len(),
+                &decryption_key,
+                &shuffled[0]
+            )
+            .is_ok());
+
+            // The second player's token is for another card
+            let mut wrong_card = decryption_key.clone();
+            let (pk, sk, _) = &keys[1];
+            let (token, proof) = CardProtocol::compute_reveal_token(
+                rng,
+                &parameters,
+                SESSION_ID,
+                sk,
+                pk,
+                &shuffled[1],
+            )
+            .unwrap();
+            wrong_card[1] = (token, proof, *pk);
+            assert!(CardProtocol::unmask(
+                &parameters,
+                SESSION_ID,
+                keys.len(),
+                &wrong_card,
+                &shuffled[0]
+            )
+            .is_err());
+        });
+
+        let recorded = recorded.lock().unwrap();
+        let spans = |name: &str| {
+            recorded
+                .spans
+                .iter()
+                .filter(|(span, _)| span == name)
+                .map(|(_, fields)| fields.clone())
+                .collect::<Vec<_>>()
+        };
+
+        let aggregations = spans("compute_aggregate_key");
+        assert_eq!(1, aggregations.len());
+        assert_eq!("2", aggregations[0]["players"]);
+
+        let shuffles = spans("shuffle_and_remask");
+        assert_eq!(1, shuffles.len());
+        assert_eq!("52", shuffles[0]["deck_size"]);
+        let proof_bytes = &shuffles[0]["proof_bytes"];
+
+        let verifications = spans("verify_shuffle");
+        assert_eq!(2, verifications.len());
+        for fields in &verifications {
+            assert_eq!("52", fields["deck_size"]);
+            assert_eq!(proof_bytes, &fields["proof_bytes"]);
+        }
+
+        let unmasks = spans("unmask");
+        assert_eq!(2, unmasks.len());
+        for fields in &unmasks {
+            assert_eq!("2", fields["players"]);
+        }
+
+        // Only the failed verifications are reported, with their error
+        assert_eq!(2, recorded.events.len());
+        assert_eq!("shuffle proof rejected", recorded.events[0]["message"]);
+        assert!(recorded.events[0].contains_key("error"));
+        assert_eq!("reveal token rejected", recorded.events[1]["message"]);
+        assert_eq!("1", recorded.events[1]["player"]);
+        assert!(recorded.events[1].contains_key("error"));
+    }
+}
@@ -0,0 +1,591 @@
+//! Bindings for running a player in the browser, built with `wasm-pack` and the `wasm` feature.
+//!
+//! The protocol runs on the starknet curve. Every value crosses the boundary as a `Uint8Array`
+//! holding its canonical encoding, and a list is the canonical encoding of a `Vec`: its length as
+//! a little-endian `u64` followed by its elements. Values that come from other players are decoded
+//! with the checks of the `validation` module, and secret keys never leave the [`KeyPair`] they
+//! are generated in.
+//!
+//! Errors are thrown as strings of the form `"Code: message"`, where the code is the name of the
+//! `CardProtocolError` variant, so that JavaScript can branch on it.
+
+use crate::crypto_primitives::utils::format::{Hex, HexBytes};
+use crate::discrete_log_cards::dkg::KeyOwnershipProof;
+use crate::discrete_log_cards::encoding::encode_standard_deck;
+use crate::discrete_log_cards::session::{RevealProof, ShuffleProof};
+use crate::discrete_log_cards::validation::{
+    bounded_from_bytes, from_bytes, public_key_from_bytes, to_bytes, validate_point,
+    vec_from_bytes, DecodingLimits,
+};
+use crate::discrete_log_cards::{
+    self, Card, DLCards, MaskedCard, Parameters, PlayerSecretKey, PublicKey, RevealToken,
+};
+use crate::error::{CardProtocolError, InputError};
+use crate::{BarnettSmartProtocol, KeyListDigest};
+
+use ark_serialize::{CanonicalDeserialize, CanonicalSerialize, SerializationError};
+use rand::thread_rng;
+use std::convert::TryFrom;
+use std::fmt;
+use wasm_bindgen::prelude::*;
+
+type Curve = starknet_curve::Projective;
+type CardProtocol<'a> = DLCards<'a, Curve>;
+
+#[wasm_bindgen]
+#[derive(Debug)]
+pub struct GameParameters {
+    pp: Parameters<Curve>,
+}
+
+#[wasm_bindgen]
+impl GameParameters {
+    /// Run the setup for a deck of `m * n` cards.
+    pub fn generate(m: usize, n: usize) -> Result<GameParameters, JsValue> {
+        let pp = CardProtocol::setup(&mut thread_rng(), m, n).map_err(js_error)?;
+
+        Ok(Self { pp })
+    }
+
+    /// Decode parameters produced by somebody else, and check that nobody knows a trapdoor to
+    /// their commitment key.
+    #[wasm_bindgen(js_name = fromBytes)]
+    pub fn from_bytes(bytes: &[u8]) -> Result<GameParameters, JsValue> {
+        let pp = Parameters::<Curve>::deserialize(bytes).map_err(js_error)?;
+        CardProtocol::verify_parameters(&pp).map_err(js_error)?;
+
+        Ok(Self { pp })
+    }
+
+    #[wasm_bindgen(js_name = toBytes)]
+    pub fn to_bytes(&self) -> Result<Vec<u8>, JsValue> {
+        to_bytes(&self.pp).map_err(js_error)
+    }
+
+    #[wasm_bindgen(js_name = numCards)]
+    pub fn num_cards(&self) -> usize {
+        self.pp.num_cards()
+    }
+}
+
+#[wasm_bindgen]
+pub struct KeyPair {
+    pk: PublicKey<Curve>,
+    sk: PlayerSecretKey<Curve>,
+}
+
+impl fmt::Debug for KeyPair {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("KeyPair")
+            .field("pk", &Hex(&self.pk))
+            .field("sk", &self.sk)
+            .finish()
+    }
+}
+
+#[wasm_bindgen]
+impl KeyPair {
+    pub fn generate(pp: &GameParameters) -> Result<KeyPair, JsValue> {
+        let (pk, sk) = CardProtocol::player_keygen(&mut thread_rng(), &pp.pp).map_err(js_error)?;
+
+        Ok(Self { pk, sk })
+    }
+
+    #[wasm_bindgen(js_name = publicKey)]
+    pub fn public_key(&self) -> Result<Vec<u8>, JsValue> {
+        to_bytes(&self.pk).map_err(js_error)
+    }
+}
+
+/// A value together with the proof that it was computed correctly.
+#[wasm_bindgen]
+pub struct Proven {
+    value: Vec<u8>,
+    proof: Vec<u8>,
+}
+
+#[wasm_bindgen]
+impl Proven {
+    #[wasm_bindgen(getter)]
+    pub fn value(&self) -> Vec<u8> {
+        self.value.clone()
+    }
+
+    #[wasm_bindgen(getter)]
+    pub fn proof(&self) -> Vec<u8> {
+        self.proof.clone()
+    }
+}
+
+impl fmt::Debug for Proven {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Proven")
+            .field("value", &HexBytes(&self.value))
+            .field("proof", &HexBytes(&self.proof))
+            .finish()
+    }
+}
+
+impl Proven {
+    fn new<V: CanonicalSerialize, P: CanonicalSerialize>(
+        value: &V,
+        proof: &P,
+    ) -> Result<Self, CardProtocolError> {
+        Ok(Self {
+            value: to_bytes(value)?,
+            proof: to_bytes(proof)?,
+        })
+    }
+}
+
+/// The list of the 52 cards of the standard deck, in the order of `standard_deck_labels`.
+#[wasm_bindgen(js_name = standardDeck)]
+pub fn standard_deck() -> Result<Vec<u8>, JsValue> {
+    let cards = encode_standard_deck::<Curve>(1).map_err(js_error)?;
+
+    to_bytes(&cards).map_err(js_error)
+}
+
+/// Digest of the list of the players' public keys, in seat order.
+#[wasm_bindgen(js_name = keyListDigest)]
+pub fn key_list_digest(keys: &[u8]) -> Result<Vec<u8>, JsValue> {
+    let keys = keys_from_bytes(keys, &DecodingLimits::default()).map_err(js_error)?;
+    let digest = CardProtocol::key_list_digest(&keys).map_err(js_error)?;
+
+    Ok(digest.to_vec())
+}
+
+#[wasm_bindgen(js_name = proveKeyOwnership)]
+pub fn prove_key_ownership(
+    pp: &GameParameters,
+    context: &[u8],
+    key_pair: &KeyPair,
+    player_info: &[u8],
+    player_index: usize,
+    key_list_digest: &[u8],
+) -> Result<Vec<u8>, JsValue> {
+    let proof = CardProtocol::prove_key_ownership(
+        &mut thread_rng(),
+        &pp.pp,
+        context,
+        &key_pair.pk,
+        &key_pair.sk,
+        &player_info.to_vec(),
+        player_index,
+        &digest_from_bytes(key_list_digest).map_err(js_error)?,
+    )
+    .map_err(js_error)?;
+
+    to_bytes(&proof).map_err(js_error)
+}
+
+#[wasm_bindgen(js_name = verifyKeyOwnership)]
+pub fn verify_key_ownership(
+    pp: &GameParameters,
+    context: &[u8],
+    public_key: &[u8],
+    player_info: &[u8],
+    player_index: usize,
+    key_list_digest: &[u8],
+    proof: &[u8],
+) -> Result<(), JsValue> {
+    let limits = DecodingLimits::for_parameters(&pp.pp);
+    let pk = public_key_from_bytes::<PublicKey<Curve>>(public_key).map_err(js_error)?;
+    let proof = bounded_from_bytes::<KeyOwnershipProof<Curve>>(proof, &limits).map_err(js_error)?;
+
+    CardProtocol::verify_key_ownership(
+        &pp.pp,
+        context,
+        &pk,
+        &player_info.to_vec(),
+        player_index,
+        &digest_from_bytes(key_list_digest).map_err(js_error)?,
+        &proof,
+    )
+    .map_err(js_error)
+}
+
+/// Aggregate key of the players, in seat order, after checking every proof of key ownership.
+/// `keys` and `proofs` are lists, and `player_info` is a list of byte strings.
+#[wasm_bindgen(js_name = aggregateKey)]
+pub fn aggregate_key(
+    pp: &GameParameters,
+    context: &[u8],
+    keys: &[u8],
+    proofs: &[u8],
+    player_info: &[u8],
+) -> Result<Vec<u8>, JsValue> {
+    let limits = DecodingLimits::for_parameters(&pp.pp);
+    let keys = keys_from_bytes(keys, &limits).map_err(js_error)?;
+    let proofs =
+        bounded_from_bytes::<Vec<KeyOwnershipProof<Curve>>>(proofs, &limits).map_err(js_error)?;
+    let player_info = bounded_from_bytes::<Vec<Vec<u8>>>(player_info, &limits).map_err(js_error)?;
+    if proofs.len() != keys.len() || player_info.len() != keys.len() {
+        return Err(js_error(CardProtocolError::InvalidInput(
+            InputError::InvalidPlayerCount(keys.len()),
+        )));
+    }
+
+    let key_proof_info = keys
+        .into_iter()
+        .zip(proofs)
+        .zip(player_info)
+        .map(|((pk, proof), info)| (pk, proof, info))
+        .collect::<Vec<_>>();
+    let shared_key =
+        CardProtocol::compute_aggregate_key(&pp.pp, context, &key_proof_info).map_err(js_error)?;
+
+    to_bytes(&shared_key).map_err(js_error)
+}
+
+/// The list of `cards` masked under `shared_key` with a masking factor of one, which every
+/// player can recompute before the first shuffle.
+#[wasm_bindgen(js_name = startingDeck)]
+pub fn starting_deck(
+    pp: &GameParameters,
+    shared_key: &[u8],
+    cards: &[u8],
+) -> Result<Vec<u8>, JsValue> {
+    let limits = DecodingLimits::for_parameters(&pp.pp);
+    let shared_key = public_key_from_bytes::<PublicKey<Curve>>(shared_key).map_err(js_error)?;
+    let cards = vec_from_bytes::<Card<Curve>>(cards, &limits).map_err(js_error)?;
+    let deck = discrete_log_cards::starting_deck(&pp.pp, &shared_key, &cards).map_err(js_error)?;
+
+    to_bytes(&deck).map_err(js_error)
+}
+
+#[wasm_bindgen]
+pub fn mask(
+    pp: &GameParameters,
+    context: &[u8],
+    shared_key: &[u8],
+    card: &[u8],
+) -> Result<Proven, JsValue> {
+    let shared_key = public_key_from_bytes::<PublicKey<Curve>>(shared_key).map_err(js_error)?;
+    let card = from_bytes::<Card<Curve>>(card).map_err(js_error)?;
+    let (masked_card, proof) =
+        CardProtocol::mask_with_rng(&mut thread_rng(), &pp.pp, context, &shared_key, &card)
+            .map_err(js_error)?;
+
+    Proven::new(&masked_card, &proof).map_err(js_error)
+}
+
+#[wasm_bindgen(js_name = verifyMask)]
+pub fn verify_mask(
+    pp: &GameParameters,
+    context: &[u8],
+    shared_key: &[u8],
+    card: &[u8],
+    masked_card: &[u8],
+    proof: &[u8],
+) -> Result<(), JsValue> {
+    let limits = DecodingLimits::for_parameters(&pp.pp);
+    let shared_key = public_key_from_bytes::<PublicKey<Curve>>(shared_key).map_err(js_error)?;
+    let card = from_bytes::<Card<Curve>>(card).map_err(js_error)?;
+    let masked_card = from_bytes::<MaskedCard<Curve>>(masked_card).map_err(js_error)?;
+    let proof = bounded_from_bytes::<RevealProof<Curve>>(proof, &limits).map_err(js_error)?;
+
+    CardProtocol::verify_mask(&pp.pp, context, &shared_key, &card, &masked_card, &proof)
+        .map_err(js_error)
+}
+
+/// Shuffle and remask the list `deck`. The value of the result is the shuffled list.
+#[wasm_bindgen]
+pub fn shuffle(
+    pp: &GameParameters,
+    context: &[u8],
+    shared_key: &[u8],
+    deck: &[u8],
+) -> Result<Proven, JsValue> {
+    let limits = DecodingLimits::for_parameters(&pp.pp);
+    let shared_key = public_key_from_bytes::<PublicKey<Curve>>(shared_key).map_err(js_error)?;
+    let deck = vec_from_bytes::<MaskedCard<Curve>>(deck, &limits).map_err(js_error)?;
+    let (shuffled, proof) = CardProtocol::shuffle_and_remask_with_rng(
+        &mut thread_rng(),
+        &pp.pp,
+        context,
+        &shared_key,
+        &deck,
+    )
+    .map_err(js_error)?;
+
+    Proven::new(&shuffled, &proof).map_err(js_error)
+}
+
+#[wasm_bindgen(js_name = verifyShuffle)]
+pub fn verify_shuffle(
+    pp: &GameParameters,
+    context: &[u8],
+    shared_key: &[u8],
+    deck: &[u8],
+    shuffled: &[u8],
+    proof: &[u8],
+) -> Result<(), JsValue> {
+    let limits = DecodingLimits::for_parameters(&pp.pp);
+    let shared_key = public_key_from_bytes::<PublicKey<Curve>>(shared_key).map_err(js_error)?;
+    let deck = vec_from_bytes::<MaskedCard<Curve>>(deck, &limits).map_err(js_error)?;
+    let shuffled = vec_from_bytes::<MaskedCard<Curve>>(shuffled, &limits).map_err(js_error)?;
+    let proof = bounded_from_bytes::<ShuffleProof<Curve>>(proof, &limits).map_err(js_error)?;
+
+    CardProtocol::verify_shuffle(&pp.pp, context, &shared_key, &deck, &shuffled, &proof)
+        .map_err(js_error)
+}
+
+#[wasm_bindgen(js_name = computeRevealToken)]
+pub fn compute_reveal_token(
+    pp: &GameParameters,
+    context: &[u8],
+    key_pair: &KeyPair,
+    masked_card: &[u8],
+) -> Result<Proven, JsValue> {
+    let masked_card = from_bytes::<MaskedCard<Curve>>(masked_card).map_err(js_error)?;
+    let (token, proof) = CardProtocol::compute_reveal_token(
+        &mut thread_rng(),
+        &pp.pp,
+        context,
+        &key_pair.sk,
+        &key_pair.pk,
+        &masked_card,
+    )
+    .map_err(js_error)?;
+
+    Proven::new(&token, &proof).map_err(js_error)
+}
+
+#[wasm_bindgen(js_name = verifyReveal)]
+pub fn verify_reveal(
+    pp: &GameParameters,
+    context: &[u8],
+    public_key: &[u8],
+    token: &[u8],
+    masked_card: &[u8],
+    proof: &[u8],
+) -> Result<(), JsValue> {
+    let limits = DecodingLimits::for_parameters(&pp.pp);
+    let pk = public_key_from_bytes::<PublicKey<Curve>>(public_key).map_err(js_error)?;
+    let token = from_bytes::<RevealToken<Curve>>(token).map_err(js_error)?;
+    let masked_card = from_bytes::<MaskedCard<Curve>>(masked_card).map_err(js_error)?;
+    let proof = bounded_from_bytes::<RevealProof<Curve>>(proof, &limits).map_err(js_error)?;
+
+    CardProtocol::verify_reveal(&pp.pp, context, &pk, &token, &masked_card, &proof)
+        .map_err(js_error)
+}
+
+/// Open `masked_card` from `tokens`, a list with the reveal token of every one of the
+/// `num_of_players` players followed by its proof and the player's public key. Every proof is
+/// checked.
+#[wasm_bindgen]
+pub fn unmask(
+    pp: &GameParameters,
+    context: &[u8],
+    num_of_players: usize,
+    tokens: &[u8],
+    masked_card: &[u8],
+) -> Result<Vec<u8>, JsValue> {
+    let limits = DecodingLimits::for_parameters(&pp.pp);
+    let tokens = vec_from_bytes::<(RevealToken<Curve>, RevealProof<Curve>, PublicKey<Curve>)>(
+        tokens, &limits,
+    )
+    .map_err(js_error)?;
+    let masked_card = from_bytes::<MaskedCard<Curve>>(masked_card).map_err(js_error)?;
+    let card = CardProtocol::unmask(&pp.pp, context, num_of_players, &tokens, &masked_card)
+        .map_err(js_error)?;
+
+    to_bytes(&card).map_err(js_error)
+}
+
+fn keys_from_bytes(
+    bytes: &[u8],
+    limits: &DecodingLimits,
+) -> Result<Vec<PublicKey<Curve>>, CardProtocolError> {
+    let keys = bounded_from_bytes::<Vec<PublicKey<Curve>>>(bytes, limits)?;
+    keys.iter().try_for_each(validate_point)?;
+
+    Ok(keys)
+}
+
+fn digest_from_bytes(bytes: &[u8]) -> Result<KeyListDigest, CardProtocolError> {
+    KeyListDigest::try_from(bytes).map_err(|_| SerializationError::InvalidData.into())
+}
+
+/// `"Code: message"`, where the code is the name of the error variant.
+fn js_error<E: Into<CardProtocolError>>(error: E) -> JsValue {
+    let error = error.into();
+
+    JsValue::from_str(&format!("{}: {}", error.code().name(), error))
+}
+
+#[cfg(all(test, target_arch = "wasm32"))]
+mod test {
+    use super::{
+        aggregate_key, compute_reveal_token, key_list_digest, mask, prove_key_ownership, shuffle,
+        standard_deck, starting_deck, unmask, verify_key_ownership, verify_mask, verify_reveal,
+        verify_shuffle, GameParameters, KeyPair,
+    };
+    use crate::discrete_log_cards::{Card, MaskedCard};
+
+    use ark_serialize::{CanonicalDeserialize, CanonicalSerialize};
+    use wasm_bindgen_test::{wasm_bindgen_test, wasm_bindgen_test_configure};
+
+    wasm_bindgen_test_configure!(run_in_browser);
+
+    // Choose elliptic curve setting
+    type Curve = starknet_curve::Projective;
+
+    const SESSION_ID: &[u8] = b"wasm test";
+
+    /// Canonical encoding of a list of encoded elements.
+    fn list(items: &[Vec<u8>]) -> Vec<u8> {
+        let mut bytes = (items.len() as u64).to_le_bytes().to_vec();
+        for item in items {
+            bytes.extend_from_slice(item);
+        }
+
+        bytes
+    }
+
+    #[wasm_bindgen_test]
+    fn test_two_player_game() {
+        let m = 4;
+        let n = 13;
+        let num_of_players = 2;
+
+        // The parameters travel from whoever generated them to every browser
+        let parameters = GameParameters::generate(m, n).unwrap();
+        let parameters = GameParameters::from_bytes(&parameters.to_bytes().unwrap()).unwrap();
+
+        let players = (0..num_of_players)
+            .map(|_| KeyPair::generate(&parameters).unwrap())
+            .collect::<Vec<_>>();
+        let keys = players
+            .iter()
+            .map(|player| player.public_key().unwrap())
+            .collect::<Vec<_>>();
+        let player_info = [b"alice".to_vec(), b"bob".to_vec()];
+
+        let digest = key_list_digest(&list(&keys)).unwrap();
+        let proofs = players
+            .iter()
+            .enumerate()
+            .map(|(index, player)| {
+                prove_key_ownership(
+                    &parameters,
+                    SESSION_ID,
+                    player,
+                    &player_info[index],
+                    index,
+                    &digest,
+                )
+                .unwrap()
+            })
+            .collect::<Vec<_>>();
+        for index in 0..num_of_players {
+            verify_key_ownership(
+                &parameters,
+                SESSION_ID,
+                &keys[index],
+                &player_info[index],
+                index,
+                &digest,
+                &proofs[index],
+            )
+            .unwrap();
+        }
+        let error = verify_key_ownership(
+            &parameters,
+            SESSION_ID,
+            &keys[0],
+            &player_info[0],
+            1,
+            &digest,
+            &proofs[0],
+        )
+        .unwrap_err();
+        assert!(error
+            .as_string()
+            .unwrap()
+            .starts_with("ProofVerificationError: "));
+
+        let mut encoded_info = Vec::new();
+        player_info.to_vec().serialize(&mut encoded_info).unwrap();
+        let shared_key = aggregate_key(
+            &parameters,
+            SESSION_ID,
+            &list(&keys),
+            &list(&proofs),
+            &encoded_info,
+        )
+        .unwrap();
+
+        let cards = standard_deck().unwrap();
+        let open_cards = Vec::<Card<Curve>>::deserialize(&cards[..]).unwrap();
+
+        // A card masked on its own
+        let mut card = Vec::new();
+        open_cards[0].serialize(&mut card).unwrap();
+        let masked = mask(&parameters, SESSION_ID, &shared_key, &card).unwrap();
+        verify_mask(
+            &parameters,
+            SESSION_ID,
+            &shared_key,
+            &card,
+            &masked.value(),
+            &masked.proof(),
+        )
+        .unwrap();
+
+        // Both players shuffle the deck in turn, and check each other's shuffle
+        let mut deck = starting_deck(&parameters, &shared_key, &cards).unwrap();
+        for _ in 0..num_of_players {
+            let shuffled = shuffle(&parameters, SESSION_ID, &shared_key, &deck).unwrap();
+            verify_shuffle(
+                &parameters,
+                SESSION_ID,
+                &shared_key,
+                &deck,
+                &shuffled.value(),
+                &shuffled.proof(),
+            )
+            .unwrap();
+            deck = shuffled.value();
+        }
+
+        // Both players reveal the top card
+        let masked_cards = Vec::<MaskedCard<Curve>>::deserialize(&deck[..]).unwrap();
+        let mut top = Vec::new();
+        masked_cards[0].serialize(&mut top).unwrap();
+        let tokens = players
+            .iter()
+            .enumerate()
+            .map(|(index, player)| {
+                let token = compute_reveal_token(&parameters, SESSION_ID, player, &top).unwrap();
+                verify_reveal(
+                    &parameters,
+                    SESSION_ID,
+                    &keys[index],
+                    &token.value(),
+                    &top,
+                    &token.proof(),
+                )
+                .unwrap();
+                [token.value(), token.proof(), keys[index].clone()].concat()
+            })
+            .collect::<Vec<_>>();
+
+        let card = unmask(&parameters, SESSION_ID, players.len(), &list(&tokens), &top).unwrap();
+        let card = Card::<Curve>::deserialize(&card[..]).unwrap();
+        assert!(open_cards.contains(&card));
+
+        // Tokens do not open any other card
+        let mut second = Vec::new();
+        masked_cards[1].serialize(&mut second).unwrap();
+        assert!(unmask(
+            &parameters,
+            SESSION_ID,
+            players.len(),
+            &list(&tokens),
+            &second
+        )
+        .is_err());
+    }
+}
@@ -0,0 +1,497 @@
+//! The `mentalpoker` Python module: the Barnett-Smart card protocol on the starknet curve.
+//!
+//! Every protocol value is an immutable object with `to_bytes` and a `from_bytes` static method
+//! decoding its canonical encoding, with the checks of the `validation` module. Values compare
+//! equal and hash alike when their encodings do. Decks are lists of `MaskedCard`s, and secret keys
+//! never leave the `PlayerKeys` they are generated in.
+//!
+//! Errors are raised as `CardProtocolError`, or one of its subclasses: `ProofVerificationError`
+//! when a proof or a check on public values fails, and `DecodingError` when bytes do not decode to
+//! a valid value.
+
+use barnett_smart_card_protocol::discrete_log_cards::encoding::{
+    encode_standard_deck, standard_deck_labels as labels,
+};
+use barnett_smart_card_protocol::discrete_log_cards::validation::{
+    bounded_from_bytes, from_bytes, public_key_from_bytes, to_bytes, DecodingLimits,
+};
+use barnett_smart_card_protocol::discrete_log_cards::{self as dl, DLCards};
+use barnett_smart_card_protocol::error::{
+    CardProtocolError as ProtocolError, InputError, InternalError, VerificationError,
+};
+use barnett_smart_card_protocol::{BarnettSmartProtocol, FromLabel, KeyListDigest};
+
+use ark_serialize::CanonicalSerialize;
+use pyo3::basic::CompareOp;
+use pyo3::create_exception;
+use pyo3::exceptions::PyException;
+use pyo3::prelude::*;
+use pyo3::types::PyBytes;
+use rand::thread_rng;
+use std::collections::hash_map::DefaultHasher;
+use std::convert::TryFrom;
+use std::hash::{Hash, Hasher};
+
+type Curve = starknet_curve::Projective;
+type CardProtocol<'a> = DLCards<'a, Curve>;
+
+create_exception!(mentalpoker, CardProtocolError, PyException);
+create_exception!(mentalpoker, ProofVerificationError, CardProtocolError);
+create_exception!(mentalpoker, DecodingError, CardProtocolError);
+
+/// A protocol value exposed to Python through its canonical encoding, with any further methods.
+macro_rules! value_class {
+    ($(#[$attr:meta])* $name:ident($inner:ty), $decode:expr $(, { $($method:item)* })?) => {
+        $(#[$attr])*
+        #[pyclass(module = "mentalpoker")]
+        #[derive(Clone)]
+        pub struct $name($inner);
+
+        #[pymethods]
+        impl $name {
+            #[staticmethod]
+            fn from_bytes(bytes: &[u8]) -> PyResult<Self> {
+                ($decode)(bytes).map(Self).map_err(py_error)
+            }
+
+            fn to_bytes(&self, py: Python<'_>) -> PyResult<Py<PyBytes>> {
+                py_bytes(py, &self.0)
+            }
+
+            fn __bytes__(&self, py: Python<'_>) -> PyResult<Py<PyBytes>> {
+                py_bytes(py, &self.0)
+            }
+
+            fn __richcmp__(
+                &self,
+                other: PyRef<'_, Self>,
+                op: CompareOp,
+                py: Python<'_>,
+            ) -> PyResult<PyObject> {
+                let equal = to_bytes(&self.0).map_err(py_error)?
+                    == to_bytes(&other.0).map_err(py_error)?;
+
+                Ok(match op {
+                    CompareOp::Eq => equal.into_py(py),
+                    CompareOp::Ne => (!equal).into_py(py),
+                    _ => py.NotImplemented(),
+                })
+            }
+
+            fn __hash__(&self) -> PyResult<u64> {
+                let mut hasher = DefaultHasher::new();
+                to_bytes(&self.0).map_err(py_error)?.hash(&mut hasher);
+
+                Ok(hasher.finish())
+            }
+
+            $($($method)*)?
+        }
+    };
+}
+
+value_class!(
+    /// A player's public key, or the aggregate key of the table.
+    PublicKey(dl::PublicKey<Curve>),
+    public_key_from_bytes::<dl::PublicKey<Curve>>
+);
+value_class!(
+    /// An open card.
+    Card(dl::Card<Curve>),
+    from_bytes::<dl::Card<Curve>>,
+    {
+        /// The card with the given label, such as `"As"`. See `standard_deck_labels`.
+        #[staticmethod]
+        fn from_label(label: &str) -> PyResult<Self> {
+            dl::Card::<Curve>::from_label(label.as_bytes())
+                .map(Self)
+                .map_err(py_error)
+        }
+    }
+);
+value_class!(
+    /// A card masked under the aggregate key.
+    MaskedCard(dl::MaskedCard<Curve>),
+    from_bytes::<dl::MaskedCard<Curve>>
+);
+value_class!(
+    /// A player's share of the unmasking of a card.
+    RevealToken(dl::RevealToken<Curve>),
+    from_bytes::<dl::RevealToken<Curve>>
+);
+value_class!(
+    KeyOwnershipProof(dl::dkg::KeyOwnershipProof<Curve>),
+    |bytes| bounded_from_bytes(bytes, &DecodingLimits::default())
+);
+value_class!(
+    /// Proof of a masking or of a reveal token.
+    RevealProof(dl::session::RevealProof<Curve>),
+    |bytes| bounded_from_bytes(bytes, &DecodingLimits::default())
+);
+value_class!(ShuffleProof(dl::session::ShuffleProof<Curve>), |bytes| {
+    bounded_from_bytes(bytes, &DecodingLimits::default())
+});
+
+#[pyclass(module = "mentalpoker")]
+pub struct Parameters(dl::Parameters<Curve>);
+
+#[pymethods]
+impl Parameters {
+    /// Run the setup for a deck of `m * n` cards.
+    #[staticmethod]
+    fn generate(m: usize, n: usize) -> PyResult<Self> {
+        CardProtocol::setup(&mut thread_rng(), m, n)
+            .map(Self)
+            .map_err(py_error)
+    }
+
+    /// Decode parameters produced by somebody else, and check that nobody knows a trapdoor to
+    /// their commitment key.
+    #[staticmethod]
+    fn from_bytes(bytes: &[u8]) -> PyResult<Self> {
+        let pp = bounded_from_bytes::<dl::Parameters<Curve>>(bytes, &DecodingLimits::default())
+            .map_err(py_error)?;
+        CardProtocol::verify_parameters(&pp).map_err(py_error)?;
+
+        Ok(Self(pp))
+    }
+
+    fn to_bytes(&self, py: Python<'_>) -> PyResult<Py<PyBytes>> {
+        py_bytes(py, &self.0)
+    }
+
+    #[getter]
+    fn num_cards(&self) -> usize {
+        self.0.num_cards()
+    }
+}
+
+#[pyclass(module = "mentalpoker")]
+pub struct PlayerKeys {
+    pk: dl::PublicKey<Curve>,
+    sk: dl::PlayerSecretKey<Curve>,
+}
+
+#[pymethods]
+impl PlayerKeys {
+    #[staticmethod]
+    fn generate(pp: &Parameters) -> PyResult<Self> {
+        let (pk, sk) = CardProtocol::player_keygen(&mut thread_rng(), &pp.0).map_err(py_error)?;
+
+        Ok(Self { pk, sk })
+    }
+
+    #[getter]
+    fn public_key(&self) -> PublicKey {
+        PublicKey(self.pk)
+    }
+
+    /// Prove ownership of the key for the seat `player_index`.
+    fn prove_ownership(
+        &self,
+        pp: &Parameters,
+        context: &[u8],
+        player_info: &[u8],
+        player_index: usize,
+        key_list_digest: &[u8],
+    ) -> PyResult<KeyOwnershipProof> {
+        CardProtocol::prove_key_ownership(
+            &mut thread_rng(),
+            &pp.0,
+            context,
+            &self.pk,
+            &self.sk,
+            &player_info.to_vec(),
+            player_index,
+            &digest_from_bytes(key_list_digest)?,
+        )
+        .map(KeyOwnershipProof)
+        .map_err(py_error)
+    }
+
+    /// This player's reveal token for a masked card, with its proof.
+    fn reveal_token(
+        &self,
+        pp: &Parameters,
+        context: &[u8],
+        masked_card: &MaskedCard,
+    ) -> PyResult<(RevealToken, RevealProof)> {
+        let (token, proof) = CardProtocol::compute_reveal_token(
+            &mut thread_rng(),
+            &pp.0,
+            context,
+            &self.sk,
+            &self.pk,
+            &masked_card.0,
+        )
+        .map_err(py_error)?;
+
+        Ok((RevealToken(token), RevealProof(proof)))
+    }
+}
+
+/// Labels of the 52 standard cards, ordered by rank and then by suit: `2c, 2d, ..., As`.
+#[pyfunction]
+fn standard_deck_labels() -> Vec<String> {
+    labels()
+}
+
+/// The 52 cards of the standard deck, in the order of `standard_deck_labels`.
+#[pyfunction]
+fn standard_deck() -> PyResult<Vec<Card>> {
+    let cards = encode_standard_deck::<Curve>(1).map_err(py_error)?;
+
+    Ok(cards.into_iter().map(Card).collect())
+}
+
+/// Digest of the list of the players' public keys, in seat order.
+#[pyfunction]
+fn key_list_digest(py: Python<'_>, keys: Vec<PublicKey>) -> PyResult<Py<PyBytes>> {
+    let keys = keys.into_iter().map(|key| key.0).collect::<Vec<_>>();
+    let digest = CardProtocol::key_list_digest(&keys).map_err(py_error)?;
+
+    Ok(PyBytes::new(py, &digest).into())
+}
+
+#[pyfunction]
+fn verify_key_ownership(
+    pp: &Parameters,
+    context: &[u8],
+    public_key: &PublicKey,
+    player_info: &[u8],
+    player_index: usize,
+    key_list_digest: &[u8],
+    proof: &KeyOwnershipProof,
+) -> PyResult<()> {
+    CardProtocol::verify_key_ownership(
+        &pp.0,
+        context,
+        &public_key.0,
+        &player_info.to_vec(),
+        player_index,
+        &digest_from_bytes(key_list_digest)?,
+        &proof.0,
+    )
+    .map_err(py_error)
+}
+
+/// Aggregate key of the players, from a list of `(public_key, proof, player_info)` in seat order,
+/// after checking every proof of key ownership.
+#[pyfunction]
+fn aggregate_key(
+    pp: &Parameters,
+    context: &[u8],
+    players: Vec<(PublicKey, KeyOwnershipProof, Vec<u8>)>,
+) -> PyResult<PublicKey> {
+    let key_proof_info = players
+        .into_iter()
+        .map(|(pk, proof, info)| (pk.0, proof.0, info))
+        .collect::<Vec<_>>();
+
+    CardProtocol::compute_aggregate_key(&pp.0, context, &key_proof_info)
+        .map(PublicKey)
+        .map_err(py_error)
+}
+
+/// The cards masked under `shared_key` with a masking factor of one, which every player can
+/// recompute before the first shuffle.
+#[pyfunction]
+fn starting_deck(
+    pp: &Parameters,
+    shared_key: &PublicKey,
+    cards: Vec<Card>,
+) -> PyResult<Vec<MaskedCard>> {
+    let cards = cards.into_iter().map(|card| card.0).collect::<Vec<_>>();
+    let deck = dl::starting_deck(&pp.0, &shared_key.0, &cards).map_err(py_error)?;
+
+    Ok(deck.into_iter().map(MaskedCard).collect())
+}
+
+#[pyfunction]
+fn mask(
+    pp: &Parameters,
+    context: &[u8],
+    shared_key: &PublicKey,
+    card: &Card,
+) -> PyResult<(MaskedCard, RevealProof)> {
+    let (masked_card, proof) =
+        CardProtocol::mask_with_rng(&mut thread_rng(), &pp.0, context, &shared_key.0, &card.0)
+            .map_err(py_error)?;
+
+    Ok((MaskedCard(masked_card), RevealProof(proof)))
+}
+
+#[pyfunction]
+fn verify_mask(
+    pp: &Parameters,
+    context: &[u8],
+    shared_key: &PublicKey,
+    card: &Card,
+    masked_card: &MaskedCard,
+    proof: &RevealProof,
+) -> PyResult<()> {
+    CardProtocol::verify_mask(
+        &pp.0,
+        context,
+        &shared_key.0,
+        &card.0,
+        &masked_card.0,
+        &proof.0,
+    )
+    .map_err(py_error)
+}
+
+/// Shuffle and remask a deck, and prove it.
+#[pyfunction]
+fn shuffle(
+    pp: &Parameters,
+    context: &[u8],
+    shared_key: &PublicKey,
+    deck: Vec<MaskedCard>,
+) -> PyResult<(Vec<MaskedCard>, ShuffleProof)> {
+    let deck = deck.into_iter().map(|card| card.0).collect::<Vec<_>>();
+    let (shuffled, proof) = CardProtocol::shuffle_and_remask_with_rng(
+        &mut thread_rng(),
+        &pp.0,
+        context,
+        &shared_key.0,
+        &deck,
+    )
+    .map_err(py_error)?;
+
+    Ok((
+        shuffled.into_iter().map(MaskedCard).collect(),
+        ShuffleProof(proof),
+    ))
+}
+
+#[pyfunction]
+fn verify_shuffle(
+    pp: &Parameters,
+    context: &[u8],
+    shared_key: &PublicKey,
+    deck: Vec<MaskedCard>,
+    shuffled: Vec<MaskedCard>,
+    proof: &ShuffleProof,
+) -> PyResult<()> {
+    let deck = deck.into_iter().map(|card| card.0).collect::<Vec<_>>();
+    let shuffled = shuffled.into_iter().map(|card| card.0).collect::<Vec<_>>();
+
+    CardProtocol::verify_shuffle(&pp.0, context, &shared_key.0, &deck, &shuffled, &proof.0)
+        .map_err(py_error)
+}
+
+#[pyfunction]
+fn verify_reveal(
+    pp: &Parameters,
+    context: &[u8],
+    public_key: &PublicKey,
+    token: &RevealToken,
+    masked_card: &MaskedCard,
+    proof: &RevealProof,
+) -> PyResult<()> {
+    CardProtocol::verify_reveal(
+        &pp.0,
+        context,
+        &public_key.0,
+        &token.0,
+        &masked_card.0,
+        &proof.0,
+    )
+    .map_err(py_error)
+}
+
+/// Open a masked card from a list of `(token, proof, public_key)`, with the reveal token of every
+/// one of the `num_of_players` players. Every proof is checked.
+#[pyfunction]
+fn unmask(
+    pp: &Parameters,
+    context: &[u8],
+    num_of_players: usize,
+    tokens: Vec<(RevealToken, RevealProof, PublicKey)>,
+    masked_card: &MaskedCard,
+) -> PyResult<Card> {
+    let tokens = tokens
+        .into_iter()
+        .map(|(token, proof, pk)| (token.0, proof.0, pk.0))
+        .collect::<Vec<_>>();
+
+    CardProtocol::unmask(&pp.0, context, num_of_players, &tokens, &masked_card.0)
+        .map(Card)
+        .map_err(py_error)
+}
+
+#[pymodule]
+fn mentalpoker(py: Python<'_>, m: &PyModule) -> PyResult<()> {
+    m.add("CardProtocolError", py.get_type::<CardProtocolError>())?;
+    m.add(
+        "ProofVerificationError",
+        py.get_type::<ProofVerificationError>(),
+    )?;
+    m.add("DecodingError", py.get_type::<DecodingError>())?;
+
+    m.add_class::<Parameters>()?;
+    m.add_class::<PlayerKeys>()?;
+    m.add_class::<PublicKey>()?;
+    m.add_class::<Card>()?;
+    m.add_class::<MaskedCard>()?;
+    m.add_class::<RevealToken>()?;
+    m.add_class::<KeyOwnershipProof>()?;
+    m.add_class::<RevealProof>()?;
+    m.add_class::<ShuffleProof>()?;
+
+    m.add_function(wrap_pyfunction!(standard_deck_labels, m)?)?;
+    m.add_function(wrap_pyfunction!(standard_deck, m)?)?;
+    m.add_function(wrap_pyfunction!(key_list_digest, m)?)?;
+    m.add_function(wrap_pyfunction!(verify_key_ownership, m)?)?;
+    m.add_function(wrap_pyfunction!(aggregate_key, m)?)?;
+    m.add_function(wrap_pyfunction!(starting_deck, m)?)?;
+    m.add_function(wrap_pyfunction!(mask, m)?)?;
+    m.add_function(wrap_pyfunction!(verify_mask, m)?)?;
+    m.add_function(wrap_pyfunction!(shuffle, m)?)?;
+    m.add_function(wrap_pyfunction!(verify_shuffle, m)?)?;
+    m.add_function(wrap_pyfunction!(verify_reveal, m)?)?;
+    m.add_function(wrap_pyfunction!(unmask, m)?)?;
+
+    Ok(())
+}
+
+fn py_bytes<T: CanonicalSerialize>(py: Python<'_>, value: &T) -> PyResult<Py<PyBytes>> {
+    let bytes = to_bytes(value).map_err(py_error)?;
+
+    Ok(PyBytes::new(py, &bytes).into())
+}
+
+fn digest_from_bytes(bytes: &[u8]) -> PyResult<KeyListDigest> {
+    KeyListDigest::try_from(bytes)
+        .map_err(|_| DecodingError::new_err("A key list digest is 32 bytes long"))
+}
+
+/// The exception for an error of the protocol. Failed proofs carry the message of the underlying
+/// `CryptoError` or `ShuffleVerificationFailure`.
+fn py_error<E: Into<ProtocolError>>(error: E) -> PyErr {
+    let error = error.into();
+    match &error {
+        ProtocolError::VerificationFailed(VerificationError::ProofVerificationError(crypto)) => {
+            ProofVerificationError::new_err(crypto.to_string())
+        }
+        ProtocolError::VerificationFailed(VerificationError::ShuffleVerificationFailed(
+            failure,
+        )) => ProofVerificationError::new_err(failure.to_string()),
+        ProtocolError::VerificationFailed(
+            VerificationError::PlayerMessage { .. }
+            | VerificationError::InvalidKeyOwnershipProof { .. }
+            | VerificationError::InvalidRevealToken { .. }
+            | VerificationError::UnverifiableParameters,
+        ) => ProofVerificationError::new_err(error.to_string()),
+        ProtocolError::InvalidInput(
+            InputError::SerializationError(_)
+            | InputError::InvalidPoint
+            | InputError::MessageTooLarge(_),
+        )
+        | ProtocolError::Internal(InternalError::IoError(_)) => {
+            DecodingError::new_err(error.to_string())
+        }
+        _ => CardProtocolError::new_err(error.to_string()),
+    }
+}
@@ -0,0 +1,106 @@
+"""A complete three-player hand through the Python bindings: setup, keys, shuffles, two hole
+cards per player and five community cards."""
+
+import pytest
+
+import mentalpoker as mp
+
+SESSION_ID = b"python test"
+NUM_PLAYERS = 3
+
+
+def reveal(pp, players, masked_card):
+    """Every player's verified reveal token for a card."""
+    tokens = []
+    for player in players:
+        token, proof = player.reveal_token(pp, SESSION_ID, masked_card)
+        mp.verify_reveal(pp, SESSION_ID, player.public_key, token, masked_card, proof)
+        tokens.append((token, proof, player.public_key))
+    return tokens
+
+
+@pytest.fixture(scope="module")
+def table():
+    # The parameters travel from whoever generated them to every player
+    pp = mp.Parameters.from_bytes(mp.Parameters.generate(4, 13).to_bytes())
+    assert pp.num_cards == 52
+
+    players = [mp.PlayerKeys.generate(pp) for _ in range(NUM_PLAYERS)]
+    keys = [player.public_key for player in players]
+    info = [b"alice", b"bob", b"carol"]
+    digest = mp.key_list_digest(keys)
+
+    proofs = []
+    for index, player in enumerate(players):
+        proof = player.prove_ownership(pp, SESSION_ID, info[index], index, digest)
+        mp.verify_key_ownership(pp, SESSION_ID, keys[index], info[index], index, digest, proof)
+        proofs.append(proof)
+
+    shared_key = mp.aggregate_key(pp, SESSION_ID, list(zip(keys, proofs, info)))
+    return pp, players, shared_key
+
+
+def test_three_player_hand(table):
+    pp, players, shared_key = table
+    cards = mp.standard_deck()
+    labels = dict(zip(cards, mp.standard_deck_labels()))
+
+    # Every player shuffles the deck in turn, and the others check the shuffle
+    deck = mp.starting_deck(pp, shared_key, cards)
+    for _ in players:
+        shuffled, proof = mp.shuffle(pp, SESSION_ID, shared_key, deck)
+        mp.verify_shuffle(pp, SESSION_ID, shared_key, deck, shuffled, proof)
+        deck = shuffled
+
+    # Hole cards: the other players send their tokens, and the owner adds its own
+    dealt = []
+    for seat, owner in enumerate(players):
+        for masked_card in deck[2 * seat : 2 * seat + 2]:
+            others = [player for player in players if player is not owner]
+            tokens = reveal(pp, others, masked_card) + reveal(pp, [owner], masked_card)
+            dealt.append(mp.unmask(pp, SESSION_ID, NUM_PLAYERS, tokens, masked_card))
+
+    # Community cards are opened by everybody
+    for masked_card in deck[2 * NUM_PLAYERS : 2 * NUM_PLAYERS + 5]:
+        tokens = reveal(pp, players, masked_card)
+        dealt.append(mp.unmask(pp, SESSION_ID, NUM_PLAYERS, tokens, masked_card))
+
+    assert len(set(dealt)) == 2 * NUM_PLAYERS + 5
+    assert all(card in labels for card in dealt)
+    assert mp.Card.from_label(labels[dealt[0]]) == dealt[0]
+
+
+def test_values_round_trip(table):
+    pp, players, shared_key = table
+    card = mp.Card.from_label("As")
+    masked_card, proof = mp.mask(pp, SESSION_ID, shared_key, card)
+
+    masked_card = mp.MaskedCard.from_bytes(masked_card.to_bytes())
+    proof = mp.RevealProof.from_bytes(bytes(proof))
+    mp.verify_mask(pp, SESSION_ID, shared_key, card, masked_card, proof)
+    assert mp.PublicKey.from_bytes(shared_key.to_bytes()) == shared_key
+
+
+def test_errors(table):
+    pp, players, shared_key = table
+    deck = mp.starting_deck(pp, shared_key, mp.standard_deck())
+    shuffled, proof = mp.shuffle(pp, SESSION_ID, shared_key, deck)
+
+    # The proof does not hold for the decks swapped, nor in another session
+    with pytest.raises(mp.ProofVerificationError):
+        mp.verify_shuffle(pp, SESSION_ID, shared_key, shuffled, deck, proof)
+    with pytest.raises(mp.ProofVerificationError):
+        mp.verify_shuffle(pp, b"another session", shared_key, deck, shuffled, proof)
+
+    # Tokens for one card do not open another
+    tokens = reveal(pp, players, shuffled[0])
+    with pytest.raises(mp.ProofVerificationError, match="Reveal token 0"):
+        mp.unmask(pp, SESSION_ID, len(players), tokens, shuffled[1])
+
+    with pytest.raises(mp.DecodingError):
+        mp.MaskedCard.from_bytes(b"not a card")
+    with pytest.raises(mp.DecodingError):
+        mp.Parameters.from_bytes(pp.to_bytes()[:-1])
+    with pytest.raises(mp.DecodingError):
+        players[0].prove_ownership(pp, SESSION_ID, b"alice", 0, b"short digest")
+    assert issubclass(mp.DecodingError, mp.CardProtocolError)
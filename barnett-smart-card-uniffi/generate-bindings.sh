@@ -0,0 +1,14 @@
+#!/bin/sh
+# Regenerate the Kotlin and Swift sources under `bindings` from the compiled library. Run it after
+# changing the exported API, and check the output in: CI fails when it differs.
+set -eu
+
+cd "$(dirname "$0")"
+cargo build --release --lib
+library="../target/release/libmentalpoker_uniffi.$(if [ "$(uname)" = Darwin ]; then echo dylib; else echo so; fi)"
+
+for language in kotlin swift; do
+    rm -rf "bindings/$language"
+    cargo run --release --bin uniffi-bindgen -- generate --library "$library" \
+        --language "$language" --config uniffi.toml --out-dir "bindings/$language"
+done
@@ -0,0 +1,6 @@
+//! The uniffi binding generator, pinned to the version of uniffi the crate is built with. See
+//! `generate-bindings.sh` for how it is run.
+
+fn main() {
+    uniffi::uniffi_bindgen_main()
+}
@@ -0,0 +1,512 @@
+//! Kotlin and Swift bindings for the Barnett-Smart card protocol on the starknet curve, generated
+//! with uniffi. The generated sources are under `bindings`.
+//!
+//! Protocol values cross the boundary as byte arrays holding their canonical encoding, as in the
+//! C and Python bindings, and are decoded with the checks of the `validation` module. Parameters
+//! and key pairs are objects, so that secret keys never leave the `PlayerKeys` they are generated
+//! in. Lists of values are lists of byte arrays.
+//!
+//! Every error is a [`CardProtocolError`], whose variants mirror the ones of the protocol that the
+//! exported operations can return, with the message of the protocol error. Kotlin sees it as
+//! `CardProtocolException`.
+
+use barnett_smart_card_protocol::discrete_log_cards::dkg::KeyOwnershipProof;
+use barnett_smart_card_protocol::discrete_log_cards::encoding::{
+    encode_standard_deck, standard_deck_labels as labels,
+};
+use barnett_smart_card_protocol::discrete_log_cards::session::{RevealProof, ShuffleProof};
+use barnett_smart_card_protocol::discrete_log_cards::validation::{
+    bounded_from_bytes, from_bytes, public_key_from_bytes, to_bytes, DecodingLimits, Validate,
+};
+use barnett_smart_card_protocol::discrete_log_cards::{self as dl, DLCards};
+use barnett_smart_card_protocol::error::{
+    CardProtocolError as ProtocolError, CryptoError, InputError, InternalError,
+    ShuffleVerificationFailure, VerificationError,
+};
+use barnett_smart_card_protocol::{BarnettSmartProtocol, FromLabel, KeyListDigest};
+
+use ark_serialize::{CanonicalDeserialize, CanonicalSerialize};
+use rand::thread_rng;
+use std::convert::TryFrom;
+use std::sync::Arc;
+
+// Namespace of the generated sources, which does not follow the name of the library
+uniffi::setup_scaffolding!("mentalpoker");
+
+type Curve = starknet_curve::Projective;
+type CardProtocol<'a> = DLCards<'a, Curve>;
+
+/// Errors of the protocol, by the name of their variant in the protocol crate. Errors of the
+/// protocol that none of the exported operations return are reported as `Other`.
+#[derive(Debug, thiserror::Error, uniffi::Error)]
+#[uniffi(flat_error)]
+pub enum CardProtocolError {
+    /// A proof did not verify
+    #[error("{0}")]
+    ProofVerificationError(String),
+    /// A shuffle proof did not verify
+    #[error("{0}")]
+    ShuffleVerificationFailed(String),
+    /// A check on public values failed
+    #[error("{0}")]
+    VerificationFailed(String),
+    /// The proof of ownership of a key in a list does not verify
+    #[error("{0}")]
+    InvalidKeyOwnershipProof(String),
+    /// A reveal token in a list does not verify
+    #[error("{0}")]
+    InvalidRevealToken(String),
+    #[error("{0}")]
+    UnverifiableParameters(String),
+    #[error("{0}")]
+    SerializationError(String),
+    #[error("{0}")]
+    IoError(String),
+    #[error("{0}")]
+    InvalidPoint(String),
+    #[error("{0}")]
+    EncodingLengthError(String),
+    #[error("{0}")]
+    MessageTooLarge(String),
+    #[error("{0}")]
+    DimensionMismatch(String),
+    #[error("{0}")]
+    IdentityPublicKey(String),
+    #[error("{0}")]
+    DuplicatePlayerKey(String),
+    #[error("{0}")]
+    ReusedMaskingFactor(String),
+    #[error("{0}")]
+    InvalidCardLabel(String),
+    #[error("{0}")]
+    UnknownCard(String),
+    #[error("{0}")]
+    Other(String),
+}
+
+impl From<ProtocolError> for CardProtocolError {
+    fn from(error: ProtocolError) -> Self {
+        let message = error.to_string();
+
+        match error {
+            ProtocolError::VerificationFailed(error) => match error {
+                VerificationError::ProofVerificationError(_) => {
+                    Self::ProofVerificationError(message)
+                }
+                VerificationError::ShuffleVerificationFailed(failure) => {
+                    Self::ShuffleVerificationFailed(format!("{}: {}", message, failure))
+                }
+                VerificationError::PlayerMessage { .. } => Self::VerificationFailed(message),
+                VerificationError::InvalidKeyOwnershipProof { .. } => {
+                    Self::InvalidKeyOwnershipProof(message)
+                }
+                VerificationError::InvalidRevealToken { .. } => Self::InvalidRevealToken(message),
+                VerificationError::UnverifiableParameters => Self::UnverifiableParameters(message),
+                _ => Self::Other(message),
+            },
+            ProtocolError::InvalidInput(error) => match error {
+                InputError::SerializationError(_) => Self::SerializationError(message),
+                InputError::InvalidPoint => Self::InvalidPoint(message),
+                InputError::EncodingLengthError { .. } => Self::EncodingLengthError(message),
+                InputError::MessageTooLarge(_) => Self::MessageTooLarge(message),
+                InputError::DimensionMismatch { .. } => Self::DimensionMismatch(message),
+                InputError::IdentityPublicKey(_) => Self::IdentityPublicKey(message),
+                InputError::DuplicatePlayerKey { .. } => Self::DuplicatePlayerKey(message),
+                InputError::ReusedMaskingFactor => Self::ReusedMaskingFactor(message),
+                InputError::InvalidCardLabel(_) => Self::InvalidCardLabel(message),
+                InputError::UnknownCard => Self::UnknownCard(message),
+                _ => Self::Other(message),
+            },
+            ProtocolError::Internal(error) => match error {
+                InternalError::SerializationError(_) => Self::SerializationError(message),
+                InternalError::IoError(_) => Self::IoError(message),
+                _ => Self::Other(message),
+            },
+        }
+    }
+}
+
+impl From<CryptoError> for CardProtocolError {
+    fn from(error: CryptoError) -> Self {
+        ProtocolError::from(error).into()
+    }
+}
+
+impl From<ShuffleVerificationFailure> for CardProtocolError {
+    fn from(error: ShuffleVerificationFailure) -> Self {
+        ProtocolError::from(error).into()
+    }
+}
+
+type Result<T> = std::result::Result<T, CardProtocolError>;
+
+/// A masked card with the proof of its masking.
+#[derive(uniffi::Record)]
+pub struct Masking {
+    pub masked_card: Vec<u8>,
+    pub proof: Vec<u8>,
+}
+
+/// A shuffled and remasked deck with the proof of the shuffle.
+#[derive(uniffi::Record)]
+pub struct Shuffle {
+    pub deck: Vec<Vec<u8>>,
+    pub proof: Vec<u8>,
+}
+
+/// A player's reveal token for a card, with its proof and the player's public key.
+#[derive(uniffi::Record)]
+pub struct Reveal {
+    pub token: Vec<u8>,
+    pub proof: Vec<u8>,
+    pub public_key: Vec<u8>,
+}
+
+/// A player's public key, with the proof that they own it and the information it was proven for.
+#[derive(uniffi::Record)]
+pub struct KeyOwnership {
+    pub public_key: Vec<u8>,
+    pub proof: Vec<u8>,
+    pub player_info: Vec<u8>,
+}
+
+#[derive(uniffi::Object)]
+pub struct Parameters(dl::Parameters<Curve>);
+
+#[uniffi::export]
+impl Parameters {
+    /// Run the setup for a deck of `m * n` cards.
+    #[uniffi::constructor]
+    pub fn generate(m: u32, n: u32) -> Result<Arc<Self>> {
+        let pp = CardProtocol::setup(&mut thread_rng(), m as usize, n as usize)?;
+
+        Ok(Arc::new(Self(pp)))
+    }
+
+    /// Decode parameters produced by somebody else, and check that nobody knows a trapdoor to
+    /// their commitment key.
+    #[uniffi::constructor]
+    pub fn from_bytes(bytes: Vec<u8>) -> Result<Arc<Self>> {
+        let pp = bounded_from_bytes::<dl::Parameters<Curve>>(&bytes, &DecodingLimits::default())?;
+        CardProtocol::verify_parameters(&pp)?;
+
+        Ok(Arc::new(Self(pp)))
+    }
+
+    pub fn to_bytes(&self) -> Result<Vec<u8>> {
+        Ok(to_bytes(&self.0)?)
+    }
+
+    pub fn num_cards(&self) -> u32 {
+        self.0.num_cards() as u32
+    }
+}
+
+#[derive(uniffi::Object)]
+pub struct PlayerKeys {
+    pk: dl::PublicKey<Curve>,
+    sk: dl::PlayerSecretKey<Curve>,
+}
+
+#[uniffi::export]
+impl PlayerKeys {
+    #[uniffi::constructor]
+    pub fn generate(pp: Arc<Parameters>) -> Result<Arc<Self>> {
+        let (pk, sk) = CardProtocol::player_keygen(&mut thread_rng(), &pp.0)?;
+
+        Ok(Arc::new(Self { pk, sk }))
+    }
+
+    pub fn public_key(&self) -> Result<Vec<u8>> {
+        Ok(to_bytes(&self.pk)?)
+    }
+
+    /// Prove ownership of the key for the seat `player_index`.
+    pub fn prove_ownership(
+        &self,
+        pp: Arc<Parameters>,
+        context: Vec<u8>,
+        player_info: Vec<u8>,
+        player_index: u32,
+        key_list_digest: Vec<u8>,
+    ) -> Result<Vec<u8>> {
+        let proof = CardProtocol::prove_key_ownership(
+            &mut thread_rng(),
+            &pp.0,
+            &context,
+            &self.pk,
+            &self.sk,
+            &player_info,
+            player_index as usize,
+            &digest_from_bytes(&key_list_digest)?,
+        )?;
+
+        Ok(to_bytes(&proof)?)
+    }
+
+    /// This player's reveal token for a masked card, with its proof.
+    pub fn reveal_token(
+        &self,
+        pp: Arc<Parameters>,
+        context: Vec<u8>,
+        masked_card: Vec<u8>,
+    ) -> Result<Reveal> {
+        let masked_card = from_bytes::<dl::MaskedCard<Curve>>(&masked_card)?;
+        let (token, proof) = CardProtocol::compute_reveal_token(
+            &mut thread_rng(),
+            &pp.0,
+            &context,
+            &self.sk,
+            &self.pk,
+            &masked_card,
+        )?;
+
+        Ok(Reveal {
+            token: to_bytes(&token)?,
+            proof: to_bytes(&proof)?,
+            public_key: to_bytes(&self.pk)?,
+        })
+    }
+}
+
+/// Labels of the 52 standard cards, ordered by rank and then by suit: `2c, 2d, ..., As`.
+#[uniffi::export]
+pub fn standard_deck_labels() -> Vec<String> {
+    labels()
+}
+
+/// The 52 cards of the standard deck, in the order of `standard_deck_labels`.
+#[uniffi::export]
+pub fn standard_deck() -> Result<Vec<Vec<u8>>> {
+    encode_standard_deck::<Curve>(1)?
+        .iter()
+        .map(|card| Ok(to_bytes(card)?))
+        .collect()
+}
+
+/// The card with the given label, such as `"As"`.
+#[uniffi::export]
+pub fn card_from_label(label: String) -> Result<Vec<u8>> {
+    let card = dl::Card::<Curve>::from_label(label.as_bytes())?;
+
+    Ok(to_bytes(&card)?)
+}
+
+/// Digest of the list of the players' public keys, in seat order.
+#[uniffi::export]
+pub fn key_list_digest(public_keys: Vec<Vec<u8>>) -> Result<Vec<u8>> {
+    let keys = public_keys
+        .iter()
+        .map(|key| public_key_from_bytes::<dl::PublicKey<Curve>>(key))
+        .collect::<std::result::Result<Vec<_>, _>>()?;
+
+    Ok(CardProtocol::key_list_digest(&keys)?.to_vec())
+}
+
+#[uniffi::export]
+pub fn verify_key_ownership(
+    pp: Arc<Parameters>,
+    context: Vec<u8>,
+    public_key: Vec<u8>,
+    player_info: Vec<u8>,
+    player_index: u32,
+    key_list_digest: Vec<u8>,
+    proof: Vec<u8>,
+) -> Result<()> {
+    Ok(CardProtocol::verify_key_ownership(
+        &pp.0,
+        &context,
+        &public_key_from_bytes(&public_key)?,
+        &player_info,
+        player_index as usize,
+        &digest_from_bytes(&key_list_digest)?,
+        &decode_proof::<KeyOwnershipProof<Curve>>(&proof)?,
+    )?)
+}
+
+/// Aggregate key of the players, in seat order, after checking every proof of key ownership.
+#[uniffi::export]
+pub fn aggregate_key(
+    pp: Arc<Parameters>,
+    context: Vec<u8>,
+    players: Vec<KeyOwnership>,
+) -> Result<Vec<u8>> {
+    let key_proof_info = players
+        .iter()
+        .map(|player| {
+            Ok((
+                public_key_from_bytes(&player.public_key)?,
+                decode_proof::<KeyOwnershipProof<Curve>>(&player.proof)?,
+                player.player_info.clone(),
+            ))
+        })
+        .collect::<Result<Vec<_>>>()?;
+    let shared_key = CardProtocol::compute_aggregate_key(&pp.0, &context, &key_proof_info)?;
+
+    Ok(to_bytes(&shared_key)?)
+}
+
+/// The cards masked under `shared_key` with a masking factor of one, which every player can
+/// recompute before the first shuffle.
+#[uniffi::export]
+pub fn starting_deck(
+    pp: Arc<Parameters>,
+    shared_key: Vec<u8>,
+    cards: Vec<Vec<u8>>,
+) -> Result<Vec<Vec<u8>>> {
+    let cards = decode_list::<dl::Card<Curve>>(&cards)?;
+    let deck = dl::starting_deck(&pp.0, &public_key_from_bytes(&shared_key)?, &cards)?;
+
+    encode_list(&deck)
+}
+
+#[uniffi::export]
+pub fn mask(
+    pp: Arc<Parameters>,
+    context: Vec<u8>,
+    shared_key: Vec<u8>,
+    card: Vec<u8>,
+) -> Result<Masking> {
+    let (masked_card, proof) = CardProtocol::mask_with_rng(
+        &mut thread_rng(),
+        &pp.0,
+        &context,
+        &public_key_from_bytes(&shared_key)?,
+        &from_bytes(&card)?,
+    )?;
+
+    Ok(Masking {
+        masked_card: to_bytes(&masked_card)?,
+        proof: to_bytes(&proof)?,
+    })
+}
+
+#[uniffi::export]
+pub fn verify_mask(
+    pp: Arc<Parameters>,
+    context: Vec<u8>,
+    shared_key: Vec<u8>,
+    card: Vec<u8>,
+    masked_card: Vec<u8>,
+    proof: Vec<u8>,
+) -> Result<()> {
+    Ok(CardProtocol::verify_mask(
+        &pp.0,
+        &context,
+        &public_key_from_bytes(&shared_key)?,
+        &from_bytes(&card)?,
+        &from_bytes(&masked_card)?,
+        &decode_proof::<RevealProof<Curve>>(&proof)?,
+    )?)
+}
+
+/// Shuffle and remask a deck, and prove it.
+#[uniffi::export]
+pub fn shuffle(
+    pp: Arc<Parameters>,
+    context: Vec<u8>,
+    shared_key: Vec<u8>,
+    deck: Vec<Vec<u8>>,
+) -> Result<Shuffle> {
+    let (shuffled, proof) = CardProtocol::shuffle_and_remask_with_rng(
+        &mut thread_rng(),
+        &pp.0,
+        &context,
+        &public_key_from_bytes(&shared_key)?,
+        &decode_list(&deck)?,
+    )?;
+
+    Ok(Shuffle {
+        deck: encode_list(&shuffled)?,
+        proof: to_bytes(&proof)?,
+    })
+}
+
+#[uniffi::export]
+pub fn verify_shuffle(
+    pp: Arc<Parameters>,
+    context: Vec<u8>,
+    shared_key: Vec<u8>,
+    deck: Vec<Vec<u8>>,
+    shuffled: Vec<Vec<u8>>,
+    proof: Vec<u8>,
+) -> Result<()> {
+    Ok(CardProtocol::verify_shuffle(
+        &pp.0,
+        &context,
+        &public_key_from_bytes(&shared_key)?,
+        &decode_list(&deck)?,
+        &decode_list(&shuffled)?,
+        &decode_proof::<ShuffleProof<Curve>>(&proof)?,
+    )?)
+}
+
+#[uniffi::export]
+pub fn verify_reveal(
+    pp: Arc<Parameters>,
+    context: Vec<u8>,
+    reveal: Reveal,
+    masked_card: Vec<u8>,
+) -> Result<()> {
+    Ok(CardProtocol::verify_reveal(
+        &pp.0,
+        &context,
+        &public_key_from_bytes(&reveal.public_key)?,
+        &from_bytes(&reveal.token)?,
+        &from_bytes(&masked_card)?,
+        &decode_proof::<RevealProof<Curve>>(&reveal.proof)?,
+    )?)
+}
+
+/// Open a masked card with the reveal token of every one of the `num_of_players` players. Every
+/// proof is checked.
+#[uniffi::export]
+pub fn unmask(
+    pp: Arc<Parameters>,
+    context: Vec<u8>,
+    num_of_players: u32,
+    reveals: Vec<Reveal>,
+    masked_card: Vec<u8>,
+) -> Result<Vec<u8>> {
+    let decryption_key = reveals
+        .iter()
+        .map(|reveal| {
+            Ok((
+                from_bytes(&reveal.token)?,
+                decode_proof::<RevealProof<Curve>>(&reveal.proof)?,
+                public_key_from_bytes(&reveal.public_key)?,
+            ))
+        })
+        .collect::<Result<Vec<_>>>()?;
+    let card = CardProtocol::unmask(
+        &pp.0,
+        &context,
+        num_of_players as usize,
+        &decryption_key,
+        &from_bytes(&masked_card)?,
+    )?;
+
+    Ok(to_bytes(&card)?)
+}
+
+fn decode_proof<T: CanonicalDeserialize>(bytes: &[u8]) -> Result<T> {
+    Ok(bounded_from_bytes(bytes, &DecodingLimits::default())?)
+}
+
+fn decode_list<T: CanonicalDeserialize + Validate>(values: &[Vec<u8>]) -> Result<Vec<T>> {
+    values.iter().map(|bytes| Ok(from_bytes(bytes)?)).collect()
+}
+
+fn encode_list<T: CanonicalSerialize>(values: &[T]) -> Result<Vec<Vec<u8>>> {
+    values.iter().map(|value| Ok(to_bytes(value)?)).collect()
+}
+
+fn digest_from_bytes(bytes: &[u8]) -> Result<KeyListDigest> {
+    KeyListDigest::try_from(bytes).map_err(|_| {
+        ProtocolError::InvalidInput(InputError::EncodingLengthError {
+            expected: 32,
+            length: bytes.len(),
+        })
+        .into()
+    })
+}
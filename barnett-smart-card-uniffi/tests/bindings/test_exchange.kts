@@ -0,0 +1,52 @@
+// A two-player exchange through the Kotlin bindings: setup, keys, a masked card, one shuffle and
+// the opening of a card with both players' reveal tokens.
+
+import xyz.geometryresearch.mentalpoker.*
+
+val sessionId = "kotlin test".toByteArray()
+
+// The parameters travel from whoever generated them to both players
+val pp = Parameters.fromBytes(Parameters.generate(4u, 13u).toBytes())
+assert(pp.numCards() == 52u)
+
+val players = listOf(PlayerKeys.generate(pp), PlayerKeys.generate(pp))
+val keys = players.map { it.publicKey() }
+val info = listOf("alice".toByteArray(), "bob".toByteArray())
+val digest = keyListDigest(keys)
+
+val ownership = players.mapIndexed { index, player ->
+    val proof = player.proveOwnership(pp, sessionId, info[index], index.toUInt(), digest)
+    verifyKeyOwnership(pp, sessionId, keys[index], info[index], index.toUInt(), digest, proof)
+    KeyOwnership(keys[index], proof, info[index])
+}
+val sharedKey = aggregateKey(pp, sessionId, ownership)
+
+// A proof of ownership for the wrong seat is rejected
+try {
+    verifyKeyOwnership(pp, sessionId, keys[0], info[0], 1u, digest, ownership[0].proof)
+    throw AssertionError("a proof for another seat verified")
+} catch (error: CardProtocolException) {
+}
+
+// The first player masks a card, the second checks it
+val card = cardFromLabel("As")
+val masking = mask(pp, sessionId, sharedKey, card)
+verifyMask(pp, sessionId, sharedKey, card, masking.maskedCard, masking.proof)
+
+// The second player shuffles the deck, the first checks the shuffle
+val deck = startingDeck(pp, sharedKey, standardDeck())
+val shuffled = shuffle(pp, sessionId, sharedKey, deck)
+verifyShuffle(pp, sessionId, sharedKey, deck, shuffled.deck, shuffled.proof)
+
+// Both players reveal the masked card, and either of them opens it
+val reveals = players.map { it.revealToken(pp, sessionId, masking.maskedCard) }
+reveals.forEach { verifyReveal(pp, sessionId, it, masking.maskedCard) }
+assert(unmask(pp, sessionId, reveals, masking.maskedCard).contentEquals(card))
+
+// A reveal token for another card does not open it
+val other = players[1].revealToken(pp, sessionId, shuffled.deck[0])
+try {
+    unmask(pp, sessionId, listOf(reveals[0], other), masking.maskedCard)
+    throw AssertionError("a reveal token for another card was accepted")
+} catch (error: CardProtocolException.InvalidRevealToken) {
+}
@@ -0,0 +1,3 @@
+// Each script is run against bindings generated from the library, with kotlinc on the `PATH` and
+// JNA on the `CLASSPATH`.
+uniffi::build_foreign_language_testcases!("tests/bindings/test_exchange.kts");
@@ -0,0 +1,194 @@
+//! Plays a hand of a small poker game over TCP, using only the public API of the protocol crate.
+//!
+//! Host a table for three players, which also seats the host, and join it from two other
+//! terminals:
+//!
+//! ```text
+//! cargo run -p mental-poker-demo -- host 127.0.0.1:4000 3
+//! cargo run -p mental-poker-demo -- join 127.0.0.1:4000
+//! ```
+//!
+//! Every player publishes a key and proves they own it, everyone shuffles the deck in turn and
+//! each player is dealt two hole cards, all with `run_player` from the `runner` module. Once every
+//! player has opened their hand, one community card is revealed, and the best three-card hand of
+//! hole cards and community card wins: three of a kind, then a pair, then a high card, with ties
+//! broken by rank. Every message is printed with the outcome of the verification of its proofs.
+
+mod table;
+mod tcp;
+
+use crate::table::{hole_cards, Table, HOLE_CARDS};
+use crate::tcp::{relay, TcpTransport, CONTEXT_LENGTH};
+
+use barnett_smart_card_protocol::discrete_log_cards::encoding::encode_standard_deck;
+use barnett_smart_card_protocol::discrete_log_cards::runner::{
+    run_player, GameOutcome, Role, RunnerRules,
+};
+use barnett_smart_card_protocol::discrete_log_cards::session::Session;
+use barnett_smart_card_protocol::discrete_log_cards::DLCards;
+use barnett_smart_card_protocol::playing_card::{ClassicPlayingCard, Rank};
+use barnett_smart_card_protocol::BarnettSmartProtocol;
+
+use anyhow::{anyhow, bail};
+use rand::{thread_rng, RngCore};
+use std::cmp::Reverse;
+use std::net::SocketAddr;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::net::TcpListener;
+
+type Curve = starknet_curve::Projective;
+type CardProtocol<'a> = DLCards<'a, Curve>;
+
+const USAGE: &str = "usage: mental-poker-demo host <address> <players> | join <address>";
+
+// A standard deck of 52 cards, as 4 rows of 13 for the shuffle argument
+const M: usize = 4;
+const N: usize = 13;
+
+const RULES: RunnerRules = RunnerRules {
+    message_timeout: Duration::from_secs(300),
+};
+
+#[tokio::main]
+async fn main() -> anyhow::Result<()> {
+    let args = std::env::args().skip(1).collect::<Vec<_>>();
+    match args
+        .iter()
+        .map(String::as_str)
+        .collect::<Vec<_>>()
+        .as_slice()
+    {
+        ["host", address, players] => host(address.parse()?, players.parse()?).await,
+        ["join", address] => play(address.parse()?).await,
+        _ => bail!(USAGE),
+    }
+}
+
+/// Run the relay for a table of `num_of_players` on `address` and take a seat at it.
+async fn host(address: SocketAddr, num_of_players: usize) -> anyhow::Result<()> {
+    if num_of_players < 2 || num_of_players * HOLE_CARDS >= M * N {
+        bail!("a table seats 2 to {} players", (M * N - 1) / HOLE_CARDS);
+    }
+
+    let rng = &mut thread_rng();
+    let parameters = CardProtocol::setup(rng, M, N)?;
+    let mut context = [0u8; CONTEXT_LENGTH];
+    rng.fill_bytes(&mut context);
+
+    let listener = TcpListener::bind(address).await?;
+    let address = listener.local_addr()?;
+    println!(
+        "Hosting a table for {} players on {}",
+        num_of_players, address
+    );
+
+    let relay = tokio::spawn(relay(listener, num_of_players, context, parameters));
+    play(address).await?;
+
+    // The relay runs until every player has left, so that nobody misses the end of the game
+    relay.await?
+}
+
+/// Join the table at `address` and play a hand.
+async fn play(address: SocketAddr) -> anyhow::Result<()> {
+    let (transport, welcome) = TcpTransport::connect(address).await?;
+    let seat = welcome.seat;
+    let num_of_players = welcome.num_of_players;
+    println!("Seated at seat {} of {}", seat, num_of_players);
+
+    let parameters = Arc::new(welcome.parameters);
+    let session = Session::new(
+        &parameters,
+        &welcome.context,
+        (0..num_of_players as u64).collect(),
+        encode_standard_deck(1)?,
+        hole_cards(num_of_players),
+    )?;
+    let rng = &mut thread_rng();
+    let keys = CardProtocol::player_keygen(rng, &parameters)?;
+
+    let mut table = Table::new(seat, parameters.clone(), session.clone(), transport);
+    let role = Role {
+        seat,
+        parameters,
+        session,
+    };
+    let hands = match run_player(rng, keys.clone(), role, &mut table, &RULES).await {
+        GameOutcome::Completed { hands } => hands,
+        outcome => bail!("the hand ended early: {:?}", outcome),
+    };
+    table.catch_up(&RULES).await?;
+
+    let community_card = table.reveal_community_card(&keys, &RULES).await?;
+    showdown(seat, &hands, community_card)
+}
+
+/// Print every hand with the community card and the winners. `hands` lists positions in the
+/// standard deck, as the session was started with it.
+fn showdown(
+    seat: usize,
+    hands: &[Vec<usize>],
+    community_card: ClassicPlayingCard,
+) -> anyhow::Result<()> {
+    println!();
+    println!("Showdown");
+    println!("Community card: {}", community_card);
+
+    let mut scores = Vec::new();
+    for (player, hand) in hands.iter().enumerate() {
+        let mut cards = hand
+            .iter()
+            .map(|&position| ClassicPlayingCard::from_standard_position(position))
+            .collect::<Option<Vec<_>>>()
+            .ok_or_else(|| anyhow!("seat {} opened a card outside the deck", player))?;
+        let hole_cards = cards.iter().map(ToString::to_string).collect::<Vec<_>>();
+        cards.push(community_card);
+
+        let score = score(&cards);
+        let you = if player == seat { " (you)" } else { "" };
+        println!(
+            "seat {}{}: {}, {}",
+            player,
+            you,
+            hole_cards.join(" "),
+            describe(score.0)
+        );
+        scores.push(score);
+    }
+
+    let best = scores.iter().max().ok_or_else(|| anyhow!("no players"))?;
+    let winners = (0..scores.len())
+        .filter(|&player| &scores[player] == best)
+        .map(|player| player.to_string())
+        .collect::<Vec<_>>();
+    if winners.len() == 1 {
+        println!("Winner: seat {}", winners[0]);
+    } else {
+        println!("Split pot: seats {}", winners.join(", "));
+    }
+
+    Ok(())
+}
+
+/// Score of a hand, ordered like the hands: the number of cards of the most common rank, then
+/// the ranks with the most common first.
+fn score(cards: &[ClassicPlayingCard]) -> (usize, Vec<Rank>) {
+    let count = |rank: Rank| cards.iter().filter(|card| card.rank() == rank).count();
+
+    let mut ranks = cards
+        .iter()
+        .map(ClassicPlayingCard::rank)
+        .collect::<Vec<_>>();
+    ranks.sort_unstable_by_key(|&rank| Reverse((count(rank), rank)));
+
+    (count(ranks[0]), ranks)
+}
+
+fn describe(of_a_kind: usize) -> &'static str {
+    match of_a_kind {
+        3 => "three of a kind",
+        2 => "a pair",
+        _ => "high card",
+    }
+}
@@ -0,0 +1,214 @@
+//! A player's view of the table.
+//!
+//! [`Table`] sits between the runner and the TCP transport. It applies every message the relay
+//! forwards, the player's own included, to a copy of the session, and prints what happened and
+//! whether the proofs in the message verified. The runner checks the same messages on its own
+//! copy; the view is what lets the player follow the game, and it keeps the final deck, from which
+//! the community card is revealed once the hole cards are open.
+
+use crate::tcp::TcpTransport;
+use crate::{CardProtocol, Curve};
+
+use barnett_smart_card_protocol::discrete_log_cards::runner::{PlayerId, RunnerRules, Transport};
+use barnett_smart_card_protocol::discrete_log_cards::session::{
+    GameMessage, RevealProof, Session, SessionPhase,
+};
+use barnett_smart_card_protocol::discrete_log_cards::{
+    Parameters, PlayerSecretKey, PublicKey, RevealToken,
+};
+use barnett_smart_card_protocol::error::{CardProtocolError, InputError};
+use barnett_smart_card_protocol::playing_card::ClassicPlayingCard;
+use barnett_smart_card_protocol::BarnettSmartProtocol;
+
+use anyhow::bail;
+use async_trait::async_trait;
+use rand::thread_rng;
+use std::sync::Arc;
+use tokio::time::timeout;
+
+/// Cards dealt face down to each player.
+pub const HOLE_CARDS: usize = 2;
+
+/// Deck indices dealt to each of `num_of_players` players, one card at a time around the table.
+/// The community card is the next one, at `num_of_players * HOLE_CARDS`.
+pub fn hole_cards(num_of_players: usize) -> Vec<Vec<usize>> {
+    (0..num_of_players)
+        .map(|player| {
+            (0..HOLE_CARDS)
+                .map(|round| round * num_of_players + player)
+                .collect()
+        })
+        .collect()
+}
+
+pub struct Table {
+    seat: PlayerId,
+    parameters: Arc<Parameters<Curve>>,
+    view: Session<Curve, u64>,
+    transport: TcpTransport,
+}
+
+impl Table {
+    pub fn new(
+        seat: PlayerId,
+        parameters: Arc<Parameters<Curve>>,
+        session: Session<Curve, u64>,
+        transport: TcpTransport,
+    ) -> Self {
+        Self {
+            seat,
+            parameters,
+            view: session,
+            transport,
+        }
+    }
+
+    fn player(&self, player: PlayerId) -> String {
+        if player == self.seat {
+            format!("seat {} (you)", player)
+        } else {
+            format!("seat {}", player)
+        }
+    }
+
+    /// Apply a message forwarded by the relay to the view and report it.
+    fn observe(&mut self, player: PlayerId, message: GameMessage<Curve>) {
+        let step = match (&message, self.view.phase()) {
+            (GameMessage::PublicKey(_), _) => "published a public key".to_string(),
+            (GameMessage::KeyOwnership(_), _) => "proved ownership of the key".to_string(),
+            (GameMessage::Shuffle { .. }, _) => "shuffled and remasked the deck".to_string(),
+            (GameMessage::RevealTokens(tokens), SessionPhase::Reveal) => {
+                format!("opened {} cards of their own", tokens.len())
+            }
+            (GameMessage::RevealTokens(tokens), _) => {
+                format!("sent reveal tokens for {} cards of others", tokens.len())
+            }
+        };
+
+        let status = match self.view.receive(&self.parameters, player, message) {
+            Ok(_) => "proofs verified".to_string(),
+            Err(error) => format!("REJECTED: {}", error),
+        };
+        println!("{:<16} {:<44} {}", self.player(player), step, status);
+    }
+
+    /// Follow the game until the view has seen every player open their hand. The runner stops
+    /// reading as soon as its own copy of the session is done, which can be before the echoes of
+    /// the player's last messages came back.
+    pub async fn catch_up(&mut self, rules: &RunnerRules) -> anyhow::Result<()> {
+        while self.view.phase() != SessionPhase::Done {
+            let (player, message) =
+                timeout(rules.message_timeout, self.transport.recv_any()).await??;
+            self.observe(player, message);
+        }
+
+        Ok(())
+    }
+
+    /// Reveal the community card: every player sends a reveal token for it with a proof, and the
+    /// card opens once the tokens of all players verified.
+    pub async fn reveal_community_card(
+        &mut self,
+        keys: &(PublicKey<Curve>, PlayerSecretKey<Curve>),
+        rules: &RunnerRules,
+    ) -> anyhow::Result<ClassicPlayingCard> {
+        let num_of_players = self.view.num_of_players();
+        let index = num_of_players * HOLE_CARDS;
+        let masked_card = self.view.deck()[index];
+        let context = self.view.context().to_vec();
+
+        let (token, proof) = CardProtocol::compute_reveal_token(
+            &mut thread_rng(),
+            &self.parameters,
+            &context,
+            &keys.1,
+            &keys.0,
+            &masked_card,
+        )?;
+        self.transport
+            .broadcast(GameMessage::RevealTokens(vec![(index, token, proof)]))
+            .await?;
+
+        let mut decryption_key: Vec<
+            Option<(RevealToken<Curve>, RevealProof<Curve>, PublicKey<Curve>)>,
+        > = vec![None; num_of_players];
+        while decryption_key.iter().any(Option::is_none) {
+            let (player, message) =
+                timeout(rules.message_timeout, self.transport.recv_any()).await??;
+            let (token, proof) = match message {
+                GameMessage::RevealTokens(mut tokens)
+                    if tokens.len() == 1 && tokens[0].0 == index =>
+                {
+                    let (_, token, proof) = tokens.remove(0);
+                    (token, proof)
+                }
+                _ => bail!(
+                    "seat {} sent a message other than a community token",
+                    player
+                ),
+            };
+            if decryption_key[player].is_some() {
+                return Err(
+                    CardProtocolError::InvalidInput(InputError::DuplicateMessage(player)).into(),
+                );
+            }
+
+            let pk = self
+                .view
+                .public_key(player)
+                .ok_or(CardProtocolError::InvalidInput(InputError::UnknownPlayer(
+                    player,
+                )))?;
+            let verified = CardProtocol::verify_reveal(
+                &self.parameters,
+                &context,
+                &pk,
+                &token,
+                &masked_card,
+                &proof,
+            )
+            .map_err(CardProtocolError::from);
+            let status = match &verified {
+                Ok(()) => "proofs verified".to_string(),
+                Err(error) => format!("REJECTED: {}", error),
+            };
+            println!(
+                "{:<16} {:<44} {}",
+                self.player(player),
+                "sent a reveal token for the community card",
+                status
+            );
+            verified?;
+
+            decryption_key[player] = Some((token, proof, pk));
+        }
+
+        let decryption_key = decryption_key.into_iter().flatten().collect();
+        let card = CardProtocol::unmask(
+            &self.parameters,
+            &context,
+            num_of_players,
+            &decryption_key,
+            &masked_card,
+        )?;
+
+        Ok(ClassicPlayingCard::try_from_card(&card)?)
+    }
+}
+
+#[async_trait]
+impl Transport<Curve> for Table {
+    async fn broadcast(&self, message: GameMessage<Curve>) -> Result<(), CardProtocolError> {
+        self.transport.broadcast(message).await
+    }
+
+    async fn recv(&mut self) -> Result<(PlayerId, GameMessage<Curve>), CardProtocolError> {
+        loop {
+            let (player, message) = self.transport.recv_any().await?;
+            self.observe(player, message.clone());
+            if player != self.seat {
+                return Ok((player, message));
+            }
+        }
+    }
+}
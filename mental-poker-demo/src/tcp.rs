@@ -0,0 +1,250 @@
+//! Transport over TCP through a relay run by the host.
+//!
+//! The relay accepts one connection per player, the host's own included, and then forwards every
+//! frame it receives to all players, the sender included, tagged with the seat of the sender.
+//! Every player therefore sees all messages in the same order, which keeps their views of the
+//! table in step. The relay only forwards bytes: the players verify everything.
+//!
+//! A frame is a 4-byte big-endian length followed by that many bytes. The first frame from the
+//! relay welcomes a player with their seat, the number of players, the session context and the
+//! parameters. Every later frame is the 8-byte big-endian seat of the sender followed by the
+//! canonical encoding of a [`GameMessage`], which is also what players send to the relay.
+
+use crate::{CardProtocol, Curve};
+
+use barnett_smart_card_protocol::discrete_log_cards::runner::{PlayerId, Transport};
+use barnett_smart_card_protocol::discrete_log_cards::session::GameMessage;
+use barnett_smart_card_protocol::discrete_log_cards::validation::{
+    bounded_from_bytes, to_bytes, DecodingLimits, MAX_MESSAGE_BYTES,
+};
+use barnett_smart_card_protocol::discrete_log_cards::Parameters;
+use barnett_smart_card_protocol::error::{CardProtocolError, InputError, InternalError};
+use barnett_smart_card_protocol::BarnettSmartProtocol;
+
+use anyhow::bail;
+use ark_serialize::{CanonicalDeserialize, CanonicalSerialize};
+use async_trait::async_trait;
+use std::convert::TryInto;
+use std::io;
+use tokio::io::{AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt};
+use tokio::net::tcp::{OwnedReadHalf, OwnedWriteHalf};
+use tokio::net::{TcpListener, TcpStream, ToSocketAddrs};
+use tokio::sync::{mpsc, Mutex};
+
+/// Length of the session context chosen by the host.
+pub const CONTEXT_LENGTH: usize = 32;
+
+/// Length of the seat that prefixes every message forwarded by the relay.
+const SEAT_LENGTH: usize = 8;
+
+/// Largest frame read from the network.
+const MAX_FRAME_BYTES: usize = MAX_MESSAGE_BYTES + SEAT_LENGTH;
+
+/// What the relay tells a player before the game starts.
+pub struct Welcome {
+    pub seat: PlayerId,
+    pub num_of_players: usize,
+    pub context: [u8; CONTEXT_LENGTH],
+    /// Parameters chosen by the host, already checked with `verify_parameters`
+    pub parameters: Parameters<Curve>,
+}
+
+impl Welcome {
+    fn encode(
+        seat: PlayerId,
+        num_of_players: usize,
+        context: &[u8; CONTEXT_LENGTH],
+        parameters: &Parameters<Curve>,
+    ) -> Result<Vec<u8>, CardProtocolError> {
+        let mut bytes = Vec::new();
+        (seat as u64, num_of_players as u64).serialize(&mut bytes)?;
+        bytes.extend_from_slice(context);
+        parameters.serialize(&mut bytes)?;
+
+        Ok(bytes)
+    }
+
+    fn decode(bytes: &[u8]) -> anyhow::Result<Self> {
+        let mut reader = bytes;
+        let (seat, num_of_players) = <(u64, u64)>::deserialize(&mut reader)?;
+        if reader.len() < CONTEXT_LENGTH {
+            bail!("the welcome of the relay is truncated");
+        }
+        let (context, mut reader) = reader.split_at(CONTEXT_LENGTH);
+        let parameters = Parameters::<Curve>::deserialize(&mut reader)?;
+        CardProtocol::verify_parameters(&parameters)?;
+
+        if seat >= num_of_players {
+            bail!(
+                "the relay seated us at {} of {} seats",
+                seat,
+                num_of_players
+            );
+        }
+
+        Ok(Self {
+            seat: seat as PlayerId,
+            num_of_players: num_of_players as usize,
+            context: context.try_into()?,
+            parameters,
+        })
+    }
+}
+
+async fn read_frame<R: AsyncRead + Unpin>(reader: &mut R) -> io::Result<Option<Vec<u8>>> {
+    let mut length = [0u8; 4];
+    match reader.read_exact(&mut length).await {
+        Ok(_) => {}
+        Err(error) if error.kind() == io::ErrorKind::UnexpectedEof => return Ok(None),
+        Err(error) => return Err(error),
+    }
+
+    let length = u32::from_be_bytes(length) as usize;
+    if length > MAX_FRAME_BYTES {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            "frame too large",
+        ));
+    }
+
+    let mut frame = vec![0u8; length];
+    reader.read_exact(&mut frame).await?;
+
+    Ok(Some(frame))
+}
+
+async fn write_frame<W: AsyncWrite + Unpin>(writer: &mut W, frame: &[u8]) -> io::Result<()> {
+    writer
+        .write_all(&(frame.len() as u32).to_be_bytes())
+        .await?;
+    writer.write_all(frame).await?;
+    writer.flush().await
+}
+
+fn transport_error(error: io::Error) -> CardProtocolError {
+    CardProtocolError::Internal(InternalError::TransportError(error.to_string()))
+}
+
+/// Seat `num_of_players` players as they connect to `listener`, welcome them and forward their
+/// messages until every player has left.
+pub async fn relay(
+    listener: TcpListener,
+    num_of_players: usize,
+    context: [u8; CONTEXT_LENGTH],
+    parameters: Parameters<Curve>,
+) -> anyhow::Result<()> {
+    let mut readers = Vec::new();
+    let mut writers = Vec::new();
+    while writers.len() < num_of_players {
+        let (stream, _) = listener.accept().await?;
+        stream.set_nodelay(true)?;
+        let (reader, writer) = stream.into_split();
+        readers.push(reader);
+        writers.push(writer);
+    }
+
+    for (seat, writer) in writers.iter_mut().enumerate() {
+        let welcome = Welcome::encode(seat, num_of_players, &context, &parameters)?;
+        write_frame(writer, &welcome).await?;
+    }
+
+    // Frames of all players go through one channel, which fixes the order everyone sees
+    let (sender, mut frames) = mpsc::unbounded_channel();
+    for (seat, mut reader) in readers.into_iter().enumerate() {
+        let sender = sender.clone();
+        tokio::spawn(async move {
+            while let Ok(Some(message)) = read_frame(&mut reader).await {
+                let mut frame = (seat as u64).to_be_bytes().to_vec();
+                frame.extend_from_slice(&message);
+                if sender.send(frame).is_err() {
+                    break;
+                }
+            }
+        });
+    }
+    drop(sender);
+
+    while let Some(frame) = frames.recv().await {
+        for writer in writers.iter_mut() {
+            // A player who left the table misses the rest of the game, which is their loss
+            let _ = write_frame(writer, &frame).await;
+        }
+    }
+
+    Ok(())
+}
+
+/// The connection of one player to the relay.
+pub struct TcpTransport {
+    seat: PlayerId,
+    num_of_players: usize,
+    limits: DecodingLimits,
+    reader: OwnedReadHalf,
+    writer: Mutex<OwnedWriteHalf>,
+}
+
+impl TcpTransport {
+    /// Connect to the relay at `address` and wait for the table to fill up.
+    pub async fn connect<A: ToSocketAddrs>(address: A) -> anyhow::Result<(Self, Welcome)> {
+        let stream = TcpStream::connect(address).await?;
+        stream.set_nodelay(true)?;
+        let (mut reader, writer) = stream.into_split();
+
+        let welcome = match read_frame(&mut reader).await? {
+            Some(frame) => Welcome::decode(&frame)?,
+            None => bail!("the relay closed the connection before the game started"),
+        };
+
+        let transport = Self {
+            seat: welcome.seat,
+            num_of_players: welcome.num_of_players,
+            limits: DecodingLimits::for_parameters(&welcome.parameters),
+            reader,
+            writer: Mutex::new(writer),
+        };
+
+        Ok((transport, welcome))
+    }
+
+    /// Next message forwarded by the relay, including the echoes of the player's own messages.
+    pub async fn recv_any(&mut self) -> Result<(PlayerId, GameMessage<Curve>), CardProtocolError> {
+        let frame = read_frame(&mut self.reader)
+            .await
+            .map_err(transport_error)?
+            .ok_or_else(|| {
+                CardProtocolError::Internal(InternalError::TransportError(
+                    "the relay left".to_string(),
+                ))
+            })?;
+        if frame.len() < SEAT_LENGTH {
+            return Err(InternalError::TransportError("truncated frame".to_string()).into());
+        }
+
+        let (seat, message) = frame.split_at(SEAT_LENGTH);
+        let seat = u64::from_be_bytes(seat.try_into().unwrap()) as PlayerId;
+        if seat >= self.num_of_players {
+            return Err(InputError::UnknownPlayer(seat).into());
+        }
+
+        Ok((seat, bounded_from_bytes(message, &self.limits)?))
+    }
+}
+
+#[async_trait]
+impl Transport<Curve> for TcpTransport {
+    async fn broadcast(&self, message: GameMessage<Curve>) -> Result<(), CardProtocolError> {
+        let mut writer = self.writer.lock().await;
+        write_frame(&mut *writer, &to_bytes(&message)?)
+            .await
+            .map_err(transport_error)
+    }
+
+    async fn recv(&mut self) -> Result<(PlayerId, GameMessage<Curve>), CardProtocolError> {
+        loop {
+            let (seat, message) = self.recv_any().await?;
+            if seat != self.seat {
+                return Ok((seat, message));
+            }
+        }
+    }
+}
@@ -0,0 +1,55 @@
+//! Plays a hand between three instances of the demo on localhost.
+
+use std::io::{BufRead, BufReader, Read};
+use std::process::{Child, Command, Stdio};
+
+const DEMO: &str = env!("CARGO_BIN_EXE_mental-poker-demo");
+
+fn spawn(args: &[&str]) -> Child {
+    Command::new(DEMO)
+        .args(args)
+        .stdout(Stdio::piped())
+        .spawn()
+        .unwrap()
+}
+
+/// The showdown printed by an instance, as seen by everyone else.
+fn showdown(stdout: &str) -> String {
+    assert!(!stdout.contains("REJECTED"), "{}", stdout);
+
+    stdout
+        .split_once("Showdown\n")
+        .expect("no showdown")
+        .1
+        .replace(" (you)", "")
+}
+
+#[test]
+fn test_three_players_on_localhost() {
+    let mut host = spawn(&["host", "127.0.0.1:0", "3"]);
+
+    // The host prints the address it listens on before anything else
+    let mut host_stdout = BufReader::new(host.stdout.take().unwrap());
+    let mut line = String::new();
+    host_stdout.read_line(&mut line).unwrap();
+    let address = line.trim().rsplit(' ').next().unwrap().to_string();
+
+    let players = vec![spawn(&["join", &address]), spawn(&["join", &address])];
+
+    let mut stdout = String::new();
+    host_stdout.read_to_string(&mut stdout).unwrap();
+    assert!(host.wait().unwrap().success(), "{}", stdout);
+    let expected = showdown(&stdout);
+
+    assert!(expected.starts_with("Community card: "));
+    let hands = expected.lines().filter(|line| line.starts_with("seat "));
+    assert_eq!(3, hands.count());
+
+    // Everyone saw the same cards and the same winner
+    for player in players {
+        let output = player.wait_with_output().unwrap();
+        let stdout = String::from_utf8(output.stdout).unwrap();
+        assert!(output.status.success(), "{}", stdout);
+        assert_eq!(expected, showdown(&stdout));
+    }
+}